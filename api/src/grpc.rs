@@ -0,0 +1,232 @@
+//! gRPC transport for submissions, heartbeats and client settings, used with
+//! servers that have `shared::capability::GRPC`.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_channel::mpsc;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    StatusCode,
+};
+use shared::{
+    auth::Credentials,
+    grpc::{self, beelzebub_client::BeelzebubClient},
+};
+use tokio::sync::Mutex;
+use tonic::{metadata::MetadataMap, transport::Channel, Code, Request, Status, Streaming};
+
+use crate::Error;
+
+/// Heartbeats that can wait to be sent on the stream. Each heartbeat waits
+/// for its result before the next one is sent, so one is enough.
+const HEARTBEAT_BUFFER: usize = 1;
+
+/// Open stream of heartbeats and their results.
+#[derive(Debug)]
+struct HeartbeatStream {
+    sender: mpsc::Sender<grpc::Submission>,
+    results: Streaming<grpc::HeartbeatResult>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Grpc {
+    client: BeelzebubClient<Channel>,
+
+    /// Sent with every call, like the default headers of the HTTP client.
+    headers: HeaderMap,
+
+    /// Longest wait for the answer to a call or a heartbeat.
+    timeout: Duration,
+
+    /// Opened by the first heartbeat and left open for the next ones. A
+    /// heartbeat that fails closes it, and the next one opens it again with
+    /// the credentials of the time.
+    heartbeats: Arc<Mutex<Option<HeartbeatStream>>>,
+}
+
+/// Error of the client from the status that a call failed with, matching the
+/// errors of the same REST endpoint.
+fn error(status: Status) -> Error {
+    match status.code() {
+        Code::Unauthenticated => Error::Unauthorized,
+        Code::FailedPrecondition => Error::UpgradeRequired,
+        Code::NotFound => Error::Status {
+            status: StatusCode::NOT_FOUND,
+            code: Some(shared::ApiErrorCode::NotFound),
+            request_id: None,
+        },
+        _ => Error::Grpc(Box::new(status)),
+    }
+}
+
+/// Add the header, whose name can be in any case, to the headers.
+fn insert(headers: &mut HeaderMap, name: &str, value: String) -> Result<(), Error> {
+    let name = HeaderName::from_bytes(name.as_bytes())
+        .map_err(|error| Error::Encode(std::io::Error::other(error)))?;
+    let value = HeaderValue::try_from(value)
+        .map_err(|error| Error::Encode(std::io::Error::other(error)))?;
+    headers.insert(name, value);
+    Ok(())
+}
+
+impl Grpc {
+    pub fn new(channel: Channel, headers: HeaderMap, timeout: Duration) -> Self {
+        Self {
+            client: BeelzebubClient::new(channel),
+            headers,
+            timeout,
+            heartbeats: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Headers of a call, sent as its metadata, with the credentials.
+    fn headers(&self, credentials: Vec<Credentials>) -> Result<HeaderMap, Error> {
+        let mut headers = self.headers.clone();
+        for credentials in credentials {
+            let (name, value) = credentials.header();
+            insert(&mut headers, name, value)?;
+        }
+        Ok(headers)
+    }
+
+    fn request<T>(&self, message: T, headers: HeaderMap) -> Request<T> {
+        let mut request = Request::new(message);
+        *request.metadata_mut() = MetadataMap::from_headers(headers);
+        request.set_timeout(self.timeout);
+        request
+    }
+
+    pub async fn submit(
+        &self,
+        submission: &shared::Submission,
+        credentials: Vec<Credentials>,
+    ) -> Result<shared::SubmissionResponse, Error> {
+        let mut headers = self.headers(credentials)?;
+        if let Some(session_id) = &submission.session_id {
+            insert(
+                &mut headers,
+                shared::IDEMPOTENCY_KEY_HEADER,
+                session_id.clone(),
+            )?;
+        }
+        let request = self.request(grpc::Submission::from(submission), headers);
+        let response = self
+            .client
+            .clone()
+            .submit(request)
+            .await
+            .map_err(error)?
+            .into_inner();
+        let Ok(response) = shared::SubmissionResponse::try_from(response) else {
+            return Err(Error::Grpc(Box::new(Status::unknown(
+                "unknown submission status",
+            ))));
+        };
+        match response.status {
+            shared::SubmissionResponseStatus::Ok | shared::SubmissionResponseStatus::Duplicate => {
+                Ok(response)
+            }
+            _ => Err(Error::Rejected {
+                response,
+                request_id: None,
+            }),
+        }
+    }
+
+    /// Send the heartbeat on the stream, opening it if it isn't open.
+    pub async fn heartbeat(
+        &self,
+        heartbeat: &shared::Submission,
+        credentials: Vec<Credentials>,
+    ) -> Result<(), Error> {
+        let mut stream = self.heartbeats.lock().await;
+        let open = match stream.take() {
+            Some(open) => open,
+            None => self.open_heartbeats(credentials).await?,
+        };
+        let (open, status) = self.send_heartbeat(open, heartbeat).await?;
+        let result = match status {
+            grpc::HeartbeatStatus::Ok => Ok(()),
+            grpc::HeartbeatStatus::Unauthenticated => Err(Error::Unauthorized),
+            grpc::HeartbeatStatus::UpgradeRequired => Err(Error::UpgradeRequired),
+            grpc::HeartbeatStatus::Invalid => Err(Error::Grpc(Box::new(Status::invalid_argument(
+                "the server rejected the heartbeat",
+            )))),
+            grpc::HeartbeatStatus::DatabaseError => Err(Error::Grpc(Box::new(Status::internal(
+                "the server could not save the heartbeat",
+            )))),
+            grpc::HeartbeatStatus::Unspecified => Err(Error::Grpc(Box::new(Status::unknown(
+                "unknown heartbeat status",
+            )))),
+        };
+        if result.is_ok() {
+            *stream = Some(open);
+        }
+        result
+    }
+
+    async fn open_heartbeats(
+        &self,
+        credentials: Vec<Credentials>,
+    ) -> Result<HeartbeatStream, Error> {
+        let (sender, receiver) = mpsc::channel(HEARTBEAT_BUFFER);
+        let mut request = Request::new(receiver);
+        *request.metadata_mut() = MetadataMap::from_headers(self.headers(credentials)?);
+        let mut client = self.client.clone();
+        let results = tokio::time::timeout(self.timeout, client.heartbeats(request))
+            .await
+            .map_err(|_| {
+                Error::Grpc(Box::new(Status::deadline_exceeded(
+                    "no answer from the server",
+                )))
+            })?
+            .map_err(error)?
+            .into_inner();
+        Ok(HeartbeatStream { sender, results })
+    }
+
+    async fn send_heartbeat(
+        &self,
+        mut stream: HeartbeatStream,
+        heartbeat: &shared::Submission,
+    ) -> Result<(HeartbeatStream, grpc::HeartbeatStatus), Error> {
+        stream.sender.try_send(heartbeat.into()).map_err(|_| {
+            Error::Grpc(Box::new(Status::unavailable(
+                "the heartbeat stream is closed",
+            )))
+        })?;
+        let result = tokio::time::timeout(self.timeout, stream.results.message())
+            .await
+            .map_err(|_| {
+                Error::Grpc(Box::new(Status::deadline_exceeded(
+                    "no answer from the server",
+                )))
+            })?
+            .map_err(error)?;
+        match result {
+            Some(result) => {
+                let status = result.status();
+                Ok((stream, status))
+            }
+            None => Err(Error::Grpc(Box::new(Status::unavailable(
+                "the server closed the heartbeat stream",
+            )))),
+        }
+    }
+
+    pub async fn client_settings(
+        &self,
+        credentials: Vec<Credentials>,
+    ) -> Result<shared::ClientSettings, Error> {
+        let request = self.request(grpc::ClientSettingsRequest {}, self.headers(credentials)?);
+        let settings = self
+            .client
+            .clone()
+            .get_client_settings(request)
+            .await
+            .map_err(error)?
+            .into_inner();
+        Ok(settings.into())
+    }
+}
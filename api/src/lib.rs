@@ -0,0 +1,606 @@
+//! Typed client for the HTTP API of a Beelzebub server, used by the Windows
+//! client and usable by importers and other clients.
+//!
+//! The client finds the endpoints of the server with [`Client::negotiate`].
+//! Until that is called, the endpoints are assumed to be under
+//! `shared::API_PREFIX`.
+
+use std::fmt;
+use std::io::Write;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use flate2::{write::GzEncoder, Compression};
+use reqwest::{
+    header::{HeaderValue, ACCEPT, CONTENT_ENCODING, CONTENT_TYPE},
+    Method, RequestBuilder, Response, StatusCode, Url,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use shared::auth::Credentials;
+
+pub use shared;
+#[cfg(feature = "grpc")]
+pub use tonic;
+
+#[cfg(feature = "grpc")]
+mod grpc;
+
+/// What the server answered at `/api/version`.
+#[derive(Clone, Debug)]
+pub enum Server {
+    /// Endpoints are under the prefix of the version.
+    Versioned(shared::ApiVersion),
+
+    /// Server from before the versioned API, with its endpoints at the root.
+    Unversioned,
+
+    /// Server that doesn't speak the protocol of this crate.
+    Incompatible(shared::ApiVersion),
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The server URL can't be joined with the path of an endpoint.
+    InvalidUrl(String),
+
+    /// The server doesn't speak the protocol of this crate.
+    Incompatible(shared::ApiVersion),
+
+    /// The server doesn't have the capability from `shared::capability` that
+    /// the endpoint needs.
+    Unsupported(&'static str),
+
+    /// The request could not be sent or its response not received.
+    Http(reqwest::Error),
+
+    /// The body of the request could not be serialised or compressed.
+    Encode(std::io::Error),
+
+    /// The secret key, API key or device token was not accepted.
+    Unauthorized,
+
+    /// The server requires a newer client version.
+    UpgradeRequired,
+
+    /// The server answered with a status that the endpoint doesn't return
+    /// on success, with the code of the error if the server sent one.
+    Status {
+        status: StatusCode,
+        code: Option<shared::ApiErrorCode>,
+        request_id: Option<String>,
+    },
+
+    /// The server did not save the submission.
+    Rejected {
+        response: shared::SubmissionResponse,
+        request_id: Option<String>,
+    },
+
+    /// The body of the response was not what the endpoint returns.
+    Decode(reqwest::Error),
+
+    /// The MessagePack body of the response was not what the endpoint
+    /// returns.
+    DecodeMsgpack(shared::msgpack::Error),
+
+    /// The gRPC call failed with a status that the REST endpoint has no
+    /// error for.
+    #[cfg(feature = "grpc")]
+    Grpc(Box<tonic::Status>),
+}
+
+impl Error {
+    /// Sending the same request again would fail the same way.
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            Error::Rejected { response, .. } => response.status.is_permanent(),
+            _ => false,
+        }
+    }
+
+    /// Code of the error that the server answered with.
+    pub fn code(&self) -> Option<shared::ApiErrorCode> {
+        match self {
+            Error::Unauthorized => Some(shared::ApiErrorCode::Unauthenticated),
+            Error::UpgradeRequired => Some(shared::ApiErrorCode::VersionMismatch),
+            Error::Status { code, .. } => *code,
+            Error::Rejected { response, .. } => response.code.or(response.status.code()),
+            _ => None,
+        }
+    }
+
+    /// ID the server logged the request under, if it answered with one.
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            Error::Status { request_id, .. } | Error::Rejected { request_id, .. } => {
+                request_id.as_deref()
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidUrl(url) => write!(f, "invalid server URL {}", url),
+            Error::Incompatible(version) => write!(
+                f,
+                "server {} speaks API protocols {} to {} and this client speaks {}, update the {}",
+                version.server_version,
+                version.minimum_protocol,
+                version.protocol,
+                shared::PROTOCOL_VERSION,
+                if version.protocol < shared::PROTOCOL_VERSION {
+                    "server"
+                } else {
+                    "client"
+                }
+            ),
+            Error::Unsupported(capability) => {
+                write!(f, "the server does not support {}", capability)
+            }
+            Error::Http(error) => write!(f, "{}", error),
+            Error::Encode(error) => write!(f, "could not encode the request: {}", error),
+            Error::Unauthorized => write!(f, "unauthorized"),
+            Error::UpgradeRequired => write!(f, "the server requires a newer client version"),
+            Error::Status { status, .. } => write!(f, "unexpected response {}", status),
+            Error::Rejected { response, .. } => match &response.message {
+                Some(message) => write!(f, "rejected as {:?}: {}", response.status, message),
+                None => write!(f, "rejected as {:?}", response.status),
+            },
+            Error::Decode(error) => write!(f, "could not parse the response: {}", error),
+            Error::DecodeMsgpack(error) => write!(f, "could not parse the response: {}", error),
+            #[cfg(feature = "grpc")]
+            Error::Grpc(status) => write!(f, "{:?}: {}", status.code(), status.message()),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Http(error) | Error::Decode(error) => Some(error),
+            Error::Encode(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+fn request_id(response: &Response) -> Option<String> {
+    response
+        .headers()
+        .get(shared::REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(ToString::to_string)
+}
+
+fn gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+/// Client for one server. Clones share what [`Client::negotiate`] found.
+#[derive(Clone, Debug)]
+pub struct Client {
+    http: reqwest::Client,
+    url: Url,
+    secret: Option<String>,
+    device_token: Option<String>,
+    compress_requests: bool,
+    msgpack: bool,
+    sign_requests: bool,
+    server: Arc<RwLock<Option<Server>>>,
+    #[cfg(feature = "grpc")]
+    grpc: Option<grpc::Grpc>,
+}
+
+impl Client {
+    /// Client for the server at the URL, sending requests with the HTTP
+    /// client, which sets timeouts, proxies and TLS.
+    pub fn new(http: reqwest::Client, url: &str) -> Result<Self, Error> {
+        let url = Url::parse(url).map_err(|_| Error::InvalidUrl(url.to_string()))?;
+        Ok(Self {
+            http,
+            url,
+            secret: None,
+            device_token: None,
+            compress_requests: false,
+            msgpack: false,
+            sign_requests: false,
+            server: Arc::new(RwLock::new(None)),
+            #[cfg(feature = "grpc")]
+            grpc: None,
+        })
+    }
+
+    /// Authenticate with the secret key or an API key.
+    pub fn with_secret(mut self, secret: Option<String>) -> Self {
+        self.secret = secret;
+        self
+    }
+
+    /// Authenticate with the token of a registered device.
+    pub fn with_device_token(mut self, token: Option<String>) -> Self {
+        self.device_token = token;
+        self
+    }
+
+    /// Gzip submission and backfill bodies, and heartbeats sent as
+    /// MessagePack, for servers that support it.
+    pub fn with_compressed_requests(mut self, compress: bool) -> Self {
+        self.compress_requests = compress;
+        self
+    }
+
+    /// Send backfills and heartbeats as MessagePack, and ask for backfill
+    /// responses in it, for servers that support it. The bodies are smaller
+    /// than JSON, which matters on metered connections.
+    pub fn with_msgpack(mut self, msgpack: bool) -> Self {
+        self.msgpack = msgpack;
+        self
+    }
+
+    /// Sign requests with the secret instead of sending it, so that
+    /// requests that are captured can't be made again.
+    pub fn with_signed_requests(mut self, sign: bool) -> Self {
+        self.sign_requests = sign;
+        self
+    }
+
+    /// Submit sessions, send heartbeats and get client settings over gRPC on
+    /// the channel, for servers that support it. The headers are sent with
+    /// every call, and calls that aren't answered within the timeout fail.
+    /// Requests aren't signed over gRPC, so the REST API is used instead
+    /// when signing is enabled.
+    #[cfg(feature = "grpc")]
+    pub fn with_grpc(
+        mut self,
+        channel: tonic::transport::Channel,
+        headers: reqwest::header::HeaderMap,
+        timeout: std::time::Duration,
+    ) -> Self {
+        self.grpc = Some(grpc::Grpc::new(channel, headers, timeout));
+        self
+    }
+
+    pub fn set_device_token(&mut self, token: Option<String>) {
+        self.device_token = token;
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// What the server answered at the last [`Client::negotiate`].
+    pub fn server(&self) -> Option<Server> {
+        self.server
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// The server has the capability from `shared::capability`. Servers that
+    /// haven't been negotiated with yet and ones from before the versioned
+    /// API are assumed to have it.
+    pub fn supports(&self, capability: &str) -> bool {
+        match self.server() {
+            Some(Server::Versioned(version)) | Some(Server::Incompatible(version)) => {
+                version.has(capability)
+            }
+            Some(Server::Unversioned) | None => true,
+        }
+    }
+
+    /// Ask the server which protocols it speaks and where its endpoints are.
+    /// Failures leave what was found before.
+    pub async fn negotiate(&self) -> Result<Server, Error> {
+        let url = self
+            .url
+            .join(shared::API_VERSION_PATH)
+            .map_err(|_| Error::InvalidUrl(self.url.to_string()))?;
+        let response = self.http.get(url).send().await.map_err(Error::Http)?;
+        let server = if response.status() == StatusCode::NOT_FOUND {
+            Server::Unversioned
+        } else {
+            let version = response
+                .error_for_status()
+                .map_err(Error::Http)?
+                .json::<shared::ApiVersion>()
+                .await
+                .map_err(Error::Decode)?;
+            if version.speaks(shared::PROTOCOL_VERSION) {
+                Server::Versioned(version)
+            } else {
+                Server::Incompatible(version)
+            }
+        };
+        *self.server.write().unwrap_or_else(PoisonError::into_inner) = Some(server.clone());
+        Ok(server)
+    }
+
+    fn endpoint(&self, path: &str) -> Result<Url, Error> {
+        let prefix = match self.server() {
+            Some(Server::Versioned(version)) => version.prefix,
+            Some(Server::Unversioned) => String::new(),
+            Some(Server::Incompatible(version)) => return Err(Error::Incompatible(version)),
+            None => shared::API_PREFIX.to_string(),
+        };
+        self.url
+            .join(&format!("{}{}", prefix, path))
+            .map_err(|_| Error::InvalidUrl(self.url.to_string()))
+    }
+
+    fn require(&self, capability: &'static str) -> Result<(), Error> {
+        if self.supports(capability) {
+            Ok(())
+        } else {
+            Err(Error::Unsupported(capability))
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> Result<RequestBuilder, Error> {
+        let mut request = self.http.request(method, self.endpoint(path)?);
+        for credentials in self.credentials() {
+            let (name, value) = credentials.header();
+            request = request.header(name, value);
+        }
+        Ok(request)
+    }
+
+    /// Credentials sent in headers. A secret that requests are signed with is
+    /// not sent.
+    fn credentials(&self) -> Vec<Credentials> {
+        let secret = self
+            .secret
+            .clone()
+            .filter(|_| !self.sign_requests)
+            .map(Credentials::SecretKey);
+        let token = self.device_token.clone().map(Credentials::DeviceToken);
+        secret.into_iter().chain(token).collect()
+    }
+
+    /// The gRPC transport, if it is configured and can be used with the
+    /// server.
+    #[cfg(feature = "grpc")]
+    fn grpc(&self) -> Option<&grpc::Grpc> {
+        self.grpc
+            .as_ref()
+            .filter(|_| !self.sign_requests && self.supports(shared::capability::GRPC))
+    }
+
+    /// Request with the body as JSON, gzipped if that is enabled and the
+    /// server supports it.
+    fn with_body(
+        &self,
+        request: RequestBuilder,
+        body: &impl Serialize,
+    ) -> Result<RequestBuilder, Error> {
+        if !self.compress_requests || !self.supports(shared::capability::GZIP_REQUESTS) {
+            return Ok(request.json(body));
+        }
+        let body = serde_json::to_vec(body).map_err(|error| Error::Encode(error.into()))?;
+        let body = gzip(&body).map_err(Error::Encode)?;
+        Ok(request
+            .header(CONTENT_TYPE, "application/json")
+            .header(CONTENT_ENCODING, "gzip")
+            .body(body))
+    }
+
+    /// Request with the body as MessagePack if that is enabled and the server
+    /// supports it, gzipped like `with_body`, and as JSON otherwise.
+    fn with_msgpack_body(
+        &self,
+        request: RequestBuilder,
+        body: &impl Serialize,
+    ) -> Result<RequestBuilder, Error> {
+        if !self.msgpack || !self.supports(shared::capability::MSGPACK) {
+            return self.with_body(request, body);
+        }
+        let body = shared::msgpack::encode(body)
+            .map_err(|error| Error::Encode(std::io::Error::other(error)))?;
+        let request = request
+            .header(CONTENT_TYPE, shared::MSGPACK_CONTENT_TYPE)
+            .header(ACCEPT, shared::MSGPACK_CONTENT_TYPE);
+        if !self.compress_requests || !self.supports(shared::capability::GZIP_REQUESTS) {
+            return Ok(request.body(body));
+        }
+        let body = gzip(&body).map_err(Error::Encode)?;
+        Ok(request.header(CONTENT_ENCODING, "gzip").body(body))
+    }
+
+    /// Send the request, signed with the secret if signing is enabled.
+    async fn execute(&self, request: RequestBuilder) -> Result<Response, Error> {
+        let mut request = request.build().map_err(Error::Http)?;
+        if let (Some(secret), true) = (&self.secret, self.sign_requests) {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs());
+            let url = request.url();
+            let path = match url.query() {
+                Some(query) => format!("{}?{}", url.path(), query),
+                None => url.path().to_string(),
+            };
+            let body = request
+                .body()
+                .and_then(reqwest::Body::as_bytes)
+                .unwrap_or_default();
+            let signature = shared::signing::sign(
+                &shared::signing::key(secret),
+                timestamp as i64,
+                request.method().as_str(),
+                &path,
+                body,
+            );
+            let headers = request.headers_mut();
+            headers.insert(
+                shared::SIGNATURE_HEADER,
+                HeaderValue::try_from(format!("sha256={}", signature))
+                    .map_err(|error| Error::Encode(std::io::Error::other(error)))?,
+            );
+            headers.insert(
+                shared::SIGNATURE_TIMESTAMP_HEADER,
+                HeaderValue::from(timestamp),
+            );
+        }
+        self.http.execute(request).await.map_err(Error::Http)
+    }
+
+    /// Send the request, turning statuses other than success into errors.
+    async fn send(&self, request: RequestBuilder) -> Result<Response, Error> {
+        let response = self.execute(request).await?;
+        match response.status() {
+            status if status.is_success() => Ok(response),
+            StatusCode::UNAUTHORIZED => Err(Error::Unauthorized),
+            StatusCode::UPGRADE_REQUIRED => Err(Error::UpgradeRequired),
+            status => {
+                let request_id = request_id(&response);
+                let code = response
+                    .json::<shared::ApiError>()
+                    .await
+                    .ok()
+                    .map(|error| error.code);
+                Err(Error::Status {
+                    status,
+                    code,
+                    request_id,
+                })
+            }
+        }
+    }
+
+    /// Body of the response as JSON or, if the server answered with it,
+    /// MessagePack.
+    async fn json<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, Error> {
+        let response = self.send(request).await?;
+        let msgpack = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .is_some_and(|value| value == shared::MSGPACK_CONTENT_TYPE);
+        if !msgpack {
+            return response.json::<T>().await.map_err(Error::Decode);
+        }
+        let body = response.bytes().await.map_err(Error::Decode)?;
+        shared::msgpack::decode(&body).map_err(Error::DecodeMsgpack)
+    }
+
+    /// Submit a finished session. Sessions that the server already has are
+    /// accepted as duplicates.
+    pub async fn submit(
+        &self,
+        submission: &shared::Submission,
+    ) -> Result<shared::SubmissionResponse, Error> {
+        #[cfg(feature = "grpc")]
+        if let Some(grpc) = self.grpc() {
+            return grpc.submit(submission, self.credentials()).await;
+        }
+        let mut request = self.with_body(self.request(Method::POST, "/submit")?, submission)?;
+        if let Some(session_id) = &submission.session_id {
+            request = request.header(shared::IDEMPOTENCY_KEY_HEADER, session_id);
+        }
+        let response = self.execute(request).await?;
+        let status = response.status();
+        let request_id = request_id(&response);
+        let Ok(response) = response.json::<shared::SubmissionResponse>().await else {
+            return Err(Error::Status {
+                status,
+                code: None,
+                request_id,
+            });
+        };
+        match response.status {
+            shared::SubmissionResponseStatus::Ok | shared::SubmissionResponseStatus::Duplicate => {
+                Ok(response)
+            }
+            _ => Err(Error::Rejected {
+                response,
+                request_id,
+            }),
+        }
+    }
+
+    /// Import sessions with their recorded times, such as from another
+    /// tracker.
+    pub async fn backfill(
+        &self,
+        backfill: &shared::Backfill,
+    ) -> Result<shared::BackfillResponse, Error> {
+        self.require(shared::capability::BACKFILL)?;
+        let request = self.with_msgpack_body(self.request(Method::POST, "/backfill")?, backfill)?;
+        self.json(request).await
+    }
+
+    /// Report a session in progress, so that it shows as playing.
+    pub async fn heartbeat(&self, heartbeat: &shared::Submission) -> Result<(), Error> {
+        self.require(shared::capability::HEARTBEAT)?;
+        #[cfg(feature = "grpc")]
+        if let Some(grpc) = self.grpc() {
+            return grpc.heartbeat(heartbeat, self.credentials()).await;
+        }
+        let request =
+            self.with_msgpack_body(self.request(Method::POST, "/heartbeat")?, heartbeat)?;
+        self.send(request).await?;
+        Ok(())
+    }
+
+    /// Ask the server to register the device, or check on an earlier
+    /// request with the same nonce.
+    pub async fn register(
+        &self,
+        registration: &shared::RegistrationRequest,
+    ) -> Result<shared::RegistrationResponse, Error> {
+        self.require(shared::capability::DEVICE_REGISTRATION)?;
+        let request = self.request(Method::POST, "/register")?.json(registration);
+        // Pending and revoked registrations are answered with other statuses
+        // than 200 but the same body.
+        let response = self.execute(request).await?;
+        let status = response.status();
+        let request_id = request_id(&response);
+        response
+            .json::<shared::RegistrationResponse>()
+            .await
+            .map_err(|_| Error::Status {
+                status,
+                code: None,
+                request_id,
+            })
+    }
+
+    /// Settings that the server manages for its clients.
+    pub async fn client_settings(&self) -> Result<shared::ClientSettings, Error> {
+        self.require(shared::capability::CLIENT_SETTINGS)?;
+        #[cfg(feature = "grpc")]
+        if let Some(grpc) = self.grpc() {
+            return grpc.client_settings(self.credentials()).await;
+        }
+        self.json(self.request(Method::GET, "/client-settings")?)
+            .await
+    }
+
+    /// Playtime per process.
+    pub async fn stats(
+        &self,
+        query: &shared::StatsQuery,
+    ) -> Result<Vec<shared::ProcessStats>, Error> {
+        self.json(self.request(Method::GET, "/stats")?.query(query))
+            .await
+    }
+
+    /// Page of recorded sessions from newest to oldest.
+    pub async fn events(&self, query: &shared::EventsQuery) -> Result<shared::EventsPage, Error> {
+        self.json(self.request(Method::GET, "/events")?.query(query))
+            .await
+    }
+
+    /// Page of the sessions of a process, sorted as the query asks.
+    pub async fn process_sessions(
+        &self,
+        id: i32,
+        query: &shared::ListQuery,
+    ) -> Result<shared::Page<shared::Event>, Error> {
+        let path = format!("/processes/{}/sessions", id);
+        self.json(self.request(Method::GET, &path)?.query(query))
+            .await
+    }
+}
@@ -0,0 +1,70 @@
+use std::sync::RwLock;
+use std::time::Duration;
+
+use beelzebub_api::Server;
+use log::{debug, error, info, warn};
+
+use crate::config::Config;
+
+/// How often the server is asked for its protocols, so that the client notices
+/// when the server is updated.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Ask the server which protocols it speaks and where its endpoints are.
+/// Failures are logged and leave what was found before.
+pub async fn check(config: &RwLock<Config>) {
+    let api = {
+        let config = config.read().unwrap();
+        if config.local.is_some() {
+            return;
+        }
+        let Some(api) = config.api.clone() else {
+            return;
+        };
+        api
+    };
+    check_client(&api).await;
+}
+
+/// `check` for the server of the client.
+pub async fn check_client(api: &beelzebub_api::Client) {
+    let previous = api.server();
+    let server = match api.negotiate().await {
+        Ok(server) => server,
+        Err(beelzebub_api::Error::Decode(error)) => {
+            warn!("Could not parse the API version of the server: {}", error);
+            return;
+        }
+        Err(error) => {
+            warn!("Could not check the API version of the server: {}", error);
+            return;
+        }
+    };
+    match &server {
+        Server::Versioned(version) => {
+            let changed = match &previous {
+                Some(Server::Versioned(previous)) => {
+                    previous.server_version != version.server_version
+                }
+                _ => true,
+            };
+            if changed {
+                info!(
+                    "Server {} speaks API protocol {}",
+                    version.server_version,
+                    shared::PROTOCOL_VERSION
+                );
+            }
+            debug!("Server capabilities: {}", version.capabilities.join(", "));
+        }
+        Server::Unversioned => {
+            if !matches!(previous, Some(Server::Unversioned)) {
+                warn!("Server is older than the versioned API, using its deprecated endpoints");
+            }
+        }
+        Server::Incompatible(version) => error!(
+            "Not talking to the server: {}",
+            beelzebub_api::Error::Incompatible(version.clone())
+        ),
+    }
+}
@@ -0,0 +1,74 @@
+use log::{error, info};
+use windows::{
+    core::HSTRING,
+    Win32::{
+        Foundation::ERROR_FILE_NOT_FOUND,
+        System::Registry::{RegDeleteKeyValueW, RegSetKeyValueW, HKEY_CURRENT_USER, REG_SZ},
+    },
+};
+
+use crate::win;
+
+/// Per-user registry key for programs started at logon.
+const RUN_KEY: &str = "Software\\Microsoft\\Windows\\CurrentVersion\\Run";
+
+/// Name of the value under the run key.
+const RUN_VALUE: &str = "Beelzebub";
+
+/// Command that starts the current executable.
+pub fn command() -> std::io::Result<String> {
+    let executable = std::env::current_exe()?;
+    Ok(format!("\"{}\"", executable.display()))
+}
+
+/// Command registered to start at logon, `None` if autostart isn't enabled.
+pub fn registered() -> windows::core::Result<Option<String>> {
+    win::read_registry_string(HKEY_CURRENT_USER, RUN_KEY, RUN_VALUE)
+}
+
+/// Start the current executable when the user logs on.
+pub fn enable() -> Result<(), ()> {
+    let command =
+        command().map_err(|error| error!("Could not determine executable path: {}", error))?;
+    let data = command
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect::<Vec<u16>>();
+    let result = unsafe {
+        RegSetKeyValueW(
+            HKEY_CURRENT_USER,
+            &HSTRING::from(RUN_KEY),
+            &HSTRING::from(RUN_VALUE),
+            REG_SZ.0,
+            Some(data.as_ptr().cast()),
+            (data.len() * std::mem::size_of::<u16>()) as u32,
+        )
+    };
+    if let Err(error) = result.ok() {
+        error!("Could not enable autostart: {}", error);
+        return Err(());
+    }
+    info!("Enabled autostart for {}", command);
+    Ok(())
+}
+
+/// Stop starting the client when the user logs on.
+pub fn disable() -> Result<(), ()> {
+    let result = unsafe {
+        RegDeleteKeyValueW(
+            HKEY_CURRENT_USER,
+            &HSTRING::from(RUN_KEY),
+            &HSTRING::from(RUN_VALUE),
+        )
+    };
+    if result == ERROR_FILE_NOT_FOUND {
+        info!("Autostart was not enabled");
+        return Ok(());
+    }
+    if let Err(error) = result.ok() {
+        error!("Could not disable autostart: {}", error);
+        return Err(());
+    }
+    info!("Disabled autostart");
+    Ok(())
+}
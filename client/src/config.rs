@@ -1,10 +1,22 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use log::debug;
+use log::{debug, warn};
 use serde::Deserialize;
 use shared;
 
+use crate::defer::DeferConfig;
+use crate::device::{self, RegisterConfig};
+use crate::discord::DiscordConfig;
+use crate::hooks::Hook;
+use crate::http::{self, HttpConfig};
+use crate::local::LocalConfig;
+use crate::logging::{LogFileConfig, LogFormat};
+use crate::naming::{self, CommandLineRule};
+use crate::win;
+
 #[derive(Debug)]
 pub enum Error {
     /// Could not deserialise the Yaml.
@@ -15,26 +27,348 @@ pub enum Error {
 
     /// IO error with the configuration.
     IOError(std::io::Error),
+
+    /// Configuration was read but contains invalid settings.
+    ValidationError(String),
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Config {
+    #[serde(default)]
+    pub log_format: LogFormat,
+
+    pub log_file: Option<LogFileConfig>,
+
+    /// Write warnings and errors to the Windows Application event log.
+    #[serde(default)]
+    pub event_log: bool,
+
+    /// Log sessions instead of submitting them.
+    #[serde(default)]
+    pub dry_run: bool,
+
     #[serde(default = "default_minimum_duration")]
     pub minimum_duration: u32,
 
-    pub monitor: Vec<PathBuf>,
+    pub monitor: Vec<Monitor>,
 
-    pub url: String,
+    /// Executable names to track even when Windows reports no path for them.
+    #[serde(default)]
+    pub track_by_name: Vec<String>,
+
+    /// Name sessions of known emulators after the game they are running.
+    #[serde(default = "default_emulator_naming")]
+    pub emulator_naming: bool,
+
+    /// Version info fields to name sessions from, in order of preference.
+    #[serde(default = "naming::default_name_fields")]
+    pub name_fields: Vec<String>,
+
+    /// Names in the version info that are ignored as meaningless.
+    #[serde(default = "naming::default_generic_names")]
+    pub generic_names: Vec<String>,
+
+    /// Languages to prefer when reading version info, e.g. `0411` for Japanese.
+    #[serde(default)]
+    pub version_languages: Vec<win::LanguagePreference>,
+
+    /// Session names by executable, taking precedence over everything else.
+    #[serde(default)]
+    pub name_overrides: HashMap<String, String>,
+
+    /// Rules for naming sessions from the process command line.
+    #[serde(default)]
+    pub command_line_rules: Vec<CommandLineRule>,
+
+    /// Seconds between samples of the main window title of tracked
+    /// processes. Disabled if not set. Only read when the client starts.
+    pub window_title_interval: Option<u64>,
+
+    /// Port for the status endpoint on localhost. Disabled if not set.
+    pub status_port: Option<u16>,
+
+    /// Keep a journal of active watches to recover sessions after a crash.
+    /// Only read when the client starts.
+    #[serde(default = "default_journal")]
+    pub journal: bool,
+
+    /// Report sessions in progress to the server every minute, so that they
+    /// show as playing and are recorded even if the client crashes. Requires
+    /// a server that supports heartbeats.
+    #[serde(default)]
+    pub heartbeats: bool,
+
+    /// Show the current session as Discord Rich Presence. Only read when the
+    /// client starts.
+    pub discord: Option<DiscordConfig>,
+
+    /// What to do with time played over remote desktop.
+    #[serde(default)]
+    pub remote_sessions: RemoteSessionMode,
+
+    /// Show a notification when a session is submitted or fails to submit.
+    #[serde(default)]
+    pub notifications: bool,
+
+    /// Commands run when sessions start, end or are submitted.
+    #[serde(default)]
+    pub hooks: Vec<Hook>,
+
+    /// Global hotkey that toggles tracking on and off, e.g. `Ctrl+Alt+B`.
+    pub pause_hotkey: Option<String>,
+
+    /// Write sessions to a local file instead of submitting them to a server.
+    pub local: Option<LocalConfig>,
+
+    /// Queue submissions while on battery or a metered connection and send
+    /// them once the conditions clear.
+    pub defer: Option<DeferConfig>,
+
+    pub url: Option<String>,
+
+    #[serde(rename = "secret")]
+    secret_source: Option<SecretSource>,
+    #[serde(skip)]
     pub secret: Option<String>,
+
+    /// Register this device with the server to get a device token.
+    pub register: Option<RegisterConfig>,
+
+    /// Token issued by the server once the registration was approved.
+    #[serde(skip)]
+    pub device_token: Option<String>,
+    /// ID of the device on the server, sent with the sessions.
+    #[serde(skip)]
+    pub device_id: Option<i32>,
+
+    pub proxy: Option<ProxyConfig>,
+
+    /// Fetch monitors, names and the minimum duration from the server.
+    pub pull_settings: Option<PullSettingsConfig>,
+
+    /// Settings last fetched from the server.
+    #[serde(skip)]
+    pub pulled_settings: Option<shared::ClientSettings>,
+
+    #[serde(default)]
+    pub http: HttpConfig,
+
+    #[serde(default)]
+    pub transport: Transport,
+
+    /// Client for talking to the server, created when the config is loaded.
+    #[serde(skip)]
+    pub api: Option<beelzebub_api::Client>,
+
+    /// Gzip submission bodies. Requires a server that supports it.
+    #[serde(default)]
+    pub compress_requests: bool,
+
+    /// Send heartbeats as MessagePack, which is smaller than JSON. Requires
+    /// a server that supports it.
+    #[serde(default)]
+    pub msgpack: bool,
+
+    /// Sign requests with the secret instead of sending it.
+    #[serde(default)]
+    pub sign_requests: bool,
+    pub tls: Option<TlsConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyConfig {
+    /// Proxy URL, e.g. `http://proxy.internal:3128` or `socks5://proxy.internal:1080`.
+    pub url: String,
+
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PullSettingsConfig {
+    /// Seconds between fetches. Only read when the client starts.
+    #[serde(default = "default_pull_settings_interval")]
+    pub interval: u64,
+}
+
+fn default_pull_settings_interval() -> u64 {
+    3600
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsConfig {
+    /// PEM encoded client certificate for mutual TLS.
+    pub certificate: Option<PathBuf>,
+
+    /// PEM encoded PKCS #8 private key for the client certificate.
+    pub key: Option<PathBuf>,
+
+    /// PEM encoded CA certificate to trust in addition to the system roots.
+    pub ca: Option<PathBuf>,
+}
+
+/// How submissions, heartbeats and client settings go to the server.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    /// The REST endpoints.
+    #[default]
+    Rest,
+
+    /// The gRPC service of servers that have it, streaming heartbeats over
+    /// one connection. Other requests and servers without it use REST.
+    Grpc,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoteSessionMode {
+    /// Count remote time like any other.
+    #[default]
+    Track,
+
+    /// Tag sessions that were played remotely with `remote`.
+    Tag,
+
+    /// Don't count time while the session is remote.
+    Exclude,
+}
+
+/// Monitored directory or UWP package, given either as a plain path or with
+/// extra settings.
+#[derive(Deserialize, Debug)]
+#[serde(from = "MonitorEntry")]
+pub struct Monitor {
+    pub target: MonitorTarget,
+
+    /// Tags attached to every session started from this directory or package.
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum MonitorTarget {
+    /// Directory, normalised for matching with `win::normalize_path`.
+    Path(PathBuf),
+
+    /// Package family name, e.g. `Microsoft.624F8B84B80_8wekyb3d8bbwe`.
+    Package(String),
+}
+
+impl MonitorTarget {
+    fn path(path: PathBuf) -> Self {
+        MonitorTarget::Path(win::normalize_path(&path))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MonitorEntry {
+    Path(PathBuf),
+    Detailed {
+        path: PathBuf,
+        #[serde(default)]
+        tags: Vec<String>,
+    },
+    Package {
+        package: String,
+        #[serde(default)]
+        tags: Vec<String>,
+    },
+}
+
+impl From<MonitorEntry> for Monitor {
+    fn from(entry: MonitorEntry) -> Self {
+        match entry {
+            MonitorEntry::Path(path) => Monitor {
+                target: MonitorTarget::path(path),
+                tags: Vec::new(),
+            },
+            MonitorEntry::Detailed { path, tags } => Monitor {
+                target: MonitorTarget::path(path),
+                tags,
+            },
+            MonitorEntry::Package { package, tags } => Monitor {
+                target: MonitorTarget::Package(package),
+                tags,
+            },
+        }
+    }
+}
+
+/// Secret given either directly or as a reference to where it is stored.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum SecretSource {
+    Plain(String),
+    #[serde(rename_all = "camelCase")]
+    Reference {
+        /// Environment variable containing the secret.
+        env: Option<String>,
+
+        /// Generic credential in the Windows Credential Manager.
+        credential: Option<String>,
+
+        /// Fallback value if neither of the above yield a secret.
+        value: Option<String>,
+    },
+}
+
+impl SecretSource {
+    pub fn resolve(&self) -> Option<String> {
+        match self {
+            SecretSource::Plain(value) => Some(value.clone()),
+            SecretSource::Reference {
+                env,
+                credential,
+                value,
+            } => env
+                .as_ref()
+                .and_then(|name| std::env::var(name).ok())
+                .or_else(|| credential.as_deref().and_then(win::read_credential))
+                .or_else(|| value.clone()),
+        }
+    }
 }
 
 fn default_minimum_duration() -> u32 {
     0
 }
 
+fn default_journal() -> bool {
+    true
+}
+
+fn default_emulator_naming() -> bool {
+    true
+}
+
+/// Environment variable prefix for settings that override the config file.
+const ENV_PREFIX: &str = "BEELZEBUB_";
+
+fn env_override(name: &str) -> Option<String> {
+    std::env::var(format!("{}{}", ENV_PREFIX, name)).ok()
+}
+
+/// Parse an environment variable override, if it is set.
+fn parse_env_override<T: std::str::FromStr>(name: &str) -> Result<Option<T>, String> {
+    match env_override(name) {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("invalid value for {}{}: {}", ENV_PREFIX, name, value)),
+        None => Ok(None),
+    }
+}
+
 impl Config {
     pub fn get_path() -> Result<PathBuf, Error> {
+        if let Some(path) = env_override("CONFIG") {
+            return Ok(PathBuf::from(path));
+        }
         let Some(project_directory) = directories::ProjectDirs::from(
             shared::CONFIG_QUALIFIER,
             shared::CONFIG_ORGANIZATION,
@@ -48,20 +382,203 @@ impl Config {
         return Ok(config_path);
     }
 
-    /// Given path is configured for monitoring.
-    pub fn is_monitored(&self, path: &Path) -> bool {
-        for monitor in &self.monitor {
-            if path.starts_with(monitor) {
-                return true;
-            }
+    /// Monitor configuration for the given path, if it is monitored.
+    pub fn get_monitor(&self, path: &Path) -> Option<&Monitor> {
+        let path = win::normalize_path(path);
+        self.monitor.iter().find(|monitor| match &monitor.target {
+            MonitorTarget::Path(monitor_path) => path.starts_with(monitor_path),
+            MonitorTarget::Package(_) => false,
+        })
+    }
+
+    /// Monitor configuration for the given package family, if it is monitored.
+    pub fn get_package_monitor(&self, family_name: &str) -> Option<&Monitor> {
+        self.monitor.iter().find(|monitor| match &monitor.target {
+            MonitorTarget::Path(_) => false,
+            MonitorTarget::Package(package) => package.eq_ignore_ascii_case(family_name),
+        })
+    }
+
+    /// Any UWP packages are configured for monitoring.
+    pub fn has_package_monitors(&self) -> bool {
+        self.monitor
+            .iter()
+            .any(|monitor| matches!(monitor.target, MonitorTarget::Package(_)))
+    }
+
+    /// Configured name for the executable, if any.
+    pub fn name_override(&self, executable: &str) -> Option<String> {
+        self.name_overrides
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(executable))
+            .map(|(_, name)| name.clone())
+    }
+
+    /// Replace the settings from the file with the ones from the server.
+    pub fn apply_pulled_settings(&mut self, settings: shared::ClientSettings) {
+        let monitor = settings
+            .monitor
+            .iter()
+            .filter_map(|setting| {
+                let target = match (&setting.path, &setting.package) {
+                    (Some(path), None) => MonitorTarget::path(PathBuf::from(path)),
+                    (None, Some(package)) => MonitorTarget::Package(package.clone()),
+                    _ => {
+                        warn!("Ignoring monitor from the server without exactly one of path or package");
+                        return None;
+                    }
+                };
+                Some(Monitor {
+                    target,
+                    tags: setting.tags.clone(),
+                })
+            })
+            .collect::<Vec<Monitor>>();
+        if !monitor.is_empty() {
+            self.monitor = monitor;
+        }
+        self.name_overrides.extend(settings.name_overrides.clone());
+        if let Some(minimum_duration) = settings.minimum_duration {
+            self.minimum_duration = minimum_duration;
+        }
+        self.pulled_settings = Some(settings);
+    }
+
+    /// Given executable name is configured to be tracked without a path.
+    pub fn is_tracked_by_name(&self, executable: &str) -> bool {
+        self.track_by_name
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(executable))
+    }
+
+    /// Use the token issued when the registration of the device was approved.
+    pub fn set_device_token(&mut self, token: String, device_id: Option<i32>) {
+        if let Some(api) = &mut self.api {
+            api.set_device_token(Some(token.clone()));
         }
-        return false;
+        self.device_token = Some(token);
+        self.device_id = device_id;
     }
 
     pub fn load(config_path: &Path) -> Result<Self, Error> {
         debug!("Loading config from {}", config_path.display());
         let fp = File::open(&config_path).map_err(Error::IOError)?;
-        let config: Config = serde_yaml::from_reader(fp).map_err(Error::DeserialisationError)?;
+        let mut config: Config =
+            serde_yaml::from_reader(fp).map_err(Error::DeserialisationError)?;
+        config.secret = config
+            .secret_source
+            .as_ref()
+            .and_then(SecretSource::resolve);
+        if config.register.is_some() {
+            config.device_token = device::load_token();
+            config.device_id = device::load_device_id();
+        }
+        config
+            .apply_env_overrides()
+            .map_err(Error::ValidationError)?;
+        config.validate().map_err(Error::ValidationError)?;
+        let http_client = http::build_client(&config)
+            .map_err(|_| Error::ValidationError("invalid HTTP settings".to_string()))?;
+        if let Some(url) = &config.url {
+            let mut api = beelzebub_api::Client::new(http_client, url)
+                .map_err(|error| Error::ValidationError(error.to_string()))?
+                .with_secret(config.secret.clone())
+                .with_device_token(config.device_token.clone())
+                .with_compressed_requests(config.compress_requests)
+                .with_msgpack(config.msgpack)
+                .with_signed_requests(config.sign_requests);
+            if config.transport == Transport::Grpc {
+                let channel = http::build_channel(&config, url)
+                    .map_err(|_| Error::ValidationError("invalid gRPC settings".to_string()))?;
+                api = api.with_grpc(
+                    channel,
+                    http::default_headers(),
+                    Duration::from_secs(config.http.timeout),
+                );
+            }
+            config.api = Some(api);
+        }
         return Ok(config);
     }
+
+    /// Replace settings from the file with the ones given in `BEELZEBUB_*`
+    /// environment variables.
+    fn apply_env_overrides(&mut self) -> Result<(), String> {
+        if let Some(url) = env_override("URL") {
+            self.url = Some(url);
+        }
+        if let Some(secret) = env_override("SECRET") {
+            self.secret = Some(secret);
+        }
+        if let Some(dry_run) = parse_env_override("DRY_RUN")? {
+            self.dry_run = dry_run;
+        }
+        if let Some(minimum_duration) = parse_env_override("MINIMUM_DURATION")? {
+            self.minimum_duration = minimum_duration;
+        }
+        if let Some(status_port) = parse_env_override("STATUS_PORT")? {
+            self.status_port = Some(status_port);
+        }
+        if let Some(compress_requests) = parse_env_override("COMPRESS_REQUESTS")? {
+            self.compress_requests = compress_requests;
+        }
+        Ok(())
+    }
+
+    /// Check for settings that deserialise fine but cannot work.
+    fn validate(&self) -> Result<(), String> {
+        if self.local.is_none() {
+            let Some(url) = &self.url else {
+                return Err("either url or local must be configured".to_string());
+            };
+            if let Err(error) = reqwest::Url::parse(url) {
+                return Err(format!("invalid url {}: {}", url, error));
+            }
+        }
+        if let Some(proxy) = &self.proxy {
+            if let Err(error) = reqwest::Url::parse(&proxy.url) {
+                return Err(format!("invalid proxy url {}: {}", proxy.url, error));
+            }
+        }
+        if self.transport == Transport::Grpc {
+            if self.proxy.is_some() {
+                return Err("the grpc transport can't be used with a proxy".to_string());
+            }
+            if self.sign_requests {
+                return Err("the grpc transport can't be used with signRequests".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monitor_of_path() {
+        let config: Config = serde_yaml::from_str(
+            "monitor:\n  - 'Q:\\Games\\'\n  - path: 'q:/Emulators'\n    tags: [emulator]\n",
+        )
+        .unwrap();
+        let cases = [
+            (r"Q:\Games\Doom\doom.exe", Some(0)),
+            (r"q:\GAMES\Doom\doom.exe", Some(0)),
+            (r"\\?\Q:\Games\Doom\doom.exe", Some(0)),
+            (r"Q:\Emulators\RetroArch\retroarch.exe", Some(1)),
+            (r"Q:\Games2\doom.exe", None),
+            (r"R:\Games\Doom\doom.exe", None),
+        ];
+        for (path, output) in cases {
+            let monitor = config.get_monitor(Path::new(path));
+            let index = monitor.and_then(|monitor| {
+                config
+                    .monitor
+                    .iter()
+                    .position(|other| std::ptr::eq(other, monitor))
+            });
+            assert_eq!(index, output, "{}", path);
+        }
+    }
 }
@@ -0,0 +1,130 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use log::{debug, warn};
+use serde::Deserialize;
+
+use crate::files;
+use crate::win;
+
+/// How often to check whether deferred submissions can be sent.
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeferConfig {
+    /// Hold submissions while the system is running on battery.
+    #[serde(default = "default_on_battery")]
+    pub on_battery: bool,
+
+    /// Hold submissions while the internet connection is metered.
+    #[serde(default = "default_on_metered")]
+    pub on_metered: bool,
+}
+
+fn default_on_battery() -> bool {
+    true
+}
+
+fn default_on_metered() -> bool {
+    true
+}
+
+impl DeferConfig {
+    /// Reason to hold submissions right now, if any.
+    pub fn reason(&self) -> Option<&'static str> {
+        if self.on_battery && win::is_on_battery() {
+            return Some("on battery");
+        }
+        if self.on_metered && win::is_metered_connection() {
+            return Some("on a metered connection");
+        }
+        None
+    }
+}
+
+/// File that holds submissions waiting to be sent.
+pub struct Queue {
+    path: PathBuf,
+}
+
+impl Queue {
+    /// Queue in the data directory, if one can be determined.
+    pub fn open() -> Option<Self> {
+        Some(Self {
+            path: files::data_path("queue.json")?,
+        })
+    }
+
+    /// Submissions currently in the queue. A queue that can't be parsed is
+    /// moved aside to `queue.json.bad`, so that the next write doesn't lose
+    /// the sessions in it. Fails if the queue can't be read or moved aside,
+    /// as writing it would then replace the queued sessions.
+    pub fn read(&self) -> Result<Vec<shared::Submission>, ()> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => {
+                warn!("Could not read queue {}: {}", self.path.display(), error);
+                return Err(());
+            }
+        };
+        let error = match serde_json::from_slice(&data) {
+            Ok(submissions) => return Ok(submissions),
+            Err(error) => error,
+        };
+        let mut bad_path = self.path.as_os_str().to_owned();
+        bad_path.push(".bad");
+        let bad_path = PathBuf::from(bad_path);
+        match fs::rename(&self.path, &bad_path) {
+            Ok(()) => {
+                warn!(
+                    "Could not parse queue {}, moved it to {}: {}",
+                    self.path.display(),
+                    bad_path.display(),
+                    error
+                );
+                Ok(Vec::new())
+            }
+            Err(rename_error) => {
+                warn!(
+                    "Could not parse queue {} or move it aside: {}, {}",
+                    self.path.display(),
+                    error,
+                    rename_error
+                );
+                Err(())
+            }
+        }
+    }
+
+    /// Add a submission to the end of the queue.
+    pub fn push(&self, submission: shared::Submission) -> Result<(), ()> {
+        let mut submissions = self.read()?;
+        submissions.push(submission);
+        self.write(&submissions)
+    }
+
+    /// Replace the queue with the given submissions, keeping the queued
+    /// sessions if writing fails.
+    pub fn write(&self, submissions: &[shared::Submission]) -> Result<(), ()> {
+        let data = match serde_json::to_vec(submissions) {
+            Ok(data) => data,
+            Err(error) => {
+                warn!("Could not serialise queue: {}", error);
+                return Err(());
+            }
+        };
+        match files::write_atomically(&self.path, &data) {
+            Ok(()) => {
+                debug!("Wrote {} submissions to the queue", submissions.len());
+                Ok(())
+            }
+            Err(error) => {
+                warn!("Could not write queue {}: {}", self.path.display(), error);
+                Err(())
+            }
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::files;
+
+/// How often to check whether a pending registration has been approved.
+pub const REGISTRATION_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisterConfig {
+    /// Name shown on the server when approving the device. Defaults to the
+    /// computer name.
+    pub name: Option<String>,
+}
+
+/// Registration of this device, kept in the data directory.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeviceState {
+    nonce: String,
+    token: Option<String>,
+
+    /// Registrations approved by older servers don't have it.
+    #[serde(default)]
+    device_id: Option<i32>,
+}
+
+fn state_path() -> Option<PathBuf> {
+    files::data_path("device.json")
+}
+
+fn load_state() -> Option<DeviceState> {
+    let data = fs::read(state_path()?).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+fn save_state(state: &DeviceState) -> Result<(), ()> {
+    let Some(path) = state_path() else {
+        error!("Could not determine path for the device registration");
+        return Err(());
+    };
+    let data = serde_json::to_vec(state)
+        .map_err(|error| error!("Could not serialise device registration: {}", error))?;
+    files::write_atomically(&path, &data)
+        .map_err(|error| error!("Could not write {}: {}", path.display(), error))
+}
+
+fn generate_nonce() -> Result<String, ()> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|error| error!("Could not generate registration nonce: {}", error))?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Token issued to this device, if it has been approved.
+pub fn load_token() -> Option<String> {
+    load_state()?.token
+}
+
+/// ID of this device on the server, if it has been approved.
+pub fn load_device_id() -> Option<i32> {
+    load_state()?.device_id
+}
+
+/// Ask the server to register this device, or check on an earlier request.
+/// Returns `true` once no further attempts are needed.
+pub async fn register(config: &RwLock<Config>) -> bool {
+    let (api, name) = {
+        let config = config.read().unwrap();
+        let Some(register) = &config.register else {
+            return true;
+        };
+        if config.device_token.is_some() {
+            return true;
+        }
+        let Some(api) = config.api.clone() else {
+            error!("Could not register device: no server URL configured");
+            return true;
+        };
+        let name = register
+            .name
+            .clone()
+            .or_else(|| std::env::var("COMPUTERNAME").ok())
+            .unwrap_or_else(|| "Unknown".to_string());
+        (api, name)
+    };
+
+    let mut state = load_state().unwrap_or_default();
+    if state.nonce.is_empty() {
+        let Ok(nonce) = generate_nonce() else {
+            return false;
+        };
+        state.nonce = nonce;
+        if save_state(&state).is_err() {
+            return false;
+        }
+    }
+
+    let request = shared::RegistrationRequest {
+        name,
+        nonce: state.nonce.clone(),
+    };
+    let response = match api.register(&request).await {
+        Ok(response) => response,
+        Err(error) => {
+            warn!("Could not register device: {}", error);
+            return false;
+        }
+    };
+    match (response.status, response.token) {
+        (shared::RegistrationStatus::Approved, Some(token)) => {
+            state.token = Some(token.clone());
+            state.device_id = response.device_id;
+            if save_state(&state).is_err() {
+                return false;
+            }
+            config
+                .write()
+                .unwrap()
+                .set_device_token(token, response.device_id);
+            info!("Device registration approved");
+            true
+        }
+        (shared::RegistrationStatus::Revoked, _) => {
+            error!("Device registration has been revoked");
+            true
+        }
+        _ => {
+            info!("Device registration is waiting for approval");
+            false
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::time::Duration;
+
+use log::{debug, info};
+use serde::Deserialize;
+use serde_json::json;
+use time::OffsetDateTime;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::windows::named_pipe::{ClientOptions, NamedPipeClient};
+
+use crate::watch::Tracker;
+
+/// How often the presence is updated. Discord limits activity updates to
+/// five per 20 seconds.
+pub const PRESENCE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Discord listens on the first free pipe of `discord-ipc-0` to `discord-ipc-9`.
+const PIPE_COUNT: u32 = 10;
+
+const OP_HANDSHAKE: u32 = 0;
+const OP_FRAME: u32 = 1;
+
+/// Start times closer than this are considered the same, since the start is
+/// derived from the session duration and moves around by a second or so.
+const START_TOLERANCE: i64 = 2;
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscordConfig {
+    /// Application ID of a Discord application, which is shown as the
+    /// "Playing" title above the game name.
+    pub client_id: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Activity {
+    name: String,
+
+    /// Unix timestamp of the session start, excluding paused time.
+    start: i64,
+}
+
+impl Activity {
+    fn from_tracker(tracker: &Tracker) -> Option<Self> {
+        if tracker.paused || tracker.suspended {
+            return None;
+        }
+        let watch = tracker.watches.values().max_by_key(|watch| watch.start)?;
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        Some(Self {
+            name: watch.name.clone().unwrap_or(watch.executable.clone()),
+            start: now - watch.duration().as_secs() as i64,
+        })
+    }
+
+    fn is_same(&self, other: &Activity) -> bool {
+        self.name == other.name && (self.start - other.start).abs() <= START_TOLERANCE
+    }
+}
+
+/// Publishes the current session as Discord Rich Presence over the local
+/// Discord IPC pipe.
+pub struct Presence {
+    client_id: String,
+    pipe: Option<NamedPipeClient>,
+    current: Option<Activity>,
+    nonce: u64,
+}
+
+async fn write_frame(
+    pipe: &mut NamedPipeClient,
+    op: u32,
+    payload: &serde_json::Value,
+) -> std::io::Result<()> {
+    let payload = serde_json::to_vec(payload)?;
+    let mut frame = Vec::with_capacity(payload.len() + 8);
+    frame.extend_from_slice(&op.to_le_bytes());
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&payload);
+    pipe.write_all(&frame).await
+}
+
+async fn read_frame(pipe: &mut NamedPipeClient) -> std::io::Result<(u32, serde_json::Value)> {
+    let op = pipe.read_u32_le().await?;
+    let length = pipe.read_u32_le().await?;
+    let mut payload = vec![0u8; length as usize];
+    pipe.read_exact(&mut payload).await?;
+    Ok((op, serde_json::from_slice(&payload)?))
+}
+
+impl Presence {
+    pub fn new(config: &DiscordConfig) -> Self {
+        Self {
+            client_id: config.client_id.clone(),
+            pipe: None,
+            current: None,
+            nonce: 0,
+        }
+    }
+
+    async fn connect(&self) -> std::io::Result<NamedPipeClient> {
+        let mut last_error = None;
+        for index in 0..PIPE_COUNT {
+            let path = format!(r"\\.\pipe\discord-ipc-{}", index);
+            match ClientOptions::new().open(&path) {
+                Ok(mut pipe) => {
+                    let handshake = json!({"v": 1, "client_id": self.client_id});
+                    write_frame(&mut pipe, OP_HANDSHAKE, &handshake).await?;
+                    read_frame(&mut pipe).await?;
+                    info!("Connected to Discord");
+                    return Ok(pipe);
+                }
+                Err(error) => last_error = Some(error),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| std::io::ErrorKind::NotFound.into()))
+    }
+
+    async fn set_activity(&mut self, activity: Option<&Activity>) -> std::io::Result<()> {
+        if self.pipe.is_none() {
+            self.pipe = Some(self.connect().await?);
+        }
+        let Some(pipe) = self.pipe.as_mut() else {
+            return Ok(());
+        };
+        self.nonce += 1;
+        let activity = activity.map(|activity| {
+            json!({
+                "details": activity.name,
+                "timestamps": {"start": activity.start},
+            })
+        });
+        let command = json!({
+            "cmd": "SET_ACTIVITY",
+            "args": {"pid": std::process::id(), "activity": activity},
+            "nonce": self.nonce.to_string(),
+        });
+        write_frame(pipe, OP_FRAME, &command).await?;
+        read_frame(pipe).await?;
+        Ok(())
+    }
+
+    /// Show the most recently started session, or clear the presence if
+    /// nothing is being tracked.
+    pub async fn update(&mut self, tracker: &Tracker) {
+        let activity = Activity::from_tracker(tracker);
+        let unchanged = match (&activity, &self.current) {
+            (Some(activity), Some(current)) => activity.is_same(current),
+            (None, None) => true,
+            _ => false,
+        };
+        if unchanged {
+            return;
+        }
+        match self.set_activity(activity.as_ref()).await {
+            Ok(()) => self.current = activity,
+            Err(error) => {
+                // Discord is most likely just not running.
+                debug!("Could not update Discord presence: {}", error);
+                self.pipe = None;
+                self.current = None;
+            }
+        }
+    }
+}
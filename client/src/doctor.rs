@@ -0,0 +1,283 @@
+use std::path::{Path, PathBuf};
+
+use beelzebub_api::Server;
+use reqwest::StatusCode;
+use wmi::COMLibrary;
+
+use crate::autostart;
+use crate::config::{Config, MonitorTarget};
+use crate::win;
+
+/// Most directories looked into when searching a monitored directory for an
+/// executable, so that a monitor of a whole drive doesn't take forever.
+const SEARCH_LIMIT: usize = 500;
+
+enum Outcome {
+    Ok(String),
+    Warning(String),
+    Failed(String),
+}
+
+fn report(check: &str, outcome: &Outcome) {
+    let (label, message) = match outcome {
+        Outcome::Ok(message) => ("ok", message),
+        Outcome::Warning(message) => ("warning", message),
+        Outcome::Failed(message) => ("FAILED", message),
+    };
+    println!("{:<8} {:<13} {}", label, check, message);
+}
+
+/// Run every check and print what was found, `Err` if any check failed.
+pub async fn run() -> Result<(), ()> {
+    let mut outcomes = vec![("COM", check_com()), ("WMI", check_wmi())];
+    let config = match Config::get_path() {
+        Ok(path) => match Config::load(&path) {
+            Ok(config) => {
+                outcomes.push((
+                    "Config",
+                    Outcome::Ok(format!("{} is valid", path.display())),
+                ));
+                Some(config)
+            }
+            Err(error) => {
+                let message = format!(
+                    "{} could not be loaded: {:?}. Fix the file and run the doctor again.",
+                    path.display(),
+                    error
+                );
+                outcomes.push(("Config", Outcome::Failed(message)));
+                None
+            }
+        },
+        Err(error) => {
+            let message = format!("Could not determine the path of the config: {:?}", error);
+            outcomes.push(("Config", Outcome::Failed(message)));
+            None
+        }
+    };
+    if let Some(config) = &config {
+        outcomes.extend(check_server(config).await);
+        outcomes.push(("Version info", check_version_info(config)));
+    }
+    outcomes.push(("Autostart", check_autostart()));
+
+    for (check, outcome) in &outcomes {
+        report(check, outcome);
+    }
+    if outcomes
+        .iter()
+        .any(|(_, outcome)| matches!(outcome, Outcome::Failed(_)))
+    {
+        return Err(());
+    }
+    Ok(())
+}
+
+fn check_com() -> Outcome {
+    match COMLibrary::new() {
+        Ok(_) => Outcome::Ok("Initialised".to_string()),
+        Err(error) => Outcome::Failed(format!(
+            "Could not be initialised: {}. Processes can't be watched without it.",
+            error
+        )),
+    }
+}
+
+fn check_wmi() -> Outcome {
+    match win::create_streams() {
+        Ok(_) => Outcome::Ok("Subscribed to process and power events".to_string()),
+        Err(error) => Outcome::Failed(format!(
+            "Could not subscribe to process events: {}. Check that the Windows Management \
+            Instrumentation service is running.",
+            error
+        )),
+    }
+}
+
+/// Reachability of the server and whether it accepts the credentials.
+async fn check_server(config: &Config) -> Vec<(&'static str, Outcome)> {
+    if let Some(local) = &config.local {
+        let message = match local.get_path() {
+            Some(path) => format!("Not used, sessions are written to {}", path.display()),
+            None => "Not used, but the file to write sessions to can't be determined. Set \
+                `local.path`."
+                .to_string(),
+        };
+        return vec![("Server", Outcome::Ok(message))];
+    }
+    let Some(api) = &config.api else {
+        let message = "No url configured. Set `url` to the address of the server.".to_string();
+        return vec![("Server", Outcome::Failed(message))];
+    };
+    let url = api.url().to_string();
+    let server = match api.negotiate().await {
+        Ok(Server::Versioned(version)) => Outcome::Ok(format!(
+            "{} is running server {}",
+            url, version.server_version
+        )),
+        Ok(Server::Unversioned) => Outcome::Warning(format!(
+            "{} is older than the versioned API. Update the server.",
+            url
+        )),
+        Ok(Server::Incompatible(version)) => {
+            let message = format!(
+                "{} can't be used: {}",
+                url,
+                beelzebub_api::Error::Incompatible(version)
+            );
+            return vec![("Server", Outcome::Failed(message))];
+        }
+        Err(error) => {
+            let message = format!(
+                "Could not reach {}: {}. Check `url`, `proxy` and `tls`.",
+                url, error
+            );
+            return vec![("Server", Outcome::Failed(message))];
+        }
+    };
+
+    let credentials = if config.secret.is_none() && config.device_token.is_none() {
+        match &config.register {
+            Some(_) => Outcome::Warning(
+                "Device is not registered yet. Approve the registration on the server.".to_string(),
+            ),
+            None => Outcome::Failed(
+                "No secret configured. Set `secret` to the secret key of the server.".to_string(),
+            ),
+        }
+    } else {
+        // Client settings need the same credentials as submissions, and the
+        // server answers Not Found if it has none to give.
+        match api.client_settings().await {
+            Ok(_) => Outcome::Ok("Accepted by the server".to_string()),
+            Err(beelzebub_api::Error::Status { status, .. }) if status == StatusCode::NOT_FOUND => {
+                Outcome::Ok("Accepted by the server".to_string())
+            }
+            Err(beelzebub_api::Error::Unauthorized) => Outcome::Failed(
+                "Not accepted by the server. Double check `secret` or register the device again."
+                    .to_string(),
+            ),
+            Err(beelzebub_api::Error::UpgradeRequired) => Outcome::Failed(
+                "The server requires a newer client. Update the client.".to_string(),
+            ),
+            Err(beelzebub_api::Error::Unsupported(_)) => Outcome::Warning(
+                "Can't be checked as the server is too old to have client settings".to_string(),
+            ),
+            Err(error) => Outcome::Failed(format!("Could not be checked: {}", error)),
+        }
+    };
+    return vec![("Server", server), ("Credentials", credentials)];
+}
+
+/// Whether version information can be read of an executable in the first
+/// monitored directory that exists.
+fn check_version_info(config: &Config) -> Outcome {
+    let directories = config
+        .monitor
+        .iter()
+        .filter_map(|monitor| match &monitor.target {
+            MonitorTarget::Path(path) => Some(path),
+            MonitorTarget::Package(_) => None,
+        })
+        .collect::<Vec<_>>();
+    if directories.is_empty() {
+        return Outcome::Warning("No monitored directories to check".to_string());
+    }
+    let missing = directories
+        .iter()
+        .filter(|directory| !directory.is_dir())
+        .map(|directory| directory.display().to_string())
+        .collect::<Vec<_>>();
+    let Some(executable) = directories
+        .iter()
+        .filter(|directory| directory.is_dir())
+        .find_map(|directory| find_executable(directory))
+    else {
+        if missing.is_empty() {
+            return Outcome::Warning(
+                "No executables found in the monitored directories".to_string(),
+            );
+        }
+        return Outcome::Warning(format!(
+            "No executables found in the monitored directories, and these don't exist: {}",
+            missing.join(", ")
+        ));
+    };
+
+    let process = win::Process::scripted(0, &executable.to_string_lossy(), None);
+    let version_info = process.get_version_info(
+        &config.name_fields,
+        &config.generic_names,
+        &config.version_languages,
+    );
+    let outcome = match version_info.name {
+        Some(name) => format!("{} is named {}", executable.display(), name),
+        None => {
+            return Outcome::Warning(format!(
+                "{} has no usable name in its version information. Sessions of it are named \
+                with `nameOverrides` or on the server.",
+                executable.display()
+            ))
+        }
+    };
+    if !missing.is_empty() {
+        return Outcome::Warning(format!(
+            "{}, but monitored directories don't exist: {}",
+            outcome,
+            missing.join(", ")
+        ));
+    }
+    Outcome::Ok(outcome)
+}
+
+/// First executable in the directory or its subdirectories.
+fn find_executable(directory: &Path) -> Option<PathBuf> {
+    let mut pending = vec![directory.to_path_buf()];
+    let mut searched = 0;
+    while let Some(directory) = pending.pop() {
+        searched += 1;
+        if searched > SEARCH_LIMIT {
+            return None;
+        }
+        let Ok(entries) = std::fs::read_dir(&directory) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("exe"))
+            {
+                return Some(path);
+            }
+        }
+    }
+    None
+}
+
+fn check_autostart() -> Outcome {
+    let registered = match autostart::registered() {
+        Ok(Some(registered)) => registered,
+        Ok(None) => {
+            return Outcome::Warning(
+                "Not enabled. Run `beelzebub-client autostart enable` to start the client at \
+                logon."
+                    .to_string(),
+            )
+        }
+        Err(error) => return Outcome::Failed(format!("Could not be read: {}", error)),
+    };
+    match autostart::command() {
+        Ok(command) if command.eq_ignore_ascii_case(&registered) => {
+            Outcome::Ok("Starts at logon".to_string())
+        }
+        Ok(_) => Outcome::Warning(format!(
+            "Starts {} at logon instead of this client. Run `beelzebub-client autostart enable` \
+            again if the client was moved.",
+            registered
+        )),
+        Err(error) => Outcome::Failed(format!("Could not determine executable path: {}", error)),
+    }
+}
@@ -0,0 +1,26 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Path of the file in the data directory, if one can be determined.
+pub fn data_path(name: &str) -> Option<PathBuf> {
+    let project_directory = directories::ProjectDirs::from(
+        shared::CONFIG_QUALIFIER,
+        shared::CONFIG_ORGANIZATION,
+        shared::CONFIG_APPLICATION,
+    )?;
+    Some(project_directory.data_dir().join(name))
+}
+
+/// Replace the file with the data, creating its directory if needed.
+///
+/// The data is written to a temporary file first and then renamed over the
+/// old file, so that a crash mid-write leaves the previous contents intact.
+pub fn write_atomically(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut temporary_path = path.as_os_str().to_owned();
+    temporary_path.push(".tmp");
+    fs::write(&temporary_path, data)?;
+    fs::rename(&temporary_path, path)
+}
@@ -0,0 +1,68 @@
+use std::sync::RwLock;
+use std::time::Duration;
+
+use log::{debug, warn};
+use time::OffsetDateTime;
+
+use crate::config::Config;
+use crate::watch::{Tracker, Watch};
+
+/// How often sessions in progress are reported to the server. Well below
+/// the default timeout after which the server closes a session.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Session as it is so far. Watches without a session ID can't be matched
+/// to their final submission and are left out.
+fn heartbeat(watch: &Watch, device_id: Option<i32>) -> Option<shared::Submission> {
+    Some(shared::Submission {
+        duration: watch.duration().into(),
+        executable: watch.executable.clone(),
+        name: watch.name.clone(),
+        product_version: watch.product_version.clone(),
+        file_version: watch.file_version.clone(),
+        started_at: Some(watch.started_at),
+        ended_at: Some(OffsetDateTime::now_utc()),
+        focused_duration: Some(watch.focused.into()),
+        tags: watch.tags.clone(),
+        window_title: watch.window_title(),
+        session_id: Some(watch.session_id.clone()?),
+        device_id,
+    })
+}
+
+/// Report every session long enough to be submitted to the server, so that
+/// it shows as playing and is recorded even if the client stops.
+pub async fn send(config: &RwLock<Config>, tracker: &Tracker) {
+    let (api, heartbeats) = {
+        let config = config.read().unwrap();
+        if config.dry_run || config.local.is_some() {
+            return;
+        }
+        let Some(api) = config
+            .api
+            .clone()
+            .filter(|api| api.supports(shared::capability::HEARTBEAT))
+        else {
+            return;
+        };
+        let minimum_duration = Duration::from_secs(config.minimum_duration.into());
+        let heartbeats = tracker
+            .watches
+            .values()
+            .filter(|watch| watch.duration() >= minimum_duration)
+            .filter_map(|watch| heartbeat(watch, config.device_id))
+            .collect::<Vec<_>>();
+        (api, heartbeats)
+    };
+
+    for heartbeat in heartbeats {
+        match api.heartbeat(&heartbeat).await {
+            Ok(()) => debug!("Sent heartbeat for {}", heartbeat.display()),
+            Err(error) => warn!(
+                "Could not send heartbeat for {}: {}",
+                heartbeat.display(),
+                error
+            ),
+        }
+    }
+}
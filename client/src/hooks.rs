@@ -0,0 +1,132 @@
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+
+use log::{debug, error, warn};
+use serde::Deserialize;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::watch::Watch;
+
+/// Longest a hook may run before it is stopped, so that hung scripts don't
+/// pile up.
+const HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum HookEvent {
+    /// A watch started for a monitored process.
+    Start,
+
+    /// The last process of a session exited.
+    End,
+
+    /// The session was saved by the server or written to the local file.
+    Submitted,
+}
+
+impl HookEvent {
+    fn as_str(self) -> &'static str {
+        match self {
+            HookEvent::Start => "start",
+            HookEvent::End => "end",
+            HookEvent::Submitted => "submitted",
+        }
+    }
+}
+
+/// External command run when something happens to a session.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hook {
+    pub on: HookEvent,
+
+    /// Program to run, looked up from `PATH` unless it is a path.
+    pub command: PathBuf,
+
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// Session of a watch that just started.
+pub fn started(watch: &Watch) -> shared::Submission {
+    shared::Submission {
+        duration: shared::SessionDuration::from_secs(0),
+        executable: watch.executable.clone(),
+        name: watch.name.clone(),
+        product_version: watch.product_version.clone(),
+        file_version: watch.file_version.clone(),
+        started_at: Some(watch.started_at),
+        ended_at: None,
+        focused_duration: None,
+        tags: watch.tags.clone(),
+        window_title: None,
+        session_id: watch.session_id.clone(),
+        device_id: None,
+    }
+}
+
+/// Start the hooks of the event without waiting for them to finish. The
+/// session is given as JSON on standard input and its main fields as
+/// `BEELZEBUB_*` environment variables.
+pub fn run(hooks: &[Hook], event: HookEvent, session: &shared::Submission) {
+    let mut hooks = hooks.iter().filter(|hook| hook.on == event).peekable();
+    if hooks.peek().is_none() {
+        return;
+    }
+    let input = match serde_json::to_vec(session) {
+        Ok(input) => input,
+        Err(error) => {
+            error!("Could not serialise session for hooks: {}", error);
+            return;
+        }
+    };
+    for hook in hooks {
+        let mut command = Command::new(&hook.command);
+        command
+            .args(&hook.args)
+            .env("BEELZEBUB_EVENT", event.as_str())
+            .env("BEELZEBUB_EXECUTABLE", &session.executable)
+            .env(
+                "BEELZEBUB_NAME",
+                session.name.as_deref().unwrap_or_default(),
+            )
+            .env("BEELZEBUB_DURATION", session.duration.as_secs().to_string())
+            .env("BEELZEBUB_TAGS", session.tags.join(","))
+            .env(
+                "BEELZEBUB_SESSION_ID",
+                session.session_id.as_deref().unwrap_or_default(),
+            )
+            .stdin(Stdio::piped())
+            .kill_on_drop(true);
+        tokio::spawn(wait(hook.command.clone(), command, input.clone()));
+    }
+}
+
+async fn wait(name: PathBuf, mut command: Command, input: Vec<u8>) {
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(error) => {
+            warn!("Could not run hook {}: {}", name.display(), error);
+            return;
+        }
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // Hooks that don't read their input may exit before it is written.
+        let _ = stdin.write_all(&input).await;
+    }
+    match tokio::time::timeout(HOOK_TIMEOUT, child.wait()).await {
+        Ok(Ok(status)) if status.success() => debug!("Hook {} finished", name.display()),
+        Ok(Ok(status)) => warn!("Hook {} failed: {}", name.display(), status),
+        Ok(Err(error)) => warn!("Could not wait for hook {}: {}", name.display(), error),
+        Err(_) => {
+            warn!(
+                "Hook {} did not finish in {} seconds, stopping it",
+                name.display(),
+                HOOK_TIMEOUT.as_secs()
+            );
+            let _ = child.kill().await;
+        }
+    }
+}
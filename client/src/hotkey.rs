@@ -0,0 +1,110 @@
+use log::{debug, error};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use windows::Win32::UI::{
+    Input::KeyboardAndMouse::{
+        RegisterHotKey, HOT_KEY_MODIFIERS, MOD_ALT, MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT, MOD_WIN,
+    },
+    WindowsAndMessaging::{GetMessageW, MSG, WM_HOTKEY},
+};
+
+/// Identifier for the pause hotkey registration.
+const HOTKEY_ID: i32 = 1;
+
+/// Virtual-key code of F1. F2–F24 follow sequentially.
+const VK_F1: u32 = 0x70;
+
+#[derive(Debug)]
+pub struct Hotkey {
+    modifiers: HOT_KEY_MODIFIERS,
+    key: u32,
+}
+
+impl Hotkey {
+    /// Parse a hotkey description such as `Ctrl+Alt+B` or `Shift+F9`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut modifiers = MOD_NOREPEAT;
+        let mut key = None;
+        for part in value.split('+').map(str::trim) {
+            match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => modifiers |= MOD_CONTROL,
+                "alt" => modifiers |= MOD_ALT,
+                "shift" => modifiers |= MOD_SHIFT,
+                "win" | "super" => modifiers |= MOD_WIN,
+                _ if key.is_some() => return None,
+                _ => key = Some(parse_key(part)?),
+            }
+        }
+        Some(Self {
+            modifiers,
+            key: key?,
+        })
+    }
+}
+
+fn parse_key(value: &str) -> Option<u32> {
+    let mut chars = value.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_alphanumeric() {
+            return Some(c.to_ascii_uppercase() as u32);
+        }
+        return None;
+    }
+    let number = value.strip_prefix(['F', 'f'])?.parse::<u32>().ok()?;
+    if (1..=24).contains(&number) {
+        return Some(VK_F1 + number - 1);
+    }
+    None
+}
+
+/// Register the global hotkey and return a channel that receives a message
+/// every time it is pressed.
+///
+/// Hotkey messages are delivered to the thread that registered the hotkey, so
+/// the registration and the message loop live on a dedicated thread.
+pub fn listen(hotkey: Hotkey) -> UnboundedReceiver<()> {
+    let (sender, receiver) = unbounded_channel();
+    std::thread::spawn(move || {
+        let registered = unsafe { RegisterHotKey(None, HOTKEY_ID, hotkey.modifiers, hotkey.key) };
+        if let Err(error) = registered {
+            error!("Could not register pause hotkey: {}", error);
+            return;
+        }
+        debug!("Registered pause hotkey {:?}", hotkey);
+
+        let mut message = MSG::default();
+        while unsafe { GetMessageW(&mut message, None, 0, 0) }.as_bool() {
+            if message.message == WM_HOTKEY && sender.send(()).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_hotkey() {
+        let cases = [
+            ("Ctrl+Alt+B", MOD_CONTROL | MOD_ALT, 0x42),
+            ("shift + f9", MOD_SHIFT, 0x78),
+            ("Win+F24", MOD_WIN, 0x87),
+            ("Control+Super+1", MOD_CONTROL | MOD_WIN, 0x31),
+            ("F1", HOT_KEY_MODIFIERS(0), VK_F1),
+        ];
+        for (value, modifiers, key) in cases {
+            let hotkey = Hotkey::parse(value).unwrap();
+            assert_eq!(hotkey.modifiers, modifiers | MOD_NOREPEAT, "{}", value);
+            assert_eq!(hotkey.key, key, "{}", value);
+        }
+    }
+
+    #[test]
+    fn parse_invalid_hotkey() {
+        for value in ["", "Ctrl+Alt", "Ctrl+A+B", "F0", "F25", "Ctrl+Esc", "Alt+é"] {
+            assert!(Hotkey::parse(value).is_none(), "{}", value);
+        }
+    }
+}
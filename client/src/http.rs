@@ -0,0 +1,162 @@
+use std::path::Path;
+use std::time::Duration;
+
+use beelzebub_api::tonic::transport::{Channel, Endpoint};
+use hyper_tls::{native_tls, HttpsConnector};
+use hyper_util::client::legacy::connect::HttpConnector;
+use log::error;
+use reqwest::{
+    header::{HeaderMap, HeaderValue},
+    Certificate, Identity, Proxy,
+};
+use serde::Deserialize;
+
+use crate::config::Config;
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpConfig {
+    /// Seconds to wait for a connection to the server.
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
+
+    /// Seconds to wait for a whole request to complete.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+
+    /// Seconds between TCP keep-alive probes. Disabled if not set.
+    pub tcp_keepalive: Option<u64>,
+
+    /// Seconds to keep idle connections around for reuse.
+    #[serde(default = "default_pool_idle_timeout")]
+    pub pool_idle_timeout: u64,
+
+    /// Maximum number of idle connections to keep per host.
+    #[serde(default = "default_pool_max_idle_per_host")]
+    pub pool_max_idle_per_host: usize,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: default_connect_timeout(),
+            timeout: default_timeout(),
+            tcp_keepalive: None,
+            pool_idle_timeout: default_pool_idle_timeout(),
+            pool_max_idle_per_host: default_pool_max_idle_per_host(),
+        }
+    }
+}
+
+fn default_connect_timeout() -> u64 {
+    10
+}
+
+fn default_timeout() -> u64 {
+    30
+}
+
+fn default_pool_idle_timeout() -> u64 {
+    90
+}
+
+fn default_pool_max_idle_per_host() -> usize {
+    1
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, ()> {
+    std::fs::read(path).map_err(|error| error!("Could not read {}: {}", path.display(), error))
+}
+
+/// Headers sent with every request to the server.
+pub fn default_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        shared::CLIENT_VERSION_HEADER,
+        HeaderValue::from_static(env!("CARGO_PKG_VERSION")),
+    );
+    headers
+}
+
+/// Create the HTTP client used for all requests to the server.
+pub fn build_client(config: &Config) -> Result<reqwest::Client, ()> {
+    let http = &config.http;
+    let mut builder = reqwest::Client::builder()
+        .default_headers(default_headers())
+        .connect_timeout(Duration::from_secs(http.connect_timeout))
+        .timeout(Duration::from_secs(http.timeout))
+        .tcp_keepalive(http.tcp_keepalive.map(Duration::from_secs))
+        .pool_idle_timeout(Duration::from_secs(http.pool_idle_timeout))
+        .pool_max_idle_per_host(http.pool_max_idle_per_host);
+    if let Some(proxy_config) = &config.proxy {
+        let mut proxy = Proxy::all(&proxy_config.url)
+            .map_err(|error| error!("Invalid proxy {}: {}", proxy_config.url, error))?;
+        if let Some(username) = &proxy_config.username {
+            let password = proxy_config.password.as_deref().unwrap_or_default();
+            proxy = proxy.basic_auth(username, password);
+        }
+        builder = builder.proxy(proxy);
+    }
+    if let Some(tls) = &config.tls {
+        if let Some(ca) = &tls.ca {
+            let certificate = Certificate::from_pem(&read_file(ca)?)
+                .map_err(|error| error!("Invalid CA certificate {}: {}", ca.display(), error))?;
+            builder = builder.add_root_certificate(certificate);
+        }
+        match (&tls.certificate, &tls.key) {
+            (Some(certificate), Some(key)) => {
+                let identity = Identity::from_pkcs8_pem(&read_file(certificate)?, &read_file(key)?)
+                    .map_err(|error| error!("Invalid client certificate: {}", error))?;
+                builder = builder.identity(identity);
+            }
+            (None, None) => {}
+            _ => {
+                error!("Client certificate requires both a certificate and a key");
+                return Err(());
+            }
+        }
+    }
+    builder
+        .build()
+        .map_err(|error| error!("Could not create HTTP client: {}", error))
+}
+
+/// Create the channel for gRPC calls to the server at the URL. TLS is set up
+/// like for the HTTP client, and HTTP/2 negotiated over it. The channel
+/// connects on its first call.
+pub fn build_channel(config: &Config, url: &str) -> Result<Channel, ()> {
+    let http = &config.http;
+    let endpoint = Endpoint::from_shared(url.to_string())
+        .map_err(|error| error!("Invalid server URL {}: {}", url, error))?
+        .connect_timeout(Duration::from_secs(http.connect_timeout))
+        .tcp_keepalive(http.tcp_keepalive.map(Duration::from_secs));
+    let mut tls = native_tls::TlsConnector::builder();
+    tls.request_alpns(&["h2"]);
+    if let Some(tls_config) = &config.tls {
+        if let Some(ca) = &tls_config.ca {
+            let certificate = native_tls::Certificate::from_pem(&read_file(ca)?)
+                .map_err(|error| error!("Invalid CA certificate {}: {}", ca.display(), error))?;
+            tls.add_root_certificate(certificate);
+        }
+        match (&tls_config.certificate, &tls_config.key) {
+            (Some(certificate), Some(key)) => {
+                let identity =
+                    native_tls::Identity::from_pkcs8(&read_file(certificate)?, &read_file(key)?)
+                        .map_err(|error| error!("Invalid client certificate: {}", error))?;
+                tls.identity(identity);
+            }
+            (None, None) => {}
+            _ => {
+                error!("Client certificate requires both a certificate and a key");
+                return Err(());
+            }
+        }
+    }
+    let tls = tls
+        .build()
+        .map_err(|error| error!("Could not set up TLS: {}", error))?;
+    let mut connector = HttpConnector::new();
+    connector.enforce_http(false);
+    let connector = HttpsConnector::from((connector, tls.into()));
+    Ok(endpoint.connect_with_connector_lazy(connector))
+}
@@ -0,0 +1,210 @@
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use log::{error, info};
+use regex::Regex;
+use serde::Deserialize;
+use windows::Win32::System::Registry::HKEY_CURRENT_USER;
+
+use crate::config;
+use crate::win;
+
+/// Library paths in Steam's `libraryfolders.vdf`.
+static STEAM_LIBRARY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#""path"\s+"((?:[^"\\]|\\.)*)""#).unwrap());
+
+/// Installed game in the manifests of the Epic Games Launcher.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EpicManifest {
+    install_location: PathBuf,
+}
+
+/// Ask on the terminal, with the answer that an empty line stands for.
+fn ask(question: &str, default: &str) -> std::io::Result<String> {
+    if default.is_empty() {
+        print!("{}: ", question);
+    } else {
+        print!("{} [{}]: ", question, default);
+    }
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return Ok(default.to_string());
+    }
+    return Ok(answer.to_string());
+}
+
+fn confirm(question: &str) -> std::io::Result<bool> {
+    let answer = ask(&format!("{} (y/n)", question), "y")?;
+    return Ok(answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes"));
+}
+
+/// Value written as a YAML scalar, quoted if it needs to be.
+fn yaml_value(value: &str) -> String {
+    return serde_yaml::to_string(value)
+        .map(|yaml| yaml.trim_end().to_string())
+        .unwrap_or_else(|_| format!("{:?}", value));
+}
+
+/// `steamapps\common` of every Steam library.
+fn steam_directories() -> Vec<PathBuf> {
+    let Ok(Some(steam)) =
+        win::read_registry_string(HKEY_CURRENT_USER, "Software\\Valve\\Steam", "SteamPath")
+    else {
+        return Vec::new();
+    };
+    let steam = PathBuf::from(steam.replace('/', "\\"));
+    let libraries = std::fs::read_to_string(steam.join("steamapps").join("libraryfolders.vdf"))
+        .map(|libraries| {
+            STEAM_LIBRARY
+                .captures_iter(&libraries)
+                .map(|library| PathBuf::from(library[1].replace("\\\\", "\\")))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let libraries = if libraries.is_empty() {
+        vec![steam]
+    } else {
+        libraries
+    };
+    return libraries
+        .into_iter()
+        .map(|library| library.join("steamapps").join("common"))
+        .collect();
+}
+
+/// Directories that the Epic Games Launcher installed games into.
+fn epic_directories() -> Vec<PathBuf> {
+    let Some(program_data) = std::env::var_os("ProgramData") else {
+        return Vec::new();
+    };
+    let manifests = Path::new(&program_data).join("Epic\\EpicGamesLauncher\\Data\\Manifests");
+    let Ok(entries) = std::fs::read_dir(manifests) else {
+        return Vec::new();
+    };
+    return entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "item")
+        })
+        .filter_map(|path| std::fs::read(path).ok())
+        .filter_map(|manifest| serde_json::from_slice::<EpicManifest>(&manifest).ok())
+        .filter_map(|manifest| manifest.install_location.parent().map(Path::to_path_buf))
+        .collect();
+}
+
+/// Default game directory of GOG Galaxy.
+fn gog_directories() -> Vec<PathBuf> {
+    let Some(program_files) = std::env::var_os("ProgramFiles(x86)") else {
+        return Vec::new();
+    };
+    return vec![Path::new(&program_files).join("GOG Galaxy\\Games")];
+}
+
+/// Existing game directories of the launchers installed on this machine.
+fn discover() -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+    return steam_directories()
+        .into_iter()
+        .chain(epic_directories())
+        .chain(gog_directories())
+        .filter(|directory| directory.is_dir())
+        .filter(|directory| seen.insert(win::normalize_path(directory)))
+        .collect();
+}
+
+/// Configuration file with the server and the monitored directories.
+fn config_file(url: &str, secret: &str, monitors: &[PathBuf]) -> String {
+    let mut file =
+        String::from("# Beelzebub client configuration. The README describes every setting.\n");
+    file.push_str(&format!(
+        "url: {}  # Address of the Beelzebub server\n",
+        yaml_value(url)
+    ));
+    if secret.is_empty() {
+        file.push_str("# secret: Secret key of the server\n");
+    } else {
+        file.push_str(&format!(
+            "secret: {}  # Secret key of the server\n",
+            yaml_value(secret)
+        ));
+    }
+    if monitors.is_empty() {
+        file.push_str("monitor: []  # Directories whose games are tracked\n");
+    } else {
+        file.push_str("monitor:  # Directories whose games are tracked\n");
+        for monitor in monitors {
+            file.push_str(&format!("  - {}\n", yaml_value(&monitor.to_string_lossy())));
+        }
+    }
+    return file;
+}
+
+fn questions() -> std::io::Result<(String, String, Vec<PathBuf>)> {
+    let url = ask("Server URL", "http://localhost:8080")?;
+    let secret = ask("Secret key of the server", "")?;
+    let mut monitors = Vec::new();
+    if confirm("Look for the game directories of Steam, Epic Games and GOG?")? {
+        let discovered = discover();
+        if discovered.is_empty() {
+            println!("No game directories found");
+        }
+        for directory in discovered {
+            if confirm(&format!("Track games in {}?", directory.display()))? {
+                monitors.push(directory);
+            }
+        }
+    }
+    loop {
+        let directory = ask("Other directory with games, empty when done", "")?;
+        if directory.is_empty() {
+            break;
+        }
+        if !Path::new(&directory).is_dir() {
+            println!("{} is not a directory", directory);
+            continue;
+        }
+        monitors.push(PathBuf::from(directory));
+    }
+    return Ok((url, secret, monitors));
+}
+
+/// Ask for the server and the directories to monitor and write them to the
+/// configuration file.
+pub fn run(force: bool) -> Result<(), ()> {
+    let config_path =
+        config::Config::get_path().map_err(|_| error!("Could not determine configuration path"))?;
+    if config_path.exists() && !force {
+        error!(
+            "{} already exists, pass --force to overwrite it",
+            config_path.display()
+        );
+        return Err(());
+    }
+    let (url, secret, monitors) =
+        questions().map_err(|error| error!("Could not read the answers: {}", error))?;
+
+    if let Some(directory) = config_path.parent() {
+        std::fs::create_dir_all(directory)
+            .map_err(|error| error!("Could not create {}: {}", directory.display(), error))?;
+    }
+    std::fs::write(&config_path, config_file(&url, &secret, &monitors))
+        .map_err(|error| error!("Could not write {}: {}", config_path.display(), error))?;
+    info!("Wrote configuration to {}", config_path.display());
+    Ok(())
+}
+
+/// Point to `init` if loading failed because there is no configuration yet.
+pub fn suggest(error: &config::Error) {
+    if matches!(error, config::Error::IOError(error) if error.kind() == std::io::ErrorKind::NotFound)
+    {
+        info!("Run `beelzebub-client init` to create a configuration");
+    }
+}
@@ -0,0 +1,132 @@
+use std::fs;
+use std::path::PathBuf;
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::files;
+use crate::watch::{ProcessWatchMap, Watch};
+
+/// Snapshot of an active watch, written so that sessions survive the client
+/// or the whole system crashing.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEntry {
+    pub process_ids: Vec<u32>,
+    pub executable: String,
+
+    /// Path of the executable. Journals of earlier versions don't have it.
+    #[serde(default)]
+    pub path: Option<String>,
+    pub name: Option<String>,
+    pub product_version: Option<String>,
+    pub file_version: Option<String>,
+    pub tags: Vec<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+
+    /// Session duration in seconds when the entry was written.
+    pub duration: u64,
+    pub focused_duration: u64,
+
+    /// When the entry was written, i.e. when the session was last known to
+    /// be running.
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+impl From<&Watch> for JournalEntry {
+    fn from(watch: &Watch) -> Self {
+        let mut process_ids = watch.process_ids.iter().copied().collect::<Vec<u32>>();
+        process_ids.sort();
+        Self {
+            process_ids,
+            executable: watch.executable.clone(),
+            path: watch.path.clone(),
+            name: watch.name.clone(),
+            product_version: watch.product_version.clone(),
+            file_version: watch.file_version.clone(),
+            tags: watch.tags.clone(),
+            started_at: watch.started_at,
+            duration: watch.duration().as_secs(),
+            focused_duration: watch.focused.as_secs(),
+            updated_at: OffsetDateTime::now_utc(),
+            session_id: watch.session_id.clone(),
+        }
+    }
+}
+
+impl JournalEntry {
+    /// Best-effort submission for a session that ended while the client
+    /// wasn't running.
+    pub fn into_submission(self) -> shared::Submission {
+        shared::Submission {
+            duration: shared::SessionDuration::from_secs(self.duration),
+            executable: self.executable,
+            name: self.name,
+            product_version: self.product_version,
+            file_version: self.file_version,
+            started_at: Some(self.started_at),
+            ended_at: Some(self.updated_at),
+            focused_duration: Some(shared::SessionDuration::from_secs(self.focused_duration)),
+            tags: self.tags,
+            window_title: None,
+            session_id: self.session_id,
+            device_id: None,
+        }
+    }
+}
+
+/// File that holds the active watches.
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    /// Journal in the data directory, if one can be determined.
+    pub fn open() -> Option<Self> {
+        Some(Self {
+            path: files::data_path("watches.json")?,
+        })
+    }
+
+    /// Entries left over from the previous run.
+    pub fn read(&self) -> Vec<JournalEntry> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+            Err(error) => {
+                warn!("Could not read journal {}: {}", self.path.display(), error);
+                return Vec::new();
+            }
+        };
+        match serde_json::from_slice(&data) {
+            Ok(entries) => entries,
+            Err(error) => {
+                warn!("Could not parse journal {}: {}", self.path.display(), error);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Replace the journal with the current watches, keeping the previous
+    /// state if writing fails.
+    pub fn write(&self, watches: &ProcessWatchMap) {
+        let entries = watches.values().map(JournalEntry::from).collect::<Vec<_>>();
+        let data = match serde_json::to_vec(&entries) {
+            Ok(data) => data,
+            Err(error) => {
+                warn!("Could not serialise journal: {}", error);
+                return;
+            }
+        };
+        match files::write_atomically(&self.path, &data) {
+            Ok(()) => debug!("Wrote {} watches to the journal", entries.len()),
+            Err(error) => warn!("Could not write journal {}: {}", self.path.display(), error),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use log::{error, info};
+use serde::Deserialize;
+use time::format_description::well_known::Rfc3339;
+
+use crate::files;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LocalFormat {
+    Csv,
+    #[default]
+    Jsonl,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalConfig {
+    #[serde(default)]
+    pub format: LocalFormat,
+
+    /// File to append sessions to. Defaults to `sessions.jsonl` or
+    /// `sessions.csv` in the data directory.
+    pub path: Option<PathBuf>,
+}
+
+impl LocalConfig {
+    pub fn get_path(&self) -> Option<PathBuf> {
+        if let Some(path) = &self.path {
+            return Some(path.clone());
+        }
+        let filename = match self.format {
+            LocalFormat::Csv => "sessions.csv",
+            LocalFormat::Jsonl => "sessions.jsonl",
+        };
+        files::data_path(filename)
+    }
+}
+
+fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        return format!("\"{}\"", value.replace('"', "\"\""));
+    }
+    value.to_string()
+}
+
+fn format_timestamp(timestamp: Option<time::OffsetDateTime>) -> String {
+    timestamp
+        .and_then(|timestamp| timestamp.format(&Rfc3339).ok())
+        .unwrap_or_default()
+}
+
+fn format_line(
+    format: LocalFormat,
+    submission: &shared::Submission,
+) -> Result<String, serde_json::Error> {
+    match format {
+        LocalFormat::Csv => Ok(format!(
+            "{},{},{},{},{}\n",
+            format_timestamp(submission.started_at),
+            format_timestamp(submission.ended_at),
+            submission.duration.as_secs(),
+            escape_csv(&submission.executable),
+            escape_csv(submission.name.as_deref().unwrap_or_default()),
+        )),
+        LocalFormat::Jsonl => Ok(serde_json::to_string(submission)? + "\n"),
+    }
+}
+
+/// Append a finished session to the local sessions file.
+pub fn append(config: &LocalConfig, submission: &shared::Submission) -> Result<(), ()> {
+    let Some(path) = config.get_path() else {
+        error!("Could not determine path for the sessions file");
+        return Err(());
+    };
+    let Ok(line) = format_line(config.format, submission) else {
+        error!("Could not serialise session {}", submission.display());
+        return Err(());
+    };
+
+    if let Some(parent) = path.parent() {
+        if let Err(error) = fs::create_dir_all(parent) {
+            error!("Could not create directory {}: {}", parent.display(), error);
+            return Err(());
+        }
+    }
+    let mut file = match OpenOptions::new().create(true).append(true).open(&path) {
+        Ok(file) => file,
+        Err(error) => {
+            error!("Could not open {}: {}", path.display(), error);
+            return Err(());
+        }
+    };
+    let is_empty = file.metadata().map(|m| m.len() == 0).unwrap_or(false);
+    if config.format == LocalFormat::Csv && is_empty {
+        let _ = file.write_all(b"started_at,ended_at,duration,executable,name\n");
+    }
+    match file.write_all(line.as_bytes()) {
+        Ok(()) => {
+            info!("Session written to {}", path.display());
+            Ok(())
+        }
+        Err(error) => {
+            error!("Could not write session to {}: {}", path.display(), error);
+            Err(())
+        }
+    }
+}
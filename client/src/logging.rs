@@ -0,0 +1,281 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use log::kv::{Key, Value, VisitSource};
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use serde::Deserialize;
+use simple_logger::SimpleLogger;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::win::EventLog;
+
+/// Source name that entries are written under in the Windows event log.
+const EVENT_LOG_SOURCE: &str = "Beelzebub";
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+
+    /// One JSON object per line, with the key-values of the record as fields.
+    Json,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LogFileConfig {
+    pub path: PathBuf,
+
+    #[serde(default = "default_log_file_level")]
+    pub level: LevelFilter,
+
+    /// Size in bytes after which the log file is rotated.
+    #[serde(default = "default_log_file_max_size")]
+    pub max_size: u64,
+
+    /// Number of rotated log files to keep around.
+    #[serde(default = "default_log_file_rotations")]
+    pub rotations: u32,
+}
+
+fn default_log_file_level() -> LevelFilter {
+    LevelFilter::Info
+}
+
+fn default_log_file_max_size() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_log_file_rotations() -> u32 {
+    3
+}
+
+/// Log file that is rotated once it grows past the configured size.
+struct RotatingFile {
+    config: LogFileConfig,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn open(config: LogFileConfig) -> std::io::Result<Self> {
+        if let Some(parent) = config.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { config, file, size })
+    }
+
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.config.max_size {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shift `client.log.1` to `client.log.2` and so on, dropping the oldest
+    /// file, and start writing to an empty file.
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let path = &self.config.path;
+        if self.config.rotations > 0 {
+            let _ = fs::remove_file(rotated_path(path, self.config.rotations));
+            for index in (1..self.config.rotations).rev() {
+                let _ = fs::rename(rotated_path(path, index), rotated_path(path, index + 1));
+            }
+            fs::rename(path, rotated_path(path, 1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn rotated_path(path: &Path, index: u32) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(format!(".{}", index));
+    PathBuf::from(rotated)
+}
+
+/// Logs to stdout using SimpleLogger and optionally to a log file and the
+/// Windows event log.
+struct Logger {
+    stdout: SimpleLogger,
+    stdout_level: LevelFilter,
+    file: Mutex<Option<RotatingFile>>,
+
+    /// Event source for warnings and errors, if enabled.
+    event_log: Mutex<Option<EventLog>>,
+
+    /// Write JSON lines instead of text.
+    json: AtomicBool,
+}
+
+/// Collects the key-values of a record into a JSON object.
+struct JsonFields(serde_json::Map<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), log::kv::Error> {
+        let value = serde_json::to_value(value).unwrap_or(serde_json::Value::Null);
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+fn timestamp() -> String {
+    OffsetDateTime::now_local()
+        .unwrap_or_else(|_| OffsetDateTime::now_utc())
+        .format(&Rfc3339)
+        .unwrap_or_default()
+}
+
+fn format_json(record: &Record) -> String {
+    let mut fields = JsonFields(serde_json::Map::new());
+    let _ = record.key_values().visit(&mut fields);
+    let line = serde_json::json!({
+        "timestamp": timestamp(),
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": record.args().to_string(),
+        "fields": fields.0,
+    });
+    line.to_string() + "\n"
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        let json = self.json.load(Ordering::Relaxed);
+        let json_line = json.then(|| format_json(record));
+        if self.stdout.enabled(record.metadata()) {
+            match &json_line {
+                Some(line) => print!("{}", line),
+                None => self.stdout.log(record),
+            }
+        }
+
+        if record.level() <= LevelFilter::Warn {
+            if let Some(event_log) = self.event_log.lock().unwrap().as_ref() {
+                let message = format!("[{}] {}", record.target(), record.args());
+                if let Err(error) = event_log.report(record.level(), &message) {
+                    eprintln!("Could not write to event log: {}", error);
+                }
+            }
+        }
+
+        let mut file = self.file.lock().unwrap();
+        let Some(file) = file.as_mut() else {
+            return;
+        };
+        if record.level() > file.config.level {
+            return;
+        }
+        let line = json_line.unwrap_or_else(|| {
+            format!(
+                "{} {:<5} [{}] {}\n",
+                timestamp(),
+                record.level(),
+                record.target(),
+                record.args()
+            )
+        });
+        if let Err(error) = file.write_line(&line) {
+            eprintln!("Could not write to log file: {}", error);
+        }
+    }
+
+    fn flush(&self) {
+        self.stdout.flush();
+        if let Some(file) = self.file.lock().unwrap().as_mut() {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+/// Install the logger. Only stdout logging is active until `configure` is called.
+pub fn init() -> Result<(), SetLoggerError> {
+    let stdout = SimpleLogger::new().with_level(LevelFilter::Info).env();
+    let stdout_level = stdout.max_level();
+    let logger = LOGGER.get_or_init(|| Logger {
+        stdout,
+        stdout_level,
+        file: Mutex::new(None),
+        event_log: Mutex::new(None),
+        json: AtomicBool::new(false),
+    });
+    log::set_logger(logger)?;
+    log::set_max_level(stdout_level);
+    Ok(())
+}
+
+/// Apply the log format, log file and event log settings from the configuration.
+pub fn configure(config: &Option<LogFileConfig>, event_log: bool, format: LogFormat) {
+    let Some(logger) = LOGGER.get() else {
+        return;
+    };
+    logger
+        .json
+        .store(format == LogFormat::Json, Ordering::Relaxed);
+    configure_event_log(logger, event_log);
+
+    let mut file = logger.file.lock().unwrap();
+    if file.as_ref().map(|file| &file.config) == config.as_ref() {
+        return;
+    }
+
+    *file = match config {
+        Some(config) => match RotatingFile::open(config.clone()) {
+            Ok(rotating_file) => Some(rotating_file),
+            Err(error) => {
+                eprintln!(
+                    "Could not open log file {}: {}",
+                    config.path.display(),
+                    error
+                );
+                None
+            }
+        },
+        None => None,
+    };
+    let file_level = file
+        .as_ref()
+        .map(|file| file.config.level)
+        .unwrap_or(LevelFilter::Off);
+    log::set_max_level(logger.stdout_level.max(file_level));
+}
+
+fn configure_event_log(logger: &Logger, enabled: bool) {
+    let mut event_log = logger.event_log.lock().unwrap();
+    if event_log.is_some() == enabled {
+        return;
+    }
+    if !enabled {
+        *event_log = None;
+        return;
+    }
+    *event_log = match EventLog::register(EVENT_LOG_SOURCE) {
+        Ok(event_log) => Some(event_log),
+        Err(error) => {
+            eprintln!("Could not register event log source: {}", error);
+            None
+        }
+    };
+}
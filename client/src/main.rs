@@ -1,42 +1,118 @@
 use futures::StreamExt;
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
-use std::time::Instant;
+use std::time::Duration;
 
-use log::{debug, error, info, warn, LevelFilter};
+use clap::{Parser, Subcommand};
+use log::{debug, error, info, warn};
 use notify::Watcher;
-use reqwest::{StatusCode, Url};
-use simple_logger::SimpleLogger;
+use time::OffsetDateTime;
+use tokio::sync::mpsc::{self, unbounded_channel, UnboundedReceiver};
 
+mod api;
+mod autostart;
 mod config;
+mod defer;
+mod device;
+mod discord;
+mod doctor;
+mod files;
+mod heartbeat;
+mod hooks;
+mod hotkey;
+mod http;
+mod init;
+mod journal;
+mod local;
+mod logging;
+mod naming;
+mod source;
+mod stats;
+mod status;
+mod toast;
+mod watch;
 mod win;
+// Only processes on Linux run under Wine, and there is no Linux process source
+// yet.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+mod wine;
 
-type ProcessWatchMap = HashMap<u32, Watch>;
+use hooks::HookEvent;
+use source::{ProcessEvent, ProcessEventSource};
+use watch::{Tracker, Watch, FOCUS_POLL_INTERVAL};
 
-struct Watch {
-    start: Instant,
-    executable: String,
-    name: Option<String>,
+/// How often the journal is rewritten to keep durations in it current.
+const JOURNAL_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Log sessions instead of submitting them.
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Run even if another instance of the client is already running.
+    #[arg(long)]
+    force: bool,
+
+    /// Replay the process events of a script file instead of watching the
+    /// processes that actually run.
+    #[arg(long, value_name = "EVENTS")]
+    simulate: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-impl Watch {
-    fn new(process: win::Process) -> (u32, Self) {
-        let name = process.get_display_name();
-        (
-            process.process_id,
-            Self {
-                start: Instant::now(),
-                executable: process.name,
-                name: name,
-            },
-        )
-    }
+#[derive(Subcommand)]
+enum Command {
+    /// Start the client automatically when logging on to Windows.
+    Autostart {
+        #[command(subcommand)]
+        action: AutostartAction,
+    },
+
+    /// Check that everything the client needs works and tell what to fix.
+    Doctor,
+
+    /// Write a configuration file from answers to a few questions.
+    Init {
+        /// Overwrite the configuration file if it already exists.
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Show playtime per game from the server.
+    Stats {
+        /// Only count sessions from this far back, e.g. `12h`, `7d` or `2w`.
+        #[arg(long, value_parser = stats::parse_since)]
+        since: Option<Duration>,
+
+        /// Only show games whose executable or name contains this text.
+        #[arg(long)]
+        process: Option<String>,
+    },
 }
 
+#[derive(Subcommand)]
+enum AutostartAction {
+    /// Register the client in the per-user Run key.
+    Enable,
+    /// Remove the client from the per-user Run key.
+    Disable,
+}
+
+/// Name of the mutex that prevents running several clients at once. Local to
+/// the logon session, so each user can run their own client.
+const INSTANCE_MUTEX: &str = "Local\\BeelzebubClient";
+
+/// How long to wait for configuration file changes to settle before reloading.
+const CONFIG_DEBOUNCE: Duration = Duration::from_millis(500);
+
 async fn handle_process_start(
     config: &RwLock<config::Config>,
-    map: &mut ProcessWatchMap,
+    tracker: &mut Tracker,
     event: win::ProcessStartResult,
 ) {
     let event = match event {
@@ -47,41 +123,95 @@ async fn handle_process_start(
         }
     };
 
-    // Processes with no reported path are probably system stuff and not worth to track.
-    let Some(executable_path) = &event.target_instance.executable_path else {
+    if tracker.paused {
         debug!(
-            "Process {} ({}) does not have a path",
+            "Process {} ({}) ignored while tracking is paused",
             event.target_instance.name, event.target_instance.process_id
         );
         return;
-    };
+    }
 
-    let path = Path::new(&executable_path);
     let config = config.read().unwrap();
-    if !config.is_monitored(path) {
+    let process = &event.target_instance;
+    // Store apps live in WindowsApps and are matched by their package
+    // identity instead, so only look it up if it can match something.
+    let package = if config.has_package_monitors() {
+        process.get_package_info()
+    } else {
+        None
+    };
+    if let Some(package) = &package {
         debug!(
-            "Process {} ({}) isn't configured for watching",
-            event.target_instance.name, event.target_instance.process_id
+            "Process {} ({}) is packaged as {}",
+            process.name,
+            process.process_id,
+            package
+                .app_user_model_id
+                .as_ref()
+                .unwrap_or(&package.family_name)
         );
-        return;
     }
+    let path_monitor = process
+        .executable_path
+        .as_deref()
+        .and_then(|path| config.get_monitor(Path::new(path)));
+    let package_monitor = package
+        .as_ref()
+        .and_then(|package| config.get_package_monitor(&package.family_name));
+    let tags = match path_monitor.or(package_monitor) {
+        Some(monitor) => monitor.tags.clone(),
+        // Processes with no reported path are probably system stuff and not
+        // worth to track, unless they have been explicitly listed by name.
+        None if process.executable_path.is_none() && config.is_tracked_by_name(&process.name) => {
+            Vec::new()
+        }
+        None if process.executable_path.is_none() => {
+            debug!(
+                "Process {} ({}) does not have a path",
+                process.name, process.process_id
+            );
+            return;
+        }
+        None => {
+            debug!(
+                "Process {} ({}) isn't configured for watching",
+                process.name, process.process_id
+            );
+            return;
+        }
+    };
 
     // TODO: Limit tracking based on parent processes?
 
-    let (pid, watch) = Watch::new(event.target_instance);
+    // Games that run several processes of the same executable are counted
+    // as a single session lasting from the first start to the last exit.
+    if tracker.join(&event.target_instance) {
+        debug!(
+            "Process {} ({}) joined an existing watch",
+            event.target_instance.name, event.target_instance.process_id
+        );
+        return;
+    }
+
+    let pid = event.target_instance.process_id;
+    let watch = Watch::new(event.target_instance, package, &config, tags);
+    hooks::run(&config.hooks, HookEvent::Start, &hooks::started(&watch));
     let product_name_display = watch.name.clone();
     info!(
+        pid = pid,
+        executable = watch.executable.as_str();
         "Starting watch for {} ({} {})",
         product_name_display.unwrap_or("?".to_string()),
         pid,
         watch.executable,
     );
-    map.insert(pid, watch);
+    tracker.insert(watch);
 }
 
 async fn handle_process_end(
+    cli: &Cli,
     config: &RwLock<config::Config>,
-    map: &mut ProcessWatchMap,
+    tracker: &mut Tracker,
     event: win::ProcessEndResult,
 ) {
     let event = match event {
@@ -91,123 +221,654 @@ async fn handle_process_end(
             return;
         }
     };
-    let Some(watch) = map.remove(&event.target_instance.process_id) else {
+    let Some(watch) = tracker.remove(event.target_instance.process_id) else {
         return;
     };
 
-    let duration_seconds = watch.start.elapsed().as_secs();
+    let duration_seconds = watch.duration().as_secs();
     info!(
-        "Process {} ({}) ran for {} seconds",
+        executable = watch.executable.as_str(),
+        duration = duration_seconds,
+        focused_duration = watch.focused.as_secs();
+        "Process {} ({}) ran for {} seconds, {} in the foreground",
         watch.name.as_ref().unwrap_or(&String::from("?")),
         &watch.executable,
-        duration_seconds
+        duration_seconds,
+        watch.focused.as_secs()
     );
 
-    let config = config.read().unwrap();
-    let minimum_duration = config.minimum_duration;
-    if duration_seconds < minimum_duration.into() {
-        info!(
-            "Skipping submission: doesn't meet minimum duration of {} seconds",
-            minimum_duration
-        );
-        return;
-    }
-
+    let window_title = watch.window_title();
     let submission = shared::Submission {
-        duration: duration_seconds,
+        duration: watch.duration().into(),
         executable: watch.executable,
         name: watch.name,
+        product_version: watch.product_version,
+        file_version: watch.file_version,
+        started_at: Some(watch.started_at),
+        ended_at: Some(OffsetDateTime::now_utc()),
+        focused_duration: Some(watch.focused.into()),
+        tags: watch.tags,
+        window_title,
+        session_id: watch.session_id,
+        device_id: None,
     };
-    submit(&config, submission).await;
+    hooks::run(&config.read().unwrap().hooks, HookEvent::End, &submission);
+    record_session(cli, config, tracker, submission).await;
 }
 
-async fn submit(config: &config::Config, submission: shared::Submission) {
-    // TODO: Check/make the URL when the configuration is parsed.
-    let Ok(url) = Url::parse(&config.url).and_then(|u| u.join("/submit")) else {
-        error!("Could not parse URL {}", &config.url);
-        return;
+/// Submit or store a finished session.
+async fn record_session(
+    cli: &Cli,
+    config: &RwLock<config::Config>,
+    tracker: &mut Tracker,
+    mut submission: shared::Submission,
+) {
+    // The configuration can't stay locked while the session is sent, so
+    // what sending needs is taken out of it first.
+    let (recorded, api, hooks, can_defer, notifications) = {
+        let config = config.read().unwrap();
+        let minimum_duration = config.minimum_duration;
+        if submission.duration.as_secs() < minimum_duration.into() {
+            info!(
+                "Skipping submission: doesn't meet minimum duration of {} seconds",
+                minimum_duration
+            );
+            return;
+        }
+
+        // Texts are shortened to the default limits so that a long window
+        // title doesn't lose the session. Whether the session is acceptable
+        // is for the server to decide, as its limits can differ.
+        submission.repair(&shared::SubmissionLimits::default());
+        submission.device_id = config.device_id;
+
+        if cli.dry_run || config.dry_run {
+            info!("Dry run, not submitting {}", submission.display());
+            debug!("Submission: {:?}", submission);
+            return;
+        }
+
+        // Whether the session was recorded for good, unlike deferred ones.
+        let recorded = match &config.local {
+            Some(local) => Some(local::append(local, &submission).map(|()| true)),
+            None => config
+                .defer
+                .as_ref()
+                .and_then(defer::DeferConfig::reason)
+                .map(|reason| defer_submission(&submission, reason).map(|()| false)),
+        };
+        (
+            recorded,
+            config.api.clone(),
+            config.hooks.clone(),
+            config.defer.is_some(),
+            config.notifications,
+        )
     };
+    let result = match recorded {
+        Some(result) => result,
+        None => match submit(api.as_ref(), &submission).await {
+            Ok(()) => Ok(true),
+            Err(SubmitError::Retryable) if can_defer => {
+                let _ = defer_submission(&submission, "the server is unavailable");
+                Err(())
+            }
+            Err(_) => Err(()),
+        },
+    };
+    if result == Ok(true) {
+        hooks::run(&hooks, HookEvent::Submitted, &submission);
+    }
+    tracker.last_submission = Some(status::SubmissionStatus {
+        at: OffsetDateTime::now_utc(),
+        session: submission.display(),
+        success: result.is_ok(),
+    });
+    if notifications {
+        let name = submission.name.as_ref().unwrap_or(&submission.executable);
+        match result {
+            Ok(true) => toast::session_recorded(name, submission.duration.as_secs()),
+            Ok(false) => toast::session_deferred(name, submission.duration.as_secs()),
+            Err(()) => toast::submission_failed(name, submission.duration.as_secs()),
+        }
+    }
+}
+
+/// Put the submission in the queue to be sent later.
+fn defer_submission(submission: &shared::Submission, reason: &str) -> Result<(), ()> {
+    let queue = defer::Queue::open().ok_or_else(|| {
+        error!("Could not determine the location of the submission queue");
+    })?;
+    queue.push(submission.clone())?;
+    info!(
+        "Deferred submission of {} while {}",
+        submission.display(),
+        reason
+    );
+    return Ok(());
+}
 
-    let client = reqwest::Client::new();
-    let mut request = client.post(url).json(&submission);
-    if let Some(secret) = &config.secret {
-        request = request.header("X-Secret-Key", secret);
+/// Send the deferred submissions unless they still need to be held. Ones
+/// that fail to submit stay in the queue, unless the server rejects them for
+/// good.
+async fn flush_deferred(config: &RwLock<config::Config>) {
+    // Taken out of the configuration, which can't stay locked while the
+    // submissions are sent.
+    let (api, hooks) = {
+        let config = config.read().unwrap();
+        let Some(defer) = &config.defer else {
+            return;
+        };
+        if let Some(reason) = defer.reason() {
+            debug!("Holding deferred submissions while {}", reason);
+            return;
+        }
+        (config.api.clone(), config.hooks.clone())
+    };
+    let Some(queue) = defer::Queue::open() else {
+        return;
+    };
+    let Ok(submissions) = queue.read() else {
+        return;
+    };
+    if submissions.is_empty() {
+        return;
     }
-    match request.send().await {
-        Ok(response) => {
-            let status_code = response.status();
-            match status_code {
-                StatusCode::CREATED => info!("Event submitted to the server"),
-                StatusCode::INTERNAL_SERVER_ERROR => {
-                    info!("Error submitting event: unknown server error.")
-                }
-                StatusCode::UNAUTHORIZED => error!(
-                    "Error submitting event: unauthorized. Double check secret key settings."
-                ),
-                _ => warn!("Unknown response from the server: {}", status_code),
+    info!("Sending {} deferred submissions", submissions.len());
+    let mut remaining = Vec::new();
+    for submission in submissions {
+        match submit(api.as_ref(), &submission).await {
+            Ok(()) => hooks::run(&hooks, HookEvent::Submitted, &submission),
+            Err(SubmitError::Permanent) => {
+                warn!("Dropping deferred submission of {}", submission.display());
             }
+            Err(SubmitError::Retryable) => remaining.push(submission),
         }
+    }
+    let _ = queue.write(&remaining);
+}
+
+/// Pick up the watches that were active when the client last stopped.
+///
+/// Sessions whose processes are still running are continued, and the rest
+/// are submitted with the duration last written to the journal.
+async fn recover_journal(cli: &Cli, config: &RwLock<config::Config>, tracker: &mut Tracker) {
+    let Some(entries) = tracker.journal.as_ref().map(journal::Journal::read) else {
+        return;
+    };
+    for entry in entries {
+        let running = entry
+            .process_ids
+            .iter()
+            .copied()
+            .filter(|process_id| win::is_running(*process_id, &entry.executable))
+            .collect::<HashSet<u32>>();
+        if running.is_empty() {
+            info!(
+                "Recovered session of {} that ended while the client was not running",
+                entry.executable
+            );
+            record_session(cli, config, tracker, entry.into_submission()).await;
+        } else {
+            info!("Continuing watch for {} from the journal", entry.executable);
+            tracker.insert(Watch::restore(entry, running));
+        }
+    }
+    tracker.save_journal();
+}
+
+async fn handle_event(
+    cli: &Cli,
+    config: &RwLock<config::Config>,
+    tracker: &mut Tracker,
+    event: ProcessEvent,
+) {
+    match event {
+        ProcessEvent::Start(event) => handle_process_start(config, tracker, event).await,
+        ProcessEvent::End(event) => handle_process_end(cli, config, tracker, event).await,
+        ProcessEvent::Power(event) => handle_power_event(tracker, event),
+    }
+}
+
+fn handle_power_event(tracker: &mut Tracker, event: win::PowerEventResult) {
+    let event = match event {
+        Ok(event) => event,
         Err(error) => {
-            error!("Could not submit event to server: {}", error);
+            warn!("Could not process power event: {:?}", error);
             return;
         }
     };
+
+    // Windows may report the resume more than once, so only act on changes.
+    if event.is_suspend() && !tracker.suspended {
+        info!(
+            "System is suspending, pausing {} watches",
+            tracker.watches.len()
+        );
+        tracker.update(|tracker| tracker.suspended = true);
+    } else if event.is_resume() && tracker.suspended {
+        info!("System resumed, continuing watches");
+        tracker.update(|tracker| tracker.suspended = false);
+    }
+}
+
+fn toggle_pause(tracker: &mut Tracker) {
+    tracker.update(|tracker| tracker.paused = !tracker.paused);
+    if tracker.paused {
+        info!("Tracking paused");
+    } else {
+        info!("Tracking resumed");
+    }
+}
+
+/// Why a submission did not reach the server.
+enum SubmitError {
+    /// Sending the same submission again later may work.
+    Retryable,
+
+    /// The server rejected the submission itself and would do so again.
+    Permanent,
+}
+
+async fn submit(
+    api: Option<&beelzebub_api::Client>,
+    submission: &shared::Submission,
+) -> Result<(), SubmitError> {
+    let Some(api) = api else {
+        error!("Could not submit event: no server URL configured");
+        return Err(SubmitError::Retryable);
+    };
+    let error = match api.submit(submission).await {
+        Ok(response) if response.status == shared::SubmissionResponseStatus::Duplicate => {
+            info!("Event was already submitted to the server");
+            return Ok(());
+        }
+        Ok(_) => {
+            info!("Event submitted to the server");
+            return Ok(());
+        }
+        Err(error) => error,
+    };
+    match &error {
+        beelzebub_api::Error::Rejected { response, .. } => match response.status {
+            shared::SubmissionResponseStatus::DatabaseError => {
+                info!("Error submitting event: the server could not save it.")
+            }
+            shared::SubmissionResponseStatus::Unauthenticated => {
+                error!("Error submitting event: unauthorized. Double check secret key settings.")
+            }
+            shared::SubmissionResponseStatus::UpgradeRequired => {
+                error!("Error submitting event: the server requires a newer client version.")
+            }
+            _ => error!(
+                "Error submitting event: the server rejected it: {}",
+                response.message.as_deref().unwrap_or("no reason given")
+            ),
+        },
+        beelzebub_api::Error::Status {
+            status, request_id, ..
+        } => {
+            warn!(
+                "Unknown response from the server: {} (request {})",
+                status,
+                request_id.as_deref().unwrap_or("unknown")
+            );
+            return Err(SubmitError::Retryable);
+        }
+        beelzebub_api::Error::Http(error) => {
+            error!("Could not submit event to server: {}", error);
+            return Err(SubmitError::Retryable);
+        }
+        error => {
+            error!("Could not submit event: {}", error);
+            return Err(SubmitError::Retryable);
+        }
+    }
+    info!(
+        "Server logged the submission as request {}",
+        error.request_id().unwrap_or("unknown")
+    );
+    if error.is_permanent() {
+        Err(SubmitError::Permanent)
+    } else {
+        Err(SubmitError::Retryable)
+    }
+}
+
+/// Fetch the centrally managed settings from the server and apply them.
+async fn pull_settings(config: &RwLock<config::Config>) {
+    let Some(api) = config.read().unwrap().api.clone() else {
+        error!("Could not fetch settings: no server URL configured");
+        return;
+    };
+    match api.client_settings().await {
+        Ok(settings) => {
+            config.write().unwrap().apply_pulled_settings(settings);
+            info!("Applied settings from the server");
+        }
+        Err(beelzebub_api::Error::UpgradeRequired) => {
+            error!("Could not fetch settings: the server requires a newer client version")
+        }
+        Err(
+            error @ (beelzebub_api::Error::InvalidUrl(_) | beelzebub_api::Error::Incompatible(_)),
+        ) => error!("Could not fetch settings: {}", error),
+        Err(beelzebub_api::Error::Decode(error)) => {
+            warn!("Could not parse settings from the server: {}", error)
+        }
+        Err(error) => warn!(
+            "Could not fetch settings, keeping current settings: {}",
+            error
+        ),
+    }
+}
+
+/// Watch the configuration file for changes and reload it.
+///
+/// The parent directory is watched instead of the file itself, since editors
+/// that save by replacing the file would otherwise end the watch.
+fn watch_config(
+    config_path: &Path,
+    config: Arc<RwLock<config::Config>>,
+) -> notify::Result<notify::RecommendedWatcher> {
+    let (sender, receiver) = unbounded_channel();
+    let file_name = config_path.file_name().map(|name| name.to_owned());
+    let mut watcher =
+        notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
+            Ok(event) => {
+                let is_config = event
+                    .paths
+                    .iter()
+                    .any(|path| path.file_name() == file_name.as_deref());
+                if is_config && !event.kind.is_access() {
+                    let _ = sender.send(());
+                }
+            }
+            Err(e) => warn!("Error monitoring configuration file: {}", e),
+        })?;
+    let directory = config_path.parent().unwrap_or(config_path);
+    watcher.watch(directory, notify::RecursiveMode::NonRecursive)?;
+    debug!("Monitoring {} for changes", config_path.display());
+
+    tokio::spawn(reload_config(config_path.to_path_buf(), config, receiver));
+    Ok(watcher)
+}
+
+async fn reload_config(
+    config_path: PathBuf,
+    config: Arc<RwLock<config::Config>>,
+    mut changes: UnboundedReceiver<()>,
+) {
+    while changes.recv().await.is_some() {
+        // Saving a file often produces several events in quick succession, so
+        // wait for them to settle before reading the file.
+        while let Ok(Some(())) = tokio::time::timeout(CONFIG_DEBOUNCE, changes.recv()).await {}
+
+        match config::Config::load(&config_path) {
+            Ok(mut new_config) => {
+                // Settings from the server take precedence over the file until
+                // they are fetched again.
+                let pulled_settings = config.read().unwrap().pulled_settings.clone();
+                if let Some(settings) = pulled_settings {
+                    new_config.apply_pulled_settings(settings);
+                }
+                logging::configure(
+                    &new_config.log_file,
+                    new_config.event_log,
+                    new_config.log_format,
+                );
+                *config.write().unwrap() = new_config;
+                info!("Reloaded configuration");
+                api::check(&config).await;
+            }
+            Err(error) => error!(
+                "Could not reload configuration, keeping previous configuration: {:?}",
+                error
+            ),
+        }
+    }
+}
+
+/// Wait for the next status request, or forever if the endpoint is disabled.
+async fn recv_status(
+    requests: &mut Option<mpsc::Receiver<status::StatusRequest>>,
+) -> Option<status::StatusRequest> {
+    match requests {
+        Some(receiver) => receiver.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Wait for the next tick of an optional interval, or forever if disabled.
+async fn tick(interval: &mut Option<tokio::time::Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Wait for the next presence update, or forever if Discord is disabled.
+async fn tick_presence(presence: &mut Option<(discord::Presence, tokio::time::Interval)>) {
+    match presence {
+        Some((_, interval)) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Wait for the next hotkey press, or forever if no hotkey is registered.
+async fn recv_hotkey(presses: &mut Option<UnboundedReceiver<()>>) -> Option<()> {
+    match presses {
+        Some(receiver) => receiver.recv().await,
+        None => std::future::pending().await,
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    SimpleLogger::new()
-        .with_level(LevelFilter::Info)
-        .env()
-        .init()
-        .unwrap();
+    let cli = Cli::parse();
+    logging::init().unwrap();
+
+    if let Some(Command::Autostart { action }) = &cli.command {
+        let result = match action {
+            AutostartAction::Enable => autostart::enable(),
+            AutostartAction::Disable => autostart::disable(),
+        };
+        if result.is_err() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(Command::Init { force }) = &cli.command {
+        if init::run(*force).is_err() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(Command::Doctor) = &cli.command {
+        if doctor::run().await.is_err() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(Command::Stats { since, process }) = &cli.command {
+        let result = match config::Config::get_path().and_then(|path| config::Config::load(&path)) {
+            Ok(config) => stats::show(&config, *since, process.clone()).await,
+            Err(error) => {
+                error!("Could not load configuration: {:?}", error);
+                init::suggest(&error);
+                Err(())
+            }
+        };
+        if result.is_err() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // Two clients would both submit every session, but simulated sessions
+    // are separate from the real ones.
+    let _instance_lock = match win::InstanceLock::acquire(INSTANCE_MUTEX) {
+        Ok(Some(lock)) => Some(lock),
+        Ok(None) if cli.force || cli.simulate.is_some() => {
+            warn!("Another instance of the client is already running, continuing anyway");
+            None
+        }
+        Ok(None) => {
+            error!("Another instance of the client is already running, use --force to run anyway");
+            std::process::exit(1);
+        }
+        Err(error) => {
+            warn!("Could not check for other running instances: {}", error);
+            None
+        }
+    };
 
     let Ok(config_path) = config::Config::get_path() else {
         error!("Could not determine configuration path");
         return Ok(());
     };
     let config = match config::Config::load(&config_path) {
-        Ok(config) => Arc::new(RwLock::new(config)),
-        Err(_) => {
-            error!("Could not load configuration");
+        Ok(config) => {
+            logging::configure(&config.log_file, config.event_log, config.log_format);
+            Arc::new(RwLock::new(config))
+        }
+        Err(error) => {
+            error!("Could not load configuration: {:?}", error);
+            init::suggest(&error);
             return Ok(());
         }
     };
     info!("Loaded configuration");
 
     // Reload the configuration if the config file is changed.
-    let w_config = config.clone();
-    let mut watcher =
-        notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
-            Ok(event) => {
-                if let Ok(new_config) = config::Config::load(event.paths[0].as_path()) {
-                    let mut config_write = w_config.write().unwrap();
-                    *config_write = new_config;
-                }
-            }
-            Err(e) => warn!("Error monitoring configuration file: {}", e),
-        })?;
-    match watcher.watch(&config_path.as_path(), notify::RecursiveMode::NonRecursive) {
-        Ok(()) => debug!("Monitoring {} for changes", &config_path.display()),
-        Err(_) => warn!(
-            "Can't monitor config file {} for changes",
-            &config_path.display()
-        ),
+    let _watcher = match watch_config(&config_path, config.clone()) {
+        Ok(watcher) => Some(watcher),
+        Err(error) => {
+            warn!(
+                "Can't monitor config file {} for changes: {}",
+                &config_path.display(),
+                error
+            );
+            None
+        }
     };
 
-    let (mut stream_start, mut stream_end) = match win::create_streams() {
-        Ok((start, end)) => (start, end),
+    let events = match &cli.simulate {
+        Some(path) => match source::Scripted::load(path) {
+            Ok(script) => {
+                info!("Simulating events from {}", path.display());
+                script.events()
+            }
+            Err(error) => {
+                error!("Could not load event script {}: {}", path.display(), error);
+                return Ok(());
+            }
+        },
+        None => source::Wmi.events(),
+    };
+    let mut events = match events {
+        Ok(events) => events,
         _ => return Ok(()),
     };
 
-    let mut process_watch = ProcessWatchMap::new();
+    let pause_hotkey = config.read().unwrap().pause_hotkey.clone();
+    let mut hotkey_presses = match pause_hotkey.as_deref().map(hotkey::Hotkey::parse) {
+        Some(Some(hotkey)) => Some(hotkey::listen(hotkey)),
+        Some(None) => {
+            error!("Invalid pause hotkey {}", pause_hotkey.unwrap_or_default());
+            None
+        }
+        None => None,
+    };
+
+    let status_port = config.read().unwrap().status_port;
+    let mut status_requests = status_port.map(status::serve);
+
+    let mut tracker = Tracker::default();
+    if config.read().unwrap().journal && cli.simulate.is_none() {
+        tracker.journal = journal::Journal::open();
+    }
+    recover_journal(&cli, &config, &mut tracker).await;
+
+    let mut focus_poll = tokio::time::interval(FOCUS_POLL_INTERVAL);
+    let mut journal_interval = tokio::time::interval(JOURNAL_INTERVAL);
+    let window_title_interval = config.read().unwrap().window_title_interval;
+    let mut window_title_sampling = window_title_interval
+        .map(|seconds| tokio::time::interval(Duration::from_secs(seconds.max(1))));
+    let needs_registration = {
+        let config = config.read().unwrap();
+        config.register.is_some() && config.device_token.is_none()
+    };
+    let mut registration_timer =
+        needs_registration.then(|| tokio::time::interval(device::REGISTRATION_INTERVAL));
+    let pull_settings_interval = config
+        .read()
+        .unwrap()
+        .pull_settings
+        .as_ref()
+        .map(|pull_settings| pull_settings.interval);
+    let mut pull_settings_timer = pull_settings_interval
+        .map(|seconds| tokio::time::interval(Duration::from_secs(seconds.max(60))));
+    let mut flush_timer = config
+        .read()
+        .unwrap()
+        .defer
+        .is_some()
+        .then(|| tokio::time::interval(defer::FLUSH_INTERVAL));
+    let mut heartbeat_timer = config
+        .read()
+        .unwrap()
+        .heartbeats
+        .then(|| tokio::time::interval(heartbeat::HEARTBEAT_INTERVAL));
+    let mut api_check = tokio::time::interval(api::CHECK_INTERVAL);
+    let mut presence = config.read().unwrap().discord.as_ref().map(|discord| {
+        (
+            discord::Presence::new(discord),
+            tokio::time::interval(discord::PRESENCE_INTERVAL),
+        )
+    });
     info!("Listening to events");
     loop {
         tokio::select! {
-            Some(event) = stream_start.next() => handle_process_start(&config, &mut process_watch, event).await,
-            Some(event) = stream_end.next() => handle_process_end(&config, &mut process_watch, event).await,
+            event = events.next() => match event {
+                Some(event) => handle_event(&cli, &config, &mut tracker, event).await,
+                None => break,
+            },
+            Some(()) = recv_hotkey(&mut hotkey_presses) => toggle_pause(&mut tracker),
+            _ = focus_poll.tick() => {
+                tracker.poll_focus();
+                tracker.poll_remote(config.read().unwrap().remote_sessions);
+            }
+            _ = journal_interval.tick() => tracker.save_journal(),
+            _ = tick(&mut window_title_sampling) => tracker.sample_window_titles(),
+            _ = tick(&mut registration_timer) => {
+                if device::register(&config).await {
+                    registration_timer = None;
+                }
+            }
+            _ = api_check.tick() => api::check(&config).await,
+            _ = tick(&mut pull_settings_timer) => pull_settings(&config).await,
+            _ = tick(&mut flush_timer) => flush_deferred(&config).await,
+            _ = tick(&mut heartbeat_timer) => heartbeat::send(&config, &tracker).await,
+            _ = tick_presence(&mut presence) => {
+                if let Some((presence, _)) = presence.as_mut() {
+                    presence.update(&tracker).await;
+                }
+            }
+            Some(request) = recv_status(&mut status_requests) => {
+                let _ = request.send(status::Status::from(&tracker));
+            }
             else => break,
         }
     }
+    for watch in tracker.watches.values() {
+        warn!(
+            "{} was still running when the events ended",
+            watch.name.as_ref().unwrap_or(&watch.executable)
+        );
+    }
     Ok(())
 }
@@ -0,0 +1,345 @@
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+/// Regular expression that is compiled when the configuration is loaded.
+#[derive(Debug)]
+pub struct Pattern(Regex);
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern)
+            .map(Pattern)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Rule for naming sessions based on the process command line, for processes
+/// like emulators where the executable alone says nothing about the game.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandLineRule {
+    /// Executable name the rule applies to, e.g. `retroarch.exe`.
+    pub executable: String,
+
+    /// Pattern matched against the command line. The `name` capture group is
+    /// used as the session name if present, otherwise the first group, and
+    /// otherwise the whole match.
+    pub pattern: Pattern,
+}
+
+impl CommandLineRule {
+    fn matches(&self, executable: &str) -> bool {
+        self.executable.eq_ignore_ascii_case(executable)
+    }
+
+    fn extract(&self, command_line: &str) -> Option<String> {
+        let captures = self.pattern.0.captures(command_line)?;
+        let value = captures
+            .name("name")
+            .or_else(|| captures.get(1))
+            .or_else(|| captures.get(0))?
+            .as_str()
+            .trim();
+        if value.is_empty() {
+            return None;
+        }
+        Some(value.to_string())
+    }
+}
+
+/// Name from the first matching command line rule for the executable.
+pub fn name_from_command_line(
+    rules: &[CommandLineRule],
+    executable: &str,
+    command_line: Option<&str>,
+) -> Option<String> {
+    let command_line = command_line?;
+    rules
+        .iter()
+        .filter(|rule| rule.matches(executable))
+        .find_map(|rule| rule.extract(command_line))
+}
+
+/// Version info fields tried for the session name, in order.
+pub fn default_name_fields() -> Vec<String> {
+    vec!["ProductName".to_string(), "FileDescription".to_string()]
+}
+
+/// Names that engines and toolchains put in the version info of the games
+/// built with them, and which say nothing about the game itself.
+pub fn default_generic_names() -> Vec<String> {
+    ["Unity", "BuildTool", "Unreal Engine", "Launcher", "Game"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+/// Value is empty or one of the given generic names.
+pub fn is_generic_name(value: &str, generic_names: &[String]) -> bool {
+    let value = value.trim();
+    value.is_empty()
+        || generic_names
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(value))
+}
+
+/// Emulator whose command line names the game being played.
+struct Emulator {
+    executables: &'static [&'static str],
+
+    /// Options that are followed by the path of the game.
+    game_options: &'static [&'static str],
+
+    /// Options that are followed by a value that isn't the game.
+    value_options: &'static [&'static str],
+}
+
+const EMULATORS: &[Emulator] = &[
+    Emulator {
+        executables: &["retroarch.exe"],
+        game_options: &[],
+        value_options: &[
+            "-L",
+            "--libretro",
+            "-c",
+            "--config",
+            "--appendconfig",
+            "--subsystem",
+        ],
+    },
+    Emulator {
+        executables: &["dolphin.exe"],
+        game_options: &["-e", "--exec"],
+        value_options: &["-u", "--user", "-m", "--movie", "-s", "--save_state"],
+    },
+    Emulator {
+        executables: &["pcsx2.exe", "pcsx2-qt.exe"],
+        game_options: &[],
+        value_options: &["-elf", "-state", "-statefile", "-gameargs"],
+    },
+];
+
+/// Split a command line into arguments the way most Windows programs do.
+fn split_command_line(command_line: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut in_argument = false;
+    for c in command_line.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                in_argument = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if in_argument {
+                    arguments.push(std::mem::take(&mut current));
+                    in_argument = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_argument = true;
+            }
+        }
+    }
+    if in_argument {
+        arguments.push(current);
+    }
+    arguments
+}
+
+/// Path of the game from the emulator arguments, excluding the executable.
+fn game_path<'a>(emulator: &Emulator, arguments: &'a [String]) -> Option<&'a str> {
+    let mut game = None;
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        if let Some((option, value)) = argument.split_once('=') {
+            if emulator.game_options.contains(&option) {
+                return Some(value);
+            }
+            if argument.starts_with('-') {
+                continue;
+            }
+        }
+        if emulator.game_options.contains(&argument.as_str()) {
+            return arguments.next().map(String::as_str);
+        }
+        if emulator.value_options.contains(&argument.as_str()) {
+            arguments.next();
+        } else if !argument.starts_with('-') {
+            game = Some(argument.as_str());
+        }
+    }
+    game
+}
+
+/// Turn a ROM or disc image file name into a game name by dropping the
+/// extension and the region and dump tags, e.g. `Super Metroid (USA) [!].sfc`
+/// becomes `Super Metroid`.
+fn clean_game_file_name(path: &str) -> Option<String> {
+    let stem = std::path::Path::new(path).file_stem()?.to_str()?;
+    let end = stem.find(['(', '[']).unwrap_or(stem.len());
+    let name = stem[..end].trim();
+    if name.is_empty() {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+/// Name of the game run by a known emulator, from its command line.
+pub fn name_from_emulator(executable: &str, command_line: Option<&str>) -> Option<String> {
+    let emulator = EMULATORS.iter().find(|emulator| {
+        emulator
+            .executables
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(executable))
+    })?;
+    let arguments = split_command_line(command_line?);
+    let path = game_path(emulator, arguments.get(1..)?)?;
+    clean_game_file_name(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(executable: &str, pattern: &str) -> CommandLineRule {
+        CommandLineRule {
+            executable: executable.to_string(),
+            pattern: Pattern(Regex::new(pattern).unwrap()),
+        }
+    }
+
+    #[test]
+    fn name_of_command_line() {
+        let rules = [
+            rule("java.exe", r"-jar (?:\S*[\\/])?(?P<name>[^\\/ ]+)\.jar"),
+            rule("launcher.exe", r"--game (\w+)"),
+            rule("launcher.exe", r"--profile \w+"),
+        ];
+        let cases = [
+            (
+                "java.exe",
+                Some(r"java.exe -Xmx2G -jar C:\Games\Starsector\starsector.jar"),
+                Some("starsector"),
+            ),
+            (
+                "JAVA.EXE",
+                Some("java.exe -jar minecraft.jar"),
+                Some("minecraft"),
+            ),
+            (
+                "launcher.exe",
+                Some("launcher.exe --game doom"),
+                Some("doom"),
+            ),
+            (
+                "launcher.exe",
+                Some("launcher.exe --profile default"),
+                Some("--profile default"),
+            ),
+            ("launcher.exe", Some("launcher.exe"), None),
+            ("java.exe", None, None),
+            ("javaw.exe", Some("javaw.exe -jar minecraft.jar"), None),
+        ];
+        for (executable, command_line, output) in cases {
+            assert_eq!(
+                name_from_command_line(&rules, executable, command_line).as_deref(),
+                output,
+                "{:?}",
+                command_line
+            );
+        }
+    }
+
+    #[test]
+    fn blank_capture_is_no_name() {
+        let rules = [
+            rule("game.exe", r"--name=(\S*)"),
+            rule("game.exe", r"--id=(\d+)"),
+        ];
+        assert_eq!(
+            name_from_command_line(&rules, "game.exe", Some("game.exe --name= --id=42")),
+            Some("42".to_string())
+        );
+    }
+
+    #[test]
+    fn invalid_pattern_is_not_loaded() {
+        let rule: Result<CommandLineRule, _> =
+            serde_yaml::from_str("executable: game.exe\npattern: \"(unclosed\"\n");
+        assert!(rule.is_err());
+    }
+
+    #[test]
+    fn split_quoted_command_line() {
+        assert_eq!(
+            split_command_line(r#""C:\Program Files\app.exe"  -a "b c" d""e"#),
+            [r"C:\Program Files\app.exe", "-a", "b c", "de"]
+        );
+        assert_eq!(split_command_line(r#"app.exe """#), ["app.exe", ""]);
+        assert!(split_command_line("  ").is_empty());
+    }
+
+    #[test]
+    fn name_of_emulator_game() {
+        let cases = [
+            (
+                "retroarch.exe",
+                r#""C:\RetroArch\retroarch.exe" -L "C:\RetroArch\cores\snes9x_libretro.dll" "D:\ROMs\Super Metroid (USA) [!].sfc""#,
+                Some("Super Metroid"),
+            ),
+            (
+                "retroarch.exe",
+                r#"retroarch.exe --config=C:\RetroArch\retroarch.cfg "D:\ROMs\Chrono Trigger (USA).sfc" --verbose"#,
+                Some("Chrono Trigger"),
+            ),
+            (
+                "Dolphin.exe",
+                r#"Dolphin.exe -u C:\Dolphin\User -e "D:\Games\Metroid Prime (USA).iso" -b"#,
+                Some("Metroid Prime"),
+            ),
+            (
+                "dolphin.exe",
+                r#"dolphin.exe "--exec=D:\Games\The Wind Waker.rvz""#,
+                Some("The Wind Waker"),
+            ),
+            (
+                "pcsx2-qt.exe",
+                r#"pcsx2-qt.exe -state 1 "D:\PS2\Shadow of the Colossus (Europe).chd""#,
+                Some("Shadow of the Colossus"),
+            ),
+        ];
+        for (executable, command_line, output) in cases {
+            assert_eq!(
+                name_from_emulator(executable, Some(command_line)).as_deref(),
+                output,
+                "{}",
+                command_line
+            );
+        }
+    }
+
+    #[test]
+    fn no_emulator_game() {
+        let cases = [
+            (
+                "retroarch.exe",
+                Some(r"retroarch.exe -L cores\snes9x_libretro.dll"),
+            ),
+            ("retroarch.exe", Some(r"retroarch.exe D:\ROMs\(USA).sfc")),
+            ("retroarch.exe", None),
+            ("mame.exe", Some(r"mame.exe D:\ROMs\pacman.zip")),
+        ];
+        for (executable, command_line) in cases {
+            assert_eq!(
+                name_from_emulator(executable, command_line),
+                None,
+                "{:?}",
+                command_line
+            );
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use std::fs::File;
+use std::path::Path;
+use std::time::Duration;
+
+use futures::stream::{self, LocalBoxStream, StreamExt};
+use serde::Deserialize;
+use wmi::WMIError;
+
+use crate::win;
+
+/// Something that happened to a process or to the system.
+pub enum ProcessEvent {
+    Start(win::ProcessStartResult),
+    End(win::ProcessEndResult),
+    Power(win::PowerEventResult),
+}
+
+/// Where the process and power events that drive the watches come from.
+pub trait ProcessEventSource {
+    fn events(self) -> Result<LocalBoxStream<'static, ProcessEvent>, WMIError>;
+}
+
+/// Events reported by Windows Management Instrumentation.
+pub struct Wmi;
+
+impl ProcessEventSource for Wmi {
+    fn events(self) -> Result<LocalBoxStream<'static, ProcessEvent>, WMIError> {
+        let (starts, ends, power) = win::create_streams()?;
+        let events = stream::select(
+            starts.map(ProcessEvent::Start),
+            stream::select(ends.map(ProcessEvent::End), power.map(ProcessEvent::Power)),
+        );
+        return Ok(events.boxed_local());
+    }
+}
+
+#[derive(Debug)]
+pub enum ScriptError {
+    IOError(std::io::Error),
+    DeserialisationError(serde_yaml::Error),
+}
+
+impl std::fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ScriptError::IOError(error) => error.fmt(f),
+            ScriptError::DeserialisationError(error) => error.fmt(f),
+        }
+    }
+}
+
+/// Entry of an event script file.
+#[derive(Deserialize)]
+#[serde(untagged, deny_unknown_fields, rename_all_fields = "camelCase")]
+enum ScriptEntry {
+    Start {
+        /// Executable path of the process.
+        start: String,
+        pid: u32,
+        command_line: Option<String>,
+    },
+    End {
+        /// Process ID of a process started before.
+        end: u32,
+    },
+    Wait {
+        /// Seconds until the next event.
+        wait: u64,
+    },
+    Power {
+        power: PowerChange,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum PowerChange {
+    Suspend,
+    Resume,
+}
+
+enum Step {
+    Event(ProcessEvent),
+    Wait(Duration),
+}
+
+/// Events given up front, replayed in order.
+#[derive(Default)]
+pub struct Scripted {
+    steps: Vec<Step>,
+}
+
+impl Scripted {
+    /// Read an event script from a YAML file.
+    pub fn load(path: &Path) -> Result<Self, ScriptError> {
+        let fp = File::open(path).map_err(ScriptError::IOError)?;
+        let entries: Vec<ScriptEntry> =
+            serde_yaml::from_reader(fp).map_err(ScriptError::DeserialisationError)?;
+        let script = entries
+            .into_iter()
+            .fold(Scripted::default(), |script, entry| match entry {
+                ScriptEntry::Start {
+                    start,
+                    pid,
+                    command_line,
+                } => script.start(pid, &start, command_line.as_deref()),
+                ScriptEntry::End { end } => script.end(end),
+                ScriptEntry::Wait { wait } => script.wait(Duration::from_secs(wait)),
+                ScriptEntry::Power {
+                    power: PowerChange::Suspend,
+                } => script.suspend(),
+                ScriptEntry::Power {
+                    power: PowerChange::Resume,
+                } => script.resume(),
+            });
+        return Ok(script);
+    }
+
+    /// Process with the given executable path starts.
+    pub fn start(
+        mut self,
+        process_id: u32,
+        executable_path: &str,
+        command_line: Option<&str>,
+    ) -> Self {
+        let process = win::Process::scripted(process_id, executable_path, command_line);
+        self.steps.push(Step::Event(ProcessEvent::Start(Ok(
+            win::ProcessStartEvent {
+                target_instance: process,
+            },
+        ))));
+        self
+    }
+
+    /// Process that was started before exits.
+    pub fn end(mut self, process_id: u32) -> Self {
+        let process = win::Process::scripted(process_id, "", None);
+        self.steps
+            .push(Step::Event(ProcessEvent::End(Ok(win::ProcessEndEvent {
+                target_instance: process,
+            }))));
+        self
+    }
+
+    /// Nothing happens for a while.
+    pub fn wait(mut self, duration: Duration) -> Self {
+        self.steps.push(Step::Wait(duration));
+        self
+    }
+
+    /// System enters standby or hibernation.
+    pub fn suspend(mut self) -> Self {
+        self.steps.push(Step::Event(ProcessEvent::Power(Ok(
+            win::PowerManagementEvent {
+                event_type: win::POWER_EVENT_SUSPEND,
+            },
+        ))));
+        self
+    }
+
+    /// System wakes up from standby or hibernation.
+    pub fn resume(mut self) -> Self {
+        self.steps.push(Step::Event(ProcessEvent::Power(Ok(
+            win::PowerManagementEvent {
+                event_type: win::POWER_EVENT_RESUME_SUSPEND,
+            },
+        ))));
+        self
+    }
+}
+
+impl ProcessEventSource for Scripted {
+    fn events(self) -> Result<LocalBoxStream<'static, ProcessEvent>, WMIError> {
+        let events = stream::iter(self.steps).filter_map(|step| async move {
+            match step {
+                Step::Event(event) => Some(event),
+                Step::Wait(duration) => {
+                    tokio::time::sleep(duration).await;
+                    None
+                }
+            }
+        });
+        return Ok(events.boxed_local());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::RwLock;
+
+    use clap::Parser;
+
+    use super::*;
+    use crate::{config::Config, handle_event, watch::Tracker, Cli};
+
+    const CONFIG: &str = "
+monitor:
+  - C:\\Games
+nameOverrides:
+  sekiro.exe: Sekiro
+";
+
+    async fn replay(script: Scripted, tracker: &mut Tracker) {
+        let cli = Cli::parse_from(["beelzebub-client", "--dry-run"]);
+        let config = RwLock::new(serde_yaml::from_str::<Config>(CONFIG).unwrap());
+        let mut events = script.events().unwrap();
+        while let Some(event) = events.next().await {
+            handle_event(&cli, &config, tracker, event).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn monitored_process_is_watched() {
+        let mut tracker = Tracker::default();
+        let script = Scripted::default().start(1, "C:\\Games\\Sekiro\\sekiro.exe", None);
+        replay(script, &mut tracker).await;
+        let watch = tracker
+            .watches
+            .get("c:\\games\\sekiro\\sekiro.exe")
+            .unwrap();
+        assert_eq!(watch.name.as_deref(), Some("Sekiro"));
+    }
+
+    #[tokio::test]
+    async fn unmonitored_process_is_ignored() {
+        let mut tracker = Tracker::default();
+        let script = Scripted::default().start(1, "C:\\Windows\\notepad.exe", None);
+        replay(script, &mut tracker).await;
+        assert!(tracker.watches.is_empty());
+    }
+
+    #[tokio::test]
+    async fn processes_of_one_executable_share_a_watch() {
+        let mut tracker = Tracker::default();
+        let script = Scripted::default()
+            .start(1, "C:\\Games\\Sekiro\\sekiro.exe", None)
+            .start(2, "C:\\Games\\Sekiro\\sekiro.exe", None)
+            .end(1);
+        replay(script, &mut tracker).await;
+        assert_eq!(tracker.watches.len(), 1);
+
+        replay(Scripted::default().end(2), &mut tracker).await;
+        assert!(tracker.watches.is_empty());
+    }
+
+    #[tokio::test]
+    async fn executables_of_the_same_name_have_their_own_watches() {
+        let mut tracker = Tracker::default();
+        let script = Scripted::default()
+            .start(1, "C:\\Games\\Hades\\Game.exe", None)
+            .start(2, "C:\\Games\\Celeste\\Game.exe", None)
+            .start(3, "C:\\Games\\Hades\\game.exe", None);
+        replay(script, &mut tracker).await;
+        assert_eq!(tracker.watches.len(), 2);
+        let hades = tracker.watches.get("c:\\games\\hades\\game.exe").unwrap();
+        assert_eq!(hades.process_ids, HashSet::from([1, 3]));
+
+        replay(Scripted::default().end(1).end(3), &mut tracker).await;
+        assert_eq!(tracker.watches.len(), 1);
+        assert!(tracker.watches.contains_key("c:\\games\\celeste\\game.exe"));
+    }
+
+    #[tokio::test]
+    async fn paused_tracking_ignores_processes() {
+        let mut tracker = Tracker::default();
+        tracker.paused = true;
+        let script = Scripted::default().start(1, "C:\\Games\\Sekiro\\sekiro.exe", None);
+        replay(script, &mut tracker).await;
+        assert!(tracker.watches.is_empty());
+    }
+
+    #[tokio::test]
+    async fn suspend_freezes_watches_until_resume() {
+        let mut tracker = Tracker::default();
+        let script = Scripted::default()
+            .start(1, "C:\\Games\\Sekiro\\sekiro.exe", None)
+            .suspend();
+        replay(script, &mut tracker).await;
+        assert!(tracker.suspended);
+
+        replay(Scripted::default().resume(), &mut tracker).await;
+        assert!(!tracker.suspended);
+    }
+}
@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+use log::error;
+use time::OffsetDateTime;
+
+use crate::api;
+use crate::config::Config;
+use crate::toast::format_duration;
+
+/// Parse a duration such as `30m`, `12h`, `7d` or `2w`.
+pub fn parse_since(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value.len() - value.chars().last().map_or(0, char::len_utf8);
+    let (number, unit) = value.split_at(split);
+    let number = number
+        .parse::<u64>()
+        .map_err(|_| format!("invalid duration {}, expected e.g. 7d", value))?;
+    let seconds = match unit {
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("invalid unit in {}, expected m, h, d or w", value)),
+    };
+    number
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("duration {} is too long", value))
+}
+
+fn truncate(value: &str, width: usize) -> String {
+    if value.chars().count() <= width {
+        return value.to_string();
+    }
+    let mut truncated = value.chars().take(width - 1).collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+fn print_table(stats: &[shared::ProcessStats]) {
+    const NAME_WIDTH: usize = 40;
+    println!(
+        "{:<NAME_WIDTH$} {:>8} {:>10}",
+        "Game", "Sessions", "Playtime"
+    );
+    for process in stats {
+        let name = process.name.as_ref().unwrap_or(&process.executable);
+        println!(
+            "{:<NAME_WIDTH$} {:>8} {:>10}",
+            truncate(name, NAME_WIDTH),
+            process.sessions,
+            format_duration(process.duration)
+        );
+    }
+    let total = stats.iter().map(|process| process.duration).sum();
+    println!(
+        "{:<NAME_WIDTH$} {:>8} {:>10}",
+        "Total",
+        "",
+        format_duration(total)
+    );
+}
+
+/// Fetch playtime per game from the server and print it as a table.
+pub async fn show(
+    config: &Config,
+    since: Option<Duration>,
+    process: Option<String>,
+) -> Result<(), ()> {
+    let Some(api) = &config.api else {
+        error!("Could not fetch stats: no server URL configured");
+        return Err(());
+    };
+    api::check_client(api).await;
+    let query = shared::StatsQuery {
+        // Sessions don't start before the epoch, which longer durations are
+        // counted from.
+        since: since.map(|since| {
+            time::Duration::try_from(since)
+                .ok()
+                .and_then(|since| OffsetDateTime::now_utc().checked_sub(since))
+                .map_or(OffsetDateTime::UNIX_EPOCH, |since| {
+                    since.max(OffsetDateTime::UNIX_EPOCH)
+                })
+        }),
+        process,
+        ..Default::default()
+    };
+    let stats = match api.stats(&query).await {
+        Ok(stats) => stats,
+        Err(beelzebub_api::Error::Unauthorized) => {
+            error!("Could not fetch stats: unauthorized. Double check secret key settings.");
+            return Err(());
+        }
+        Err(beelzebub_api::Error::Decode(error)) => {
+            error!("Could not parse stats: {}", error);
+            return Err(());
+        }
+        Err(error) => {
+            error!("Could not fetch stats: {}", error);
+            return Err(());
+        }
+    };
+    print_table(&stats);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn since() {
+        let cases = [
+            ("30m", 30 * 60),
+            ("12h", 12 * 60 * 60),
+            (" 7d ", 7 * 24 * 60 * 60),
+            ("2w", 14 * 24 * 60 * 60),
+            ("0d", 0),
+        ];
+        for (value, seconds) in cases {
+            assert_eq!(
+                parse_since(value),
+                Ok(Duration::from_secs(seconds)),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_since() {
+        for value in [
+            "",
+            "7",
+            "d",
+            "-1d",
+            "7 d",
+            "7D",
+            "1.5h",
+            "7é",
+            "40000000000000w",
+        ] {
+            assert!(parse_since(value).is_err(), "{}", value);
+        }
+    }
+}
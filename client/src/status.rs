@@ -0,0 +1,111 @@
+use std::net::{Ipv4Addr, SocketAddr};
+
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use log::{error, info};
+use serde::Serialize;
+use time::OffsetDateTime;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::watch::{Tracker, Watch};
+
+/// Request for a status snapshot, answered by the main event loop.
+pub type StatusRequest = oneshot::Sender<Status>;
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmissionStatus {
+    #[serde(with = "time::serde::rfc3339")]
+    pub at: OffsetDateTime,
+    pub session: String,
+    pub success: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchStatus {
+    pub executable: String,
+    pub name: Option<String>,
+    pub process_ids: Vec<u32>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+    pub duration: u64,
+    pub focused_duration: u64,
+    pub tags: Vec<String>,
+    pub window_title: Option<String>,
+}
+
+impl From<&Watch> for WatchStatus {
+    fn from(watch: &Watch) -> Self {
+        let mut process_ids = watch.process_ids.iter().copied().collect::<Vec<u32>>();
+        process_ids.sort();
+        Self {
+            executable: watch.executable.clone(),
+            name: watch.name.clone(),
+            process_ids,
+            started_at: watch.started_at,
+            duration: watch.duration().as_secs(),
+            focused_duration: watch.focused.as_secs(),
+            tags: watch.tags.clone(),
+            window_title: watch.window_title(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    pub paused: bool,
+    pub suspended: bool,
+    pub remote: bool,
+    pub watches: Vec<WatchStatus>,
+    pub last_submission: Option<SubmissionStatus>,
+}
+
+impl From<&Tracker> for Status {
+    fn from(tracker: &Tracker) -> Self {
+        Self {
+            paused: tracker.paused,
+            suspended: tracker.suspended,
+            remote: tracker.remote,
+            watches: tracker.watches.values().map(WatchStatus::from).collect(),
+            last_submission: tracker.last_submission.clone(),
+        }
+    }
+}
+
+async fn status(
+    State(requests): State<mpsc::Sender<StatusRequest>>,
+) -> Result<Json<Status>, StatusCode> {
+    let (sender, receiver) = oneshot::channel();
+    if requests.send(sender).await.is_err() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    match receiver.await {
+        Ok(status) => Ok(Json(status)),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+/// Serve the status endpoint on localhost and return the channel on which
+/// snapshot requests arrive.
+pub fn serve(port: u16) -> mpsc::Receiver<StatusRequest> {
+    let (sender, receiver) = mpsc::channel(8);
+    let app = Router::new()
+        .route("/status", get(status))
+        .with_state(sender);
+    tokio::spawn(async move {
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+        let listener = match tokio::net::TcpListener::bind(address).await {
+            Ok(listener) => listener,
+            Err(error) => {
+                error!("Could not bind status endpoint to {}: {}", address, error);
+                return;
+            }
+        };
+        info!("Serving status on http://{}/status", address);
+        if let Err(error) = axum::serve(listener, app).await {
+            error!("Status endpoint stopped: {}", error);
+        }
+    });
+    receiver
+}
@@ -0,0 +1,77 @@
+use log::warn;
+use windows::{
+    core::HSTRING,
+    Data::Xml::Dom::XmlDocument,
+    UI::Notifications::{ToastNotification, ToastNotificationManager},
+};
+
+/// Toasts need an application user model ID that is registered with the
+/// shell. Unpackaged applications don't have one, so borrow PowerShell's.
+const APP_ID: &str =
+    "{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\\WindowsPowerShell\\v1.0\\powershell.exe";
+
+/// Format a duration for humans, e.g. `1h 23m`.
+pub fn format_duration(seconds: u64) -> String {
+    let hours = seconds / 3600;
+    let minutes = (seconds % 3600) / 60;
+    match (hours, minutes) {
+        (0, 0) => format!("{}s", seconds),
+        (0, _) => format!("{}m", minutes),
+        _ => format!("{}h {}m", hours, minutes),
+    }
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn show(title: &str, text: &str) -> windows::core::Result<()> {
+    let xml = format!(
+        "<toast><visual><binding template=\"ToastGeneric\">\
+        <text>{}</text><text>{}</text>\
+        </binding></visual></toast>",
+        escape_xml(title),
+        escape_xml(text),
+    );
+    let document = XmlDocument::new()?;
+    document.LoadXml(&HSTRING::from(xml))?;
+    let toast = ToastNotification::CreateToastNotification(&document)?;
+    ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(APP_ID))?.Show(&toast)
+}
+
+/// Notify that a session was successfully submitted.
+pub fn session_recorded(name: &str, duration: u64) {
+    let text = format!("Recorded {} of {}", format_duration(duration), name);
+    if let Err(error) = show("Beelzebub", &text) {
+        warn!("Could not show notification: {}", error);
+    }
+}
+
+/// Notify that a session was queued to be submitted later.
+pub fn session_deferred(name: &str, duration: u64) {
+    let text = format!(
+        "Queued {} of {} to be submitted later",
+        format_duration(duration),
+        name
+    );
+    if let Err(error) = show("Beelzebub", &text) {
+        warn!("Could not show notification: {}", error);
+    }
+}
+
+/// Notify that a session could not be submitted.
+pub fn submission_failed(name: &str, duration: u64) {
+    let text = format!(
+        "Could not submit {} of {} to the server",
+        format_duration(duration),
+        name
+    );
+    if let Err(error) = show("Beelzebub", &text) {
+        warn!("Could not show notification: {}", error);
+    }
+}
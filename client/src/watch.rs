@@ -0,0 +1,344 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use time::OffsetDateTime;
+
+use crate::config::RemoteSessionMode;
+use crate::journal::{Journal, JournalEntry};
+use crate::status::SubmissionStatus;
+use crate::{config, naming, win};
+
+/// Watches keyed by the session key of their executable.
+pub type ProcessWatchMap = HashMap<String, Watch>;
+
+/// How often to check which process is in the foreground.
+pub const FOCUS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Tag added to sessions played over remote desktop.
+const REMOTE_TAG: &str = "remote";
+
+/// Key that groups concurrently running instances of the same executable
+/// into a single session. Executables are told apart by their path, so that
+/// games that ship executables of the same name aren't counted as one, and
+/// by their name if Windows reported no path.
+pub fn session_key(executable: &str, path: Option<&str>) -> String {
+    path.unwrap_or(executable).to_lowercase()
+}
+
+pub struct Watch {
+    /// Running processes that belong to this session.
+    pub process_ids: HashSet<u32>,
+
+    pub start: Instant,
+    pub started_at: OffsetDateTime,
+    pub executable: String,
+
+    /// Path of the executable, if Windows reported one.
+    pub path: Option<String>,
+    pub name: Option<String>,
+    pub product_version: Option<String>,
+    pub file_version: Option<String>,
+    pub tags: Vec<String>,
+
+    /// Time that should not count towards the session, such as system sleep.
+    excluded: Duration,
+
+    /// When the watch was paused, if it currently is.
+    frozen_since: Option<Instant>,
+
+    /// Time the process has spent in the foreground.
+    pub focused: Duration,
+
+    /// How many times each main window title has been seen.
+    window_titles: HashMap<String, u32>,
+
+    /// Identifies the session to the server to ignore duplicate submissions.
+    pub session_id: Option<String>,
+}
+
+/// Random version 4 UUID for a new session.
+fn generate_session_id() -> Option<String> {
+    let mut bytes = [0u8; 16];
+    if let Err(error) = getrandom::getrandom(&mut bytes) {
+        warn!("Could not generate session ID: {}", error);
+        return None;
+    }
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    Some(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    ))
+}
+
+impl Watch {
+    pub fn new(
+        process: win::Process,
+        package: Option<win::PackageInfo>,
+        config: &config::Config,
+        tags: Vec<String>,
+    ) -> Self {
+        let version_info = process.get_version_info(
+            &config.name_fields,
+            &config.generic_names,
+            &config.version_languages,
+        );
+        let name = config
+            .name_override(&process.name)
+            .or_else(|| {
+                naming::name_from_command_line(
+                    &config.command_line_rules,
+                    &process.name,
+                    process.command_line.as_deref(),
+                )
+            })
+            .or_else(|| {
+                config
+                    .emulator_naming
+                    .then(|| {
+                        naming::name_from_emulator(&process.name, process.command_line.as_deref())
+                    })
+                    .flatten()
+            })
+            .or(version_info.name)
+            .or(package.and_then(|package| package.display_name));
+        Self {
+            process_ids: HashSet::from([process.process_id]),
+            start: Instant::now(),
+            started_at: OffsetDateTime::now_utc(),
+            executable: process.name,
+            path: process.executable_path,
+            name,
+            product_version: version_info.product_version,
+            file_version: version_info.file_version,
+            tags,
+            excluded: Duration::ZERO,
+            frozen_since: None,
+            focused: Duration::ZERO,
+            window_titles: HashMap::new(),
+            session_id: generate_session_id(),
+        }
+    }
+
+    /// Continue a watch from the journal for processes that are still running.
+    ///
+    /// Time between the journal entry and now is counted as part of the
+    /// session, since the processes kept running while the client didn't.
+    pub fn restore(entry: JournalEntry, process_ids: HashSet<u32>) -> Self {
+        let since_update = (OffsetDateTime::now_utc() - entry.updated_at)
+            .try_into()
+            .unwrap_or(Duration::ZERO);
+        let duration = Duration::from_secs(entry.duration) + since_update;
+        Self {
+            process_ids,
+            start: Instant::now()
+                .checked_sub(duration)
+                .unwrap_or_else(Instant::now),
+            started_at: entry.started_at,
+            executable: entry.executable,
+            path: entry.path,
+            name: entry.name,
+            product_version: entry.product_version,
+            file_version: entry.file_version,
+            tags: entry.tags,
+            excluded: Duration::ZERO,
+            frozen_since: None,
+            focused: Duration::from_secs(entry.focused_duration),
+            window_titles: HashMap::new(),
+            session_id: entry.session_id,
+        }
+    }
+
+    /// Time the process has been running, minus any excluded time.
+    pub fn duration(&self) -> Duration {
+        let frozen = self
+            .frozen_since
+            .map(|since| since.elapsed())
+            .unwrap_or_default();
+        self.start
+            .elapsed()
+            .saturating_sub(self.excluded)
+            .saturating_sub(frozen)
+    }
+
+    /// Window title seen most often during the session.
+    pub fn window_title(&self) -> Option<String> {
+        self.window_titles
+            .iter()
+            .max_by_key(|(_, count)| **count)
+            .map(|(title, _)| title.clone())
+    }
+
+    /// Stop counting time towards the session until thawed.
+    fn freeze(&mut self) {
+        self.frozen_since.get_or_insert_with(Instant::now);
+    }
+
+    /// Resume counting time towards the session.
+    fn thaw(&mut self) {
+        if let Some(since) = self.frozen_since.take() {
+            self.excluded += since.elapsed();
+        }
+    }
+}
+
+/// Active watches and the reasons for which tracking may be halted.
+#[derive(Default)]
+pub struct Tracker {
+    pub watches: ProcessWatchMap,
+
+    /// Tracking has been paused by the user.
+    pub paused: bool,
+
+    /// System is in standby or hibernation.
+    pub suspended: bool,
+
+    /// Session is a remote desktop session and remote time is excluded.
+    pub remote: bool,
+
+    /// When the foreground process was last checked.
+    last_focus_poll: Option<Instant>,
+
+    /// Outcome of the most recent submission.
+    pub last_submission: Option<SubmissionStatus>,
+
+    /// Record of active watches for recovering from crashes.
+    pub journal: Option<Journal>,
+}
+
+impl Tracker {
+    fn is_frozen(&self) -> bool {
+        self.paused || self.suspended || self.remote
+    }
+
+    /// Apply a state change and freeze or thaw every watch if tracking
+    /// stopped or resumed as a result.
+    pub fn update(&mut self, change: impl FnOnce(&mut Self)) {
+        let was_frozen = self.is_frozen();
+        change(self);
+        match (was_frozen, self.is_frozen()) {
+            (false, true) => self.watches.values_mut().for_each(Watch::freeze),
+            (true, false) => self.watches.values_mut().for_each(Watch::thaw),
+            _ => {}
+        }
+    }
+
+    /// Find the session key of the watch that the process belongs to.
+    fn find_key(&self, process_id: u32) -> Option<String> {
+        self.watches
+            .iter()
+            .find(|(_, watch)| watch.process_ids.contains(&process_id))
+            .map(|(key, _)| key.clone())
+    }
+
+    /// Add a process to an existing watch for the same executable, if any.
+    /// Returns `false` if there is no such watch.
+    pub fn join(&mut self, process: &win::Process) -> bool {
+        let key = session_key(&process.name, process.executable_path.as_deref());
+        let Some(watch) = self.watches.get_mut(&key) else {
+            return false;
+        };
+        watch.process_ids.insert(process.process_id);
+        self.save_journal();
+        true
+    }
+
+    pub fn insert(&mut self, mut watch: Watch) {
+        if self.is_frozen() {
+            watch.freeze();
+        }
+        let key = session_key(&watch.executable, watch.path.as_deref());
+        self.watches.insert(key, watch);
+        self.save_journal();
+    }
+
+    /// Remove a process from its watch, returning the watch if this was the
+    /// last running process of the session.
+    pub fn remove(&mut self, process_id: u32) -> Option<Watch> {
+        let key = self.find_key(process_id)?;
+        let watch = self.watches.get_mut(&key)?;
+        watch.process_ids.remove(&process_id);
+        if !watch.process_ids.is_empty() {
+            self.save_journal();
+            return None;
+        }
+        let mut watch = self.watches.remove(&key)?;
+        watch.thaw();
+        self.save_journal();
+        Some(watch)
+    }
+
+    /// Write the active watches to the journal, if enabled.
+    pub fn save_journal(&self) {
+        if let Some(journal) = &self.journal {
+            journal.write(&self.watches);
+        }
+    }
+
+    /// Record the current main window title of every watch.
+    pub fn sample_window_titles(&mut self) {
+        if self.is_frozen() {
+            return;
+        }
+        for watch in self.watches.values_mut() {
+            if let Some(title) = win::main_window_title(&watch.process_ids) {
+                *watch.window_titles.entry(title).or_default() += 1;
+            }
+        }
+    }
+
+    /// Check whether the session is remote and tag or exclude the time
+    /// according to the configuration.
+    pub fn poll_remote(&mut self, mode: RemoteSessionMode) {
+        let remote = mode != RemoteSessionMode::Track && win::is_remote_session();
+        if mode == RemoteSessionMode::Tag && remote {
+            for watch in self.watches.values_mut() {
+                if !watch.tags.iter().any(|tag| tag == REMOTE_TAG) {
+                    watch.tags.push(REMOTE_TAG.to_string());
+                }
+            }
+        }
+
+        // Only excluded remote time freezes the watches.
+        let excluded = mode == RemoteSessionMode::Exclude && remote;
+        if excluded != self.remote {
+            if excluded {
+                info!("Remote session detected, pausing watches");
+            } else {
+                info!("Remote session ended, continuing watches");
+            }
+            self.update(|tracker| tracker.remote = excluded);
+        }
+    }
+
+    /// Credit the time since the previous poll to the foreground process.
+    pub fn poll_focus(&mut self) {
+        let now = Instant::now();
+        let Some(last_poll) = self.last_focus_poll.replace(now) else {
+            return;
+        };
+        if self.is_frozen() {
+            return;
+        }
+        // Polls may have been delayed by sleep or a busy runtime, and that
+        // delay can't be attributed to anything with certainty.
+        let elapsed = (now - last_poll).min(FOCUS_POLL_INTERVAL * 2);
+        let Some(process_id) = win::foreground_process_id() else {
+            return;
+        };
+        if let Some(key) = self.find_key(process_id) {
+            if let Some(watch) = self.watches.get_mut(&key) {
+                watch.focused += elapsed;
+            }
+        }
+    }
+}
@@ -1,14 +1,47 @@
 use futures::Stream;
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+use crate::naming;
 use log::{debug, warn};
 use serde::Deserialize;
 use windows::{
-    core::{HSTRING, PCWSTR},
-    Win32::Storage::FileSystem::{GetFileVersionInfoSizeW, GetFileVersionInfoW, VerQueryValueW},
+    core::{HSTRING, PCWSTR, PWSTR},
+    Data::Xml::Dom::XmlDocument,
+    Networking::Connectivity::{NetworkCostType, NetworkInformation},
+    Win32::{
+        Foundation::{
+            CloseHandle, GetLastError, BOOL, ERROR_ALREADY_EXISTS, ERROR_FILE_NOT_FOUND,
+            ERROR_INSUFFICIENT_BUFFER, HANDLE, HWND, LPARAM, WIN32_ERROR,
+        },
+        Security::{
+            Credentials::{CredFree, CredReadW, CREDENTIALW, CRED_TYPE_GENERIC},
+            PSID,
+        },
+        Storage::{
+            FileSystem::{GetFileVersionInfoSizeW, GetFileVersionInfoW, VerQueryValueW},
+            Packaging::Appx::{GetApplicationUserModelId, GetPackageFamilyName},
+        },
+        System::{
+            EventLog::{
+                DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE,
+                EVENTLOG_WARNING_TYPE,
+            },
+            Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS},
+            Registry::{RegGetValueW, HKEY, RRF_RT_REG_SZ},
+            Threading::{
+                CreateMutexW, OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+                PROCESS_QUERY_LIMITED_INFORMATION,
+            },
+        },
+        UI::WindowsAndMessaging::{
+            EnumWindows, GetForegroundWindow, GetSystemMetrics, GetWindow, GetWindowTextW,
+            GetWindowThreadProcessId, IsWindowVisible, GW_OWNER, SM_REMOTESESSION,
+        },
+    },
 };
+
 use wmi::{COMLibrary, FilterValue, WMIConnection, WMIError};
 
 const FALLBACK_LANG_CODES: [(u16, u16); 6] = [
@@ -20,8 +53,82 @@ const FALLBACK_LANG_CODES: [(u16, u16); 6] = [
     (0x0000, 0x04B0), // Neutral Unicode
 ];
 
+/// Preferred language for reading version information, given in config as
+/// a hex language ID such as `0411`, optionally followed by a code page such
+/// as `041103a4`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LanguagePreference {
+    language: u16,
+    code_page: Option<u16>,
+}
+
+impl LanguagePreference {
+    fn parse(value: &str) -> Option<Self> {
+        let parse_hex = |value: &str| u16::from_str_radix(value, 16).ok();
+        match value.len() {
+            4 => Some(Self {
+                language: parse_hex(value)?,
+                code_page: None,
+            }),
+            8 => Some(Self {
+                language: parse_hex(&value[..4])?,
+                code_page: Some(parse_hex(&value[4..])?),
+            }),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, lang_code_page: &(u16, u16)) -> bool {
+        self.language == lang_code_page.0
+            && self
+                .code_page
+                .is_none_or(|code_page| code_page == lang_code_page.1)
+    }
+}
+
+impl<'de> Deserialize<'de> for LanguagePreference {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        LanguagePreference::parse(&value).ok_or_else(|| {
+            serde::de::Error::custom(format!("invalid language {}, expected e.g. 0411", value))
+        })
+    }
+}
+
+/// Order the translations of the version info so that preferred languages
+/// come first. Preferences with a code page are tried even if the executable
+/// doesn't list them, as the translation table is not always accurate.
+fn sort_lang_code_pages(
+    lang_code_pages: Vec<(u16, u16)>,
+    preferences: &[LanguagePreference],
+) -> Vec<(u16, u16)> {
+    let mut sorted = Vec::with_capacity(lang_code_pages.len());
+    for preference in preferences {
+        if let Some(code_page) = preference.code_page {
+            sorted.push((preference.language, code_page));
+        }
+        sorted.extend(
+            lang_code_pages
+                .iter()
+                .filter(|lang_code_page| preference.matches(lang_code_page)),
+        );
+    }
+    sorted.extend(lang_code_pages);
+    let mut seen = HashSet::new();
+    sorted.retain(|lang_code_page| seen.insert(*lang_code_page));
+    sorted
+}
+
 pub type ProcessStartResult = Result<ProcessStartEvent, WMIError>;
 pub type ProcessEndResult = Result<ProcessEndEvent, WMIError>;
+pub type PowerEventResult = Result<PowerManagementEvent, WMIError>;
+
+/// Win32_PowerManagementEvent event type for entering suspend.
+pub const POWER_EVENT_SUSPEND: u16 = 4;
+/// Win32_PowerManagementEvent event type for resuming from suspend.
+pub const POWER_EVENT_RESUME_SUSPEND: u16 = 7;
+/// Win32_PowerManagementEvent event type for automatic resume.
+const POWER_EVENT_RESUME_AUTOMATIC: u16 = 18;
 
 #[derive(Deserialize, Debug)]
 #[serde(rename = "__InstanceCreationEvent")]
@@ -36,6 +143,28 @@ pub struct ProcessEndEvent {
     pub target_instance: Process,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename = "Win32_PowerManagementEvent")]
+#[serde(rename_all = "PascalCase")]
+pub struct PowerManagementEvent {
+    pub event_type: u16,
+}
+
+impl PowerManagementEvent {
+    /// System is about to enter standby or hibernation.
+    pub fn is_suspend(&self) -> bool {
+        self.event_type == POWER_EVENT_SUSPEND
+    }
+
+    /// System has woken up from standby or hibernation.
+    pub fn is_resume(&self) -> bool {
+        matches!(
+            self.event_type,
+            POWER_EVENT_RESUME_SUSPEND | POWER_EVENT_RESUME_AUTOMATIC
+        )
+    }
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename = "Win32_Process")]
 #[serde(rename_all = "PascalCase")]
@@ -43,72 +172,192 @@ pub struct Process {
     pub process_id: u32,
     pub name: String,
     pub executable_path: Option<String>,
+    pub command_line: Option<String>,
     parent_process_id: u32,
 }
 
-fn read_product_name(
-    version_info_buffer: &mut Vec<u8>,
+impl Process {
+    /// Process like WMI would report it, for scripted events.
+    pub fn scripted(process_id: u32, executable_path: &str, command_line: Option<&str>) -> Self {
+        let path = Path::new(executable_path);
+        let name = path
+            .file_name()
+            .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+        Process {
+            process_id,
+            name,
+            executable_path: (!executable_path.is_empty()).then(|| executable_path.to_string()),
+            command_line: command_line.map(str::to_string),
+            parent_process_id: 0,
+        }
+    }
+}
+
+/// Fields read from the string table of the executable's version information.
+#[derive(Debug, Default)]
+pub struct VersionInfo {
+    /// First usable value of the configured name fields.
+    pub name: Option<String>,
+    pub product_version: Option<String>,
+    pub file_version: Option<String>,
+}
+
+/// Identity of a packaged (UWP / Microsoft Store) application.
+#[derive(Debug)]
+pub struct PackageInfo {
+    pub family_name: String,
+    pub app_user_model_id: Option<String>,
+
+    /// Display name from the package manifest.
+    pub display_name: Option<String>,
+}
+
+/// Call a packaging function that fills a string buffer, growing the buffer
+/// when it is too small.
+fn read_package_string(
+    handle: HANDLE,
+    function: fn(HANDLE, *mut u32, PWSTR) -> WIN32_ERROR,
+) -> Option<String> {
+    let mut length = 0;
+    let result = function(handle, &mut length, PWSTR::null());
+    if result != ERROR_INSUFFICIENT_BUFFER {
+        return None;
+    }
+    let mut buffer = vec![0u16; length as usize];
+    let result = function(handle, &mut length, PWSTR::from_raw(buffer.as_mut_ptr()));
+    if result.is_err() || length == 0 {
+        return None;
+    }
+    Some(String::from_utf16_lossy(&buffer[..length as usize - 1]))
+}
+
+/// Read the display name from the AppxManifest.xml of the package the
+/// executable belongs to.
+///
+/// Names given as `ms-resource:` references would need the package resource
+/// index to resolve, so those are ignored.
+fn read_manifest_display_name(executable_path: &Path) -> Option<String> {
+    let manifest_path = executable_path
+        .ancestors()
+        .skip(1)
+        .map(|directory| directory.join("AppxManifest.xml"))
+        .find(|path: &PathBuf| path.is_file())?;
+    let manifest = std::fs::read_to_string(&manifest_path)
+        .map_err(|error| debug!("Could not read {}: {}", manifest_path.display(), error))
+        .ok()?;
+    let document = XmlDocument::new().ok()?;
+    document.LoadXml(&HSTRING::from(manifest)).ok()?;
+    let display_name = document
+        .GetElementsByTagName(&HSTRING::from("DisplayName"))
+        .and_then(|nodes| nodes.Item(0))
+        .and_then(|node| node.InnerText())
+        .ok()?
+        .to_string();
+    let display_name = display_name.trim();
+    if display_name.is_empty() || display_name.starts_with("ms-resource:") {
+        return None;
+    }
+    Some(display_name.to_string())
+}
+
+fn read_string_value(
+    version_info_buffer: &mut [u8],
     lang_code_page: &(u16, u16),
+    field: &str,
 ) -> Result<String, ()> {
     let sub_block = format!(
-        "\\StringFileInfo\\{:04x}{:04x}\\ProductName\0",
-        lang_code_page.0, lang_code_page.1,
+        "\\StringFileInfo\\{:04x}{:04x}\\{}\0",
+        lang_code_page.0, lang_code_page.1, field,
     )
     .encode_utf16()
     .collect::<Vec<u16>>();
-    let mut product_name_ptr = std::ptr::null_mut();
-    let mut product_name_length = 0;
+    let mut value_ptr = std::ptr::null_mut();
+    let mut value_length = 0;
     unsafe {
         let query_success = VerQueryValueW(
             version_info_buffer.as_mut_ptr() as *mut std::ffi::c_void,
             PCWSTR::from_raw(sub_block.as_ptr()),
-            &mut product_name_ptr,
-            &mut product_name_length,
+            &mut value_ptr,
+            &mut value_length,
         )
         .as_bool();
         if !query_success {
             debug!(
-                "Could not retrieve product name for language {:04x}{:04x}: \
-                        couldn't query product name",
-                lang_code_page.0, lang_code_page.1
+                "Could not retrieve {} for language {:04x}{:04x}: \
+                        couldn't query value",
+                field, lang_code_page.0, lang_code_page.1
             );
             return Err(());
         }
     }
-    if product_name_length == 0 {
+    if value_length == 0 {
         debug!(
-            "Could not retrieve product name for language {:04x}{:04x}: \
-                    no product name",
-            lang_code_page.0, lang_code_page.1
+            "Could not retrieve {} for language {:04x}{:04x}: \
+                    no value",
+            field, lang_code_page.0, lang_code_page.1
         );
         return Err(());
     }
-    let product_name = unsafe {
-        std::slice::from_raw_parts(product_name_ptr.cast(), product_name_length as usize - 1)
-    };
-    let product_name = String::from_utf16_lossy(product_name);
-    return Ok(product_name);
+    let value = unsafe { std::slice::from_raw_parts(value_ptr.cast(), value_length as usize - 1) };
+    let value = String::from_utf16_lossy(value);
+    return Ok(value);
+}
+
+/// Read a string value using the first language that has it.
+fn read_string_value_any(
+    version_info_buffer: &mut [u8],
+    lang_code_pages: &[(u16, u16)],
+    field: &str,
+) -> Option<String> {
+    // In case none of the languages in \VarFileInfo\Translation return any
+    // useful data, which is completely possible because Windows doesn't
+    // really care about things existing, try some fallback language codes
+    // that might actually exist.
+    // This for example fixes reading Forza Horizon 4, which will not return
+    // anything with the language codes returned by \VarFileInfo\Translation.
+    for lang_code_page in lang_code_pages.iter().chain(FALLBACK_LANG_CODES.iter()) {
+        match read_string_value(version_info_buffer, lang_code_page, field) {
+            Ok(value) => return Some(value),
+            Err(_) => {
+                debug!(
+                    "Could not find {} for language \"{:04x}{:04x}\"",
+                    field, lang_code_page.0, lang_code_page.1,
+                );
+            }
+        }
+    }
+    return None;
 }
 
 impl Process {
-    /// Fetch the executable product name for prettier reporting.
-    pub fn get_display_name(&self) -> Option<String> {
+    /// Fetch the executable version information for prettier reporting.
+    ///
+    /// The name is taken from the first of `name_fields` that has a value
+    /// that isn't one of `generic_names`. Languages are tried in the order of
+    /// `languages` before the rest.
+    pub fn get_version_info(
+        &self,
+        name_fields: &[String],
+        generic_names: &[String],
+        languages: &[LanguagePreference],
+    ) -> VersionInfo {
         let executable_path = match &self.executable_path {
             Some(path) => Path::new(path),
-            None => return None,
+            None => return VersionInfo::default(),
         };
         let filename = &HSTRING::from(executable_path.as_os_str());
 
         let version_info_size = unsafe { GetFileVersionInfoSizeW(filename, None) };
         if version_info_size == 0 {
             warn!(
-                "Could not retrieve product name: \
-                could not get version info size"
+                "Could not retrieve version info for {}: \
+                could not get version info size",
+                executable_path.display()
             );
-            return None;
+            return VersionInfo::default();
         }
 
-        let mut version_info_buffer = Vec::<u8>::with_capacity(version_info_size as usize);
+        let mut version_info_buffer = vec![0u8; version_info_size as usize];
         unsafe {
             let version_info_success = GetFileVersionInfoW(
                 filename,
@@ -118,11 +367,11 @@ impl Process {
             );
             if version_info_success.is_err() {
                 warn!(
-                    "Could not retrieve product name for {}: \
+                    "Could not retrieve version info for {}: \
                     could not get version info",
                     executable_path.display()
                 );
-                return None;
+                return VersionInfo::default();
             };
         }
 
@@ -138,62 +387,363 @@ impl Process {
             .as_bool();
             if !query_success {
                 warn!(
-                    "Could not retrieve product name for {}: \
+                    "Could not retrieve version info for {}: \
                     couldn't query translation info",
                     executable_path.display()
                 );
-                return None;
+                return VersionInfo::default();
             }
         }
         if lang_code_pages_length == 0 {
             warn!(
-                "Could not retrieve product name for {}: no translation info",
+                "Could not retrieve version info for {}: no translation info",
                 executable_path.display()
             );
-            return None;
+            return VersionInfo::default();
         }
+        // The length is in bytes, and each entry is a pair of u16 values.
         let lang_code_pages = unsafe {
             std::slice::from_raw_parts::<(u16, u16)>(
                 lang_code_pages_ptr.cast(),
-                lang_code_pages_length as usize,
+                lang_code_pages_length as usize / std::mem::size_of::<(u16, u16)>(),
             )
+        }
+        .to_vec();
+        let lang_code_pages = sort_lang_code_pages(lang_code_pages, languages);
+
+        let version_info = VersionInfo {
+            name: name_fields.iter().find_map(|field| {
+                read_string_value_any(&mut version_info_buffer, &lang_code_pages, field)
+                    .filter(|value| !naming::is_generic_name(value, generic_names))
+            }),
+            product_version: read_string_value_any(
+                &mut version_info_buffer,
+                &lang_code_pages,
+                "ProductVersion",
+            ),
+            file_version: read_string_value_any(
+                &mut version_info_buffer,
+                &lang_code_pages,
+                "FileVersion",
+            ),
         };
+        if version_info.name.is_none() {
+            warn!("Could not determine name for {}", executable_path.display());
+        }
+        return version_info;
+    }
+}
 
-        for lang_code_page in lang_code_pages {
-            match read_product_name(&mut version_info_buffer, lang_code_page) {
-                Ok(product_name) => return Some(product_name),
-                Err(_) => {
+impl Process {
+    /// Package identity of the process, if it is a packaged application.
+    pub fn get_package_info(&self) -> Option<PackageInfo> {
+        let handle =
+            unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, self.process_id) }
+                .map_err(|error| {
                     debug!(
-                        "Could not find product name for language \"{:04x}{:04x}\"",
-                        lang_code_page.0, lang_code_page.1,
-                    );
-                }
-            }
+                        "Could not open process {} ({}): {}",
+                        self.name, self.process_id, error
+                    )
+                })
+                .ok()?;
+        let family_name = read_package_string(handle, |handle, length, buffer| unsafe {
+            GetPackageFamilyName(handle, length, buffer)
+        });
+        let app_user_model_id = read_package_string(handle, |handle, length, buffer| unsafe {
+            GetApplicationUserModelId(handle, length, buffer)
+        });
+        let _ = unsafe { CloseHandle(handle) };
+
+        Some(PackageInfo {
+            family_name: family_name?,
+            app_user_model_id,
+            display_name: self
+                .executable_path
+                .as_deref()
+                .and_then(|path| read_manifest_display_name(Path::new(path))),
+        })
+    }
+}
+
+/// Path in a form suitable for comparisons: junctions, symbolic links and
+/// 8.3 short names resolved when the path exists, without the verbatim
+/// prefix, lowercased and without trailing separators.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let resolved = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let resolved = resolved.to_string_lossy();
+    let resolved = match resolved.strip_prefix(r"\\?\UNC\") {
+        Some(share) => format!(r"\\{}", share),
+        None => resolved
+            .strip_prefix(r"\\?\")
+            .unwrap_or(&resolved)
+            .to_string(),
+    };
+    let lowercase = resolved.replace('/', "\\").to_lowercase();
+    let trimmed = lowercase.trim_end_matches('\\');
+    // Keep the separator of a drive root so that it remains absolute.
+    if trimmed.ends_with(':') {
+        return PathBuf::from(format!("{}\\", trimmed));
+    }
+    PathBuf::from(trimmed)
+}
+
+/// Process with the given ID is running the given executable. Process IDs
+/// are reused, so the executable name is checked as well.
+pub fn is_running(process_id: u32, executable: &str) -> bool {
+    let Ok(handle) = (unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, process_id) })
+    else {
+        return false;
+    };
+    let mut buffer = vec![0u16; 1024];
+    let mut length = buffer.len() as u32;
+    let result = unsafe {
+        QueryFullProcessImageNameW(
+            handle,
+            PROCESS_NAME_WIN32,
+            PWSTR::from_raw(buffer.as_mut_ptr()),
+            &mut length,
+        )
+    };
+    let _ = unsafe { CloseHandle(handle) };
+    if result.is_err() {
+        return false;
+    }
+    let path = String::from_utf16_lossy(&buffer[..length as usize]);
+    Path::new(&path)
+        .file_name()
+        .is_some_and(|name| name.eq_ignore_ascii_case(executable))
+}
+
+/// Interactive session is a Remote Desktop session.
+pub fn is_remote_session() -> bool {
+    unsafe { GetSystemMetrics(SM_REMOTESESSION) != 0 }
+}
+
+/// System is running on battery power.
+pub fn is_on_battery() -> bool {
+    let mut status = SYSTEM_POWER_STATUS::default();
+    if unsafe { GetSystemPowerStatus(&mut status) }.is_err() {
+        return false;
+    }
+    // 0 is offline, 1 online and 255 unknown.
+    status.ACLineStatus == 0
+}
+
+/// Internet connection is metered, roaming or over its data limit.
+pub fn is_metered_connection() -> bool {
+    let cost = NetworkInformation::GetInternetConnectionProfile()
+        .and_then(|profile| profile.GetConnectionCost());
+    let Ok(cost) = cost else {
+        return false;
+    };
+    let metered = cost.NetworkCostType().is_ok_and(|cost_type| {
+        cost_type == NetworkCostType::Fixed || cost_type == NetworkCostType::Variable
+    });
+    metered || cost.Roaming().unwrap_or(false) || cost.OverDataLimit().unwrap_or(false)
+}
+
+/// Process ID of the process owning the current foreground window.
+pub fn foreground_process_id() -> Option<u32> {
+    let mut process_id = 0;
+    unsafe {
+        let window = GetForegroundWindow();
+        if window.is_invalid() {
+            return None;
         }
+        GetWindowThreadProcessId(window, Some(&mut process_id));
+    }
+    if process_id == 0 {
+        return None;
+    }
+    Some(process_id)
+}
 
-        // In case none of the languages in \VarFileInfo\Translation return any
-        // useful data, which is completely possible because Windows doesn't
-        // really care about things existing, try some fallback language codes
-        // that might actually exist.
-        // This for example fixes reading Forza Horizon 4, which will not return
-        // anything with the language codes returned by \VarFileInfo\Translation.
-        for lang_code_page in FALLBACK_LANG_CODES {
-            match read_product_name(&mut version_info_buffer, &lang_code_page) {
-                Ok(product_name) => return Some(product_name),
-                Err(_) => {
-                    debug!(
-                        "Could not find product name for language \"{:04x}{:04x}\"",
-                        lang_code_page.0, lang_code_page.1,
-                    );
-                }
-            }
+struct WindowSearch<'a> {
+    process_ids: &'a HashSet<u32>,
+    title: Option<String>,
+}
+
+unsafe extern "system" fn find_main_window(window: HWND, search: LPARAM) -> BOOL {
+    let search = &mut *(search.0 as *mut WindowSearch);
+    let mut process_id = 0;
+    GetWindowThreadProcessId(window, Some(&mut process_id));
+    // Main windows are visible top-level windows without an owner.
+    if !search.process_ids.contains(&process_id)
+        || !IsWindowVisible(window).as_bool()
+        || GetWindow(window, GW_OWNER).is_ok()
+    {
+        return true.into();
+    }
+    let mut buffer = [0u16; 512];
+    let length = GetWindowTextW(window, &mut buffer);
+    if length <= 0 {
+        return true.into();
+    }
+    search.title = Some(String::from_utf16_lossy(&buffer[..length as usize]));
+    false.into()
+}
+
+/// Title of the main window of any of the given processes.
+pub fn main_window_title(process_ids: &HashSet<u32>) -> Option<String> {
+    let mut search = WindowSearch {
+        process_ids,
+        title: None,
+    };
+    // EnumWindows reports an error when the callback stops the enumeration
+    // early, which is what happens when a title is found.
+    let _ = unsafe {
+        EnumWindows(
+            Some(find_main_window),
+            LPARAM(&mut search as *mut WindowSearch as isize),
+        )
+    };
+    search
+        .title
+        .map(|title| title.trim().to_string())
+        .filter(|title| !title.is_empty())
+}
+
+/// String value from the registry, `None` if the key or the value doesn't
+/// exist.
+pub fn read_registry_string(
+    hkey: HKEY,
+    key: &str,
+    value: &str,
+) -> windows::core::Result<Option<String>> {
+    let key = HSTRING::from(key);
+    let value = HSTRING::from(value);
+    let mut size = 0u32;
+    let result = unsafe {
+        RegGetValueW(
+            hkey,
+            &key,
+            &value,
+            RRF_RT_REG_SZ,
+            None,
+            None,
+            Some(&mut size),
+        )
+    };
+    if result == ERROR_FILE_NOT_FOUND {
+        return Ok(None);
+    }
+    result.ok()?;
+    let mut data = vec![0u16; (size as usize).div_ceil(std::mem::size_of::<u16>())];
+    unsafe {
+        RegGetValueW(
+            hkey,
+            &key,
+            &value,
+            RRF_RT_REG_SZ,
+            None,
+            Some(data.as_mut_ptr().cast()),
+            Some(&mut size),
+        )
+    }
+    .ok()?;
+    let length = data.iter().position(|c| *c == 0).unwrap_or(data.len());
+    Ok(Some(String::from_utf16_lossy(&data[..length])))
+}
+
+/// Read the password of a generic credential from the Windows Credential Manager.
+pub fn read_credential(target: &str) -> Option<String> {
+    let mut credential_ptr: *mut CREDENTIALW = std::ptr::null_mut();
+    unsafe {
+        if let Err(error) = CredReadW(
+            &HSTRING::from(target),
+            CRED_TYPE_GENERIC,
+            0,
+            &mut credential_ptr,
+        ) {
+            warn!("Could not read credential {}: {}", target, error);
+            return None;
+        }
+    }
+    let blob = unsafe {
+        let credential = &*credential_ptr;
+        std::slice::from_raw_parts(
+            credential.CredentialBlob,
+            credential.CredentialBlobSize as usize,
+        )
+        .to_vec()
+    };
+    unsafe { CredFree(credential_ptr as *const std::ffi::c_void) };
+
+    // Credentials stored with cmdkey or the Control Panel are UTF-16, but
+    // other tools may store plain UTF-8.
+    if blob.len() % 2 == 0 && blob.iter().skip(1).step_by(2).all(|b| *b == 0) {
+        let wide = blob
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect::<Vec<u16>>();
+        return String::from_utf16(&wide).ok();
+    }
+    String::from_utf8(blob).ok()
+}
+
+/// Named mutex held for the lifetime of the client to detect other instances.
+pub struct InstanceLock {
+    handle: HANDLE,
+}
+
+impl InstanceLock {
+    /// Create the named mutex. Returns `None` if another process already
+    /// holds it.
+    pub fn acquire(name: &str) -> windows::core::Result<Option<Self>> {
+        let handle = unsafe { CreateMutexW(None, true, &HSTRING::from(name))? };
+        if unsafe { GetLastError() } == ERROR_ALREADY_EXISTS {
+            let _ = unsafe { CloseHandle(handle) };
+            return Ok(None);
         }
+        Ok(Some(Self { handle }))
+    }
+}
 
-        warn!(
-            "Could not determine product name for {}",
-            executable_path.display()
-        );
-        return None;
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        let _ = unsafe { CloseHandle(self.handle) };
+    }
+}
+
+/// Event source for writing to the Windows Application event log.
+pub struct EventLog {
+    handle: HANDLE,
+}
+
+// The event log handle can be used from any thread.
+unsafe impl Send for EventLog {}
+unsafe impl Sync for EventLog {}
+
+impl EventLog {
+    pub fn register(source: &str) -> windows::core::Result<Self> {
+        let handle = unsafe { RegisterEventSourceW(None, &HSTRING::from(source))? };
+        Ok(Self { handle })
+    }
+
+    pub fn report(&self, level: log::Level, message: &str) -> windows::core::Result<()> {
+        let event_type = match level {
+            log::Level::Error => EVENTLOG_ERROR_TYPE,
+            _ => EVENTLOG_WARNING_TYPE,
+        };
+        let message = HSTRING::from(message);
+        unsafe {
+            ReportEventW(
+                self.handle,
+                event_type,
+                0,
+                0,
+                PSID::default(),
+                0,
+                Some(&[PCWSTR::from_raw(message.as_ptr())]),
+                None,
+            )
+        }
+    }
+}
+
+impl Drop for EventLog {
+    fn drop(&mut self) {
+        let _ = unsafe { DeregisterEventSource(self.handle) };
     }
 }
 
@@ -201,6 +751,7 @@ pub fn create_streams() -> Result<
     (
         impl Stream<Item = ProcessStartResult>,
         impl Stream<Item = ProcessEndResult>,
+        impl Stream<Item = PowerEventResult>,
     ),
     WMIError,
 > {
@@ -217,5 +768,45 @@ pub fn create_streams() -> Result<
         .async_filtered_notification::<ProcessStartEvent>(&filters, Some(Duration::from_secs(1)))?;
     let stream_end =
         wmi.async_filtered_notification::<ProcessEndEvent>(&filters, Some(Duration::from_secs(1)))?;
-    return Ok((stream_start, stream_end));
+    let stream_power = wmi.async_notification::<PowerManagementEvent>()?;
+    return Ok((stream_start, stream_end, stream_power));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_missing_path() {
+        let cases = [
+            (r"Q:\Games\Steam\", r"q:\games\steam"),
+            ("Q:/Games/Steam", r"q:\games\steam"),
+            (r"\\?\Q:\Games", r"q:\games"),
+            (r"Q:\", r"q:\"),
+            ("Q:", r"q:\"),
+        ];
+        for (path, output) in cases {
+            assert_eq!(
+                normalize_path(Path::new(path)),
+                PathBuf::from(output),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn normalize_existing_path() {
+        let directory =
+            std::env::temp_dir().join(format!("Beelzebub-Normalize-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let normalized = normalize_path(&directory);
+        let uppercase = normalize_path(Path::new(
+            &format!("{}\\", directory.display()).to_uppercase(),
+        ));
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(normalized, uppercase);
+        assert!(!normalized.to_string_lossy().starts_with(r"\\?\"));
+        assert!(normalized.ends_with(format!("beelzebub-normalize-{}", std::process::id())));
+    }
 }
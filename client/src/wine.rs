@@ -0,0 +1,301 @@
+use std::path::{Path, PathBuf};
+
+/// Executables of Wine that run the Windows executable given to them, and
+/// that games under Wine and Proton show up as.
+const LOADERS: &[&str] = &["wine", "wine64", "wine-preloader", "wine64-preloader"];
+
+/// Variables that Steam sets to the app ID of the game it starts, in order of
+/// preference. `SteamGameId` is a 64-bit ID for shortcuts to non-Steam games,
+/// which doesn't parse as an app ID.
+const STEAM_APP_ID_VARIABLES: &[&str] = &["SteamAppId", "SteamGameId"];
+
+/// Game run by Wine or Proton.
+#[derive(Debug, PartialEq)]
+pub struct WineGame {
+    /// File name of the Windows executable, e.g. `eldenring.exe`.
+    pub executable: String,
+
+    /// Path of the executable as Wine was given it, either a Windows path or
+    /// a path on the host.
+    pub path: String,
+
+    /// App ID of the game, if it was started by Steam.
+    pub steam_app_id: Option<u32>,
+
+    /// `steamapps` directory of the Steam library that the game is in.
+    steam_apps: Option<PathBuf>,
+}
+
+/// Last component of the path, with either kind of separator.
+fn file_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+/// Executable of Wine itself or of the Windows installation in the prefix,
+/// such as `explorer.exe` and the `steam.exe` that Proton starts games with.
+fn is_system_executable(path: &str) -> bool {
+    let path = path.to_lowercase().replace('/', "\\");
+    path.contains("\\windows\\")
+}
+
+/// Process is Wine running a Windows executable. Wine replaces the command
+/// line of the processes it starts with the Windows path of the executable,
+/// so either the loader or an executable of Windows takes the first place.
+pub fn is_wine(arguments: &[String]) -> bool {
+    let Some(first) = arguments.first() else {
+        return false;
+    };
+    LOADERS.contains(&file_name(first)) || first.to_lowercase().ends_with(".exe")
+}
+
+/// App ID of the Steam game from the environment of the process. Proton
+/// puts the prefix of every game in `steamapps/compatdata/<app ID>`, which
+/// identifies the game when Steam didn't set the variables.
+fn steam_app_id(environment: &[(String, String)]) -> Option<u32> {
+    let variable = |name: &str| {
+        environment
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let parse = |value: &str| value.parse::<u32>().ok().filter(|id| *id != 0);
+    STEAM_APP_ID_VARIABLES
+        .iter()
+        .find_map(|name| variable(name).and_then(parse))
+        .or_else(|| {
+            let prefix = Path::new(variable("STEAM_COMPAT_DATA_PATH")?);
+            parse(prefix.file_name()?.to_str()?)
+        })
+}
+
+/// `steamapps` directory that the Proton prefix of the process is in.
+fn steam_apps(environment: &[(String, String)]) -> Option<PathBuf> {
+    let (_, prefix) = environment
+        .iter()
+        .find(|(key, _)| key == "STEAM_COMPAT_DATA_PATH")?;
+    let compat_data = Path::new(prefix).parent()?;
+    if compat_data.file_name()? != "compatdata" {
+        return None;
+    }
+    Some(compat_data.parent()?.to_path_buf())
+}
+
+/// Game that the Wine process runs, from its arguments and environment. The
+/// game is the first Windows executable that isn't part of Wine or Proton.
+pub fn game(arguments: &[String], environment: &[(String, String)]) -> Option<WineGame> {
+    if !is_wine(arguments) {
+        return None;
+    }
+    let path = arguments
+        .iter()
+        .filter(|argument| argument.to_lowercase().ends_with(".exe"))
+        .find(|argument| !is_system_executable(argument))?;
+    Some(WineGame {
+        executable: file_name(path).to_string(),
+        path: path.clone(),
+        steam_app_id: steam_app_id(environment),
+        steam_apps: steam_apps(environment),
+    })
+}
+
+/// Value of the `name` key in a Steam app manifest.
+pub fn manifest_name(manifest: &str) -> Option<String> {
+    manifest.lines().find_map(|line| {
+        let value = line.trim().strip_prefix("\"name\"")?.trim();
+        let value = value.strip_prefix('"')?.strip_suffix('"')?;
+        let name = value.replace("\\\"", "\"").replace("\\\\", "\\");
+        (!name.trim().is_empty()).then_some(name)
+    })
+}
+
+impl WineGame {
+    /// Path of the executable on the host. Wine maps `Z:` to the root of the
+    /// file system, and the paths of other drives depend on the prefix.
+    pub fn host_path(&self) -> Option<PathBuf> {
+        if self.path.starts_with('/') {
+            return Some(PathBuf::from(&self.path));
+        }
+        let rest = self
+            .path
+            .strip_prefix("Z:\\")
+            .or_else(|| self.path.strip_prefix("z:\\"))?;
+        Some(PathBuf::from(format!("/{}", rest.replace('\\', "/"))))
+    }
+
+    /// Name of the Steam game from the manifest in its library.
+    pub fn steam_name(&self) -> Option<String> {
+        let manifest = self
+            .steam_apps
+            .as_ref()?
+            .join(format!("appmanifest_{}.acf", self.steam_app_id?));
+        manifest_name(&std::fs::read_to_string(manifest).ok()?)
+    }
+}
+
+/// Game of the process with the ID, if it is a Wine process. The arguments
+/// and environment are read from `/proc`, which only the user running the
+/// process and root can read the environment of.
+#[cfg(target_os = "linux")]
+pub fn process_game(process_id: u32) -> Option<WineGame> {
+    let read = |file: &str| std::fs::read(format!("/proc/{}/{}", process_id, file)).ok();
+    let split = |data: Vec<u8>| {
+        data.split(|byte| *byte == 0)
+            .filter(|part| !part.is_empty())
+            .map(|part| String::from_utf8_lossy(part).into_owned())
+            .collect::<Vec<String>>()
+    };
+    let arguments = split(read("cmdline")?);
+    let environment = read("environ")
+        .map(split)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|variable| {
+            let (key, value) = variable.split_once('=')?;
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect::<Vec<_>>();
+    game(&arguments, &environment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPAT_DATA: &str = "/home/user/.local/share/Steam/steamapps/compatdata/1245620";
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn environment(variables: &[(&str, &str)]) -> Vec<(String, String)> {
+        variables
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn game_of_proton() {
+        let arguments = strings(&[
+            "/home/user/.local/share/Steam/steamapps/common/Proton 9.0/files/bin/wine64",
+            "c:\\windows\\system32\\steam.exe",
+            "/home/user/.local/share/Steam/steamapps/common/ELDEN RING/Game/eldenring.exe",
+        ]);
+        let environment = environment(&[
+            ("SteamAppId", "1245620"),
+            ("STEAM_COMPAT_DATA_PATH", COMPAT_DATA),
+        ]);
+        let game = game(&arguments, &environment).unwrap();
+        assert_eq!(game.executable, "eldenring.exe");
+        assert_eq!(game.steam_app_id, Some(1245620));
+        assert_eq!(
+            game.host_path(),
+            Some(PathBuf::from(
+                "/home/user/.local/share/Steam/steamapps/common/ELDEN RING/Game/eldenring.exe"
+            ))
+        );
+        assert_eq!(
+            game.steam_apps,
+            Some(PathBuf::from("/home/user/.local/share/Steam/steamapps"))
+        );
+    }
+
+    #[test]
+    fn game_of_rewritten_command_line() {
+        let arguments = strings(&["Z:\\home\\user\\Games\\Hades\\Hades.exe", "-nosteam"]);
+        let game = game(&arguments, &[]).unwrap();
+        assert_eq!(game.executable, "Hades.exe");
+        assert_eq!(game.steam_app_id, None);
+        assert_eq!(
+            game.host_path(),
+            Some(PathBuf::from("/home/user/Games/Hades/Hades.exe"))
+        );
+    }
+
+    #[test]
+    fn game_of_other_drive() {
+        let arguments = strings(&["wine", "C:\\Program Files\\Game\\game.exe"]);
+        let game = game(&arguments, &[]).unwrap();
+        assert_eq!(game.executable, "game.exe");
+        assert_eq!(game.host_path(), None);
+    }
+
+    #[test]
+    fn no_game() {
+        for arguments in [
+            strings(&["C:\\windows\\system32\\winedevice.exe"]),
+            strings(&[
+                "/usr/bin/wine64-preloader",
+                "C:\\windows\\explorer.exe",
+                "/desktop",
+            ]),
+            strings(&["/usr/bin/firefox", "game.exe"]),
+            strings(&[]),
+        ] {
+            assert_eq!(game(&arguments, &[]), None, "{:?}", arguments);
+        }
+    }
+
+    #[test]
+    fn app_id_of_environment() {
+        let cases = [
+            (environment(&[("SteamAppId", "1245620")]), Some(1245620)),
+            (
+                environment(&[("SteamAppId", "0"), ("SteamGameId", "1145360")]),
+                Some(1145360),
+            ),
+            (
+                environment(&[("SteamGameId", "13835058055282163712")]),
+                None,
+            ),
+            (
+                environment(&[("STEAM_COMPAT_DATA_PATH", COMPAT_DATA)]),
+                Some(1245620),
+            ),
+            (environment(&[("WINEPREFIX", "/home/user/.wine")]), None),
+        ];
+        for (environment, output) in cases {
+            assert_eq!(steam_app_id(&environment), output, "{:?}", environment);
+        }
+    }
+
+    #[test]
+    fn name_of_manifest() {
+        let manifest = "\"AppState\"\n{\n\t\"appid\"\t\t\"1245620\"\n\t\"name\"\t\t\"ELDEN RING\"\n\t\"installdir\"\t\t\"ELDEN RING\"\n}\n";
+        assert_eq!(manifest_name(manifest), Some("ELDEN RING".to_string()));
+        let manifest = "\"AppState\"\n{\n\t\"name\"\t\t\"The \\\"Game\\\"\"\n}\n";
+        assert_eq!(manifest_name(manifest), Some("The \"Game\"".to_string()));
+        assert_eq!(
+            manifest_name("\"AppState\"\n{\n\t\"name\"\t\t\"\"\n}\n"),
+            None
+        );
+        assert_eq!(manifest_name("\"AppState\"\n{\n}\n"), None);
+    }
+
+    #[test]
+    fn name_of_steam_game() {
+        let steam_apps =
+            std::env::temp_dir().join(format!("beelzebub-wine-{}", std::process::id()));
+        std::fs::create_dir_all(&steam_apps).unwrap();
+        std::fs::write(
+            steam_apps.join("appmanifest_1245620.acf"),
+            "\"AppState\"\n{\n\t\"name\"\t\t\"ELDEN RING\"\n}\n",
+        )
+        .unwrap();
+        let game = WineGame {
+            executable: "eldenring.exe".to_string(),
+            path: "Z:\\games\\eldenring.exe".to_string(),
+            steam_app_id: Some(1245620),
+            steam_apps: Some(steam_apps.clone()),
+        };
+        let name = game.steam_name();
+        let other = WineGame {
+            steam_app_id: Some(1145360),
+            ..game
+        };
+        let other_name = other.steam_name();
+        std::fs::remove_dir_all(&steam_apps).unwrap();
+        assert_eq!(name, Some("ELDEN RING".to_string()));
+        assert_eq!(other_name, None);
+    }
+}
@@ -0,0 +1,2 @@
+ALTER TABLE events DROP COLUMN file_version;
+ALTER TABLE events DROP COLUMN product_version;
@@ -0,0 +1,2 @@
+ALTER TABLE events ADD COLUMN product_version VARCHAR NULL;
+ALTER TABLE events ADD COLUMN file_version VARCHAR NULL;
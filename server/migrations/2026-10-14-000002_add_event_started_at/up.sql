@@ -0,0 +1,3 @@
+ALTER TABLE events ADD COLUMN started_at TIMESTAMPTZ NULL;
+UPDATE events SET started_at = time - duration;
+ALTER TABLE events ALTER COLUMN started_at SET NOT NULL;
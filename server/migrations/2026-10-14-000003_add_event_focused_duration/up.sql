@@ -0,0 +1 @@
+ALTER TABLE events ADD COLUMN focused_duration INTERVAL NULL;
@@ -0,0 +1 @@
+ALTER TABLE events ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}';
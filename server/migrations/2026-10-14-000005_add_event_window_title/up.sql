@@ -0,0 +1 @@
+ALTER TABLE events ADD COLUMN window_title VARCHAR NULL;
@@ -0,0 +1,9 @@
+CREATE TABLE devices (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL,
+    nonce VARCHAR NOT NULL UNIQUE,
+    token VARCHAR NULL UNIQUE,
+    approved BOOLEAN DEFAULT false NOT NULL,
+    revoked BOOLEAN DEFAULT false NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT now() NOT NULL
+);
@@ -0,0 +1,9 @@
+CREATE TABLE users (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL UNIQUE,
+    leaderboard BOOLEAN DEFAULT false NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT now() NOT NULL
+);
+ALTER TABLE events ADD COLUMN user_id INTEGER NULL REFERENCES users (id);
+ALTER TABLE devices ADD COLUMN user_id INTEGER NULL REFERENCES users (id);
+CREATE INDEX events_user_id ON events (user_id);
@@ -0,0 +1 @@
+ALTER TABLE events ADD COLUMN device INTEGER NULL REFERENCES devices (id);
@@ -0,0 +1 @@
+ALTER TABLE events ADD COLUMN idempotency_key VARCHAR NULL UNIQUE;
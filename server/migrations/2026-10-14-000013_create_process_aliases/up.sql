@@ -0,0 +1,8 @@
+CREATE TABLE process_aliases (
+    id SERIAL PRIMARY KEY,
+    executable VARCHAR NOT NULL,
+    name VARCHAR NULL,
+    process INTEGER REFERENCES processes(id) ON DELETE CASCADE NOT NULL
+);
+
+CREATE UNIQUE INDEX unique_process_alias ON process_aliases (executable, name) NULLS NOT DISTINCT;
@@ -0,0 +1,7 @@
+CREATE TABLE process_categories (
+    process INTEGER REFERENCES processes(id) ON DELETE CASCADE NOT NULL,
+    category VARCHAR NOT NULL,
+    PRIMARY KEY (process, category)
+);
+
+CREATE INDEX process_categories_category ON process_categories (category);
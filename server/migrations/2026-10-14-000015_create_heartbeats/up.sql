@@ -0,0 +1,18 @@
+CREATE TABLE heartbeats (
+    session_id VARCHAR PRIMARY KEY,
+    executable VARCHAR NOT NULL,
+    name VARCHAR NULL,
+    started_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    duration INTERVAL NOT NULL,
+    focused_duration INTERVAL NULL,
+    product_version VARCHAR NULL,
+    file_version VARCHAR NULL,
+    tags TEXT[] DEFAULT '{}' NOT NULL,
+    window_title VARCHAR NULL,
+    api_key VARCHAR NULL,
+    user_id INTEGER REFERENCES users(id) NULL,
+    device INTEGER REFERENCES devices(id) NULL
+);
+
+CREATE INDEX heartbeats_updated_at ON heartbeats (updated_at);
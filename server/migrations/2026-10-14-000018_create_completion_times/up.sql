@@ -0,0 +1,10 @@
+-- Games looked up on HowLongToBeat. Games that weren't found have no game.
+CREATE TABLE completion_times (
+    process INTEGER PRIMARY KEY REFERENCES processes(id) ON DELETE CASCADE,
+    checked_at TIMESTAMPTZ NOT NULL,
+    game_id INTEGER NULL,
+    game VARCHAR NULL,
+    main_story INTERVAL NULL,
+    main_extra INTERVAL NULL,
+    completionist INTERVAL NULL
+);
@@ -0,0 +1,5 @@
+-- End of the last period each email report was sent for.
+CREATE TABLE sent_reports (
+    report VARCHAR PRIMARY KEY,
+    sent_until TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,14 @@
+-- Audit trail of edited and deleted events. The events are kept as JSON, so
+-- that the trail outlives them.
+CREATE TABLE event_changes (
+    id SERIAL PRIMARY KEY,
+    event INTEGER NOT NULL,
+    user_id INTEGER NULL,
+    action VARCHAR NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL,
+    changed_by VARCHAR NOT NULL,
+    old_event TEXT NOT NULL,
+    new_event TEXT NULL
+);
+
+CREATE INDEX event_changes_event ON event_changes (event);
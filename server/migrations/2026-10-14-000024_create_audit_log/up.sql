@@ -0,0 +1,9 @@
+-- Append-only log of mutating actions and who did them.
+CREATE TABLE audit_log (
+    id SERIAL PRIMARY KEY,
+    recorded_at TIMESTAMPTZ NOT NULL,
+    actor VARCHAR NOT NULL,
+    action VARCHAR NOT NULL,
+    target VARCHAR NULL,
+    details TEXT NULL
+);
@@ -0,0 +1,4 @@
+DROP TRIGGER events_daily_stats ON events;
+DROP FUNCTION maintain_daily_stats();
+DROP FUNCTION count_daily_stats(events, INTEGER);
+DROP TABLE daily_stats;
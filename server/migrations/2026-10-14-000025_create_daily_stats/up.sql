@@ -0,0 +1,53 @@
+-- Playtime and sessions per day in UTC, process, user and device, kept up to
+-- date by a trigger on events so that stats don't sum every event. Sessions
+-- without a user or device are counted under 0.
+CREATE TABLE daily_stats (
+    day DATE NOT NULL,
+    process INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    device INTEGER NOT NULL,
+    duration INTERVAL NOT NULL,
+    sessions INTEGER NOT NULL,
+    PRIMARY KEY (day, process, user_id, device)
+);
+
+INSERT INTO daily_stats (day, process, user_id, device, duration, sessions)
+SELECT (time AT TIME ZONE 'UTC')::date, process, COALESCE(user_id, 0), COALESCE(device, 0),
+       SUM(duration), COUNT(*)
+FROM events
+GROUP BY 1, 2, 3, 4;
+
+CREATE FUNCTION count_daily_stats(event events, change INTEGER) RETURNS VOID AS $$
+DECLARE
+    event_day DATE := (event.time AT TIME ZONE 'UTC')::date;
+BEGIN
+    INSERT INTO daily_stats (day, process, user_id, device, duration, sessions)
+    VALUES (event_day, event.process, COALESCE(event.user_id, 0), COALESCE(event.device, 0),
+            change * event.duration, change)
+    ON CONFLICT (day, process, user_id, device) DO UPDATE
+    SET duration = daily_stats.duration + excluded.duration,
+        sessions = daily_stats.sessions + excluded.sessions;
+    DELETE FROM daily_stats
+    WHERE day = event_day
+      AND process = event.process
+      AND user_id = COALESCE(event.user_id, 0)
+      AND device = COALESCE(event.device, 0)
+      AND sessions <= 0;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE FUNCTION maintain_daily_stats() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        PERFORM count_daily_stats(OLD, -1);
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        PERFORM count_daily_stats(NEW, 1);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER events_daily_stats
+AFTER INSERT OR UPDATE OR DELETE ON events
+FOR EACH ROW EXECUTE FUNCTION maintain_daily_stats();
@@ -0,0 +1,3 @@
+DROP TRIGGER archived_events_daily_stats ON archived_events;
+DROP FUNCTION count_daily_stats(archived_events, INTEGER);
+DROP TABLE archived_events;
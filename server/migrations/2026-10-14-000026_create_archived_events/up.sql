@@ -0,0 +1,46 @@
+-- Sessions of months moved out of events by `archiveAfterMonths`, and when
+-- they were moved, with IDs of their own. They keep counting in daily_stats.
+CREATE TABLE archived_events (
+    id SERIAL PRIMARY KEY,
+    time TIMESTAMPTZ NOT NULL,
+    process INTEGER NOT NULL REFERENCES processes (id),
+    duration INTERVAL NOT NULL,
+    product_version VARCHAR NULL,
+    file_version VARCHAR NULL,
+    started_at TIMESTAMPTZ NOT NULL,
+    focused_duration INTERVAL NULL,
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    window_title VARCHAR NULL,
+    api_key VARCHAR NULL,
+    user_id INTEGER NULL REFERENCES users (id),
+    device INTEGER NULL REFERENCES devices (id),
+    idempotency_key VARCHAR NULL UNIQUE,
+    imported BOOLEAN NOT NULL DEFAULT false,
+    archived_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX archived_events_time ON archived_events (time);
+
+-- Same as for events, picked by the row type of the trigger.
+CREATE FUNCTION count_daily_stats(event archived_events, change INTEGER) RETURNS VOID AS $$
+DECLARE
+    event_day DATE := (event.time AT TIME ZONE 'UTC')::date;
+BEGIN
+    INSERT INTO daily_stats (day, process, user_id, device, duration, sessions)
+    VALUES (event_day, event.process, COALESCE(event.user_id, 0), COALESCE(event.device, 0),
+            change * event.duration, change)
+    ON CONFLICT (day, process, user_id, device) DO UPDATE
+    SET duration = daily_stats.duration + excluded.duration,
+        sessions = daily_stats.sessions + excluded.sessions;
+    DELETE FROM daily_stats
+    WHERE day = event_day
+      AND process = event.process
+      AND user_id = COALESCE(event.user_id, 0)
+      AND device = COALESCE(event.device, 0)
+      AND sessions <= 0;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER archived_events_daily_stats
+AFTER INSERT OR UPDATE OR DELETE ON archived_events
+FOR EACH ROW EXECUTE FUNCTION maintain_daily_stats();
@@ -0,0 +1,4 @@
+ALTER TABLE archived_events DROP COLUMN note;
+ALTER TABLE archived_events DROP COLUMN manual;
+ALTER TABLE events DROP COLUMN note;
+ALTER TABLE events DROP COLUMN manual;
@@ -0,0 +1,8 @@
+-- Links that show the stats of a user, or of one game, without authentication.
+CREATE TABLE shares (
+    id SERIAL PRIMARY KEY,
+    token VARCHAR NOT NULL UNIQUE,
+    user_id INTEGER NULL REFERENCES users (id),
+    process VARCHAR NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,2 @@
+ALTER TABLE archived_events DROP COLUMN rating;
+ALTER TABLE events DROP COLUMN rating;
@@ -0,0 +1,50 @@
+CREATE TABLE processes (
+    id INTEGER AUTO_INCREMENT PRIMARY KEY,
+    executable VARCHAR(255) NOT NULL,
+    name VARCHAR(255) NULL,
+    -- NULLs are distinct in unique indexes, so index an empty name instead.
+    name_key VARCHAR(255) AS (IFNULL(name, '')) STORED,
+    export BOOLEAN DEFAULT true NOT NULL,
+    UNIQUE INDEX unique_process (executable, name_key)
+);
+
+CREATE TABLE users (
+    id INTEGER AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(255) NOT NULL UNIQUE,
+    leaderboard BOOLEAN DEFAULT false NOT NULL,
+    created_at DATETIME(6) NOT NULL
+);
+
+CREATE TABLE devices (
+    id INTEGER AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    nonce VARCHAR(255) NOT NULL UNIQUE,
+    token VARCHAR(255) NULL UNIQUE,
+    approved BOOLEAN DEFAULT false NOT NULL,
+    revoked BOOLEAN DEFAULT false NOT NULL,
+    created_at DATETIME(6) NOT NULL,
+    user_id INTEGER NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE TABLE events (
+    id INTEGER AUTO_INCREMENT PRIMARY KEY,
+    time DATETIME(6) NOT NULL,
+    process INTEGER NOT NULL,
+    duration BIGINT NOT NULL,
+    product_version VARCHAR(255) NULL,
+    file_version VARCHAR(255) NULL,
+    started_at DATETIME(6) NOT NULL,
+    focused_duration BIGINT NULL,
+    tags TEXT NOT NULL,
+    window_title TEXT NULL,
+    api_key VARCHAR(255) NULL,
+    user_id INTEGER NULL,
+    device INTEGER NULL,
+    idempotency_key VARCHAR(255) NULL UNIQUE,
+    FOREIGN KEY (process) REFERENCES processes (id),
+    FOREIGN KEY (user_id) REFERENCES users (id),
+    FOREIGN KEY (device) REFERENCES devices (id),
+    INDEX events_user_id (user_id),
+    INDEX events_time (time)
+);
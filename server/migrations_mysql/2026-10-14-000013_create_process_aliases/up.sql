@@ -0,0 +1,9 @@
+CREATE TABLE process_aliases (
+    id INTEGER AUTO_INCREMENT PRIMARY KEY,
+    executable VARCHAR(255) NOT NULL,
+    name VARCHAR(255) NULL,
+    name_key VARCHAR(255) AS (IFNULL(name, '')) STORED,
+    process INTEGER NOT NULL,
+    UNIQUE INDEX unique_process_alias (executable, name_key),
+    FOREIGN KEY (process) REFERENCES processes (id) ON DELETE CASCADE
+);
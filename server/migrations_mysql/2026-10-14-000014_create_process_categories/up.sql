@@ -0,0 +1,7 @@
+CREATE TABLE process_categories (
+    process INTEGER NOT NULL,
+    category VARCHAR(255) NOT NULL,
+    PRIMARY KEY (process, category),
+    INDEX process_categories_category (category),
+    FOREIGN KEY (process) REFERENCES processes (id) ON DELETE CASCADE
+);
@@ -0,0 +1,19 @@
+CREATE TABLE heartbeats (
+    session_id VARCHAR(255) PRIMARY KEY,
+    executable VARCHAR(255) NOT NULL,
+    name VARCHAR(255) NULL,
+    started_at DATETIME(6) NOT NULL,
+    updated_at DATETIME(6) NOT NULL,
+    duration BIGINT NOT NULL,
+    focused_duration BIGINT NULL,
+    product_version VARCHAR(255) NULL,
+    file_version VARCHAR(255) NULL,
+    tags TEXT NOT NULL,
+    window_title TEXT NULL,
+    api_key VARCHAR(255) NULL,
+    user_id INTEGER NULL,
+    device INTEGER NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id),
+    FOREIGN KEY (device) REFERENCES devices (id),
+    INDEX heartbeats_updated_at (updated_at)
+);
@@ -0,0 +1,2 @@
+-- Nothing set the flag before, so no process was chosen for export.
+UPDATE processes SET export = false;
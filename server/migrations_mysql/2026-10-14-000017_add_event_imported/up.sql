@@ -0,0 +1,3 @@
+ALTER TABLE events ADD COLUMN imported BOOLEAN DEFAULT false NOT NULL;
+-- Sessions imported from ActivityWatch before the flag existed.
+UPDATE events SET imported = true WHERE idempotency_key LIKE 'activitywatch:%';
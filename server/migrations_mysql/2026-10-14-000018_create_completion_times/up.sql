@@ -0,0 +1,11 @@
+-- Games looked up on HowLongToBeat. Games that weren't found have no game.
+CREATE TABLE completion_times (
+    process INTEGER PRIMARY KEY,
+    checked_at DATETIME(6) NOT NULL,
+    game_id INTEGER NULL,
+    game VARCHAR(255) NULL,
+    main_story BIGINT NULL,
+    main_extra BIGINT NULL,
+    completionist BIGINT NULL,
+    FOREIGN KEY (process) REFERENCES processes (id) ON DELETE CASCADE
+);
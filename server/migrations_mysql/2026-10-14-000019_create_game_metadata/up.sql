@@ -0,0 +1,10 @@
+-- Games looked up on IGDB. Games that weren't found have no title.
+CREATE TABLE game_metadata (
+    process INTEGER PRIMARY KEY,
+    checked_at DATETIME(6) NOT NULL,
+    igdb_id INTEGER NULL,
+    title VARCHAR(255) NULL,
+    genres TEXT NOT NULL,
+    cover_url VARCHAR(255) NULL,
+    FOREIGN KEY (process) REFERENCES processes (id) ON DELETE CASCADE
+);
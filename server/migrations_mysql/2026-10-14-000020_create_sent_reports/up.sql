@@ -0,0 +1,5 @@
+-- End of the last period each email report was sent for.
+CREATE TABLE sent_reports (
+    report VARCHAR(255) PRIMARY KEY,
+    sent_until DATETIME(6) NOT NULL
+);
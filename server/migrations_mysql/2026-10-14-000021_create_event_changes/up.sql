@@ -0,0 +1,13 @@
+-- Audit trail of edited and deleted events. The events are kept as JSON, so
+-- that the trail outlives them.
+CREATE TABLE event_changes (
+    id INTEGER AUTO_INCREMENT PRIMARY KEY,
+    event INTEGER NOT NULL,
+    user_id INTEGER NULL,
+    action VARCHAR(255) NOT NULL,
+    changed_at DATETIME(6) NOT NULL,
+    changed_by VARCHAR(255) NOT NULL,
+    old_event TEXT NOT NULL,
+    new_event TEXT NULL,
+    INDEX event_changes_event (event)
+);
@@ -0,0 +1,5 @@
+-- Start of the last period each goal was alerted for.
+CREATE TABLE goal_alerts (
+    goal VARCHAR(255) PRIMARY KEY,
+    period_start DATETIME(6) NOT NULL
+);
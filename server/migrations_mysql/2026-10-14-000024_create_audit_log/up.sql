@@ -0,0 +1,9 @@
+-- Append-only log of mutating actions and who did them.
+CREATE TABLE audit_log (
+    id INTEGER AUTO_INCREMENT PRIMARY KEY,
+    recorded_at DATETIME(6) NOT NULL,
+    actor VARCHAR(255) NOT NULL,
+    action VARCHAR(255) NOT NULL,
+    target VARCHAR(255) NULL,
+    details TEXT NULL
+);
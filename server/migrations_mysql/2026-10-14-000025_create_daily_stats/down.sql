@@ -0,0 +1,5 @@
+DROP TRIGGER events_daily_stats_update_new;
+DROP TRIGGER events_daily_stats_update_old;
+DROP TRIGGER events_daily_stats_delete;
+DROP TRIGGER events_daily_stats_insert;
+DROP TABLE daily_stats;
@@ -0,0 +1,50 @@
+-- Playtime and sessions per day in UTC, process, user and device, kept up to
+-- date by triggers on events so that stats don't sum every event. Sessions
+-- without a user or device are counted under 0. Triggers are single
+-- statements, so rows that drop to no sessions are left for stats to skip.
+CREATE TABLE daily_stats (
+    day DATE NOT NULL,
+    process INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    device INTEGER NOT NULL,
+    duration BIGINT NOT NULL,
+    sessions BIGINT NOT NULL,
+    PRIMARY KEY (day, process, user_id, device)
+);
+
+INSERT INTO daily_stats (day, process, user_id, device, duration, sessions)
+SELECT DATE(time), process, COALESCE(user_id, 0), COALESCE(device, 0), SUM(duration), COUNT(*)
+FROM events
+GROUP BY 1, 2, 3, 4;
+
+CREATE TRIGGER events_daily_stats_insert AFTER INSERT ON events
+FOR EACH ROW
+INSERT INTO daily_stats (day, process, user_id, device, duration, sessions)
+VALUES (DATE(NEW.time), NEW.process, COALESCE(NEW.user_id, 0), COALESCE(NEW.device, 0),
+        NEW.duration, 1)
+ON DUPLICATE KEY UPDATE duration = duration + VALUES(duration), sessions = sessions + 1;
+
+CREATE TRIGGER events_daily_stats_delete AFTER DELETE ON events
+FOR EACH ROW
+UPDATE daily_stats
+SET duration = duration - OLD.duration, sessions = sessions - 1
+WHERE day = DATE(OLD.time)
+  AND process = OLD.process
+  AND user_id = COALESCE(OLD.user_id, 0)
+  AND device = COALESCE(OLD.device, 0);
+
+CREATE TRIGGER events_daily_stats_update_old AFTER UPDATE ON events
+FOR EACH ROW
+UPDATE daily_stats
+SET duration = duration - OLD.duration, sessions = sessions - 1
+WHERE day = DATE(OLD.time)
+  AND process = OLD.process
+  AND user_id = COALESCE(OLD.user_id, 0)
+  AND device = COALESCE(OLD.device, 0);
+
+CREATE TRIGGER events_daily_stats_update_new AFTER UPDATE ON events
+FOR EACH ROW FOLLOWS events_daily_stats_update_old
+INSERT INTO daily_stats (day, process, user_id, device, duration, sessions)
+VALUES (DATE(NEW.time), NEW.process, COALESCE(NEW.user_id, 0), COALESCE(NEW.device, 0),
+        NEW.duration, 1)
+ON DUPLICATE KEY UPDATE duration = duration + VALUES(duration), sessions = sessions + 1;
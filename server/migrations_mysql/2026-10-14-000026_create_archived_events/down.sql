@@ -0,0 +1,5 @@
+DROP TRIGGER archived_events_daily_stats_update_new;
+DROP TRIGGER archived_events_daily_stats_update_old;
+DROP TRIGGER archived_events_daily_stats_delete;
+DROP TRIGGER archived_events_daily_stats_insert;
+DROP TABLE archived_events;
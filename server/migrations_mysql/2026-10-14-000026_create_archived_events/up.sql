@@ -0,0 +1,56 @@
+-- Sessions of months moved out of events by `archiveAfterMonths`, and when
+-- they were moved, with IDs of their own. They keep counting in daily_stats.
+CREATE TABLE archived_events (
+    id INTEGER AUTO_INCREMENT PRIMARY KEY,
+    time DATETIME(6) NOT NULL,
+    process INTEGER NOT NULL,
+    duration BIGINT NOT NULL,
+    product_version VARCHAR(255) NULL,
+    file_version VARCHAR(255) NULL,
+    started_at DATETIME(6) NOT NULL,
+    focused_duration BIGINT NULL,
+    tags TEXT NOT NULL,
+    window_title TEXT NULL,
+    api_key VARCHAR(255) NULL,
+    user_id INTEGER NULL,
+    device INTEGER NULL,
+    idempotency_key VARCHAR(255) NULL UNIQUE,
+    imported BOOLEAN DEFAULT false NOT NULL,
+    archived_at DATETIME(6) NOT NULL,
+    FOREIGN KEY (process) REFERENCES processes (id),
+    FOREIGN KEY (user_id) REFERENCES users (id),
+    FOREIGN KEY (device) REFERENCES devices (id),
+    INDEX archived_events_time (time)
+);
+
+CREATE TRIGGER archived_events_daily_stats_insert AFTER INSERT ON archived_events
+FOR EACH ROW
+INSERT INTO daily_stats (day, process, user_id, device, duration, sessions)
+VALUES (DATE(NEW.time), NEW.process, COALESCE(NEW.user_id, 0), COALESCE(NEW.device, 0),
+        NEW.duration, 1)
+ON DUPLICATE KEY UPDATE duration = duration + VALUES(duration), sessions = sessions + 1;
+
+CREATE TRIGGER archived_events_daily_stats_delete AFTER DELETE ON archived_events
+FOR EACH ROW
+UPDATE daily_stats
+SET duration = duration - OLD.duration, sessions = sessions - 1
+WHERE day = DATE(OLD.time)
+  AND process = OLD.process
+  AND user_id = COALESCE(OLD.user_id, 0)
+  AND device = COALESCE(OLD.device, 0);
+
+CREATE TRIGGER archived_events_daily_stats_update_old AFTER UPDATE ON archived_events
+FOR EACH ROW
+UPDATE daily_stats
+SET duration = duration - OLD.duration, sessions = sessions - 1
+WHERE day = DATE(OLD.time)
+  AND process = OLD.process
+  AND user_id = COALESCE(OLD.user_id, 0)
+  AND device = COALESCE(OLD.device, 0);
+
+CREATE TRIGGER archived_events_daily_stats_update_new AFTER UPDATE ON archived_events
+FOR EACH ROW FOLLOWS archived_events_daily_stats_update_old
+INSERT INTO daily_stats (day, process, user_id, device, duration, sessions)
+VALUES (DATE(NEW.time), NEW.process, COALESCE(NEW.user_id, 0), COALESCE(NEW.device, 0),
+        NEW.duration, 1)
+ON DUPLICATE KEY UPDATE duration = duration + VALUES(duration), sessions = sessions + 1;
@@ -0,0 +1,2 @@
+DROP INDEX archived_events_process_time ON archived_events;
+DROP INDEX events_process_time ON events;
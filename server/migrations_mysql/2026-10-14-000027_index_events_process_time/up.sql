@@ -0,0 +1,2 @@
+CREATE INDEX events_process_time ON events (process, time);
+CREATE INDEX archived_events_process_time ON archived_events (process, time);
@@ -0,0 +1,4 @@
+ALTER TABLE events ADD COLUMN manual BOOLEAN DEFAULT false NOT NULL;
+ALTER TABLE events ADD COLUMN note TEXT;
+ALTER TABLE archived_events ADD COLUMN manual BOOLEAN DEFAULT false NOT NULL;
+ALTER TABLE archived_events ADD COLUMN note TEXT;
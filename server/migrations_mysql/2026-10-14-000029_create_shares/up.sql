@@ -0,0 +1,9 @@
+-- Links that show the stats of a user, or of one game, without authentication.
+CREATE TABLE shares (
+    id INTEGER AUTO_INCREMENT PRIMARY KEY,
+    token VARCHAR(255) NOT NULL UNIQUE,
+    user_id INTEGER NULL,
+    process VARCHAR(255) NULL,
+    created_at DATETIME(6) NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
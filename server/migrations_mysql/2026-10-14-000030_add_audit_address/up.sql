@@ -0,0 +1,2 @@
+-- Client the action was requested from, behind trusted proxies the original one.
+ALTER TABLE audit_log ADD COLUMN address VARCHAR;
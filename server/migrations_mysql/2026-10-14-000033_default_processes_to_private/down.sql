@@ -0,0 +1 @@
+ALTER TABLE processes ALTER COLUMN export SET DEFAULT true;
@@ -0,0 +1,3 @@
+-- New processes are kept out of the export unless chosen, whichever way
+-- they are inserted.
+ALTER TABLE processes ALTER COLUMN export SET DEFAULT false;
@@ -0,0 +1,4 @@
+DROP TABLE events;
+DROP TABLE devices;
+DROP TABLE users;
+DROP TABLE processes;
@@ -0,0 +1,46 @@
+CREATE TABLE processes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    executable TEXT NOT NULL,
+    name TEXT NULL,
+    export BOOLEAN DEFAULT true NOT NULL
+);
+
+CREATE UNIQUE INDEX unique_process ON processes (executable, IFNULL(name, ''));
+
+CREATE TABLE users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    leaderboard BOOLEAN DEFAULT false NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE devices (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    nonce TEXT NOT NULL UNIQUE,
+    token TEXT NULL UNIQUE,
+    approved BOOLEAN DEFAULT false NOT NULL,
+    revoked BOOLEAN DEFAULT false NOT NULL,
+    created_at TEXT NOT NULL,
+    user_id INTEGER NULL REFERENCES users (id)
+);
+
+CREATE TABLE events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    time TEXT NOT NULL,
+    process INTEGER NOT NULL REFERENCES processes (id),
+    duration BIGINT NOT NULL,
+    product_version TEXT NULL,
+    file_version TEXT NULL,
+    started_at TEXT NOT NULL,
+    focused_duration BIGINT NULL,
+    tags TEXT DEFAULT '[]' NOT NULL,
+    window_title TEXT NULL,
+    api_key TEXT NULL,
+    user_id INTEGER NULL REFERENCES users (id),
+    device INTEGER NULL REFERENCES devices (id),
+    idempotency_key TEXT NULL UNIQUE
+);
+
+CREATE INDEX events_user_id ON events (user_id);
+CREATE INDEX events_time ON events (time);
@@ -0,0 +1,8 @@
+CREATE TABLE process_aliases (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    executable TEXT NOT NULL,
+    name TEXT NULL,
+    process INTEGER NOT NULL REFERENCES processes (id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX unique_process_alias ON process_aliases (executable, IFNULL(name, ''));
@@ -0,0 +1 @@
+DROP TABLE process_categories;
@@ -0,0 +1,7 @@
+CREATE TABLE process_categories (
+    process INTEGER NOT NULL REFERENCES processes (id) ON DELETE CASCADE,
+    category TEXT NOT NULL,
+    PRIMARY KEY (process, category)
+);
+
+CREATE INDEX process_categories_category ON process_categories (category);
@@ -0,0 +1,18 @@
+CREATE TABLE heartbeats (
+    session_id TEXT PRIMARY KEY NOT NULL,
+    executable TEXT NOT NULL,
+    name TEXT NULL,
+    started_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    duration BIGINT NOT NULL,
+    focused_duration BIGINT NULL,
+    product_version TEXT NULL,
+    file_version TEXT NULL,
+    tags TEXT DEFAULT '[]' NOT NULL,
+    window_title TEXT NULL,
+    api_key TEXT NULL,
+    user_id INTEGER NULL REFERENCES users (id),
+    device INTEGER NULL REFERENCES devices (id)
+);
+
+CREATE INDEX heartbeats_updated_at ON heartbeats (updated_at);
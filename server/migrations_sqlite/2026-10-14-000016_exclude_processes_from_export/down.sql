@@ -0,0 +1 @@
+UPDATE processes SET export = true;
@@ -0,0 +1 @@
+DROP TABLE completion_times;
@@ -0,0 +1,10 @@
+-- Games looked up on HowLongToBeat. Games that weren't found have no game.
+CREATE TABLE completion_times (
+    process INTEGER PRIMARY KEY NOT NULL REFERENCES processes (id) ON DELETE CASCADE,
+    checked_at TEXT NOT NULL,
+    game_id INTEGER NULL,
+    game TEXT NULL,
+    main_story BIGINT NULL,
+    main_extra BIGINT NULL,
+    completionist BIGINT NULL
+);
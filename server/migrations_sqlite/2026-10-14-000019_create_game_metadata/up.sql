@@ -0,0 +1,9 @@
+-- Games looked up on IGDB. Games that weren't found have no title.
+CREATE TABLE game_metadata (
+    process INTEGER PRIMARY KEY NOT NULL REFERENCES processes (id) ON DELETE CASCADE,
+    checked_at TEXT NOT NULL,
+    igdb_id INTEGER NULL,
+    title TEXT NULL,
+    genres TEXT DEFAULT '[]' NOT NULL,
+    cover_url TEXT NULL
+);
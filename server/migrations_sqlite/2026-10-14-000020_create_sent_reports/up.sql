@@ -0,0 +1,5 @@
+-- End of the last period each email report was sent for.
+CREATE TABLE sent_reports (
+    report TEXT PRIMARY KEY NOT NULL,
+    sent_until TEXT NOT NULL
+);
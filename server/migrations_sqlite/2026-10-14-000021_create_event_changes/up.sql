@@ -0,0 +1,14 @@
+-- Audit trail of edited and deleted events. The events are kept as JSON, so
+-- that the trail outlives them.
+CREATE TABLE event_changes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    event INTEGER NOT NULL,
+    user_id INTEGER NULL,
+    action TEXT NOT NULL,
+    changed_at TEXT NOT NULL,
+    changed_by TEXT NOT NULL,
+    old_event TEXT NOT NULL,
+    new_event TEXT NULL
+);
+
+CREATE INDEX event_changes_event ON event_changes (event);
@@ -0,0 +1 @@
+ALTER TABLE processes ADD COLUMN hidden BOOLEAN DEFAULT false NOT NULL;
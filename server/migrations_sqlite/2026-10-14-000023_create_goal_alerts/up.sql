@@ -0,0 +1,5 @@
+-- Start of the last period each goal was alerted for.
+CREATE TABLE goal_alerts (
+    goal TEXT PRIMARY KEY NOT NULL,
+    period_start TEXT NOT NULL
+);
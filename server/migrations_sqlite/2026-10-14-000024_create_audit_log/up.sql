@@ -0,0 +1,9 @@
+-- Append-only log of mutating actions and who did them.
+CREATE TABLE audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    recorded_at TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    action TEXT NOT NULL,
+    target TEXT NULL,
+    details TEXT NULL
+);
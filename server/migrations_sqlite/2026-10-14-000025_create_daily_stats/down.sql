@@ -0,0 +1,4 @@
+DROP TRIGGER events_daily_stats_update;
+DROP TRIGGER events_daily_stats_delete;
+DROP TRIGGER events_daily_stats_insert;
+DROP TABLE daily_stats;
@@ -0,0 +1,63 @@
+-- Playtime and sessions per day in UTC, process, user and device, kept up to
+-- date by triggers on events so that stats don't sum every event. Sessions
+-- without a user or device are counted under 0.
+CREATE TABLE daily_stats (
+    day TEXT NOT NULL,
+    process INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    device INTEGER NOT NULL,
+    duration BIGINT NOT NULL,
+    sessions BIGINT NOT NULL,
+    PRIMARY KEY (day, process, user_id, device)
+);
+
+INSERT INTO daily_stats (day, process, user_id, device, duration, sessions)
+SELECT date(time), process, COALESCE(user_id, 0), COALESCE(device, 0), SUM(duration), COUNT(*)
+FROM events
+GROUP BY 1, 2, 3, 4;
+
+CREATE TRIGGER events_daily_stats_insert AFTER INSERT ON events
+BEGIN
+    INSERT INTO daily_stats (day, process, user_id, device, duration, sessions)
+    VALUES (date(NEW.time), NEW.process, COALESCE(NEW.user_id, 0), COALESCE(NEW.device, 0),
+            NEW.duration, 1)
+    ON CONFLICT (day, process, user_id, device) DO UPDATE
+    SET duration = duration + excluded.duration, sessions = sessions + excluded.sessions;
+END;
+
+CREATE TRIGGER events_daily_stats_delete AFTER DELETE ON events
+BEGIN
+    UPDATE daily_stats
+    SET duration = duration - OLD.duration, sessions = sessions - 1
+    WHERE day = date(OLD.time)
+      AND process = OLD.process
+      AND user_id = COALESCE(OLD.user_id, 0)
+      AND device = COALESCE(OLD.device, 0);
+    DELETE FROM daily_stats
+    WHERE day = date(OLD.time)
+      AND process = OLD.process
+      AND user_id = COALESCE(OLD.user_id, 0)
+      AND device = COALESCE(OLD.device, 0)
+      AND sessions <= 0;
+END;
+
+CREATE TRIGGER events_daily_stats_update AFTER UPDATE ON events
+BEGIN
+    UPDATE daily_stats
+    SET duration = duration - OLD.duration, sessions = sessions - 1
+    WHERE day = date(OLD.time)
+      AND process = OLD.process
+      AND user_id = COALESCE(OLD.user_id, 0)
+      AND device = COALESCE(OLD.device, 0);
+    INSERT INTO daily_stats (day, process, user_id, device, duration, sessions)
+    VALUES (date(NEW.time), NEW.process, COALESCE(NEW.user_id, 0), COALESCE(NEW.device, 0),
+            NEW.duration, 1)
+    ON CONFLICT (day, process, user_id, device) DO UPDATE
+    SET duration = duration + excluded.duration, sessions = sessions + excluded.sessions;
+    DELETE FROM daily_stats
+    WHERE day = date(OLD.time)
+      AND process = OLD.process
+      AND user_id = COALESCE(OLD.user_id, 0)
+      AND device = COALESCE(OLD.device, 0)
+      AND sessions <= 0;
+END;
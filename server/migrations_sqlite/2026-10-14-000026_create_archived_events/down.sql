@@ -0,0 +1,4 @@
+DROP TRIGGER archived_events_daily_stats_update;
+DROP TRIGGER archived_events_daily_stats_delete;
+DROP TRIGGER archived_events_daily_stats_insert;
+DROP TABLE archived_events;
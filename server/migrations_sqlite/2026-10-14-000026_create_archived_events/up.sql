@@ -0,0 +1,68 @@
+-- Sessions of months moved out of events by `archiveAfterMonths`, and when
+-- they were moved, with IDs of their own. They keep counting in daily_stats.
+CREATE TABLE archived_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    time TEXT NOT NULL,
+    process INTEGER NOT NULL REFERENCES processes (id),
+    duration BIGINT NOT NULL,
+    product_version TEXT NULL,
+    file_version TEXT NULL,
+    started_at TEXT NOT NULL,
+    focused_duration BIGINT NULL,
+    tags TEXT DEFAULT '[]' NOT NULL,
+    window_title TEXT NULL,
+    api_key TEXT NULL,
+    user_id INTEGER NULL REFERENCES users (id),
+    device INTEGER NULL REFERENCES devices (id),
+    idempotency_key TEXT NULL UNIQUE,
+    imported BOOLEAN DEFAULT false NOT NULL,
+    archived_at TEXT NOT NULL
+);
+
+CREATE INDEX archived_events_time ON archived_events (time);
+
+CREATE TRIGGER archived_events_daily_stats_insert AFTER INSERT ON archived_events
+BEGIN
+    INSERT INTO daily_stats (day, process, user_id, device, duration, sessions)
+    VALUES (date(NEW.time), NEW.process, COALESCE(NEW.user_id, 0), COALESCE(NEW.device, 0),
+            NEW.duration, 1)
+    ON CONFLICT (day, process, user_id, device) DO UPDATE
+    SET duration = duration + excluded.duration, sessions = sessions + excluded.sessions;
+END;
+
+CREATE TRIGGER archived_events_daily_stats_delete AFTER DELETE ON archived_events
+BEGIN
+    UPDATE daily_stats
+    SET duration = duration - OLD.duration, sessions = sessions - 1
+    WHERE day = date(OLD.time)
+      AND process = OLD.process
+      AND user_id = COALESCE(OLD.user_id, 0)
+      AND device = COALESCE(OLD.device, 0);
+    DELETE FROM daily_stats
+    WHERE day = date(OLD.time)
+      AND process = OLD.process
+      AND user_id = COALESCE(OLD.user_id, 0)
+      AND device = COALESCE(OLD.device, 0)
+      AND sessions <= 0;
+END;
+
+CREATE TRIGGER archived_events_daily_stats_update AFTER UPDATE ON archived_events
+BEGIN
+    UPDATE daily_stats
+    SET duration = duration - OLD.duration, sessions = sessions - 1
+    WHERE day = date(OLD.time)
+      AND process = OLD.process
+      AND user_id = COALESCE(OLD.user_id, 0)
+      AND device = COALESCE(OLD.device, 0);
+    INSERT INTO daily_stats (day, process, user_id, device, duration, sessions)
+    VALUES (date(NEW.time), NEW.process, COALESCE(NEW.user_id, 0), COALESCE(NEW.device, 0),
+            NEW.duration, 1)
+    ON CONFLICT (day, process, user_id, device) DO UPDATE
+    SET duration = duration + excluded.duration, sessions = sessions + excluded.sessions;
+    DELETE FROM daily_stats
+    WHERE day = date(OLD.time)
+      AND process = OLD.process
+      AND user_id = COALESCE(OLD.user_id, 0)
+      AND device = COALESCE(OLD.device, 0)
+      AND sessions <= 0;
+END;
@@ -0,0 +1,8 @@
+-- Links that show the stats of a user, or of one game, without authentication.
+CREATE TABLE shares (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    token TEXT NOT NULL UNIQUE,
+    user_id INTEGER NULL REFERENCES users (id),
+    process TEXT NULL,
+    created_at TEXT NOT NULL
+);
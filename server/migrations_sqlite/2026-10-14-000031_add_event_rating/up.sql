@@ -0,0 +1,3 @@
+-- Out of 5, given to the session along with its note.
+ALTER TABLE events ADD COLUMN rating SMALLINT;
+ALTER TABLE archived_events ADD COLUMN rating SMALLINT;
@@ -0,0 +1,3 @@
+DROP VIEW report_sessions;
+DROP VIEW report_process_totals;
+DROP VIEW report_daily_playtime;
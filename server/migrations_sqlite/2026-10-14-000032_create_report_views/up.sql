@@ -0,0 +1,98 @@
+-- Views for reading playtime straight from the database, such as with the
+-- SQLite data source plugin of Grafana. Their columns stay the same when the
+-- tables change, so migrations that change the columns used here recreate
+-- the views rather than changing or dropping their columns. Durations are in
+-- seconds, tags are JSON arrays and days are text such as 2026-10-14, and
+-- sessions without a user or device have no user_id or device_id.
+
+-- Playtime and sessions per day in UTC, process and user, including archived
+-- and rolled up sessions.
+CREATE VIEW report_daily_playtime AS
+SELECT daily_stats.day,
+       processes.id AS process_id,
+       processes.executable,
+       processes.name,
+       COALESCE(processes.name, processes.executable) AS title,
+       processes.hidden,
+       users.id AS user_id,
+       users.name AS user_name,
+       CAST(SUM(daily_stats.duration) AS INTEGER) AS playtime_seconds,
+       CAST(SUM(daily_stats.sessions) AS INTEGER) AS sessions
+FROM daily_stats
+JOIN processes ON processes.id = daily_stats.process
+LEFT JOIN users ON users.id = daily_stats.user_id
+WHERE daily_stats.sessions > 0
+GROUP BY daily_stats.day, processes.id, processes.executable, processes.name, processes.hidden,
+         users.id, users.name;
+
+-- Playtime and sessions of every process that has been played, and the first
+-- and last days in UTC that it was played on.
+CREATE VIEW report_process_totals AS
+SELECT processes.id AS process_id,
+       processes.executable,
+       processes.name,
+       COALESCE(processes.name, processes.executable) AS title,
+       processes.hidden,
+       CAST(SUM(daily_stats.duration) AS INTEGER) AS playtime_seconds,
+       CAST(SUM(daily_stats.sessions) AS INTEGER) AS sessions,
+       MIN(daily_stats.day) AS first_day,
+       MAX(daily_stats.day) AS last_day
+FROM daily_stats
+JOIN processes ON processes.id = daily_stats.process
+WHERE daily_stats.sessions > 0
+GROUP BY processes.id, processes.executable, processes.name, processes.hidden;
+
+-- Every session that is kept on its own, archived or not. Archived sessions
+-- have IDs of their own, so sessions are told apart by id and archived.
+CREATE VIEW report_sessions AS
+SELECT events.id,
+       0 AS archived,
+       events.started_at,
+       events.time AS ended_at,
+       events.duration AS duration_seconds,
+       events.focused_duration AS focused_seconds,
+       processes.id AS process_id,
+       processes.executable,
+       processes.name,
+       COALESCE(processes.name, processes.executable) AS title,
+       processes.hidden,
+       users.id AS user_id,
+       users.name AS user_name,
+       devices.id AS device_id,
+       devices.name AS device_name,
+       events.tags,
+       events.window_title,
+       events.note,
+       events.rating,
+       events.manual,
+       events.imported
+FROM events
+JOIN processes ON processes.id = events.process
+LEFT JOIN users ON users.id = events.user_id
+LEFT JOIN devices ON devices.id = events.device
+UNION ALL
+SELECT archived_events.id,
+       1 AS archived,
+       archived_events.started_at,
+       archived_events.time AS ended_at,
+       archived_events.duration AS duration_seconds,
+       archived_events.focused_duration AS focused_seconds,
+       processes.id AS process_id,
+       processes.executable,
+       processes.name,
+       COALESCE(processes.name, processes.executable) AS title,
+       processes.hidden,
+       users.id AS user_id,
+       users.name AS user_name,
+       devices.id AS device_id,
+       devices.name AS device_name,
+       archived_events.tags,
+       archived_events.window_title,
+       archived_events.note,
+       archived_events.rating,
+       archived_events.manual,
+       archived_events.imported
+FROM archived_events
+JOIN processes ON processes.id = archived_events.process
+LEFT JOIN users ON users.id = archived_events.user_id
+LEFT JOIN devices ON devices.id = archived_events.device;
@@ -0,0 +1,18 @@
+PRAGMA foreign_keys = OFF;
+PRAGMA legacy_alter_table = ON;
+BEGIN;
+CREATE TABLE processes_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    executable TEXT NOT NULL,
+    name TEXT NULL,
+    export BOOLEAN DEFAULT true NOT NULL,
+    hidden BOOLEAN DEFAULT false NOT NULL
+);
+INSERT INTO processes_new (id, executable, name, export, hidden)
+SELECT id, executable, name, export, hidden FROM processes;
+DROP TABLE processes;
+ALTER TABLE processes_new RENAME TO processes;
+CREATE UNIQUE INDEX unique_process ON processes (executable, IFNULL(name, ''));
+COMMIT;
+PRAGMA legacy_alter_table = OFF;
+PRAGMA foreign_keys = ON;
@@ -0,0 +1,22 @@
+-- SQLite can't change the default of a column, so the table is rebuilt. The
+-- foreign keys of other tables are turned off while the table is replaced,
+-- which can't be done in a transaction, and the legacy rename leaves the
+-- views on processes alone.
+PRAGMA foreign_keys = OFF;
+PRAGMA legacy_alter_table = ON;
+BEGIN;
+CREATE TABLE processes_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    executable TEXT NOT NULL,
+    name TEXT NULL,
+    export BOOLEAN DEFAULT false NOT NULL,
+    hidden BOOLEAN DEFAULT false NOT NULL
+);
+INSERT INTO processes_new (id, executable, name, export, hidden)
+SELECT id, executable, name, export, hidden FROM processes;
+DROP TABLE processes;
+ALTER TABLE processes_new RENAME TO processes;
+CREATE UNIQUE INDEX unique_process ON processes (executable, IFNULL(name, ''));
+COMMIT;
+PRAGMA legacy_alter_table = OFF;
+PRAGMA foreign_keys = ON;
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::Deserialize;
+use shared::SessionDuration;
+use time::OffsetDateTime;
+
+use crate::db::NewEvent;
+
+/// Bucket type of the ActivityWatch window watcher.
+const WINDOW_BUCKET_TYPE: &str = "currentwindow";
+
+/// Export of one or all buckets from the ActivityWatch web UI or
+/// `/api/0/export`.
+#[derive(Deserialize)]
+pub struct Export {
+    buckets: HashMap<String, Bucket>,
+}
+
+#[derive(Deserialize)]
+struct Bucket {
+    #[serde(rename = "type")]
+    kind: String,
+
+    #[serde(default)]
+    events: Vec<WindowEvent>,
+}
+
+/// Time a window was focused.
+#[derive(Deserialize)]
+struct WindowEvent {
+    #[serde(with = "time::serde::rfc3339")]
+    timestamp: OffsetDateTime,
+
+    /// Seconds.
+    duration: f64,
+    data: WindowData,
+}
+
+#[derive(Deserialize)]
+struct WindowData {
+    app: Option<String>,
+    title: Option<String>,
+}
+
+/// Focused windows of one app close enough together to be one session.
+struct Session {
+    executable: String,
+    started_at: OffsetDateTime,
+    ended_at: OffsetDateTime,
+    focused: f64,
+
+    /// Focused seconds per window title.
+    titles: HashMap<String, f64>,
+}
+
+impl Session {
+    fn new(executable: String, event: &WindowEvent, ended_at: OffsetDateTime) -> Self {
+        let mut session = Session {
+            executable,
+            started_at: event.timestamp,
+            ended_at,
+            focused: 0.0,
+            titles: HashMap::new(),
+        };
+        session.add(event, ended_at);
+        session
+    }
+
+    fn add(&mut self, event: &WindowEvent, ended_at: OffsetDateTime) {
+        self.ended_at = self.ended_at.max(ended_at);
+        self.focused += event.duration;
+        if let Some(title) = event.data.title.as_ref().filter(|title| !title.is_empty()) {
+            *self.titles.entry(title.clone()).or_default() += event.duration;
+        }
+    }
+
+    fn into_event(self, bucket: &str) -> NewEvent {
+        let window_title = self
+            .titles
+            .into_iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(title, _)| title);
+        NewEvent {
+            idempotency_key: Some(format!(
+                "activitywatch:{}:{}",
+                bucket,
+                self.started_at.unix_timestamp()
+            )),
+            executable: self.executable,
+            name: None,
+            time: self.ended_at,
+            started_at: self.started_at,
+            duration: SessionDuration::from_secs_i64(
+                (self.ended_at - self.started_at).whole_seconds(),
+            ),
+            focused_duration: Some(SessionDuration::from_secs(self.focused.round() as u64)),
+            product_version: None,
+            file_version: None,
+            tags: Vec::new(),
+            window_title,
+            api_key: None,
+            user_id: None,
+            device: None,
+            imported: true,
+            manual: false,
+            note: None,
+        }
+    }
+}
+
+/// Sessions of the apps in the window watcher buckets of the export, for
+/// the apps that `keep` accepts. Focused windows of an app at most `gap`
+/// apart are one session, whose duration is the time from the first to the
+/// end of the last window and whose focused duration is the time the
+/// windows were focused. Sessions shorter than `minimum_duration` are left
+/// out.
+pub fn sessions(
+    export: Export,
+    keep: impl Fn(&str) -> bool,
+    gap: Duration,
+    minimum_duration: u64,
+) -> Vec<NewEvent> {
+    let mut events = Vec::new();
+    for (bucket_id, bucket) in export.buckets {
+        if bucket.kind != WINDOW_BUCKET_TYPE {
+            continue;
+        }
+        let mut windows = bucket.events;
+        windows.sort_by_key(|window| window.timestamp);
+        let mut open: HashMap<String, Session> = HashMap::new();
+        let mut closed = Vec::new();
+        for window in windows {
+            let Some(app) = window.data.app.as_ref().filter(|app| keep(app)) else {
+                continue;
+            };
+            let ended_at =
+                window.timestamp + Duration::try_from_secs_f64(window.duration).unwrap_or_default();
+            match open.get_mut(app) {
+                Some(session) if window.timestamp - session.ended_at <= gap => {
+                    session.add(&window, ended_at);
+                }
+                _ => {
+                    let session = Session::new(app.clone(), &window, ended_at);
+                    if let Some(previous) = open.insert(app.clone(), session) {
+                        closed.push(previous);
+                    }
+                }
+            }
+        }
+        closed.extend(open.into_values());
+        events.extend(
+            closed
+                .into_iter()
+                .map(|session| session.into_event(&bucket_id))
+                .filter(|event| event.duration.as_secs() >= minimum_duration),
+        );
+    }
+    events.sort_by_key(|event| event.started_at);
+    events
+}
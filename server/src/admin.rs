@@ -0,0 +1,494 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use async_graphql::SimpleObject;
+use clap::Subcommand;
+use log::error;
+use serde::Serialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    activitywatch,
+    audit::{self, Action},
+    config::Config,
+    db::{Database, NewEvent},
+    processes, steam, users, util,
+};
+
+/// Process with the totals of its sessions.
+#[derive(Serialize, SimpleObject)]
+#[graphql(name = "Process", complex)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessSummary {
+    pub id: i32,
+    pub executable: String,
+    pub name: Option<String>,
+
+    /// Included in `/public/stats`.
+    pub export: bool,
+
+    /// Left out of stats, but its sessions are kept.
+    pub hidden: bool,
+    pub sessions: u64,
+
+    /// Total duration in seconds.
+    pub duration: u64,
+}
+
+#[derive(Subcommand)]
+pub enum AdminAction {
+    /// List processes with their number of sessions and total playtime.
+    ListProcesses {
+        /// Only list processes whose executable or name contains this text.
+        #[arg(long)]
+        search: Option<String>,
+
+        /// Only list processes whose executable or name roughly matches this
+        /// text, best match first.
+        #[arg(long)]
+        fuzzy: Option<String>,
+
+        /// Field to sort by, like the `sort` of `/processes`: id, name,
+        /// sessions or duration.
+        #[arg(long)]
+        sort: Option<String>,
+
+        /// Direction of the sort, asc or desc.
+        #[arg(long, default_value_t)]
+        order: shared::SortOrder,
+    },
+
+    /// Change the name of a process.
+    RenameProcess {
+        id: i32,
+
+        /// New name. The name is removed if not given.
+        #[arg(long)]
+        name: Option<String>,
+    },
+
+    /// Move the sessions of a process to another one and delete it. Later
+    /// sessions of the deleted process are recorded for the other one.
+    MergeProcess {
+        /// Process to merge and delete.
+        source: i32,
+
+        /// Process that gets the sessions.
+        target: i32,
+    },
+
+    /// List processes that are likely the same game, with the ID to merge
+    /// them with merge-duplicates.
+    ListDuplicates,
+
+    /// Merge the duplicate processes listed with the ID into the most played
+    /// of them.
+    MergeDuplicates {
+        /// ID from list-duplicates.
+        id: String,
+    },
+
+    /// Delete sessions of a process or from before a time.
+    DeleteEvents {
+        /// Only delete sessions of this process.
+        #[arg(long)]
+        process: Option<i32>,
+
+        /// Only delete sessions that ended before this time, e.g.
+        /// 2024-01-01T00:00:00Z.
+        #[arg(long, value_parser = parse_time)]
+        before: Option<OffsetDateTime>,
+    },
+
+    /// Give the sessions submitted with an API key before it was given a user
+    /// to the user that it now belongs to.
+    AssignKeySessions {
+        /// Name of the API key.
+        key: String,
+    },
+
+    /// Import sessions from an ActivityWatch export of window watcher
+    /// buckets. Only apps that are already known processes are imported,
+    /// unless more are given with --app. Importing the same export again
+    /// skips the sessions that were already imported.
+    ImportActivitywatch {
+        /// JSON file exported from ActivityWatch.
+        file: PathBuf,
+
+        /// Also import this app, e.g. eldenring.exe. Can be repeated.
+        #[arg(long)]
+        app: Vec<String>,
+
+        /// User to record the sessions for.
+        #[arg(long)]
+        user: Option<String>,
+
+        /// Longest break in seconds between windows of the same session.
+        #[arg(long, default_value_t = 300)]
+        gap: u64,
+
+        /// Skip sessions shorter than this many seconds.
+        #[arg(long, default_value_t = 60)]
+        minimum_duration: u64,
+    },
+
+    /// Backfill the playtime of the games of a Steam account as imported
+    /// sessions. Meant to be run once: importing the same account again skips
+    /// the games that were already imported.
+    ImportSteam {
+        /// 64-bit Steam ID of the account, whose game details must be public.
+        steam_id: String,
+
+        /// Steam Web API key. Read from STEAM_API_KEY if not given.
+        #[arg(long)]
+        api_key: Option<String>,
+
+        /// User to record the sessions for.
+        #[arg(long)]
+        user: Option<String>,
+    },
+}
+
+fn parse_time(value: &str) -> Result<OffsetDateTime, String> {
+    OffsetDateTime::parse(value, &Rfc3339)
+        .map_err(|_| format!("invalid time {}, expected e.g. 2024-01-01T00:00:00Z", value))
+}
+
+fn format_duration(seconds: u64) -> String {
+    format!("{}h {:02}m", seconds / 3600, seconds / 60 % 60)
+}
+
+fn print_processes(processes: &[ProcessSummary]) {
+    println!(
+        "{:>6} {:<32} {:<32} {:>8} {:>10}",
+        "ID", "Executable", "Name", "Sessions", "Playtime"
+    );
+    for process in processes {
+        println!(
+            "{:>6} {:<32} {:<32} {:>8} {:>10}",
+            process.id,
+            process.executable,
+            process.name.as_deref().unwrap_or("-"),
+            process.sessions,
+            format_duration(process.duration)
+        );
+    }
+}
+
+/// ID of the configured user with the name.
+async fn user_id(db: &Database, config: &Config, user: Option<String>) -> Result<Option<i32>, ()> {
+    let Some(user) = user else {
+        return Ok(None);
+    };
+    match users::sync(db, config).await?.get(&user) {
+        Some(user_id) => Ok(Some(*user_id)),
+        None => {
+            error!("User {} is not configured", user);
+            Err(())
+        }
+    }
+}
+
+/// Save the imported sessions from the source and report how many were new.
+async fn save_imported(
+    db: &Database,
+    source: String,
+    sessions: Vec<NewEvent>,
+    user_id: Option<i32>,
+) -> Result<(), ()> {
+    let (mut imported, mut skipped) = (0, 0);
+    for mut session in sessions {
+        session.user_id = user_id;
+        match db.save_event(session).await {
+            Ok(true) => imported += 1,
+            Ok(false) => skipped += 1,
+            Err(error) => {
+                error!("Could not save session: {}", error);
+                return Err(());
+            }
+        }
+    }
+    println!(
+        "Imported {} sessions, skipped {} imported before",
+        imported, skipped
+    );
+    let details = Some(format!("{} sessions", imported));
+    audit::record(
+        db,
+        audit::ADMIN_COMMAND,
+        Action::Import,
+        Some(source),
+        details,
+    )
+    .await;
+    Ok(())
+}
+
+async fn import_activitywatch(
+    db: &Database,
+    config: &Config,
+    file: PathBuf,
+    apps: Vec<String>,
+    user: Option<String>,
+    gap: u64,
+    minimum_duration: u64,
+) -> Result<(), ()> {
+    let user_id = user_id(db, config, user).await?;
+    let export: activitywatch::Export = File::open(&file)
+        .map_err(|error| error.to_string())
+        .and_then(|fp| {
+            serde_json::from_reader(BufReader::new(fp)).map_err(|error| error.to_string())
+        })
+        .map_err(|error| error!("Could not read {}: {}", file.display(), error))?;
+    let processes = db
+        .list_processes(None)
+        .await
+        .map_err(|error| error!("Could not list processes: {}", error))?;
+    let known = processes
+        .into_iter()
+        .map(|process| process.executable.to_lowercase())
+        .chain(apps.into_iter().map(|app| app.to_lowercase()))
+        .collect::<HashSet<String>>();
+    let sessions = activitywatch::sessions(
+        export,
+        |app| known.contains(&app.to_lowercase()),
+        Duration::from_secs(gap),
+        minimum_duration,
+    );
+    let source = format!("activitywatch {}", file.display());
+    save_imported(db, source, sessions, user_id).await
+}
+
+async fn import_steam(
+    db: &Database,
+    config: &Config,
+    steam_id: String,
+    api_key: Option<String>,
+    user: Option<String>,
+) -> Result<(), ()> {
+    let Some(api_key) = api_key.or_else(|| std::env::var("STEAM_API_KEY").ok()) else {
+        error!("Give a Steam Web API key with --api-key or STEAM_API_KEY");
+        return Err(());
+    };
+    let user_id = user_id(db, config, user).await?;
+    let games = steam::owned_games(&api_key, &steam_id)
+        .await
+        .map_err(|error| error!("Could not get the games of {}: {}", steam_id, error))?;
+    let sessions = steam::backfill(&games, &steam_id, OffsetDateTime::now_utc());
+    let source = format!("steam {}", steam_id);
+    save_imported(db, source, sessions, user_id).await
+}
+
+pub async fn run(db: &Database, config: &Config, action: AdminAction) -> Result<(), ()> {
+    match action {
+        AdminAction::ListProcesses {
+            search,
+            fuzzy,
+            sort,
+            order,
+        } => match db.list_processes(search).await {
+            Ok(mut processes) => {
+                let query = shared::ListQuery {
+                    sort,
+                    order,
+                    ..Default::default()
+                };
+                let sorts = processes::sorts(fuzzy.is_some(), &query);
+                if let Some(text) = fuzzy {
+                    processes = processes::fuzzy_search(processes, &text);
+                }
+                let Some(processes) = util::sort(processes, &query, sorts) else {
+                    error!(
+                        "Unknown sort {}, expected one of: {}",
+                        query.sort.unwrap_or_default(),
+                        sorts
+                            .iter()
+                            .map(|(name, _)| *name)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                    return Err(());
+                };
+                print_processes(&processes);
+                Ok(())
+            }
+            Err(error) => {
+                error!("Could not list processes: {}", error);
+                Err(())
+            }
+        },
+        AdminAction::RenameProcess { id, name } => {
+            match db.rename_process(id, name.clone()).await {
+                Ok(0) => {
+                    error!("Process {} does not exist", id);
+                    Err(())
+                }
+                Ok(_) => {
+                    println!("Renamed process {}", id);
+                    let target = Some(format!("process {}", id));
+                    audit::record(
+                        db,
+                        audit::ADMIN_COMMAND,
+                        Action::RenameProcess,
+                        target,
+                        name,
+                    )
+                    .await;
+                    Ok(())
+                }
+                Err(error) => {
+                    error!("Could not rename process {}: {}", id, error);
+                    Err(())
+                }
+            }
+        }
+        AdminAction::MergeProcess { source, target } => {
+            if source == target {
+                error!("Cannot merge process {} into itself", source);
+                return Err(());
+            }
+            match db.merge_processes(source, target).await {
+                Ok(Some(moved)) => {
+                    println!(
+                        "Moved {} sessions from process {} to {}",
+                        moved, source, target
+                    );
+                    let details = Some(format!("into process {}, moving {} events", target, moved));
+                    let target = Some(format!("process {}", source));
+                    audit::record(
+                        db,
+                        audit::ADMIN_COMMAND,
+                        Action::MergeProcess,
+                        target,
+                        details,
+                    )
+                    .await;
+                    Ok(())
+                }
+                Ok(None) => {
+                    error!("Process {} or {} does not exist", source, target);
+                    Err(())
+                }
+                Err(error) => {
+                    error!("Could not merge process {}: {}", source, error);
+                    Err(())
+                }
+            }
+        }
+        AdminAction::ListDuplicates => match processes::suggestions(db).await {
+            Ok(suggestions) => {
+                for suggestion in suggestions {
+                    println!("{}:", suggestion.id);
+                    print_processes(
+                        &std::iter::once(suggestion.into)
+                            .chain(suggestion.merge)
+                            .collect::<Vec<_>>(),
+                    );
+                    println!();
+                }
+                Ok(())
+            }
+            Err(error) => {
+                error!("Could not look for duplicate processes: {}", error);
+                Err(())
+            }
+        },
+        AdminAction::MergeDuplicates { id } => {
+            match processes::merge_suggestion(db, audit::ADMIN_COMMAND, &id).await {
+                Ok(Some(moved)) => {
+                    println!("Merged duplicates {}, moving {} sessions", id, moved);
+                    Ok(())
+                }
+                Ok(None) => {
+                    error!("Processes {} are not duplicates", id);
+                    Err(())
+                }
+                Err(error) => {
+                    error!("Could not merge duplicates {}: {}", id, error);
+                    Err(())
+                }
+            }
+        }
+        AdminAction::DeleteEvents { process, before } => {
+            if process.is_none() && before.is_none() {
+                error!("Give --process or --before to choose the sessions to delete");
+                return Err(());
+            }
+            let filters = match (process, before) {
+                (Some(process), Some(before)) => {
+                    format!("of process {} before {}", process, before)
+                }
+                (Some(process), None) => format!("of process {}", process),
+                (None, Some(before)) => format!("before {}", before),
+                (None, None) => String::new(),
+            };
+            match db.delete_events(process, before).await {
+                Ok(deleted) => {
+                    println!("Deleted {} sessions", deleted);
+                    let details = Some(format!("{} sessions {}", deleted, filters));
+                    audit::record(
+                        db,
+                        audit::ADMIN_COMMAND,
+                        Action::DeleteEvents,
+                        None,
+                        details,
+                    )
+                    .await;
+                    Ok(())
+                }
+                Err(error) => {
+                    error!("Could not delete sessions: {}", error);
+                    Err(())
+                }
+            }
+        }
+        AdminAction::AssignKeySessions { key } => {
+            let Some(api_key) = config.api_keys.iter().find(|api_key| api_key.name == key) else {
+                error!("API key {} is not configured", key);
+                return Err(());
+            };
+            let Some(user_id) = user_id(db, config, api_key.user.clone()).await? else {
+                error!("API key {} does not belong to a user", key);
+                return Err(());
+            };
+            match db.assign_key_events(key.clone(), user_id).await {
+                Ok(assigned) => {
+                    println!("Gave {} sessions of API key {} to its user", assigned, key);
+                    let details = api_key
+                        .user
+                        .as_ref()
+                        .map(|user| format!("{} sessions to {}", assigned, user));
+                    let target = Some(format!("api key {}", key));
+                    audit::record(
+                        db,
+                        audit::ADMIN_COMMAND,
+                        Action::AssignEvents,
+                        target,
+                        details,
+                    )
+                    .await;
+                    Ok(())
+                }
+                Err(error) => {
+                    error!("Could not give sessions of API key {}: {}", key, error);
+                    Err(())
+                }
+            }
+        }
+        AdminAction::ImportActivitywatch {
+            file,
+            app,
+            user,
+            gap,
+            minimum_duration,
+        } => import_activitywatch(db, config, file, app, user, gap, minimum_duration).await,
+        AdminAction::ImportSteam {
+            steam_id,
+            api_key,
+            user,
+        } => import_steam(db, config, steam_id, api_key, user).await,
+    }
+}
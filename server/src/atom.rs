@@ -0,0 +1,172 @@
+use axum::{
+    extract::{Query, State},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use log::error;
+use serde::Deserialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{add_query_key, authorize, config::Scope, util, AppState};
+
+/// Sessions listed in the feed.
+const FEED_SIZE: i64 = 50;
+
+#[derive(Deserialize)]
+pub struct FeedQuery {
+    /// Only list the sessions of this user.
+    user: Option<String>,
+
+    /// Secret or API key, for feed readers that cannot send headers. Not
+    /// needed for users with a public feed.
+    key: Option<String>,
+}
+
+/// Text with the characters that are special in XML replaced by entities.
+pub fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn format_time(time: OffsetDateTime) -> String {
+    time.format(&Rfc3339).unwrap_or_default()
+}
+
+fn entry(event: &shared::Event) -> String {
+    let game = event.name.as_deref().unwrap_or(&event.executable);
+    let duration = util::format_duration(event.duration);
+    let mut summary = format!("Played {} for {}", game, duration);
+    if let Some(device) = &event.device {
+        summary.push_str(&format!(" on {}", device));
+    }
+    format!(
+        concat!(
+            "  <entry>\n",
+            "    <title>{} ({})</title>\n",
+            "    <id>urn:beelzebub:event:{}</id>\n",
+            "    <published>{}</published>\n",
+            "    <updated>{}</updated>\n",
+            "    <summary>{}.</summary>\n",
+            "  </entry>\n",
+        ),
+        xml_escape(game),
+        duration,
+        event.id,
+        format_time(event.started_at),
+        format_time(event.ended_at),
+        xml_escape(&summary),
+    )
+}
+
+fn feed(title: &str, id: &str, events: &[shared::Event]) -> String {
+    let updated = events
+        .first()
+        .map(|event| event.ended_at)
+        .unwrap_or_else(OffsetDateTime::now_utc);
+    let mut feed = format!(
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n",
+            "<feed xmlns=\"http://www.w3.org/2005/Atom\">\n",
+            "  <title>{}</title>\n",
+            "  <id>{}</id>\n",
+            "  <updated>{}</updated>\n",
+            "  <author><name>Beelzebub</name></author>\n",
+        ),
+        xml_escape(title),
+        id,
+        format_time(updated),
+    );
+    feed.extend(events.iter().map(entry));
+    feed.push_str("</feed>\n");
+    feed
+}
+
+/// Whether the configured user lets anyone read their feed.
+fn is_public(state: &AppState, user: &str) -> bool {
+    let Ok(config) = state.config.read() else {
+        error!("Could not read configuration");
+        return false;
+    };
+    config
+        .users
+        .iter()
+        .any(|config_user| config_user.name == user && config_user.public_feed)
+}
+
+/// Latest recorded sessions as an Atom feed. Callers limited to a user only
+/// get the sessions of the user, and the feed of a user with `publicFeed`
+/// can be read without authentication.
+pub async fn atom(
+    State(state): State<AppState>,
+    mut headers: HeaderMap,
+    Query(query): Query<FeedQuery>,
+) -> Result<Response, StatusCode> {
+    let public = query
+        .user
+        .as_deref()
+        .is_some_and(|user| is_public(&state, user));
+    let caller = if public {
+        None
+    } else {
+        add_query_key(&mut headers, query.key.as_deref())?;
+        let Some(caller) = authorize(&headers, &state, Scope::Read).await else {
+            return Err(StatusCode::UNAUTHORIZED);
+        };
+        Some(caller)
+    };
+    let requested = match &query.user {
+        Some(user) => match state.users.get(user) {
+            Some(user_id) => Some(*user_id),
+            None => return Err(StatusCode::NOT_FOUND),
+        },
+        None => None,
+    };
+    let user_id = match caller.and_then(|caller| caller.user_id) {
+        Some(own) if requested.is_some_and(|requested| requested != own) => {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        Some(own) => Some(own),
+        None => requested,
+    };
+    let events = match state
+        .db
+        .events(shared::EventsQuery::default(), None, FEED_SIZE, user_id)
+        .await
+    {
+        Ok(events) => events,
+        Err(error) => {
+            error!("Could not query events for the feed: {}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let (title, id) = match &query.user {
+        Some(user) => (
+            format!("Beelzebub sessions of {}", user),
+            format!("urn:beelzebub:feed:{}", xml_escape(user)),
+        ),
+        None => (
+            "Beelzebub sessions".to_string(),
+            "urn:beelzebub:feed".to_string(),
+        ),
+    };
+    let body = feed(&title, &id, &events);
+    Ok((
+        [(CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+        body,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    #[test_case("Factorio", "Factorio"; "plain")]
+    #[test_case("Tom & Jerry's <\"Game\">", "Tom &amp; Jerry&apos;s &lt;&quot;Game&quot;&gt;"; "special")]
+    fn xml_escape(text: &str, output: &str) {
+        assert_eq!(super::xml_escape(text), output);
+    }
+}
@@ -0,0 +1,146 @@
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use log::error;
+use shared::pagination::Cursor;
+use time::OffsetDateTime;
+
+use crate::{db, devices::is_admin, pagination, proxies, storage::Storage, AppState};
+
+/// Actor of the admin commands of the server.
+pub const ADMIN_COMMAND: &str = "admin";
+
+/// Actor of requests made with the admin secret.
+pub const ADMIN_SECRET: &str = "admin secret";
+
+/// Actor of the retention policy.
+pub const RETENTION: &str = "retention";
+
+/// Actor of requests that don't need authentication, such as device
+/// registrations.
+pub const ANONYMOUS: &str = "anonymous";
+
+/// Kind of mutating action in the audit log.
+#[derive(Clone, Copy, Debug)]
+pub enum Action {
+    Submit,
+    AddEvent,
+    UpdateEvent,
+    SetNote,
+    DeleteEvent,
+    DeleteEvents,
+    RollUpEvents,
+    ArchiveEvents,
+    AssignEvents,
+    RenameProcess,
+    MergeProcess,
+    SetCategories,
+    SetExport,
+    SetHidden,
+    AddAlias,
+    DeleteAlias,
+    SetCover,
+    DeleteCover,
+    CreateShare,
+    RevokeShare,
+    RegisterDevice,
+    ApproveDevice,
+    RevokeDevice,
+    Restore,
+    Import,
+}
+
+impl Action {
+    /// Name of the action in the audit log and its `action` filter.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Submit => "submit",
+            Action::AddEvent => "add_event",
+            Action::UpdateEvent => "update_event",
+            Action::SetNote => "set_note",
+            Action::DeleteEvent => "delete_event",
+            Action::DeleteEvents => "delete_events",
+            Action::RollUpEvents => "roll_up_events",
+            Action::ArchiveEvents => "archive_events",
+            Action::AssignEvents => "assign_events",
+            Action::RenameProcess => "rename_process",
+            Action::MergeProcess => "merge_process",
+            Action::SetCategories => "set_categories",
+            Action::SetExport => "set_export",
+            Action::SetHidden => "set_hidden",
+            Action::AddAlias => "add_alias",
+            Action::DeleteAlias => "delete_alias",
+            Action::SetCover => "set_cover",
+            Action::DeleteCover => "delete_cover",
+            Action::CreateShare => "create_share",
+            Action::RevokeShare => "revoke_share",
+            Action::RegisterDevice => "register_device",
+            Action::ApproveDevice => "approve_device",
+            Action::RevokeDevice => "revoke_device",
+            Action::Restore => "restore",
+            Action::Import => "import",
+        }
+    }
+}
+
+/// Append the action to the audit log. The action has been done already, so
+/// failing to record it is only logged.
+pub async fn record(
+    storage: &(impl Storage + ?Sized),
+    actor: &str,
+    action: Action,
+    target: Option<String>,
+    details: Option<String>,
+) {
+    let entry = db::NewAuditEntry {
+        recorded_at: OffsetDateTime::now_utc(),
+        actor: actor.to_string(),
+        action,
+        target,
+        details,
+        address: proxies::client_address().map(|address| address.to_string()),
+    };
+    if let Err(error) = storage.record_audit(entry).await {
+        error!(
+            "Could not record {} by {} in the audit log: {}",
+            action.name(),
+            actor,
+            error
+        );
+    }
+}
+
+/// Mutating actions from newest to oldest, paginated by the ID of the last
+/// entry on the previous page.
+pub async fn list(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<shared::AuditQuery>,
+) -> Result<Json<shared::AuditPage>, StatusCode> {
+    if !is_admin(&headers, &state) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let before = match query.page.as_deref().map(i32::decode) {
+        Some(Some(id)) => Some(id),
+        Some(None) => return Err(StatusCode::BAD_REQUEST),
+        None => None,
+    };
+    let limit = pagination::limit(query.limit);
+    // One extra to tell whether there is another page.
+    let mut entries = match state.db.audit_log(query, before, limit as i64 + 1).await {
+        Ok(entries) => entries,
+        Err(error) => {
+            error!("Could not query the audit log: {}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let next_page = if entries.len() > limit as usize {
+        entries.truncate(limit as usize);
+        entries.last().map(|entry| entry.id.encode())
+    } else {
+        None
+    };
+    Ok(Json(shared::AuditPage { entries, next_page }))
+}
@@ -0,0 +1,126 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::Response,
+};
+use log::{error, info, warn};
+use time::OffsetDateTime;
+
+use crate::{
+    audit, authorize,
+    config::{IgnoreAction, Scope},
+    ignore_rules, match_name_case, name_normalization, new_event,
+    payload::{self, Payload},
+    submission_limits, util, AppState,
+};
+
+/// Import sessions with their recorded times, such as from another tracker.
+/// The sessions are marked as imported and tagged with the source. Sessions
+/// that were imported before are skipped, so a backfill that failed part of
+/// the way can be sent again.
+#[utoipa::path(
+    post,
+    path = "/backfill",
+    request_body = shared::Backfill,
+    responses(
+        (status = 200, description = "Sessions saved, skipped and rejected", body = shared::BackfillResponse),
+        (status = 401, description = "Not authenticated"),
+        (status = 413, description = "Sessions larger than the limit", body = shared::SubmissionResponse),
+        (status = 422, description = "Missing or too long source"),
+        (status = 500, description = "Database error, sessions before the error are saved"),
+    ),
+)]
+pub async fn backfill(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Payload(backfill): Payload<shared::Backfill>,
+) -> Result<Response, StatusCode> {
+    let Some(caller) = authorize(&headers, &state, Scope::Submit).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let limits = submission_limits(&state.config);
+    let source = backfill.source.trim();
+    if source.is_empty() || source.chars().count() > limits.maximum_length {
+        warn!("Rejected backfill with source {:?}", backfill.source);
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let rules = name_normalization(&state.config);
+    let ignore = ignore_rules(&state.config);
+    let now = OffsetDateTime::now_utc();
+    let mut response = shared::BackfillResponse {
+        saved: 0,
+        duplicates: 0,
+        rejected: Vec::new(),
+    };
+    for (index, session) in backfill.sessions.iter().enumerate() {
+        let problem = util::backfill_problem(session, &limits, now)
+            .or_else(|| util::device_problem(session, caller.device_id));
+        if let Some((field, message)) = problem {
+            response.rejected.push(shared::BackfillRejection {
+                index,
+                field: field.to_string(),
+                message,
+            });
+            continue;
+        }
+        match util::ignored(&session.executable, &ignore) {
+            Some(IgnoreAction::Reject) => {
+                response.rejected.push(shared::BackfillRejection {
+                    index,
+                    field: "executable".to_string(),
+                    message: "executable is ignored".to_string(),
+                });
+                continue;
+            }
+            Some(IgnoreAction::Drop) => continue,
+            None => {}
+        }
+        let key = util::backfill_key(source, session);
+        let mut event = new_event(session, &caller, Some(key), &rules, &limits);
+        event.imported = true;
+        if !event.tags.iter().any(|tag| tag == source) {
+            event.tags.push(source.to_string());
+        }
+        if rules.case_insensitive {
+            match_name_case(&*state.storage, &mut event).await;
+        }
+        let saved = match limits.duplicate_tolerance {
+            Some(tolerance) => state.storage.save_submission(event, tolerance).await,
+            None => state.storage.save_event(event).await,
+        };
+        match saved {
+            Ok(true) => response.saved += 1,
+            Ok(false) => response.duplicates += 1,
+            Err(error) => {
+                error!(
+                    "Could not save backfilled session {}: {}",
+                    session.display(),
+                    error
+                );
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        }
+    }
+    info!(
+        "Backfilled {} sessions from {}, skipped {} duplicates and rejected {}",
+        response.saved,
+        source,
+        response.duplicates,
+        response.rejected.len()
+    );
+    let details = Some(format!(
+        "{} sessions, {} duplicates, {} rejected",
+        response.saved,
+        response.duplicates,
+        response.rejected.len()
+    ));
+    audit::record(
+        &*state.storage,
+        &caller.actor(),
+        audit::Action::Import,
+        Some(source.to_string()),
+        details,
+    )
+    .await;
+    Ok(payload::respond(&headers, response))
+}
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use diesel::Queryable;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::{
+    audit,
+    devices::{admin, is_admin},
+    processes::Alias,
+    AppState,
+};
+
+/// Version of the backup format, increased when restoring older backups
+/// needs a conversion.
+pub const BACKUP_VERSION: u32 = 1;
+
+/// Rows inserted with a single statement when restoring.
+pub const RESTORE_BATCH_SIZE: usize = 1000;
+
+/// Complete contents of the database. IDs are kept so that the references
+/// between the tables stay intact.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Backup {
+    pub version: u32,
+    pub users: Vec<BackupUser>,
+    pub processes: Vec<BackupProcess>,
+
+    /// Missing from backups made before aliases were added.
+    #[serde(default)]
+    pub aliases: Vec<Alias>,
+
+    /// Missing from backups made before categories were added.
+    #[serde(default)]
+    pub categories: Vec<BackupCategory>,
+    pub devices: Vec<BackupDevice>,
+    pub events: Vec<BackupEvent>,
+
+    /// Missing from backups made before archiving was added. The IDs are
+    /// those of the archive.
+    #[serde(default)]
+    pub archived_events: Vec<BackupEvent>,
+}
+
+#[derive(Deserialize, Queryable, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupUser {
+    pub id: i32,
+    pub name: String,
+    pub leaderboard: bool,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Deserialize, Queryable, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupProcess {
+    pub id: i32,
+    pub executable: String,
+    pub name: Option<String>,
+    pub export: bool,
+
+    /// Missing from backups made before processes could be hidden.
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+#[derive(Deserialize, Queryable, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupCategory {
+    pub process: i32,
+    pub category: String,
+}
+
+#[derive(Deserialize, Queryable, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupDevice {
+    pub id: i32,
+    pub name: String,
+    pub nonce: String,
+    pub token: Option<String>,
+    pub approved: bool,
+    pub revoked: bool,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    pub user_id: Option<i32>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupEvent {
+    pub id: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub time: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+    pub process: i32,
+
+    /// Duration in seconds.
+    pub duration: u64,
+    pub focused_duration: Option<u64>,
+    pub product_version: Option<String>,
+    pub file_version: Option<String>,
+    pub tags: Vec<String>,
+    pub window_title: Option<String>,
+    pub api_key: Option<String>,
+    pub user_id: Option<i32>,
+    pub device: Option<i32>,
+    pub idempotency_key: Option<String>,
+    #[serde(default)]
+    pub imported: bool,
+    #[serde(default)]
+    pub manual: bool,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub rating: Option<u8>,
+}
+
+/// User IDs of the backup mapped to the IDs of the same users by name in the
+/// database being restored to, which already has the configured users.
+pub type UserMapping = HashMap<i32, i32>;
+
+pub fn map_user(mapping: &UserMapping, user: Option<i32>) -> Option<i32> {
+    user.and_then(|user| mapping.get(&user).copied())
+}
+
+pub async fn backup(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Backup>, StatusCode> {
+    if !is_admin(&headers, &state) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    match state.db.backup().await {
+        Ok(backup) => {
+            info!(
+                "Backed up {} processes, {} events and {} devices",
+                backup.processes.len(),
+                backup.events.len(),
+                backup.devices.len()
+            );
+            Ok(Json(backup))
+        }
+        Err(error) => {
+            error!("Could not back up database: {}", error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Load a backup into a database without processes, events or devices.
+pub async fn restore(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(backup): Json<Backup>,
+) -> StatusCode {
+    let Some(actor) = admin(&headers, &state) else {
+        return StatusCode::UNAUTHORIZED;
+    };
+    if backup.version != BACKUP_VERSION {
+        error!("Cannot restore backup version {}", backup.version);
+        return StatusCode::BAD_REQUEST;
+    }
+    let counts = (
+        backup.processes.len(),
+        backup.events.len(),
+        backup.devices.len(),
+    );
+    match state.db.restore(backup).await {
+        Ok(true) => {
+            info!(
+                "Restored {} processes, {} events and {} devices",
+                counts.0, counts.1, counts.2
+            );
+            let details = Some(format!(
+                "{} processes, {} events and {} devices",
+                counts.0, counts.1, counts.2
+            ));
+            audit::record(&state.db, &actor, audit::Action::Restore, None, details).await;
+            StatusCode::NO_CONTENT
+        }
+        Ok(false) => {
+            error!("Cannot restore backup over existing data");
+            StatusCode::CONFLICT
+        }
+        Err(error) => {
+            error!("Could not restore backup: {}", error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
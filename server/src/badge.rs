@@ -0,0 +1,100 @@
+use axum::{
+    extract::{Path, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{IntoResponse, Response},
+};
+use log::error;
+
+use crate::{atom, util, AppState};
+
+/// Total playtime of the processes marked for export whose executable or
+/// name contains the process, as an SVG badge for embedding in web pages,
+/// e.g. `/badge/Factorio.svg`. Needs no authentication, like `/public/stats`.
+pub async fn badge(
+    State(state): State<AppState>,
+    Path(file): Path<String>,
+) -> Result<Response, StatusCode> {
+    let Some(process) = file
+        .strip_suffix(".svg")
+        .filter(|process| !process.is_empty())
+    else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    let query = shared::StatsQuery {
+        process: Some(process.to_string()),
+        ..Default::default()
+    };
+    let stats = match state.db.public_stats(query).await {
+        Ok(stats) => stats,
+        Err(error) => {
+            error!("Could not query the badge of {}: {}", process, error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    if stats.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let duration = stats.iter().map(|process| process.duration).sum();
+    let body = svg(process, &util::format_hours(duration));
+    Ok(([(CONTENT_TYPE, "image/svg+xml; charset=utf-8")], body).into_response())
+}
+
+/// Rough width in pixels of the text in the 11px Verdana of badges.
+fn text_width(text: &str) -> usize {
+    return text.chars().count() * 7 + 10;
+}
+
+/// SVG badge in the flat style of shields.io, with the label on grey and the
+/// value on green.
+fn svg(label: &str, value: &str) -> String {
+    let label_width = text_width(label);
+    let value_width = text_width(value);
+    let label = atom::xml_escape(label);
+    let value = atom::xml_escape(value);
+    return format!(
+        concat!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"20\" role=\"img\" aria-label=\"{label}: {value}\">",
+            "<title>{label}: {value}</title>",
+            "<linearGradient id=\"s\" x2=\"0\" y2=\"100%\">",
+            "<stop offset=\"0\" stop-color=\"#bbb\" stop-opacity=\".1\"/><stop offset=\"1\" stop-opacity=\".1\"/>",
+            "</linearGradient>",
+            "<clipPath id=\"r\"><rect width=\"{width}\" height=\"20\" rx=\"3\" fill=\"#fff\"/></clipPath>",
+            "<g clip-path=\"url(#r)\">",
+            "<rect width=\"{label_width}\" height=\"20\" fill=\"#555\"/>",
+            "<rect x=\"{label_width}\" width=\"{value_width}\" height=\"20\" fill=\"#4c1\"/>",
+            "<rect width=\"{width}\" height=\"20\" fill=\"url(#s)\"/>",
+            "</g>",
+            "<g fill=\"#fff\" text-anchor=\"middle\" font-family=\"Verdana,Geneva,DejaVu Sans,sans-serif\" font-size=\"11\">",
+            "<text x=\"{label_x}\" y=\"14\">{label}</text>",
+            "<text x=\"{value_x}\" y=\"14\">{value}</text>",
+            "</g>",
+            "</svg>\n",
+        ),
+        width = label_width + value_width,
+        label_width = label_width,
+        value_width = value_width,
+        label_x = label_width / 2,
+        value_x = label_width + value_width / 2,
+        label = label,
+        value = value,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use crate::atom;
+
+    #[test_case("Factorio", "1,234 h", 125; "plain")]
+    #[test_case("Tom & Jerry", "5 min", 132; "escaped")]
+    fn svg(label: &str, value: &str, width: usize) {
+        let badge = super::svg(label, value);
+        assert!(badge.starts_with(&format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\"",
+            width
+        )));
+        let title = format!("<title>{}: {}</title>", atom::xml_escape(label), value);
+        assert!(badge.contains(&title));
+    }
+}
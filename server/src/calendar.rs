@@ -0,0 +1,165 @@
+use axum::{
+    extract::{Query, State},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use time::{OffsetDateTime, UtcOffset};
+
+use crate::{add_query_key, authorize, config::Scope, export, util, AppState};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedQuery {
+    /// Only include sessions that ended at or after this time.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    from: Option<OffsetDateTime>,
+
+    /// Only include sessions that ended before this time.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    to: Option<OffsetDateTime>,
+
+    /// Only include processes whose executable or name contains this text.
+    process: Option<String>,
+
+    /// Only include sessions from the registered device with this name.
+    device: Option<String>,
+
+    /// Secret or API key, for calendar apps that cannot send headers.
+    key: Option<String>,
+}
+
+fn calendar_header() -> String {
+    [
+        ical_line("BEGIN", "VCALENDAR"),
+        ical_line("VERSION", "2.0"),
+        ical_line("PRODID", "-//Hamuko//Beelzebub//EN"),
+        ical_line("CALSCALE", "GREGORIAN"),
+        ical_line("X-WR-CALNAME", "Beelzebub"),
+    ]
+    .concat()
+}
+
+fn calendar_event(event: &shared::Event) -> String {
+    let game = event.name.as_deref().unwrap_or(&event.executable);
+    let summary = format!("{} ({})", game, util::format_duration(event.duration));
+    let mut description = vec![format!("Executable: {}", event.executable)];
+    if let Some(device) = &event.device {
+        description.push(format!("Device: {}", device));
+    }
+    if let Some(window_title) = &event.window_title {
+        description.push(format!("Window: {}", window_title));
+    }
+    if let Some(rating) = event.rating {
+        description.push(format!("Rating: {}/5", rating));
+    }
+    if let Some(note) = &event.note {
+        description.push(format!("Note: {}", note));
+    }
+    [
+        ical_line("BEGIN", "VEVENT"),
+        ical_line("UID", &format!("event-{}@beelzebub", event.id)),
+        ical_line("DTSTAMP", &ical_time(event.ended_at)),
+        ical_line("DTSTART", &ical_time(event.started_at)),
+        ical_line("DTEND", &ical_time(event.ended_at)),
+        ical_line("SUMMARY", &ical_text(&summary)),
+        ical_line("DESCRIPTION", &ical_text(&description.join("\n"))),
+        ical_line("TRANSP", "TRANSPARENT"),
+        ical_line("END", "VEVENT"),
+    ]
+    .concat()
+}
+
+/// Recorded sessions as an iCalendar feed, newest first.
+pub async fn feed(
+    State(state): State<AppState>,
+    mut headers: HeaderMap,
+    Query(query): Query<FeedQuery>,
+) -> Result<Response, StatusCode> {
+    add_query_key(&mut headers, query.key.as_deref())?;
+    let Some(caller) = authorize(&headers, &state, Scope::Read).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let events_query = shared::EventsQuery {
+        from: query.from,
+        to: query.to,
+        process: query.process,
+        device: query.device,
+        ..Default::default()
+    };
+    let body = export::event_body(
+        state.db,
+        events_query,
+        caller.user_id,
+        calendar_header(),
+        ical_line("END", "VCALENDAR"),
+        calendar_event,
+    );
+    Ok(([(CONTENT_TYPE, "text/calendar; charset=utf-8")], body).into_response())
+}
+
+/// Time in UTC in the iCalendar format, e.g. `20240301T120000Z`.
+pub fn ical_time(time: OffsetDateTime) -> String {
+    let time = time.to_offset(UtcOffset::UTC);
+    return format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        time.year(),
+        time.month() as u8,
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    );
+}
+
+/// Text with the characters that have a meaning in iCalendar values escaped.
+pub fn ical_text(text: &str) -> String {
+    return text
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n");
+}
+
+/// iCalendar content line, folded so that no line is longer than 75 bytes.
+pub fn ical_line(name: &str, value: &str) -> String {
+    let mut line = String::new();
+    let mut length = 0;
+    for character in name.chars().chain([':']).chain(value.chars()) {
+        if length + character.len_utf8() > 75 {
+            line.push_str("\r\n ");
+            length = 1;
+        }
+        line.push(character);
+        length += character.len_utf8();
+    }
+    line.push_str("\r\n");
+    return line;
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+    use time::OffsetDateTime;
+
+    #[test_case(OffsetDateTime::from_unix_timestamp(1_709_294_400).unwrap(), "20240301T120000Z"; "utc")]
+    #[test_case(OffsetDateTime::from_unix_timestamp(1_709_294_400).unwrap().to_offset(time::UtcOffset::from_hms(9, 0, 0).unwrap()), "20240301T120000Z"; "other offset")]
+    fn ical_time(time: OffsetDateTime, output: &str) {
+        assert_eq!(super::ical_time(time), output);
+    }
+
+    #[test_case("Factorio", "Factorio"; "plain")]
+    #[test_case("Hearts of Iron IV; Man, the Guns", "Hearts of Iron IV\\; Man\\, the Guns"; "separators")]
+    #[test_case("C:\\Games\ngame.exe", "C:\\\\Games\\ngame.exe"; "backslash and newline")]
+    fn ical_text(text: &str, output: &str) {
+        assert_eq!(super::ical_text(text), output);
+    }
+
+    #[test_case("SUMMARY", "Factorio (2h 14m)", "SUMMARY:Factorio (2h 14m)\r\n"; "short")]
+    #[test_case("DESCRIPTION", &"a".repeat(80), &format!("DESCRIPTION:{}\r\n {}\r\n", "a".repeat(63), "a".repeat(17)); "folded")]
+    #[test_case("SUMMARY", &"é".repeat(40), &format!("SUMMARY:{}\r\n {}\r\n", "é".repeat(33), "é".repeat(7)); "multibyte")]
+    fn ical_line(name: &str, value: &str, output: &str) {
+        assert_eq!(super::ical_line(name, value), output);
+    }
+}
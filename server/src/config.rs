@@ -1,8 +1,34 @@
-use log::debug;
-use serde::Deserialize;
+use log::{debug, error, info};
+use regex::Regex;
+use ring::{constant_time, digest};
+use serde::{Deserialize, Deserializer};
 use shared;
+use std::collections::HashMap;
 use std::fs::File;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::{Path, PathBuf};
+use time_tz::{timezones, Tz};
+
+use crate::util;
+
+/// Environment variable overriding `dbUrl`.
+const DB_URL_VARIABLE: &str = "BEELZEBUB_DB_URL";
+
+/// Environment variable overriding `secret`.
+const SECRET_VARIABLE: &str = "BEELZEBUB_SECRET";
+
+/// Environment variable overriding `adminSecret`.
+const ADMIN_SECRET_VARIABLE: &str = "BEELZEBUB_ADMIN_SECRET";
+
+/// Environment variable overriding `listen` as an address and port, such as
+/// `0.0.0.0:8080`.
+const LISTEN_VARIABLE: &str = "BEELZEBUB_LISTEN";
+
+/// Environment variable overriding `listen.address`.
+const LISTEN_ADDRESS_VARIABLE: &str = "BEELZEBUB_LISTEN_ADDRESS";
+
+/// Environment variable overriding `listen.port`.
+const LISTEN_PORT_VARIABLE: &str = "BEELZEBUB_LISTEN_PORT";
 
 #[derive(Debug)]
 pub enum Error {
@@ -12,8 +38,959 @@ pub enum Error {
     /// Could not determine from where to load the settings.
     DirectoryError,
 
+    /// An environment variable overriding a setting has an invalid value.
+    EnvironmentError,
+
     /// IO error with the configuration.
     IOError(std::io::Error),
+
+    /// A hashed secret is not a SHA-256 digest in hex, or is given where the
+    /// secret itself is needed.
+    SecretError,
+}
+
+/// Secret given either directly or as a reference to where it is stored.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum SecretSource {
+    Plain(String),
+
+    /// Only the digest of the secret, so that it isn't stored on the server.
+    Hashed {
+        /// SHA-256 of the secret in hex.
+        sha256: String,
+    },
+    Reference {
+        /// Environment variable containing the secret.
+        env: Option<String>,
+
+        /// Fallback value if the environment variable is not set.
+        value: Option<String>,
+    },
+}
+
+impl SecretSource {
+    /// The secret itself, which can't be recovered from a hash.
+    pub fn resolve(&self) -> Option<String> {
+        match self {
+            SecretSource::Plain(value) => Some(value.clone()),
+            SecretSource::Hashed { .. } => None,
+            SecretSource::Reference { env, value } => env
+                .as_ref()
+                .and_then(|name| std::env::var(name).ok())
+                .or_else(|| value.clone()),
+        }
+    }
+
+    /// What presented values are checked against.
+    fn resolve_secret(&self) -> Result<Option<Secret>, Error> {
+        match self {
+            SecretSource::Hashed { sha256 } => match util::parse_sha256_hex(sha256) {
+                Some(digest) => Ok(Some(Secret::Sha256(digest))),
+                None => Err(Error::SecretError),
+            },
+            _ => Ok(self.resolve().map(Secret::Plain)),
+        }
+    }
+}
+
+/// Secret that callers authenticate with.
+#[derive(Clone, Debug)]
+pub enum Secret {
+    Plain(String),
+
+    /// SHA-256 digest of the secret.
+    Sha256([u8; 32]),
+}
+
+impl Secret {
+    /// Whether the value is the secret, taking the same time for every value
+    /// of the same length.
+    pub fn matches(&self, value: &str) -> bool {
+        let given = digest::digest(&digest::SHA256, value.as_bytes());
+        let expected = match self {
+            Secret::Plain(secret) => digest::digest(&digest::SHA256, secret.as_bytes())
+                .as_ref()
+                .to_vec(),
+            Secret::Sha256(digest) => digest.to_vec(),
+        };
+        constant_time::verify_slices_are_equal(given.as_ref(), &expected).is_ok()
+    }
+}
+
+/// Named key that clients can authenticate with instead of the secret.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKey {
+    /// Stored with every submission made with the key.
+    pub name: String,
+
+    #[serde(rename = "key")]
+    key_source: SecretSource,
+    #[serde(skip)]
+    pub key: Option<Secret>,
+
+    /// When the key was handed out. Only for bookkeeping.
+    pub created: Option<String>,
+
+    /// User whose data the key is limited to. Keys without a user can read
+    /// the sessions of everyone.
+    pub user: Option<String>,
+
+    /// Disabled keys are rejected.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+
+    /// What the key may be used for.
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<Scope>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_scopes() -> Vec<Scope> {
+    vec![Scope::Read, Scope::Submit]
+}
+
+/// Access granted to an API key.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Reading sessions and statistics.
+    Read,
+
+    /// Submitting sessions and heartbeats.
+    Submit,
+
+    /// Everything, including the endpoints that otherwise require the
+    /// secret, such as merging processes and restoring backups.
+    Admin,
+}
+
+impl Scope {
+    /// Whether a key with the scopes may be used for this.
+    pub fn granted_by(self, scopes: &[Scope]) -> bool {
+        scopes.contains(&self) || scopes.contains(&Scope::Admin)
+    }
+}
+
+/// Person whose playtime is kept separate from the others.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct UserConfig {
+    pub name: String,
+
+    /// Include the user in the combined leaderboard.
+    #[serde(default)]
+    pub leaderboard: bool,
+
+    /// Let anyone read the recent sessions of the user from `/feed.atom`.
+    #[serde(default)]
+    pub public_feed: bool,
+
+    /// Time zone for the reports of the user, instead of that of the server.
+    #[serde(default, deserialize_with = "deserialize_timezone")]
+    pub timezone: Option<&'static Tz>,
+}
+
+/// IANA time zone such as `Europe/Helsinki`.
+fn deserialize_timezone<'de, D>(deserializer: D) -> Result<Option<&'static Tz>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let name = String::deserialize(deserializer)?;
+    match timezones::get_by_name(&name) {
+        Some(timezone) => Ok(Some(timezone)),
+        None => Err(serde::de::Error::custom(format!(
+            "unknown time zone {}",
+            name
+        ))),
+    }
+}
+
+/// Where the server accepts connections.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ListenConfig {
+    #[serde(default = "default_listen_address")]
+    pub address: IpAddr,
+
+    #[serde(default = "default_listen_port")]
+    pub port: u16,
+}
+
+impl Default for ListenConfig {
+    fn default() -> Self {
+        ListenConfig {
+            address: default_listen_address(),
+            port: default_listen_port(),
+        }
+    }
+}
+
+fn default_listen_address() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+}
+
+fn default_listen_port() -> u16 {
+    8080
+}
+
+/// Value of the environment variable parsed, if it is set.
+fn env_override<T: std::str::FromStr>(name: &str) -> Result<Option<T>, Error> {
+    let Ok(value) = std::env::var(name) else {
+        return Ok(None);
+    };
+    match value.parse() {
+        Ok(parsed) => Ok(Some(parsed)),
+        Err(_) => {
+            error!("Invalid value for {}: {}", name, value);
+            Err(Error::EnvironmentError)
+        }
+    }
+}
+
+/// Requests signed with the shared secret instead of sending it.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureConfig {
+    /// Seconds that the timestamp of a signed request may be off from the
+    /// clock of the server, either way.
+    #[serde(default = "default_signature_maximum_age")]
+    pub maximum_age: u64,
+
+    /// Refuse the shared secret in `X-Secret-Key`, so that it is only
+    /// accepted as a signature.
+    #[serde(default)]
+    pub required: bool,
+}
+
+impl Default for SignatureConfig {
+    fn default() -> Self {
+        SignatureConfig {
+            maximum_age: default_signature_maximum_age(),
+            required: false,
+        }
+    }
+}
+
+fn default_signature_maximum_age() -> u64 {
+    300
+}
+
+/// Token bucket for each client.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitConfig {
+    /// Sustained rate at which requests are allowed.
+    #[serde(default = "default_requests_per_minute")]
+    pub requests_per_minute: u32,
+
+    /// Requests allowed in a burst on top of the sustained rate.
+    #[serde(default = "default_burst")]
+    pub burst: u32,
+}
+
+fn default_requests_per_minute() -> u32 {
+    60
+}
+
+fn default_burst() -> u32 {
+    30
+}
+
+/// Waiting for the database at startup, such as for a database container
+/// that starts at the same time.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseRetryConfig {
+    /// Times to try connecting before giving up. 1 gives up right away.
+    #[serde(default = "default_database_attempts")]
+    pub attempts: u32,
+
+    /// Seconds to wait after the first failed attempt, doubling after every
+    /// attempt up to `maximumDelay`.
+    #[serde(default = "default_database_initial_delay")]
+    pub initial_delay: u64,
+
+    #[serde(default = "default_database_maximum_delay")]
+    pub maximum_delay: u64,
+}
+
+impl Default for DatabaseRetryConfig {
+    fn default() -> Self {
+        DatabaseRetryConfig {
+            attempts: default_database_attempts(),
+            initial_delay: default_database_initial_delay(),
+            maximum_delay: default_database_maximum_delay(),
+        }
+    }
+}
+
+fn default_database_attempts() -> u32 {
+    5
+}
+
+fn default_database_initial_delay() -> u64 {
+    1
+}
+
+fn default_database_maximum_delay() -> u64 {
+    30
+}
+
+/// Sizing of the database connection pool. The default size of four
+/// connections per CPU is more than a small server or SQLite needs and may
+/// be too few for a busy server.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabasePoolConfig {
+    /// Most connections open at once, four per CPU if not set.
+    pub maximum_size: Option<usize>,
+
+    /// Seconds to wait for a free connection before failing the request.
+    #[serde(default = "default_database_pool_timeout")]
+    pub timeout: u64,
+
+    /// Seconds after which a connection is closed instead of being reused.
+    pub maximum_lifetime: Option<u64>,
+
+    /// Seconds between logging the state of the pool. 0 turns it off.
+    #[serde(default = "default_database_pool_log_interval")]
+    pub log_interval: u64,
+}
+
+impl Default for DatabasePoolConfig {
+    fn default() -> Self {
+        DatabasePoolConfig {
+            maximum_size: None,
+            timeout: default_database_pool_timeout(),
+            maximum_lifetime: None,
+            log_interval: default_database_pool_log_interval(),
+        }
+    }
+}
+
+fn default_database_pool_timeout() -> u64 {
+    30
+}
+
+fn default_database_pool_log_interval() -> u64 {
+    300
+}
+
+/// Bounds on submitted sessions, beyond which they are rejected.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmissionLimits {
+    /// Longest plausible session in seconds.
+    #[serde(default = "default_maximum_duration")]
+    pub maximum_duration: u64,
+
+    /// Shortest session in seconds worth keeping.
+    #[serde(default)]
+    pub minimum_duration: u64,
+
+    /// Most characters in the executable, name, versions, window title, each
+    /// tag and the note of a manual session. MySQL and MariaDB store at most
+    /// 255.
+    #[serde(default = "default_maximum_length")]
+    pub maximum_length: usize,
+
+    /// Treat a session as a duplicate if it overlaps one of the same process
+    /// from the same device by more than this many seconds, or both starts
+    /// and ends within this many seconds of it. Protects against client bugs
+    /// and clients running twice.
+    pub duplicate_tolerance: Option<u64>,
+}
+
+fn default_submit_body_limit() -> usize {
+    64 * 1024
+}
+
+fn default_backfill_body_limit() -> usize {
+    8 * 1024 * 1024
+}
+
+fn default_restore_body_limit() -> usize {
+    1024 * 1024 * 1024
+}
+
+fn default_body_limit() -> usize {
+    2 * 1024 * 1024
+}
+
+/// Largest request bodies read in bytes, after decompression, so that a
+/// client can't exhaust the memory of the server.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BodyLimits {
+    /// Session submitted to `/submit`.
+    #[serde(default = "default_submit_body_limit")]
+    pub submit: usize,
+
+    /// Sessions imported with `/backfill`.
+    #[serde(default = "default_backfill_body_limit")]
+    pub backfill: usize,
+
+    /// Backup restored with `/restore`.
+    #[serde(default = "default_restore_body_limit")]
+    pub restore: usize,
+
+    /// Every other endpoint.
+    #[serde(default = "default_body_limit")]
+    pub default: usize,
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        BodyLimits {
+            submit: default_submit_body_limit(),
+            backfill: default_backfill_body_limit(),
+            restore: default_restore_body_limit(),
+            default: default_body_limit(),
+        }
+    }
+}
+
+impl Default for SubmissionLimits {
+    fn default() -> Self {
+        SubmissionLimits {
+            maximum_duration: default_maximum_duration(),
+            minimum_duration: 0,
+            maximum_length: default_maximum_length(),
+            duplicate_tolerance: None,
+        }
+    }
+}
+
+impl From<&SubmissionLimits> for shared::SubmissionLimits {
+    fn from(limits: &SubmissionLimits) -> Self {
+        shared::SubmissionLimits {
+            maximum_duration: limits.maximum_duration,
+            minimum_duration: limits.minimum_duration,
+            maximum_length: limits.maximum_length,
+        }
+    }
+}
+
+fn default_maximum_duration() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+fn default_maximum_length() -> usize {
+    255
+}
+
+/// Regular expression that is compiled when the configuration is loaded.
+#[derive(Clone, Debug)]
+pub struct Pattern(pub Regex);
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern)
+            .map(Pattern)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Range of IP addresses written like `10.0.0.0/8`, or a single address.
+#[derive(Clone, Copy, Debug)]
+pub struct Network {
+    pub address: IpAddr,
+    pub prefix: u8,
+}
+
+impl<'de> Deserialize<'de> for Network {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let network = String::deserialize(deserializer)?;
+        util::parse_network(&network).ok_or_else(|| {
+            serde::de::Error::custom(format!("invalid address or network {}", network))
+        })
+    }
+}
+
+/// Replacement of every match of the pattern in submitted names.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NameReplacement {
+    pub pattern: Pattern,
+
+    /// Text the matches are replaced with, which can refer to capture groups
+    /// as `$1` or `${name}`. Defaults to removing the matches.
+    #[serde(default)]
+    pub replacement: String,
+}
+
+/// Clean-up of submitted names before the process is looked up, so that the
+/// same game isn't recorded as several processes.
+#[derive(Clone, Default, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NameNormalization {
+    /// Remove whitespace from both ends and collapse the whitespace between
+    /// words into single spaces.
+    #[serde(default)]
+    pub trim: bool,
+
+    /// Remove ™, ® and ©.
+    #[serde(default)]
+    pub strip_trademarks: bool,
+
+    /// Applied in order after removing trademark symbols and before
+    /// trimming.
+    #[serde(default)]
+    pub replacements: Vec<NameReplacement>,
+
+    /// Use the spelling of an existing process of the executable whose name
+    /// differs only in case.
+    #[serde(default)]
+    pub case_insensitive: bool,
+}
+
+/// What is done with the sessions of an ignored executable.
+#[derive(Clone, Copy, Default, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IgnoreAction {
+    /// Answer with 422 Unprocessable Entity, so that the client logs it.
+    #[default]
+    Reject,
+
+    /// Answer as if the session was saved, so that the client doesn't retry.
+    Drop,
+}
+
+/// Executable, such as a launcher or a helper, whose sessions are noise even
+/// if a client's monitor config matches it.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct IgnoreRule {
+    /// Executable to ignore, compared without case.
+    pub executable: Option<String>,
+
+    /// Ignore every executable that the pattern matches.
+    pub pattern: Option<Pattern>,
+
+    #[serde(default)]
+    pub action: IgnoreAction,
+}
+
+/// How long sessions are kept.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionConfig {
+    /// Delete sessions that ended more than this many days ago.
+    pub delete_after_days: Option<u32>,
+
+    /// Replace the sessions of each game that ended more than this many days
+    /// ago with a single session per day.
+    pub aggregate_after_days: Option<u32>,
+
+    /// Move the sessions of months in UTC that ended more than this many
+    /// months ago to the archive, where they still count in stats but are
+    /// left out of `/events`, histograms and streaks.
+    pub archive_after_months: Option<u32>,
+}
+
+fn default_how_long_to_beat_refresh_days() -> u64 {
+    30
+}
+
+fn default_how_long_to_beat_search_url() -> String {
+    "https://howlongtobeat.com/api/search".to_string()
+}
+
+/// Looking up how long games take to beat on HowLongToBeat.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HowLongToBeatConfig {
+    /// Look up games again after this many days.
+    #[serde(default = "default_how_long_to_beat_refresh_days")]
+    pub refresh_days: u64,
+
+    /// Search endpoint of HowLongToBeat, which has no official API and moves
+    /// its endpoint from time to time.
+    #[serde(default = "default_how_long_to_beat_search_url")]
+    pub search_url: String,
+}
+
+fn default_igdb_refresh_days() -> u64 {
+    30
+}
+
+/// Looking up the titles, genres and cover art of games on IGDB, with the
+/// credentials of a Twitch application.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct IgdbConfig {
+    pub client_id: String,
+
+    #[serde(rename = "clientSecret")]
+    client_secret_source: SecretSource,
+    #[serde(skip)]
+    pub client_secret: String,
+
+    /// Look up games again after this many days.
+    #[serde(default = "default_igdb_refresh_days")]
+    pub refresh_days: u64,
+}
+
+fn default_cover_maximum_size() -> usize {
+    5 * 1024 * 1024
+}
+
+/// Cover images uploaded for processes.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CoversConfig {
+    /// Where the images and their resized variants are stored.
+    pub directory: PathBuf,
+
+    /// Largest image accepted in bytes.
+    #[serde(default = "default_cover_maximum_size")]
+    pub maximum_size: usize,
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_s3_backup_interval_hours() -> u64 {
+    24
+}
+
+fn default_s3_backup_keep() -> usize {
+    7
+}
+
+/// Backups of the database uploaded to a bucket on S3 or a compatible service
+/// such as MinIO.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct S3BackupConfig {
+    /// URL of the service, such as `https://s3.eu-north-1.amazonaws.com`. The
+    /// bucket is given in the path, not as a subdomain.
+    pub endpoint: String,
+    pub bucket: String,
+
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    pub access_key_id: String,
+
+    #[serde(rename = "secretAccessKey")]
+    secret_access_key_source: SecretSource,
+    #[serde(skip)]
+    pub secret_access_key: String,
+
+    /// Start of the names of the backups, such as `beelzebub/`.
+    #[serde(default)]
+    pub prefix: String,
+
+    /// Hours between backups, the first of which is made at startup.
+    #[serde(default = "default_s3_backup_interval_hours")]
+    pub interval_hours: u64,
+
+    /// Number of backups to keep. Older ones are deleted after each upload.
+    #[serde(default = "default_s3_backup_keep")]
+    pub keep: usize,
+}
+
+fn default_tracing_service_name() -> String {
+    "beelzebub-server".to_string()
+}
+
+/// Collector that spans of requests and database queries are exported to
+/// with OTLP over HTTP, such as Grafana Tempo or Jaeger.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TracingConfig {
+    /// URL of the OTLP/HTTP receiver, such as `http://tempo:4318`. Spans are
+    /// posted to `/v1/traces` under it.
+    pub endpoint: String,
+
+    /// `service.name` of the spans.
+    #[serde(default = "default_tracing_service_name")]
+    pub service_name: String,
+
+    /// Headers sent with every export, such as for authentication.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+/// Endpoint that recorded sessions are posted to as JSON.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookConfig {
+    pub url: String,
+
+    /// Key for signing the body with HMAC-SHA256.
+    #[serde(rename = "secret")]
+    secret_source: Option<SecretSource>,
+    #[serde(skip)]
+    pub secret: Option<String>,
+
+    /// Only send sessions of processes whose executable or name contains one
+    /// of these.
+    #[serde(default)]
+    pub processes: Vec<String>,
+
+    /// Only send sessions of these users.
+    #[serde(default)]
+    pub users: Vec<String>,
+
+    /// Only send sessions at least this many seconds long.
+    #[serde(default)]
+    pub minimum_duration: u64,
+}
+
+/// Chat service that a notifier posts to.
+#[derive(Clone, Copy, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifierService {
+    Discord,
+    Slack,
+}
+
+/// Chat channel that gets a message for every recorded session.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NotifierConfig {
+    pub service: NotifierService,
+
+    /// Incoming webhook URL of the channel.
+    pub url: String,
+
+    /// Message with `{user}`, `{game}`, `{executable}` and `{duration}`
+    /// replaced from the session.
+    #[serde(default = "default_notifier_template")]
+    pub template: String,
+
+    /// Only post sessions at least this many seconds long.
+    #[serde(default)]
+    pub minimum_duration: u64,
+}
+
+fn default_notifier_template() -> String {
+    "{user} played {game} for {duration}".to_string()
+}
+
+/// Time tracking service that sessions are entered into.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(tag = "service", rename_all = "lowercase")]
+pub enum TimeTrackerService {
+    /// Time entries of Toggl Track.
+    Toggl {
+        /// API token from the Toggl profile.
+        token: SecretSource,
+
+        /// ID of the workspace that entries are created in.
+        workspace: u64,
+    },
+
+    /// Time entries of Clockify.
+    Clockify {
+        /// API key from the Clockify profile.
+        token: SecretSource,
+
+        /// ID of the workspace that entries are created in.
+        workspace: String,
+    },
+
+    /// Any service that creates an entry from a JSON request.
+    #[serde(rename_all = "camelCase")]
+    Webhook {
+        url: String,
+
+        /// Body with `{description}`, `{project}`, `{start}`, `{end}`,
+        /// `{duration}`, `{tags}`, `{game}`, `{executable}` and `{user}`
+        /// replaced from the session, escaped for JSON strings.
+        #[serde(default = "default_time_entry_template")]
+        template: String,
+
+        /// Headers sent with every entry, such as for authentication.
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+}
+
+fn default_time_entry_template() -> String {
+    concat!(
+        r#"{"description": "{description}", "project": "{project}", "#,
+        r#""start": "{start}", "end": "{end}", "duration": {duration}}"#
+    )
+    .to_string()
+}
+
+/// How the sessions of matching processes are entered into a time tracker.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeEntryRule {
+    /// Only sessions of processes whose executable or name contains this.
+    /// Matches every session if left out.
+    pub process: Option<String>,
+
+    /// ID of the project that the entry is booked on.
+    pub project: Option<String>,
+
+    /// Description with `{game}`, `{executable}` and `{user}` replaced from
+    /// the session.
+    #[serde(default = "default_time_entry_description")]
+    pub description: String,
+
+    /// Tag names for Toggl and tag IDs for Clockify.
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    #[serde(default)]
+    pub billable: bool,
+}
+
+fn default_time_entry_description() -> String {
+    "{game}".to_string()
+}
+
+/// External time tracker that recorded sessions are sent to as time entries.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeTrackerConfig {
+    #[serde(flatten)]
+    pub service: TimeTrackerService,
+
+    /// Token of Toggl or Clockify, resolved from the service.
+    #[serde(skip)]
+    pub token: Option<String>,
+
+    /// The first rule matching a session decides its entry. Sessions that
+    /// match no rule are not sent.
+    pub rules: Vec<TimeEntryRule>,
+
+    /// Only send sessions of these users.
+    #[serde(default)]
+    pub users: Vec<String>,
+
+    /// Only send sessions at least this many seconds long.
+    #[serde(default)]
+    pub minimum_duration: u64,
+}
+
+/// How the connection to the SMTP server is secured.
+#[derive(Clone, Copy, Default, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Upgrade the connection with STARTTLS, on port 587 by default.
+    #[default]
+    Starttls,
+
+    /// TLS from the start, on port 465 by default.
+    Tls,
+
+    /// Unencrypted, on port 25 by default. Only for servers on the same
+    /// machine or network.
+    None,
+}
+
+/// Mail server that reports are sent through.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SmtpConfig {
+    pub host: String,
+
+    /// Defaults to the usual port of `security`.
+    pub port: Option<u16>,
+
+    #[serde(default)]
+    pub security: SmtpSecurity,
+
+    pub username: Option<String>,
+
+    #[serde(rename = "password")]
+    password_source: Option<SecretSource>,
+    #[serde(skip)]
+    pub password: Option<String>,
+}
+
+/// Playtime summary sent by email at the end of every week or month.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailReportConfig {
+    /// Recipient address, such as `hamuko@example.com`.
+    pub to: String,
+
+    /// Only summarise the sessions of this user, in their time zone.
+    pub user: Option<String>,
+
+    #[serde(default)]
+    pub period: shared::SummaryPeriod,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailReportsConfig {
+    pub smtp: SmtpConfig,
+
+    /// Sender address, such as `Beelzebub <beelzebub@example.com>`.
+    pub from: String,
+
+    pub reports: Vec<EmailReportConfig>,
+}
+
+/// Where crossing a goal is announced.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GoalAlertConfig {
+    pub url: String,
+
+    /// Post a message to a Discord or Slack channel instead of the status of
+    /// the goal as JSON.
+    pub service: Option<NotifierService>,
+}
+
+/// Limit or target for the playtime or sessions of every day, week or month.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GoalConfig {
+    /// Identifies the goal, so it should not be changed.
+    pub name: String,
+
+    /// Only count processes whose executable or name contains this.
+    pub process: Option<String>,
+
+    /// Only count the sessions of this user, in their time zone.
+    pub user: Option<String>,
+
+    #[serde(default)]
+    pub period: shared::SummaryPeriod,
+    pub kind: shared::GoalKind,
+
+    /// Target duration in seconds.
+    pub duration: Option<u64>,
+    pub sessions: Option<u64>,
+
+    #[serde(default)]
+    pub alerts: Vec<GoalAlertConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsConfig {
+    /// PEM encoded certificate chain for the server.
+    pub certificate: PathBuf,
+
+    /// PEM encoded private key for the server certificate.
+    pub key: PathBuf,
+
+    /// PEM encoded CA bundle used to verify client certificates.
+    pub client_ca: Option<PathBuf>,
+
+    /// Reject clients that do not present a certificate signed by `clientCa`.
+    #[serde(default)]
+    pub require_client_certificate: bool,
+}
+
+fn default_heartbeat_timeout() -> u64 {
+    300
 }
 
 #[derive(Deserialize, Debug)]
@@ -21,7 +998,131 @@ pub enum Error {
 pub struct Config {
     pub db_url: String,
 
-    pub secret: Option<String>,
+    /// How long to wait for the database at startup.
+    #[serde(default)]
+    pub database_retry: DatabaseRetryConfig,
+
+    #[serde(default)]
+    pub database_pool: DatabasePoolConfig,
+
+    #[serde(default)]
+    pub listen: ListenConfig,
+
+    #[serde(rename = "secret")]
+    secret_source: Option<SecretSource>,
+    #[serde(skip)]
+    pub secret: Option<Secret>,
+
+    /// Secret for administering the server, such as merging processes,
+    /// restoring backups and managing devices. Once set, the shared secret
+    /// only reads and submits.
+    #[serde(rename = "adminSecret")]
+    admin_secret_source: Option<SecretSource>,
+    #[serde(skip)]
+    pub admin_secret: Option<Secret>,
+
+    /// Keys for submitting and reading sessions. Device management still
+    /// requires the secret.
+    #[serde(default)]
+    pub api_keys: Vec<ApiKey>,
+
+    /// Users that API keys and devices can be assigned to.
+    #[serde(default)]
+    pub users: Vec<UserConfig>,
+
+    pub tls: Option<TlsConfig>,
+
+    /// Reject clients older than this version, e.g. `0.2.0`. Clients that do
+    /// not report a version are rejected too.
+    pub minimum_client_version: Option<String>,
+
+    /// Reject sessions that can't be right.
+    #[serde(default)]
+    pub submission_limits: SubmissionLimits,
+
+    /// Clean up submitted names before looking up their process.
+    #[serde(default)]
+    pub name_normalization: NameNormalization,
+
+    /// What stats are totalled by when the request doesn't say.
+    #[serde(default)]
+    pub stats_grouping: shared::StatsGrouping,
+
+    /// Executables whose sessions and heartbeats are not recorded. The first
+    /// rule that matches decides what is done with them.
+    #[serde(default)]
+    pub ignore: Vec<IgnoreRule>,
+
+    /// Prune old sessions in the background.
+    pub retention: Option<RetentionConfig>,
+
+    /// Seconds without a heartbeat after which a session in progress is
+    /// recorded as having ended at its last heartbeat.
+    #[serde(default = "default_heartbeat_timeout")]
+    pub heartbeat_timeout: u64,
+
+    /// Time zone whose days, weeks and months are used for reports. Defaults
+    /// to UTC.
+    #[serde(default, deserialize_with = "deserialize_timezone")]
+    pub timezone: Option<&'static Tz>,
+
+    /// Largest request bodies accepted.
+    #[serde(default)]
+    pub body_limits: BodyLimits,
+
+    /// Limit requests to submission and query endpoints.
+    pub rate_limit: Option<RateLimitConfig>,
+
+    /// Checks of requests signed with the shared secret.
+    #[serde(default)]
+    pub signatures: SignatureConfig,
+
+    /// Reverse proxies whose `Forwarded` and `X-Forwarded-For` headers are
+    /// believed for the address of the client.
+    #[serde(default)]
+    pub trusted_proxies: Vec<Network>,
+
+    /// Notify other services of recorded sessions.
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+
+    /// Post recorded sessions to Discord or Slack channels.
+    #[serde(default)]
+    pub notifiers: Vec<NotifierConfig>,
+
+    /// Enter recorded sessions into Toggl, Clockify or other time trackers.
+    #[serde(default)]
+    pub time_trackers: Vec<TimeTrackerConfig>,
+
+    /// Look up how long the games take to beat for `/stats/completion`.
+    pub how_long_to_beat: Option<HowLongToBeatConfig>,
+
+    /// Look up titles, genres and cover art of the games for stats.
+    pub igdb: Option<IgdbConfig>,
+
+    /// Email playtime summaries at the end of every week or month.
+    pub email_reports: Option<EmailReportsConfig>,
+
+    /// Accept cover images for processes.
+    pub covers: Option<CoversConfig>,
+
+    /// Upload backups to an S3 bucket in the background.
+    pub s3_backups: Option<S3BackupConfig>,
+
+    /// Limits and targets whose progress is tracked, with alerts when they
+    /// are crossed.
+    #[serde(default)]
+    pub goals: Vec<GoalConfig>,
+
+    /// Expose counters and request latencies at `/metrics` for Prometheus.
+    #[serde(default)]
+    pub metrics: bool,
+
+    /// Export traces of requests to an OpenTelemetry collector.
+    pub tracing: Option<TracingConfig>,
+
+    /// Settings handed out to clients that fetch them.
+    pub client_settings: Option<shared::ClientSettings>,
 }
 
 impl Config {
@@ -39,10 +1140,141 @@ impl Config {
         return Ok(config_path);
     }
 
+    /// Take the settings that are only read at startup from the running
+    /// configuration, so that a reload leaves them as they are until the
+    /// server is restarted.
+    pub fn keep_startup_settings(&mut self, running: &mut Config) {
+        std::mem::swap(&mut self.db_url, &mut running.db_url);
+        std::mem::swap(&mut self.database_retry, &mut running.database_retry);
+        std::mem::swap(&mut self.database_pool, &mut running.database_pool);
+        std::mem::swap(&mut self.listen, &mut running.listen);
+        std::mem::swap(&mut self.users, &mut running.users);
+        std::mem::swap(&mut self.tls, &mut running.tls);
+        std::mem::swap(&mut self.body_limits, &mut running.body_limits);
+        std::mem::swap(&mut self.retention, &mut running.retention);
+        std::mem::swap(&mut self.timezone, &mut running.timezone);
+        std::mem::swap(&mut self.how_long_to_beat, &mut running.how_long_to_beat);
+        std::mem::swap(&mut self.igdb, &mut running.igdb);
+        std::mem::swap(&mut self.email_reports, &mut running.email_reports);
+        std::mem::swap(&mut self.s3_backups, &mut running.s3_backups);
+        std::mem::swap(&mut self.metrics, &mut running.metrics);
+        std::mem::swap(&mut self.tracing, &mut running.tracing);
+    }
+
+    /// Load the configuration file, with the settings that are given as
+    /// environment variables overriding the file. The file can be left out if
+    /// the database URL is given as an environment variable.
     pub fn load(config_path: &Path) -> Result<Self, Error> {
-        debug!("Loading config from {}", config_path.display());
-        let fp = File::open(&config_path).map_err(Error::IOError)?;
-        let config: Config = serde_yaml::from_reader(fp).map_err(Error::DeserialisationError)?;
+        let db_url = std::env::var(DB_URL_VARIABLE).ok();
+        let mut values = match File::open(config_path) {
+            Ok(fp) => {
+                debug!("Loading config from {}", config_path.display());
+                serde_yaml::from_reader(fp).map_err(Error::DeserialisationError)?
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound && db_url.is_some() => {
+                info!(
+                    "No configuration file at {}, using environment variables",
+                    config_path.display()
+                );
+                serde_yaml::Value::Mapping(serde_yaml::Mapping::new())
+            }
+            Err(error) => return Err(Error::IOError(error)),
+        };
+        if let serde_yaml::Value::Mapping(mapping) = &mut values {
+            if let Some(db_url) = db_url {
+                mapping.insert("dbUrl".into(), db_url.into());
+            }
+            if let Ok(secret) = std::env::var(SECRET_VARIABLE) {
+                mapping.insert("secret".into(), secret.into());
+            }
+            if let Ok(secret) = std::env::var(ADMIN_SECRET_VARIABLE) {
+                mapping.insert("adminSecret".into(), secret.into());
+            }
+        }
+        let mut config: Config =
+            serde_yaml::from_value(values).map_err(Error::DeserialisationError)?;
+        config.secret = match &config.secret_source {
+            Some(source) => source.resolve_secret()?,
+            None => None,
+        };
+        config.admin_secret = match &config.admin_secret_source {
+            Some(source) => source.resolve_secret()?,
+            None => None,
+        };
+        for api_key in &mut config.api_keys {
+            api_key.key = api_key.key_source.resolve_secret()?;
+        }
+        if let Some(igdb) = &mut config.igdb {
+            // Logging in to Twitch needs the secret itself.
+            match igdb.client_secret_source.resolve() {
+                Some(secret) => igdb.client_secret = secret,
+                None => {
+                    error!("The IGDB client secret must be set and cannot be a hash");
+                    return Err(Error::SecretError);
+                }
+            }
+        }
+        if let Some(s3_backups) = &mut config.s3_backups {
+            // Signing requests needs the key itself.
+            match s3_backups.secret_access_key_source.resolve() {
+                Some(key) => s3_backups.secret_access_key = key,
+                None => {
+                    error!("The S3 secret access key must be set and cannot be a hash");
+                    return Err(Error::SecretError);
+                }
+            }
+        }
+        if let Some(smtp) = config
+            .email_reports
+            .as_mut()
+            .map(|reports| &mut reports.smtp)
+        {
+            // Logging in to the SMTP server needs the password itself.
+            if let Some(SecretSource::Hashed { .. }) = smtp.password_source {
+                error!("The SMTP password cannot be a hash");
+                return Err(Error::SecretError);
+            }
+            smtp.password = smtp
+                .password_source
+                .as_ref()
+                .and_then(SecretSource::resolve);
+        }
+        for webhook in &mut config.webhooks {
+            // Signing needs the key itself.
+            if let Some(SecretSource::Hashed { .. }) = webhook.secret_source {
+                error!("The secret of webhook {} cannot be a hash", webhook.url);
+                return Err(Error::SecretError);
+            }
+            webhook.secret = webhook
+                .secret_source
+                .as_ref()
+                .and_then(SecretSource::resolve);
+        }
+        for time_tracker in &mut config.time_trackers {
+            // Creating entries needs the token itself.
+            let source = match &time_tracker.service {
+                TimeTrackerService::Toggl { token, .. } => token,
+                TimeTrackerService::Clockify { token, .. } => token,
+                TimeTrackerService::Webhook { .. } => continue,
+            };
+            match source.resolve() {
+                Some(token) => time_tracker.token = Some(token),
+                None => {
+                    error!("The token of a time tracker must be set and cannot be a hash");
+                    return Err(Error::SecretError);
+                }
+            }
+        }
+        if let Some(listen) = env_override::<SocketAddr>(LISTEN_VARIABLE)? {
+            config.listen.address = listen.ip();
+            config.listen.port = listen.port();
+        }
+        if let Some(address) = env_override(LISTEN_ADDRESS_VARIABLE)? {
+            config.listen.address = address;
+        }
+        if let Some(port) = env_override(LISTEN_PORT_VARIABLE)? {
+            config.listen.port = port;
+        }
         return Ok(config);
     }
 }
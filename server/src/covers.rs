@@ -0,0 +1,345 @@
+use std::io::Cursor;
+use std::path::{Path as FilePath, PathBuf};
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Path, Query, State},
+    http::{
+        header::{CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use image::{ImageFormat, ImageReader, Limits};
+use log::{debug, error, info, warn};
+use serde::Deserialize;
+
+use crate::{
+    add_query_key, audit, authorize, config::CoversConfig, config::Scope, devices::admin, etag,
+    util, AppState,
+};
+
+/// Widths that PNG covers are resized to. Other widths get the next wider
+/// one, so that only a few variants are stored.
+const COVER_WIDTHS: [u32; 4] = [64, 128, 256, 512];
+
+/// Widest and tallest PNG that is decoded, so that a small file can't claim
+/// to be enormous.
+const MAXIMUM_SIDE: u32 = 4096;
+
+/// Bytes that every PNG file starts with.
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+#[derive(Deserialize)]
+pub struct CoverQuery {
+    /// Scale the cover down to about this many pixels wide.
+    width: Option<u32>,
+
+    /// Secret or API key, for image tags that cannot send headers.
+    key: Option<String>,
+}
+
+fn covers_config(state: &AppState) -> Option<CoversConfig> {
+    match state.config.read() {
+        Ok(config) => config.covers.clone(),
+        Err(_) => {
+            error!("Could not read configuration for covers");
+            None
+        }
+    }
+}
+
+fn original_path(directory: &FilePath, process_id: i32) -> PathBuf {
+    directory.join(process_id.to_string())
+}
+
+fn variant_path(directory: &FilePath, process_id: i32, width: u32) -> PathBuf {
+    directory.join(format!("{}-{}.png", process_id, width))
+}
+
+/// Delete the resized variants of the cover, which are made again from the
+/// original when they are next asked for.
+fn remove_variants(directory: &FilePath, process_id: i32) {
+    for width in COVER_WIDTHS {
+        let path = variant_path(directory, process_id, width);
+        if let Err(error) = std::fs::remove_file(&path) {
+            if error.kind() != std::io::ErrorKind::NotFound {
+                error!("Could not delete {}: {}", path.display(), error);
+            }
+        }
+    }
+}
+
+/// Replace the cover, writing it beside the old one first so that a failed
+/// upload doesn't leave half an image.
+fn save(directory: &FilePath, process_id: i32, image: &[u8]) -> std::io::Result<()> {
+    std::fs::create_dir_all(directory)?;
+    let temporary = directory.join(format!("{}.tmp", process_id));
+    std::fs::write(&temporary, image)?;
+    std::fs::rename(&temporary, original_path(directory, process_id))?;
+    remove_variants(directory, process_id);
+    Ok(())
+}
+
+/// The PNG scaled down to the width, keeping its aspect ratio, or None if it
+/// isn't a PNG wider than that.
+fn resize(png: &[u8], width: u32) -> Option<Vec<u8>> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAXIMUM_SIDE);
+    limits.max_image_height = Some(MAXIMUM_SIDE);
+    let mut reader = ImageReader::with_format(Cursor::new(png), ImageFormat::Png);
+    reader.limits(limits);
+    let image = match reader.decode() {
+        Ok(image) => image,
+        Err(error) => {
+            debug!("Could not decode cover as PNG: {}", error);
+            return None;
+        }
+    };
+    if image.width() <= width {
+        return None;
+    }
+    let mut resized = Vec::new();
+    if let Err(error) = image
+        .thumbnail(width, u32::MAX)
+        .write_to(&mut Cursor::new(&mut resized), ImageFormat::Png)
+    {
+        error!("Could not encode resized cover: {}", error);
+        return None;
+    }
+    Some(resized)
+}
+
+/// Cover of the process scaled down to the wanted width if it is a PNG wider
+/// than that, otherwise as it was uploaded. Resized variants are stored for
+/// the next time.
+fn load(
+    directory: &FilePath,
+    process_id: i32,
+    width: Option<u32>,
+) -> std::io::Result<Option<Vec<u8>>> {
+    let original = match std::fs::read(original_path(directory, process_id)) {
+        Ok(original) => original,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    let Some(width) = width else {
+        return Ok(Some(original));
+    };
+    let width = COVER_WIDTHS
+        .into_iter()
+        .find(|allowed| *allowed >= width)
+        .unwrap_or(COVER_WIDTHS[COVER_WIDTHS.len() - 1]);
+    let path = variant_path(directory, process_id, width);
+    if let Ok(variant) = std::fs::read(&path) {
+        return Ok(Some(variant));
+    }
+    let Some(variant) = resize(&original, width) else {
+        return Ok(Some(original));
+    };
+    if let Err(error) = std::fs::write(&path, &variant) {
+        error!("Could not store {}: {}", path.display(), error);
+    }
+    Ok(Some(variant))
+}
+
+/// Content type of an image from its first bytes, for the formats accepted
+/// as covers.
+fn image_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(PNG_SIGNATURE) {
+        return Some("image/png");
+    }
+    if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        return Some("image/jpeg");
+    }
+    if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        return Some("image/gif");
+    }
+    if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        return Some("image/webp");
+    }
+    None
+}
+
+/// Set the cover image of a process from the PNG, JPEG, GIF or WebP in the
+/// body.
+pub async fn upload(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(process_id): Path<i32>,
+    body: Body,
+) -> StatusCode {
+    let Some(actor) = admin(&headers, &state) else {
+        return StatusCode::UNAUTHORIZED;
+    };
+    let Some(config) = covers_config(&state) else {
+        return StatusCode::NOT_FOUND;
+    };
+    let Ok(image) = to_bytes(body, config.maximum_size).await else {
+        warn!("Rejected too large cover of process {}", process_id);
+        return StatusCode::PAYLOAD_TOO_LARGE;
+    };
+    let Some(content_type) = image_type(&image) else {
+        warn!("Rejected cover of process {} of unknown type", process_id);
+        return StatusCode::UNSUPPORTED_MEDIA_TYPE;
+    };
+    match state.db.process_exists(process_id).await {
+        Ok(true) => {}
+        Ok(false) => return StatusCode::NOT_FOUND,
+        Err(error) => {
+            error!("Could not look up process {}: {}", process_id, error);
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
+    let saved = tokio::task::spawn_blocking(move || save(&config.directory, process_id, &image));
+    match saved.await {
+        Ok(Ok(())) => {
+            info!("Set the cover of process {}", process_id);
+            let target = Some(format!("process {}", process_id));
+            let details = Some(content_type.to_string());
+            audit::record(&state.db, &actor, audit::Action::SetCover, target, details).await;
+            StatusCode::NO_CONTENT
+        }
+        Ok(Err(error)) => {
+            error!("Could not save cover of process {}: {}", process_id, error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+        Err(error) => {
+            error!("Could not save cover of process {}: {}", process_id, error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Delete the cover image of a process.
+pub async fn delete(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(process_id): Path<i32>,
+) -> StatusCode {
+    let Some(actor) = admin(&headers, &state) else {
+        return StatusCode::UNAUTHORIZED;
+    };
+    let Some(config) = covers_config(&state) else {
+        return StatusCode::NOT_FOUND;
+    };
+    match std::fs::remove_file(original_path(&config.directory, process_id)) {
+        Ok(()) => {
+            remove_variants(&config.directory, process_id);
+            info!("Deleted the cover of process {}", process_id);
+            let target = Some(format!("process {}", process_id));
+            audit::record(&state.db, &actor, audit::Action::DeleteCover, target, None).await;
+            StatusCode::NO_CONTENT
+        }
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        Err(error) => {
+            error!(
+                "Could not delete cover of process {}: {}",
+                process_id, error
+            );
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Cover image of a process, scaled down if it is a PNG and `width` is given.
+#[utoipa::path(
+    get,
+    path = "/processes/{id}/cover",
+    params(
+        ("id" = i32, Path, description = "ID of the process"),
+        ("width" = Option<u32>, Query, description = "Width to scale a PNG cover down to, rounded up to 64, 128, 256 or 512"),
+    ),
+    responses(
+        (status = 200, description = "PNG, JPEG, GIF or WebP cover image", content_type = "image/*"),
+        (status = 304, description = "Not modified since the ETag in If-None-Match"),
+        (status = 401, description = "Not authenticated"),
+        (status = 404, description = "No cover for the process"),
+    ),
+)]
+pub async fn cover(
+    State(state): State<AppState>,
+    mut headers: HeaderMap,
+    Path(process_id): Path<i32>,
+    Query(query): Query<CoverQuery>,
+) -> Result<Response, StatusCode> {
+    add_query_key(&mut headers, query.key.as_deref())?;
+    if authorize(&headers, &state, Scope::Read).await.is_none() {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let Some(config) = covers_config(&state) else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    let loaded =
+        tokio::task::spawn_blocking(move || load(&config.directory, process_id, query.width));
+    let image = match loaded.await {
+        Ok(Ok(Some(image))) => image,
+        Ok(Ok(None)) => return Err(StatusCode::NOT_FOUND),
+        Ok(Err(error)) => {
+            error!("Could not read cover of process {}: {}", process_id, error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        Err(error) => {
+            error!("Could not read cover of process {}: {}", process_id, error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let etag = format!("\"{}\"", &util::sha256_hex(&image)[..32]);
+    let unchanged = headers
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|if_none_match| etag::etag_matches(if_none_match, &etag));
+    if unchanged {
+        return Ok((StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response());
+    }
+    let content_type = image_type(&image).unwrap_or("application/octet-stream");
+    Ok((
+        [(CONTENT_TYPE, content_type.to_string()), (ETAG, etag)],
+        image,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageFormat, Rgba, RgbaImage};
+    use test_case::test_case;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbaImage::from_pixel(width, height, Rgba([200, 100, 50, 255]));
+        let mut png = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    #[test_case(png(8, 4), 4, Some((4, 2)); "aspect ratio")]
+    #[test_case(png(3, 9), 2, Some((2, 6)); "tall")]
+    #[test_case(png(4, 4), 4, None; "same width")]
+    #[test_case(png(1, 1), 64, None; "not scaled up")]
+    #[test_case(b"\xff\xd8\xff\xe0".to_vec(), 64, None; "jpeg")]
+    fn resize(data: Vec<u8>, width: u32, output: Option<(u32, u32)>) {
+        let resized = super::resize(&data, width).map(|resized| {
+            let image = image::load_from_memory_with_format(&resized, ImageFormat::Png).unwrap();
+            (image.width(), image.height())
+        });
+        assert_eq!(resized, output);
+    }
+
+    #[test]
+    fn resize_too_large() {
+        assert_eq!(super::resize(&png(super::MAXIMUM_SIDE + 1, 1), 64), None);
+    }
+
+    #[test_case(b"\x89PNG\r\n\x1a\n\0\0", Some("image/png"); "png")]
+    #[test_case(b"\xff\xd8\xff\xe0\0\x10JFIF", Some("image/jpeg"); "jpeg")]
+    #[test_case(b"GIF89a\x01\0", Some("image/gif"); "gif")]
+    #[test_case(b"RIFF\0\0\0\0WEBPVP8 ", Some("image/webp"); "webp")]
+    #[test_case(b"<svg xmlns=", None; "svg")]
+    fn image_type(data: &[u8], output: Option<&str>) {
+        assert_eq!(super::image_type(data), output);
+    }
+}
@@ -0,0 +1,7 @@
+use axum::response::Html;
+
+/// Dashboard page. The page itself holds no data and is served to anyone;
+/// it asks for the secret and uses it to read `/stats` and `/events`.
+pub async fn index() -> Html<&'static str> {
+    Html(include_str!("dashboard/index.html"))
+}
@@ -1,12 +1,802 @@
-use deadpool_diesel::postgres::Pool;
-use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
 
-pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/");
+use diesel_async::pooled_connection::deadpool::Pool;
+use time::OffsetDateTime;
 
-pub async fn run_migrations(pool: &Pool) {
-    let conn = pool.get().await.unwrap();
-    conn.interact(|conn| conn.run_pending_migrations(MIGRATIONS).map(|_| ()))
+use crate::admin::ProcessSummary;
+use crate::audit::Action;
+use crate::backup::Backup;
+use crate::config::DatabasePoolConfig;
+use crate::devices::Device;
+use crate::howlongtobeat::CompletionTime;
+use crate::igdb::Metadata;
+use crate::processes::Alias;
+use crate::retention::{AgedEvent, Rollup};
+use crate::users::UserIds;
+use crate::util;
+
+#[cfg(feature = "mysql")]
+mod mysql;
+mod postgres;
+mod sqlite;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Could not get a connection from the pool.
+    PoolError(String),
+
+    /// The migrations could not be run.
+    MigrationError(String),
+
+    /// The database returned an error.
+    QueryError(diesel::result::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::PoolError(error) => write!(f, "could not get connection: {}", error),
+            Error::MigrationError(error) => write!(f, "could not run migrations: {}", error),
+            Error::QueryError(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Session to be saved, with names already cleaned up.
+pub struct NewEvent {
+    pub executable: String,
+    pub name: Option<String>,
+    pub time: OffsetDateTime,
+    pub started_at: OffsetDateTime,
+    pub duration: shared::SessionDuration,
+    pub focused_duration: Option<shared::SessionDuration>,
+    pub product_version: Option<String>,
+    pub file_version: Option<String>,
+    pub tags: Vec<String>,
+    pub window_title: Option<String>,
+    pub api_key: Option<String>,
+    pub user_id: Option<i32>,
+    pub device: Option<i32>,
+    pub idempotency_key: Option<String>,
+
+    /// Backfilled by an importer instead of tracked by a client.
+    pub imported: bool,
+
+    /// Entered by hand, such as for a session on a console.
+    pub manual: bool,
+    pub note: Option<String>,
+}
+
+/// Edit or deletion of an event for the audit trail.
+pub struct NewEventChange {
+    pub action: shared::EventChangeAction,
+    pub changed_at: OffsetDateTime,
+    pub changed_by: String,
+    pub before: shared::Event,
+    pub after: Option<shared::Event>,
+}
+
+impl NewEventChange {
+    fn action_name(&self) -> &'static str {
+        match self.action {
+            shared::EventChangeAction::Update => "update",
+            shared::EventChangeAction::Delete => "delete",
+        }
+    }
+}
+
+/// Change in the audit trail as ID, event ID, action, time, who made it and
+/// the events before and after as JSON.
+type EventChangeRow = (
+    i32,
+    i32,
+    String,
+    OffsetDateTime,
+    String,
+    String,
+    Option<String>,
+);
+
+fn event_json(event: &shared::Event) -> String {
+    serde_json::to_string(event).unwrap_or_default()
+}
+
+/// Change from the audit trail, or None if it can't be read.
+fn event_change(row: EventChangeRow) -> Option<shared::EventChange> {
+    let (id, event, action, changed_at, changed_by, before, after) = row;
+    let action = match action.as_str() {
+        "update" => shared::EventChangeAction::Update,
+        "delete" => shared::EventChangeAction::Delete,
+        _ => return None,
+    };
+    let after = match after {
+        Some(after) => Some(serde_json::from_str(&after).ok()?),
+        None => None,
+    };
+    Some(shared::EventChange {
+        id,
+        event,
+        action,
+        changed_at,
+        changed_by,
+        before: serde_json::from_str(&before).ok()?,
+        after,
+    })
+}
+
+/// Mutating action to append to the audit log.
+pub struct NewAuditEntry {
+    pub recorded_at: OffsetDateTime,
+    pub actor: String,
+    pub action: Action,
+    pub target: Option<String>,
+    pub details: Option<String>,
+    pub address: Option<String>,
+}
+
+/// Entry in the audit log as ID, time, actor, action, target, details and
+/// address.
+type AuditRow = (
+    i32,
+    OffsetDateTime,
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+fn audit_entry(row: AuditRow) -> shared::AuditEntry {
+    let (id, recorded_at, actor, action, target, details, address) = row;
+    shared::AuditEntry {
+        id,
+        recorded_at,
+        actor,
+        action,
+        target,
+        details,
+        address,
+    }
+}
+
+/// Share link as ID, token, process filter and creation time.
+type ShareRow = (i32, String, Option<String>, OffsetDateTime);
+
+fn share(row: ShareRow) -> shared::Share {
+    let (id, token, process, created_at) = row;
+    shared::Share {
+        id,
+        token,
+        process,
+        created_at,
+    }
+}
+
+/// Registration state of an existing device as its ID, approved, revoked and
+/// the token.
+pub type DeviceRegistration = (i32, bool, bool, Option<String>);
+
+/// IDs of the process and device, and end times of their first and last
+/// sessions.
+pub type PlayTimes = ((i32, Option<i32>), OffsetDateTime, OffsetDateTime);
+
+/// Executable, name, end time and duration of a session.
+pub type SessionTime = (String, Option<String>, OffsetDateTime, u64);
+
+/// IDs of the process and device, executable, name and device name that
+/// stats are grouped by.
+pub type ProcessKey = (i32, Option<i32>, String, Option<String>, Option<String>);
+
+/// Playtime and sessions of a group, such as a process on a device.
+pub type Totals<K> = (K, u64, u64);
+
+/// Stats of the processes from totals of whole days and parts of days, with
+/// the first and last sessions of live and archived sessions.
+fn process_stats(
+    totals: Vec<Totals<ProcessKey>>,
+    play_times: Vec<PlayTimes>,
+) -> Vec<shared::ProcessStats> {
+    let mut times: HashMap<(i32, Option<i32>), (OffsetDateTime, OffsetDateTime)> = HashMap::new();
+    for (key, first, last) in play_times {
+        let entry = times.entry(key).or_insert((first, last));
+        entry.0 = entry.0.min(first);
+        entry.1 = entry.1.max(last);
+    }
+    util::merge_totals(totals)
+        .into_iter()
+        .map(
+            |((process, device_id, executable, name, device), duration, sessions)| {
+                let times = times.get(&(process, device_id));
+                shared::ProcessStats {
+                    executable,
+                    name,
+                    duration,
+                    sessions,
+                    device,
+                    first_played: times.map(|(first, _)| *first),
+                    last_played: times.map(|(_, last)| *last),
+                    metadata: None,
+                }
+            },
+        )
+        .collect()
+}
+
+/// Size and wait timeout of the connection pool.
+fn pool_config(config: &DatabasePoolConfig) -> deadpool::managed::PoolConfig {
+    let mut pool_config = deadpool::managed::PoolConfig::default();
+    if let Some(maximum_size) = config.maximum_size {
+        pool_config.max_size = maximum_size;
+    }
+    pool_config.timeouts.wait = Some(Duration::from_secs(config.timeout));
+    pool_config
+}
+
+/// Latest event in the scope, audit log entry and metadata and completion
+/// time lookups, which change whenever the stats do.
+pub type DataVersion = (
+    Option<i32>,
+    Option<i32>,
+    Option<OffsetDateTime>,
+    Option<OffsetDateTime>,
+);
+
+/// Run a query function of the backend module matching the database, with
+/// a connection from its pool. PostgreSQL and MySQL connections are
+/// asynchronous, so waiting on the database doesn't hold a thread. SQLite
+/// has no asynchronous driver, so its queries are moved into a blocking
+/// thread of the runtime with the arguments. Failures come back as `Error`
+/// like every other method. Queries of traced requests get a span of their
+/// own named after the function.
+macro_rules! dispatch {
+    ($database:expr, $function:ident $(, $argument:expr)*) => {
+        crate::telemetry::query(stringify!($function), async move {
+            match $database {
+                #[cfg(feature = "mysql")]
+                Database::Mysql(pool) => {
+                    let mut conn = pool
+                        .get()
+                        .await
+                        .map_err(|error| Error::PoolError(error.to_string()))?;
+                    mysql::$function(&mut conn $(, $argument)*)
+                        .await
+                        .map_err(Error::QueryError)
+                }
+                Database::Postgres(pool) => {
+                    let mut conn = pool
+                        .get()
+                        .await
+                        .map_err(|error| Error::PoolError(error.to_string()))?;
+                    postgres::$function(&mut conn $(, $argument)*)
+                        .await
+                        .map_err(Error::QueryError)
+                }
+                Database::Sqlite(pool) => {
+                    sqlite::run(pool, move |conn| sqlite::$function(conn $(, $argument)*)).await
+                }
+            }
+        })
         .await
-        .unwrap()
-        .unwrap();
+    };
+}
+
+/// Connection pool for the database given in `dbUrl`. `sqlite://` URLs use
+/// a SQLite file, `mysql://` URLs a MySQL or MariaDB server if the server is
+/// built with the `mysql` feature, and everything else is treated as a
+/// PostgreSQL URL.
+///
+/// This is the storage layer of the server: handlers and background jobs
+/// only call its methods, which run the query function of the same name in
+/// the module of the backend with `dispatch!`, so another backend is a module
+/// with the same functions. Handlers
+/// that record sessions use it as a `storage::Storage`.
+#[derive(Clone)]
+pub enum Database {
+    #[cfg(feature = "mysql")]
+    Mysql(Pool<diesel_async::AsyncMysqlConnection>),
+    Postgres(Pool<diesel_async::AsyncPgConnection>),
+    Sqlite(Pool<sqlite::AsyncSqliteConnection>),
+}
+
+impl Database {
+    pub fn connect(url: &str, config: &DatabasePoolConfig) -> Result<Self, Error> {
+        if let Some(path) = url.strip_prefix("sqlite://") {
+            return sqlite::connect(path, config).map(Database::Sqlite);
+        }
+        if url.starts_with("mysql://") {
+            #[cfg(feature = "mysql")]
+            return mysql::connect(url, config).map(Database::Mysql);
+            #[cfg(not(feature = "mysql"))]
+            return Err(Error::PoolError(
+                "server was built without MySQL support".to_string(),
+            ));
+        }
+        return postgres::connect(url, config).map(Database::Postgres);
+    }
+
+    pub fn status(&self) -> deadpool::Status {
+        match self {
+            #[cfg(feature = "mysql")]
+            Database::Mysql(pool) => pool.status(),
+            Database::Postgres(pool) => pool.status(),
+            Database::Sqlite(pool) => pool.status(),
+        }
+    }
+
+    /// Close the idle connections and refuse to hand out new ones.
+    pub fn close(&self) {
+        match self {
+            #[cfg(feature = "mysql")]
+            Database::Mysql(pool) => pool.close(),
+            Database::Postgres(pool) => pool.close(),
+            Database::Sqlite(pool) => pool.close(),
+        }
+    }
+
+    pub async fn run_migrations(&self) -> Result<(), Error> {
+        match self {
+            #[cfg(feature = "mysql")]
+            Database::Mysql(pool) => mysql::run_migrations(pool).await,
+            Database::Postgres(pool) => postgres::run_migrations(pool).await,
+            Database::Sqlite(pool) => sqlite::run_migrations(pool).await,
+        }
+    }
+
+    /// Names of the migrations that haven't been run, oldest first.
+    pub async fn pending_migrations(&self) -> Result<Vec<String>, Error> {
+        match self {
+            #[cfg(feature = "mysql")]
+            Database::Mysql(pool) => mysql::pending_migrations(pool).await,
+            Database::Postgres(pool) => postgres::pending_migrations(pool).await,
+            Database::Sqlite(pool) => sqlite::pending_migrations(pool).await,
+        }
+    }
+
+    /// Run a trivial query to check that the database can be used.
+    pub async fn ping(&self) -> Result<(), Error> {
+        dispatch!(self, ping)
+    }
+
+    /// Save the event, unless one with the same idempotency key exists.
+    /// Returns whether the event was saved.
+    pub async fn save_event(&self, event: NewEvent) -> Result<bool, Error> {
+        dispatch!(self, save_event, &event)
+    }
+
+    /// Save the submitted event, unless one with the same idempotency key
+    /// exists or it is a duplicate of another within `tolerance` seconds, see
+    /// `duplicateTolerance`. Returns whether the event was saved.
+    pub async fn save_submission(&self, event: NewEvent, tolerance: u64) -> Result<bool, Error> {
+        dispatch!(self, save_submission, &event, tolerance)
+    }
+
+    /// Playtime per process and device.
+    pub async fn stats(
+        &self,
+        query: shared::StatsQuery,
+        user: Option<i32>,
+    ) -> Result<Vec<shared::ProcessStats>, Error> {
+        dispatch!(self, stats, &query, user, false)
+    }
+
+    /// Names of the processes of the executable.
+    pub async fn process_names(&self, executable: String) -> Result<Vec<Option<String>>, Error> {
+        dispatch!(self, process_names, &executable)
+    }
+
+    /// Executables, names, end times and durations of the matching sessions.
+    pub async fn session_times(
+        &self,
+        query: shared::StatsQuery,
+        user: Option<i32>,
+    ) -> Result<Vec<SessionTime>, Error> {
+        dispatch!(self, session_times, &query, user)
+    }
+
+    /// Playtime per process and device of the processes marked for export.
+    pub async fn public_stats(
+        &self,
+        query: shared::StatsQuery,
+    ) -> Result<Vec<shared::ProcessStats>, Error> {
+        dispatch!(self, stats, &query, None, true)
+    }
+
+    /// Total playtime per category, most played first. Processes without a
+    /// category are left out.
+    pub async fn category_stats(
+        &self,
+        query: shared::StatsQuery,
+        user: Option<i32>,
+    ) -> Result<Vec<shared::CategoryStats>, Error> {
+        dispatch!(self, category_stats, &query, user)
+    }
+
+    /// Up to `limit` events from newest to oldest, starting after the cursor.
+    pub async fn events(
+        &self,
+        query: shared::EventsQuery,
+        cursor: Option<(OffsetDateTime, i32)>,
+        limit: i64,
+        user: Option<i32>,
+    ) -> Result<Vec<shared::Event>, Error> {
+        dispatch!(self, events, &query, cursor, limit, user)
+    }
+
+    /// Session with the ID, if it exists and belongs to the user when one is
+    /// given.
+    pub async fn event(&self, id: i32, user: Option<i32>) -> Result<Option<shared::Event>, Error> {
+        dispatch!(self, event, id, user)
+    }
+
+    /// Sessions of the process that belong to the user when one is given, or
+    /// None if there is no such process.
+    pub async fn process_sessions(
+        &self,
+        process_id: i32,
+        user: Option<i32>,
+    ) -> Result<Option<Vec<shared::Event>>, Error> {
+        dispatch!(self, process_sessions, process_id, user)
+    }
+
+    /// Change the times of the event to those of `change.after` and record
+    /// the change. Returns the number of events changed.
+    pub async fn update_event(&self, change: NewEventChange) -> Result<usize, Error> {
+        dispatch!(self, update_event, &change)
+    }
+
+    /// Delete the event of `change.before` and record the deletion. Returns
+    /// the number of events deleted.
+    pub async fn delete_event(&self, change: NewEventChange) -> Result<usize, Error> {
+        dispatch!(self, delete_event, &change)
+    }
+
+    /// Edits and deletions of the event, oldest first, if it belongs or
+    /// belonged to the user when one is given.
+    pub async fn event_changes(
+        &self,
+        id: i32,
+        user: Option<i32>,
+    ) -> Result<Vec<shared::EventChange>, Error> {
+        dispatch!(self, event_changes, id, user)
+    }
+
+    /// What the stats of the user, or everyone, are computed from, to tell
+    /// whether they have changed without computing them.
+    pub async fn data_version(&self, user: Option<i32>) -> Result<DataVersion, Error> {
+        dispatch!(self, data_version, user)
+    }
+
+    /// Append the entry to the audit log.
+    pub async fn record_audit(&self, entry: NewAuditEntry) -> Result<(), Error> {
+        dispatch!(self, record_audit, &entry)
+    }
+
+    /// Audit log entries from newest to oldest, starting after the entry
+    /// with the ID `before` when given.
+    pub async fn audit_log(
+        &self,
+        query: shared::AuditQuery,
+        before: Option<i32>,
+        limit: i64,
+    ) -> Result<Vec<shared::AuditEntry>, Error> {
+        dispatch!(self, audit_log, &query, before, limit)
+    }
+
+    /// Save a share link of the stats of the user, or everyone, with the
+    /// token.
+    pub async fn create_share(
+        &self,
+        token: String,
+        user: Option<i32>,
+        process: Option<String>,
+    ) -> Result<shared::Share, Error> {
+        dispatch!(self, create_share, &token, user, process.as_deref())
+    }
+
+    /// Share links of the user, or everyone's, newest first.
+    pub async fn shares(&self, user: Option<i32>) -> Result<Vec<shared::Share>, Error> {
+        dispatch!(self, shares, user)
+    }
+
+    /// User and process filter of the share link with the token.
+    pub async fn find_share(
+        &self,
+        token: String,
+    ) -> Result<Option<(Option<i32>, Option<String>)>, Error> {
+        dispatch!(self, find_share, &token)
+    }
+
+    /// Delete the share link, only if it belongs to the user when one is
+    /// given. Returns the number of links deleted.
+    pub async fn delete_share(&self, id: i32, user: Option<i32>) -> Result<usize, Error> {
+        dispatch!(self, delete_share, id, user)
+    }
+
+    /// Create or update users by name and return their IDs.
+    pub async fn sync_users(&self, users: Vec<(String, bool)>) -> Result<UserIds, Error> {
+        dispatch!(self, sync_users, users)
+    }
+
+    /// Playtime per user for the users on the leaderboard.
+    pub async fn leaderboard(
+        &self,
+        query: shared::StatsQuery,
+    ) -> Result<Vec<shared::LeaderboardEntry>, Error> {
+        dispatch!(self, leaderboard, &query)
+    }
+
+    /// ID and user of the approved and not revoked device with the token.
+    pub async fn authenticate_device(
+        &self,
+        token: String,
+    ) -> Result<Option<(i32, Option<i32>)>, Error> {
+        dispatch!(self, authenticate_device, &token)
+    }
+
+    /// Registration state of the device with the nonce. New devices are
+    /// added as pending and give `None`.
+    pub async fn register_device(
+        &self,
+        name: String,
+        nonce: String,
+    ) -> Result<Option<DeviceRegistration>, Error> {
+        dispatch!(self, register_device, &name, &nonce)
+    }
+
+    pub async fn list_devices(&self) -> Result<Vec<Device>, Error> {
+        dispatch!(self, list_devices)
+    }
+
+    /// Approve a device that hasn't been revoked. Returns the number of
+    /// devices updated.
+    pub async fn approve_device(
+        &self,
+        device_id: i32,
+        token: String,
+        user: Option<i32>,
+    ) -> Result<usize, Error> {
+        dispatch!(self, approve_device, device_id, &token, user)
+    }
+
+    /// Revoke a device and drop its token. Returns the number of devices
+    /// updated.
+    pub async fn revoke_device(&self, device_id: i32) -> Result<usize, Error> {
+        dispatch!(self, revoke_device, device_id)
+    }
+
+    pub async fn backup(&self) -> Result<Backup, Error> {
+        dispatch!(self, backup)
+    }
+
+    /// Restore the backup unless there already are processes, events or
+    /// devices. Returns whether the backup was restored.
+    pub async fn restore(&self, backup: Backup) -> Result<bool, Error> {
+        dispatch!(self, restore, &backup)
+    }
+
+    /// Delete the events, archived or not, that ended before the time.
+    /// Returns the number of events deleted.
+    pub async fn delete_events_before(&self, time: OffsetDateTime) -> Result<usize, Error> {
+        dispatch!(self, delete_events_before, time)
+    }
+
+    /// Move the events that ended before the time to the archive, where they
+    /// still count in stats but are left out of listings. Returns the number
+    /// of events moved.
+    pub async fn archive_events_before(&self, time: OffsetDateTime) -> Result<usize, Error> {
+        dispatch!(self, archive_events_before, time, OffsetDateTime::now_utc())
+    }
+
+    /// Events that ended before the time.
+    pub async fn aged_events(&self, time: OffsetDateTime) -> Result<Vec<AgedEvent>, Error> {
+        dispatch!(self, aged_events, time)
+    }
+
+    /// Replace events with their rollups.
+    pub async fn replace_events(&self, rollups: Vec<Rollup>) -> Result<(), Error> {
+        dispatch!(self, replace_events, &rollups)
+    }
+
+    /// Processes ordered by executable, optionally only those whose
+    /// executable or name contains the text.
+    pub async fn list_processes(
+        &self,
+        search: Option<String>,
+    ) -> Result<Vec<ProcessSummary>, Error> {
+        dispatch!(self, list_processes, search.as_deref())
+    }
+
+    /// Set or clear the name of a process. Returns the number of processes
+    /// updated.
+    pub async fn rename_process(&self, id: i32, name: Option<String>) -> Result<usize, Error> {
+        dispatch!(self, rename_process, id, name.as_deref())
+    }
+
+    /// Include or leave out the process in `/public/stats`. Returns the
+    /// number of processes updated.
+    pub async fn set_export(&self, id: i32, export: bool) -> Result<usize, Error> {
+        dispatch!(self, set_export, id, export)
+    }
+
+    pub async fn process_exists(&self, id: i32) -> Result<bool, Error> {
+        dispatch!(self, process_exists, id)
+    }
+
+    /// Hide the process from stats or show it again. Returns the number of
+    /// processes updated.
+    pub async fn set_hidden(&self, id: i32, hidden: bool) -> Result<usize, Error> {
+        dispatch!(self, set_hidden, id, hidden)
+    }
+
+    /// Move the events, aliases and categories of the source process to the
+    /// target, delete the source and add its executable and name as an alias
+    /// of the target. Returns the number of events moved, or None if either
+    /// process does not exist.
+    pub async fn merge_processes(&self, source: i32, target: i32) -> Result<Option<usize>, Error> {
+        dispatch!(self, merge_processes, source, target)
+    }
+
+    /// Delete the events, archived or not, of the process and/or that ended
+    /// before the time. Returns the number of events deleted.
+    pub async fn delete_events(
+        &self,
+        process: Option<i32>,
+        before: Option<OffsetDateTime>,
+    ) -> Result<usize, Error> {
+        dispatch!(self, delete_events, process, before)
+    }
+
+    /// Give the sessions submitted with the API key before it belonged to a
+    /// user to the user. Returns the number of sessions given.
+    pub async fn assign_key_events(&self, api_key: String, user_id: i32) -> Result<usize, Error> {
+        dispatch!(self, assign_key_events, api_key, user_id)
+    }
+
+    pub async fn list_aliases(&self) -> Result<Vec<Alias>, Error> {
+        dispatch!(self, list_aliases)
+    }
+
+    /// Add an alias for the process, replacing any alias with the same
+    /// executable and name. Returns the ID of the alias, or None if the
+    /// process does not exist.
+    pub async fn add_alias(
+        &self,
+        executable: String,
+        name: Option<String>,
+        process: i32,
+    ) -> Result<Option<i32>, Error> {
+        dispatch!(self, add_alias, &executable, name.as_deref(), process)
+    }
+
+    /// Returns the number of aliases deleted.
+    pub async fn delete_alias(&self, id: i32) -> Result<usize, Error> {
+        dispatch!(self, delete_alias, id)
+    }
+
+    pub async fn process_categories(&self, process: i32) -> Result<Vec<String>, Error> {
+        dispatch!(self, process_categories, process)
+    }
+
+    /// ID, executable and name of the processes that haven't been looked up
+    /// on HowLongToBeat since the time.
+    pub async fn completion_lookups(
+        &self,
+        stale_before: OffsetDateTime,
+    ) -> Result<Vec<(i32, String, Option<String>)>, Error> {
+        dispatch!(self, completion_lookups, stale_before)
+    }
+
+    /// Remember the result of looking up the process on HowLongToBeat,
+    /// `None` if it wasn't found.
+    pub async fn save_completion_time(
+        &self,
+        process: i32,
+        time: Option<CompletionTime>,
+        checked_at: OffsetDateTime,
+    ) -> Result<(), Error> {
+        dispatch!(
+            self,
+            save_completion_time,
+            process,
+            time.as_ref(),
+            checked_at
+        )
+    }
+
+    /// Executable, name and completion time of the processes found on
+    /// HowLongToBeat.
+    pub async fn completion_times(
+        &self,
+    ) -> Result<Vec<(String, Option<String>, CompletionTime)>, Error> {
+        dispatch!(self, completion_times)
+    }
+
+    /// ID, executable and name of the processes that haven't been looked up
+    /// on IGDB since the time.
+    pub async fn metadata_lookups(
+        &self,
+        stale_before: OffsetDateTime,
+    ) -> Result<Vec<(i32, String, Option<String>)>, Error> {
+        dispatch!(self, metadata_lookups, stale_before)
+    }
+
+    /// Remember the result of looking up the process on IGDB, `None` if it
+    /// wasn't found.
+    pub async fn save_metadata(
+        &self,
+        process: i32,
+        metadata: Option<Metadata>,
+        checked_at: OffsetDateTime,
+    ) -> Result<(), Error> {
+        dispatch!(self, save_metadata, process, metadata.as_ref(), checked_at)
+    }
+
+    /// Executable, name and details of the processes found on IGDB.
+    pub async fn game_metadata(
+        &self,
+    ) -> Result<Vec<(String, Option<String>, shared::GameMetadata)>, Error> {
+        dispatch!(self, game_metadata)
+    }
+
+    /// End of the last period the email report was sent for.
+    pub async fn report_sent_until(&self, report: String) -> Result<Option<OffsetDateTime>, Error> {
+        dispatch!(self, report_sent_until, &report)
+    }
+
+    /// Remember that the email report was sent for the period ending at
+    /// `until`.
+    pub async fn save_report_sent(
+        &self,
+        report: String,
+        until: OffsetDateTime,
+    ) -> Result<(), Error> {
+        dispatch!(self, save_report_sent, &report, until)
+    }
+
+    /// Start of the last period the goal was alerted for.
+    pub async fn goal_alerted_since(&self, goal: String) -> Result<Option<OffsetDateTime>, Error> {
+        dispatch!(self, goal_alerted_since, &goal)
+    }
+
+    /// Remember that the goal was alerted for the period starting at `start`.
+    pub async fn save_goal_alert(&self, goal: String, start: OffsetDateTime) -> Result<(), Error> {
+        dispatch!(self, save_goal_alert, &goal, start)
+    }
+
+    /// Replace the categories of the process. Returns false if the process
+    /// does not exist.
+    pub async fn set_categories(
+        &self,
+        process: i32,
+        categories: Vec<String>,
+    ) -> Result<bool, Error> {
+        dispatch!(self, set_categories, process, &categories)
+    }
+
+    /// Save the latest state of a session in progress under its session ID,
+    /// replacing the previous heartbeat.
+    pub async fn save_heartbeat(&self, session_id: String, event: NewEvent) -> Result<(), Error> {
+        dispatch!(self, save_heartbeat, &session_id, &event)
+    }
+
+    /// Returns the number of heartbeats deleted.
+    pub async fn delete_heartbeat(&self, session_id: String) -> Result<usize, Error> {
+        dispatch!(self, delete_heartbeat, &session_id)
+    }
+
+    /// Sessions in progress, oldest first.
+    pub async fn now_playing(&self, user: Option<i32>) -> Result<Vec<shared::NowPlaying>, Error> {
+        dispatch!(self, now_playing, user)
+    }
+
+    /// Save the sessions whose last heartbeat was before the time as events
+    /// ending at the heartbeat, and delete the heartbeats. Returns the events
+    /// that were saved, leaving out sessions that were already submitted.
+    pub async fn reap_heartbeats(&self, before: OffsetDateTime) -> Result<Vec<NewEvent>, Error> {
+        dispatch!(self, reap_heartbeats, before)
+    }
 }
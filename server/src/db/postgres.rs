@@ -0,0 +1,2847 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use deadpool::Runtime;
+use diesel::{
+    dsl::count,
+    expression_methods::EscapeExpressionMethods,
+    pg::{data_types::PgInterval, Pg},
+    result::{DatabaseErrorKind::UniqueViolation, Error::DatabaseError},
+    sql_types::{Integer, Interval, Timestamptz},
+    upsert::excluded,
+    BoolExpressionMethods, ConnectionError, ConnectionResult, ExpressionMethods, IntoSql,
+    NullableExpressionMethods, OptionalExtension, PgTextExpressionMethods, QueryDsl, QueryResult,
+};
+use diesel_async::{
+    async_connection_wrapper::AsyncConnectionWrapper,
+    pooled_connection::{
+        deadpool::{Hook, HookError, Object, Pool},
+        AsyncDieselConnectionManager, ManagerConfig,
+    },
+    scoped_futures::ScopedFutureExt,
+    AsyncConnection, AsyncPgConnection, RunQueryDsl,
+};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use futures_util::future::{BoxFuture, FutureExt};
+use rustls::{crypto::ring, ClientConfig, RootCertStore};
+use shared::SessionDuration;
+use time::OffsetDateTime;
+use tokio_postgres_rustls::MakeRustlsConnect;
+
+use super::{
+    audit_entry, event_change, event_json, pool_config, process_stats, share, AuditRow,
+    DataVersion, DeviceRegistration, Error, EventChangeRow, NewAuditEntry, NewEvent,
+    NewEventChange, PlayTimes, ProcessKey, SessionTime, ShareRow, Totals,
+};
+use crate::admin::ProcessSummary;
+use crate::backup::{self, Backup, BackupEvent, UserMapping, RESTORE_BATCH_SIZE};
+use crate::config::DatabasePoolConfig;
+use crate::devices::Device;
+use crate::howlongtobeat::CompletionTime;
+use crate::igdb::Metadata;
+use crate::processes::Alias;
+use crate::retention::{AgedEvent, Rollup};
+use crate::users::UserIds;
+use crate::{schema, util};
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/");
+
+diesel::define_sql_function! {
+    /// `sum` for intervals. `diesel::dsl::sum` trips the ambiguous glob
+    /// import lint on current compilers.
+    #[aggregate]
+    #[sql_name = "sum"]
+    fn sum_interval(x: Interval) -> Nullable<Interval>;
+}
+
+diesel::define_sql_function! {
+    /// `sum` for the sessions of daily stats, which are integers so that the
+    /// sum is a `bigint` rather than a `numeric`.
+    #[aggregate]
+    #[sql_name = "sum"]
+    fn sum_sessions(x: Integer) -> Nullable<BigInt>;
+}
+
+// Stats are grouped by process and device. Kept out of the generated schema.
+diesel::allow_columns_to_appear_in_same_group_by_clause!(
+    schema::devices::id,
+    schema::devices::name,
+    schema::processes::id,
+    schema::processes::executable,
+    schema::processes::name,
+);
+
+fn to_interval(seconds: u64) -> PgInterval {
+    PgInterval::from_microseconds(SessionDuration::from_secs(seconds).as_micros())
+}
+
+/// Connect with TLS if the server supports it, or as `sslmode` in the URL
+/// says. Certificates are verified against the Mozilla root certificates.
+fn establish(url: &str) -> BoxFuture<'_, ConnectionResult<AsyncPgConnection>> {
+    async move {
+        let provider = Arc::new(ring::default_provider());
+        let tls_config = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(|error| ConnectionError::BadConnection(error.to_string()))?
+            .with_root_certificates(RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+            })
+            .with_no_client_auth();
+        let (client, connection) = tokio_postgres::connect(url, MakeRustlsConnect::new(tls_config))
+            .await
+            .map_err(|error| ConnectionError::BadConnection(error.to_string()))?;
+        AsyncPgConnection::try_from_client_and_connection(client, connection).await
+    }
+    .boxed()
+}
+
+pub fn connect(url: &str, config: &DatabasePoolConfig) -> Result<Pool<AsyncPgConnection>, Error> {
+    let mut manager_config = ManagerConfig::default();
+    manager_config.custom_setup = Box::new(establish);
+    let manager = AsyncDieselConnectionManager::new_with_config(url, manager_config);
+    let maximum_lifetime = config.maximum_lifetime.map(Duration::from_secs);
+    Pool::builder(manager)
+        .config(pool_config(config))
+        .runtime(Runtime::Tokio1)
+        .pre_recycle(Hook::sync_fn(move |_, metrics| match maximum_lifetime {
+            Some(lifetime) if metrics.age() > lifetime => Err(HookError::message(
+                "connection is past its maximum lifetime",
+            )),
+            _ => Ok(()),
+        }))
+        .build()
+        .map_err(|error| Error::PoolError(error.to_string()))
+}
+
+/// Run the migrations with a synchronous connection in a blocking thread,
+/// which is what Diesel's migration harness needs.
+async fn migrate<F, R>(pool: &Pool<AsyncPgConnection>, migrations: F) -> Result<R, Error>
+where
+    F: FnOnce(&mut AsyncConnectionWrapper<AsyncPgConnection>) -> diesel::migration::Result<R>
+        + Send
+        + 'static,
+    R: Send + 'static,
+{
+    let conn = pool
+        .get()
+        .await
+        .map_err(|error| Error::PoolError(error.to_string()))?;
+    let conn = Object::take(conn);
+    tokio::task::spawn_blocking(move || {
+        let mut conn = AsyncConnectionWrapper::<AsyncPgConnection>::from(conn);
+        migrations(&mut conn).map_err(|error| error.to_string())
+    })
+    .await
+    .map_err(|error| Error::MigrationError(error.to_string()))?
+    .map_err(Error::MigrationError)
+}
+
+pub async fn run_migrations(pool: &Pool<AsyncPgConnection>) -> Result<(), Error> {
+    migrate(pool, |conn| {
+        conn.run_pending_migrations(MIGRATIONS).map(|_| ())
+    })
+    .await
+}
+
+/// Names of the migrations that haven't been run.
+pub async fn pending_migrations(pool: &Pool<AsyncPgConnection>) -> Result<Vec<String>, Error> {
+    migrate(pool, |conn| {
+        conn.pending_migrations(MIGRATIONS).map(|migrations| {
+            migrations
+                .iter()
+                .map(|migration| migration.name().to_string())
+                .collect()
+        })
+    })
+    .await
+}
+
+pub async fn ping(conn: &mut AsyncPgConnection) -> QueryResult<()> {
+    diesel::sql_query("SELECT 1")
+        .execute(conn)
+        .await
+        .map(|_| ())
+}
+
+fn process_query<'a>(event: &'a NewEvent) -> schema::processes::BoxedQuery<'a, Pg, Integer> {
+    use schema::processes::dsl::*;
+
+    let query = processes
+        .limit(1)
+        .select(id)
+        .filter(executable.eq(&event.executable))
+        .into_boxed();
+    match &event.name {
+        Some(process_name) => query.filter(name.eq(process_name)),
+        None => query.filter(name.is_null()),
+    }
+}
+
+fn alias_query<'a>(event: &'a NewEvent) -> schema::process_aliases::BoxedQuery<'a, Pg, Integer> {
+    use schema::process_aliases::dsl::*;
+
+    let query = process_aliases
+        .limit(1)
+        .select(process)
+        .filter(executable.eq(&event.executable))
+        .into_boxed();
+    match &event.name {
+        Some(alias_name) => query.filter(name.eq(alias_name)),
+        None => query.filter(name.is_null()),
+    }
+}
+
+async fn get_process(conn: &mut AsyncPgConnection, event: &NewEvent) -> QueryResult<i32> {
+    use schema::processes::dsl::*;
+
+    if let Some(result) = alias_query(event).first::<i32>(conn).await.optional()? {
+        return Ok(result);
+    }
+    if let Some(result) = process_query(event).first::<i32>(conn).await.optional()? {
+        return Ok(result);
+    }
+
+    // New processes are left out of the public stats until they are
+    // included explicitly.
+    match diesel::insert_into(processes)
+        .values((
+            executable.eq(&event.executable),
+            name.eq(&event.name),
+            export.eq(false),
+        ))
+        .returning(id)
+        .get_result::<i32>(conn)
+        .await
+    {
+        // Saved by a concurrent submission.
+        Err(DatabaseError(UniqueViolation, _)) => process_query(event).first::<i32>(conn).await,
+        result => result,
+    }
+}
+
+/// Names of the processes of the executable.
+pub async fn process_names(
+    conn: &mut AsyncPgConnection,
+    process_executable: &str,
+) -> QueryResult<Vec<Option<String>>> {
+    use schema::processes::dsl::*;
+
+    processes
+        .filter(executable.eq(process_executable))
+        .select(name)
+        .load::<Option<String>>(conn)
+        .await
+}
+
+/// Whether an archived event has the idempotency key.
+async fn archived(conn: &mut AsyncPgConnection, key: &str) -> QueryResult<bool> {
+    use schema::archived_events::dsl::*;
+
+    let found = archived_events
+        .filter(idempotency_key.eq(key))
+        .count()
+        .get_result::<i64>(conn)
+        .await?;
+    Ok(found > 0)
+}
+
+pub async fn save_event(conn: &mut AsyncPgConnection, event: &NewEvent) -> QueryResult<bool> {
+    use schema::events::dsl::*;
+
+    if let Some(key) = &event.idempotency_key {
+        if archived(conn, key).await? {
+            return Ok(false);
+        }
+    }
+    let process_id = get_process(conn, event).await?;
+    let saved = diesel::insert_into(events)
+        .values((
+            time.eq(event.time),
+            started_at.eq(event.started_at),
+            process.eq(process_id),
+            duration.eq(to_interval(event.duration.as_secs())),
+            focused_duration.eq(event
+                .focused_duration
+                .map(SessionDuration::as_secs)
+                .map(to_interval)),
+            tags.eq(&event.tags),
+            window_title.eq(&event.window_title),
+            api_key.eq(&event.api_key),
+            user_id.eq(event.user_id),
+            device.eq(event.device),
+            product_version.eq(&event.product_version),
+            file_version.eq(&event.file_version),
+            idempotency_key.eq(&event.idempotency_key),
+            imported.eq(event.imported),
+            manual.eq(event.manual),
+            note.eq(&event.note),
+        ))
+        .on_conflict(idempotency_key)
+        .do_nothing()
+        .execute(conn)
+        .await?;
+    Ok(saved > 0)
+}
+
+/// Whether an event of the same process, device, API key and user overlaps
+/// the event by more than `tolerance` seconds, or starts and ends within
+/// `tolerance` seconds of it.
+async fn overlapping(
+    conn: &mut AsyncPgConnection,
+    event: &NewEvent,
+    process_id: i32,
+    tolerance: u64,
+) -> QueryResult<bool> {
+    use schema::events::dsl::*;
+
+    let tolerance = std::time::Duration::from_secs(tolerance);
+    let mut query = events
+        .select(id)
+        .filter(process.eq(process_id))
+        .filter(
+            started_at
+                .lt(event.time - tolerance)
+                .and(time.gt(event.started_at + tolerance))
+                .or(started_at
+                    .between(event.started_at - tolerance, event.started_at + tolerance)
+                    .and(time.between(event.time - tolerance, event.time + tolerance))),
+        )
+        .into_boxed();
+    query = match event.device {
+        Some(value) => query.filter(device.eq(value)),
+        None => query.filter(device.is_null()),
+    };
+    query = match &event.api_key {
+        Some(value) => query.filter(api_key.eq(value)),
+        None => query.filter(api_key.is_null()),
+    };
+    query = match event.user_id {
+        Some(value) => query.filter(user_id.eq(value)),
+        None => query.filter(user_id.is_null()),
+    };
+    Ok(query.first::<i32>(conn).await.optional()?.is_some())
+}
+
+pub async fn save_submission(
+    conn: &mut AsyncPgConnection,
+    event: &NewEvent,
+    tolerance: u64,
+) -> QueryResult<bool> {
+    conn.transaction(|conn| {
+        async move {
+            let process_id = get_process(conn, event).await?;
+            if overlapping(conn, event, process_id, tolerance).await? {
+                return Ok(false);
+            }
+            save_event(conn, event).await
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+pub async fn stats(
+    conn: &mut AsyncPgConnection,
+    query: &shared::StatsQuery,
+    user: Option<i32>,
+    exported_only: bool,
+) -> QueryResult<Vec<shared::ProcessStats>> {
+    let (days, edges) = util::split_days(query.since, query.until);
+    let mut totals = Vec::new();
+    if let Some(days) = days {
+        totals.extend(daily_process_stats(conn, query, days, user, exported_only).await?);
+    }
+    for (since, until) in edges {
+        totals.extend(event_process_stats(conn, query, since, until, user, exported_only).await?);
+    }
+    let play_times = play_times(conn, query, user, exported_only).await?;
+    Ok(process_stats(totals, play_times))
+}
+
+/// First and last sessions per process and device, live and archived,
+/// whatever the range of the query.
+async fn play_times(
+    conn: &mut AsyncPgConnection,
+    query: &shared::StatsQuery,
+    user: Option<i32>,
+    exported_only: bool,
+) -> QueryResult<Vec<PlayTimes>> {
+    use schema::{archived_events, devices, events, processes};
+
+    // The live and archived sessions are the same columns in different tables.
+    macro_rules! play_times_in {
+        ($events:ident) => {{
+            let mut statement = $events::table
+                .inner_join(processes::table)
+                .left_join(devices::table)
+                .filter(processes::hidden.eq(false))
+                .group_by(($events::process, $events::device))
+                .select((
+                    $events::process,
+                    $events::device,
+                    diesel::dsl::min($events::time),
+                    diesel::dsl::max($events::time),
+                ))
+                .into_boxed();
+            if exported_only {
+                statement = statement.filter(processes::export.eq(true));
+            }
+            if let Some(user) = user {
+                statement = statement.filter($events::user_id.eq(user));
+            }
+            if let Some(device) = &query.device {
+                statement = statement.filter(devices::name.eq(device));
+            }
+            if let Some(process) = &query.process {
+                let pattern = util::like_pattern(process);
+                statement = statement.filter(
+                    processes::executable
+                        .ilike(pattern.clone())
+                        .escape('\\')
+                        .or(processes::name.ilike(pattern).escape('\\')),
+                );
+            }
+            statement
+                .load::<(
+                    i32,
+                    Option<i32>,
+                    Option<OffsetDateTime>,
+                    Option<OffsetDateTime>,
+                )>(conn)
+                .await?
+        }};
+    }
+    let mut rows = play_times_in!(events);
+    rows.extend(play_times_in!(archived_events));
+    Ok(rows
+        .into_iter()
+        .filter_map(|(process, device, first, last)| Some(((process, device), first?, last?)))
+        .collect())
+}
+
+/// Playtime per process and device in the whole days from the daily totals.
+async fn daily_process_stats(
+    conn: &mut AsyncPgConnection,
+    query: &shared::StatsQuery,
+    (first, end): util::DayRange,
+    user: Option<i32>,
+    exported_only: bool,
+) -> QueryResult<Vec<Totals<ProcessKey>>> {
+    use schema::{daily_stats, devices, processes};
+
+    let mut statement = daily_stats::table
+        .inner_join(processes::table)
+        .left_join(devices::table)
+        .filter(processes::hidden.eq(false))
+        .filter(daily_stats::sessions.gt(0))
+        .group_by((
+            processes::id,
+            processes::executable,
+            processes::name,
+            devices::id,
+            devices::name,
+        ))
+        .select((
+            processes::id,
+            devices::id.nullable(),
+            processes::executable,
+            processes::name,
+            devices::name.nullable(),
+            sum_interval(daily_stats::duration),
+            sum_sessions(daily_stats::sessions),
+        ))
+        .into_boxed();
+    if let Some(first) = first {
+        statement = statement.filter(daily_stats::day.ge(first));
+    }
+    if let Some(end) = end {
+        statement = statement.filter(daily_stats::day.lt(end));
+    }
+    if exported_only {
+        statement = statement.filter(processes::export.eq(true));
+    }
+    if let Some(user) = user {
+        statement = statement.filter(daily_stats::user_id.eq(user));
+    }
+    if let Some(device) = &query.device {
+        statement = statement.filter(devices::name.eq(device));
+    }
+    if let Some(process) = &query.process {
+        let pattern = util::like_pattern(process);
+        statement = statement.filter(
+            processes::executable
+                .ilike(pattern.clone())
+                .escape('\\')
+                .or(processes::name.ilike(pattern).escape('\\')),
+        );
+    }
+    let rows = statement
+        .load::<(
+            i32,
+            Option<i32>,
+            String,
+            Option<String>,
+            Option<String>,
+            Option<PgInterval>,
+            Option<i64>,
+        )>(conn)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(process, device, executable, name, device_name, duration, sessions)| {
+                (
+                    (process, device, executable, name, device_name),
+                    duration.as_ref().map(util::interval_seconds).unwrap_or(0),
+                    sessions.unwrap_or(0) as u64,
+                )
+            },
+        )
+        .collect())
+}
+
+/// Playtime per process and device in a part of a day from the events.
+async fn event_process_stats(
+    conn: &mut AsyncPgConnection,
+    query: &shared::StatsQuery,
+    since: OffsetDateTime,
+    until: OffsetDateTime,
+    user: Option<i32>,
+    exported_only: bool,
+) -> QueryResult<Vec<Totals<ProcessKey>>> {
+    use schema::{archived_events, devices, events, processes};
+
+    // Archived sessions count towards their days as in the daily totals.
+    // They are the same columns in a different table.
+    macro_rules! totals_in {
+        ($events:ident) => {{
+            let mut statement = $events::table
+                .inner_join(processes::table)
+                .left_join(devices::table)
+                .filter(processes::hidden.eq(false))
+                .filter($events::time.ge(since))
+                .filter($events::time.lt(until))
+                .group_by((
+                    processes::id,
+                    processes::executable,
+                    processes::name,
+                    devices::id,
+                    devices::name,
+                ))
+                .select((
+                    processes::id,
+                    devices::id.nullable(),
+                    processes::executable,
+                    processes::name,
+                    devices::name.nullable(),
+                    sum_interval($events::duration),
+                    count($events::id),
+                ))
+                .into_boxed();
+            if exported_only {
+                statement = statement.filter(processes::export.eq(true));
+            }
+            if let Some(user) = user {
+                statement = statement.filter($events::user_id.eq(user));
+            }
+            if let Some(device) = &query.device {
+                statement = statement.filter(devices::name.eq(device));
+            }
+            if let Some(process) = &query.process {
+                let pattern = util::like_pattern(process);
+                statement = statement.filter(
+                    processes::executable
+                        .ilike(pattern.clone())
+                        .escape('\\')
+                        .or(processes::name.ilike(pattern).escape('\\')),
+                );
+            }
+            statement
+                .load::<(
+                    i32,
+                    Option<i32>,
+                    String,
+                    Option<String>,
+                    Option<String>,
+                    Option<PgInterval>,
+                    i64,
+                )>(conn)
+                .await?
+        }};
+    }
+    let mut rows = totals_in!(events);
+    rows.extend(totals_in!(archived_events));
+    Ok(rows
+        .into_iter()
+        .map(
+            |(process, device, executable, name, device_name, duration, sessions)| {
+                (
+                    (process, device, executable, name, device_name),
+                    duration.as_ref().map(util::interval_seconds).unwrap_or(0),
+                    sessions as u64,
+                )
+            },
+        )
+        .collect())
+}
+
+/// Executables, names, end times and durations of the sessions matching the
+/// filters of the query, other than `by_device`.
+pub async fn session_times(
+    conn: &mut AsyncPgConnection,
+    query: &shared::StatsQuery,
+    user: Option<i32>,
+) -> QueryResult<Vec<SessionTime>> {
+    use schema::{devices, events, processes};
+
+    let mut statement = events::table
+        .inner_join(processes::table)
+        .left_join(devices::table)
+        .filter(processes::hidden.eq(false))
+        .select((
+            processes::executable,
+            processes::name,
+            events::time,
+            events::duration,
+        ))
+        .into_boxed();
+    if let Some(since) = query.since {
+        statement = statement.filter(events::time.ge(since));
+    }
+    if let Some(until) = query.until {
+        statement = statement.filter(events::time.lt(until));
+    }
+    if let Some(user) = user {
+        statement = statement.filter(events::user_id.eq(user));
+    }
+    if let Some(device) = &query.device {
+        statement = statement.filter(devices::name.eq(device));
+    }
+    if let Some(process) = &query.process {
+        let pattern = util::like_pattern(process);
+        statement = statement.filter(
+            processes::executable
+                .ilike(pattern.clone())
+                .escape('\\')
+                .or(processes::name.ilike(pattern).escape('\\')),
+        );
+    }
+    let rows = statement
+        .load::<(String, Option<String>, OffsetDateTime, PgInterval)>(conn)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(executable, name, time, duration)| {
+            (executable, name, time, util::interval_seconds(&duration))
+        })
+        .collect())
+}
+
+type EventRow = (
+    i32,
+    OffsetDateTime,
+    OffsetDateTime,
+    PgInterval,
+    Option<PgInterval>,
+    Option<String>,
+    Option<String>,
+    Vec<String>,
+    Option<String>,
+    Option<String>,
+    bool,
+    bool,
+    Option<String>,
+    Option<i16>,
+    Option<String>,
+    String,
+    Option<String>,
+);
+
+fn event_from_row(row: EventRow) -> shared::Event {
+    let (
+        id,
+        time,
+        started_at,
+        duration,
+        focused_duration,
+        product_version,
+        file_version,
+        tags,
+        window_title,
+        api_key,
+        imported,
+        manual,
+        note,
+        rating,
+        device,
+        executable,
+        name,
+    ) = row;
+    shared::Event {
+        id,
+        executable,
+        name,
+        started_at,
+        ended_at: time,
+        duration: util::interval_seconds(&duration),
+        focused_duration: focused_duration.as_ref().map(util::interval_seconds),
+        product_version,
+        file_version,
+        tags,
+        window_title,
+        api_key,
+        imported,
+        manual,
+        note,
+        rating: rating.map(|rating| rating as u8),
+        device,
+    }
+}
+
+pub async fn events(
+    conn: &mut AsyncPgConnection,
+    query: &shared::EventsQuery,
+    cursor: Option<(OffsetDateTime, i32)>,
+    limit: i64,
+    user: Option<i32>,
+) -> QueryResult<Vec<shared::Event>> {
+    use schema::{devices, events, processes};
+
+    let mut statement = events::table
+        .inner_join(processes::table)
+        .left_join(devices::table)
+        .select((
+            events::id,
+            events::time,
+            events::started_at,
+            events::duration,
+            events::focused_duration,
+            events::product_version,
+            events::file_version,
+            events::tags,
+            events::window_title,
+            events::api_key,
+            events::imported,
+            events::manual,
+            events::note,
+            events::rating,
+            devices::name.nullable(),
+            processes::executable,
+            processes::name,
+        ))
+        .order((events::time.desc(), events::id.desc()))
+        .limit(limit)
+        .into_boxed();
+    if let Some(from) = query.from {
+        statement = statement.filter(events::time.ge(from));
+    }
+    if let Some(to) = query.to {
+        statement = statement.filter(events::time.lt(to));
+    }
+    if let Some(user) = user {
+        statement = statement.filter(events::user_id.eq(user));
+    }
+    if let Some(device) = &query.device {
+        statement = statement.filter(devices::name.eq(device));
+    }
+    if let Some(process) = &query.process {
+        let pattern = util::like_pattern(process);
+        statement = statement.filter(
+            processes::executable
+                .ilike(pattern.clone())
+                .escape('\\')
+                .or(processes::name.ilike(pattern).escape('\\')),
+        );
+    }
+    if let Some((time, id)) = cursor {
+        statement = statement.filter(
+            events::time
+                .lt(time)
+                .or(events::time.eq(time).and(events::id.lt(id))),
+        );
+    }
+    let rows = statement.load::<EventRow>(conn).await?;
+    Ok(rows.into_iter().map(event_from_row).collect())
+}
+
+pub async fn event(
+    conn: &mut AsyncPgConnection,
+    event_id: i32,
+    user: Option<i32>,
+) -> QueryResult<Option<shared::Event>> {
+    use schema::{devices, events, processes};
+
+    let mut statement = events::table
+        .inner_join(processes::table)
+        .left_join(devices::table)
+        .select((
+            events::id,
+            events::time,
+            events::started_at,
+            events::duration,
+            events::focused_duration,
+            events::product_version,
+            events::file_version,
+            events::tags,
+            events::window_title,
+            events::api_key,
+            events::imported,
+            events::manual,
+            events::note,
+            events::rating,
+            devices::name.nullable(),
+            processes::executable,
+            processes::name,
+        ))
+        .filter(events::id.eq(event_id))
+        .into_boxed();
+    if let Some(user) = user {
+        statement = statement.filter(events::user_id.eq(user));
+    }
+    let row = statement.first::<EventRow>(conn).await.optional()?;
+    Ok(row.map(event_from_row))
+}
+
+/// Sessions of the process, or None if there is no such process.
+pub async fn process_sessions(
+    conn: &mut AsyncPgConnection,
+    process_id: i32,
+    user: Option<i32>,
+) -> QueryResult<Option<Vec<shared::Event>>> {
+    use schema::{devices, events, processes};
+
+    let exists = processes::table
+        .find(process_id)
+        .select(processes::id)
+        .first::<i32>(conn)
+        .await
+        .optional()?;
+    if exists.is_none() {
+        return Ok(None);
+    }
+    let mut statement = events::table
+        .inner_join(processes::table)
+        .left_join(devices::table)
+        .select((
+            events::id,
+            events::time,
+            events::started_at,
+            events::duration,
+            events::focused_duration,
+            events::product_version,
+            events::file_version,
+            events::tags,
+            events::window_title,
+            events::api_key,
+            events::imported,
+            events::manual,
+            events::note,
+            events::rating,
+            devices::name.nullable(),
+            processes::executable,
+            processes::name,
+        ))
+        .filter(events::process.eq(process_id))
+        .into_boxed();
+    if let Some(user) = user {
+        statement = statement.filter(events::user_id.eq(user));
+    }
+    let rows = statement.load::<EventRow>(conn).await?;
+    Ok(Some(rows.into_iter().map(event_from_row).collect()))
+}
+
+async fn save_change(
+    conn: &mut AsyncPgConnection,
+    change: &NewEventChange,
+    user: Option<i32>,
+) -> QueryResult<()> {
+    use schema::event_changes;
+
+    diesel::insert_into(event_changes::table)
+        .values((
+            event_changes::event.eq(change.before.id),
+            event_changes::user_id.eq(user),
+            event_changes::action.eq(change.action_name()),
+            event_changes::changed_at.eq(change.changed_at),
+            event_changes::changed_by.eq(&change.changed_by),
+            event_changes::old_event.eq(event_json(&change.before)),
+            event_changes::new_event.eq(change.after.as_ref().map(event_json)),
+        ))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+pub async fn update_event(
+    conn: &mut AsyncPgConnection,
+    change: &NewEventChange,
+) -> QueryResult<usize> {
+    use schema::events::dsl::*;
+
+    let Some(after) = &change.after else {
+        return Ok(0);
+    };
+    conn.transaction(|conn| {
+        async move {
+            let Some(user) = events
+                .find(change.before.id)
+                .select(user_id)
+                .first::<Option<i32>>(conn)
+                .await
+                .optional()?
+            else {
+                return Ok(0);
+            };
+            let updated = diesel::update(events.find(change.before.id))
+                .set((
+                    started_at.eq(after.started_at),
+                    time.eq(after.ended_at),
+                    duration.eq(to_interval(after.duration)),
+                    focused_duration.eq(after.focused_duration.map(to_interval)),
+                    note.eq(&after.note),
+                    rating.eq(after.rating.map(i16::from)),
+                ))
+                .execute(conn)
+                .await?;
+            save_change(conn, change, user).await?;
+            Ok(updated)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+pub async fn delete_event(
+    conn: &mut AsyncPgConnection,
+    change: &NewEventChange,
+) -> QueryResult<usize> {
+    use schema::events::dsl::*;
+
+    conn.transaction(|conn| {
+        async move {
+            let Some(user) = events
+                .find(change.before.id)
+                .select(user_id)
+                .first::<Option<i32>>(conn)
+                .await
+                .optional()?
+            else {
+                return Ok(0);
+            };
+            let deleted = diesel::delete(events.find(change.before.id))
+                .execute(conn)
+                .await?;
+            save_change(conn, change, user).await?;
+            Ok(deleted)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+pub async fn event_changes(
+    conn: &mut AsyncPgConnection,
+    event_id: i32,
+    user: Option<i32>,
+) -> QueryResult<Vec<shared::EventChange>> {
+    use schema::event_changes;
+
+    let mut statement = event_changes::table
+        .filter(event_changes::event.eq(event_id))
+        .order(event_changes::id)
+        .select((
+            event_changes::id,
+            event_changes::event,
+            event_changes::action,
+            event_changes::changed_at,
+            event_changes::changed_by,
+            event_changes::old_event,
+            event_changes::new_event,
+        ))
+        .into_boxed();
+    if let Some(user) = user {
+        statement = statement.filter(event_changes::user_id.eq(user));
+    }
+    let rows = statement.load::<EventChangeRow>(conn).await?;
+    Ok(rows.into_iter().filter_map(event_change).collect())
+}
+
+pub async fn data_version(
+    conn: &mut AsyncPgConnection,
+    user: Option<i32>,
+) -> QueryResult<DataVersion> {
+    use schema::{audit_log, completion_times, events, game_metadata};
+
+    let mut latest_event = events::table
+        .select(events::id)
+        .order(events::id.desc())
+        .into_boxed();
+    if let Some(user) = user {
+        latest_event = latest_event.filter(events::user_id.eq(user));
+    }
+    Ok((
+        latest_event.first(conn).await.optional()?,
+        audit_log::table
+            .select(audit_log::id)
+            .order(audit_log::id.desc())
+            .first(conn)
+            .await
+            .optional()?,
+        game_metadata::table
+            .select(game_metadata::checked_at)
+            .order(game_metadata::checked_at.desc())
+            .first(conn)
+            .await
+            .optional()?,
+        completion_times::table
+            .select(completion_times::checked_at)
+            .order(completion_times::checked_at.desc())
+            .first(conn)
+            .await
+            .optional()?,
+    ))
+}
+
+pub async fn record_audit(conn: &mut AsyncPgConnection, entry: &NewAuditEntry) -> QueryResult<()> {
+    use schema::audit_log;
+
+    diesel::insert_into(audit_log::table)
+        .values((
+            audit_log::recorded_at.eq(entry.recorded_at),
+            audit_log::actor.eq(&entry.actor),
+            audit_log::action.eq(entry.action.name()),
+            audit_log::target.eq(&entry.target),
+            audit_log::details.eq(&entry.details),
+            audit_log::address.eq(&entry.address),
+        ))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+pub async fn audit_log(
+    conn: &mut AsyncPgConnection,
+    query: &shared::AuditQuery,
+    before: Option<i32>,
+    limit: i64,
+) -> QueryResult<Vec<shared::AuditEntry>> {
+    use schema::audit_log;
+
+    let mut statement = audit_log::table
+        .order(audit_log::id.desc())
+        .limit(limit)
+        .select((
+            audit_log::id,
+            audit_log::recorded_at,
+            audit_log::actor,
+            audit_log::action,
+            audit_log::target,
+            audit_log::details,
+            audit_log::address,
+        ))
+        .into_boxed();
+    if let Some(since) = query.since {
+        statement = statement.filter(audit_log::recorded_at.ge(since));
+    }
+    if let Some(until) = query.until {
+        statement = statement.filter(audit_log::recorded_at.lt(until));
+    }
+    if let Some(actor) = &query.actor {
+        statement = statement.filter(audit_log::actor.eq(actor.clone()));
+    }
+    if let Some(action) = &query.action {
+        statement = statement.filter(audit_log::action.eq(action.clone()));
+    }
+    if let Some(before) = before {
+        statement = statement.filter(audit_log::id.lt(before));
+    }
+    let rows = statement.load::<AuditRow>(conn).await?;
+    Ok(rows.into_iter().map(audit_entry).collect())
+}
+
+pub async fn create_share(
+    conn: &mut AsyncPgConnection,
+    share_token: &str,
+    user: Option<i32>,
+    share_process: Option<&str>,
+) -> QueryResult<shared::Share> {
+    use schema::shares::dsl::*;
+
+    diesel::insert_into(shares)
+        .values((
+            token.eq(share_token),
+            user_id.eq(user),
+            process.eq(share_process),
+            created_at.eq(OffsetDateTime::now_utc()),
+        ))
+        .returning((id, token, process, created_at))
+        .get_result::<ShareRow>(conn)
+        .await
+        .map(share)
+}
+
+pub async fn shares(
+    conn: &mut AsyncPgConnection,
+    user: Option<i32>,
+) -> QueryResult<Vec<shared::Share>> {
+    use schema::shares::dsl::*;
+
+    let mut statement = shares
+        .order(id.desc())
+        .select((id, token, process, created_at))
+        .into_boxed();
+    if let Some(user) = user {
+        statement = statement.filter(user_id.eq(user));
+    }
+    let rows = statement.load::<ShareRow>(conn).await?;
+    Ok(rows.into_iter().map(share).collect())
+}
+
+pub async fn find_share(
+    conn: &mut AsyncPgConnection,
+    share_token: &str,
+) -> QueryResult<Option<(Option<i32>, Option<String>)>> {
+    use schema::shares::dsl::*;
+
+    shares
+        .filter(token.eq(share_token))
+        .select((user_id, process))
+        .first(conn)
+        .await
+        .optional()
+}
+
+pub async fn delete_share(
+    conn: &mut AsyncPgConnection,
+    share_id: i32,
+    user: Option<i32>,
+) -> QueryResult<usize> {
+    use schema::shares::dsl::*;
+
+    let mut statement = diesel::delete(shares.find(share_id)).into_boxed();
+    if let Some(user) = user {
+        statement = statement.filter(user_id.eq(user));
+    }
+    statement.execute(conn).await
+}
+
+pub async fn sync_users(
+    conn: &mut AsyncPgConnection,
+    configured: Vec<(String, bool)>,
+) -> QueryResult<UserIds> {
+    use schema::users::dsl::*;
+
+    let mut ids = UserIds::new();
+    for (user_name, user_leaderboard) in configured {
+        let user_id = diesel::insert_into(users)
+            .values((name.eq(&user_name), leaderboard.eq(user_leaderboard)))
+            .on_conflict(name)
+            .do_update()
+            .set(leaderboard.eq(excluded(leaderboard)))
+            .returning(id)
+            .get_result::<i32>(conn)
+            .await?;
+        ids.insert(user_name, user_id);
+    }
+    Ok(ids)
+}
+
+pub async fn leaderboard(
+    conn: &mut AsyncPgConnection,
+    query: &shared::StatsQuery,
+) -> QueryResult<Vec<shared::LeaderboardEntry>> {
+    let (days, edges) = util::split_days(query.since, query.until);
+    let mut totals = Vec::new();
+    if let Some(days) = days {
+        totals.extend(daily_leaderboard(conn, query, days).await?);
+    }
+    for (since, until) in edges {
+        totals.extend(event_leaderboard(conn, query, since, until).await?);
+    }
+    Ok(util::merge_totals(totals)
+        .into_iter()
+        .map(|(user, duration, sessions)| shared::LeaderboardEntry {
+            user,
+            duration,
+            sessions,
+        })
+        .collect())
+}
+
+/// Playtime per user in the whole days from the daily totals.
+async fn daily_leaderboard(
+    conn: &mut AsyncPgConnection,
+    query: &shared::StatsQuery,
+    (first, end): util::DayRange,
+) -> QueryResult<Vec<Totals<String>>> {
+    use schema::{daily_stats, devices, processes, users};
+
+    let mut statement = daily_stats::table
+        .inner_join(users::table)
+        .inner_join(processes::table)
+        .left_join(devices::table)
+        .filter(users::leaderboard.eq(true))
+        .filter(processes::hidden.eq(false))
+        .filter(daily_stats::sessions.gt(0))
+        .group_by((users::id, users::name))
+        .select((
+            users::name,
+            sum_interval(daily_stats::duration),
+            sum_sessions(daily_stats::sessions),
+        ))
+        .into_boxed();
+    if let Some(first) = first {
+        statement = statement.filter(daily_stats::day.ge(first));
+    }
+    if let Some(end) = end {
+        statement = statement.filter(daily_stats::day.lt(end));
+    }
+    if let Some(device) = &query.device {
+        statement = statement.filter(devices::name.eq(device));
+    }
+    if let Some(process) = &query.process {
+        let pattern = util::like_pattern(process);
+        statement = statement.filter(
+            processes::executable
+                .ilike(pattern.clone())
+                .escape('\\')
+                .or(processes::name.ilike(pattern).escape('\\')),
+        );
+    }
+    let rows = statement
+        .load::<(String, Option<PgInterval>, Option<i64>)>(conn)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(user, duration, sessions)| {
+            (
+                user,
+                duration.as_ref().map(util::interval_seconds).unwrap_or(0),
+                sessions.unwrap_or(0) as u64,
+            )
+        })
+        .collect())
+}
+
+/// Playtime per user in a part of a day from the events.
+async fn event_leaderboard(
+    conn: &mut AsyncPgConnection,
+    query: &shared::StatsQuery,
+    since: OffsetDateTime,
+    until: OffsetDateTime,
+) -> QueryResult<Vec<Totals<String>>> {
+    use schema::{archived_events, devices, events, processes, users};
+
+    // Archived sessions count towards their days as in the daily totals.
+    // They are the same columns in a different table.
+    macro_rules! totals_in {
+        ($events:ident) => {{
+            let mut statement = $events::table
+                .inner_join(users::table)
+                .inner_join(processes::table)
+                .left_join(devices::table)
+                .filter(users::leaderboard.eq(true))
+                .filter(processes::hidden.eq(false))
+                .filter($events::time.ge(since))
+                .filter($events::time.lt(until))
+                .group_by((users::id, users::name))
+                .select((
+                    users::name,
+                    sum_interval($events::duration),
+                    count($events::id),
+                ))
+                .into_boxed();
+            if let Some(device) = &query.device {
+                statement = statement.filter(devices::name.eq(device));
+            }
+            if let Some(process) = &query.process {
+                let pattern = util::like_pattern(process);
+                statement = statement.filter(
+                    processes::executable
+                        .ilike(pattern.clone())
+                        .escape('\\')
+                        .or(processes::name.ilike(pattern).escape('\\')),
+                );
+            }
+            statement
+                .load::<(String, Option<PgInterval>, i64)>(conn)
+                .await?
+        }};
+    }
+    let mut rows = totals_in!(events);
+    rows.extend(totals_in!(archived_events));
+    Ok(rows
+        .into_iter()
+        .map(|(user, duration, sessions)| {
+            (
+                user,
+                duration.as_ref().map(util::interval_seconds).unwrap_or(0),
+                sessions as u64,
+            )
+        })
+        .collect())
+}
+
+pub async fn authenticate_device(
+    conn: &mut AsyncPgConnection,
+    token_value: &str,
+) -> QueryResult<Option<(i32, Option<i32>)>> {
+    use schema::devices::dsl::*;
+
+    devices
+        .filter(token.eq(token_value))
+        .filter(approved.eq(true))
+        .filter(revoked.eq(false))
+        .select((id, user_id))
+        .first::<(i32, Option<i32>)>(conn)
+        .await
+        .optional()
+}
+
+pub async fn register_device(
+    conn: &mut AsyncPgConnection,
+    device_name: &str,
+    device_nonce: &str,
+) -> QueryResult<Option<DeviceRegistration>> {
+    use schema::devices::dsl::*;
+
+    let existing = devices
+        .filter(nonce.eq(device_nonce))
+        .select((id, approved, revoked, token))
+        .first::<DeviceRegistration>(conn)
+        .await
+        .optional()?;
+    if existing.is_none() {
+        diesel::insert_into(devices)
+            .values((name.eq(device_name), nonce.eq(device_nonce)))
+            .execute(conn)
+            .await?;
+    }
+    Ok(existing)
+}
+
+pub async fn list_devices(conn: &mut AsyncPgConnection) -> QueryResult<Vec<Device>> {
+    use schema::devices;
+
+    let rows = devices::table
+        .order(devices::id)
+        .select((
+            devices::id,
+            devices::name,
+            devices::approved,
+            devices::revoked,
+            devices::created_at,
+            devices::user_id,
+        ))
+        .load::<(i32, String, bool, bool, OffsetDateTime, Option<i32>)>(conn)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(id, name, approved, revoked, created_at, user_id)| Device {
+                id,
+                name,
+                approved,
+                revoked,
+                created_at,
+                user_id,
+            },
+        )
+        .collect())
+}
+
+pub async fn approve_device(
+    conn: &mut AsyncPgConnection,
+    device_id: i32,
+    new_token: &str,
+    device_user: Option<i32>,
+) -> QueryResult<usize> {
+    use schema::devices::dsl::*;
+
+    diesel::update(devices.filter(id.eq(device_id)).filter(revoked.eq(false)))
+        .set((
+            approved.eq(true),
+            token.eq(new_token),
+            user_id.eq(device_user),
+        ))
+        .execute(conn)
+        .await
+}
+
+pub async fn revoke_device(conn: &mut AsyncPgConnection, device_id: i32) -> QueryResult<usize> {
+    use schema::devices::dsl::*;
+
+    diesel::update(devices.filter(id.eq(device_id)))
+        .set((revoked.eq(true), token.eq(None::<String>)))
+        .execute(conn)
+        .await
+}
+
+/// Columns of an event in a backup, in the order of the table.
+type BackupEventRow = (
+    i32,
+    OffsetDateTime,
+    i32,
+    PgInterval,
+    Option<String>,
+    Option<String>,
+    OffsetDateTime,
+    Option<PgInterval>,
+    Vec<String>,
+    Option<String>,
+    Option<String>,
+    Option<i32>,
+    Option<i32>,
+    Option<String>,
+    bool,
+    bool,
+    Option<String>,
+    Option<i16>,
+);
+
+fn backup_event(
+    (
+        id,
+        time,
+        process,
+        duration,
+        product_version,
+        file_version,
+        started_at,
+        focused_duration,
+        tags,
+        window_title,
+        api_key,
+        user_id,
+        device,
+        idempotency_key,
+        imported,
+        manual,
+        note,
+        rating,
+    ): BackupEventRow,
+) -> BackupEvent {
+    BackupEvent {
+        id,
+        time,
+        started_at,
+        process,
+        duration: util::interval_seconds(&duration),
+        focused_duration: focused_duration.as_ref().map(util::interval_seconds),
+        product_version,
+        file_version,
+        tags,
+        window_title,
+        api_key,
+        user_id,
+        device,
+        idempotency_key,
+        imported,
+        manual,
+        note,
+        rating: rating.map(|rating| rating as u8),
+    }
+}
+
+pub async fn backup(conn: &mut AsyncPgConnection) -> QueryResult<Backup> {
+    use schema::{
+        archived_events, devices, events, process_aliases, process_categories, processes, users,
+    };
+
+    let users = users::table
+        .order(users::id)
+        .select((
+            users::id,
+            users::name,
+            users::leaderboard,
+            users::created_at,
+        ))
+        .load(conn)
+        .await?;
+    let processes = processes::table
+        .order(processes::id)
+        .select((
+            processes::id,
+            processes::executable,
+            processes::name,
+            processes::export,
+            processes::hidden,
+        ))
+        .load(conn)
+        .await?;
+    let aliases = process_aliases::table
+        .order(process_aliases::id)
+        .select((
+            process_aliases::id,
+            process_aliases::executable,
+            process_aliases::name,
+            process_aliases::process,
+        ))
+        .load(conn)
+        .await?;
+    let categories = process_categories::table
+        .order((process_categories::process, process_categories::category))
+        .select((process_categories::process, process_categories::category))
+        .load(conn)
+        .await?;
+    let devices = devices::table
+        .order(devices::id)
+        .select((
+            devices::id,
+            devices::name,
+            devices::nonce,
+            devices::token,
+            devices::approved,
+            devices::revoked,
+            devices::created_at,
+            devices::user_id,
+        ))
+        .load(conn)
+        .await?;
+    let events = events::table
+        .order(events::id)
+        .load::<BackupEventRow>(conn)
+        .await?
+        .into_iter()
+        .map(backup_event)
+        .collect();
+    let archived_events = archived_events::table
+        .order(archived_events::id)
+        .select((
+            archived_events::id,
+            archived_events::time,
+            archived_events::process,
+            archived_events::duration,
+            archived_events::product_version,
+            archived_events::file_version,
+            archived_events::started_at,
+            archived_events::focused_duration,
+            archived_events::tags,
+            archived_events::window_title,
+            archived_events::api_key,
+            archived_events::user_id,
+            archived_events::device,
+            archived_events::idempotency_key,
+            archived_events::imported,
+            archived_events::manual,
+            archived_events::note,
+            archived_events::rating,
+        ))
+        .load::<BackupEventRow>(conn)
+        .await?
+        .into_iter()
+        .map(backup_event)
+        .collect();
+    Ok(Backup {
+        version: backup::BACKUP_VERSION,
+        users,
+        processes,
+        aliases,
+        categories,
+        devices,
+        events,
+        archived_events,
+    })
+}
+
+pub async fn restore(conn: &mut AsyncPgConnection, backup: &Backup) -> QueryResult<bool> {
+    use schema::{
+        archived_events, devices, events, process_aliases, process_categories, processes, users,
+    };
+
+    conn.transaction(|conn| {
+        async move {
+            let existing = processes::table.count().get_result::<i64>(conn).await?
+                + events::table.count().get_result::<i64>(conn).await?
+                + archived_events::table
+                    .count()
+                    .get_result::<i64>(conn)
+                    .await?
+                + devices::table.count().get_result::<i64>(conn).await?;
+            if existing > 0 {
+                return Ok(false);
+            }
+
+            let mut mapping = UserMapping::new();
+            for user in &backup.users {
+                let user_id = diesel::insert_into(users::table)
+                    .values((
+                        users::name.eq(&user.name),
+                        users::leaderboard.eq(user.leaderboard),
+                        users::created_at.eq(user.created_at),
+                    ))
+                    .on_conflict(users::name)
+                    .do_update()
+                    .set(users::leaderboard.eq(excluded(users::leaderboard)))
+                    .returning(users::id)
+                    .get_result::<i32>(conn)
+                    .await?;
+                mapping.insert(user.id, user_id);
+            }
+            for chunk in backup.processes.chunks(RESTORE_BATCH_SIZE) {
+                let rows = chunk
+                    .iter()
+                    .map(|process| {
+                        (
+                            processes::id.eq(process.id),
+                            processes::executable.eq(&process.executable),
+                            processes::name.eq(&process.name),
+                            processes::export.eq(process.export),
+                            processes::hidden.eq(process.hidden),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                diesel::insert_into(processes::table)
+                    .values(&rows)
+                    .execute(conn)
+                    .await?;
+            }
+            for chunk in backup.aliases.chunks(RESTORE_BATCH_SIZE) {
+                let rows = chunk
+                    .iter()
+                    .map(|alias| {
+                        (
+                            process_aliases::id.eq(alias.id),
+                            process_aliases::executable.eq(&alias.executable),
+                            process_aliases::name.eq(&alias.name),
+                            process_aliases::process.eq(alias.process),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                diesel::insert_into(process_aliases::table)
+                    .values(&rows)
+                    .execute(conn)
+                    .await?;
+            }
+            for chunk in backup.categories.chunks(RESTORE_BATCH_SIZE) {
+                let rows = chunk
+                    .iter()
+                    .map(|category| {
+                        (
+                            process_categories::process.eq(category.process),
+                            process_categories::category.eq(&category.category),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                diesel::insert_into(process_categories::table)
+                    .values(&rows)
+                    .execute(conn)
+                    .await?;
+            }
+            for chunk in backup.devices.chunks(RESTORE_BATCH_SIZE) {
+                let rows = chunk
+                    .iter()
+                    .map(|device| {
+                        (
+                            devices::id.eq(device.id),
+                            devices::name.eq(&device.name),
+                            devices::nonce.eq(&device.nonce),
+                            devices::token.eq(&device.token),
+                            devices::approved.eq(device.approved),
+                            devices::revoked.eq(device.revoked),
+                            devices::created_at.eq(device.created_at),
+                            devices::user_id.eq(backup::map_user(&mapping, device.user_id)),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                diesel::insert_into(devices::table)
+                    .values(&rows)
+                    .execute(conn)
+                    .await?;
+            }
+            for chunk in backup.events.chunks(RESTORE_BATCH_SIZE) {
+                let rows = chunk
+                    .iter()
+                    .map(|event| {
+                        (
+                            events::id.eq(event.id),
+                            events::time.eq(event.time),
+                            events::started_at.eq(event.started_at),
+                            events::process.eq(event.process),
+                            events::duration.eq(to_interval(event.duration)),
+                            events::focused_duration.eq(event.focused_duration.map(to_interval)),
+                            events::product_version.eq(&event.product_version),
+                            events::file_version.eq(&event.file_version),
+                            events::tags.eq(&event.tags),
+                            events::window_title.eq(&event.window_title),
+                            events::api_key.eq(&event.api_key),
+                            events::user_id.eq(backup::map_user(&mapping, event.user_id)),
+                            events::device.eq(event.device),
+                            events::idempotency_key.eq(&event.idempotency_key),
+                            events::imported.eq(event.imported),
+                            events::manual.eq(event.manual),
+                            events::note.eq(&event.note),
+                            events::rating.eq(event.rating.map(i16::from)),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                diesel::insert_into(events::table)
+                    .values(&rows)
+                    .execute(conn)
+                    .await?;
+            }
+            // The archive is restored as archived now.
+            let archived_at = OffsetDateTime::now_utc();
+            for chunk in backup.archived_events.chunks(RESTORE_BATCH_SIZE) {
+                let rows = chunk
+                    .iter()
+                    .map(|event| {
+                        (
+                            archived_events::id.eq(event.id),
+                            archived_events::time.eq(event.time),
+                            archived_events::started_at.eq(event.started_at),
+                            archived_events::process.eq(event.process),
+                            archived_events::duration.eq(to_interval(event.duration)),
+                            archived_events::focused_duration
+                                .eq(event.focused_duration.map(to_interval)),
+                            archived_events::product_version.eq(&event.product_version),
+                            archived_events::file_version.eq(&event.file_version),
+                            archived_events::tags.eq(&event.tags),
+                            archived_events::window_title.eq(&event.window_title),
+                            archived_events::api_key.eq(&event.api_key),
+                            archived_events::user_id.eq(backup::map_user(&mapping, event.user_id)),
+                            archived_events::device.eq(event.device),
+                            archived_events::idempotency_key.eq(&event.idempotency_key),
+                            archived_events::imported.eq(event.imported),
+                            archived_events::manual.eq(event.manual),
+                            archived_events::note.eq(&event.note),
+                            archived_events::rating.eq(event.rating.map(i16::from)),
+                            archived_events::archived_at.eq(archived_at),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                diesel::insert_into(archived_events::table)
+                    .values(&rows)
+                    .execute(conn)
+                    .await?;
+            }
+
+            // Rows were inserted with their IDs, so move the sequences past them.
+            for table in [
+                "processes",
+                "process_aliases",
+                "devices",
+                "events",
+                "archived_events",
+            ] {
+                diesel::sql_query(format!(
+                    "SELECT setval(pg_get_serial_sequence('{0}', 'id'), \
+                 COALESCE((SELECT MAX(id) FROM {0}), 0) + 1, false)",
+                    table
+                ))
+                .execute(conn)
+                .await?;
+            }
+            Ok(true)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+pub async fn delete_events_before(
+    conn: &mut AsyncPgConnection,
+    before: OffsetDateTime,
+) -> QueryResult<usize> {
+    use schema::{archived_events, events};
+
+    conn.transaction(|conn| {
+        async move {
+            let deleted = diesel::delete(events::table.filter(events::time.lt(before)))
+                .execute(conn)
+                .await?;
+            let archived =
+                diesel::delete(archived_events::table.filter(archived_events::time.lt(before)))
+                    .execute(conn)
+                    .await?;
+            Ok(deleted + archived)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+/// Move the events that ended before the time to the archive. Returns the
+/// number of events moved.
+pub async fn archive_events_before(
+    conn: &mut AsyncPgConnection,
+    before: OffsetDateTime,
+    archived_at: OffsetDateTime,
+) -> QueryResult<usize> {
+    use schema::{archived_events, events};
+
+    conn.transaction(|conn| {
+        async move {
+            diesel::insert_into(archived_events::table)
+                .values(
+                    events::table
+                        .filter(events::time.lt(before))
+                        .order(events::id)
+                        .select((
+                            events::time,
+                            events::process,
+                            events::duration,
+                            events::product_version,
+                            events::file_version,
+                            events::started_at,
+                            events::focused_duration,
+                            events::tags,
+                            events::window_title,
+                            events::api_key,
+                            events::user_id,
+                            events::device,
+                            events::idempotency_key,
+                            events::imported,
+                            events::manual,
+                            events::note,
+                            events::rating,
+                            archived_at.into_sql::<Timestamptz>(),
+                        )),
+                )
+                .into_columns((
+                    archived_events::time,
+                    archived_events::process,
+                    archived_events::duration,
+                    archived_events::product_version,
+                    archived_events::file_version,
+                    archived_events::started_at,
+                    archived_events::focused_duration,
+                    archived_events::tags,
+                    archived_events::window_title,
+                    archived_events::api_key,
+                    archived_events::user_id,
+                    archived_events::device,
+                    archived_events::idempotency_key,
+                    archived_events::imported,
+                    archived_events::manual,
+                    archived_events::note,
+                    archived_events::rating,
+                    archived_events::archived_at,
+                ))
+                .execute(conn)
+                .await?;
+            diesel::delete(events::table.filter(events::time.lt(before)))
+                .execute(conn)
+                .await
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+pub async fn aged_events(
+    conn: &mut AsyncPgConnection,
+    before: OffsetDateTime,
+) -> QueryResult<Vec<AgedEvent>> {
+    use schema::events;
+
+    let rows = events::table
+        .filter(events::time.lt(before))
+        .select((
+            events::id,
+            events::time,
+            events::started_at,
+            events::process,
+            events::user_id,
+            events::device,
+            events::duration,
+            events::focused_duration,
+        ))
+        .load::<(
+            i32,
+            OffsetDateTime,
+            OffsetDateTime,
+            i32,
+            Option<i32>,
+            Option<i32>,
+            PgInterval,
+            Option<PgInterval>,
+        )>(conn)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(id, time, started_at, process, user_id, device, duration, focused_duration)| {
+                AgedEvent {
+                    id,
+                    time,
+                    started_at,
+                    process,
+                    user_id,
+                    device,
+                    duration: util::interval_seconds(&duration),
+                    focused_duration: focused_duration.as_ref().map(util::interval_seconds),
+                }
+            },
+        )
+        .collect())
+}
+
+pub async fn replace_events(conn: &mut AsyncPgConnection, rollups: &[Rollup]) -> QueryResult<()> {
+    use schema::events::dsl::*;
+
+    conn.transaction(|conn| {
+        async move {
+            for rollup in rollups {
+                diesel::insert_into(events)
+                    .values((
+                        time.eq(rollup.time),
+                        started_at.eq(rollup.started_at),
+                        process.eq(rollup.process),
+                        duration.eq(to_interval(rollup.duration)),
+                        focused_duration.eq(rollup.focused_duration.map(to_interval)),
+                        user_id.eq(rollup.user_id),
+                        device.eq(rollup.device),
+                    ))
+                    .execute(conn)
+                    .await?;
+                diesel::delete(events.filter(id.eq_any(&rollup.ids)))
+                    .execute(conn)
+                    .await?;
+            }
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+pub async fn list_processes(
+    conn: &mut AsyncPgConnection,
+    search: Option<&str>,
+) -> QueryResult<Vec<ProcessSummary>> {
+    use schema::{events, processes};
+
+    let mut statement = processes::table
+        .select((
+            processes::id,
+            processes::executable,
+            processes::name,
+            processes::export,
+            processes::hidden,
+        ))
+        .order(processes::executable)
+        .into_boxed();
+    if let Some(search) = search {
+        let pattern = util::like_pattern(search);
+        statement = statement.filter(
+            processes::executable
+                .ilike(pattern.clone())
+                .escape('\\')
+                .or(processes::name.ilike(pattern).escape('\\')),
+        );
+    }
+    let rows = statement
+        .load::<(i32, String, Option<String>, bool, bool)>(conn)
+        .await?;
+    let totals: HashMap<i32, (i64, Option<PgInterval>)> = events::table
+        .group_by(events::process)
+        .select((
+            events::process,
+            count(events::id),
+            sum_interval(events::duration),
+        ))
+        .load::<(i32, i64, Option<PgInterval>)>(conn)
+        .await?
+        .into_iter()
+        .map(|(process, sessions, duration)| (process, (sessions, duration)))
+        .collect();
+    Ok(rows
+        .into_iter()
+        .map(|(id, executable, name, export, hidden)| {
+            let (sessions, duration) = match totals.get(&id) {
+                Some((sessions, duration)) => (*sessions, duration.as_ref()),
+                None => (0, None),
+            };
+            ProcessSummary {
+                id,
+                executable,
+                name,
+                export,
+                hidden,
+                sessions: sessions as u64,
+                duration: duration.map(util::interval_seconds).unwrap_or(0),
+            }
+        })
+        .collect())
+}
+
+pub async fn rename_process(
+    conn: &mut AsyncPgConnection,
+    process_id: i32,
+    new_name: Option<&str>,
+) -> QueryResult<usize> {
+    use schema::{completion_times, game_metadata, processes};
+
+    conn.transaction(|conn| {
+        async move {
+            // Look the game up again by its new name.
+            diesel::delete(completion_times::table.find(process_id))
+                .execute(conn)
+                .await?;
+            diesel::delete(game_metadata::table.find(process_id))
+                .execute(conn)
+                .await?;
+            diesel::update(processes::table.find(process_id))
+                .set(processes::name.eq(new_name))
+                .execute(conn)
+                .await
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+pub async fn merge_processes(
+    conn: &mut AsyncPgConnection,
+    source: i32,
+    target: i32,
+) -> QueryResult<Option<usize>> {
+    use schema::{archived_events, events, process_aliases, process_categories, processes};
+
+    conn.transaction(|conn| {
+        async move {
+            let Some((source_executable, source_name)) = processes::table
+                .find(source)
+                .select((processes::executable, processes::name))
+                .first::<(String, Option<String>)>(conn)
+                .await
+                .optional()?
+            else {
+                return Ok(None);
+            };
+            let found = processes::table
+                .find(target)
+                .count()
+                .get_result::<i64>(conn)
+                .await?;
+            if found == 0 {
+                return Ok(None);
+            }
+            let moved = diesel::update(events::table.filter(events::process.eq(source)))
+                .set(events::process.eq(target))
+                .execute(conn)
+                .await?;
+            diesel::update(archived_events::table.filter(archived_events::process.eq(source)))
+                .set(archived_events::process.eq(target))
+                .execute(conn)
+                .await?;
+            diesel::update(process_aliases::table.filter(process_aliases::process.eq(source)))
+                .set(process_aliases::process.eq(target))
+                .execute(conn)
+                .await?;
+            let categories = process_categories::table
+                .filter(process_categories::process.eq(source))
+                .select(process_categories::category)
+                .load::<String>(conn)
+                .await?;
+            for category in categories {
+                diesel::insert_into(process_categories::table)
+                    .values((
+                        process_categories::process.eq(target),
+                        process_categories::category.eq(category),
+                    ))
+                    .on_conflict_do_nothing()
+                    .execute(conn)
+                    .await?;
+            }
+            diesel::delete(processes::table.find(source))
+                .execute(conn)
+                .await?;
+            set_alias(conn, &source_executable, source_name.as_deref(), target).await?;
+            Ok(Some(moved))
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+/// Point the executable and name to the process, replacing the alias they
+/// already have. Returns the ID of the alias.
+async fn set_alias(
+    conn: &mut AsyncPgConnection,
+    alias_executable: &str,
+    alias_name: Option<&str>,
+    process_id: i32,
+) -> QueryResult<i32> {
+    use schema::process_aliases::dsl::*;
+
+    let existing =
+        diesel::delete(process_aliases.filter(executable.eq(alias_executable))).into_boxed();
+    match alias_name {
+        Some(alias_name) => existing.filter(name.eq(alias_name)),
+        None => existing.filter(name.is_null()),
+    }
+    .execute(conn)
+    .await?;
+    diesel::insert_into(process_aliases)
+        .values((
+            executable.eq(alias_executable),
+            name.eq(alias_name),
+            process.eq(process_id),
+        ))
+        .returning(id)
+        .get_result(conn)
+        .await
+}
+
+pub async fn list_aliases(conn: &mut AsyncPgConnection) -> QueryResult<Vec<Alias>> {
+    use schema::process_aliases::dsl::*;
+
+    process_aliases
+        .order(id)
+        .select((id, executable, name, process))
+        .load(conn)
+        .await
+}
+
+pub async fn add_alias(
+    conn: &mut AsyncPgConnection,
+    alias_executable: &str,
+    alias_name: Option<&str>,
+    process_id: i32,
+) -> QueryResult<Option<i32>> {
+    use schema::processes;
+
+    conn.transaction(|conn| {
+        async move {
+            let found = processes::table
+                .find(process_id)
+                .count()
+                .get_result::<i64>(conn)
+                .await?;
+            if found == 0 {
+                return Ok(None);
+            }
+            set_alias(conn, alias_executable, alias_name, process_id)
+                .await
+                .map(Some)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+pub async fn delete_alias(conn: &mut AsyncPgConnection, alias_id: i32) -> QueryResult<usize> {
+    use schema::process_aliases::dsl::*;
+
+    diesel::delete(process_aliases.find(alias_id))
+        .execute(conn)
+        .await
+}
+
+pub async fn delete_events(
+    conn: &mut AsyncPgConnection,
+    process_id: Option<i32>,
+    before: Option<OffsetDateTime>,
+) -> QueryResult<usize> {
+    use schema::{archived_events, events};
+
+    conn.transaction(|conn| {
+        async move {
+            let mut statement = diesel::delete(events::table).into_boxed();
+            let mut archived = diesel::delete(archived_events::table).into_boxed();
+            if let Some(process_id) = process_id {
+                statement = statement.filter(events::process.eq(process_id));
+                archived = archived.filter(archived_events::process.eq(process_id));
+            }
+            if let Some(before) = before {
+                statement = statement.filter(events::time.lt(before));
+                archived = archived.filter(archived_events::time.lt(before));
+            }
+            Ok(statement.execute(conn).await? + archived.execute(conn).await?)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+/// Give the events submitted with the API key and without a user to the
+/// user, archived ones too.
+pub async fn assign_key_events(
+    conn: &mut AsyncPgConnection,
+    api_key: String,
+    user_id: i32,
+) -> QueryResult<usize> {
+    use schema::{archived_events, events};
+
+    conn.transaction(|conn| {
+        async move {
+            let assigned = diesel::update(events::table)
+                .filter(events::api_key.eq(&api_key))
+                .filter(events::user_id.is_null())
+                .set(events::user_id.eq(user_id))
+                .execute(conn)
+                .await?;
+            let archived = diesel::update(archived_events::table)
+                .filter(archived_events::api_key.eq(&api_key))
+                .filter(archived_events::user_id.is_null())
+                .set(archived_events::user_id.eq(user_id))
+                .execute(conn)
+                .await?;
+            Ok(assigned + archived)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+pub async fn category_stats(
+    conn: &mut AsyncPgConnection,
+    query: &shared::StatsQuery,
+    user: Option<i32>,
+) -> QueryResult<Vec<shared::CategoryStats>> {
+    let (days, edges) = util::split_days(query.since, query.until);
+    let mut totals = Vec::new();
+    if let Some(days) = days {
+        totals.extend(daily_category_stats(conn, query, days, user).await?);
+    }
+    for (since, until) in edges {
+        totals.extend(event_category_stats(conn, query, since, until, user).await?);
+    }
+    Ok(util::merge_totals(totals)
+        .into_iter()
+        .map(|(category, duration, sessions)| shared::CategoryStats {
+            category,
+            duration,
+            sessions,
+        })
+        .collect())
+}
+
+/// Playtime per category in the whole days from the daily totals.
+async fn daily_category_stats(
+    conn: &mut AsyncPgConnection,
+    query: &shared::StatsQuery,
+    (first, end): util::DayRange,
+    user: Option<i32>,
+) -> QueryResult<Vec<Totals<String>>> {
+    use schema::{daily_stats, devices, process_categories, processes};
+
+    let mut statement = daily_stats::table
+        .inner_join(processes::table.inner_join(process_categories::table))
+        .left_join(devices::table)
+        .filter(processes::hidden.eq(false))
+        .filter(daily_stats::sessions.gt(0))
+        .group_by(process_categories::category)
+        .select((
+            process_categories::category,
+            sum_interval(daily_stats::duration),
+            sum_sessions(daily_stats::sessions),
+        ))
+        .into_boxed();
+    if let Some(first) = first {
+        statement = statement.filter(daily_stats::day.ge(first));
+    }
+    if let Some(end) = end {
+        statement = statement.filter(daily_stats::day.lt(end));
+    }
+    if let Some(user) = user {
+        statement = statement.filter(daily_stats::user_id.eq(user));
+    }
+    if let Some(device) = &query.device {
+        statement = statement.filter(devices::name.eq(device));
+    }
+    if let Some(process) = &query.process {
+        let pattern = util::like_pattern(process);
+        statement = statement.filter(
+            processes::executable
+                .ilike(pattern.clone())
+                .escape('\\')
+                .or(processes::name.ilike(pattern).escape('\\')),
+        );
+    }
+    let rows = statement
+        .load::<(String, Option<PgInterval>, Option<i64>)>(conn)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(category, duration, sessions)| {
+            (
+                category,
+                duration.as_ref().map(util::interval_seconds).unwrap_or(0),
+                sessions.unwrap_or(0) as u64,
+            )
+        })
+        .collect())
+}
+
+/// Playtime per category in a part of a day from the events.
+async fn event_category_stats(
+    conn: &mut AsyncPgConnection,
+    query: &shared::StatsQuery,
+    since: OffsetDateTime,
+    until: OffsetDateTime,
+    user: Option<i32>,
+) -> QueryResult<Vec<Totals<String>>> {
+    use schema::{archived_events, devices, events, process_categories, processes};
+
+    // Archived sessions count towards their days as in the daily totals.
+    // They are the same columns in a different table.
+    macro_rules! totals_in {
+        ($events:ident) => {{
+            let mut statement = $events::table
+                .inner_join(processes::table.inner_join(process_categories::table))
+                .left_join(devices::table)
+                .filter(processes::hidden.eq(false))
+                .filter($events::time.ge(since))
+                .filter($events::time.lt(until))
+                .group_by(process_categories::category)
+                .select((
+                    process_categories::category,
+                    sum_interval($events::duration),
+                    count($events::id),
+                ))
+                .into_boxed();
+            if let Some(user) = user {
+                statement = statement.filter($events::user_id.eq(user));
+            }
+            if let Some(device) = &query.device {
+                statement = statement.filter(devices::name.eq(device));
+            }
+            if let Some(process) = &query.process {
+                let pattern = util::like_pattern(process);
+                statement = statement.filter(
+                    processes::executable
+                        .ilike(pattern.clone())
+                        .escape('\\')
+                        .or(processes::name.ilike(pattern).escape('\\')),
+                );
+            }
+            statement
+                .load::<(String, Option<PgInterval>, i64)>(conn)
+                .await?
+        }};
+    }
+    let mut rows = totals_in!(events);
+    rows.extend(totals_in!(archived_events));
+    Ok(rows
+        .into_iter()
+        .map(|(category, duration, sessions)| {
+            (
+                category,
+                duration.as_ref().map(util::interval_seconds).unwrap_or(0),
+                sessions as u64,
+            )
+        })
+        .collect())
+}
+
+pub async fn process_categories(
+    conn: &mut AsyncPgConnection,
+    process_id: i32,
+) -> QueryResult<Vec<String>> {
+    use schema::process_categories::dsl::*;
+
+    process_categories
+        .filter(process.eq(process_id))
+        .select(category)
+        .order(category)
+        .load(conn)
+        .await
+}
+
+pub async fn completion_lookups(
+    conn: &mut AsyncPgConnection,
+    stale_before: OffsetDateTime,
+) -> QueryResult<Vec<(i32, String, Option<String>)>> {
+    use schema::{completion_times, processes};
+
+    let checked = completion_times::table
+        .filter(completion_times::checked_at.ge(stale_before))
+        .select(completion_times::process);
+    processes::table
+        .filter(processes::id.ne_all(checked))
+        .filter(processes::hidden.eq(false))
+        .select((processes::id, processes::executable, processes::name))
+        .order(processes::id)
+        .load(conn)
+        .await
+}
+
+pub async fn save_completion_time(
+    conn: &mut AsyncPgConnection,
+    process_id: i32,
+    time: Option<&CompletionTime>,
+    checked: OffsetDateTime,
+) -> QueryResult<()> {
+    use schema::completion_times::dsl::*;
+
+    diesel::insert_into(completion_times)
+        .values((
+            process.eq(process_id),
+            checked_at.eq(checked),
+            game_id.eq(time.map(|time| time.game_id)),
+            game.eq(time.map(|time| &time.game)),
+            main_story.eq(time.and_then(|time| time.main_story).map(to_interval)),
+            main_extra.eq(time.and_then(|time| time.main_extra).map(to_interval)),
+            completionist.eq(time.and_then(|time| time.completionist).map(to_interval)),
+        ))
+        .on_conflict(process)
+        .do_update()
+        .set((
+            checked_at.eq(excluded(checked_at)),
+            game_id.eq(excluded(game_id)),
+            game.eq(excluded(game)),
+            main_story.eq(excluded(main_story)),
+            main_extra.eq(excluded(main_extra)),
+            completionist.eq(excluded(completionist)),
+        ))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+pub async fn completion_times(
+    conn: &mut AsyncPgConnection,
+) -> QueryResult<Vec<(String, Option<String>, CompletionTime)>> {
+    use schema::{completion_times, processes};
+
+    let rows = completion_times::table
+        .inner_join(processes::table)
+        .filter(completion_times::game_id.is_not_null())
+        .select((
+            processes::executable,
+            processes::name,
+            completion_times::game_id,
+            completion_times::game,
+            completion_times::main_story,
+            completion_times::main_extra,
+            completion_times::completionist,
+        ))
+        .load::<(
+            String,
+            Option<String>,
+            Option<i32>,
+            Option<String>,
+            Option<PgInterval>,
+            Option<PgInterval>,
+            Option<PgInterval>,
+        )>(conn)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(executable, name, game_id, game, main_story, main_extra, completionist)| {
+                let time = CompletionTime {
+                    game_id: game_id.unwrap_or_default(),
+                    game: game.unwrap_or_default(),
+                    main_story: main_story.as_ref().map(util::interval_seconds),
+                    main_extra: main_extra.as_ref().map(util::interval_seconds),
+                    completionist: completionist.as_ref().map(util::interval_seconds),
+                };
+                (executable, name, time)
+            },
+        )
+        .collect())
+}
+
+pub async fn metadata_lookups(
+    conn: &mut AsyncPgConnection,
+    stale_before: OffsetDateTime,
+) -> QueryResult<Vec<(i32, String, Option<String>)>> {
+    use schema::{game_metadata, processes};
+
+    let checked = game_metadata::table
+        .filter(game_metadata::checked_at.ge(stale_before))
+        .select(game_metadata::process);
+    processes::table
+        .filter(processes::id.ne_all(checked))
+        .filter(processes::hidden.eq(false))
+        .select((processes::id, processes::executable, processes::name))
+        .order(processes::id)
+        .load(conn)
+        .await
+}
+
+pub async fn save_metadata(
+    conn: &mut AsyncPgConnection,
+    process_id: i32,
+    metadata: Option<&Metadata>,
+    checked: OffsetDateTime,
+) -> QueryResult<()> {
+    use schema::game_metadata::dsl::*;
+
+    diesel::insert_into(game_metadata)
+        .values((
+            process.eq(process_id),
+            checked_at.eq(checked),
+            igdb_id.eq(metadata.map(|metadata| metadata.igdb_id)),
+            title.eq(metadata.map(|metadata| &metadata.details.title)),
+            genres.eq(metadata.map_or(&[][..], |metadata| &metadata.details.genres)),
+            cover_url.eq(metadata.and_then(|metadata| metadata.details.cover_url.as_ref())),
+        ))
+        .on_conflict(process)
+        .do_update()
+        .set((
+            checked_at.eq(excluded(checked_at)),
+            igdb_id.eq(excluded(igdb_id)),
+            title.eq(excluded(title)),
+            genres.eq(excluded(genres)),
+            cover_url.eq(excluded(cover_url)),
+        ))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+pub async fn game_metadata(
+    conn: &mut AsyncPgConnection,
+) -> QueryResult<Vec<(String, Option<String>, shared::GameMetadata)>> {
+    use schema::{game_metadata, processes};
+
+    let rows = game_metadata::table
+        .inner_join(processes::table)
+        .filter(game_metadata::title.is_not_null())
+        .select((
+            processes::executable,
+            processes::name,
+            game_metadata::title,
+            game_metadata::genres,
+            game_metadata::cover_url,
+        ))
+        .load::<(
+            String,
+            Option<String>,
+            Option<String>,
+            Vec<String>,
+            Option<String>,
+        )>(conn)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(executable, name, title, genres, cover_url)| {
+            let metadata = shared::GameMetadata {
+                title: title.unwrap_or_default(),
+                genres,
+                cover_url,
+            };
+            (executable, name, metadata)
+        })
+        .collect())
+}
+
+pub async fn set_categories(
+    conn: &mut AsyncPgConnection,
+    process_id: i32,
+    categories: &[String],
+) -> QueryResult<bool> {
+    use schema::{process_categories, processes};
+
+    conn.transaction(|conn| {
+        async move {
+            let found = processes::table
+                .find(process_id)
+                .count()
+                .get_result::<i64>(conn)
+                .await?;
+            if found == 0 {
+                return Ok(false);
+            }
+            diesel::delete(
+                process_categories::table.filter(process_categories::process.eq(process_id)),
+            )
+            .execute(conn)
+            .await?;
+            let rows = categories
+                .iter()
+                .map(|category| {
+                    (
+                        process_categories::process.eq(process_id),
+                        process_categories::category.eq(category),
+                    )
+                })
+                .collect::<Vec<_>>();
+            diesel::insert_into(process_categories::table)
+                .values(&rows)
+                .execute(conn)
+                .await?;
+            Ok(true)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+pub async fn save_heartbeat(
+    conn: &mut AsyncPgConnection,
+    session: &str,
+    event: &NewEvent,
+) -> QueryResult<()> {
+    use schema::heartbeats::dsl::*;
+
+    diesel::insert_into(heartbeats)
+        .values((
+            session_id.eq(session),
+            executable.eq(&event.executable),
+            name.eq(&event.name),
+            started_at.eq(event.started_at),
+            updated_at.eq(event.time),
+            duration.eq(to_interval(event.duration.as_secs())),
+            focused_duration.eq(event
+                .focused_duration
+                .map(SessionDuration::as_secs)
+                .map(to_interval)),
+            product_version.eq(&event.product_version),
+            file_version.eq(&event.file_version),
+            tags.eq(&event.tags),
+            window_title.eq(&event.window_title),
+            api_key.eq(&event.api_key),
+            user_id.eq(event.user_id),
+            device.eq(event.device),
+        ))
+        .on_conflict(session_id)
+        .do_update()
+        .set((
+            executable.eq(excluded(executable)),
+            name.eq(excluded(name)),
+            updated_at.eq(excluded(updated_at)),
+            duration.eq(excluded(duration)),
+            focused_duration.eq(excluded(focused_duration)),
+            product_version.eq(excluded(product_version)),
+            file_version.eq(excluded(file_version)),
+            tags.eq(excluded(tags)),
+            window_title.eq(excluded(window_title)),
+        ))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+pub async fn delete_heartbeat(conn: &mut AsyncPgConnection, session: &str) -> QueryResult<usize> {
+    use schema::heartbeats::dsl::*;
+
+    diesel::delete(heartbeats.filter(session_id.eq(session)))
+        .execute(conn)
+        .await
+}
+
+pub async fn now_playing(
+    conn: &mut AsyncPgConnection,
+    user: Option<i32>,
+) -> QueryResult<Vec<shared::NowPlaying>> {
+    use schema::{devices, heartbeats, users};
+
+    let mut statement = heartbeats::table
+        .left_join(users::table)
+        .left_join(devices::table)
+        .select((
+            heartbeats::executable,
+            heartbeats::name,
+            heartbeats::started_at,
+            heartbeats::updated_at,
+            heartbeats::duration,
+            users::name.nullable(),
+            devices::name.nullable(),
+        ))
+        .order(heartbeats::started_at)
+        .into_boxed();
+    if let Some(user) = user {
+        statement = statement.filter(heartbeats::user_id.eq(user));
+    }
+    let rows = statement
+        .load::<(
+            String,
+            Option<String>,
+            OffsetDateTime,
+            OffsetDateTime,
+            PgInterval,
+            Option<String>,
+            Option<String>,
+        )>(conn)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(executable, name, started_at, updated_at, duration, user, device)| {
+                shared::NowPlaying {
+                    executable,
+                    name,
+                    started_at,
+                    updated_at,
+                    duration: util::interval_seconds(&duration),
+                    user,
+                    device,
+                }
+            },
+        )
+        .collect())
+}
+
+pub async fn reap_heartbeats(
+    conn: &mut AsyncPgConnection,
+    before: OffsetDateTime,
+) -> QueryResult<Vec<NewEvent>> {
+    use schema::heartbeats;
+
+    conn.transaction(|conn| {
+        async move {
+            let rows = heartbeats::table
+                .filter(heartbeats::updated_at.lt(before))
+                .load::<(
+                    String,
+                    String,
+                    Option<String>,
+                    OffsetDateTime,
+                    OffsetDateTime,
+                    PgInterval,
+                    Option<PgInterval>,
+                    Option<String>,
+                    Option<String>,
+                    Vec<String>,
+                    Option<String>,
+                    Option<String>,
+                    Option<i32>,
+                    Option<i32>,
+                )>(conn)
+                .await?;
+            let mut saved = Vec::new();
+            for (
+                session,
+                executable,
+                name,
+                started_at,
+                updated_at,
+                duration,
+                focused_duration,
+                product_version,
+                file_version,
+                tags,
+                window_title,
+                api_key,
+                user_id,
+                device,
+            ) in rows
+            {
+                let event = NewEvent {
+                    executable,
+                    name,
+                    time: updated_at,
+                    started_at,
+                    duration: util::interval_duration(&duration),
+                    focused_duration: focused_duration.as_ref().map(util::interval_duration),
+                    product_version,
+                    file_version,
+                    tags,
+                    window_title,
+                    api_key,
+                    user_id,
+                    device,
+                    idempotency_key: Some(session),
+                    imported: false,
+                    manual: false,
+                    note: None,
+                };
+                if save_event(conn, &event).await? {
+                    saved.push(event);
+                }
+            }
+            diesel::delete(heartbeats::table.filter(heartbeats::updated_at.lt(before)))
+                .execute(conn)
+                .await?;
+            Ok(saved)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+pub async fn set_export(
+    conn: &mut AsyncPgConnection,
+    process_id: i32,
+    value: bool,
+) -> QueryResult<usize> {
+    use schema::processes::dsl::*;
+
+    diesel::update(processes.find(process_id))
+        .set(export.eq(value))
+        .execute(conn)
+        .await
+}
+
+pub async fn process_exists(conn: &mut AsyncPgConnection, process_id: i32) -> QueryResult<bool> {
+    use schema::processes;
+
+    let found = processes::table
+        .find(process_id)
+        .count()
+        .get_result::<i64>(conn)
+        .await?;
+    Ok(found > 0)
+}
+
+pub async fn set_hidden(
+    conn: &mut AsyncPgConnection,
+    process_id: i32,
+    value: bool,
+) -> QueryResult<usize> {
+    use schema::processes::dsl::*;
+
+    diesel::update(processes.find(process_id))
+        .set(hidden.eq(value))
+        .execute(conn)
+        .await
+}
+
+pub async fn report_sent_until(
+    conn: &mut AsyncPgConnection,
+    name: &str,
+) -> QueryResult<Option<OffsetDateTime>> {
+    use schema::sent_reports::dsl::*;
+
+    sent_reports
+        .find(name)
+        .select(sent_until)
+        .first::<OffsetDateTime>(conn)
+        .await
+        .optional()
+}
+
+pub async fn save_report_sent(
+    conn: &mut AsyncPgConnection,
+    name: &str,
+    until: OffsetDateTime,
+) -> QueryResult<()> {
+    use schema::sent_reports::dsl::*;
+
+    diesel::insert_into(sent_reports)
+        .values((report.eq(name), sent_until.eq(until)))
+        .on_conflict(report)
+        .do_update()
+        .set(sent_until.eq(excluded(sent_until)))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+pub async fn goal_alerted_since(
+    conn: &mut AsyncPgConnection,
+    name: &str,
+) -> QueryResult<Option<OffsetDateTime>> {
+    use schema::goal_alerts::dsl::*;
+
+    goal_alerts
+        .find(name)
+        .select(period_start)
+        .first::<OffsetDateTime>(conn)
+        .await
+        .optional()
+}
+
+pub async fn save_goal_alert(
+    conn: &mut AsyncPgConnection,
+    name: &str,
+    start: OffsetDateTime,
+) -> QueryResult<()> {
+    use schema::goal_alerts::dsl::*;
+
+    diesel::insert_into(goal_alerts)
+        .values((goal.eq(name), period_start.eq(start)))
+        .on_conflict(goal)
+        .do_update()
+        .set(period_start.eq(excluded(period_start)))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,259 @@
+use async_graphql::SimpleObject;
+use axum::{
+    extract::{Path, Query, State},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    Json,
+};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::{
+    api_key, audit, config::Scope, db, is_admin_secret, is_authenticated, pagination, AppState,
+};
+
+/// Number of random bytes in a device token.
+const TOKEN_BYTES: usize = 32;
+
+#[derive(Serialize, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct Device {
+    pub id: i32,
+    pub name: String,
+    pub approved: bool,
+    pub revoked: bool,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    pub user_id: Option<i32>,
+}
+
+pub fn generate_token() -> Result<String, getrandom::Error> {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    getrandom::getrandom(&mut bytes)?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Device management and backups require the admin secret, or the shared
+/// secret if there is no admin secret, or an API key with the admin scope, so
+/// they cannot be done by anyone if none of them is configured.
+pub fn is_admin(headers: &HeaderMap, state: &AppState) -> bool {
+    admin(headers, state).is_some()
+}
+
+/// Name of the API key the admin request is made with, `admin secret` or
+/// `secret`, for the audit log.
+pub fn admin(headers: &HeaderMap, state: &AppState) -> Option<String> {
+    if let Some((name, _, scopes)) = api_key(headers, &state.config) {
+        if Scope::Admin.granted_by(&scopes) {
+            return Some(name);
+        }
+        warn!(
+            "Authentication error: API key {} does not have the {:?} scope",
+            name,
+            Scope::Admin
+        );
+        return None;
+    }
+    match is_admin_secret(headers, &state.config) {
+        Some(true) => return Some(audit::ADMIN_SECRET.to_string()),
+        Some(false) => {
+            debug!("Authentication error: admin secret not provided");
+            return None;
+        }
+        None => {}
+    }
+    let has_secret = state
+        .config
+        .read()
+        .map(|config| config.secret.is_some())
+        .unwrap_or(false);
+    (has_secret && is_authenticated(headers, &state.config)).then(|| "secret".to_string())
+}
+
+/// ID and user of the approved device whose token the request is
+/// authenticated with, `None` if the token is unknown or revoked.
+pub async fn authenticate_device(
+    headers: &HeaderMap,
+    db: &db::Database,
+) -> Option<(i32, Option<i32>)> {
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(shared::auth::bearer_token)
+        .map(str::to_string)?;
+    match db.authenticate_device(token).await {
+        Ok(Some((device_id, device_user))) => {
+            debug!("Authenticated as device {}", device_id);
+            Some((device_id, device_user))
+        }
+        Ok(None) => {
+            debug!("Authentication error: unknown or revoked device token");
+            None
+        }
+        Err(error) => {
+            error!("Could not look up device token: {}", error);
+            None
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/register",
+    request_body = shared::RegistrationRequest,
+    responses(
+        (status = 200, description = "Device approved, with its token", body = shared::RegistrationResponse),
+        (status = 202, description = "Registration pending approval", body = shared::RegistrationResponse),
+        (status = 403, description = "Device revoked", body = shared::RegistrationResponse),
+    ),
+    security(()),
+)]
+pub async fn register(
+    State(state): State<AppState>,
+    Json(payload): Json<shared::RegistrationRequest>,
+) -> Result<(StatusCode, Json<shared::RegistrationResponse>), StatusCode> {
+    let name = payload.name.clone();
+    let result = state.db.register_device(payload.name, payload.nonce).await;
+    let response = match result {
+        Ok(Some((_, _, true, _))) => shared::RegistrationResponse {
+            status: shared::RegistrationStatus::Revoked,
+            token: None,
+            device_id: None,
+        },
+        Ok(Some((device_id, true, false, token))) => shared::RegistrationResponse {
+            status: shared::RegistrationStatus::Approved,
+            token,
+            device_id: Some(device_id),
+        },
+        Ok(existing) => {
+            if existing.is_none() {
+                info!("Device {} requested registration", name);
+                let target = Some(format!("device {}", name));
+                audit::record(
+                    &state.db,
+                    audit::ANONYMOUS,
+                    audit::Action::RegisterDevice,
+                    target,
+                    None,
+                )
+                .await;
+            }
+            shared::RegistrationResponse {
+                status: shared::RegistrationStatus::Pending,
+                token: None,
+                device_id: None,
+            }
+        }
+        Err(error) => {
+            error!("Could not register device: {}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let status_code = match response.status {
+        shared::RegistrationStatus::Approved => StatusCode::OK,
+        shared::RegistrationStatus::Pending => StatusCode::ACCEPTED,
+        shared::RegistrationStatus::Revoked => StatusCode::FORBIDDEN,
+    };
+    Ok((status_code, Json(response)))
+}
+
+/// Ways to sort the list of devices, by ID by default.
+const DEVICE_SORTS: [pagination::Sort<Device>; 3] = [
+    ("id", |a, b| a.id.cmp(&b.id)),
+    ("name", |a, b| a.name.cmp(&b.name)),
+    ("createdAt", |a, b| a.created_at.cmp(&b.created_at)),
+];
+
+pub async fn list(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<shared::ListQuery>,
+) -> Result<Json<shared::Page<Device>>, StatusCode> {
+    if !is_admin(&headers, &state) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    match state.db.list_devices().await {
+        Ok(devices) => Ok(Json(pagination::paginate(devices, &query, &DEVICE_SORTS)?)),
+        Err(error) => {
+            error!("Could not list devices: {}", error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ApproveQuery {
+    /// Name of the user the device belongs to.
+    user: Option<String>,
+}
+
+pub async fn approve(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(device_id): Path<i32>,
+    Query(query): Query<ApproveQuery>,
+) -> StatusCode {
+    let Some(actor) = admin(&headers, &state) else {
+        return StatusCode::UNAUTHORIZED;
+    };
+    let device_user = match &query.user {
+        Some(user) => match state.users.get(user) {
+            Some(device_user) => Some(*device_user),
+            None => return StatusCode::BAD_REQUEST,
+        },
+        None => None,
+    };
+    let Ok(new_token) = generate_token() else {
+        error!("Could not generate device token");
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    };
+    let result = state
+        .db
+        .approve_device(device_id, new_token, device_user)
+        .await;
+    let status = update_status("approve", device_id, result);
+    if status == StatusCode::NO_CONTENT {
+        let details = query.user.map(|user| format!("for user {}", user));
+        let target = Some(format!("device {}", device_id));
+        audit::record(
+            &state.db,
+            &actor,
+            audit::Action::ApproveDevice,
+            target,
+            details,
+        )
+        .await;
+    }
+    status
+}
+
+pub async fn revoke(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(device_id): Path<i32>,
+) -> StatusCode {
+    let Some(actor) = admin(&headers, &state) else {
+        return StatusCode::UNAUTHORIZED;
+    };
+    let result = state.db.revoke_device(device_id).await;
+    let status = update_status("revoke", device_id, result);
+    if status == StatusCode::NO_CONTENT {
+        let target = Some(format!("device {}", device_id));
+        audit::record(&state.db, &actor, audit::Action::RevokeDevice, target, None).await;
+    }
+    status
+}
+
+fn update_status(action: &str, device_id: i32, result: Result<usize, db::Error>) -> StatusCode {
+    match result {
+        Ok(0) => StatusCode::NOT_FOUND,
+        Ok(_) => {
+            info!("Device {}: {} done", device_id, action);
+            StatusCode::NO_CONTENT
+        }
+        Err(error) => {
+            error!("Could not {} device {}: {}", action, device_id, error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::LazyLock;
+
+use axum::{
+    extract::{Request, State},
+    http::{
+        header::{ETAG, IF_NONE_MATCH},
+        HeaderValue, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use log::error;
+use time::OffsetDateTime;
+use time_tz::OffsetDateTimeExt;
+
+use crate::{authorize, config::Scope, stats, AppState};
+
+/// Hashes tags with keys picked at startup, so that a restart, which may come
+/// with a changed configuration, changes every tag.
+static HASHER: LazyLock<RandomState> = LazyLock::new(RandomState::new);
+
+/// Answer with 304 Not Modified, without running the handler, when nothing
+/// the stats of the caller are computed from has changed since the tag in
+/// `If-None-Match`. The caller is passed on to the handler in the extensions
+/// of the request. Requests that aren't authorised are left for the handler
+/// to reject.
+pub async fn cache(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    match authorize(request.headers(), &state, Scope::Read).await {
+        Some(caller) => {
+            let user_id = caller.user_id;
+            request.extensions_mut().insert(caller);
+            respond(&state, user_id, request, next).await
+        }
+        None => next.run(request).await,
+    }
+}
+
+/// Like `cache` for endpoints that cover everyone whatever user the caller
+/// is limited to, such as the leaderboard.
+pub async fn cache_everyone(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    match authorize(request.headers(), &state, Scope::Read).await {
+        Some(caller) => {
+            request.extensions_mut().insert(caller);
+            respond(&state, None, request, next).await
+        }
+        None => next.run(request).await,
+    }
+}
+
+/// Like `cache` for endpoints that don't need authentication and cover
+/// everyone.
+pub async fn cache_public(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    respond(&state, None, request, next).await
+}
+
+/// Whether an `If-None-Match` header matches the ETag. Tags are compared
+/// weakly, as compression gives the same resource different bytes.
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Tag of the request from the latest changes in the scope of the user and
+/// the current day, which periods such as `this week` and streaks depend on.
+async fn respond(state: &AppState, user_id: Option<i32>, request: Request, next: Next) -> Response {
+    let version = match state.db.data_version(user_id).await {
+        Ok(version) => version,
+        Err(error) => {
+            error!("Could not check whether stats have changed: {}", error);
+            return next.run(request).await;
+        }
+    };
+    let today = OffsetDateTime::now_utc()
+        .to_timezone(state.timezones.get(user_id))
+        .date();
+    // Stats are grouped differently once the configuration is reloaded with
+    // another default.
+    let grouping = stats::stats_grouping(state);
+    let uri = request.uri();
+    let hash = HASHER.hash_one((uri.path(), uri.query(), user_id, version, today, grouping));
+    let etag = format!("W/\"{:016x}\"", hash);
+    let not_modified = request
+        .headers()
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| etag_matches(value, &etag));
+    if not_modified {
+        return (StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response();
+    }
+    let mut response = next.run(request).await;
+    if response.status() == StatusCode::OK {
+        if let Ok(value) = HeaderValue::from_str(&etag) {
+            response.headers_mut().insert(ETAG, value);
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    #[test_case("W/\"abc\"", "W/\"abc\"", true; "same")]
+    #[test_case("\"abc\"", "W/\"abc\"", true; "strong")]
+    #[test_case("\"xyz\", W/\"abc\"", "W/\"abc\"", true; "list")]
+    #[test_case("*", "W/\"abc\"", true; "any")]
+    #[test_case("W/\"xyz\"", "W/\"abc\"", false; "different")]
+    #[test_case("W/\"abcd\"", "W/\"abc\"", false; "longer")]
+    fn etag_matches(if_none_match: &str, etag: &str, output: bool) {
+        assert_eq!(super::etag_matches(if_none_match, etag), output);
+    }
+}
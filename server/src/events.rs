@@ -0,0 +1,555 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use log::{error, info, warn};
+use shared::pagination::Cursor;
+use time::OffsetDateTime;
+
+use crate::{
+    audit, authorize,
+    config::{self, Scope},
+    db, match_name_case, name_normalization, new_event, pagination, submission_limits,
+    submission_response, util, AppState, Caller,
+};
+
+/// Page of events for the caller. Returns None if the page is not a valid
+/// `nextPage`.
+pub async fn page(
+    db: &db::Database,
+    query: shared::EventsQuery,
+    user: Option<i32>,
+) -> Result<Option<shared::EventsPage>, db::Error> {
+    let cursor = match query.page.as_deref().map(<(OffsetDateTime, i32)>::decode) {
+        Some(None) => return Ok(None),
+        Some(cursor) => cursor,
+        None => None,
+    };
+    let limit = pagination::limit(query.limit);
+    // One extra to tell whether there is another page.
+    let mut events = db.events(query, cursor, limit as i64 + 1, user).await?;
+    let next_page = if events.len() > limit as usize {
+        events.truncate(limit as usize);
+        events
+            .last()
+            .map(|event| (event.ended_at, event.id).encode())
+    } else {
+        None
+    };
+    Ok(Some(shared::EventsPage { events, next_page }))
+}
+
+/// Individual sessions from newest to oldest, paginated by the end time and
+/// ID of the last event on the previous page.
+#[utoipa::path(
+    get,
+    path = "/events",
+    params(shared::EventsQuery),
+    responses(
+        (status = 200, description = "Page of sessions", body = shared::EventsPage),
+        (status = 400, description = "Invalid page"),
+        (status = 401, description = "Not authenticated"),
+    ),
+)]
+pub async fn events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<shared::EventsQuery>,
+) -> Result<Json<shared::EventsPage>, StatusCode> {
+    let Some(caller) = authorize(&headers, &state, Scope::Read).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    match page(&state.db, query, caller.user_id).await {
+        Ok(Some(page)) => Ok(Json(page)),
+        Ok(None) => Err(StatusCode::BAD_REQUEST),
+        Err(error) => {
+            error!("Could not query events: {}", error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Submission of the manual session, which has no versions or tags.
+fn manual_submission(session: &shared::ManualSession) -> shared::Submission {
+    shared::Submission {
+        duration: shared::SessionDuration::from_secs(session.duration),
+        executable: session
+            .executable
+            .clone()
+            .unwrap_or_else(|| session.name.clone()),
+        name: Some(session.name.clone()),
+        product_version: None,
+        file_version: None,
+        started_at: Some(session.started_at),
+        ended_at: Some(session.started_at + time::Duration::seconds(session.duration as i64)),
+        focused_duration: None,
+        tags: Vec::new(),
+        window_title: None,
+        session_id: None,
+        device_id: None,
+    }
+}
+
+/// Field of the manual session that is wrong and what is wrong with it. Like
+/// backfilled sessions, manual sessions need to have ended already.
+fn manual_problem(
+    session: &shared::ManualSession,
+    limits: &config::SubmissionLimits,
+    now: OffsetDateTime,
+) -> Option<(&'static str, String)> {
+    if session.name.trim().is_empty() {
+        return Some(("name", "name is empty".to_string()));
+    }
+    if let Some(note) = &session.note {
+        if note.chars().count() > limits.maximum_length {
+            let message = format!("note is over {} characters", limits.maximum_length);
+            return Some(("note", message));
+        }
+    }
+    util::backfill_problem(&manual_submission(session), limits, now)
+}
+
+/// Record a session entered by hand, such as one played on a console. The
+/// session is marked as manual to tell it apart from tracked sessions.
+#[utoipa::path(
+    post,
+    path = "/events",
+    request_body = shared::ManualSession,
+    responses(
+        (status = 201, description = "Session saved, or already saved before", body = shared::SubmissionResponse),
+        (status = 401, description = "Not authenticated"),
+        (status = 422, description = "Invalid session", body = shared::SubmissionResponse),
+        (status = 500, description = "Database error", body = shared::SubmissionResponse),
+    ),
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(session): Json<shared::ManualSession>,
+) -> (StatusCode, Json<shared::SubmissionResponse>) {
+    let Some(caller) = authorize(&headers, &state, Scope::Submit).await else {
+        return submission_response(
+            StatusCode::UNAUTHORIZED,
+            shared::SubmissionResponseStatus::Unauthenticated,
+            None,
+        );
+    };
+    let limits = submission_limits(&state.config);
+    if let Some((field, problem)) = manual_problem(&session, &limits, OffsetDateTime::now_utc()) {
+        warn!("Rejected manual session of {}: {}", session.name, problem);
+        let response = shared::SubmissionResponse {
+            status: shared::SubmissionResponseStatus::ValidationError,
+            code: Some(shared::ApiErrorCode::Validation),
+            message: Some(problem),
+            field: Some(field.to_string()),
+        };
+        return (StatusCode::UNPROCESSABLE_ENTITY, Json(response));
+    }
+    let submission = manual_submission(&session);
+    let key = headers
+        .get(shared::IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let rules = name_normalization(&state.config);
+    let mut event = new_event(&submission, &caller, key, &rules, &limits);
+    event.manual = true;
+    event.note = session.note;
+    if rules.case_insensitive {
+        match_name_case(&*state.storage, &mut event).await;
+    }
+    let saved = match limits.duplicate_tolerance {
+        Some(tolerance) => state.storage.save_submission(event, tolerance).await,
+        None => state.storage.save_event(event).await,
+    };
+    let status = match saved {
+        Ok(true) => {
+            info!("Manual session {} saved", submission.display());
+            audit::record(
+                &*state.storage,
+                &caller.actor(),
+                audit::Action::AddEvent,
+                Some(submission.executable.clone()),
+                Some(submission.display()),
+            )
+            .await;
+            shared::SubmissionResponseStatus::Ok
+        }
+        Ok(false) => {
+            info!("Manual session {} was already saved", submission.display());
+            shared::SubmissionResponseStatus::Duplicate
+        }
+        Err(error) => {
+            error!(
+                "Could not save manual session {}: {}",
+                submission.display(),
+                error
+            );
+            return submission_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                shared::SubmissionResponseStatus::DatabaseError,
+                None,
+            );
+        }
+    };
+    submission_response(StatusCode::CREATED, status, None)
+}
+
+/// The event if the caller may change it.
+async fn find(state: &AppState, caller: &Caller, id: i32) -> Result<shared::Event, StatusCode> {
+    match state.db.event(id, caller.user_id).await {
+        Ok(Some(event)) => Ok(event),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(error) => {
+            error!("Could not query event {}: {}", id, error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// The event with the update applied, or what is wrong with the update.
+fn update_event(
+    event: &shared::Event,
+    update: &shared::EventUpdate,
+    maximum_duration: u64,
+) -> Result<shared::Event, String> {
+    let started_at = update.started_at.unwrap_or(event.started_at);
+    let ended_at = update.ended_at.unwrap_or(event.ended_at);
+    if started_at > ended_at {
+        return Err("session ends before it starts".to_string());
+    }
+    let span = (ended_at - started_at).whole_seconds() as u64;
+    let duration = match update.duration {
+        Some(duration) if duration > span => {
+            return Err(format!(
+                "duration is over the {} seconds from start to end",
+                span
+            ));
+        }
+        Some(duration) => duration,
+        None => event.duration.min(span),
+    };
+    if duration > maximum_duration {
+        return Err(format!("duration is over {} seconds", maximum_duration));
+    }
+    let mut updated = event.clone();
+    updated.started_at = started_at;
+    updated.ended_at = ended_at;
+    updated.duration = duration;
+    updated.focused_duration = event.focused_duration.map(|focused| focused.min(duration));
+    Ok(updated)
+}
+
+/// Correct the times of a session, such as one of a game left running by
+/// mistake. The change is recorded in the audit trail.
+#[utoipa::path(
+    patch,
+    path = "/events/{id}",
+    params(("id" = i32, Path, description = "ID of the session")),
+    request_body = shared::EventUpdate,
+    responses(
+        (status = 200, description = "Session as changed", body = shared::Event),
+        (status = 401, description = "Not authenticated"),
+        (status = 404, description = "No such session of the caller"),
+        (status = 422, description = "Session would end before it starts or be too long"),
+    ),
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Json(update): Json<shared::EventUpdate>,
+) -> Result<Json<shared::Event>, StatusCode> {
+    let Some(caller) = authorize(&headers, &state, Scope::Admin).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let before = find(&state, &caller, id).await?;
+    let maximum_duration = submission_limits(&state.config).maximum_duration;
+    let after = match update_event(&before, &update, maximum_duration) {
+        Ok(after) => after,
+        Err(problem) => {
+            warn!("Rejected change of event {}: {}", id, problem);
+            return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+    };
+    let actor = caller.actor();
+    let change = db::NewEventChange {
+        action: shared::EventChangeAction::Update,
+        changed_at: OffsetDateTime::now_utc(),
+        changed_by: actor.clone(),
+        before,
+        after: Some(after.clone()),
+    };
+    match state.db.update_event(change).await {
+        Ok(0) => Err(StatusCode::NOT_FOUND),
+        Ok(_) => {
+            info!("Changed event {}", id);
+            let target = Some(format!("event {}", id));
+            audit::record(&state.db, &actor, audit::Action::UpdateEvent, target, None).await;
+            Ok(Json(after))
+        }
+        Err(error) => {
+            error!("Could not change event {}: {}", id, error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Attach a note and rating to a session, such as "finished the DLC", or
+/// clear them. The change is recorded in the audit trail like an edit.
+#[utoipa::path(
+    patch,
+    path = "/events/{id}/note",
+    params(("id" = i32, Path, description = "ID of the session")),
+    request_body = shared::EventNote,
+    responses(
+        (status = 200, description = "Session with the note", body = shared::Event),
+        (status = 401, description = "Not authenticated"),
+        (status = 404, description = "No such session of the caller"),
+        (status = 422, description = "Note too long or rating not from 1 to 5"),
+    ),
+)]
+pub async fn note(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Json(note): Json<shared::EventNote>,
+) -> Result<Json<shared::Event>, StatusCode> {
+    let Some(caller) = authorize(&headers, &state, Scope::Submit).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let before = find(&state, &caller, id).await?;
+    let maximum_length = submission_limits(&state.config).maximum_length;
+    let after = match util::annotate_event(&before, &note, maximum_length) {
+        Ok(after) => after,
+        Err(problem) => {
+            warn!("Rejected note of event {}: {}", id, problem);
+            return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+    };
+    let actor = caller.actor();
+    let change = db::NewEventChange {
+        action: shared::EventChangeAction::Update,
+        changed_at: OffsetDateTime::now_utc(),
+        changed_by: actor.clone(),
+        before,
+        after: Some(after.clone()),
+    };
+    match state.db.update_event(change).await {
+        Ok(0) => Err(StatusCode::NOT_FOUND),
+        Ok(_) => {
+            info!("Set the note of event {}", id);
+            let target = Some(format!("event {}", id));
+            audit::record(&state.db, &actor, audit::Action::SetNote, target, None).await;
+            Ok(Json(after))
+        }
+        Err(error) => {
+            error!("Could not set the note of event {}: {}", id, error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Delete a bogus session. The session is kept in the audit trail.
+#[utoipa::path(
+    delete,
+    path = "/events/{id}",
+    params(("id" = i32, Path, description = "ID of the session")),
+    responses(
+        (status = 204, description = "Session deleted"),
+        (status = 401, description = "Not authenticated"),
+        (status = 404, description = "No such session of the caller"),
+    ),
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> StatusCode {
+    let Some(caller) = authorize(&headers, &state, Scope::Admin).await else {
+        return StatusCode::UNAUTHORIZED;
+    };
+    let before = match find(&state, &caller, id).await {
+        Ok(event) => event,
+        Err(status) => return status,
+    };
+    let actor = caller.actor();
+    let change = db::NewEventChange {
+        action: shared::EventChangeAction::Delete,
+        changed_at: OffsetDateTime::now_utc(),
+        changed_by: actor.clone(),
+        before,
+        after: None,
+    };
+    match state.db.delete_event(change).await {
+        Ok(0) => StatusCode::NOT_FOUND,
+        Ok(_) => {
+            info!("Deleted event {}", id);
+            let target = Some(format!("event {}", id));
+            audit::record(&state.db, &actor, audit::Action::DeleteEvent, target, None).await;
+            StatusCode::NO_CONTENT
+        }
+        Err(error) => {
+            error!("Could not delete event {}: {}", id, error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Edits and deletions of a session, oldest first. Callers limited to a user
+/// only see the changes of the sessions of the user.
+#[utoipa::path(
+    get,
+    path = "/events/{id}/changes",
+    params(("id" = i32, Path, description = "ID of the session")),
+    responses(
+        (status = 200, description = "Changes of the session", body = Vec<shared::EventChange>),
+        (status = 401, description = "Not authenticated"),
+    ),
+)]
+pub async fn changes(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<shared::EventChange>>, StatusCode> {
+    let Some(caller) = authorize(&headers, &state, Scope::Read).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    match state.db.event_changes(id, caller.user_id).await {
+        Ok(changes) => Ok(Json(changes)),
+        Err(error) => {
+            error!("Could not query changes of event {}: {}", id, error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use crate::tests::{headers, state, CONFIG};
+
+    fn session() -> shared::ManualSession {
+        shared::ManualSession {
+            name: "Metroid Prime".to_string(),
+            executable: None,
+            started_at: OffsetDateTime::from_unix_timestamp(1_709_290_800).unwrap(),
+            duration: 3600,
+            note: Some("On the GameCube".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn retried_manual_session_is_saved_once() {
+        let storage = Arc::new(MemoryStorage::default());
+        let state = state(CONFIG, storage.clone());
+        let mut headers = headers();
+        headers.insert(shared::IDEMPOTENCY_KEY_HEADER, "8d6a3f1e".parse().unwrap());
+        let mut statuses = Vec::new();
+        for _ in 0..2 {
+            let (status, Json(response)) =
+                create(State(state.clone()), headers.clone(), Json(session())).await;
+            assert_eq!(status, StatusCode::CREATED);
+            statuses.push(response.status);
+        }
+        assert_eq!(
+            statuses,
+            [
+                shared::SubmissionResponseStatus::Ok,
+                shared::SubmissionResponseStatus::Duplicate
+            ]
+        );
+        let events = storage.events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(events[0].manual);
+        assert_eq!(events[0].note.as_deref(), Some("On the GameCube"));
+        assert_eq!(storage.audit_log.lock().unwrap().len(), 1);
+    }
+
+    #[test_case("Mario Kart", 1_709_290_800, 3600, None, None; "valid")]
+    #[test_case(" ", 1_709_290_800, 3600, None, Some(("name", "name is empty")); "no name")]
+    #[test_case("Mario Kart", 1_709_296_200, 3600, None, Some(("ended_at", "session ends in the future")); "future")]
+    #[test_case("Mario Kart", 1_709_290_800, 3600, Some("x".repeat(11)), Some(("note", "note is over 10 characters")); "long note")]
+    fn manual_problem(
+        name: &str,
+        started_at: i64,
+        duration: u64,
+        note: Option<String>,
+        output: Option<(&str, &str)>,
+    ) {
+        let session = shared::ManualSession {
+            name: name.to_string(),
+            executable: None,
+            started_at: OffsetDateTime::from_unix_timestamp(started_at).unwrap(),
+            duration,
+            note,
+        };
+        let limits = crate::config::SubmissionLimits {
+            maximum_length: 10,
+            ..Default::default()
+        };
+        let now = OffsetDateTime::from_unix_timestamp(1_709_298_000).unwrap();
+        let problem = super::manual_problem(&session, &limits, now);
+        assert_eq!(
+            problem
+                .as_ref()
+                .map(|(field, message)| (*field, message.as_str())),
+            output
+        );
+    }
+
+    #[test_case(None, Some(4800), None, Some((0, 4800, 4800, 4800)); "shortened end")]
+    #[test_case(Some(1800), None, None, Some((1800, 7200, 5400, 5400)); "later start")]
+    #[test_case(None, None, Some(600), Some((0, 7200, 600, 600)); "shorter duration")]
+    #[test_case(None, None, Some(7201), None; "duration over span")]
+    #[test_case(Some(7300), None, None, None; "ends before start")]
+    fn update_event(
+        started_at: Option<i64>,
+        ended_at: Option<i64>,
+        duration: Option<u64>,
+        output: Option<(i64, i64, u64, u64)>,
+    ) {
+        let start = OffsetDateTime::from_unix_timestamp(1_709_294_400).unwrap();
+        let time = |seconds: i64| start + time::Duration::seconds(seconds);
+        let event = shared::Event {
+            id: 1,
+            executable: "factorio.exe".to_string(),
+            name: None,
+            started_at: start,
+            ended_at: time(7200),
+            duration: 5400,
+            focused_duration: Some(5400),
+            product_version: None,
+            file_version: None,
+            tags: Vec::new(),
+            window_title: None,
+            api_key: None,
+            device: None,
+            imported: false,
+            manual: false,
+            note: None,
+            rating: None,
+        };
+        let update = shared::EventUpdate {
+            started_at: started_at.map(time),
+            ended_at: ended_at.map(time),
+            duration,
+        };
+        let updated = super::update_event(&event, &update, 604800)
+            .ok()
+            .map(|event| {
+                (
+                    (event.started_at - start).whole_seconds(),
+                    (event.ended_at - start).whole_seconds(),
+                    event.duration,
+                    event.focused_duration.unwrap(),
+                )
+            });
+        assert_eq!(updated, output);
+    }
+}
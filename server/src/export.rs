@@ -0,0 +1,266 @@
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use futures_util::stream;
+use log::error;
+use serde::Deserialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    authorize,
+    config::Scope,
+    db,
+    stats::{merge_devices, stats_grouping},
+    util, AppState,
+};
+
+/// Events fetched from the database at a time while exporting.
+const PAGE_SIZE: i64 = 1000;
+
+const EVENTS_HEADER: [&str; 15] = [
+    "id",
+    "executable",
+    "name",
+    "started_at",
+    "ended_at",
+    "duration",
+    "focused_duration",
+    "product_version",
+    "file_version",
+    "tags",
+    "window_title",
+    "api_key",
+    "device",
+    "note",
+    "rating",
+];
+
+const TOTALS_HEADER: [&str; 4] = ["executable", "name", "duration", "sessions"];
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportQuery {
+    /// Only include sessions that ended at or after this time.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    from: Option<OffsetDateTime>,
+
+    /// Only include sessions that ended before this time.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    to: Option<OffsetDateTime>,
+
+    /// Only include processes whose executable or name contains this text.
+    process: Option<String>,
+
+    /// Only include sessions from the registered device with this name.
+    device: Option<String>,
+
+    /// Total the playtime of every executable, name or pair of them.
+    group_by: Option<shared::StatsGrouping>,
+}
+
+/// Position of the export in the events table.
+enum Position {
+    Header,
+    After(Option<(OffsetDateTime, i32)>),
+    Footer,
+    Done,
+}
+
+/// Line of comma separated values, quoting the fields that need it.
+fn csv_row(fields: &[&str]) -> String {
+    let mut row = fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",");
+    row.push_str("\r\n");
+    row
+}
+
+fn format_time(time: OffsetDateTime) -> String {
+    time.format(&Rfc3339).unwrap_or_default()
+}
+
+fn event_row(event: &shared::Event) -> String {
+    let id = event.id.to_string();
+    let started_at = format_time(event.started_at);
+    let ended_at = format_time(event.ended_at);
+    let duration = event.duration.to_string();
+    let focused_duration = event
+        .focused_duration
+        .map(|seconds| seconds.to_string())
+        .unwrap_or_default();
+    let tags = event.tags.join(";");
+    let rating = event
+        .rating
+        .map(|rating| rating.to_string())
+        .unwrap_or_default();
+    csv_row(&[
+        &id,
+        &event.executable,
+        event.name.as_deref().unwrap_or(""),
+        &started_at,
+        &ended_at,
+        &duration,
+        &focused_duration,
+        event.product_version.as_deref().unwrap_or(""),
+        event.file_version.as_deref().unwrap_or(""),
+        &tags,
+        event.window_title.as_deref().unwrap_or(""),
+        event.api_key.as_deref().unwrap_or(""),
+        event.device.as_deref().unwrap_or(""),
+        event.note.as_deref().unwrap_or(""),
+        &rating,
+    ])
+}
+
+fn csv_response(filename: &str, body: Body) -> Response {
+    (
+        [
+            (CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+/// Matching sessions from newest to oldest between the header and footer.
+/// Events are read from the database and written a page at a time.
+pub fn event_body(
+    database: db::Database,
+    query: shared::EventsQuery,
+    user: Option<i32>,
+    header: String,
+    footer: String,
+    row: fn(&shared::Event) -> String,
+) -> Body {
+    let chunks = stream::unfold(Position::Header, move |position| {
+        let database = database.clone();
+        let query = query.clone();
+        let header = header.clone();
+        let footer = footer.clone();
+        async move {
+            let cursor = match position {
+                Position::Header => return Some((Ok(header), Position::After(None))),
+                Position::After(cursor) => cursor,
+                Position::Footer => return Some((Ok(footer), Position::Done)),
+                Position::Done => return None,
+            };
+            let result = database.events(query, cursor, PAGE_SIZE, user).await;
+            match result {
+                Ok(events) => {
+                    let next = match events.last() {
+                        Some(last) if events.len() as i64 == PAGE_SIZE => {
+                            Position::After(Some((last.ended_at, last.id)))
+                        }
+                        _ => Position::Footer,
+                    };
+                    let chunk = events.iter().map(row).collect::<String>();
+                    Some((Ok(chunk), next))
+                }
+                Err(error) => {
+                    error!("Could not export events: {}", error);
+                    Some((Err::<String, db::Error>(error), Position::Done))
+                }
+            }
+        }
+    });
+    Body::from_stream(chunks)
+}
+
+/// All matching sessions from newest to oldest.
+pub async fn events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, StatusCode> {
+    let Some(caller) = authorize(&headers, &state, Scope::Read).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let events_query = shared::EventsQuery {
+        from: query.from,
+        to: query.to,
+        process: query.process,
+        device: query.device,
+        ..Default::default()
+    };
+    let body = event_body(
+        state.db,
+        events_query,
+        caller.user_id,
+        csv_row(&EVENTS_HEADER),
+        String::new(),
+        event_row,
+    );
+    Ok(csv_response("events.csv", body))
+}
+
+/// Total playtime per process, most played first.
+pub async fn totals(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, StatusCode> {
+    let Some(caller) = authorize(&headers, &state, Scope::Read).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let grouping = query.group_by.unwrap_or_else(|| stats_grouping(&state));
+    let stats_query = shared::StatsQuery {
+        since: query.from,
+        until: query.to,
+        process: query.process,
+        device: query.device,
+        by_device: false,
+        group_by: None,
+    };
+    let stats = match state.db.stats(stats_query, caller.user_id).await {
+        Ok(stats) => util::group_stats(merge_devices(stats), grouping),
+        Err(error) => {
+            error!("Could not export totals: {}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let rows = std::iter::once(csv_row(&TOTALS_HEADER))
+        .chain(stats.into_iter().map(|process| {
+            csv_row(&[
+                &process.executable,
+                process.name.as_deref().unwrap_or(""),
+                &process.duration.to_string(),
+                &process.sessions.to_string(),
+            ])
+        }))
+        .map(Ok::<String, db::Error>);
+    Ok(csv_response(
+        "totals.csv",
+        Body::from_stream(stream::iter(rows)),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    #[test_case(&["game.exe", "Game", "120"], "game.exe,Game,120\r\n"; "plain")]
+    #[test_case(&["Game, The", ""], "\"Game, The\",\r\n"; "comma")]
+    #[test_case(&["12\" Doll"], "\"12\"\" Doll\"\r\n"; "quote")]
+    #[test_case(&["Line\nbreak"], "\"Line\nbreak\"\r\n"; "newline")]
+    fn csv_row(fields: &[&str], output: &str) {
+        assert_eq!(super::csv_row(fields), output);
+    }
+}
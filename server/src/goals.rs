@@ -0,0 +1,240 @@
+use std::time::Duration;
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    Extension, Json,
+};
+use log::{error, info};
+use time::OffsetDateTime;
+use time_tz::Tz;
+
+use crate::{authorize_read, config::GoalConfig, db, util, webhooks, AppState, Caller};
+
+/// Time between checking whether goals have been crossed.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Goals in the configuration with the IDs of their users. Goals of unknown
+/// users are left out.
+fn goals(state: &AppState) -> Vec<(GoalConfig, Option<i32>)> {
+    let Ok(config) = state.config.read() else {
+        error!("Could not read configuration");
+        return Vec::new();
+    };
+    let mut goals = Vec::new();
+    for goal in &config.goals {
+        let user_id = match &goal.user {
+            Some(user) => match state.users.get(user) {
+                Some(user_id) => Some(*user_id),
+                None => {
+                    error!("Goal {} is for unknown user {}", goal.name, user);
+                    continue;
+                }
+            },
+            None => None,
+        };
+        goals.push((goal.clone(), user_id));
+    }
+    goals
+}
+
+/// Whether the playtime and sessions go over the targets of a maximum, or
+/// reach every target of a minimum. Goals without targets are never crossed.
+fn goal_crossed(
+    kind: shared::GoalKind,
+    target_duration: Option<u64>,
+    target_sessions: Option<u64>,
+    duration: u64,
+    sessions: u64,
+) -> bool {
+    let targets = [(target_duration, duration), (target_sessions, sessions)];
+    let mut set = targets
+        .iter()
+        .filter_map(|(target, value)| target.map(|target| (target, *value)))
+        .peekable();
+    if set.peek().is_none() {
+        return false;
+    }
+    match kind {
+        shared::GoalKind::Maximum => set.any(|(target, value)| value > target),
+        shared::GoalKind::Minimum => set.all(|(target, value)| value >= target),
+    }
+}
+
+/// Progress of the goal in the period that `now` is in.
+async fn status(
+    db: &db::Database,
+    goal: &GoalConfig,
+    user_id: Option<i32>,
+    timezone: &Tz,
+    now: OffsetDateTime,
+) -> Result<shared::GoalStatus, db::Error> {
+    let since = util::start_of_period(goal.period, now, timezone);
+    let query = shared::StatsQuery {
+        since: Some(since),
+        until: None,
+        process: goal.process.clone(),
+        device: None,
+        by_device: false,
+        group_by: None,
+    };
+    let stats = db.stats(query, user_id).await?;
+    let duration = stats.iter().map(|process| process.duration).sum();
+    let sessions = stats.iter().map(|process| process.sessions).sum();
+    Ok(shared::GoalStatus {
+        name: goal.name.clone(),
+        process: goal.process.clone(),
+        user: goal.user.clone(),
+        period: goal.period,
+        kind: goal.kind,
+        target_duration: goal.duration,
+        target_sessions: goal.sessions,
+        since,
+        duration,
+        sessions,
+        crossed: goal_crossed(goal.kind, goal.duration, goal.sessions, duration, sessions),
+    })
+}
+
+/// Progress of the goals in their current periods. Callers limited to a user
+/// only see the goals of that user.
+#[utoipa::path(
+    get,
+    path = "/goals",
+    responses(
+        (status = 200, description = "Progress of every goal", body = Vec<shared::GoalStatus>),
+        (status = 401, description = "Not authenticated"),
+    ),
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    authorized: Option<Extension<Caller>>,
+) -> Result<Json<Vec<shared::GoalStatus>>, StatusCode> {
+    let Some(caller) = authorize_read(authorized, &headers, &state).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let now = OffsetDateTime::now_utc();
+    let mut statuses = Vec::new();
+    for (goal, user_id) in goals(&state) {
+        if caller.user_id.is_some() && user_id != caller.user_id {
+            continue;
+        }
+        let timezone = state.timezones.get(user_id);
+        match status(&state.db, &goal, user_id, timezone, now).await {
+            Ok(status) => statuses.push(status),
+            Err(error) => {
+                error!("Could not query goal {}: {}", goal.name, error);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        }
+    }
+    Ok(Json(statuses))
+}
+
+/// Chat message announcing that the goal has been crossed.
+fn message(status: &shared::GoalStatus) -> String {
+    let period = match status.period {
+        shared::SummaryPeriod::Day => "today",
+        shared::SummaryPeriod::Week => "this week",
+        shared::SummaryPeriod::Month => "this month",
+    };
+    let crossed = match status.kind {
+        shared::GoalKind::Maximum => "over the limit",
+        shared::GoalKind::Minimum => "reached",
+    };
+    format!(
+        "{}: {} with {} in {} sessions {}",
+        status.name,
+        crossed,
+        util::format_duration(status.duration),
+        status.sessions,
+        period
+    )
+}
+
+/// Alert the goal, unless it isn't crossed or has been alerted for the
+/// current period.
+async fn check(state: &AppState, goal: &GoalConfig, user_id: Option<i32>, now: OffsetDateTime) {
+    let timezone = state.timezones.get(user_id);
+    let status = match status(&state.db, goal, user_id, timezone, now).await {
+        Ok(status) => status,
+        Err(error) => {
+            error!("Could not check goal {}: {}", goal.name, error);
+            return;
+        }
+    };
+    if !status.crossed {
+        return;
+    }
+    match state.db.goal_alerted_since(goal.name.clone()).await {
+        Ok(Some(alerted_since)) if alerted_since >= status.since => return,
+        Ok(_) => {}
+        Err(error) => {
+            error!("Could not check alerts of goal {}: {}", goal.name, error);
+            return;
+        }
+    }
+    let body = match serde_json::to_vec(&status) {
+        Ok(body) => body,
+        Err(error) => {
+            error!("Could not serialise goal {}: {}", goal.name, error);
+            return;
+        }
+    };
+    let message = message(&status);
+    for alert in &goal.alerts {
+        match alert.service {
+            Some(service) => state
+                .webhooks
+                .post(&alert.url, webhooks::chat_body(service, &message)),
+            None => state.webhooks.post(&alert.url, body.clone()),
+        }
+    }
+    info!("{}", message);
+    if let Err(error) = state
+        .db
+        .save_goal_alert(goal.name.clone(), status.since)
+        .await
+    {
+        error!("Could not save alert of goal {}: {}", goal.name, error);
+    }
+}
+
+/// Alert every goal once per period when it is crossed.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let now = OffsetDateTime::now_utc();
+        for (goal, user_id) in goals(&state) {
+            check(&state, &goal, user_id, now).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    #[test_case(shared::GoalKind::Maximum, Some(36000), None, 36000, 5, false; "at the limit")]
+    #[test_case(shared::GoalKind::Maximum, Some(36000), None, 36001, 5, true; "over the limit")]
+    #[test_case(shared::GoalKind::Maximum, Some(36000), Some(3), 600, 4, true; "too many sessions")]
+    #[test_case(shared::GoalKind::Minimum, None, Some(3), 600, 3, true; "reached")]
+    #[test_case(shared::GoalKind::Minimum, None, Some(3), 36000, 2, false; "not reached")]
+    #[test_case(shared::GoalKind::Minimum, Some(3600), Some(3), 600, 3, false; "one of two reached")]
+    #[test_case(shared::GoalKind::Minimum, None, None, 600, 3, false; "no targets")]
+    fn goal_crossed(
+        kind: shared::GoalKind,
+        target_duration: Option<u64>,
+        target_sessions: Option<u64>,
+        duration: u64,
+        sessions: u64,
+        output: bool,
+    ) {
+        assert_eq!(
+            super::goal_crossed(kind, target_duration, target_sessions, duration, sessions),
+            output
+        );
+    }
+}
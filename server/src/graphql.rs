@@ -0,0 +1,263 @@
+use async_graphql::{ComplexObject, Context, EmptyMutation, EmptySubscription, Object, Schema};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+};
+use log::error;
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+use crate::{
+    admin::ProcessSummary,
+    authorize,
+    config::Scope,
+    db::Database,
+    devices, events,
+    processes::Alias,
+    stats::{self, merge_devices, with_metadata},
+    summary,
+    timezones::Timezones,
+    util, AppState,
+};
+
+/// Deepest nesting of fields allowed in a query.
+const MAXIMUM_DEPTH: usize = 10;
+
+/// Most fields a single query can select.
+const MAXIMUM_COMPLEXITY: usize = 1000;
+
+pub type ApiSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+pub fn schema(db: Database, timezones: Arc<Timezones>) -> ApiSchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .data(db)
+        .data(timezones)
+        .limit_depth(MAXIMUM_DEPTH)
+        .limit_complexity(MAXIMUM_COMPLEXITY)
+        .finish()
+}
+
+/// Caller of the query.
+struct Viewer {
+    /// User the caller is limited to, like in the REST endpoints.
+    user_id: Option<i32>,
+
+    /// Authenticated with the shared secret.
+    admin: bool,
+
+    /// What stats are totalled by when the query doesn't say.
+    grouping: shared::StatsGrouping,
+}
+
+fn database_error(what: &str, error: crate::db::Error) -> async_graphql::Error {
+    error!("Could not query {}: {}", what, error);
+    async_graphql::Error::new("database error")
+}
+
+fn stats_query(
+    since: Option<OffsetDateTime>,
+    until: Option<OffsetDateTime>,
+    process: Option<String>,
+    device: Option<String>,
+) -> shared::StatsQuery {
+    shared::StatsQuery {
+        since,
+        until,
+        process,
+        device,
+        by_device: false,
+        group_by: None,
+    }
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// Total playtime per process, most played first.
+    #[allow(clippy::too_many_arguments)]
+    async fn stats(
+        &self,
+        ctx: &Context<'_>,
+        since: Option<OffsetDateTime>,
+        until: Option<OffsetDateTime>,
+        process: Option<String>,
+        device: Option<String>,
+        #[graphql(default)] by_device: bool,
+        group_by: Option<shared::StatsGrouping>,
+    ) -> async_graphql::Result<Vec<shared::ProcessStats>> {
+        let viewer = ctx.data::<Viewer>()?;
+        let query = stats_query(since, until, process, device);
+        let db = ctx.data::<Database>()?;
+        let mut stats = db
+            .stats(query, viewer.user_id)
+            .await
+            .map_err(|error| database_error("stats", error))?;
+        if !by_device {
+            stats = merge_devices(stats);
+        }
+        let stats = util::group_stats(stats, group_by.unwrap_or(viewer.grouping));
+        Ok(with_metadata(db, stats).await)
+    }
+
+    /// Total playtime per category, most played first.
+    async fn category_stats(
+        &self,
+        ctx: &Context<'_>,
+        since: Option<OffsetDateTime>,
+        until: Option<OffsetDateTime>,
+        process: Option<String>,
+        device: Option<String>,
+    ) -> async_graphql::Result<Vec<shared::CategoryStats>> {
+        let viewer = ctx.data::<Viewer>()?;
+        let query = stats_query(since, until, process, device);
+        ctx.data::<Database>()?
+            .category_stats(query, viewer.user_id)
+            .await
+            .map_err(|error| database_error("category stats", error))
+    }
+
+    /// Totals and most played games of the current day, week or month
+    /// compared with the same part of the period before.
+    async fn summary(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] period: shared::SummaryPeriod,
+        until: Option<OffsetDateTime>,
+        device: Option<String>,
+    ) -> async_graphql::Result<shared::Summary> {
+        let viewer = ctx.data::<Viewer>()?;
+        let query = shared::SummaryQuery {
+            period,
+            until,
+            device,
+        };
+        let timezone = ctx.data::<Arc<Timezones>>()?.get(viewer.user_id);
+        summary::summarise(ctx.data::<Database>()?, query, viewer.user_id, timezone)
+            .await
+            .map_err(|error| database_error("summary", error))
+    }
+
+    /// Total playtime of the users that opted in to the leaderboard.
+    async fn leaderboard(
+        &self,
+        ctx: &Context<'_>,
+        since: Option<OffsetDateTime>,
+        until: Option<OffsetDateTime>,
+        process: Option<String>,
+        device: Option<String>,
+    ) -> async_graphql::Result<Vec<shared::LeaderboardEntry>> {
+        let query = stats_query(since, until, process, device);
+        ctx.data::<Database>()?
+            .leaderboard(query)
+            .await
+            .map_err(|error| database_error("leaderboard", error))
+    }
+
+    /// Individual sessions from newest to oldest.
+    #[allow(clippy::too_many_arguments)]
+    async fn events(
+        &self,
+        ctx: &Context<'_>,
+        from: Option<OffsetDateTime>,
+        to: Option<OffsetDateTime>,
+        process: Option<String>,
+        device: Option<String>,
+        page: Option<String>,
+        limit: Option<u32>,
+    ) -> async_graphql::Result<shared::EventsPage> {
+        let viewer = ctx.data::<Viewer>()?;
+        let query = shared::EventsQuery {
+            from,
+            to,
+            process,
+            device,
+            page,
+            limit,
+        };
+        let db = ctx.data::<Database>()?;
+        match events::page(db, query, viewer.user_id).await {
+            Ok(Some(page)) => Ok(page),
+            Ok(None) => Err(async_graphql::Error::new("invalid page")),
+            Err(error) => Err(database_error("events", error)),
+        }
+    }
+
+    /// Sessions in progress, as reported by heartbeats.
+    async fn now_playing(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Vec<shared::NowPlaying>> {
+        let viewer = ctx.data::<Viewer>()?;
+        ctx.data::<Database>()?
+            .now_playing(viewer.user_id)
+            .await
+            .map_err(|error| database_error("sessions in progress", error))
+    }
+
+    /// Processes with the totals of everyone's sessions. Not available to
+    /// callers limited to a user.
+    async fn processes(
+        &self,
+        ctx: &Context<'_>,
+        search: Option<String>,
+    ) -> async_graphql::Result<Vec<ProcessSummary>> {
+        if ctx.data::<Viewer>()?.user_id.is_some() {
+            return Err(async_graphql::Error::new("not allowed"));
+        }
+        ctx.data::<Database>()?
+            .list_processes(search)
+            .await
+            .map_err(|error| database_error("processes", error))
+    }
+
+    /// Requires the secret.
+    async fn aliases(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Alias>> {
+        if !ctx.data::<Viewer>()?.admin {
+            return Err(async_graphql::Error::new("not allowed"));
+        }
+        ctx.data::<Database>()?
+            .list_aliases()
+            .await
+            .map_err(|error| database_error("aliases", error))
+    }
+
+    /// Requires the secret.
+    async fn devices(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<devices::Device>> {
+        if !ctx.data::<Viewer>()?.admin {
+            return Err(async_graphql::Error::new("not allowed"));
+        }
+        ctx.data::<Database>()?
+            .list_devices()
+            .await
+            .map_err(|error| database_error("devices", error))
+    }
+}
+
+#[ComplexObject]
+impl ProcessSummary {
+    async fn categories(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<String>> {
+        ctx.data::<Database>()?
+            .process_categories(self.id)
+            .await
+            .map_err(|error| database_error("categories", error))
+    }
+}
+
+pub async fn graphql(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    request: GraphQLRequest,
+) -> Result<GraphQLResponse, StatusCode> {
+    let Some(caller) = authorize(&headers, &state, Scope::Read).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let viewer = Viewer {
+        user_id: caller.user_id,
+        admin: devices::is_admin(&headers, &state),
+        grouping: stats::stats_grouping(&state),
+    };
+    let request = request.into_inner().data(viewer);
+    Ok(state.graphql.execute(request).await.into())
+}
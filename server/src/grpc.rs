@@ -0,0 +1,192 @@
+use std::pin::Pin;
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use futures_util::{Stream, StreamExt};
+use shared::grpc::{
+    self,
+    beelzebub_server::{Beelzebub, BeelzebubServer},
+};
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::{client_settings, heartbeats, payload::Payload, submit, AppState};
+
+/// Path that the methods of the service are under, `/<package>.<service>/`.
+pub const PATH: &str = "/beelzebub.v1.Beelzebub/*method";
+
+/// gRPC service for clients, answering like the REST endpoints of the same
+/// name by calling their handlers with the metadata of the call as the
+/// headers.
+pub struct Service {
+    state: AppState,
+}
+
+pub fn service(state: AppState) -> BeelzebubServer<Service> {
+    BeelzebubServer::new(Service { state })
+}
+
+/// Result of a heartbeat from the status that `/heartbeat` answers with.
+fn heartbeat_status(status: StatusCode) -> grpc::HeartbeatStatus {
+    match status {
+        StatusCode::NO_CONTENT => grpc::HeartbeatStatus::Ok,
+        StatusCode::UNAUTHORIZED => grpc::HeartbeatStatus::Unauthenticated,
+        StatusCode::UPGRADE_REQUIRED => grpc::HeartbeatStatus::UpgradeRequired,
+        StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => {
+            grpc::HeartbeatStatus::Invalid
+        }
+        _ => grpc::HeartbeatStatus::DatabaseError,
+    }
+}
+
+/// Error of the call from the status that a REST endpoint answers with.
+fn error_status(status: StatusCode) -> Status {
+    match status {
+        StatusCode::UNAUTHORIZED => Status::unauthenticated("unauthorized"),
+        StatusCode::UPGRADE_REQUIRED => {
+            Status::failed_precondition("the server requires a newer client version")
+        }
+        StatusCode::NOT_FOUND => Status::not_found("not found"),
+        _ => Status::internal("internal server error"),
+    }
+}
+
+type HeartbeatResults = Pin<Box<dyn Stream<Item = Result<grpc::HeartbeatResult, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Beelzebub for Service {
+    async fn submit(
+        &self,
+        request: Request<grpc::Submission>,
+    ) -> Result<Response<grpc::SubmissionResponse>, Status> {
+        let headers = request.metadata().clone().into_headers();
+        let submission = match shared::Submission::try_from(request.into_inner()) {
+            Ok(submission) => submission,
+            Err(field) => {
+                return Ok(Response::new(grpc::SubmissionResponse {
+                    status: grpc::SubmissionStatus::ValidationError.into(),
+                    message: Some(format!("{} is out of range", field)),
+                    field: Some(field.to_string()),
+                }))
+            }
+        };
+        let (_, Json(response)) =
+            submit(State(self.state.clone()), headers, Ok(Json(submission))).await;
+        Ok(Response::new((&response).into()))
+    }
+
+    type HeartbeatsStream = HeartbeatResults;
+
+    async fn heartbeats(
+        &self,
+        request: Request<Streaming<grpc::Submission>>,
+    ) -> Result<Response<Self::HeartbeatsStream>, Status> {
+        let headers = request.metadata().clone().into_headers();
+        let state = self.state.clone();
+        let results = request.into_inner().then(move |heartbeat| {
+            let state = state.clone();
+            let headers: HeaderMap = headers.clone();
+            async move {
+                let heartbeat = heartbeat?;
+                let session_id = heartbeat.session_id.clone();
+                let status = match shared::Submission::try_from(heartbeat) {
+                    Ok(heartbeat) => heartbeat_status(
+                        heartbeats::heartbeat(State(state), headers, Payload(heartbeat)).await,
+                    ),
+                    Err(_) => grpc::HeartbeatStatus::Invalid,
+                };
+                Ok(grpc::HeartbeatResult {
+                    session_id,
+                    status: status.into(),
+                })
+            }
+        });
+        Ok(Response::new(Box::pin(results)))
+    }
+
+    async fn get_client_settings(
+        &self,
+        request: Request<grpc::ClientSettingsRequest>,
+    ) -> Result<Response<grpc::ClientSettings>, Status> {
+        let headers = request.metadata().clone().into_headers();
+        match client_settings(State(self.state.clone()), headers).await {
+            Ok(Json(settings)) => Ok(Response::new((&settings).into())),
+            Err(status) => Err(error_status(status)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use crate::tests::{state, submission, CONFIG};
+
+    fn request(submission: &shared::Submission, secret: Option<&str>) -> Request<grpc::Submission> {
+        let mut request = Request::new(grpc::Submission::from(submission));
+        if let Some(secret) = secret {
+            let metadata = request.metadata_mut();
+            metadata.insert("x-secret-key", secret.parse().unwrap());
+            metadata.insert("idempotency-key", "8d6a3f1e".parse().unwrap());
+        }
+        request
+    }
+
+    #[test_case(StatusCode::NO_CONTENT, grpc::HeartbeatStatus::Ok; "saved")]
+    #[test_case(StatusCode::UNAUTHORIZED, grpc::HeartbeatStatus::Unauthenticated; "unauthorized")]
+    #[test_case(StatusCode::UPGRADE_REQUIRED, grpc::HeartbeatStatus::UpgradeRequired; "old client")]
+    #[test_case(StatusCode::BAD_REQUEST, grpc::HeartbeatStatus::Invalid; "no session id")]
+    #[test_case(StatusCode::UNPROCESSABLE_ENTITY, grpc::HeartbeatStatus::Invalid; "invalid")]
+    #[test_case(StatusCode::INTERNAL_SERVER_ERROR, grpc::HeartbeatStatus::DatabaseError; "database error")]
+    fn status_of_heartbeat(status: StatusCode, output: grpc::HeartbeatStatus) {
+        assert_eq!(heartbeat_status(status), output);
+    }
+
+    #[tokio::test]
+    async fn submission_is_saved_once() {
+        let storage = Arc::new(MemoryStorage::default());
+        let service = Service {
+            state: state(CONFIG, storage.clone()),
+        };
+        let submission = submission("ELDEN RING", "2024-03-01T12:00:00Z");
+        let mut statuses = Vec::new();
+        for _ in 0..2 {
+            let response = service
+                .submit(request(&submission, Some("secret")))
+                .await
+                .unwrap();
+            statuses.push(response.into_inner().status());
+        }
+        assert_eq!(
+            statuses,
+            [
+                grpc::SubmissionStatus::Ok,
+                grpc::SubmissionStatus::Duplicate
+            ]
+        );
+        let events = storage.events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].name.as_deref(), Some("ELDEN RING"));
+    }
+
+    #[tokio::test]
+    async fn submission_without_secret_is_unauthenticated() {
+        let storage = Arc::new(MemoryStorage::default());
+        let service = Service {
+            state: state(CONFIG, storage.clone()),
+        };
+        let submission = submission("ELDEN RING", "2024-03-01T12:00:00Z");
+        let response = service.submit(request(&submission, None)).await.unwrap();
+        assert_eq!(
+            response.into_inner().status(),
+            grpc::SubmissionStatus::Unauthenticated
+        );
+        assert!(storage.events.lock().unwrap().is_empty());
+    }
+}
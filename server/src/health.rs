@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use axum::{extract::State, http::StatusCode};
+use log::{error, info, warn};
+
+use crate::{db::Database, AppState};
+
+/// Liveness check: the server is up and handling requests.
+#[utoipa::path(
+    get,
+    path = "/health",
+    responses((status = 200, description = "Server is running")),
+    security(()),
+)]
+pub async fn health() -> StatusCode {
+    StatusCode::OK
+}
+
+/// Readiness check: a database connection can be checked out and used.
+#[utoipa::path(
+    get,
+    path = "/ready",
+    responses(
+        (status = 200, description = "Database can be queried"),
+        (status = 503, description = "Database cannot be queried"),
+    ),
+    security(()),
+)]
+pub async fn ready(State(state): State<AppState>) -> StatusCode {
+    match state.db.ping().await {
+        Ok(()) => StatusCode::OK,
+        Err(error) => {
+            error!("Readiness check failed: {}", error);
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    }
+}
+
+/// Log the state of the connection pool every `interval`, as a warning when
+/// requests are waiting for a connection.
+pub async fn log_pool(db: Database, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        let status = db.status();
+        let in_use = status.size - status.available;
+        if status.waiting > 0 {
+            warn!(
+                "Database pool is exhausted: {} of {} connections in use, {} requests waiting",
+                in_use, status.max_size, status.waiting
+            );
+        } else {
+            info!(
+                "Database pool: {} of {} connections open, {} in use",
+                status.size, status.max_size, in_use
+            );
+        }
+    }
+}
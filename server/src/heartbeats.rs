@@ -0,0 +1,160 @@
+use std::time::Duration;
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use log::{debug, error, info, warn};
+use time::OffsetDateTime;
+
+use crate::{
+    authorize,
+    config::{IgnoreAction, Scope, SubmissionLimits},
+    ignore_rules, is_supported_client, match_name_case, name_normalization, new_event,
+    payload::Payload,
+    stream, submission_limits, util, webhooks, AppState,
+};
+
+/// Time between checks for sessions whose heartbeats have stopped.
+const REAP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Record the current state of a session in progress. The session must have
+/// a session ID, which is also used as the idempotency key of the event if
+/// the heartbeats stop before the session is submitted.
+#[utoipa::path(
+    post,
+    path = "/heartbeat",
+    request_body = shared::Submission,
+    responses(
+        (status = 204, description = "Heartbeat saved"),
+        (status = 400, description = "Session ID missing"),
+        (status = 401, description = "Not authenticated"),
+        (status = 422, description = "Invalid session"),
+        (status = 426, description = "Client is older than the minimum version"),
+    ),
+)]
+pub async fn heartbeat(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Payload(payload): Payload<shared::Submission>,
+) -> StatusCode {
+    let Some(caller) = authorize(&headers, &state, Scope::Submit).await else {
+        return StatusCode::UNAUTHORIZED;
+    };
+    if !is_supported_client(&headers, &state.config) {
+        return StatusCode::UPGRADE_REQUIRED;
+    }
+    let Some(session_id) = payload.session_id.clone() else {
+        return StatusCode::BAD_REQUEST;
+    };
+    // Sessions in progress are allowed to be short still.
+    let limits = SubmissionLimits {
+        minimum_duration: 0,
+        ..submission_limits(&state.config)
+    };
+    let problem = payload
+        .problem(&(&limits).into())
+        .or_else(|| util::device_problem(&payload, caller.device_id));
+    if let Some((_, problem)) = problem {
+        warn!("Rejected heartbeat for {}: {}", payload.display(), problem);
+        return StatusCode::UNPROCESSABLE_ENTITY;
+    }
+    match util::ignored(&payload.executable, &ignore_rules(&state.config)) {
+        Some(IgnoreAction::Reject) => {
+            warn!("Rejected heartbeat for {}: ignored", payload.display());
+            return StatusCode::UNPROCESSABLE_ENTITY;
+        }
+        Some(IgnoreAction::Drop) => return StatusCode::NO_CONTENT,
+        None => {}
+    }
+    let rules = name_normalization(&state.config);
+    let mut event = new_event(&payload, &caller, Some(session_id.clone()), &rules, &limits);
+    if rules.case_insensitive {
+        match_name_case(&*state.storage, &mut event).await;
+    }
+    // Staleness is judged by the server clock, not the client's.
+    event.time = OffsetDateTime::now_utc();
+    match state.db.save_heartbeat(session_id, event).await {
+        Ok(()) => {
+            debug!("Heartbeat for {}", payload.display());
+            StatusCode::NO_CONTENT
+        }
+        Err(error) => {
+            error!(
+                "Could not save heartbeat for {}: {}",
+                payload.display(),
+                error
+            );
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Sessions in progress. Callers limited to a user only see the sessions of
+/// the user.
+#[utoipa::path(
+    get,
+    path = "/now-playing",
+    responses(
+        (status = 200, description = "Sessions in progress", body = Vec<shared::NowPlaying>),
+        (status = 401, description = "Not authenticated"),
+    ),
+)]
+pub async fn now_playing(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<shared::NowPlaying>>, StatusCode> {
+    let Some(caller) = authorize(&headers, &state, Scope::Read).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    match state.db.now_playing(caller.user_id).await {
+        Ok(sessions) => Ok(Json(sessions)),
+        Err(error) => {
+            error!("Could not list sessions in progress: {}", error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn reap(state: &AppState) {
+    let timeout = match state.config.read() {
+        Ok(config) => config.heartbeat_timeout,
+        Err(_) => {
+            error!("Could not read configuration");
+            return;
+        }
+    };
+    let before = OffsetDateTime::now_utc() - Duration::from_secs(timeout);
+    let events = match state.db.reap_heartbeats(before).await {
+        Ok(events) => events,
+        Err(error) => {
+            error!("Could not close stale sessions: {}", error);
+            return;
+        }
+    };
+    for event in events {
+        info!(
+            "Closed session of {} after its heartbeats stopped",
+            event.name.as_ref().unwrap_or(&event.executable)
+        );
+        let session = webhooks::Session::new(&event, &state.users);
+        state.webhooks.deliver(&session);
+        state.time_trackers.deliver(&session);
+        let update = stream::Update::Session {
+            user_id: event.user_id,
+            session,
+        };
+        stream::publish(&state.live, update);
+    }
+}
+
+/// Record sessions whose heartbeats have stopped, such as ones lost to the
+/// client crashing, as having ended at their last heartbeat.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(REAP_INTERVAL);
+    loop {
+        interval.tick().await;
+        reap(&state).await;
+    }
+}
@@ -0,0 +1,168 @@
+use std::time::Duration;
+
+use log::{debug, error, info};
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+use crate::{config::HowLongToBeatConfig, db::Database, util};
+
+/// Time between looking for games that haven't been looked up.
+const LOOKUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Time to wait between searches to go easy on HowLongToBeat.
+const SEARCH_DELAY: Duration = Duration::from_secs(2);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Average times to beat a game on HowLongToBeat, in seconds.
+pub struct CompletionTime {
+    pub game_id: i32,
+    pub game: String,
+    pub main_story: Option<u64>,
+    pub main_extra: Option<u64>,
+    pub completionist: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    data: Vec<SearchResult>,
+}
+
+/// Game in the search results. Times are in seconds, 0 if not known.
+#[derive(Deserialize)]
+struct SearchResult {
+    game_id: i32,
+    game_name: String,
+    comp_main: u64,
+    comp_plus: u64,
+    comp_100: u64,
+}
+
+impl From<SearchResult> for CompletionTime {
+    fn from(result: SearchResult) -> Self {
+        let known = |seconds: u64| Some(seconds).filter(|seconds| *seconds > 0);
+        CompletionTime {
+            game_id: result.game_id,
+            game: result.game_name,
+            main_story: known(result.comp_main),
+            main_extra: known(result.comp_plus),
+            completionist: known(result.comp_100),
+        }
+    }
+}
+
+async fn search(
+    client: &reqwest::Client,
+    config: &HowLongToBeatConfig,
+    title: &str,
+) -> Result<Option<CompletionTime>, reqwest::Error> {
+    let body = serde_json::json!({
+        "searchType": "games",
+        "searchTerms": title.split_whitespace().collect::<Vec<_>>(),
+        "searchPage": 1,
+        "size": 20,
+        "searchOptions": {
+            "games": {
+                "userId": 0,
+                "platform": "",
+                "sortCategory": "popular",
+                "rangeCategory": "main",
+                "rangeTime": { "min": 0, "max": 0 },
+                "gameplay": { "perspective": "", "flow": "", "genre": "" },
+                "modifier": "",
+            },
+            "users": { "sortCategory": "postcount" },
+            "filter": "",
+            "sort": 0,
+            "randomizer": 0,
+        },
+    });
+    let response = client
+        .post(&config.search_url)
+        .header(reqwest::header::REFERER, "https://howlongtobeat.com/")
+        .json(&body)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<SearchResponse>()
+        .await?;
+    // Results are sorted by popularity, so prefer an exact match over a more
+    // popular sequel.
+    let mut results = response.data;
+    let title = util::normalise_title(title);
+    let exact = results
+        .iter()
+        .position(|result| util::normalise_title(&result.game_name) == title);
+    let result = match exact {
+        Some(index) => Some(results.swap_remove(index)),
+        None => results.into_iter().next(),
+    };
+    Ok(result.map(CompletionTime::from))
+}
+
+/// Look up the processes that haven't been looked up or were looked up too
+/// long ago. Games that can't be found are remembered as such until they are
+/// due again.
+async fn lookup(db: &Database, client: &reqwest::Client, config: &HowLongToBeatConfig) {
+    let now = OffsetDateTime::now_utc();
+    let stale_before = now - Duration::from_secs(config.refresh_days * SECONDS_PER_DAY);
+    let processes = match db.completion_lookups(stale_before).await {
+        Ok(processes) => processes,
+        Err(error) => {
+            error!("Could not list processes to look up: {}", error);
+            return;
+        }
+    };
+    let mut found = 0;
+    for (process_id, executable, name) in &processes {
+        let title = util::search_title(executable, name.as_deref());
+        let time = match search(client, config, &title).await {
+            Ok(time) => time,
+            Err(error) => {
+                // Likely the same for the rest, so try again next time.
+                error!("Could not search HowLongToBeat for {}: {}", title, error);
+                return;
+            }
+        };
+        match &time {
+            Some(time) => {
+                debug!("Found {} on HowLongToBeat as {}", title, time.game);
+                found += 1;
+            }
+            None => debug!("Could not find {} on HowLongToBeat", title),
+        }
+        if let Err(error) = db.save_completion_time(*process_id, time, now).await {
+            error!("Could not save completion time of {}: {}", title, error);
+            return;
+        }
+        tokio::time::sleep(SEARCH_DELAY).await;
+    }
+    if !processes.is_empty() {
+        info!(
+            "Looked up {} games on HowLongToBeat, found {}",
+            processes.len(),
+            found
+        );
+    }
+}
+
+pub async fn run(db: Database, config: HowLongToBeatConfig) {
+    let client = match reqwest::Client::builder()
+        .user_agent(concat!("beelzebub-server/", env!("CARGO_PKG_VERSION")))
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+    {
+        Ok(client) => client,
+        Err(error) => {
+            error!("Could not set up HowLongToBeat lookups: {}", error);
+            return;
+        }
+    };
+    let mut interval = tokio::time::interval(LOOKUP_INTERVAL);
+    loop {
+        interval.tick().await;
+        lookup(&db, &client, &config).await;
+    }
+}
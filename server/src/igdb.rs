@@ -0,0 +1,194 @@
+use std::time::Duration;
+
+use log::{debug, error, info};
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+use crate::{config::IgdbConfig, db::Database, util};
+
+const TOKEN_URL: &str = "https://id.twitch.tv/oauth2/token";
+
+const GAMES_URL: &str = "https://api.igdb.com/v4/games";
+
+/// Size of the cover images linked to, see
+/// https://api-docs.igdb.com/#images.
+const COVER_URL: &str = "https://images.igdb.com/igdb/image/upload/t_cover_big";
+
+/// Time between looking for games that haven't been looked up.
+const LOOKUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Time to wait between searches, well within the limit of four requests a
+/// second.
+const SEARCH_DELAY: Duration = Duration::from_secs(1);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Game found on IGDB.
+pub struct Metadata {
+    pub igdb_id: i32,
+    pub details: shared::GameMetadata,
+}
+
+#[derive(Deserialize)]
+struct Token {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct Game {
+    id: i32,
+    name: String,
+    #[serde(default)]
+    genres: Vec<Genre>,
+    cover: Option<Cover>,
+}
+
+#[derive(Deserialize)]
+struct Genre {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Cover {
+    image_id: String,
+}
+
+impl From<Game> for Metadata {
+    fn from(game: Game) -> Self {
+        Metadata {
+            igdb_id: game.id,
+            details: shared::GameMetadata {
+                title: game.name,
+                genres: game.genres.into_iter().map(|genre| genre.name).collect(),
+                cover_url: game
+                    .cover
+                    .map(|cover| format!("{}/{}.jpg", COVER_URL, cover.image_id)),
+            },
+        }
+    }
+}
+
+/// Access token for the Twitch application, which IGDB accepts.
+async fn token(client: &reqwest::Client, config: &IgdbConfig) -> Result<String, reqwest::Error> {
+    let token = client
+        .post(TOKEN_URL)
+        .query(&[
+            ("client_id", config.client_id.as_str()),
+            ("client_secret", config.client_secret.as_str()),
+            ("grant_type", "client_credentials"),
+        ])
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(reqwest::Error::without_url)?
+        .json::<Token>()
+        .await?;
+    Ok(token.access_token)
+}
+
+async fn search(
+    client: &reqwest::Client,
+    config: &IgdbConfig,
+    token: &str,
+    title: &str,
+) -> Result<Option<Metadata>, reqwest::Error> {
+    let query = format!(
+        "search \"{}\"; fields name, genres.name, cover.image_id; limit 10;",
+        title.replace(['"', '\\'], "")
+    );
+    let mut games = client
+        .post(GAMES_URL)
+        .header("Client-ID", &config.client_id)
+        .bearer_auth(token)
+        .body(query)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Vec<Game>>()
+        .await?;
+    // Prefer an exact match over a more relevant remaster or sequel.
+    let title = util::normalise_title(title);
+    let exact = games
+        .iter()
+        .position(|game| util::normalise_title(&game.name) == title);
+    let game = match exact {
+        Some(index) => Some(games.swap_remove(index)),
+        None => games.into_iter().next(),
+    };
+    Ok(game.map(Metadata::from))
+}
+
+/// Look up the processes that haven't been looked up or were looked up too
+/// long ago. Games that can't be found are remembered as such until they are
+/// due again.
+async fn lookup(db: &Database, client: &reqwest::Client, config: &IgdbConfig) {
+    let now = OffsetDateTime::now_utc();
+    let stale_before = now - Duration::from_secs(config.refresh_days * SECONDS_PER_DAY);
+    let processes = match db.metadata_lookups(stale_before).await {
+        Ok(processes) => processes,
+        Err(error) => {
+            error!("Could not list processes to look up: {}", error);
+            return;
+        }
+    };
+    if processes.is_empty() {
+        return;
+    }
+    let token = match token(client, config).await {
+        Ok(token) => token,
+        Err(error) => {
+            error!("Could not log in to IGDB: {}", error);
+            return;
+        }
+    };
+    let mut found = 0;
+    for (process_id, executable, name) in &processes {
+        let title = util::search_title(executable, name.as_deref());
+        let metadata = match search(client, config, &token, &title).await {
+            Ok(metadata) => metadata,
+            Err(error) => {
+                // Likely the same for the rest, so try again next time.
+                error!("Could not search IGDB for {}: {}", title, error);
+                return;
+            }
+        };
+        match &metadata {
+            Some(metadata) => {
+                debug!("Found {} on IGDB as {}", title, metadata.details.title);
+                found += 1;
+            }
+            None => debug!("Could not find {} on IGDB", title),
+        }
+        if let Err(error) = db.save_metadata(*process_id, metadata, now).await {
+            error!("Could not save IGDB details of {}: {}", title, error);
+            return;
+        }
+        tokio::time::sleep(SEARCH_DELAY).await;
+    }
+    info!(
+        "Looked up {} games on IGDB, found {}",
+        processes.len(),
+        found
+    );
+}
+
+pub async fn run(db: Database, config: IgdbConfig) {
+    let client = match reqwest::Client::builder()
+        .user_agent(concat!("beelzebub-server/", env!("CARGO_PKG_VERSION")))
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+    {
+        Ok(client) => client,
+        Err(error) => {
+            error!("Could not set up IGDB lookups: {}", error);
+            return;
+        }
+    };
+    let mut interval = tokio::time::interval(LOOKUP_INTERVAL);
+    loop {
+        interval.tick().await;
+        lookup(&db, &client, &config).await;
+    }
+}
@@ -0,0 +1,118 @@
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use log::{error, info};
+
+use crate::devices;
+
+/// Ask on the terminal, with the answer that an empty line stands for.
+fn ask(question: &str, default: &str) -> std::io::Result<String> {
+    if default.is_empty() {
+        print!("{}: ", question);
+    } else {
+        print!("{} [{}]: ", question, default);
+    }
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return Ok(default.to_string());
+    }
+    return Ok(answer.to_string());
+}
+
+/// Value written as a YAML scalar, quoted if it needs to be.
+fn yaml_value(value: &str) -> String {
+    return serde_yaml::to_string(value)
+        .map(|yaml| yaml.trim_end().to_string())
+        .unwrap_or_else(|_| format!("{:?}", value));
+}
+
+fn default_db_url() -> String {
+    let data_directory = directories::ProjectDirs::from(
+        shared::CONFIG_QUALIFIER,
+        shared::CONFIG_ORGANIZATION,
+        shared::CONFIG_APPLICATION,
+    )
+    .map(|directories| directories.data_dir().to_path_buf());
+    match data_directory {
+        Some(directory) => format!("sqlite://{}", directory.join("beelzebub.db").display()),
+        None => "sqlite://beelzebub.db".to_string(),
+    }
+}
+
+/// Configuration file with the settings a server needs to start.
+fn config_file(db_url: &str, secret: &str, port: u16) -> String {
+    return format!(
+        "# Beelzebub server configuration. The README describes every setting.\n\
+        dbUrl: {}  # postgres://, mysql:// or sqlite:// URL of the database\n\
+        secret: {}  # Key that clients send in the x-secret-key header\n\
+        listen:\n  \
+          address: 0.0.0.0\n  \
+          port: {}\n",
+        yaml_value(db_url),
+        yaml_value(secret),
+        port
+    );
+}
+
+/// Ask for the essential settings and write them to the configuration file.
+pub fn run(config_path: &Path, force: bool) -> Result<(), ()> {
+    if config_path.exists() && !force {
+        error!(
+            "{} already exists, pass --force to overwrite it",
+            config_path.display()
+        );
+        return Err(());
+    }
+    let answers = (|| -> std::io::Result<_> {
+        let db_url = ask("Database URL", &default_db_url())?;
+        let secret = match devices::generate_token() {
+            Ok(token) => ask("Secret key for clients", &token)?,
+            Err(_) => ask("Secret key for clients", "")?,
+        };
+        let port = loop {
+            match ask("Port to listen on", "8080")?.parse::<u16>() {
+                Ok(port) => break port,
+                Err(_) => println!("Port must be a number from 0 to 65535"),
+            }
+        };
+        Ok((db_url, secret, port))
+    })();
+    let (db_url, secret, port) = match answers {
+        Ok(answers) => answers,
+        Err(error) => {
+            error!("Could not read the answers: {}", error);
+            return Err(());
+        }
+    };
+    if secret.is_empty() {
+        error!("A secret key is needed for clients to submit sessions");
+        return Err(());
+    }
+
+    let mut directories = Vec::from_iter(config_path.parent());
+    if let Some(database) = db_url.strip_prefix("sqlite://") {
+        directories.extend(Path::new(database).parent());
+    }
+    for directory in directories
+        .into_iter()
+        .filter(|path| !path.as_os_str().is_empty())
+    {
+        if let Err(error) = std::fs::create_dir_all(directory) {
+            error!("Could not create {}: {}", directory.display(), error);
+            return Err(());
+        }
+    }
+    if let Err(error) = std::fs::write(config_path, config_file(&db_url, &secret, port)) {
+        error!("Could not write {}: {}", config_path.display(), error);
+        return Err(());
+    }
+    info!("Wrote configuration to {}", config_path.display());
+    info!(
+        "Set the secret key {} in the configuration of every client",
+        secret
+    );
+    return Ok(());
+}
@@ -1,32 +1,146 @@
+use std::future::IntoFuture;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
-    routing::post,
-    Json, Router,
-};
-use deadpool_diesel::postgres::{Manager, Pool};
-use diesel::{
-    pg::data_types::PgInterval,
-    result::{DatabaseErrorKind::UniqueViolation, Error::DatabaseError},
-    ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl,
+    body::to_bytes,
+    extract::{rejection::JsonRejection, DefaultBodyLimit, State},
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE, LINK},
+        HeaderMap, HeaderName, HeaderValue, StatusCode,
+    },
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{delete, get, patch, post, put},
+    Extension, Json, Router,
 };
+use axum_server::tls_rustls::RustlsConfig;
+use clap::{Parser, Subcommand};
 use log::{debug, error, info, warn, LevelFilter};
 use shared;
 use simple_logger::SimpleLogger;
+use time::OffsetDateTime;
+use tokio::sync::broadcast;
+use tower_http::{
+    compression::CompressionLayer,
+    decompression::RequestDecompressionLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
+mod activitywatch;
+mod admin;
+mod atom;
+mod audit;
+mod backfill;
+mod backup;
+mod badge;
+mod calendar;
 mod config;
+mod covers;
+mod dashboard;
 mod db;
+mod devices;
+mod etag;
+mod events;
+mod export;
+mod goals;
+mod graphql;
+mod grpc;
+mod health;
+mod heartbeats;
+mod howlongtobeat;
+mod igdb;
+mod init;
+mod metrics;
+mod openapi;
+mod pagination;
+mod payload;
+mod processes;
+mod proxies;
+mod ratelimit;
+mod reload;
+mod reports;
+mod request_id;
+mod retention;
+mod s3;
 mod schema;
+mod shares;
+mod shutdown;
+mod signing;
+mod stats;
+mod steam;
+mod storage;
+mod stream;
+mod summary;
+mod telemetry;
+mod timetrackers;
+mod timezones;
+mod tls;
+mod users;
 mod util;
+mod webhooks;
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Configuration file to use instead of the one in the configuration
+    /// directory.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Run pending database migrations and exit, e.g. in an init container.
+    #[arg(long)]
+    migrate_only: bool,
+
+    /// Exit with an error if database migrations are pending, without
+    /// running them.
+    #[arg(long, conflicts_with = "migrate_only")]
+    check_migrations: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Maintain the database instead of running the server.
+    Admin {
+        #[command(subcommand)]
+        action: admin::AdminAction,
+    },
+
+    /// Write a configuration file from answers to a few questions.
+    Init {
+        /// Overwrite the configuration file if it already exists.
+        #[arg(long)]
+        force: bool,
+    },
+}
 
 type ConfigReference = Arc<RwLock<config::Config>>;
 
 #[derive(Clone)]
 struct AppState {
     config: ConfigReference,
-    pool: Pool,
+    db: db::Database,
+    graphql: graphql::ApiSchema,
+    live: broadcast::Sender<stream::Update>,
+    metrics: Arc<metrics::Metrics>,
+    rate_limiter: Arc<ratelimit::RateLimiter>,
+    shutdown: shutdown::Shutdown,
+    signatures: Arc<signing::Signatures>,
+
+    /// Database as the handlers that record sessions see it.
+    storage: Arc<dyn storage::Storage>,
+    timezones: Arc<timezones::Timezones>,
+    users: Arc<users::UserIds>,
+    time_trackers: Arc<timetrackers::TimeTrackers>,
+    webhooks: Arc<webhooks::Webhooks>,
 }
 
 fn is_authenticated(headers: &HeaderMap, config: &ConfigReference) -> bool {
@@ -36,9 +150,17 @@ fn is_authenticated(headers: &HeaderMap, config: &ConfigReference) -> bool {
     };
     let Some(secret) = &config.secret else {
         debug!("Secret key not set");
-        return true;
+        // Configured API keys are required even without a secret.
+        return config.api_keys.is_empty();
     };
-    let Ok(x_secret_key) = (match headers.get("x-secret-key") {
+    if signing::signed() {
+        return true;
+    }
+    if config.signatures.required && headers.contains_key(shared::SECRET_KEY_HEADER) {
+        warn!("Authentication error: the secret is only accepted as a signature");
+        return false;
+    }
+    let Ok(x_secret_key) = (match headers.get(shared::SECRET_KEY_HEADER) {
         Some(value) => value.to_str(),
         None => {
             debug!("Authentication error: X-Secret-Key not provided");
@@ -48,157 +170,1126 @@ fn is_authenticated(headers: &HeaderMap, config: &ConfigReference) -> bool {
         warn!("Authentication error: X-Secret-Key is not text");
         return false;
     };
-    return x_secret_key == secret;
+    return secret.matches(x_secret_key);
 }
 
-fn get_process(conn: &mut PgConnection, payload: &shared::Submission) -> Result<i32, ()> {
-    use schema::processes::dsl::*;
+/// Whether X-Secret-Key is the admin secret, `None` if there is no admin
+/// secret and the shared secret administers the server instead.
+fn is_admin_secret(headers: &HeaderMap, config: &ConfigReference) -> Option<bool> {
+    let Ok(config) = config.read() else {
+        error!("Authentication error: cannot read configuration");
+        return Some(false);
+    };
+    let admin_secret = config.admin_secret.as_ref()?;
+    let matches = headers
+        .get(shared::SECRET_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| admin_secret.matches(value));
+    return Some(matches);
+}
 
-    let process_name = payload.name.as_ref().map(|s| util::clean_name(s));
+/// Enabled API key given in X-Secret-Key, if any, as its name, the name of
+/// its user and its scopes.
+fn api_key(
+    headers: &HeaderMap,
+    config: &ConfigReference,
+) -> Option<(String, Option<String>, Vec<config::Scope>)> {
+    let Ok(config) = config.read() else {
+        error!("Authentication error: cannot read configuration");
+        return None;
+    };
+    let x_secret_key = headers.get(shared::SECRET_KEY_HEADER)?.to_str().ok()?;
+    let api_key = config.api_keys.iter().find(|api_key| {
+        api_key
+            .key
+            .as_ref()
+            .is_some_and(|key| key.matches(x_secret_key))
+    })?;
+    if !api_key.enabled {
+        warn!("Authentication error: API key {} is disabled", api_key.name);
+        return None;
+    }
+    debug!(
+        "Authenticated with API key {} created {}",
+        api_key.name,
+        api_key.created.as_deref().unwrap_or("at an unknown time")
+    );
+    return Some((
+        api_key.name.clone(),
+        api_key.user.clone(),
+        api_key.scopes.clone(),
+    ));
+}
 
-    let mut query = processes
-        .limit(1)
-        .select(id)
-        .filter(executable.eq(&payload.executable))
-        .into_boxed();
-    if process_name.is_some() {
-        query = query.filter(name.eq(process_name));
-    } else {
-        query = query.filter(name.is_null());
+/// Client version is at least the configured minimum, if there is one.
+fn is_supported_client(headers: &HeaderMap, config: &ConfigReference) -> bool {
+    let Ok(config) = config.read() else {
+        error!("Version check error: cannot read configuration");
+        return false;
+    };
+    let Some(minimum_version) = &config.minimum_client_version else {
+        return true;
+    };
+    let Some(minimum) = util::parse_version(minimum_version) else {
+        error!("Invalid minimum client version {}", minimum_version);
+        return true;
+    };
+    let version = headers
+        .get(shared::CLIENT_VERSION_HEADER)
+        .and_then(|value| value.to_str().ok());
+    match version.and_then(util::parse_version) {
+        Some(version) if version >= minimum => true,
+        _ => {
+            warn!(
+                "Rejecting client version {}, minimum is {}",
+                version.unwrap_or("unknown"),
+                minimum_version
+            );
+            false
+        }
     }
-    if let Ok(results) = query.limit(1).select(id).load::<i32>(conn) {
-        if let Some(result) = results.first() {
-            return Ok(*result);
+}
+
+/// Sender of an authorised request.
+#[derive(Clone, Debug, Default)]
+struct Caller {
+    /// Name of the API key the request was made with.
+    api_key: Option<String>,
+
+    /// User the request is limited to. Callers without a user, such as ones
+    /// using the shared secret, see the sessions of everyone.
+    user_id: Option<i32>,
+
+    /// Registered device the request was made from.
+    device_id: Option<i32>,
+
+    /// The request was made with the admin secret.
+    admin_secret: bool,
+}
+
+impl Caller {
+    /// Who made the request, for the audit trail: the name of the API key,
+    /// `device <ID>`, `admin secret` or `secret`.
+    fn actor(&self) -> String {
+        match (&self.api_key, self.device_id) {
+            (Some(api_key), _) => api_key.clone(),
+            (None, Some(device_id)) => format!("device {}", device_id),
+            (None, None) if self.admin_secret => audit::ADMIN_SECRET.to_string(),
+            (None, None) => "secret".to_string(),
         }
     }
+}
 
-    match diesel::insert_into(processes)
-        .values((executable.eq(&payload.executable), name.eq(process_name)))
-        .returning(id)
-        .get_results::<i32>(conn)
-    {
-        Ok(result) => return Ok(result[0]),
-        Err(DatabaseError(UniqueViolation, _)) => {
-            let mut query = processes
-                .limit(1)
-                .select(id)
-                .filter(executable.eq(&payload.executable))
-                .into_boxed();
-            if process_name.is_some() {
-                query = query.filter(name.eq(process_name));
-            } else {
-                query = query.filter(name.is_null());
-            }
-            match query.load::<i32>(conn) {
-                Ok(results) => return Ok(results[0]),
-                Err(error) => {
-                    error!("Unknown database error during SELECT: {}", error);
-                    return Err(());
-                }
-            };
+/// Sender of the request if it is authenticated with an enabled API key
+/// that has the scope, the shared secret or the token of an approved device.
+/// Devices can read and submit but not administer, and neither can the
+/// shared secret if there is an admin secret, which can do everything.
+async fn authorize(headers: &HeaderMap, state: &AppState, scope: config::Scope) -> Option<Caller> {
+    if let Some((name, user, scopes)) = api_key(headers, &state.config) {
+        if !scope.granted_by(&scopes) {
+            warn!(
+                "Authentication error: API key {} does not have the {:?} scope",
+                name, scope
+            );
+            return None;
         }
-        Err(error) => {
-            error!("Unknown database error during INSERT: {}", error);
-            debug!("Payload: {:?}", payload);
-            return Err(());
+        return Some(Caller {
+            api_key: Some(name),
+            user_id: user.and_then(|user| state.users.get(&user).copied()),
+            device_id: None,
+            admin_secret: false,
+        });
+    }
+    match is_admin_secret(headers, &state.config) {
+        Some(true) => {
+            return Some(Caller {
+                admin_secret: true,
+                ..Default::default()
+            })
         }
+        Some(false) if scope == config::Scope::Admin => return None,
+        _ => {}
+    }
+    if is_authenticated(headers, &state.config) {
+        return Some(Caller::default());
+    }
+    if scope == config::Scope::Admin {
+        return None;
+    }
+    let (device_id, user_id) = devices::authenticate_device(headers, &state.db).await?;
+    return Some(Caller {
+        api_key: None,
+        user_id,
+        device_id: Some(device_id),
+        admin_secret: false,
+    });
+}
+
+/// Caller that `etag::cache` has already authorised for reading, so that the
+/// handlers behind it don't look up the credentials again, otherwise the one
+/// the headers authorise.
+async fn authorize_read(
+    authorized: Option<Extension<Caller>>,
+    headers: &HeaderMap,
+    state: &AppState,
+) -> Option<Caller> {
+    if let Some(Extension(caller)) = authorized {
+        return Some(caller);
+    }
+    return authorize(headers, state, config::Scope::Read).await;
+}
+
+/// Use the `key` query parameter as `X-Secret-Key`, for feed readers and
+/// calendar apps that cannot send headers.
+fn add_query_key(headers: &mut HeaderMap, key: Option<&str>) -> Result<(), StatusCode> {
+    if let Some(key) = key {
+        let value = HeaderValue::from_str(key).map_err(|_| StatusCode::UNAUTHORIZED)?;
+        let name = HeaderName::try_from(shared::SECRET_KEY_HEADER)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        headers.insert(name, value);
     }
+    Ok(())
 }
 
-fn database_error() -> (StatusCode, Json<shared::SubmissionResponse>) {
+fn submission_response(
+    status_code: StatusCode,
+    status: shared::SubmissionResponseStatus,
+    message: Option<String>,
+) -> (StatusCode, Json<shared::SubmissionResponse>) {
     let response = shared::SubmissionResponse {
-        status: shared::SubmissionResponseStatus::DatabaseError,
+        status,
+        code: status.code(),
+        message,
+        field: None,
+    };
+    return (status_code, Json(response));
+}
+
+fn submission_limits(config: &ConfigReference) -> config::SubmissionLimits {
+    match config.read() {
+        Ok(config) => config.submission_limits.clone(),
+        Err(_) => {
+            error!("Could not read configuration, using default submission limits");
+            config::SubmissionLimits::default()
+        }
+    }
+}
+
+/// Response to a body that could not be read as a submission.
+fn rejected_submission(rejection: JsonRejection) -> (StatusCode, Json<shared::SubmissionResponse>) {
+    let status = match rejection.status() {
+        StatusCode::PAYLOAD_TOO_LARGE => shared::SubmissionResponseStatus::PayloadTooLarge,
+        _ => shared::SubmissionResponseStatus::ValidationError,
+    };
+    return submission_response(rejection.status(), status, Some(rejection.body_text()));
+}
+
+/// Explain a request body over the limit like a rejected submission, for
+/// every endpoint. Responses that already have a JSON body are left alone.
+async fn payload_too_large(response: Response) -> Response {
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .is_some_and(|content_type| content_type.as_bytes().starts_with(b"application/json"));
+    if response.status() != StatusCode::PAYLOAD_TOO_LARGE || is_json {
+        return response;
+    }
+    let body = to_bytes(response.into_body(), 1024)
+        .await
+        .unwrap_or_default();
+    let message = match String::from_utf8_lossy(&body).trim() {
+        "" => "Request body is larger than the server accepts".to_string(),
+        message => message.to_string(),
+    };
+    return submission_response(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        shared::SubmissionResponseStatus::PayloadTooLarge,
+        Some(message),
+    )
+    .into_response();
+}
+
+/// Give error responses that have no JSON body a `shared::ApiError` with the
+/// code of the error, keeping the text of plain-text bodies such as
+/// extractor rejections as its message.
+async fn error_body(response: Response) -> Response {
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .map(|content_type| content_type.as_bytes().to_vec());
+    let is_text = match &content_type {
+        Some(content_type) => content_type.starts_with(b"text/plain"),
+        None => true,
+    };
+    if !(status.is_client_error() || status.is_server_error()) || !is_text {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let body = to_bytes(body, 1024).await.unwrap_or_default();
+    let message = match String::from_utf8_lossy(&body).trim() {
+        "" => status.canonical_reason().map(str::to_string),
+        message => Some(message.to_string()),
+    };
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.remove(CONTENT_TYPE);
+    let error = shared::ApiError {
+        code: util::error_code(status.as_u16()),
+        message,
+    };
+    return (parts, Json(error)).into_response();
+}
+
+/// Mark the responses of the paths from before `/api/v1` as deprecated,
+/// pointing to the same path under it. They are kept for one release so that
+/// older clients keep working while they are updated.
+async fn deprecated_path(request: axum::extract::Request, next: middleware::Next) -> Response {
+    let successor = format!(
+        "<{}{}>; rel=\"successor-version\"",
+        shared::API_PREFIX,
+        request.uri().path()
+    );
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    if let Ok(successor) = HeaderValue::from_str(&successor) {
+        headers.insert(LINK, successor);
+    }
+    response
+}
+
+fn name_normalization(config: &ConfigReference) -> config::NameNormalization {
+    match config.read() {
+        Ok(config) => config.name_normalization.clone(),
+        Err(_) => {
+            error!("Could not read configuration, leaving names as they are");
+            config::NameNormalization::default()
+        }
+    }
+}
+
+fn ignore_rules(config: &ConfigReference) -> Vec<config::IgnoreRule> {
+    match config.read() {
+        Ok(config) => config.ignore.clone(),
+        Err(_) => {
+            error!("Could not read configuration, ignoring no executables");
+            Vec::new()
+        }
+    }
+}
+
+/// Event for the submitted session, with the names cleaned up. Only the name
+/// of the process is normalised with the rules.
+fn new_event(
+    payload: &shared::Submission,
+    caller: &Caller,
+    key: Option<String>,
+    rules: &config::NameNormalization,
+    limits: &config::SubmissionLimits,
+) -> db::NewEvent {
+    // Older clients don't send the session window, so assume the session
+    // ended at the time of submission.
+    let end = payload.ended_at.unwrap_or_else(OffsetDateTime::now_utc);
+    let start = payload
+        .started_at
+        .unwrap_or_else(|| end - Duration::from(payload.duration));
+    let clean = |text: &String| util::clean_name(text, limits.maximum_length);
+    db::NewEvent {
+        executable: payload.executable.clone(),
+        name: payload
+            .name
+            .as_ref()
+            .and_then(clean)
+            .and_then(|name| util::normalise_name(&name, rules)),
+        time: end,
+        started_at: start,
+        duration: payload.duration,
+        focused_duration: payload.focused_duration,
+        product_version: payload.product_version.as_ref().and_then(clean),
+        file_version: payload.file_version.as_ref().and_then(clean),
+        tags: payload.tags.clone(),
+        window_title: payload.window_title.as_ref().and_then(clean),
+        api_key: caller.api_key.clone(),
+        user_id: caller.user_id,
+        device: caller.device_id,
+        idempotency_key: key,
+        imported: false,
+        manual: false,
+        note: None,
+    }
+}
+
+/// Use the spelling of an existing process of the executable whose name
+/// differs from the name of the event only in case.
+async fn match_name_case(storage: &(impl storage::Storage + ?Sized), event: &mut db::NewEvent) {
+    let Some(name) = &event.name else {
+        return;
     };
-    return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+    let lowercase = name.to_lowercase();
+    match storage.process_names(event.executable.clone()).await {
+        Ok(names) => {
+            if let Some(existing) = names
+                .into_iter()
+                .flatten()
+                .find(|existing| existing.to_lowercase() == lowercase)
+            {
+                event.name = Some(existing);
+            }
+        }
+        Err(error) => warn!("Could not look up names of {}: {}", event.executable, error),
+    }
 }
 
+#[utoipa::path(
+    post,
+    path = "/submit",
+    request_body = shared::Submission,
+    responses(
+        (status = 201, description = "Session saved, or already saved before", body = shared::SubmissionResponse),
+        (status = 400, description = "Malformed submission", body = shared::SubmissionResponse),
+        (status = 401, description = "Not authenticated", body = shared::SubmissionResponse),
+        (status = 413, description = "Submission too large", body = shared::SubmissionResponse),
+        (status = 422, description = "Invalid submission", body = shared::SubmissionResponse),
+        (status = 426, description = "Client is older than the minimum version", body = shared::SubmissionResponse),
+        (status = 500, description = "Database error", body = shared::SubmissionResponse),
+    ),
+)]
 async fn submit(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Json(payload): Json<shared::Submission>,
+    payload: Result<Json<shared::Submission>, JsonRejection>,
 ) -> (StatusCode, Json<shared::SubmissionResponse>) {
-    if !is_authenticated(&headers, &state.config) {
+    let authorized = authorize(&headers, &state, config::Scope::Submit);
+    let Some(caller) = telemetry::in_span("authorize", authorized).await else {
+        state.metrics.submission_rejected("unauthenticated");
+        return submission_response(
+            StatusCode::UNAUTHORIZED,
+            shared::SubmissionResponseStatus::Unauthenticated,
+            None,
+        );
+    };
+    if !is_supported_client(&headers, &state.config) {
+        state.metrics.submission_rejected("upgrade_required");
+        return submission_response(
+            StatusCode::UPGRADE_REQUIRED,
+            shared::SubmissionResponseStatus::UpgradeRequired,
+            None,
+        );
+    }
+    let payload = match payload {
+        Ok(Json(payload)) => payload,
+        Err(rejection) => {
+            warn!("Rejected submission: {}", rejection.body_text());
+            state.metrics.submission_rejected("invalid");
+            return rejected_submission(rejection);
+        }
+    };
+    let limits = submission_limits(&state.config);
+    let problem = payload
+        .problem(&(&limits).into())
+        .or_else(|| util::device_problem(&payload, caller.device_id));
+    if let Some((field, problem)) = problem {
+        warn!("Rejected submission {}: {}", payload.display(), problem);
+        state.metrics.submission_rejected("invalid");
         let response = shared::SubmissionResponse {
-            status: shared::SubmissionResponseStatus::Unauthenticated,
+            status: shared::SubmissionResponseStatus::ValidationError,
+            code: Some(shared::ApiErrorCode::Validation),
+            message: Some(problem),
+            field: Some(field.to_string()),
         };
-        return (StatusCode::UNAUTHORIZED, Json(response));
+        return (StatusCode::UNPROCESSABLE_ENTITY, Json(response));
+    }
+    match util::ignored(&payload.executable, &ignore_rules(&state.config)) {
+        Some(config::IgnoreAction::Reject) => {
+            warn!("Rejected submission {}: ignored", payload.display());
+            state.metrics.submission_rejected("ignored");
+            let response = shared::SubmissionResponse {
+                status: shared::SubmissionResponseStatus::ValidationError,
+                code: Some(shared::ApiErrorCode::Validation),
+                message: Some("executable is ignored".to_string()),
+                field: Some("executable".to_string()),
+            };
+            return (StatusCode::UNPROCESSABLE_ENTITY, Json(response));
+        }
+        Some(config::IgnoreAction::Drop) => {
+            debug!("Dropped submission {}: ignored", payload.display());
+            state.metrics.submission_rejected("ignored");
+            return submission_response(
+                StatusCode::CREATED,
+                shared::SubmissionResponseStatus::Ok,
+                None,
+            );
+        }
+        None => {}
     }
 
-    let Ok(conn) = state.pool.get().await else {
-        error!("Could not get connection from pool");
-        return database_error();
-    };
-    let result = conn
-        .interact(move |conn| {
-            use diesel::{ExpressionMethods, RunQueryDsl};
-            use schema::events::dsl::*;
+    // Retries of the same session carry the same key, either in the header or
+    // as the session ID generated by the client.
+    let key = headers
+        .get(shared::IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .or_else(|| payload.session_id.clone());
 
-            let Ok(process_id) = get_process(conn, &payload) else {
-                return Err(());
-            };
-            let interval = PgInterval::from_microseconds(payload.duration as i64 * 1_000_000);
-            match diesel::insert_into(events)
-                .values((
-                    time.eq(diesel::dsl::now),
-                    process.eq(process_id),
-                    duration.eq(interval),
-                ))
-                .execute(conn)
-            {
-                Ok(_) => info!("Process {} saved", payload.display()),
-                Err(error) => {
-                    error!("Could not save event for {}: {}", payload.display(), error);
-                    return Err(());
-                }
-            }
-            Ok(())
-        })
+    let rules = name_normalization(&state.config);
+    let mut event = new_event(&payload, &caller, key, &rules, &limits);
+    if rules.case_insensitive {
+        telemetry::in_span(
+            "match name case",
+            match_name_case(&*state.storage, &mut event),
+        )
         .await;
+    }
 
-    if result.is_err() {
-        return database_error();
+    // Duplicates get the same status code as the original submission, so
+    // that older clients count them as saved.
+    let session = webhooks::Session::new(&event, &state.users);
+    let saved = match limits.duplicate_tolerance {
+        Some(tolerance) => state.storage.save_submission(event, tolerance).await,
+        None => state.storage.save_event(event).await,
+    };
+    let status = match saved {
+        Ok(true) => {
+            info!("Process {} saved", payload.display());
+            state.metrics.submission_saved();
+            state.webhooks.deliver(&session);
+            state.time_trackers.deliver(&session);
+            let update = stream::Update::Session {
+                user_id: caller.user_id,
+                session,
+            };
+            stream::publish(&state.live, update);
+            audit::record(
+                &*state.storage,
+                &caller.actor(),
+                audit::Action::Submit,
+                Some(payload.executable.clone()),
+                Some(payload.display()),
+            )
+            .await;
+            shared::SubmissionResponseStatus::Ok
+        }
+        Ok(false) => {
+            info!("Process {} was already saved", payload.display());
+            state.metrics.submission_rejected("duplicate");
+            shared::SubmissionResponseStatus::Duplicate
+        }
+        Err(error) => {
+            error!("Could not save event for {}: {}", payload.display(), error);
+            debug!("Payload: {:?}", payload);
+            state.metrics.submission_rejected("database_error");
+            return submission_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                shared::SubmissionResponseStatus::DatabaseError,
+                None,
+            );
+        }
+    };
+
+    // The session has ended, so it is no longer playing.
+    if let Some(session_id) = payload.session_id.clone() {
+        if let Err(error) = state.storage.delete_heartbeat(session_id).await {
+            warn!(
+                "Could not delete heartbeat for {}: {}",
+                payload.display(),
+                error
+            );
+        }
     }
 
-    let response = shared::SubmissionResponse {
-        status: shared::SubmissionResponseStatus::Ok,
+    submission_response(StatusCode::CREATED, status, None)
+}
+
+#[utoipa::path(
+    get,
+    path = "/client-settings",
+    responses(
+        (status = 200, description = "Centrally managed client settings", body = shared::ClientSettings),
+        (status = 401, description = "Not authenticated"),
+        (status = 404, description = "No settings configured"),
+        (status = 426, description = "Client is older than the minimum version"),
+    ),
+)]
+async fn client_settings(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<shared::ClientSettings>, StatusCode> {
+    if authorize(&headers, &state, config::Scope::Submit)
+        .await
+        .is_none()
+    {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    if !is_supported_client(&headers, &state.config) {
+        return Err(StatusCode::UPGRADE_REQUIRED);
+    }
+    let Ok(config) = state.config.read() else {
+        error!("Could not read configuration");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
     };
-    (StatusCode::CREATED, Json(response))
+    match &config.client_settings {
+        Some(settings) => Ok(Json(settings.clone())),
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+/// Protocols that the server speaks, where their endpoints are and which
+/// optional features it has. Needs no authentication, so that clients can
+/// check it before sending anything.
+#[utoipa::path(
+    get,
+    path = "/api/version",
+    security(()),
+    responses(
+        (status = 200, description = "Protocols and capabilities of the server", body = shared::ApiVersion),
+    ),
+)]
+async fn api_version(State(state): State<AppState>) -> Json<shared::ApiVersion> {
+    let mut capabilities = vec![
+        shared::capability::BACKFILL,
+        shared::capability::HEARTBEAT,
+        shared::capability::DEVICE_REGISTRATION,
+        shared::capability::GZIP_REQUESTS,
+        shared::capability::IDEMPOTENCY_KEYS,
+        shared::capability::MSGPACK,
+        shared::capability::GRPC,
+    ];
+    match state.config.read() {
+        Ok(config) if config.client_settings.is_some() => {
+            capabilities.push(shared::capability::CLIENT_SETTINGS)
+        }
+        Ok(_) => {}
+        Err(_) => error!("Could not read configuration for the API version"),
+    }
+    Json(shared::ApiVersion {
+        protocol: shared::PROTOCOL_VERSION,
+        minimum_protocol: shared::PROTOCOL_VERSION,
+        prefix: shared::API_PREFIX.to_string(),
+        server_version: env!("CARGO_PKG_VERSION").to_string(),
+        capabilities: capabilities.into_iter().map(str::to_string).collect(),
+    })
+}
+
+/// Wait until the database accepts connections, retrying with a delay that
+/// doubles after every failed attempt.
+async fn wait_for_database(
+    db: &db::Database,
+    retry: &config::DatabaseRetryConfig,
+) -> Result<(), db::Error> {
+    let mut delay = retry.initial_delay;
+    let mut attempt = 1;
+    loop {
+        match db.ping().await {
+            Ok(()) => return Ok(()),
+            Err(error) if attempt >= retry.attempts => return Err(error),
+            Err(error) => {
+                warn!(
+                    "Could not connect to database (attempt {} of {}), retrying in {}s: {}",
+                    attempt, retry.attempts, delay, error
+                );
+                tokio::time::sleep(Duration::from_secs(delay)).await;
+                delay = (delay * 2).min(retry.maximum_delay);
+                attempt += 1;
+            }
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() {
-    SimpleLogger::new()
-        .with_level(LevelFilter::Info)
-        .env()
-        .init()
-        .unwrap();
-
-    let Ok(config_path) = config::Config::get_path() else {
-        error!("Could not determine configuration path");
-        return;
+    let cli = Cli::parse();
+
+    request_id::init_logger(SimpleLogger::new().with_level(LevelFilter::Info).env()).unwrap();
+
+    let config_path = match cli.config.clone() {
+        Some(path) => path,
+        None => {
+            let Ok(path) = config::Config::get_path() else {
+                error!("Could not determine configuration path");
+                return;
+            };
+            path
+        }
     };
+    if let Some(Command::Init { force }) = &cli.command {
+        if init::run(&config_path, *force).is_err() {
+            std::process::exit(1);
+        }
+        return;
+    }
     let config = match config::Config::load(&config_path) {
         Ok(config) => config,
-        Err(_) => {
-            error!("Could not load configuration");
+        Err(error) => {
+            error!(
+                "Could not load configuration from {}: {:?}",
+                config_path.display(),
+                error
+            );
+            if matches!(&error, config::Error::IOError(error) if error.kind() == std::io::ErrorKind::NotFound)
+            {
+                info!("Run `beelzebub-server init` to create it");
+            }
             return;
         }
     };
 
-    let manager = Manager::new(&config.db_url, deadpool_diesel::Runtime::Tokio1);
-    let pool = Pool::builder(manager).build().unwrap();
-    db::run_migrations(&pool).await;
+    let db = match db::Database::connect(&config.db_url, &config.database_pool) {
+        Ok(db) => db,
+        Err(error) => {
+            error!("Could not connect to database: {}", error);
+            std::process::exit(1);
+        }
+    };
+    if let Err(error) = wait_for_database(&db, &config.database_retry).await {
+        error!(
+            "Could not connect to database after {} attempts, giving up: {}",
+            config.database_retry.attempts, error
+        );
+        std::process::exit(1);
+    }
+    if cli.check_migrations {
+        match db.pending_migrations().await {
+            Ok(pending) if pending.is_empty() => info!("Database schema is up to date"),
+            Ok(pending) => {
+                error!(
+                    "{} database migrations are pending: {}",
+                    pending.len(),
+                    pending.join(", ")
+                );
+                std::process::exit(1);
+            }
+            Err(error) => {
+                error!("Could not check database migrations: {}", error);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Err(error) = db.run_migrations().await {
+        error!("Could not run database migrations: {}", error);
+        std::process::exit(1);
+    }
+    if cli.migrate_only {
+        info!("Database migrations are done");
+        return;
+    }
+    if let Some(Command::Admin { action }) = cli.command {
+        if admin::run(&db, &config, action).await.is_err() {
+            std::process::exit(1);
+        }
+        return;
+    }
+    let Ok(user_ids) = users::sync(&db, &config).await else {
+        error!("Could not set up users");
+        return;
+    };
+
+    let tls_config = match config.tls.as_ref().map(tls::server_config) {
+        Some(Ok(tls_config)) => Some(tls_config),
+        Some(Err(error)) => {
+            error!("Could not load TLS configuration: {}", error);
+            return;
+        }
+        None => None,
+    };
+
+    let timezones = Arc::new(timezones::Timezones::new(&config, &user_ids));
+    if config.database_pool.log_interval > 0 {
+        tokio::spawn(health::log_pool(
+            db.clone(),
+            Duration::from_secs(config.database_pool.log_interval),
+        ));
+    }
+    if let Some(retention) = config.retention.clone() {
+        tokio::spawn(retention::run(db.clone(), retention, timezones.clone()));
+    }
+    if let Some(how_long_to_beat) = config.how_long_to_beat.clone() {
+        tokio::spawn(howlongtobeat::run(db.clone(), how_long_to_beat));
+    }
+    if let Some(igdb) = config.igdb.clone() {
+        tokio::spawn(igdb::run(db.clone(), igdb));
+    }
+    if let Some(email_reports) = config.email_reports.clone() {
+        tokio::spawn(reports::run(
+            db.clone(),
+            email_reports,
+            user_ids.clone(),
+            timezones.clone(),
+        ));
+    }
+    if let Some(s3_backups) = config.s3_backups.clone() {
+        tokio::spawn(s3::run(db.clone(), s3_backups));
+    }
+    if let Some(tracing) = config.tracing.clone() {
+        telemetry::start(tracing);
+    }
+
+    let webhooks = match webhooks::Webhooks::new(config.webhooks.clone(), config.notifiers.clone())
+    {
+        Ok(webhooks) => webhooks,
+        Err(error) => {
+            error!("Could not set up webhooks: {}", error);
+            return;
+        }
+    };
+    let time_trackers = match timetrackers::TimeTrackers::new(config.time_trackers.clone()) {
+        Ok(time_trackers) => time_trackers,
+        Err(error) => {
+            error!("Could not set up time trackers: {}", error);
+            return;
+        }
+    };
 
+    let expose_metrics = config.metrics;
+    let body_limits = config.body_limits.clone();
+    let address = SocketAddr::new(config.listen.address, config.listen.port);
     let config = Arc::new(RwLock::new(config));
     let shared_state = AppState {
         config: config,
-        pool: pool,
+        graphql: graphql::schema(db.clone(), timezones.clone()),
+        live: broadcast::channel(stream::CAPACITY).0,
+        db: db.clone(),
+        metrics: Arc::new(metrics::Metrics::default()),
+        rate_limiter: Arc::new(ratelimit::RateLimiter::default()),
+        shutdown: shutdown::Shutdown::new(),
+        signatures: Arc::new(signing::Signatures::default()),
+        storage: Arc::new(db),
+        timezones: timezones,
+        users: Arc::new(user_ids),
+        time_trackers: Arc::new(time_trackers),
+        webhooks: Arc::new(webhooks),
+    };
+
+    tokio::spawn(heartbeats::run(shared_state.clone()));
+    // Goals can be added by reloading the configuration.
+    tokio::spawn(goals::run(shared_state.clone()));
+    tokio::spawn(shared_state.shutdown.clone().listen());
+    // Kept for as long as the server runs, since dropping it stops the watch.
+    let _config_watcher = match reload::watch(&config_path, shared_state.clone()) {
+        Ok(watcher) => Some(watcher),
+        Err(error) => {
+            warn!(
+                "Could not monitor configuration file for changes: {}",
+                error
+            );
+            None
+        }
     };
 
-    let app = Router::new()
-        .route("/submit", post(submit))
+    // Aggregates that dashboards poll, answered with 304 Not Modified when
+    // nothing they are computed from has changed.
+    let cached = Router::new()
+        .route("/stats", get(stats::stats))
+        .route("/stats/categories", get(stats::categories))
+        .route("/stats/compare", get(stats::compare))
+        .route("/stats/devices", get(stats::devices))
+        .route("/stats/completion", get(stats::completion))
+        .route("/stats/histogram", get(stats::histogram))
+        .route("/stats/heatmap", get(stats::heatmap))
+        .route("/stats/records", get(stats::records))
+        .route("/stats/streaks", get(stats::streaks))
+        .route("/stats/year/:year", get(stats::year))
+        .route("/goals", get(goals::list))
+        .route("/summary", get(summary::summary))
+        .route_layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            etag::cache,
+        ))
+        .route(
+            "/leaderboard",
+            get(users::leaderboard).route_layer(middleware::from_fn_with_state(
+                shared_state.clone(),
+                etag::cache_everyone,
+            )),
+        )
+        .route(
+            "/public/stats",
+            get(stats::public).route_layer(middleware::from_fn_with_state(
+                shared_state.clone(),
+                etag::cache_public,
+            )),
+        )
+        .route(
+            "/badge/:file",
+            get(badge::badge).route_layer(middleware::from_fn_with_state(
+                shared_state.clone(),
+                etag::cache_public,
+            )),
+        );
+
+    // Endpoints that hit the database on behalf of clients.
+    let limited = Router::new()
+        .route(
+            "/submit",
+            post(submit).layer(DefaultBodyLimit::max(body_limits.submit)),
+        )
+        .route(
+            "/backfill",
+            post(backfill::backfill).layer(DefaultBodyLimit::max(body_limits.backfill)),
+        )
+        .route("/heartbeat", post(heartbeats::heartbeat))
+        .route("/now-playing", get(heartbeats::now_playing))
+        .route("/client-settings", get(client_settings))
+        .route("/events", get(events::events).post(events::create))
+        .route("/events/:id", patch(events::update).delete(events::delete))
+        .route("/events/:id/note", patch(events::note))
+        .route("/events/:id/changes", get(events::changes))
+        .route("/processes/:id/sessions", get(processes::sessions))
+        .route("/processes/:id/cover", get(covers::cover))
+        .route("/shares", get(shares::list).post(shares::create))
+        .route("/shares/:id", delete(shares::revoke))
+        .route("/share/:token", get(shares::view))
+        .route("/export/events.csv", get(export::events))
+        .route("/export/totals.csv", get(export::totals))
+        .route("/feed.ics", get(calendar::feed))
+        .route("/feed.atom", get(atom::atom))
+        .route("/graphql", post(graphql::graphql))
+        .route("/stream", get(stream::stream))
+        .route("/register", post(devices::register))
+        .merge(cached)
+        .route_layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            ratelimit::limit,
+        ));
+    let api = Router::new()
+        .route("/devices", get(devices::list))
+        .route("/devices/:id/approve", post(devices::approve))
+        .route("/devices/:id/revoke", post(devices::revoke))
+        .route("/processes", get(processes::list))
+        .route("/processes/:id/merge", post(processes::merge))
+        .route("/processes/duplicates", get(processes::duplicates))
+        .route(
+            "/processes/duplicates/:id",
+            post(processes::merge_duplicates),
+        )
+        .route(
+            "/processes/:id/categories",
+            get(processes::categories).put(processes::set_categories),
+        )
+        .route("/processes/:id/export", put(processes::set_export))
+        .route("/processes/:id/hidden", put(processes::set_hidden))
+        .route(
+            "/processes/:id/cover",
+            put(covers::upload).delete(covers::delete),
+        )
+        .route(
+            "/aliases",
+            get(processes::list_aliases).post(processes::add_alias),
+        )
+        .route("/aliases/:id", delete(processes::delete_alias))
+        .route("/backup", get(backup::backup))
+        .route(
+            "/restore",
+            post(backup::restore).layer(DefaultBodyLimit::max(body_limits.restore)),
+        )
+        .route("/audit", get(audit::list))
+        .merge(limited);
+    let mut app = Router::new()
+        .route("/", get(dashboard::index))
+        .route("/health", get(health::health))
+        .route("/ready", get(health::ready))
+        .route(shared::API_VERSION_PATH, get(api_version))
+        .merge(SwaggerUi::new("/swagger-ui").url("/openapi.json", openapi::ApiDoc::openapi()))
+        .nest(shared::API_PREFIX, api.clone())
+        .merge(api.route_layer(middleware::from_fn(deprecated_path)))
+        // gRPC clients call the methods at the root, by the name of the
+        // service, and are limited like the REST endpoints they stand in for.
+        .merge(
+            Router::new()
+                .route_service(grpc::PATH, grpc::service(shared_state.clone()))
+                .route_layer(middleware::from_fn_with_state(
+                    shared_state.clone(),
+                    ratelimit::limit,
+                )),
+        );
+    if expose_metrics {
+        app = app.route("/metrics", get(metrics::metrics));
+    }
+    let db = shared_state.db.clone();
+    let shutdown = shared_state.shutdown.clone();
+    let app = app
+        .route_layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            metrics::track_latency,
+        ))
+        .route_layer(middleware::from_fn(telemetry::trace))
+        // Limits apply to decompressed bodies, which a small compressed body
+        // can't get around.
+        .layer(DefaultBodyLimit::max(body_limits.default))
+        .layer(middleware::map_response(payload_too_large))
+        .layer(middleware::map_response(error_body))
+        .layer(RequestDecompressionLayer::new())
+        // Signatures are made over the body as it was sent.
+        .layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            signing::verify,
+        ))
+        // Stats and exports compress well. Event streams and small responses
+        // are left as they are.
+        .layer(CompressionLayer::new())
+        .layer(
+            TraceLayer::new_for_http()
+                .on_response(
+                    |response: &axum::response::Response, latency: Duration, _: &tracing::Span| {
+                        debug!("{} in {} ms", response.status(), latency.as_millis());
+                    },
+                )
+                // Handlers log their own errors.
+                .on_failure(()),
+        )
+        .layer(middleware::from_fn(request_id::scope))
+        .layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            proxies::resolve,
+        ))
+        .layer(PropagateRequestIdLayer::new(request_id::REQUEST_ID_HEADER))
+        .layer(SetRequestIdLayer::new(
+            request_id::REQUEST_ID_HEADER,
+            MakeRequestUuid,
+        ))
         .with_state(shared_state);
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await.unwrap();
-    info!("Launching server");
-    axum::serve(listener, app).await.unwrap();
+    match tls_config {
+        Some(tls_config) => {
+            info!("Launching server with TLS on {}", address);
+            let tls_config = RustlsConfig::from_config(Arc::new(tls_config));
+            let handle = axum_server::Handle::new();
+            let stopping = handle.clone();
+            tokio::spawn(async move {
+                shutdown.requested().await;
+                stopping.graceful_shutdown(Some(shutdown::DRAIN_TIMEOUT));
+            });
+            axum_server::bind_rustls(address, tls_config)
+                .handle(handle)
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .unwrap();
+        }
+        None => {
+            let listener = match tokio::net::TcpListener::bind(address).await {
+                Ok(listener) => listener,
+                Err(error) => {
+                    error!("Could not listen on {}: {}", address, error);
+                    return;
+                }
+            };
+            info!("Launching server on {}", address);
+            let server = axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown.clone().requested());
+            let drained = async {
+                shutdown.requested().await;
+                tokio::time::sleep(shutdown::DRAIN_TIMEOUT).await;
+            };
+            tokio::select! {
+                result = server.into_future() => result.unwrap(),
+                _ = drained => warn!(
+                    "Requests still in progress after {} seconds, stopping anyway",
+                    shutdown::DRAIN_TIMEOUT.as_secs()
+                ),
+            }
+        }
+    }
+    db.close();
+    info!("Server stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    pub(crate) const CONFIG: &str = "
+dbUrl: \"sqlite://:memory:\"
+secret: secret
+";
+
+    /// State whose handlers record sessions in the storage. The database is
+    /// never connected to, so the handlers under test must not use it.
+    pub(crate) fn state(config: &str, storage: Arc<MemoryStorage>) -> AppState {
+        let mut config: config::Config = serde_yaml::from_str(config).unwrap();
+        // Deserialising leaves the secret of `CONFIG` unresolved.
+        config.secret = Some(config::Secret::Plain("secret".to_string()));
+        let db = db::Database::connect(&config.db_url, &config.database_pool).unwrap();
+        let user_ids = users::UserIds::new();
+        let timezones = Arc::new(timezones::Timezones::new(&config, &user_ids));
+        AppState {
+            config: Arc::new(RwLock::new(config)),
+            graphql: graphql::schema(db.clone(), timezones.clone()),
+            live: broadcast::channel(stream::CAPACITY).0,
+            db,
+            metrics: Arc::new(metrics::Metrics::default()),
+            rate_limiter: Arc::new(ratelimit::RateLimiter::default()),
+            shutdown: shutdown::Shutdown::new(),
+            signatures: Arc::new(signing::Signatures::default()),
+            storage,
+            timezones,
+            users: Arc::new(user_ids),
+            time_trackers: Arc::new(timetrackers::TimeTrackers::new(Vec::new()).unwrap()),
+            webhooks: Arc::new(webhooks::Webhooks::new(Vec::new(), Vec::new()).unwrap()),
+        }
+    }
+
+    pub(crate) fn headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(shared::SECRET_KEY_HEADER, "secret".parse().unwrap());
+        headers
+    }
+
+    pub(crate) fn submission(name: &str, ended_at: &str) -> shared::Submission {
+        serde_json::from_value(serde_json::json!({
+            "duration": 3600,
+            "executable": "eldenring.exe",
+            "name": name,
+            "ended_at": ended_at,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn retried_submission_is_saved_once() {
+        let storage = Arc::new(MemoryStorage::default());
+        let state = state(CONFIG, storage.clone());
+        let mut headers = headers();
+        headers.insert(shared::IDEMPOTENCY_KEY_HEADER, "8d6a3f1e".parse().unwrap());
+        let mut statuses = Vec::new();
+        for _ in 0..2 {
+            let payload = Ok(Json(submission("ELDEN RING", "2024-03-01T12:00:00Z")));
+            let (status, Json(response)) =
+                submit(State(state.clone()), headers.clone(), payload).await;
+            assert_eq!(status, StatusCode::CREATED);
+            statuses.push(response.status);
+        }
+        assert_eq!(
+            statuses,
+            [
+                shared::SubmissionResponseStatus::Ok,
+                shared::SubmissionResponseStatus::Duplicate
+            ]
+        );
+        assert_eq!(storage.events.lock().unwrap().len(), 1);
+        assert_eq!(storage.audit_log.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn submission_uses_the_name_case_of_the_process() {
+        let config = format!("{}nameNormalization:\n  caseInsensitive: true\n", CONFIG);
+        let storage = Arc::new(MemoryStorage::default());
+        let state = state(&config, storage.clone());
+        for (name, ended_at) in [
+            ("Elden Ring", "2024-03-01T12:00:00Z"),
+            ("ELDEN RING", "2024-03-02T12:00:00Z"),
+        ] {
+            let payload = Ok(Json(submission(name, ended_at)));
+            let (status, _) = submit(State(state.clone()), headers(), payload).await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+        let events = storage.events.lock().unwrap();
+        let names = events
+            .iter()
+            .map(|event| event.name.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(names, [Some("Elden Ring"), Some("Elden Ring")]);
+    }
 }
@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{db::Database, AppState};
+
+/// Upper bounds of the request latency histogram buckets in seconds.
+const LATENCY_BUCKETS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+/// Counters exposed at `/metrics` in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    submissions: AtomicU64,
+    rejections: Mutex<BTreeMap<&'static str, u64>>,
+    latencies: Mutex<BTreeMap<(String, String), Histogram>>,
+}
+
+impl Metrics {
+    pub fn submission_saved(&self) {
+        self.submissions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn submission_rejected(&self, reason: &'static str) {
+        if let Ok(mut rejections) = self.rejections.lock() {
+            *rejections.entry(reason).or_default() += 1;
+        }
+    }
+
+    fn observe_request(&self, path: String, method: String, seconds: f64) {
+        if let Ok(mut latencies) = self.latencies.lock() {
+            latencies
+                .entry((path, method))
+                .or_default()
+                .observe(seconds);
+        }
+    }
+
+    fn render(&self, db: &Database) -> String {
+        let mut output = String::new();
+        let _ = writeln!(
+            output,
+            "# HELP beelzebub_submissions_total Submissions saved to the database.\n\
+             # TYPE beelzebub_submissions_total counter\n\
+             beelzebub_submissions_total {}",
+            self.submissions.load(Ordering::Relaxed)
+        );
+
+        let _ = writeln!(
+            output,
+            "# HELP beelzebub_submission_rejections_total Submissions that were not saved.\n\
+             # TYPE beelzebub_submission_rejections_total counter"
+        );
+        if let Ok(rejections) = self.rejections.lock() {
+            for (reason, count) in rejections.iter() {
+                let _ = writeln!(
+                    output,
+                    "beelzebub_submission_rejections_total{{reason=\"{}\"}} {}",
+                    reason, count
+                );
+            }
+        }
+
+        let status = db.status();
+        let _ = writeln!(
+            output,
+            "# HELP beelzebub_db_pool_connections Database connections in the pool.\n\
+             # TYPE beelzebub_db_pool_connections gauge\n\
+             beelzebub_db_pool_connections{{state=\"available\"}} {}\n\
+             beelzebub_db_pool_connections{{state=\"in_use\"}} {}\n\
+             # HELP beelzebub_db_pool_max_connections Maximum size of the database pool.\n\
+             # TYPE beelzebub_db_pool_max_connections gauge\n\
+             beelzebub_db_pool_max_connections {}\n\
+             # HELP beelzebub_db_pool_waiting Requests waiting for a database connection.\n\
+             # TYPE beelzebub_db_pool_waiting gauge\n\
+             beelzebub_db_pool_waiting {}",
+            status.available,
+            status.size - status.available,
+            status.max_size,
+            status.waiting
+        );
+
+        let _ = writeln!(
+            output,
+            "# HELP beelzebub_request_duration_seconds Time spent handling requests.\n\
+             # TYPE beelzebub_request_duration_seconds histogram"
+        );
+        if let Ok(latencies) = self.latencies.lock() {
+            for ((path, method), histogram) in latencies.iter() {
+                let labels = format!("path=\"{}\",method=\"{}\"", path, method);
+                for (count, bound) in histogram.buckets.iter().zip(LATENCY_BUCKETS) {
+                    let _ = writeln!(
+                        output,
+                        "beelzebub_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                        labels, bound, count
+                    );
+                }
+                let _ = writeln!(
+                    output,
+                    "beelzebub_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}\n\
+                     beelzebub_request_duration_seconds_sum{{{}}} {}\n\
+                     beelzebub_request_duration_seconds_count{{{}}} {}",
+                    labels, histogram.count, labels, histogram.sum, labels, histogram.count
+                );
+            }
+        }
+        output
+    }
+}
+
+/// Record how long each request took, by route.
+pub async fn track_latency(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = request.method().to_string();
+    let start = Instant::now();
+    let response = next.run(request).await;
+    state
+        .metrics
+        .observe_request(path, method, start.elapsed().as_secs_f64());
+    response
+}
+
+pub async fn metrics(State(state): State<AppState>) -> String {
+    state.metrics.render(&state.db)
+}
@@ -0,0 +1,102 @@
+use utoipa::{
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+
+use crate::{
+    backfill, covers, devices, events, goals, health, heartbeats, processes, shares, stats,
+    summary, users,
+};
+
+/// OpenAPI description of the endpoints used by clients and dashboards.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Beelzebub"),
+    paths(
+        crate::submit,
+        backfill::backfill,
+        heartbeats::heartbeat,
+        crate::client_settings,
+        devices::register,
+        stats::stats,
+        stats::categories,
+        stats::compare,
+        stats::devices,
+        stats::completion,
+        stats::histogram,
+        stats::heatmap,
+        stats::records,
+        stats::streaks,
+        stats::year,
+        stats::public,
+        summary::summary,
+        goals::list,
+        events::events,
+        events::create,
+        events::update,
+        events::note,
+        events::delete,
+        events::changes,
+        processes::sessions,
+        covers::cover,
+        shares::create,
+        shares::list,
+        shares::revoke,
+        shares::view,
+        users::leaderboard,
+        heartbeats::now_playing,
+        health::health,
+        health::ready,
+        crate::api_version,
+    ),
+    // Only referenced from query parameters, which doesn't add the schema.
+    components(schemas(
+        shared::SummaryPeriod,
+        shared::SortOrder,
+        shared::StatsGrouping,
+        shared::ApiError
+    )),
+    modifiers(&Security, &Prefix),
+    security(("secret" = []), ("device" = [])),
+)]
+pub struct ApiDoc;
+
+/// Ways to authenticate: the secret or an API key in `X-Secret-Key`, or the
+/// token of an approved device.
+struct Security;
+
+impl Modify for Security {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "secret",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(shared::SECRET_KEY_HEADER))),
+        );
+        components.add_security_scheme(
+            "device",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+/// Paths that stay where they are rather than moving under `/api/v1`.
+const UNVERSIONED_PATHS: [&str; 3] = ["/health", "/ready", "/api/version"];
+
+/// Endpoints given by their path under `/api/v1`, where the server has them.
+struct Prefix;
+
+impl Modify for Prefix {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let paths = std::mem::take(&mut openapi.paths.paths);
+        openapi.paths.paths = paths
+            .into_iter()
+            .map(|(path, item)| {
+                if UNVERSIONED_PATHS.contains(&path.as_str()) {
+                    (path, item)
+                } else {
+                    (format!("{}{}", shared::API_PREFIX, path), item)
+                }
+            })
+            .collect();
+    }
+}
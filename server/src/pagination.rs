@@ -0,0 +1,21 @@
+use std::cmp::Ordering;
+
+use axum::http::StatusCode;
+
+use crate::util;
+
+/// Field a list can be sorted by, as its name in `sort` and the comparison.
+pub type Sort<T> = (&'static str, fn(&T, &T) -> Ordering);
+
+pub use shared::pagination::limit;
+
+/// Page of a list that is small enough to load whole, such as devices. The
+/// first sort is the default. Bad requests are an unknown sort or a page that
+/// is not a `nextPage`.
+pub fn paginate<T>(
+    items: Vec<T>,
+    query: &shared::ListQuery,
+    sorts: &[Sort<T>],
+) -> Result<shared::Page<T>, StatusCode> {
+    util::paginate(items, query, sorts, limit(query.limit) as usize).ok_or(StatusCode::BAD_REQUEST)
+}
@@ -0,0 +1,68 @@
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, Request},
+    http::{
+        header::{ACCEPT, CONTENT_TYPE},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Json,
+};
+use log::{error, warn};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::util;
+
+fn is_msgpack(header: Option<&HeaderValue>) -> bool {
+    header
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| util::has_media_type(value, shared::MSGPACK_CONTENT_TYPE))
+}
+
+/// Body of a request as JSON or, if that is its content type, MessagePack.
+/// Bodies that aren't valid MessagePack are refused with 400 and ones that
+/// don't match the type with 422, like JSON bodies.
+pub struct Payload<T>(pub T);
+
+#[async_trait]
+impl<T: DeserializeOwned, S: Send + Sync> FromRequest<S> for Payload<T> {
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if !is_msgpack(request.headers().get(CONTENT_TYPE)) {
+            let Json(value) = Json::from_request(request, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            return Ok(Payload(value));
+        }
+        let body = Bytes::from_request(request, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        shared::msgpack::decode(&body)
+            .map(Payload)
+            .map_err(|error| {
+                warn!("Rejected MessagePack body: {}", error);
+                let status = match error {
+                    shared::msgpack::Error::Data(_) => StatusCode::UNPROCESSABLE_ENTITY,
+                    _ => StatusCode::BAD_REQUEST,
+                };
+                (status, error.to_string()).into_response()
+            })
+    }
+}
+
+/// Response with the value as MessagePack if the request accepts it, and as
+/// JSON otherwise.
+pub fn respond<T: Serialize>(headers: &HeaderMap, value: T) -> Response {
+    if !is_msgpack(headers.get(ACCEPT)) {
+        return Json(value).into_response();
+    }
+    match shared::msgpack::encode(&value) {
+        Ok(body) => ([(CONTENT_TYPE, shared::MSGPACK_CONTENT_TYPE)], body).into_response(),
+        Err(error) => {
+            error!("Could not encode response as MessagePack: {}", error);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
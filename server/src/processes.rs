@@ -0,0 +1,556 @@
+use std::cmp::Ordering;
+
+use async_graphql::SimpleObject;
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use diesel::Queryable;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    admin::ProcessSummary,
+    audit, authorize,
+    config::Scope,
+    db,
+    devices::{admin, is_admin},
+    pagination, util, AppState,
+};
+
+/// Executable and name that is recorded as another process.
+#[derive(Deserialize, Queryable, Serialize, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct Alias {
+    pub id: i32,
+    pub executable: String,
+    pub name: Option<String>,
+    pub process: i32,
+}
+
+#[derive(Deserialize)]
+pub struct NewAlias {
+    executable: String,
+    name: Option<String>,
+    process: i32,
+}
+
+#[derive(Deserialize)]
+pub struct ProcessesQuery {
+    /// Only list processes whose executable or name contains this text.
+    search: Option<String>,
+
+    /// Only list processes whose executable or name roughly matches this
+    /// text, best match first unless sorted otherwise.
+    q: Option<String>,
+}
+
+/// Lowest match of the executable or name for a fuzzy search to find the
+/// process.
+const FUZZY_THRESHOLD: f64 = 0.5;
+
+/// Sort that keeps the processes in the order they are found in.
+const RELEVANCE_SORT: [pagination::Sort<ProcessSummary>; 1] =
+    [("relevance", |_, _| Ordering::Equal)];
+
+/// Ways to sort the list of processes, by ID by default.
+const PROCESS_SORTS: [pagination::Sort<ProcessSummary>; 4] = [
+    ("id", |a, b| a.id.cmp(&b.id)),
+    ("name", |a, b| {
+        let a = a.name.as_ref().unwrap_or(&a.executable);
+        a.cmp(b.name.as_ref().unwrap_or(&b.executable))
+    }),
+    ("sessions", |a, b| a.sessions.cmp(&b.sessions)),
+    ("duration", |a, b| a.duration.cmp(&b.duration)),
+];
+
+/// Ways to sort the sessions of a process, by end time by default.
+const SESSION_SORTS: [pagination::Sort<shared::Event>; 3] = [
+    ("endedAt", |a, b| {
+        (a.ended_at, a.id).cmp(&(b.ended_at, b.id))
+    }),
+    ("startedAt", |a, b| {
+        (a.started_at, a.id).cmp(&(b.started_at, b.id))
+    }),
+    ("duration", |a, b| {
+        (a.duration, a.id).cmp(&(b.duration, b.id))
+    }),
+];
+
+#[derive(Deserialize)]
+pub struct MergeQuery {
+    /// Process that gets the sessions.
+    into: i32,
+}
+
+#[derive(Serialize)]
+pub struct MergeResponse {
+    /// Number of sessions moved.
+    moved: usize,
+}
+
+/// Processes that are likely the same game, to be merged into the most
+/// played of them.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeSuggestion {
+    /// IDs of the processes, which confirm the merge while it is still
+    /// suggested.
+    pub id: String,
+
+    /// Process that gets the sessions.
+    pub into: ProcessSummary,
+
+    /// Processes to merge and delete.
+    pub merge: Vec<ProcessSummary>,
+}
+
+/// Likely duplicates among the processes, most played first.
+pub async fn suggestions(db: &db::Database) -> Result<Vec<MergeSuggestion>, db::Error> {
+    let mut processes = db
+        .list_processes(None)
+        .await?
+        .into_iter()
+        .map(Some)
+        .collect::<Vec<_>>();
+    let names = processes
+        .iter()
+        .flatten()
+        .map(|process| (process.executable.clone(), process.name.clone()))
+        .collect::<Vec<_>>();
+    let names = names
+        .iter()
+        .map(|(executable, name)| (executable.as_str(), name.as_deref()))
+        .collect::<Vec<_>>();
+    let mut suggestions = util::duplicate_groups(&names)
+        .into_iter()
+        .map(|group| {
+            let mut group = group
+                .into_iter()
+                .filter_map(|index| processes[index].take())
+                .collect::<Vec<_>>();
+            group.sort_by(|a, b| b.duration.cmp(&a.duration).then(a.id.cmp(&b.id)));
+            let mut ids = group.iter().map(|process| process.id).collect::<Vec<_>>();
+            ids.sort();
+            let id = ids.iter().map(i32::to_string).collect::<Vec<_>>().join("-");
+            let into = group.remove(0);
+            MergeSuggestion {
+                id,
+                into,
+                merge: group,
+            }
+        })
+        .collect::<Vec<_>>();
+    suggestions.sort_by_key(|suggestion| {
+        let duration = suggestion.merge.iter().map(|process| process.duration);
+        std::cmp::Reverse(suggestion.into.duration + duration.sum::<u64>())
+    });
+    Ok(suggestions)
+}
+
+/// Merge the processes of the suggestion with the ID, recording each merge
+/// in the audit log. Returns the number of sessions moved, or None if the
+/// merge is no longer suggested.
+pub async fn merge_suggestion(
+    db: &db::Database,
+    actor: &str,
+    id: &str,
+) -> Result<Option<usize>, db::Error> {
+    let Some(suggestion) = suggestions(db)
+        .await?
+        .into_iter()
+        .find(|suggestion| suggestion.id == id)
+    else {
+        return Ok(None);
+    };
+    let mut total = 0;
+    for process in &suggestion.merge {
+        let Some(moved) = db.merge_processes(process.id, suggestion.into.id).await? else {
+            continue;
+        };
+        info!(
+            "Merged process {} into {}, moving {} events",
+            process.id, suggestion.into.id, moved
+        );
+        let target = Some(format!("process {}", process.id));
+        let details = Some(format!(
+            "into process {}, moving {} events",
+            suggestion.into.id, moved
+        ));
+        audit::record(db, actor, audit::Action::MergeProcess, target, details).await;
+        total += moved;
+    }
+    Ok(Some(total))
+}
+
+/// Processes with the totals of their sessions, like `admin list-processes`.
+pub async fn list(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(filters): Query<ProcessesQuery>,
+    Query(query): Query<shared::ListQuery>,
+) -> Result<Json<shared::Page<ProcessSummary>>, StatusCode> {
+    if !is_admin(&headers, &state) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let processes = match state.db.list_processes(filters.search).await {
+        Ok(processes) => processes,
+        Err(error) => {
+            error!("Could not list processes: {}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let sorts = sorts(filters.q.is_some(), &query);
+    let processes = match filters.q {
+        Some(text) => fuzzy_search(processes, &text),
+        None => processes,
+    };
+    Ok(Json(pagination::paginate(processes, &query, sorts)?))
+}
+
+/// Ways to sort the list of processes for the query. Fuzzy searches keep the
+/// best match first unless a sort is given.
+pub fn sorts(
+    fuzzy: bool,
+    query: &shared::ListQuery,
+) -> &'static [pagination::Sort<ProcessSummary>] {
+    if fuzzy && query.sort.is_none() {
+        &RELEVANCE_SORT
+    } else {
+        &PROCESS_SORTS
+    }
+}
+
+/// Processes whose executable or name roughly match the text, best match
+/// first.
+pub fn fuzzy_search(processes: Vec<ProcessSummary>, text: &str) -> Vec<ProcessSummary> {
+    let mut matches = processes
+        .into_iter()
+        .filter_map(|process| {
+            let name = process.name.as_deref().unwrap_or_default();
+            let score =
+                util::fuzzy_match(text, &process.executable).max(util::fuzzy_match(text, name));
+            (score >= FUZZY_THRESHOLD).then_some((score, process))
+        })
+        .collect::<Vec<_>>();
+    matches.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    matches.into_iter().map(|(_, process)| process).collect()
+}
+
+/// Individual sessions of a process, such as for the page of a game.
+#[utoipa::path(
+    get,
+    path = "/processes/{id}/sessions",
+    params(("id" = i32, Path, description = "ID of the process"), shared::ListQuery),
+    responses(
+        (status = 200, description = "Page of sessions", body = shared::Page<shared::Event>),
+        (status = 400, description = "Invalid sort or page"),
+        (status = 401, description = "Not authenticated"),
+        (status = 404, description = "No such process"),
+    ),
+)]
+pub async fn sessions(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Query(query): Query<shared::ListQuery>,
+) -> Result<Json<shared::Page<shared::Event>>, StatusCode> {
+    let Some(caller) = authorize(&headers, &state, Scope::Read).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    match state.db.process_sessions(id, caller.user_id).await {
+        Ok(Some(sessions)) => Ok(Json(pagination::paginate(
+            sessions,
+            &query,
+            &SESSION_SORTS,
+        )?)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(error) => {
+            error!("Could not query sessions of process {}: {}", id, error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Move the sessions, aliases and categories of a process to another process
+/// and delete it. The executable and name of the deleted process become an alias, so
+/// later sessions are recorded for the other process too.
+pub async fn merge(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(process_id): Path<i32>,
+    Query(query): Query<MergeQuery>,
+) -> Result<Json<MergeResponse>, StatusCode> {
+    let Some(actor) = admin(&headers, &state) else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    if process_id == query.into {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    match state.db.merge_processes(process_id, query.into).await {
+        Ok(Some(moved)) => {
+            info!(
+                "Merged process {} into {}, moving {} events",
+                process_id, query.into, moved
+            );
+            let target = Some(format!("process {}", process_id));
+            let details = Some(format!(
+                "into process {}, moving {} events",
+                query.into, moved
+            ));
+            audit::record(
+                &state.db,
+                &actor,
+                audit::Action::MergeProcess,
+                target,
+                details,
+            )
+            .await;
+            Ok(Json(MergeResponse { moved }))
+        }
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(error) => {
+            error!("Could not merge process {}: {}", process_id, error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Processes that are likely duplicates of each other, such as the same
+/// executable under different names or names with different versions.
+pub async fn duplicates(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<MergeSuggestion>>, StatusCode> {
+    if !is_admin(&headers, &state) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    match suggestions(&state.db).await {
+        Ok(suggestions) => Ok(Json(suggestions)),
+        Err(error) => {
+            error!("Could not look for duplicate processes: {}", error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Apply a suggested merge of duplicate processes.
+pub async fn merge_duplicates(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<MergeResponse>, StatusCode> {
+    let Some(actor) = admin(&headers, &state) else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    match merge_suggestion(&state.db, &actor, &id).await {
+        Ok(Some(moved)) => Ok(Json(MergeResponse { moved })),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(error) => {
+            error!("Could not merge duplicates {}: {}", id, error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn list_aliases(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<Alias>>, StatusCode> {
+    if !is_admin(&headers, &state) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    match state.db.list_aliases().await {
+        Ok(aliases) => Ok(Json(aliases)),
+        Err(error) => {
+            error!("Could not list aliases: {}", error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Record sessions of the executable and name for the process from now on.
+/// An existing alias for the same executable and name is replaced.
+pub async fn add_alias(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<NewAlias>,
+) -> Result<(StatusCode, Json<Alias>), StatusCode> {
+    let Some(actor) = admin(&headers, &state) else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let NewAlias {
+        executable,
+        name,
+        process,
+    } = payload;
+    let result = state
+        .db
+        .add_alias(executable.clone(), name.clone(), process)
+        .await;
+    match result {
+        Ok(Some(alias_id)) => {
+            info!("Added alias {} for process {}", executable, process);
+            let target = Some(format!("alias {}", alias_id));
+            let details = Some(match &name {
+                Some(name) => format!("{} ({}) as process {}", executable, name, process),
+                None => format!("{} as process {}", executable, process),
+            });
+            audit::record(&state.db, &actor, audit::Action::AddAlias, target, details).await;
+            let alias = Alias {
+                id: alias_id,
+                executable,
+                name,
+                process,
+            };
+            Ok((StatusCode::CREATED, Json(alias)))
+        }
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(error) => {
+            error!("Could not add alias {}: {}", executable, error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn delete_alias(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(alias_id): Path<i32>,
+) -> StatusCode {
+    let Some(actor) = admin(&headers, &state) else {
+        return StatusCode::UNAUTHORIZED;
+    };
+    match state.db.delete_alias(alias_id).await {
+        Ok(0) => StatusCode::NOT_FOUND,
+        Ok(_) => {
+            info!("Deleted alias {}", alias_id);
+            let target = Some(format!("alias {}", alias_id));
+            audit::record(&state.db, &actor, audit::Action::DeleteAlias, target, None).await;
+            StatusCode::NO_CONTENT
+        }
+        Err(error) => {
+            error!("Could not delete alias {}: {}", alias_id, error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+pub async fn categories(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(process_id): Path<i32>,
+) -> Result<Json<Vec<String>>, StatusCode> {
+    if !is_admin(&headers, &state) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    match state.db.process_categories(process_id).await {
+        Ok(categories) => Ok(Json(categories)),
+        Err(error) => {
+            error!(
+                "Could not list categories of process {}: {}",
+                process_id, error
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Replace the categories of a process, such as its genres.
+pub async fn set_categories(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(process_id): Path<i32>,
+    Json(categories): Json<Vec<String>>,
+) -> StatusCode {
+    let Some(actor) = admin(&headers, &state) else {
+        return StatusCode::UNAUTHORIZED;
+    };
+    let categories = util::clean_categories(categories);
+    let details = Some(categories.join(", "));
+    match state.db.set_categories(process_id, categories).await {
+        Ok(true) => {
+            info!("Updated the categories of process {}", process_id);
+            let target = Some(format!("process {}", process_id));
+            audit::record(
+                &state.db,
+                &actor,
+                audit::Action::SetCategories,
+                target,
+                details,
+            )
+            .await;
+            StatusCode::NO_CONTENT
+        }
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(error) => {
+            error!(
+                "Could not set categories of process {}: {}",
+                process_id, error
+            );
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Include the process in `/public/stats` or leave it out.
+pub async fn set_export(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(process_id): Path<i32>,
+    Json(export): Json<bool>,
+) -> StatusCode {
+    let Some(actor) = admin(&headers, &state) else {
+        return StatusCode::UNAUTHORIZED;
+    };
+    match state.db.set_export(process_id, export).await {
+        Ok(0) => StatusCode::NOT_FOUND,
+        Ok(_) => {
+            info!(
+                "{} process {} in the public stats",
+                if export { "Included" } else { "Excluded" },
+                process_id
+            );
+            let target = Some(format!("process {}", process_id));
+            let details = Some(export.to_string());
+            audit::record(&state.db, &actor, audit::Action::SetExport, target, details).await;
+            StatusCode::NO_CONTENT
+        }
+        Err(error) => {
+            error!("Could not update process {}: {}", process_id, error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Hide the process from stats, such as a launcher that slipped through the
+/// filters of the client, or show it again. Its sessions are kept.
+pub async fn set_hidden(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(process_id): Path<i32>,
+    Json(hidden): Json<bool>,
+) -> StatusCode {
+    let Some(actor) = admin(&headers, &state) else {
+        return StatusCode::UNAUTHORIZED;
+    };
+    match state.db.set_hidden(process_id, hidden).await {
+        Ok(0) => StatusCode::NOT_FOUND,
+        Ok(_) => {
+            info!(
+                "{} process {} in the stats",
+                if hidden { "Hid" } else { "Showed" },
+                process_id
+            );
+            let target = Some(format!("process {}", process_id));
+            let details = Some(hidden.to_string());
+            audit::record(&state.db, &actor, audit::Action::SetHidden, target, details).await;
+            StatusCode::NO_CONTENT
+        }
+        Err(error) => {
+            error!("Could not update process {}: {}", process_id, error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
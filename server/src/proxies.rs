@@ -0,0 +1,203 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header::FORWARDED, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use log::error;
+
+use crate::{config::Network, AppState};
+
+tokio::task_local! {
+    static CLIENT_ADDRESS: IpAddr;
+}
+
+fn trusted_proxies(state: &AppState) -> Vec<Network> {
+    match state.config.read() {
+        Ok(config) => config.trusted_proxies.clone(),
+        Err(_) => {
+            error!("Could not read configuration, trusting no proxies");
+            Vec::new()
+        }
+    }
+}
+
+/// Every value of the header joined into one list.
+fn joined(headers: &HeaderMap, name: impl axum::http::header::AsHeaderName) -> Option<String> {
+    let values = headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>();
+    (!values.is_empty()).then(|| values.join(","))
+}
+
+/// Handle the rest of the request with the address of the client available
+/// to logging, rate limiting and the audit log. Behind trusted proxies that
+/// is the address they forwarded the request for.
+pub async fn resolve(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let trusted = trusted_proxies(&state);
+    let address = if trusted.is_empty() {
+        peer.ip().to_canonical()
+    } else {
+        let headers = request.headers();
+        let forwarded = forwarded_addresses(
+            joined(headers, FORWARDED).as_deref(),
+            joined(headers, "x-forwarded-for").as_deref(),
+        );
+        original_address(peer.ip(), &forwarded, &trusted)
+    };
+    CLIENT_ADDRESS.scope(address, next.run(request)).await
+}
+
+/// Address of the client of the request being handled, `None` outside of
+/// requests.
+pub fn client_address() -> Option<IpAddr> {
+    CLIENT_ADDRESS.try_with(|address| *address).ok()
+}
+
+/// Whether the address is in the network, with IPv4 addresses mapped to IPv6
+/// treated as IPv4.
+fn in_network(network: &Network, address: IpAddr) -> bool {
+    return match (network.address, address.to_canonical()) {
+        (IpAddr::V4(network_address), IpAddr::V4(address)) => {
+            let mask = u32::MAX
+                .checked_shl(32 - network.prefix as u32)
+                .unwrap_or(0);
+            u32::from(network_address) & mask == u32::from(address) & mask
+        }
+        (IpAddr::V6(network_address), IpAddr::V6(address)) => {
+            let mask = u128::MAX
+                .checked_shl(128 - network.prefix as u32)
+                .unwrap_or(0);
+            u128::from(network_address) & mask == u128::from(address) & mask
+        }
+        _ => false,
+    };
+}
+
+/// Address in `Forwarded` or `X-Forwarded-For`, which may be quoted or have
+/// a port. `None` for obfuscated identifiers such as `unknown`.
+fn forwarded_address(text: &str) -> Option<IpAddr> {
+    let text = text.trim().trim_matches('"');
+    if let Ok(address) = text.parse::<IpAddr>() {
+        return Some(address);
+    }
+    if let Ok(address) = text.parse::<SocketAddr>() {
+        return Some(address.ip());
+    }
+    return text.strip_prefix('[')?.strip_suffix(']')?.parse().ok();
+}
+
+/// Addresses the request was forwarded for, from the original client to the
+/// one the last proxy got it from, taken from `Forwarded` if there is one and
+/// from `X-Forwarded-For` otherwise. Addresses that can't be read are `None`.
+fn forwarded_addresses(
+    forwarded: Option<&str>,
+    x_forwarded_for: Option<&str>,
+) -> Vec<Option<IpAddr>> {
+    if let Some(forwarded) = forwarded {
+        return forwarded
+            .split(',')
+            .map(|element| {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                    .and_then(|(_, value)| forwarded_address(value))
+            })
+            .collect();
+    }
+    return x_forwarded_for
+        .map(|header| header.split(',').map(forwarded_address).collect())
+        .unwrap_or_default();
+}
+
+/// Address of the client: the peer unless it is a trusted proxy, in which
+/// case the address it forwarded the request for, and so on for as long as
+/// the proxies are trusted. Addresses added by untrusted hops can be forged,
+/// so they aren't looked past.
+fn original_address(
+    peer: IpAddr,
+    forwarded: &[Option<IpAddr>],
+    trusted_proxies: &[Network],
+) -> IpAddr {
+    let mut client = peer.to_canonical();
+    for address in forwarded.iter().rev() {
+        if !trusted_proxies
+            .iter()
+            .any(|network| in_network(network, client))
+        {
+            break;
+        }
+        match address {
+            Some(address) => client = address.to_canonical(),
+            None => break,
+        }
+    }
+    return client;
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use crate::util;
+
+    #[test_case("10.0.0.0/8", "10.20.30.40", true; "inside")]
+    #[test_case("10.0.0.0/8", "11.0.0.1", false; "outside")]
+    #[test_case("0.0.0.0/0", "203.0.113.5", true; "everything")]
+    #[test_case("10.0.0.0/8", "::ffff:10.0.0.1", true; "mapped ipv4")]
+    #[test_case("fd00::/8", "fd12::1", true; "ipv6")]
+    #[test_case("10.0.0.0/8", "fd12::1", false; "other family")]
+    fn in_network(network: &str, address: &str, output: bool) {
+        let network = util::parse_network(network).unwrap();
+        assert_eq!(
+            super::in_network(&network, address.parse().unwrap()),
+            output
+        );
+    }
+
+    #[test_case(None, Some("203.0.113.5, 10.0.0.2"), vec![Some("203.0.113.5"), Some("10.0.0.2")]; "x-forwarded-for")]
+    #[test_case(Some("for=192.0.2.60;proto=http, For=\"[2001:db8::1]:4711\""), Some("10.9.9.9"), vec![Some("192.0.2.60"), Some("2001:db8::1")]; "forwarded")]
+    #[test_case(Some("for=unknown, for=\"192.0.2.43:47011\""), None, vec![None, Some("192.0.2.43")]; "obfuscated")]
+    #[test_case(None, None, vec![]; "neither")]
+    fn forwarded_addresses(
+        forwarded: Option<&str>,
+        x_forwarded_for: Option<&str>,
+        output: Vec<Option<&str>>,
+    ) {
+        let output = output
+            .into_iter()
+            .map(|address| address.map(|address| address.parse().unwrap()))
+            .collect::<Vec<Option<std::net::IpAddr>>>();
+        assert_eq!(
+            super::forwarded_addresses(forwarded, x_forwarded_for),
+            output
+        );
+    }
+
+    #[test_case("10.0.0.2", vec![Some("203.0.113.5")], "203.0.113.5"; "trusted proxy")]
+    #[test_case("198.51.100.1", vec![Some("203.0.113.5")], "198.51.100.1"; "untrusted peer")]
+    #[test_case("10.0.0.2", vec![Some("203.0.113.5"), Some("10.0.0.3")], "203.0.113.5"; "chain of proxies")]
+    #[test_case("10.0.0.2", vec![Some("192.0.2.1"), Some("203.0.113.5")], "203.0.113.5"; "forged by client")]
+    #[test_case("10.0.0.2", vec![Some("10.0.0.3")], "10.0.0.3"; "only proxies")]
+    #[test_case("10.0.0.2", vec![None], "10.0.0.2"; "unknown")]
+    #[test_case("::ffff:10.0.0.2", vec![Some("203.0.113.5")], "203.0.113.5"; "mapped peer")]
+    fn original_address(peer: &str, forwarded: Vec<Option<&str>>, output: &str) {
+        let trusted = vec![util::parse_network("10.0.0.0/8").unwrap()];
+        let forwarded = forwarded
+            .into_iter()
+            .map(|address| address.map(|address| address.parse().unwrap()))
+            .collect::<Vec<_>>();
+        let address = super::original_address(peer.parse().unwrap(), &forwarded, &trusted);
+        assert_eq!(address, output.parse::<std::net::IpAddr>().unwrap());
+    }
+}
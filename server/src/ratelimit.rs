@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header::RETRY_AFTER, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use log::warn;
+
+use crate::{
+    api_key, config::RateLimitConfig, devices, is_admin_secret, is_authenticated, proxies, signing,
+    AppState,
+};
+
+/// Buckets kept before full ones are dropped. Full buckets are the same as
+/// new ones, so dropping them only frees memory.
+const MAXIMUM_BUCKETS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets per device, user, API key or IP address. The rate and size
+/// of the buckets are taken from the configuration on every request, so that
+/// they can be reloaded.
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    /// Take a token from the bucket of the client. Returns the seconds until
+    /// the next token if the bucket is empty.
+    fn acquire(&self, client: String, config: &RateLimitConfig) -> Result<(), u64> {
+        // Tokens added per second.
+        let rate = config.requests_per_minute.max(1) as f64 / 60.0;
+        // Most tokens a bucket holds, i.e. the largest burst of requests.
+        let capacity = config.burst.max(1) as f64;
+        let Ok(mut buckets) = self.buckets.lock() else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        if buckets.len() >= MAXIMUM_BUCKETS && !buckets.contains_key(&client) {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < capacity
+            });
+            // Clients that keep their buckets from filling up, such as ones
+            // coming from many addresses, lose their least recently used one
+            // instead.
+            if buckets.len() >= MAXIMUM_BUCKETS {
+                let oldest = buckets
+                    .iter()
+                    .min_by_key(|(_, bucket)| bucket.updated)
+                    .map(|(client, _)| client.clone());
+                if let Some(oldest) = oldest {
+                    buckets.remove(&oldest);
+                }
+            }
+        }
+        let bucket = buckets.entry(client).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(((1.0 - bucket.tokens) / rate).ceil() as u64)
+    }
+}
+
+/// Bucket of the authenticated sender of the request: its device, its user,
+/// or the API key or secret it was made with. None if the request has no
+/// valid credentials, which would let anyone pick a bucket of their own.
+async fn identity(headers: &HeaderMap, state: &AppState) -> Option<String> {
+    if let Some((name, user, _)) = api_key(headers, &state.config) {
+        return Some(
+            match user.and_then(|user| state.users.get(&user).copied()) {
+                Some(user_id) => format!("user:{}", user_id),
+                None => format!("key:{}", name),
+            },
+        );
+    }
+    if is_admin_secret(headers, &state.config) == Some(true) {
+        return Some("admin secret".to_string());
+    }
+    // Servers without a secret let everyone in, so only a secret that was
+    // actually sent tells the client apart.
+    let sent_secret = signing::signed() || headers.contains_key(shared::SECRET_KEY_HEADER);
+    if sent_secret && is_authenticated(headers, &state.config) {
+        return Some("secret".to_string());
+    }
+    let (device_id, _) = devices::authenticate_device(headers, &state.db).await?;
+    Some(format!("device:{}", device_id))
+}
+
+/// Reject requests with 429 Too Many Requests once the client has used up
+/// its bucket. Clients are told apart by who they authenticate as, and by
+/// their IP address, behind trusted proxies the forwarded one, if they have
+/// no valid credentials.
+pub async fn limit(
+    State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let rate_limit = match state.config.read() {
+        Ok(config) => config.rate_limit.clone(),
+        Err(_) => None,
+    };
+    let Some(rate_limit) = rate_limit else {
+        return next.run(request).await;
+    };
+    let address = proxies::client_address().unwrap_or(address.ip());
+    let client = match identity(request.headers(), &state).await {
+        Some(identity) => identity,
+        None => format!("ip:{}", address),
+    };
+    if let Err(retry_after) = state.rate_limiter.acquire(client, &rate_limit) {
+        warn!(
+            "Rate limited request from {} to {}",
+            address,
+            request.uri().path()
+        );
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(RETRY_AFTER, retry_after.to_string())],
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::RateLimitConfig;
+
+    const CONFIG: RateLimitConfig = RateLimitConfig {
+        requests_per_minute: 1,
+        burst: 2,
+    };
+
+    #[test]
+    fn acquire() {
+        let limiter = super::RateLimiter::default();
+        assert_eq!(limiter.acquire("a".to_string(), &CONFIG), Ok(()));
+        assert_eq!(limiter.acquire("a".to_string(), &CONFIG), Ok(()));
+        assert_eq!(limiter.acquire("a".to_string(), &CONFIG), Err(60));
+        assert_eq!(limiter.acquire("b".to_string(), &CONFIG), Ok(()));
+    }
+
+    #[test]
+    fn acquire_bounded() {
+        let limiter = super::RateLimiter::default();
+        for client in 0..super::MAXIMUM_BUCKETS + 10 {
+            let _ = limiter.acquire(client.to_string(), &CONFIG);
+        }
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.len(), super::MAXIMUM_BUCKETS);
+        assert!(!buckets.contains_key("0"));
+        assert!(buckets.contains_key(&(super::MAXIMUM_BUCKETS + 9).to_string()));
+    }
+}
@@ -0,0 +1,123 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use log::{debug, error, info, warn};
+use notify::Watcher;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::{config, AppState};
+
+/// Time to wait for the events of saving a file to settle before reading it.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Reload the configuration whenever the file changes or the server gets
+/// SIGHUP. The watcher stops when it is dropped.
+///
+/// The parent directory is watched instead of the file itself, since editors
+/// that save by replacing the file would otherwise end the watch.
+pub fn watch(config_path: &Path, state: AppState) -> notify::Result<notify::RecommendedWatcher> {
+    let (sender, receiver) = unbounded_channel();
+    let file_name = config_path.file_name().map(|name| name.to_owned());
+    let changes = sender.clone();
+    let mut watcher =
+        notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
+            Ok(event) => {
+                let is_config = event
+                    .paths
+                    .iter()
+                    .any(|path| path.file_name() == file_name.as_deref());
+                if is_config && !event.kind.is_access() {
+                    let _ = changes.send(());
+                }
+            }
+            Err(error) => warn!("Error monitoring configuration file: {}", error),
+        })?;
+    let directory = config_path.parent().unwrap_or(config_path);
+    watcher.watch(directory, notify::RecursiveMode::NonRecursive)?;
+    debug!("Monitoring {} for changes", config_path.display());
+
+    tokio::spawn(hangups(sender));
+    tokio::spawn(reload_on_change(config_path.to_path_buf(), state, receiver));
+    Ok(watcher)
+}
+
+#[cfg(unix)]
+async fn hangups(sender: UnboundedSender<()>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(error) => {
+            error!("Could not listen for SIGHUP: {}", error);
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        info!("Got SIGHUP, reloading configuration");
+        if sender.send(()).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn hangups(_sender: UnboundedSender<()>) {}
+
+async fn reload_on_change(
+    config_path: PathBuf,
+    state: AppState,
+    mut changes: UnboundedReceiver<()>,
+) {
+    while changes.recv().await.is_some() {
+        while let Ok(Some(())) = tokio::time::timeout(DEBOUNCE, changes.recv()).await {}
+        reload(&config_path, &state);
+    }
+}
+
+/// Replace the settings that are read as they are needed, such as secrets,
+/// API keys, rate limits and webhooks. The rest, such as the database and
+/// the users, keep their values from startup. An invalid file is ignored.
+fn reload(config_path: &Path, state: &AppState) {
+    let mut new_config = match config::Config::load(config_path) {
+        Ok(config) => config,
+        Err(error) => {
+            error!(
+                "Could not reload configuration from {}, keeping the old one: {:?}",
+                config_path.display(),
+                error
+            );
+            return;
+        }
+    };
+    // A key of a user without an ID would see the sessions of everyone.
+    let unknown_user = new_config.api_keys.iter().find_map(|api_key| {
+        api_key
+            .user
+            .as_ref()
+            .filter(|user| !state.users.contains_key(*user))
+            .map(|user| (&api_key.name, user))
+    });
+    if let Some((name, user)) = unknown_user {
+        error!(
+            "Could not reload configuration: API key {} belongs to user {}, who is added only on restart",
+            name, user
+        );
+        return;
+    }
+    let webhooks = new_config.webhooks.clone();
+    let notifiers = new_config.notifiers.clone();
+    let time_trackers = new_config.time_trackers.clone();
+    match state.config.write() {
+        Ok(mut config) => {
+            new_config.keep_startup_settings(&mut config);
+            *config = new_config;
+        }
+        Err(_) => {
+            error!("Could not reload configuration: cannot write configuration");
+            return;
+        }
+    }
+    state.webhooks.configure(webhooks, notifiers);
+    state.time_trackers.configure(time_trackers);
+    info!("Reloaded configuration from {}", config_path.display());
+}
@@ -0,0 +1,227 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use lettre::{
+    message::header::ContentType, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use log::{error, info};
+use time::OffsetDateTime;
+use time_tz::{OffsetDateTimeExt, Tz};
+
+use crate::{
+    config::{EmailReportConfig, EmailReportsConfig, SmtpConfig, SmtpSecurity},
+    db::Database,
+    stats, summary,
+    timezones::Timezones,
+    users::UserIds,
+    util,
+};
+
+/// Time between checking whether a period has ended.
+const REPORT_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Time to wait for the SMTP server.
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Name of the period as in "the week before".
+fn period_name(period: shared::SummaryPeriod) -> &'static str {
+    match period {
+        shared::SummaryPeriod::Day => "day",
+        shared::SummaryPeriod::Week => "week",
+        shared::SummaryPeriod::Month => "month",
+    }
+}
+
+/// What the sent periods of the report are remembered by.
+fn report_key(report: &EmailReportConfig) -> String {
+    format!(
+        "{}:{}:{}",
+        report.to,
+        period_name(report.period),
+        report.user.as_deref().unwrap_or_default()
+    )
+}
+
+/// Subject and plain text body of the report of the summary.
+fn render(
+    summary: &shared::Summary,
+    report: &EmailReportConfig,
+    timezone: &Tz,
+) -> (String, String) {
+    let period = period_name(report.period);
+    let mut subject = match report.period {
+        shared::SummaryPeriod::Day => "Daily playtime".to_string(),
+        shared::SummaryPeriod::Week => "Weekly playtime".to_string(),
+        shared::SummaryPeriod::Month => "Monthly playtime".to_string(),
+    };
+    if let Some(user) = &report.user {
+        subject.push_str(&format!(" of {}", user));
+    }
+    subject.push_str(&format!(": {}", util::format_duration(summary.duration)));
+
+    let first_day = summary.since.to_timezone(timezone).date();
+    let last_day = (summary.until - Duration::from_nanos(1))
+        .to_timezone(timezone)
+        .date();
+    let mut body = if first_day == last_day {
+        format!("Playtime on {} ({})\n\n", first_day, summary.timezone)
+    } else {
+        format!(
+            "Playtime from {} to {} ({})\n\n",
+            first_day, last_day, summary.timezone
+        )
+    };
+    let change = util::describe_change(summary.change, summary.previous_duration, period);
+    if summary.sessions == 0 {
+        body.push_str(&format!("Nothing was played, {}.\n", change));
+        return (subject, body);
+    }
+    body.push_str(&format!(
+        "Played {} in {} sessions of {} games, {}.\n\nMost played:\n",
+        util::format_duration(summary.duration),
+        summary.sessions,
+        summary.games,
+        change
+    ));
+    for game in &summary.top_games {
+        let title = match &game.metadata {
+            Some(metadata) => &metadata.title,
+            None => game.name.as_ref().unwrap_or(&game.executable),
+        };
+        body.push_str(&format!(
+            "  {}: {} in {} sessions\n",
+            title,
+            util::format_duration(game.duration),
+            game.sessions
+        ));
+    }
+    (subject, body)
+}
+
+fn transport(config: &SmtpConfig) -> Result<AsyncSmtpTransport<Tokio1Executor>, String> {
+    let mut builder = match config.security {
+        SmtpSecurity::Starttls => {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+                .map_err(|error| error.to_string())?
+        }
+        SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)
+            .map_err(|error| error.to_string())?,
+        SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+    };
+    if let Some(port) = config.port {
+        builder = builder.port(port);
+    }
+    if let Some(username) = &config.username {
+        let password = config.password.clone().unwrap_or_default();
+        builder = builder.credentials(Credentials::new(username.clone(), password));
+    }
+    Ok(builder.timeout(Some(SMTP_TIMEOUT)).build())
+}
+
+async fn send(
+    config: &EmailReportsConfig,
+    to: &str,
+    subject: String,
+    body: String,
+) -> Result<(), String> {
+    let message = Message::builder()
+        .from(
+            config
+                .from
+                .parse()
+                .map_err(|error| format!("bad sender: {}", error))?,
+        )
+        .to(to
+            .parse()
+            .map_err(|error| format!("bad recipient: {}", error))?)
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(body)
+        .map_err(|error| error.to_string())?;
+    transport(&config.smtp)?
+        .send(message)
+        .await
+        .map_err(|error| error.to_string())?;
+    Ok(())
+}
+
+/// Send the report for the period that ended last, unless it has been sent.
+/// Reports that fail are tried again the next time.
+async fn report(
+    db: &Database,
+    config: &EmailReportsConfig,
+    report: &EmailReportConfig,
+    user_id: Option<i32>,
+    timezone: &Tz,
+    now: OffsetDateTime,
+) {
+    let until = util::start_of_period(report.period, now, timezone);
+    let key = report_key(report);
+    match db.report_sent_until(key.clone()).await {
+        Ok(Some(sent_until)) if sent_until >= until => return,
+        Ok(_) => {}
+        Err(error) => {
+            error!("Could not check the report to {}: {}", report.to, error);
+            return;
+        }
+    }
+    let query = shared::SummaryQuery {
+        period: report.period,
+        until: Some(until),
+        device: None,
+    };
+    let mut summary = match summary::summarise(db, query, user_id, timezone).await {
+        Ok(summary) => summary,
+        Err(error) => {
+            error!("Could not summarise the report to {}: {}", report.to, error);
+            return;
+        }
+    };
+    summary.top_games = stats::with_metadata(db, summary.top_games).await;
+    let (subject, body) = render(&summary, report, timezone);
+    if let Err(error) = send(config, &report.to, subject, body).await {
+        error!("Could not send the report to {}: {}", report.to, error);
+        return;
+    }
+    info!(
+        "Sent the {} report to {}",
+        period_name(report.period),
+        report.to
+    );
+    if let Err(error) = db.save_report_sent(key, until).await {
+        error!("Could not save the report to {}: {}", report.to, error);
+    }
+}
+
+/// Send every report once the period it covers has ended, starting with the
+/// period that ended last.
+pub async fn run(
+    db: Database,
+    config: EmailReportsConfig,
+    user_ids: UserIds,
+    timezones: Arc<Timezones>,
+) {
+    let mut reports = Vec::new();
+    for report in &config.reports {
+        let user_id = match &report.user {
+            Some(user) => match user_ids.get(user) {
+                Some(user_id) => Some(*user_id),
+                None => {
+                    error!("Email report to {} is for unknown user {}", report.to, user);
+                    continue;
+                }
+            },
+            None => None,
+        };
+        reports.push((report, user_id, timezones.get(user_id)));
+    }
+    let mut interval = tokio::time::interval(REPORT_INTERVAL);
+    loop {
+        interval.tick().await;
+        let now = OffsetDateTime::now_utc();
+        for (email_report, user_id, timezone) in &reports {
+            report(&db, &config, email_report, *user_id, timezone, now).await;
+        }
+    }
+}
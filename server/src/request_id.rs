@@ -0,0 +1,66 @@
+use axum::{extract::Request, http::HeaderName, middleware::Next, response::Response};
+use log::{Log, Metadata, Record, SetLoggerError};
+use simple_logger::SimpleLogger;
+use tower_http::request_id::RequestId;
+
+use crate::proxies;
+
+/// `shared::REQUEST_ID_HEADER`, which is generated unless the caller sends
+/// one and echoed in the response.
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Handle the rest of the request with its ID available to the logger.
+pub async fn scope(request: Request, next: Next) -> Response {
+    let id = request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .unwrap_or("-")
+        .to_string();
+    REQUEST_ID.scope(id, next.run(request)).await
+}
+
+/// Logger that starts every line logged while handling a request with the ID
+/// of the request and the address of the client.
+struct Logger {
+    inner: SimpleLogger,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        let logged = REQUEST_ID.try_with(|id| {
+            let address = proxies::client_address()
+                .map(|address| address.to_string())
+                .unwrap_or_else(|| "-".to_string());
+            self.inner.log(
+                &Record::builder()
+                    .args(format_args!("[{} {}] {}", id, address, record.args()))
+                    .metadata(record.metadata().clone())
+                    .module_path(record.module_path())
+                    .file(record.file())
+                    .line(record.line())
+                    .build(),
+            )
+        });
+        if logged.is_err() {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+pub fn init_logger(logger: SimpleLogger) -> Result<(), SetLoggerError> {
+    log::set_max_level(logger.max_level());
+    log::set_boxed_logger(Box::new(Logger { inner: logger }))
+}
@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{error, info};
+use time::OffsetDateTime;
+
+use crate::{
+    audit::{self, Action},
+    config::RetentionConfig,
+    db::Database,
+    timezones::Timezones,
+    util,
+};
+
+/// Time between runs of the retention policy.
+const RETENTION_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Event old enough to be rolled into a daily aggregate.
+pub struct AgedEvent {
+    pub id: i32,
+    pub time: OffsetDateTime,
+    pub started_at: OffsetDateTime,
+    pub process: i32,
+    pub user_id: Option<i32>,
+    pub device: Option<i32>,
+
+    /// Duration in seconds.
+    pub duration: u64,
+    pub focused_duration: Option<u64>,
+}
+
+/// Single event replacing all events of a process on one day.
+pub struct Rollup {
+    /// Events replaced by the rollup.
+    pub ids: Vec<i32>,
+    pub time: OffsetDateTime,
+    pub started_at: OffsetDateTime,
+    pub process: i32,
+    pub user_id: Option<i32>,
+    pub device: Option<i32>,
+    pub duration: u64,
+    pub focused_duration: Option<u64>,
+}
+
+/// Group the events by process, user, device and the day they ended on in
+/// the time zone of the user. Days with a single event are already rolled up
+/// and are left out.
+pub fn rollups(events: Vec<AgedEvent>, timezones: &Timezones) -> Vec<Rollup> {
+    let mut days: BTreeMap<(i32, Option<i32>, Option<i32>, OffsetDateTime), Rollup> =
+        BTreeMap::new();
+    for event in events {
+        let key = (
+            event.process,
+            event.user_id,
+            event.device,
+            util::start_of_day(event.time, timezones.get(event.user_id)),
+        );
+        match days.get_mut(&key) {
+            Some(rollup) => {
+                rollup.ids.push(event.id);
+                rollup.time = rollup.time.max(event.time);
+                rollup.started_at = rollup.started_at.min(event.started_at);
+                rollup.duration += event.duration;
+                rollup.focused_duration = match (rollup.focused_duration, event.focused_duration) {
+                    (Some(total), Some(seconds)) => Some(total + seconds),
+                    (total, seconds) => total.or(seconds),
+                };
+            }
+            None => {
+                days.insert(
+                    key,
+                    Rollup {
+                        ids: vec![event.id],
+                        time: event.time,
+                        started_at: event.started_at,
+                        process: event.process,
+                        user_id: event.user_id,
+                        device: event.device,
+                        duration: event.duration,
+                        focused_duration: event.focused_duration,
+                    },
+                );
+            }
+        }
+    }
+    days.into_values()
+        .filter(|rollup| rollup.ids.len() > 1)
+        .collect()
+}
+
+fn cutoff(days: u32) -> OffsetDateTime {
+    OffsetDateTime::now_utc() - Duration::from_secs(days as u64 * SECONDS_PER_DAY)
+}
+
+async fn apply(db: &Database, config: &RetentionConfig, timezones: &Timezones) {
+    if let Some(days) = config.delete_after_days {
+        match db.delete_events_before(cutoff(days)).await {
+            Ok(0) => {}
+            Ok(deleted) => {
+                info!("Deleted {} events older than {} days", deleted, days);
+                let details = Some(format!("{} sessions older than {} days", deleted, days));
+                audit::record(db, audit::RETENTION, Action::DeleteEvents, None, details).await;
+            }
+            Err(error) => error!("Could not delete old events: {}", error),
+        }
+    }
+    if let Some(months) = config.archive_after_months {
+        let before = util::start_of_months_ago(OffsetDateTime::now_utc(), months);
+        match db.archive_events_before(before).await {
+            Ok(0) => {}
+            Ok(archived) => {
+                info!("Archived {} events from before {}", archived, before.date());
+                let details = Some(format!("{} sessions before {}", archived, before.date()));
+                audit::record(db, audit::RETENTION, Action::ArchiveEvents, None, details).await;
+            }
+            Err(error) => error!("Could not archive old events: {}", error),
+        }
+    }
+    if let Some(days) = config.aggregate_after_days {
+        let events = match db.aged_events(cutoff(days)).await {
+            Ok(events) => events,
+            Err(error) => {
+                error!("Could not load old events: {}", error);
+                return;
+            }
+        };
+        let rollups = rollups(events, timezones);
+        if rollups.is_empty() {
+            return;
+        }
+        let count = rollups.iter().map(|rollup| rollup.ids.len()).sum::<usize>();
+        match db.replace_events(rollups).await {
+            Ok(()) => {
+                info!(
+                    "Rolled {} events older than {} days into daily totals",
+                    count, days
+                );
+                let details = Some(format!("{} sessions older than {} days", count, days));
+                audit::record(db, audit::RETENTION, Action::RollUpEvents, None, details).await;
+            }
+            Err(error) => error!("Could not roll up old events: {}", error),
+        }
+    }
+}
+
+/// Apply the retention policy at startup and then once a day.
+pub async fn run(db: Database, config: RetentionConfig, timezones: Arc<Timezones>) {
+    let mut interval = tokio::time::interval(RETENTION_INTERVAL);
+    loop {
+        interval.tick().await;
+        apply(&db, &config, &timezones).await;
+    }
+}
@@ -0,0 +1,390 @@
+use std::time::Duration;
+
+use log::{error, info};
+use reqwest::{Method, Url};
+use ring::hmac;
+use time::{OffsetDateTime, UtcOffset};
+
+use crate::{config::S3BackupConfig, db::Database, util};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+const SECONDS_PER_HOUR: u64 = 60 * 60;
+
+/// Headers signed in requests to S3, which are the only ones whose values
+/// `aws_canonical_request` takes.
+const AWS_SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+
+/// Time as AWS Signature Version 4 wants it in `X-Amz-Date`, e.g.
+/// `20130524T000000Z`.
+fn aws_date(time: OffsetDateTime) -> String {
+    let time = time.to_offset(UtcOffset::UTC);
+    return format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        time.year(),
+        time.month() as u8,
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    );
+}
+
+/// Text percent-encoded the way AWS Signature Version 4 wants: everything but
+/// letters, digits and `-._~`, and slashes too unless it is a path.
+fn aws_uri_encode(text: &str, path: bool) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else if path && byte == b'/' {
+            encoded.push('/');
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    return encoded;
+}
+
+/// Canonical request of AWS Signature Version 4 for a request that signs the
+/// headers in `AWS_SIGNED_HEADERS`. The path and query are encoded here.
+fn aws_canonical_request(
+    method: &str,
+    host: &str,
+    path: &str,
+    query: &[(&str, &str)],
+    payload_hash: &str,
+    amz_date: &str,
+) -> String {
+    let mut parameters = query
+        .iter()
+        .map(|(key, value)| (aws_uri_encode(key, false), aws_uri_encode(value, false)))
+        .collect::<Vec<_>>();
+    parameters.sort();
+    let query = parameters
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("&");
+    return format!(
+        "{}\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        method,
+        aws_uri_encode(path, true),
+        query,
+        host,
+        payload_hash,
+        amz_date,
+        AWS_SIGNED_HEADERS,
+        payload_hash
+    );
+}
+
+/// Scope of the credentials of AWS Signature Version 4 for S3 in the region
+/// on the day of `amz_date`.
+fn aws_scope(amz_date: &str, region: &str) -> String {
+    return format!("{}/{}/s3/aws4_request", &amz_date[..8], region);
+}
+
+/// Signature of the canonical request with AWS Signature Version 4 for S3.
+fn aws_signature(
+    canonical_request: &str,
+    amz_date: &str,
+    region: &str,
+    secret_access_key: &str,
+) -> String {
+    let scope = aws_scope(amz_date, region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        util::sha256_hex(canonical_request.as_bytes())
+    );
+    let mut key = format!("AWS4{}", secret_access_key).into_bytes();
+    for part in [&amz_date[..8], region, "s3", "aws4_request"] {
+        let tag = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, &key), part.as_bytes());
+        key = tag.as_ref().to_vec();
+    }
+    let tag = hmac::sign(
+        &hmac::Key::new(hmac::HMAC_SHA256, &key),
+        string_to_sign.as_bytes(),
+    );
+    return util::hex(tag.as_ref());
+}
+
+/// Text with the entities of `atom::xml_escape` replaced by their characters.
+fn xml_unescape(text: &str) -> String {
+    return text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&");
+}
+
+/// Text of every element with the tag, in order, for reading the simple
+/// responses of S3 without an XML parser. Elements with attributes or
+/// children aren't supported.
+fn xml_elements(xml: &str, tag: &str) -> Vec<String> {
+    let start = format!("<{}>", tag);
+    let end = format!("</{}>", tag);
+    let mut elements = Vec::new();
+    let mut rest = xml;
+    while let Some(index) = rest.find(&start) {
+        rest = &rest[index + start.len()..];
+        let Some(index) = rest.find(&end) else {
+            break;
+        };
+        elements.push(xml_unescape(&rest[..index]));
+        rest = &rest[index + end.len()..];
+    }
+    return elements;
+}
+
+/// Request to the bucket signed with AWS Signature Version 4. The key is
+/// appended to the path of the bucket, so an empty key is the bucket itself.
+async fn request(
+    client: &reqwest::Client,
+    config: &S3BackupConfig,
+    method: Method,
+    key: &str,
+    query: &[(&str, &str)],
+    body: Vec<u8>,
+) -> Result<reqwest::Response, String> {
+    let mut url = Url::parse(&config.endpoint).map_err(|error| error.to_string())?;
+    let Some(host) = url.host_str() else {
+        return Err(format!("{} has no host", config.endpoint));
+    };
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    };
+    let mut path = format!("/{}", config.bucket);
+    if !key.is_empty() {
+        path.push('/');
+        path.push_str(key);
+    }
+    url.set_path(&aws_uri_encode(&path, true));
+    // Encoded the same way as in the signature rather than as a form.
+    let encoded = query
+        .iter()
+        .map(|(key, value)| {
+            format!(
+                "{}={}",
+                aws_uri_encode(key, false),
+                aws_uri_encode(value, false)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    url.set_query(
+        Some(&encoded)
+            .filter(|encoded| !encoded.is_empty())
+            .map(String::as_str),
+    );
+
+    let amz_date = aws_date(OffsetDateTime::now_utc());
+    let payload_hash = util::sha256_hex(&body);
+    let canonical_request = aws_canonical_request(
+        method.as_str(),
+        &host,
+        &path,
+        query,
+        &payload_hash,
+        &amz_date,
+    );
+    let signature = aws_signature(
+        &canonical_request,
+        &amz_date,
+        &config.region,
+        &config.secret_access_key,
+    );
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        config.access_key_id,
+        aws_scope(&amz_date, &config.region),
+        AWS_SIGNED_HEADERS,
+        signature
+    );
+    let response = client
+        .request(method, url)
+        .header("x-amz-content-sha256", payload_hash)
+        .header("x-amz-date", amz_date)
+        .header("authorization", authorization)
+        .body(body)
+        .send()
+        .await
+        .map_err(|error| error.without_url().to_string())?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        let message = xml_elements(&body, "Message").into_iter().next();
+        return Err(match message {
+            Some(message) => format!("{}: {}", status, message),
+            None => status.to_string(),
+        });
+    }
+    Ok(response)
+}
+
+/// Keys of the objects starting with the prefix, following continuations of
+/// listings that don't fit in one response.
+async fn list(client: &reqwest::Client, config: &S3BackupConfig) -> Result<Vec<String>, String> {
+    let mut keys = Vec::new();
+    let mut continuation: Option<String> = None;
+    loop {
+        let mut query = vec![("list-type", "2"), ("prefix", config.prefix.as_str())];
+        if let Some(token) = &continuation {
+            query.push(("continuation-token", token.as_str()));
+        }
+        let response = request(client, config, Method::GET, "", &query, Vec::new()).await?;
+        let body = response.text().await.map_err(|error| error.to_string())?;
+        keys.extend(xml_elements(&body, "Key"));
+        let truncated = xml_elements(&body, "IsTruncated")
+            .first()
+            .is_some_and(|truncated| truncated == "true");
+        continuation = xml_elements(&body, "NextContinuationToken")
+            .into_iter()
+            .next();
+        if !truncated || continuation.is_none() {
+            return Ok(keys);
+        }
+    }
+}
+
+/// Upload a backup of the database and delete the oldest ones over the limit.
+/// Backups are named by the time they were made, so the names sort by age.
+async fn back_up(db: &Database, client: &reqwest::Client, config: &S3BackupConfig) {
+    let backup = match db.backup().await {
+        Ok(backup) => backup,
+        Err(error) => {
+            error!("Could not back up database to S3: {}", error);
+            return;
+        }
+    };
+    let body = match serde_json::to_vec(&backup) {
+        Ok(body) => body,
+        Err(error) => {
+            error!("Could not serialise backup: {}", error);
+            return;
+        }
+    };
+    let key = format!(
+        "{}beelzebub-{}.json",
+        config.prefix,
+        aws_date(OffsetDateTime::now_utc())
+    );
+    if let Err(error) = request(client, config, Method::PUT, &key, &[], body).await {
+        error!("Could not upload backup {} to S3: {}", key, error);
+        return;
+    }
+    info!(
+        "Uploaded backup {} with {} processes and {} events to S3",
+        key,
+        backup.processes.len(),
+        backup.events.len()
+    );
+
+    let mut keys = match list(client, config).await {
+        Ok(keys) => keys,
+        Err(error) => {
+            error!("Could not list backups in S3: {}", error);
+            return;
+        }
+    };
+    // Leave alone whatever else is stored under the prefix.
+    keys.retain(|key| {
+        key.strip_prefix(&config.prefix)
+            .is_some_and(|name| name.starts_with("beelzebub-") && name.ends_with(".json"))
+    });
+    keys.sort();
+    let expired = keys.len().saturating_sub(config.keep.max(1));
+    for key in &keys[..expired] {
+        match request(client, config, Method::DELETE, key, &[], Vec::new()).await {
+            Ok(_) => info!("Deleted old backup {} from S3", key),
+            Err(error) => error!("Could not delete backup {} from S3: {}", key, error),
+        }
+    }
+}
+
+pub async fn run(db: Database, config: S3BackupConfig) {
+    let client = match reqwest::Client::builder()
+        .user_agent(concat!("beelzebub-server/", env!("CARGO_PKG_VERSION")))
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+    {
+        Ok(client) => client,
+        Err(error) => {
+            error!("Could not set up S3 backups: {}", error);
+            return;
+        }
+    };
+    let mut interval = tokio::time::interval(Duration::from_secs(
+        config.interval_hours.max(1) * SECONDS_PER_HOUR,
+    ));
+    loop {
+        interval.tick().await;
+        back_up(&db, &client, &config).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use crate::util;
+
+    #[test_case(1_369_353_600, "20130524T000000Z"; "midnight")]
+    #[test_case(1_709_294_405, "20240301T120005Z"; "afternoon")]
+    fn aws_date(timestamp: i64, output: &str) {
+        let time = time::OffsetDateTime::from_unix_timestamp(timestamp).unwrap();
+        assert_eq!(super::aws_date(time), output);
+    }
+
+    #[test_case("beelzebub-20240301T120000Z.json", false, "beelzebub-20240301T120000Z.json"; "unreserved")]
+    #[test_case("backups/beelzebub.json", true, "backups/beelzebub.json"; "path")]
+    #[test_case("backups/beelzebub.json", false, "backups%2Fbeelzebub.json"; "slash")]
+    #[test_case("a b+c=ä", false, "a%20b%2Bc%3D%C3%A4"; "reserved")]
+    fn aws_uri_encode(text: &str, path: bool, output: &str) {
+        assert_eq!(super::aws_uri_encode(text, path), output);
+    }
+
+    #[test_case(&[("prefix", "J"), ("max-keys", "2")], "max-keys=2&prefix=J", "34b48302e7b5fa45bde8084f4b7868a86f0a534bc59db6670ed5711ef69dc6f7"; "list bucket")]
+    fn aws_signature(query: &[(&str, &str)], canonical_query: &str, signature: &str) {
+        let payload_hash = util::sha256_hex(b"");
+        let canonical_request = super::aws_canonical_request(
+            "GET",
+            "examplebucket.s3.amazonaws.com",
+            "/",
+            query,
+            &payload_hash,
+            "20130524T000000Z",
+        );
+        assert_eq!(
+            canonical_request,
+            format!(
+                "GET\n/\n{}\nhost:examplebucket.s3.amazonaws.com\nx-amz-content-sha256:{}\nx-amz-date:20130524T000000Z\n\nhost;x-amz-content-sha256;x-amz-date\n{}",
+                canonical_query, payload_hash, payload_hash
+            )
+        );
+        let secret_access_key = "wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY";
+        assert_eq!(
+            super::aws_signature(
+                &canonical_request,
+                "20130524T000000Z",
+                "us-east-1",
+                secret_access_key
+            ),
+            signature
+        );
+    }
+
+    // The example of listing a bucket in the documentation of AWS Signature
+    // Version 4 for S3.
+    #[test_case("<Contents><Key>a.json</Key></Contents><Contents><Key>b &amp; c.json</Key></Contents>", "Key", vec!["a.json", "b & c.json"]; "keys")]
+    #[test_case("<IsTruncated>false</IsTruncated>", "IsTruncated", vec!["false"]; "single")]
+    #[test_case("<ListBucketResult></ListBucketResult>", "Key", vec![]; "none")]
+    #[test_case("<Key>a.json", "Key", vec![]; "unclosed")]
+    fn xml_elements(xml: &str, tag: &str, output: Vec<&str>) {
+        assert_eq!(super::xml_elements(xml, tag), output);
+    }
+}
@@ -1,11 +1,163 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    archived_events (id) {
+        id -> Int4,
+        time -> Timestamptz,
+        process -> Int4,
+        duration -> Interval,
+        product_version -> Nullable<Varchar>,
+        file_version -> Nullable<Varchar>,
+        started_at -> Timestamptz,
+        focused_duration -> Nullable<Interval>,
+        tags -> Array<Text>,
+        window_title -> Nullable<Varchar>,
+        api_key -> Nullable<Varchar>,
+        user_id -> Nullable<Int4>,
+        device -> Nullable<Int4>,
+        idempotency_key -> Nullable<Varchar>,
+        imported -> Bool,
+        archived_at -> Timestamptz,
+        manual -> Bool,
+        note -> Nullable<Text>,
+        rating -> Nullable<SmallInt>,
+    }
+}
+
+diesel::table! {
+    audit_log (id) {
+        id -> Int4,
+        recorded_at -> Timestamptz,
+        actor -> Varchar,
+        action -> Varchar,
+        target -> Nullable<Varchar>,
+        details -> Nullable<Text>,
+        address -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    completion_times (process) {
+        process -> Int4,
+        checked_at -> Timestamptz,
+        game_id -> Nullable<Int4>,
+        game -> Nullable<Varchar>,
+        main_story -> Nullable<Interval>,
+        main_extra -> Nullable<Interval>,
+        completionist -> Nullable<Interval>,
+    }
+}
+
+diesel::table! {
+    daily_stats (day, process, user_id, device) {
+        day -> Date,
+        process -> Int4,
+        user_id -> Int4,
+        device -> Int4,
+        duration -> Interval,
+        sessions -> Int4,
+    }
+}
+
+diesel::table! {
+    devices (id) {
+        id -> Int4,
+        name -> Varchar,
+        nonce -> Varchar,
+        token -> Nullable<Varchar>,
+        approved -> Bool,
+        revoked -> Bool,
+        created_at -> Timestamptz,
+        user_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    event_changes (id) {
+        id -> Int4,
+        event -> Int4,
+        user_id -> Nullable<Int4>,
+        action -> Varchar,
+        changed_at -> Timestamptz,
+        changed_by -> Varchar,
+        old_event -> Text,
+        new_event -> Nullable<Text>,
+    }
+}
+
 diesel::table! {
     events (id) {
         id -> Int4,
         time -> Timestamptz,
         process -> Int4,
         duration -> Interval,
+        product_version -> Nullable<Varchar>,
+        file_version -> Nullable<Varchar>,
+        started_at -> Timestamptz,
+        focused_duration -> Nullable<Interval>,
+        tags -> Array<Text>,
+        window_title -> Nullable<Varchar>,
+        api_key -> Nullable<Varchar>,
+        user_id -> Nullable<Int4>,
+        device -> Nullable<Int4>,
+        idempotency_key -> Nullable<Varchar>,
+        imported -> Bool,
+        manual -> Bool,
+        note -> Nullable<Text>,
+        rating -> Nullable<SmallInt>,
+    }
+}
+
+diesel::table! {
+    game_metadata (process) {
+        process -> Int4,
+        checked_at -> Timestamptz,
+        igdb_id -> Nullable<Int4>,
+        title -> Nullable<Varchar>,
+        genres -> Array<Text>,
+        cover_url -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    goal_alerts (goal) {
+        goal -> Varchar,
+        period_start -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    heartbeats (session_id) {
+        session_id -> Varchar,
+        executable -> Varchar,
+        name -> Nullable<Varchar>,
+        started_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        duration -> Interval,
+        focused_duration -> Nullable<Interval>,
+        product_version -> Nullable<Varchar>,
+        file_version -> Nullable<Varchar>,
+        tags -> Array<Text>,
+        window_title -> Nullable<Varchar>,
+        api_key -> Nullable<Varchar>,
+        user_id -> Nullable<Int4>,
+        device -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    process_categories (process, category) {
+        process -> Int4,
+        category -> Varchar,
+    }
+}
+
+diesel::table! {
+    process_aliases (id) {
+        id -> Int4,
+        executable -> Varchar,
+        name -> Nullable<Varchar>,
+        process -> Int4,
     }
 }
 
@@ -15,9 +167,69 @@ diesel::table! {
         executable -> Varchar,
         name -> Nullable<Varchar>,
         export -> Bool,
+        hidden -> Bool,
+    }
+}
+
+diesel::table! {
+    sent_reports (report) {
+        report -> Varchar,
+        sent_until -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    shares (id) {
+        id -> Int4,
+        token -> Varchar,
+        user_id -> Nullable<Int4>,
+        process -> Nullable<Varchar>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    users (id) {
+        id -> Int4,
+        name -> Varchar,
+        leaderboard -> Bool,
+        created_at -> Timestamptz,
     }
 }
 
+diesel::joinable!(devices -> users (user_id));
+diesel::joinable!(archived_events -> devices (device));
+diesel::joinable!(archived_events -> processes (process));
+diesel::joinable!(archived_events -> users (user_id));
+diesel::joinable!(daily_stats -> devices (device));
+diesel::joinable!(daily_stats -> processes (process));
+diesel::joinable!(daily_stats -> users (user_id));
+diesel::joinable!(completion_times -> processes (process));
+diesel::joinable!(events -> devices (device));
 diesel::joinable!(events -> processes (process));
+diesel::joinable!(events -> users (user_id));
+diesel::joinable!(game_metadata -> processes (process));
+diesel::joinable!(heartbeats -> devices (device));
+diesel::joinable!(heartbeats -> users (user_id));
+diesel::joinable!(process_aliases -> processes (process));
+diesel::joinable!(process_categories -> processes (process));
+diesel::joinable!(shares -> users (user_id));
 
-diesel::allow_tables_to_appear_in_same_query!(events, processes,);
+diesel::allow_tables_to_appear_in_same_query!(
+    archived_events,
+    audit_log,
+    completion_times,
+    daily_stats,
+    devices,
+    event_changes,
+    events,
+    game_metadata,
+    goal_alerts,
+    heartbeats,
+    process_aliases,
+    process_categories,
+    processes,
+    sent_reports,
+    shares,
+    users,
+);
@@ -0,0 +1,190 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use log::{error, info, warn};
+
+use crate::{
+    audit, authorize, config::Scope, devices::generate_token, stats, submission_limits, AppState,
+};
+
+/// Create a link that shows the total playtime of the caller, or of the games
+/// matching `process`, at `/share/{token}` to anyone who has it.
+#[utoipa::path(
+    post,
+    path = "/shares",
+    request_body = shared::NewShare,
+    responses(
+        (status = 201, description = "Link created", body = shared::Share),
+        (status = 401, description = "Not authenticated"),
+        (status = 422, description = "Too long process filter"),
+    ),
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<shared::NewShare>,
+) -> Result<(StatusCode, Json<shared::Share>), StatusCode> {
+    let Some(caller) = authorize(&headers, &state, Scope::Read).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let process = payload
+        .process
+        .as_deref()
+        .map(str::trim)
+        .filter(|process| !process.is_empty())
+        .map(str::to_string);
+    let limits = submission_limits(&state.config);
+    if process
+        .as_ref()
+        .is_some_and(|process| process.chars().count() > limits.maximum_length)
+    {
+        warn!("Rejected share of {:?}", payload.process);
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let Ok(token) = generate_token() else {
+        error!("Could not generate share token");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+    match state
+        .db
+        .create_share(token, caller.user_id, process.clone())
+        .await
+    {
+        Ok(share) => {
+            info!("Created share {}", share.id);
+            let target = Some(format!("share {}", share.id));
+            audit::record(
+                &state.db,
+                &caller.actor(),
+                audit::Action::CreateShare,
+                target,
+                process,
+            )
+            .await;
+            Ok((StatusCode::CREATED, Json(share)))
+        }
+        Err(error) => {
+            error!("Could not create share: {}", error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Share links, newest first. Callers limited to a user only see the links of
+/// the user.
+#[utoipa::path(
+    get,
+    path = "/shares",
+    responses(
+        (status = 200, description = "Share links", body = Vec<shared::Share>),
+        (status = 401, description = "Not authenticated"),
+    ),
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<shared::Share>>, StatusCode> {
+    let Some(caller) = authorize(&headers, &state, Scope::Read).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    match state.db.shares(caller.user_id).await {
+        Ok(shares) => Ok(Json(shares)),
+        Err(error) => {
+            error!("Could not list shares: {}", error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Revoke a share link, after which its token no longer shows anything.
+#[utoipa::path(
+    delete,
+    path = "/shares/{id}",
+    params(("id" = i32, Path, description = "ID of the share link")),
+    responses(
+        (status = 204, description = "Link revoked"),
+        (status = 401, description = "Not authenticated"),
+        (status = 404, description = "No such link of the caller"),
+    ),
+)]
+pub async fn revoke(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(share_id): Path<i32>,
+) -> StatusCode {
+    let Some(caller) = authorize(&headers, &state, Scope::Read).await else {
+        return StatusCode::UNAUTHORIZED;
+    };
+    match state.db.delete_share(share_id, caller.user_id).await {
+        Ok(0) => StatusCode::NOT_FOUND,
+        Ok(_) => {
+            info!("Revoked share {}", share_id);
+            let target = Some(format!("share {}", share_id));
+            audit::record(
+                &state.db,
+                &caller.actor(),
+                audit::Action::RevokeShare,
+                target,
+                None,
+            )
+            .await;
+            StatusCode::NO_CONTENT
+        }
+        Err(error) => {
+            error!("Could not revoke share {}: {}", share_id, error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Total playtime shared by the link, which needs no authentication.
+#[utoipa::path(
+    get,
+    path = "/share/{token}",
+    params(("token" = String, Path, description = "Token of the share link")),
+    responses(
+        (status = 200, description = "Shared playtime", body = shared::SharedStats),
+        (status = 404, description = "Unknown or revoked link"),
+    ),
+    security(()),
+)]
+pub async fn view(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Json<shared::SharedStats>, StatusCode> {
+    let (user_id, process) = match state.db.find_share(token).await {
+        Ok(Some(share)) => share,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(error) => {
+            error!("Could not look up share: {}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let query = shared::StatsQuery {
+        process: process.clone(),
+        ..Default::default()
+    };
+    let games = match state.db.stats(query, user_id).await {
+        Ok(games) => stats::with_metadata(&state.db, stats::merge_devices(games)).await,
+        Err(error) => {
+            error!("Could not query shared stats: {}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let user = user_id.and_then(|user_id| {
+        state
+            .users
+            .iter()
+            .find(|(_, id)| **id == user_id)
+            .map(|(name, _)| name.clone())
+    });
+    Ok(Json(shared::SharedStats {
+        user,
+        process,
+        duration: games.iter().map(|game| game.duration).sum(),
+        sessions: games.iter().map(|game| game.sessions).sum(),
+        games,
+    }))
+}
@@ -0,0 +1,67 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{error, info};
+use tokio::sync::watch;
+
+/// Time given to requests in progress to finish once the server is stopping.
+/// Shorter than the 10 seconds Docker waits before killing a container.
+pub const DRAIN_TIMEOUT: Duration = Duration::from_secs(8);
+
+/// Resolves when the server is asked to stop with Ctrl+C or SIGTERM.
+async fn signal() {
+    let interrupt = async {
+        if let Err(error) = tokio::signal::ctrl_c().await {
+            error!("Could not listen for Ctrl+C: {}", error);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(error) => {
+                error!("Could not listen for SIGTERM: {}", error);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
+}
+
+/// Whether the server is stopping, shared between the tasks that need to
+/// wind down.
+#[derive(Clone)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Shutdown {
+            sender: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    /// Start stopping the server once it gets a signal to stop.
+    pub async fn listen(self) {
+        signal().await;
+        info!("Shutting down, finishing requests in progress");
+        self.sender.send_replace(true);
+    }
+
+    /// Resolves once the server starts stopping.
+    pub async fn requested(self) {
+        let mut receiver = self.sender.subscribe();
+        // The sender lives as long as `self`, so waiting can't fail.
+        let _ = receiver.wait_for(|stopping| *stopping).await;
+    }
+}
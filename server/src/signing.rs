@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use log::{error, warn};
+use time::OffsetDateTime;
+
+use crate::{config::Secret, AppState};
+
+tokio::task_local! {
+    static SIGNED: bool;
+}
+
+/// Signatures of the requests made within the accepted age, each with the
+/// time after which it would be refused as too old anyway.
+#[derive(Default)]
+pub struct Signatures {
+    seen: Mutex<HashMap<String, i64>>,
+}
+
+impl Signatures {
+    /// Remember the signature, `false` if it was already used.
+    fn first_use(&self, signature: &str, expires: i64, now: i64) -> bool {
+        let Ok(mut seen) = self.seen.lock() else {
+            return false;
+        };
+        seen.retain(|_, expires| *expires >= now);
+        seen.insert(signature.to_string(), expires).is_none()
+    }
+}
+
+fn unauthorized(reason: &str) -> Response {
+    warn!("Authentication error: {}", reason);
+    let error = shared::ApiError {
+        code: shared::ApiErrorCode::Unauthenticated,
+        message: Some(reason.to_string()),
+    };
+    (StatusCode::UNAUTHORIZED, Json(error)).into_response()
+}
+
+/// Check the signature of requests that have one and handle them as made
+/// with the shared secret if it is valid. Requests without a signature are
+/// passed on as they are.
+pub async fn verify(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let headers = request.headers();
+    let Some(signature) = headers.get(shared::SIGNATURE_HEADER) else {
+        return SIGNED.scope(false, next.run(request)).await;
+    };
+    let Some(signature) = signature
+        .to_str()
+        .ok()
+        .and_then(|signature| signature.strip_prefix("sha256="))
+        // Lowercase so that the same signature in another case is seen as
+        // used already.
+        .map(str::to_ascii_lowercase)
+    else {
+        return unauthorized("signature is not sha256=<hex>");
+    };
+    let Some(timestamp) = headers
+        .get(shared::SIGNATURE_TIMESTAMP_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<i64>().ok())
+    else {
+        return unauthorized("signed request has no timestamp");
+    };
+    let (key, maximum_age, body_limit) = match state.config.read() {
+        Ok(config) => {
+            // The key is derived from the secret itself, so a server that
+            // only has its hash can't check signatures.
+            let key = match &config.secret {
+                Some(Secret::Plain(secret)) => Ok(shared::signing::key(secret)),
+                Some(Secret::Sha256(_)) => {
+                    Err("request is signed but the server only has the hash of the secret")
+                }
+                None => Err("request is signed but no secret is set"),
+            };
+            let limits = &config.body_limits;
+            let body_limit = limits.default.max(limits.submit).max(limits.backfill);
+            (key, config.signatures.maximum_age, body_limit)
+        }
+        Err(_) => {
+            error!("Authentication error: cannot read configuration");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let key = match key {
+        Ok(key) => key,
+        Err(reason) => return unauthorized(reason),
+    };
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    if timestamp.abs_diff(now) > maximum_age {
+        return unauthorized("signed request is too old or from the future");
+    }
+
+    let path = request
+        .uri()
+        .path_and_query()
+        .map_or_else(|| request.uri().path().to_string(), ToString::to_string);
+    let method = request.method().to_string();
+    let (parts, body) = request.into_parts();
+    let Ok(body) = to_bytes(body, body_limit).await else {
+        let error = shared::ApiError {
+            code: shared::ApiErrorCode::PayloadTooLarge,
+            message: Some("Signed request body is larger than the server accepts".to_string()),
+        };
+        return (StatusCode::PAYLOAD_TOO_LARGE, Json(error)).into_response();
+    };
+    if !shared::signing::verify(&key, timestamp, &method, &path, &body, &signature) {
+        return unauthorized("signature does not match");
+    }
+    if !state.signatures.first_use(
+        &signature,
+        timestamp.saturating_add_unsigned(maximum_age),
+        now,
+    ) {
+        return unauthorized("signed request was already made");
+    }
+    let request = Request::from_parts(parts, Body::from(body));
+    SIGNED.scope(true, next.run(request)).await
+}
+
+/// The request being handled has a valid signature made with the shared
+/// secret.
+pub fn signed() -> bool {
+    SIGNED.try_with(|signed| *signed).unwrap_or(false)
+}
@@ -0,0 +1,760 @@
+use std::collections::{BTreeSet, HashMap};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Extension, Json,
+};
+use log::error;
+use time::{Date, OffsetDateTime};
+use time_tz::{OffsetDateTimeExt, TimeZone};
+
+use crate::{authorize_read, db::Database, util, AppState, Caller};
+
+/// Records given overall when the request doesn't give a limit.
+const RECORDS: u32 = 5;
+
+/// Most records given overall for a single request.
+const MAXIMUM_RECORDS: u32 = 100;
+
+/// Most played games in the year in review.
+const YEAR_TOP_GAMES: usize = 10;
+
+/// Total playtime per process, most played first.
+#[utoipa::path(
+    get,
+    path = "/stats",
+    params(shared::StatsQuery),
+    responses(
+        (status = 200, description = "Playtime per process", body = Vec<shared::ProcessStats>),
+        (status = 401, description = "Not authenticated"),
+    ),
+)]
+pub async fn stats(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    authorized: Option<Extension<Caller>>,
+    Query(query): Query<shared::StatsQuery>,
+) -> Result<Json<Vec<shared::ProcessStats>>, StatusCode> {
+    let Some(caller) = authorize_read(authorized, &headers, &state).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let by_device = query.by_device;
+    let grouping = query.group_by.unwrap_or_else(|| stats_grouping(&state));
+    let mut stats = match state.db.stats(query, caller.user_id).await {
+        Ok(stats) => stats,
+        Err(error) => {
+            error!("Could not query stats: {}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    if !by_device {
+        stats = merge_devices(stats);
+    }
+    let stats = util::group_stats(stats, grouping);
+    Ok(Json(with_metadata(&state.db, stats).await))
+}
+
+/// What stats are totalled by when the request doesn't say.
+pub fn stats_grouping(state: &AppState) -> shared::StatsGrouping {
+    match state.config.read() {
+        Ok(config) => config.stats_grouping,
+        Err(_) => {
+            error!("Could not read configuration, grouping stats by process");
+            shared::StatsGrouping::default()
+        }
+    }
+}
+
+/// Total playtime per category, most played first.
+#[utoipa::path(
+    get,
+    path = "/stats/categories",
+    params(shared::StatsQuery),
+    responses(
+        (status = 200, description = "Playtime per category", body = Vec<shared::CategoryStats>),
+        (status = 401, description = "Not authenticated"),
+    ),
+)]
+pub async fn categories(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    authorized: Option<Extension<Caller>>,
+    Query(query): Query<shared::StatsQuery>,
+) -> Result<Json<Vec<shared::CategoryStats>>, StatusCode> {
+    let Some(caller) = authorize_read(authorized, &headers, &state).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    match state.db.category_stats(query, caller.user_id).await {
+        Ok(stats) => Ok(Json(stats)),
+        Err(error) => {
+            error!("Could not query category stats: {}", error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Total playtime per device, most played first, for comparing the devices
+/// that games are played on.
+#[utoipa::path(
+    get,
+    path = "/stats/devices",
+    params(shared::StatsQuery),
+    responses(
+        (status = 200, description = "Playtime per device", body = Vec<shared::DeviceStats>),
+        (status = 401, description = "Not authenticated"),
+    ),
+)]
+pub async fn devices(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    authorized: Option<Extension<Caller>>,
+    Query(query): Query<shared::StatsQuery>,
+) -> Result<Json<Vec<shared::DeviceStats>>, StatusCode> {
+    let Some(caller) = authorize_read(authorized, &headers, &state).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let query = shared::StatsQuery {
+        by_device: true,
+        ..query
+    };
+    match state.db.stats(query, caller.user_id).await {
+        Ok(stats) => Ok(Json(util::device_totals(&stats))),
+        Err(error) => {
+            error!("Could not query device stats: {}", error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Playtime per day, week or month in the time zone of the caller, oldest
+/// first.
+#[utoipa::path(
+    get,
+    path = "/stats/histogram",
+    params(shared::HistogramQuery),
+    responses(
+        (status = 200, description = "Playtime per bucket", body = Vec<shared::HistogramBucket>),
+        (status = 401, description = "Not authenticated"),
+    ),
+)]
+pub async fn histogram(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    authorized: Option<Extension<Caller>>,
+    Query(query): Query<shared::HistogramQuery>,
+) -> Result<Json<Vec<shared::HistogramBucket>>, StatusCode> {
+    let Some(caller) = authorize_read(authorized, &headers, &state).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let until = query.until.unwrap_or_else(OffsetDateTime::now_utc);
+    let stats_query = shared::StatsQuery {
+        since: query.since,
+        until: Some(until),
+        process: query.process,
+        device: query.device,
+        by_device: false,
+        group_by: None,
+    };
+    let sessions = match state.db.session_times(stats_query, caller.user_id).await {
+        Ok(sessions) => sessions
+            .into_iter()
+            .map(|(_, _, time, duration)| (time, duration))
+            .collect::<Vec<_>>(),
+        Err(error) => {
+            error!("Could not query session times: {}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let timezone = state.timezones.get(caller.user_id);
+    Ok(Json(util::histogram(
+        &sessions,
+        query.bucket,
+        query.since,
+        until,
+        timezone,
+    )))
+}
+
+/// Current and longest runs of consecutive days in the sorted days, and the
+/// number of days in the month of `today`. Days after today are ignored, and
+/// the current streak is kept until a whole day passes without one.
+fn streak<'a>(days: impl IntoIterator<Item = &'a Date>, today: Date) -> shared::Streak {
+    let mut streak = shared::Streak::default();
+    let mut run = 0;
+    let mut previous: Option<Date> = None;
+    for day in days.into_iter().filter(|day| **day <= today) {
+        if previous == Some(*day) {
+            continue;
+        }
+        run = if previous.and_then(Date::next_day) == Some(*day) {
+            run + 1
+        } else {
+            1
+        };
+        streak.longest = streak.longest.max(run);
+        if day.year() == today.year() && day.month() == today.month() {
+            streak.days_this_month += 1;
+        }
+        previous = Some(*day);
+    }
+    if previous.is_some_and(|last| last == today || last.next_day() == Some(today)) {
+        streak.current = run;
+    }
+    streak
+}
+
+/// Current and longest daily streaks of the caller, overall and per game,
+/// in the time zone of the caller.
+#[utoipa::path(
+    get,
+    path = "/stats/streaks",
+    params(shared::StreaksQuery),
+    responses(
+        (status = 200, description = "Daily streaks", body = shared::Streaks),
+        (status = 401, description = "Not authenticated"),
+    ),
+)]
+pub async fn streaks(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    authorized: Option<Extension<Caller>>,
+    Query(query): Query<shared::StreaksQuery>,
+) -> Result<Json<shared::Streaks>, StatusCode> {
+    let Some(caller) = authorize_read(authorized, &headers, &state).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let stats_query = shared::StatsQuery {
+        device: query.device,
+        ..Default::default()
+    };
+    let sessions = match state.db.session_times(stats_query, caller.user_id).await {
+        Ok(sessions) => sessions,
+        Err(error) => {
+            error!("Could not query session times: {}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let timezone = state.timezones.get(caller.user_id);
+    let today = OffsetDateTime::now_utc().to_timezone(timezone).date();
+    let mut days = BTreeSet::new();
+    let mut game_days: HashMap<(String, Option<String>), BTreeSet<Date>> = HashMap::new();
+    for (executable, name, time, _) in sessions {
+        let day = time.to_timezone(timezone).date();
+        days.insert(day);
+        game_days.entry((executable, name)).or_default().insert(day);
+    }
+    let mut games = game_days
+        .into_iter()
+        .map(|((executable, name), days)| shared::GameStreak {
+            executable,
+            name,
+            streak: streak(&days, today),
+        })
+        .collect::<Vec<_>>();
+    games.sort_by(|a, b| {
+        (b.streak.current, b.streak.longest)
+            .cmp(&(a.streak.current, a.streak.longest))
+            .then_with(|| a.executable.cmp(&b.executable))
+    });
+    Ok(Json(shared::Streaks {
+        timezone: timezone.name().to_string(),
+        overall: streak(&days, today),
+        games,
+    }))
+}
+
+/// Playtime per day of a calendar year in the time zone of the caller, for
+/// rendering as a heatmap, optionally per game.
+#[utoipa::path(
+    get,
+    path = "/stats/heatmap",
+    params(shared::HeatmapQuery),
+    responses(
+        (status = 200, description = "Playtime per day of the year", body = shared::Heatmap),
+        (status = 400, description = "Year out of range"),
+        (status = 401, description = "Not authenticated"),
+    ),
+)]
+pub async fn heatmap(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    authorized: Option<Extension<Caller>>,
+    Query(query): Query<shared::HeatmapQuery>,
+) -> Result<Json<shared::Heatmap>, StatusCode> {
+    let Some(caller) = authorize_read(authorized, &headers, &state).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let timezone = state.timezones.get(caller.user_id);
+    let Some((since, until)) = util::year_range(query.year, timezone) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let stats_query = shared::StatsQuery {
+        since: Some(since),
+        until: Some(until),
+        process: query.process,
+        device: query.device,
+        by_device: false,
+        group_by: None,
+    };
+    let sessions = match state.db.session_times(stats_query, caller.user_id).await {
+        Ok(sessions) => sessions,
+        Err(error) => {
+            error!("Could not query session times: {}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let mut games = Vec::new();
+    if query.games {
+        let mut game_sessions: HashMap<(String, Option<String>), Vec<_>> = HashMap::new();
+        for (executable, name, time, duration) in &sessions {
+            game_sessions
+                .entry((executable.clone(), name.clone()))
+                .or_default()
+                .push((*time, *duration));
+        }
+        games = game_sessions
+            .into_iter()
+            .map(|((executable, name), sessions)| shared::HeatmapGame {
+                executable,
+                name,
+                duration: sessions.iter().map(|(_, duration)| duration).sum(),
+                days: util::heatmap(&sessions, query.year, timezone),
+            })
+            .collect::<Vec<_>>();
+        games.sort_by(|a, b| {
+            b.duration
+                .cmp(&a.duration)
+                .then_with(|| a.executable.cmp(&b.executable))
+        });
+    }
+    let sessions = sessions
+        .into_iter()
+        .map(|(_, _, time, duration)| (time, duration))
+        .collect::<Vec<_>>();
+    let days = util::heatmap(&sessions, query.year, timezone);
+    Ok(Json(shared::Heatmap {
+        year: query.year,
+        timezone: timezone.name().to_string(),
+        maximum: days.iter().copied().max().unwrap_or_default(),
+        days,
+        games,
+    }))
+}
+
+/// Longest sessions and most played days and weeks, overall and per game, in
+/// the time zone of the caller.
+#[utoipa::path(
+    get,
+    path = "/stats/records",
+    params(shared::RecordsQuery),
+    responses(
+        (status = 200, description = "Records overall and per game", body = shared::Records),
+        (status = 401, description = "Not authenticated"),
+    ),
+)]
+pub async fn records(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    authorized: Option<Extension<Caller>>,
+    Query(query): Query<shared::RecordsQuery>,
+) -> Result<Json<shared::Records>, StatusCode> {
+    let Some(caller) = authorize_read(authorized, &headers, &state).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let stats_query = shared::StatsQuery {
+        process: query.process,
+        device: query.device,
+        ..Default::default()
+    };
+    let sessions = match state.db.session_times(stats_query, caller.user_id).await {
+        Ok(sessions) => sessions,
+        Err(error) => {
+            error!("Could not query session times: {}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let timezone = state.timezones.get(caller.user_id);
+    let count = query.limit.unwrap_or(RECORDS).clamp(1, MAXIMUM_RECORDS) as usize;
+    let mut longest_sessions = sessions
+        .iter()
+        .map(|(executable, name, time, duration)| shared::SessionRecord {
+            executable: executable.clone(),
+            name: name.clone(),
+            ended_at: *time,
+            duration: *duration,
+        })
+        .collect::<Vec<_>>();
+    longest_sessions.sort_by(|a, b| {
+        b.duration
+            .cmp(&a.duration)
+            .then(a.ended_at.cmp(&b.ended_at))
+    });
+    let mut game_sessions: HashMap<(String, Option<String>), Vec<_>> = HashMap::new();
+    for session in &longest_sessions {
+        game_sessions
+            .entry((session.executable.clone(), session.name.clone()))
+            .or_default()
+            .push((session.ended_at, session.duration));
+    }
+    let mut games = Vec::new();
+    for ((executable, name), sessions) in game_sessions {
+        let days = util::top_periods(&sessions, shared::SummaryPeriod::Day, timezone, 1);
+        let weeks = util::top_periods(&sessions, shared::SummaryPeriod::Week, timezone, 1);
+        let (Some(best_day), Some(best_week)) = (days.into_iter().next(), weeks.into_iter().next())
+        else {
+            continue;
+        };
+        // Sessions of a game are in the order of the longest sessions.
+        let (ended_at, duration) = sessions[0];
+        games.push(shared::GameRecords {
+            longest_session: shared::SessionRecord {
+                executable: executable.clone(),
+                name: name.clone(),
+                ended_at,
+                duration,
+            },
+            executable,
+            name,
+            duration: sessions.iter().map(|(_, duration)| duration).sum(),
+            best_day,
+            best_week,
+        });
+    }
+    games.sort_by(|a, b| {
+        b.duration
+            .cmp(&a.duration)
+            .then_with(|| a.executable.cmp(&b.executable))
+    });
+    let sessions = sessions
+        .into_iter()
+        .map(|(_, _, time, duration)| (time, duration))
+        .collect::<Vec<_>>();
+    longest_sessions.truncate(count);
+    Ok(Json(shared::Records {
+        timezone: timezone.name().to_string(),
+        longest_sessions,
+        days: util::top_periods(&sessions, shared::SummaryPeriod::Day, timezone, count),
+        weeks: util::top_periods(&sessions, shared::SummaryPeriod::Week, timezone, count),
+        games,
+    }))
+}
+
+/// Total playtime, most played games, busiest month, longest session and
+/// newly played games of a calendar year in the time zone of the caller.
+#[utoipa::path(
+    get,
+    path = "/stats/year/{year}",
+    params(("year" = i32, Path, description = "Calendar year, such as 2024")),
+    responses(
+        (status = 200, description = "Review of the year", body = shared::YearInReview),
+        (status = 400, description = "Year out of range"),
+        (status = 401, description = "Not authenticated"),
+    ),
+)]
+pub async fn year(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    authorized: Option<Extension<Caller>>,
+    Path(year): Path<i32>,
+) -> Result<Json<shared::YearInReview>, StatusCode> {
+    let Some(caller) = authorize_read(authorized, &headers, &state).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let timezone = state.timezones.get(caller.user_id);
+    let Some((since, until)) = util::year_range(year, timezone) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let stats_query = || shared::StatsQuery {
+        since: Some(since),
+        until: Some(until),
+        ..Default::default()
+    };
+    let games = match state.db.stats(stats_query(), caller.user_id).await {
+        Ok(games) => with_metadata(&state.db, merge_devices(games)).await,
+        Err(error) => {
+            error!("Could not query stats: {}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let sessions = match state.db.session_times(stats_query(), caller.user_id).await {
+        Ok(sessions) => sessions,
+        Err(error) => {
+            error!("Could not query session times: {}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let longest_session = sessions
+        .iter()
+        .max_by(|a, b| a.3.cmp(&b.3).then(b.2.cmp(&a.2)))
+        .map(|(executable, name, time, duration)| shared::SessionRecord {
+            executable: executable.clone(),
+            name: name.clone(),
+            ended_at: *time,
+            duration: *duration,
+        });
+    let sessions = sessions
+        .into_iter()
+        .map(|(_, _, time, duration)| (time, duration))
+        .collect::<Vec<_>>();
+    let busiest_month = util::top_periods(&sessions, shared::SummaryPeriod::Month, timezone, 1)
+        .into_iter()
+        .next();
+    let mut new_games = games
+        .iter()
+        .filter(|game| {
+            game.first_played
+                .is_some_and(|first_played| since <= first_played && first_played < until)
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    new_games.sort_by_key(|game| game.first_played);
+    Ok(Json(shared::YearInReview {
+        year,
+        timezone: timezone.name().to_string(),
+        duration: games.iter().map(|game| game.duration).sum(),
+        sessions: games.iter().map(|game| game.sessions).sum(),
+        games: games.len() as u64,
+        top_games: games.into_iter().take(YEAR_TOP_GAMES).collect(),
+        busiest_month,
+        longest_session,
+        new_games,
+    }))
+}
+
+/// Total and per game playtime in a range next to another range, such as this
+/// month and last month.
+#[utoipa::path(
+    get,
+    path = "/stats/compare",
+    params(shared::ComparisonQuery),
+    responses(
+        (status = 200, description = "Playtime in both ranges", body = shared::Comparison),
+        (status = 400, description = "Range ends before it starts"),
+        (status = 401, description = "Not authenticated"),
+    ),
+)]
+pub async fn compare(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    authorized: Option<Extension<Caller>>,
+    Query(query): Query<shared::ComparisonQuery>,
+) -> Result<Json<shared::Comparison>, StatusCode> {
+    let Some(caller) = authorize_read(authorized, &headers, &state).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    if query.until < query.since || query.previous_until < query.previous_since {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let mut stats = Vec::new();
+    for (since, until) in [
+        (query.since, query.until),
+        (query.previous_since, query.previous_until),
+    ] {
+        let stats_query = shared::StatsQuery {
+            since: Some(since),
+            until: Some(until),
+            process: query.process.clone(),
+            device: query.device.clone(),
+            by_device: false,
+            group_by: None,
+        };
+        match state.db.stats(stats_query, caller.user_id).await {
+            Ok(range) => stats.push(range),
+            Err(error) => {
+                error!("Could not query stats: {}", error);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        }
+    }
+    let previous = stats.pop().unwrap_or_default();
+    let current = stats.pop().unwrap_or_default();
+    let games = util::compare_games(current, previous);
+    let duration = games.iter().map(|game| game.duration).sum();
+    let previous_duration = games.iter().map(|game| game.previous_duration).sum();
+    Ok(Json(shared::Comparison {
+        since: query.since,
+        until: query.until,
+        previous_since: query.previous_since,
+        previous_until: query.previous_until,
+        duration,
+        sessions: games.iter().map(|game| game.sessions).sum(),
+        previous_duration,
+        previous_sessions: games.iter().map(|game| game.previous_sessions).sum(),
+        change: util::percent_change(duration, previous_duration),
+        games,
+    }))
+}
+
+/// Total playtime per process next to how long the game takes to beat on
+/// HowLongToBeat, most played first. Processes that haven't been found on
+/// HowLongToBeat are left out.
+#[utoipa::path(
+    get,
+    path = "/stats/completion",
+    params(shared::StatsQuery),
+    responses(
+        (status = 200, description = "Playtime and time to beat per process", body = Vec<shared::CompletionStats>),
+        (status = 401, description = "Not authenticated"),
+    ),
+)]
+pub async fn completion(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    authorized: Option<Extension<Caller>>,
+    Query(query): Query<shared::StatsQuery>,
+) -> Result<Json<Vec<shared::CompletionStats>>, StatusCode> {
+    let Some(caller) = authorize_read(authorized, &headers, &state).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let stats = match state.db.stats(query, caller.user_id).await {
+        Ok(stats) => merge_devices(stats),
+        Err(error) => {
+            error!("Could not query stats: {}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let mut times = match state.db.completion_times().await {
+        Ok(times) => times
+            .into_iter()
+            .map(|(executable, name, time)| ((executable, name), time))
+            .collect::<HashMap<_, _>>(),
+        Err(error) => {
+            error!("Could not query completion times: {}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let completion = stats
+        .into_iter()
+        .filter_map(|process| {
+            let time = times.remove(&(process.executable.clone(), process.name.clone()))?;
+            Some(shared::CompletionStats {
+                main_story_ratio: time
+                    .main_story
+                    .map(|main_story| process.duration as f64 / main_story as f64),
+                executable: process.executable,
+                name: process.name,
+                duration: process.duration,
+                game: time.game,
+                main_story: time.main_story,
+                main_extra: time.main_extra,
+                completionist: time.completionist,
+            })
+        })
+        .collect();
+    Ok(Json(completion))
+}
+
+/// Everyone's total playtime of the processes marked for export, without
+/// authentication. Device and user filters are not available.
+#[utoipa::path(
+    get,
+    path = "/public/stats",
+    params(shared::StatsQuery),
+    responses(
+        (status = 200, description = "Playtime per exported process", body = Vec<shared::ProcessStats>),
+    ),
+    security(()),
+)]
+pub async fn public(
+    State(state): State<AppState>,
+    Query(query): Query<shared::StatsQuery>,
+) -> Result<Json<Vec<shared::ProcessStats>>, StatusCode> {
+    let grouping = query.group_by.unwrap_or_else(|| stats_grouping(&state));
+    let query = shared::StatsQuery {
+        since: query.since,
+        until: query.until,
+        process: query.process,
+        device: None,
+        by_device: false,
+        group_by: None,
+    };
+    match state.db.public_stats(query).await {
+        Ok(stats) => {
+            let stats = util::group_stats(merge_devices(stats), grouping);
+            Ok(Json(with_metadata(&state.db, stats).await))
+        }
+        Err(error) => {
+            error!("Could not query public stats: {}", error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Stats with the details of the games found on IGDB. The stats are given
+/// without details if they can't be read.
+pub async fn with_metadata(
+    db: &Database,
+    mut stats: Vec<shared::ProcessStats>,
+) -> Vec<shared::ProcessStats> {
+    let metadata = match db.game_metadata().await {
+        Ok(metadata) => metadata
+            .into_iter()
+            .map(|(executable, name, metadata)| ((executable, name), metadata))
+            .collect::<HashMap<_, _>>(),
+        Err(error) => {
+            error!("Could not query game details: {}", error);
+            return stats;
+        }
+    };
+    for process in &mut stats {
+        let key = (process.executable.clone(), process.name.clone());
+        process.metadata = metadata.get(&key).cloned();
+    }
+    stats
+}
+
+/// Combine the per-device playtimes of each process, most played first.
+pub fn merge_devices(stats: Vec<shared::ProcessStats>) -> Vec<shared::ProcessStats> {
+    let mut merged: Vec<shared::ProcessStats> = Vec::new();
+    for process in stats {
+        let existing = merged
+            .iter_mut()
+            .find(|other| other.executable == process.executable && other.name == process.name);
+        match existing {
+            Some(existing) => {
+                existing.duration += process.duration;
+                existing.sessions += process.sessions;
+                existing.first_played = existing
+                    .first_played
+                    .into_iter()
+                    .chain(process.first_played)
+                    .min();
+                existing.last_played = existing
+                    .last_played
+                    .into_iter()
+                    .chain(process.last_played)
+                    .max();
+            }
+            None => merged.push(shared::ProcessStats {
+                device: None,
+                ..process
+            }),
+        }
+    }
+    merged.sort_by_key(|process| std::cmp::Reverse(process.duration));
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    // Days of 2024, where 70 is the 10th of March.
+    #[test_case(vec![], 70, (0, 0, 0); "nothing played")]
+    #[test_case(vec![68, 69, 70], 70, (3, 3, 3); "played today")]
+    #[test_case(vec![68, 69], 70, (2, 2, 2); "played yesterday")]
+    #[test_case(vec![61, 62, 63, 67], 70, (0, 3, 4); "broken")]
+    #[test_case(vec![58, 59, 60, 61, 70], 70, (1, 4, 2); "across months")]
+    #[test_case(vec![69, 69, 70], 70, (2, 2, 2); "several sessions a day")]
+    #[test_case(vec![70, 71], 70, (1, 1, 1); "future")]
+    fn streak(days: Vec<u16>, today: u16, output: (u64, u64, u64)) {
+        let date = |day| time::Date::from_ordinal_date(2024, day).unwrap();
+        let days: Vec<time::Date> = days.into_iter().map(date).collect();
+        let streak = super::streak(&days, date(today));
+        assert_eq!(
+            (streak.current, streak.longest, streak.days_this_month),
+            output
+        );
+    }
+}
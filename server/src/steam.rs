@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+use shared::SessionDuration;
+use time::OffsetDateTime;
+
+use crate::db::NewEvent;
+
+const OWNED_GAMES_URL: &str = "https://api.steampowered.com/IPlayerService/GetOwnedGames/v1/";
+
+/// Time to wait for the Steam Web API.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Period that `playtime_2weeks` covers.
+const RECENT_PERIOD: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
+#[derive(Deserialize)]
+struct OwnedGamesResponse {
+    response: OwnedGames,
+}
+
+#[derive(Deserialize)]
+struct OwnedGames {
+    /// Missing when the profile or its game details are private.
+    games: Option<Vec<Game>>,
+}
+
+/// Game owned by a Steam account. Playtime is in minutes.
+#[derive(Deserialize)]
+pub struct Game {
+    appid: u32,
+    name: Option<String>,
+    playtime_forever: u64,
+    #[serde(default)]
+    playtime_2weeks: u64,
+
+    /// Unix time, or 0 if the game was never played or Steam doesn't know.
+    #[serde(default)]
+    rtime_last_played: i64,
+}
+
+/// Games owned by the account, including free games that have been played.
+pub async fn owned_games(api_key: &str, steam_id: &str) -> Result<Vec<Game>, String> {
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("beelzebub-server/", env!("CARGO_PKG_VERSION")))
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|error| error.to_string())?;
+    let response = client
+        .get(OWNED_GAMES_URL)
+        .query(&[
+            ("key", api_key),
+            ("steamid", steam_id),
+            ("include_appinfo", "true"),
+            ("include_played_free_games", "true"),
+        ])
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|error| error.without_url().to_string())?
+        .json::<OwnedGamesResponse>()
+        .await
+        .map_err(|error| error.to_string())?;
+    response
+        .response
+        .games
+        .ok_or_else(|| "game details of the profile are not public".to_string())
+}
+
+fn backfill_event(game: &Game, key: String, ended_at: OffsetDateTime, minutes: u64) -> NewEvent {
+    let duration = minutes * 60;
+    NewEvent {
+        executable: format!("steam:{}", game.appid),
+        name: game.name.clone(),
+        time: ended_at,
+        started_at: ended_at - Duration::from_secs(duration),
+        duration: SessionDuration::from_secs(duration),
+        focused_duration: None,
+        product_version: None,
+        file_version: None,
+        tags: vec!["steam".to_string()],
+        window_title: None,
+        api_key: None,
+        user_id: None,
+        device: None,
+        idempotency_key: Some(key),
+        imported: true,
+        manual: false,
+        note: None,
+    }
+}
+
+/// Sessions covering the playtime of the games up to `now`: one for the
+/// playtime of the last two weeks, ending when the game was last played, and
+/// one for the playtime before that. Their start and end times are only as
+/// accurate as Steam's data allows.
+pub fn backfill(games: &[Game], steam_id: &str, now: OffsetDateTime) -> Vec<NewEvent> {
+    let two_weeks_ago = now - RECENT_PERIOD;
+    let mut events = Vec::new();
+    for game in games {
+        let last_played = OffsetDateTime::from_unix_timestamp(game.rtime_last_played)
+            .ok()
+            .filter(|time| game.rtime_last_played > 0 && *time <= now);
+        let recent = game.playtime_2weeks.min(game.playtime_forever);
+        let earlier = game.playtime_forever - recent;
+        if recent > 0 {
+            events.push(backfill_event(
+                game,
+                format!("steam:{}:{}:recent", steam_id, game.appid),
+                last_played.unwrap_or(now),
+                recent,
+            ));
+        }
+        if earlier > 0 {
+            let ended_at = last_played
+                .filter(|time| recent == 0 && *time < two_weeks_ago)
+                .unwrap_or(two_weeks_ago);
+            events.push(backfill_event(
+                game,
+                format!("steam:{}:{}", steam_id, game.appid),
+                ended_at,
+                earlier,
+            ));
+        }
+    }
+    events
+}
@@ -0,0 +1,127 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::db::{Database, Error, NewAuditEntry, NewEvent};
+
+/// Future of a storage method, boxed so that storage can be shared as
+/// `dyn Storage`.
+pub type StorageFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, Error>> + Send + 'a>>;
+
+/// What recording sessions needs of the database. Handlers that record
+/// sessions go through it instead of `Database`, so that they don't depend on
+/// Diesel and can be tested against the in-memory storage.
+pub trait Storage: Send + Sync {
+    /// Save the event unless one with the same idempotency key exists.
+    /// Returns whether the event was saved.
+    fn save_event(&self, event: NewEvent) -> StorageFuture<'_, bool>;
+
+    /// Save the event unless it duplicates an existing one, see
+    /// `Database::save_submission`. Returns whether the event was saved.
+    fn save_submission(&self, event: NewEvent, tolerance: u64) -> StorageFuture<'_, bool>;
+
+    /// Names of the processes of the executable.
+    fn process_names(&self, executable: String) -> StorageFuture<'_, Vec<Option<String>>>;
+
+    /// Delete the heartbeat of the session. Returns the number deleted.
+    fn delete_heartbeat(&self, session_id: String) -> StorageFuture<'_, usize>;
+
+    fn record_audit(&self, entry: NewAuditEntry) -> StorageFuture<'_, ()>;
+}
+
+impl Storage for Database {
+    fn save_event(&self, event: NewEvent) -> StorageFuture<'_, bool> {
+        Box::pin(Database::save_event(self, event))
+    }
+
+    fn save_submission(&self, event: NewEvent, tolerance: u64) -> StorageFuture<'_, bool> {
+        Box::pin(Database::save_submission(self, event, tolerance))
+    }
+
+    fn process_names(&self, executable: String) -> StorageFuture<'_, Vec<Option<String>>> {
+        Box::pin(Database::process_names(self, executable))
+    }
+
+    fn delete_heartbeat(&self, session_id: String) -> StorageFuture<'_, usize> {
+        Box::pin(Database::delete_heartbeat(self, session_id))
+    }
+
+    fn record_audit(&self, entry: NewAuditEntry) -> StorageFuture<'_, ()> {
+        Box::pin(Database::record_audit(self, entry))
+    }
+}
+
+/// Storage that keeps everything in memory, for testing handlers without a
+/// database. Processes are told apart by their executable and name.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStorage {
+    pub events: std::sync::Mutex<Vec<NewEvent>>,
+    pub audit_log: std::sync::Mutex<Vec<NewAuditEntry>>,
+    pub heartbeats: std::sync::Mutex<Vec<String>>,
+}
+
+#[cfg(test)]
+impl MemoryStorage {
+    /// Event of the same process, device, API key and user that overlaps the
+    /// event like `overlapping` in the database backends.
+    fn overlapping(&self, event: &NewEvent, tolerance: u64) -> bool {
+        let tolerance = std::time::Duration::from_secs(tolerance);
+        let events = self.events.lock().unwrap();
+        events.iter().any(|other| {
+            other.executable == event.executable
+                && other.name == event.name
+                && other.device == event.device
+                && other.api_key == event.api_key
+                && other.user_id == event.user_id
+                && ((other.started_at < event.time - tolerance
+                    && other.time > event.started_at + tolerance)
+                    || ((other.started_at - event.started_at).abs() <= tolerance
+                        && (other.time - event.time).abs() <= tolerance))
+        })
+    }
+}
+
+#[cfg(test)]
+impl Storage for MemoryStorage {
+    fn save_event(&self, event: NewEvent) -> StorageFuture<'_, bool> {
+        let mut events = self.events.lock().unwrap();
+        let duplicate = event.idempotency_key.is_some()
+            && events
+                .iter()
+                .any(|other| other.idempotency_key == event.idempotency_key);
+        if !duplicate {
+            events.push(event);
+        }
+        Box::pin(std::future::ready(Ok(!duplicate)))
+    }
+
+    fn save_submission(&self, event: NewEvent, tolerance: u64) -> StorageFuture<'_, bool> {
+        if self.overlapping(&event, tolerance) {
+            return Box::pin(std::future::ready(Ok(false)));
+        }
+        self.save_event(event)
+    }
+
+    fn process_names(&self, executable: String) -> StorageFuture<'_, Vec<Option<String>>> {
+        let events = self.events.lock().unwrap();
+        let mut names = Vec::new();
+        for event in events.iter().filter(|event| event.executable == executable) {
+            if !names.contains(&event.name) {
+                names.push(event.name.clone());
+            }
+        }
+        Box::pin(std::future::ready(Ok(names)))
+    }
+
+    fn delete_heartbeat(&self, session_id: String) -> StorageFuture<'_, usize> {
+        let mut heartbeats = self.heartbeats.lock().unwrap();
+        let count = heartbeats.len();
+        heartbeats.retain(|heartbeat| *heartbeat != session_id);
+        Box::pin(std::future::ready(Ok(count - heartbeats.len())))
+    }
+
+    fn record_audit(&self, entry: NewAuditEntry) -> StorageFuture<'_, ()> {
+        self.audit_log.lock().unwrap().push(entry);
+        Box::pin(std::future::ready(Ok(())))
+    }
+}
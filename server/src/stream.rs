@@ -0,0 +1,87 @@
+use std::convert::Infallible;
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::{stream, Stream};
+use log::{error, warn};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::{authorize, config::Scope, webhooks::Session, AppState};
+
+/// Updates kept for each connection before a slow one starts missing them.
+pub const CAPACITY: usize = 256;
+
+/// Change pushed to everyone connected to `/stream`.
+#[derive(Clone)]
+pub enum Update {
+    /// A session was recorded.
+    Session {
+        user_id: Option<i32>,
+        session: Session,
+    },
+}
+
+impl Update {
+    fn user_id(&self) -> Option<i32> {
+        match self {
+            Update::Session { user_id, .. } => *user_id,
+        }
+    }
+
+    fn event(&self) -> Result<Event, axum::Error> {
+        match self {
+            Update::Session { session, .. } => Event::default().event("session").json_data(session),
+        }
+    }
+}
+
+/// Send the update to the connected clients, if there are any.
+pub fn publish(sender: &broadcast::Sender<Update>, update: Update) {
+    // Fails only when nobody is connected.
+    let _ = sender.send(update);
+}
+
+/// Server-sent events for updates as they happen. Callers limited to a user
+/// only get the updates of the user.
+pub async fn stream(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    let Some(caller) = authorize(&headers, &state, Scope::Read).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let user_id = caller.user_id;
+    let receiver = state.live.subscribe();
+    let shutdown = state.shutdown;
+    let updates = stream::unfold(receiver, move |mut receiver| {
+        let shutdown = shutdown.clone();
+        async move {
+            loop {
+                let received = tokio::select! {
+                    received = receiver.recv() => received,
+                    // Open streams would keep the server from stopping.
+                    _ = shutdown.clone().requested() => return None,
+                };
+                match received {
+                    Ok(update) => {
+                        if user_id.is_some() && update.user_id() != user_id {
+                            continue;
+                        }
+                        match update.event() {
+                            Ok(event) => return Some((Ok(event), receiver)),
+                            Err(error) => error!("Could not serialise update: {}", error),
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Stream client fell behind and missed {} updates", skipped);
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        }
+    });
+    Ok(Sse::new(updates).keep_alive(KeepAlive::default()))
+}
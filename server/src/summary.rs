@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Extension, Json,
+};
+use log::error;
+use time::OffsetDateTime;
+use time_tz::{TimeZone, Tz};
+
+use crate::{authorize_read, db, stats::merge_devices, util, AppState, Caller};
+
+/// Games listed in a summary.
+const TOP_GAMES: usize = 5;
+
+/// Playtime per game between the times, combined across devices.
+async fn games(
+    db: &db::Database,
+    since: OffsetDateTime,
+    until: OffsetDateTime,
+    device: Option<String>,
+    user: Option<i32>,
+) -> Result<Vec<shared::ProcessStats>, db::Error> {
+    let query = shared::StatsQuery {
+        since: Some(since),
+        until: Some(until),
+        process: None,
+        device,
+        by_device: false,
+        group_by: None,
+    };
+    Ok(merge_devices(db.stats(query, user).await?))
+}
+
+/// Summary of the period up to `until` or now for the caller, in the time
+/// zone of the caller.
+pub async fn summarise(
+    db: &db::Database,
+    query: shared::SummaryQuery,
+    user: Option<i32>,
+    timezone: &Tz,
+) -> Result<shared::Summary, db::Error> {
+    let until = query.until.unwrap_or_else(OffsetDateTime::now_utc);
+    // An end at the start of a period belongs to the period before.
+    let since = util::start_of_period(query.period, until - Duration::from_nanos(1), timezone);
+    let previous_since =
+        util::start_of_period(query.period, since - Duration::from_nanos(1), timezone);
+    let previous_until = (previous_since + (until - since)).min(since);
+    let current = games(db, since, until, query.device.clone(), user).await?;
+    let previous = games(db, previous_since, previous_until, query.device, user).await?;
+    let duration = current.iter().map(|game| game.duration).sum();
+    let previous_duration = previous.iter().map(|game| game.duration).sum();
+    Ok(shared::Summary {
+        since,
+        until,
+        timezone: timezone.name().to_string(),
+        duration,
+        sessions: current.iter().map(|game| game.sessions).sum(),
+        games: current.len() as u64,
+        previous_duration,
+        change: util::percent_change(duration, previous_duration),
+        top_games: current.into_iter().take(TOP_GAMES).collect(),
+    })
+}
+
+/// Totals and most played games of the current day, week or month, for
+/// recaps.
+#[utoipa::path(
+    get,
+    path = "/summary",
+    params(shared::SummaryQuery),
+    responses(
+        (status = 200, description = "Summary of the period", body = shared::Summary),
+        (status = 401, description = "Not authenticated"),
+    ),
+)]
+pub async fn summary(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    authorized: Option<Extension<Caller>>,
+    Query(query): Query<shared::SummaryQuery>,
+) -> Result<Json<shared::Summary>, StatusCode> {
+    let Some(caller) = authorize_read(authorized, &headers, &state).await else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let timezone = state.timezones.get(caller.user_id);
+    match summarise(&state.db, query, caller.user_id, timezone).await {
+        Ok(summary) => Ok(Json(summary)),
+        Err(error) => {
+            error!("Could not query summary: {}", error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
@@ -0,0 +1,410 @@
+use std::{
+    fmt::Display,
+    future::Future,
+    sync::OnceLock,
+    time::{Duration, SystemTime},
+};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::{HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use log::{debug, error, warn};
+use ring::rand::{SecureRandom, SystemRandom};
+use tokio::sync::mpsc;
+
+use crate::{config::TracingConfig, util};
+
+/// Spans waiting to be exported. Spans recorded while the queue is full are
+/// dropped rather than slowing requests down.
+const QUEUE_CAPACITY: usize = 8192;
+
+/// Most spans sent in one export request.
+const BATCH_SIZE: usize = 512;
+
+/// Longest time a span waits before it is exported.
+const EXPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Queue of the exporter, unset unless `tracing` is configured.
+static EXPORTER: OnceLock<mpsc::Sender<FinishedSpan>> = OnceLock::new();
+
+/// Trace and span that spans started while handling a request belong to.
+#[derive(Clone, Copy)]
+struct Context {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+}
+
+tokio::task_local! {
+    static CURRENT: Context;
+}
+
+/// OpenTelemetry kind of a span.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpanKind {
+    Internal = 1,
+    Server = 2,
+    Client = 3,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpanValue {
+    String(String),
+    Int(i64),
+}
+
+/// Span that has ended and is waiting to be exported.
+#[derive(Clone, Debug)]
+pub struct FinishedSpan {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+    pub parent_span_id: Option<[u8; 8]>,
+    pub name: String,
+    pub kind: SpanKind,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub attributes: Vec<(&'static str, SpanValue)>,
+
+    /// Why the work failed, if it did.
+    pub error: Option<String>,
+}
+
+fn random_id<const N: usize>() -> Option<[u8; N]> {
+    let mut id = [0; N];
+    SystemRandom::new().fill(&mut id).ok()?;
+    Some(id)
+}
+
+fn record(span: FinishedSpan) {
+    if let Some(exporter) = EXPORTER.get() {
+        if exporter.try_send(span).is_err() {
+            debug!("Dropped a span, the export queue is full");
+        }
+    }
+}
+
+/// Run the future in a span of the trace of the request being handled,
+/// marking the span as failed if `error` finds something wrong with the
+/// output. Outside of traced requests the future is just run.
+async fn child<F: Future>(
+    name: &'static str,
+    kind: SpanKind,
+    future: F,
+    error: impl FnOnce(&F::Output) -> Option<String>,
+) -> F::Output {
+    let Ok(parent) = CURRENT.try_with(|context| *context) else {
+        return future.await;
+    };
+    let Some(span_id) = random_id() else {
+        return future.await;
+    };
+    let start = SystemTime::now();
+    let context = Context {
+        trace_id: parent.trace_id,
+        span_id,
+    };
+    let output = CURRENT.scope(context, future).await;
+    record(FinishedSpan {
+        trace_id: parent.trace_id,
+        span_id,
+        parent_span_id: Some(parent.span_id),
+        name: name.to_string(),
+        kind,
+        start,
+        end: SystemTime::now(),
+        attributes: Vec::new(),
+        error: error(&output),
+    });
+    output
+}
+
+/// Run a step of handling the request in a span of its own, such as
+/// authentication, so that traces show how long each step took.
+pub async fn in_span<F: Future>(name: &'static str, future: F) -> F::Output {
+    child(name, SpanKind::Internal, future, |_| None).await
+}
+
+/// Run a database query in a span named after the query function, marked as
+/// failed if the query fails.
+pub async fn query<T, E: Display>(
+    name: &'static str,
+    future: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    child(name, SpanKind::Client, future, |output| {
+        output.as_ref().err().map(ToString::to_string)
+    })
+    .await
+}
+
+/// Handle the rest of the request in a server span, continuing the trace of
+/// the caller if it sent a `traceparent` header. Requests that the caller
+/// didn't sample aren't traced either.
+pub async fn trace(request: Request, next: Next) -> Response {
+    if EXPORTER.get().is_none() {
+        return next.run(request).await;
+    }
+    let parent = request
+        .headers()
+        .get("traceparent")
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_traceparent);
+    let (trace_id, parent_span_id) = match parent {
+        Some((_, _, false)) => return next.run(request).await,
+        Some((trace_id, span_id, true)) => (Some(trace_id), Some(span_id)),
+        None => (random_id(), None),
+    };
+    let (Some(trace_id), Some(span_id)) = (trace_id, random_id()) else {
+        return next.run(request).await;
+    };
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = request.method().to_string();
+    let start = SystemTime::now();
+    let context = Context { trace_id, span_id };
+    let response = CURRENT.scope(context, next.run(request)).await;
+    let status = response.status();
+    record(FinishedSpan {
+        trace_id,
+        span_id,
+        parent_span_id,
+        name: format!("{} {}", method, route),
+        kind: SpanKind::Server,
+        start,
+        end: SystemTime::now(),
+        attributes: vec![
+            ("http.request.method", SpanValue::String(method)),
+            ("http.route", SpanValue::String(route)),
+            (
+                "http.response.status_code",
+                SpanValue::Int(status.as_u16().into()),
+            ),
+        ],
+        error: status.is_server_error().then(|| status.to_string()),
+    });
+    response
+}
+
+async fn export(client: &reqwest::Client, url: &str, service_name: &str, spans: &[FinishedSpan]) {
+    let body = otlp_traces(service_name, spans);
+    let sent = client
+        .post(url)
+        .json(&body)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    match sent {
+        Ok(_) => debug!("Exported {} spans", spans.len()),
+        Err(error) => warn!(
+            "Could not export {} spans to {}: {}",
+            spans.len(),
+            url,
+            error.without_url()
+        ),
+    }
+}
+
+/// Start recording spans and exporting them in batches to the OTLP/HTTP
+/// receiver of the collector.
+pub fn start(config: TracingConfig) {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &config.headers {
+        match (
+            HeaderName::try_from(name.as_str()),
+            HeaderValue::try_from(value.as_str()),
+        ) {
+            (Ok(name), Ok(value)) => {
+                headers.insert(name, value);
+            }
+            _ => {
+                error!("Could not set up tracing: invalid header {}", name);
+                return;
+            }
+        }
+    }
+    let client = match reqwest::Client::builder()
+        .user_agent(concat!("beelzebub-server/", env!("CARGO_PKG_VERSION")))
+        .default_headers(headers)
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+    {
+        Ok(client) => client,
+        Err(error) => {
+            error!("Could not set up tracing: {}", error);
+            return;
+        }
+    };
+    let (sender, mut receiver) = mpsc::channel(QUEUE_CAPACITY);
+    if EXPORTER.set(sender).is_err() {
+        return;
+    }
+    let url = format!("{}/v1/traces", config.endpoint.trim_end_matches('/'));
+    tokio::spawn(async move {
+        let mut batch = Vec::new();
+        let mut interval = tokio::time::interval(EXPORT_INTERVAL);
+        loop {
+            tokio::select! {
+                span = receiver.recv() => {
+                    let Some(span) = span else {
+                        return;
+                    };
+                    batch.push(span);
+                    if batch.len() < BATCH_SIZE {
+                        continue;
+                    }
+                }
+                _ = interval.tick() => {
+                    if batch.is_empty() {
+                        continue;
+                    }
+                }
+            }
+            export(&client, &url, &config.service_name, &batch).await;
+            batch.clear();
+        }
+    });
+}
+
+/// Trace ID, parent span ID and whether the caller sampled the trace from a
+/// W3C `traceparent` header, e.g.
+/// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`. Versions after
+/// 00 may add fields, which are ignored.
+pub fn parse_traceparent(value: &str) -> Option<([u8; 16], [u8; 8], bool)> {
+    let mut fields = value.trim().split('-');
+    let version = fields.next()?;
+    let trace_id = util::parse_hex::<16>(fields.next()?)?;
+    let parent_id = util::parse_hex::<8>(fields.next()?)?;
+    let flags = util::parse_hex::<1>(fields.next()?)?;
+    let valid_version = match util::parse_hex::<1>(version) {
+        Some([0]) => fields.next().is_none(),
+        Some([version]) => version != 0xff,
+        None => false,
+    };
+    if !valid_version || trace_id == [0; 16] || parent_id == [0; 8] {
+        return None;
+    }
+    return Some((trace_id, parent_id, flags[0] & 1 == 1));
+}
+
+fn unix_nanos(time: std::time::SystemTime) -> String {
+    let nanos = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or(0);
+    return nanos.to_string();
+}
+
+/// Spans as the JSON body of an OTLP/HTTP export request, in which IDs are
+/// hex and times are nanoseconds given as strings.
+pub fn otlp_traces(service_name: &str, spans: &[FinishedSpan]) -> serde_json::Value {
+    let attribute = |key: &str, value: &SpanValue| {
+        let value = match value {
+            SpanValue::String(value) => serde_json::json!({ "stringValue": value }),
+            SpanValue::Int(value) => serde_json::json!({ "intValue": value.to_string() }),
+        };
+        return serde_json::json!({ "key": key, "value": value });
+    };
+    let spans = spans
+        .iter()
+        .map(|span| {
+            let mut json = serde_json::json!({
+                "traceId": util::hex(&span.trace_id),
+                "spanId": util::hex(&span.span_id),
+                "name": span.name,
+                "kind": span.kind as u8,
+                "startTimeUnixNano": unix_nanos(span.start),
+                "endTimeUnixNano": unix_nanos(span.end),
+                "attributes": span
+                    .attributes
+                    .iter()
+                    .map(|(key, value)| attribute(key, value))
+                    .collect::<Vec<_>>(),
+                "status": match &span.error {
+                    Some(message) => serde_json::json!({ "code": 2, "message": message }),
+                    None => serde_json::json!({}),
+                },
+            });
+            if let Some(parent_span_id) = span.parent_span_id {
+                json["parentSpanId"] = serde_json::json!(util::hex(&parent_span_id));
+            }
+            return json;
+        })
+        .collect::<Vec<_>>();
+    return serde_json::json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [attribute("service.name", &SpanValue::String(service_name.to_string()))],
+            },
+            "scopeSpans": [{
+                "scope": { "name": "beelzebub-server", "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans,
+            }],
+        }],
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::{FinishedSpan, SpanKind, SpanValue};
+    use crate::util;
+
+    #[test_case("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01", Some(("4bf92f3577b34da6a3ce929d0e0e4736", "00f067aa0ba902b7", true)); "sampled")]
+    #[test_case("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00", Some(("4bf92f3577b34da6a3ce929d0e0e4736", "00f067aa0ba902b7", false)); "not sampled")]
+    #[test_case("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-03-extra", Some(("4bf92f3577b34da6a3ce929d0e0e4736", "00f067aa0ba902b7", true)); "later version")]
+    #[test_case("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra", None; "extra field")]
+    #[test_case("ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01", None; "invalid version")]
+    #[test_case("00-00000000000000000000000000000000-00f067aa0ba902b7-01", None; "zero trace")]
+    #[test_case("00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01", None; "zero parent")]
+    #[test_case("00-4bf92f3577b34da6a3ce929d0e0e47-00f067aa0ba902b7-01", None; "short trace")]
+    #[test_case("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7", None; "no flags")]
+    fn parse_traceparent(input: &str, output: Option<(&str, &str, bool)>) {
+        let parsed = super::parse_traceparent(input).map(|(trace_id, parent_id, sampled)| {
+            (util::hex(&trace_id), util::hex(&parent_id), sampled)
+        });
+        let output = output.map(|(trace_id, parent_id, sampled)| {
+            (trace_id.to_string(), parent_id.to_string(), sampled)
+        });
+        assert_eq!(parsed, output);
+    }
+
+    #[test_case(true, None; "child")]
+    #[test_case(false, Some("database is locked"); "failed")]
+    fn otlp_traces(child: bool, error: Option<&str>) {
+        let start = std::time::UNIX_EPOCH + std::time::Duration::from_millis(1_500);
+        let span = FinishedSpan {
+            trace_id: [1; 16],
+            span_id: [2; 8],
+            parent_span_id: child.then_some([3; 8]),
+            name: "save_event".to_string(),
+            kind: SpanKind::Client,
+            start,
+            end: start + std::time::Duration::from_millis(2),
+            attributes: vec![("http.response.status_code", SpanValue::Int(201))],
+            error: error.map(str::to_string),
+        };
+        let json = super::otlp_traces("beelzebub", &[span]);
+        let resource = &json["resourceSpans"][0]["resource"]["attributes"][0];
+        assert_eq!(resource["value"]["stringValue"], "beelzebub");
+        let span = &json["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["traceId"], "01010101010101010101010101010101");
+        assert_eq!(span["spanId"], "0202020202020202");
+        assert_eq!(
+            span["parentSpanId"].as_str(),
+            child.then_some("0303030303030303")
+        );
+        assert_eq!(span["kind"], 3);
+        assert_eq!(span["startTimeUnixNano"], "1500000000");
+        assert_eq!(span["endTimeUnixNano"], "1502000000");
+        assert_eq!(span["attributes"][0]["value"]["intValue"], "201");
+        assert_eq!(span["status"]["message"].as_str(), error);
+        assert_eq!(span["status"]["code"].as_i64(), error.map(|_| 2));
+    }
+}
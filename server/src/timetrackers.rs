@@ -0,0 +1,254 @@
+use std::sync::RwLock;
+use std::time::Duration;
+
+use log::{debug, error, warn};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+
+use crate::{
+    config::{TimeEntryRule, TimeTrackerConfig, TimeTrackerService},
+    util,
+    webhooks::Session,
+};
+
+/// Attempts at creating an entry before the session is dropped.
+const MAXIMUM_ATTEMPTS: u32 = 5;
+
+/// Wait before the first retry, doubled for every retry after it.
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+const TOGGL_URL: &str = "https://api.track.toggl.com/api/v9";
+
+const CLOCKIFY_URL: &str = "https://api.clockify.me/api/v1";
+
+/// Rule that decides the entry of the session, None if it isn't sent.
+fn rule<'a>(time_tracker: &'a TimeTrackerConfig, session: &Session) -> Option<&'a TimeEntryRule> {
+    if session.duration < time_tracker.minimum_duration {
+        return None;
+    }
+    if !time_tracker.users.is_empty()
+        && !session
+            .user
+            .as_ref()
+            .is_some_and(|user| time_tracker.users.contains(user))
+    {
+        return None;
+    }
+    let executable = session.executable.to_lowercase();
+    let name = session.name.as_deref().unwrap_or("").to_lowercase();
+    time_tracker.rules.iter().find(|rule| match &rule.process {
+        Some(process) => {
+            let process = process.to_lowercase();
+            executable.contains(&process) || name.contains(&process)
+        }
+        None => true,
+    })
+}
+
+/// Time in UTC to the second, which Clockify requires.
+fn timestamp(time: OffsetDateTime) -> String {
+    time.to_offset(UtcOffset::UTC)
+        .replace_nanosecond(0)
+        .unwrap_or(time)
+        .format(&Rfc3339)
+        .unwrap_or_default()
+}
+
+/// Request creating the time entry of the session, None if the rule can't be
+/// used with the service.
+fn entry_request(
+    client: &reqwest::Client,
+    time_tracker: &TimeTrackerConfig,
+    rule: &TimeEntryRule,
+    session: &Session,
+) -> Option<reqwest::RequestBuilder> {
+    let game = session.name.as_deref().unwrap_or(&session.executable);
+    let user = session.user.as_deref().unwrap_or("");
+    let description = util::render_template(
+        &rule.description,
+        &[
+            ("game", game),
+            ("executable", &session.executable),
+            ("user", user),
+        ],
+    );
+    let start = timestamp(session.started_at);
+    let end = timestamp(session.ended_at);
+    let token = time_tracker.token.as_deref().unwrap_or_default();
+    match &time_tracker.service {
+        TimeTrackerService::Toggl { workspace, .. } => {
+            let project = match rule.project.as_deref().map(str::parse::<u64>) {
+                Some(Ok(project)) => Some(project),
+                Some(Err(_)) => {
+                    error!(
+                        "Toggl project {} is not a number",
+                        rule.project.as_deref().unwrap_or_default()
+                    );
+                    return None;
+                }
+                None => None,
+            };
+            let body = serde_json::json!({
+                "created_with": "beelzebub-server",
+                "workspace_id": workspace,
+                "project_id": project,
+                "description": description,
+                "start": start,
+                "stop": end,
+                "duration": session.duration,
+                "tags": rule.tags,
+                "billable": rule.billable,
+            });
+            let url = format!("{}/workspaces/{}/time_entries", TOGGL_URL, workspace);
+            Some(
+                client
+                    .post(url)
+                    .basic_auth(token, Some("api_token"))
+                    .json(&body),
+            )
+        }
+        TimeTrackerService::Clockify { workspace, .. } => {
+            let body = serde_json::json!({
+                "projectId": rule.project,
+                "description": description,
+                "start": start,
+                "end": end,
+                "tagIds": rule.tags,
+                "billable": rule.billable,
+            });
+            let url = format!("{}/workspaces/{}/time-entries", CLOCKIFY_URL, workspace);
+            Some(client.post(url).header("X-Api-Key", token).json(&body))
+        }
+        TimeTrackerService::Webhook {
+            url,
+            template,
+            headers,
+        } => {
+            let duration = session.duration.to_string();
+            let body = util::render_json_template(
+                template,
+                &[
+                    ("description", &description),
+                    ("project", rule.project.as_deref().unwrap_or("")),
+                    ("start", &start),
+                    ("end", &end),
+                    ("duration", &duration),
+                    ("tags", &rule.tags.join(",")),
+                    ("game", game),
+                    ("executable", &session.executable),
+                    ("user", user),
+                ],
+            );
+            let mut request = client
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body);
+            for (name, value) in headers {
+                request = request.header(name, value);
+            }
+            Some(request)
+        }
+    }
+}
+
+fn service_name(service: &TimeTrackerService) -> String {
+    match service {
+        TimeTrackerService::Toggl { .. } => "Toggl".to_string(),
+        TimeTrackerService::Clockify { .. } => "Clockify".to_string(),
+        // Webhook URLs often contain a token in the path.
+        TimeTrackerService::Webhook { url, .. } => match reqwest::Url::parse(url) {
+            Ok(url) => format!("time tracker {}", url.origin().ascii_serialization()),
+            Err(_) => "time tracker with an invalid URL".to_string(),
+        },
+    }
+}
+
+/// External time trackers that recorded sessions are entered into.
+pub struct TimeTrackers {
+    client: reqwest::Client,
+
+    /// Replaced when the configuration is reloaded.
+    time_trackers: RwLock<Vec<TimeTrackerConfig>>,
+}
+
+impl TimeTrackers {
+    pub fn new(time_trackers: Vec<TimeTrackerConfig>) -> Result<Self, reqwest::Error> {
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("beelzebub-server/", env!("CARGO_PKG_VERSION")))
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+        Ok(TimeTrackers {
+            client,
+            time_trackers: RwLock::new(time_trackers),
+        })
+    }
+
+    /// Replace the time trackers. Entries in progress go on to the old ones.
+    pub fn configure(&self, time_trackers: Vec<TimeTrackerConfig>) {
+        match self.time_trackers.write() {
+            Ok(mut current) => *current = time_trackers,
+            Err(_) => error!("Could not update time trackers"),
+        }
+    }
+
+    /// Create a time entry of the session in every time tracker with a
+    /// matching rule in the background.
+    pub fn deliver(&self, session: &Session) {
+        let Ok(time_trackers) = self.time_trackers.read() else {
+            error!("Could not read time trackers");
+            return;
+        };
+        for time_tracker in time_trackers.iter() {
+            let Some(rule) = rule(time_tracker, session) else {
+                continue;
+            };
+            if let Some(request) = entry_request(&self.client, time_tracker, rule, session) {
+                tokio::spawn(send(service_name(&time_tracker.service), request));
+            }
+        }
+    }
+}
+
+async fn send(target: String, request: reqwest::RequestBuilder) {
+    let mut delay = RETRY_DELAY;
+    for attempt in 1..=MAXIMUM_ATTEMPTS {
+        let Some(attempt_request) = request.try_clone() else {
+            error!("Could not send time entry to {}", target);
+            return;
+        };
+        match attempt_request.send().await {
+            Ok(response) if response.status().is_success() => {
+                debug!("Created time entry in {}", target);
+                return;
+            }
+            // Other client errors will not go away by trying again.
+            Ok(response)
+                if response.status().is_client_error()
+                    && response.status() != reqwest::StatusCode::REQUEST_TIMEOUT
+                    && response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS =>
+            {
+                error!("{} rejected time entry: {}", target, response.status());
+                return;
+            }
+            Ok(response) => warn!(
+                "Time entry in {} failed on attempt {}: {}",
+                target,
+                attempt,
+                response.status()
+            ),
+            Err(error) => warn!(
+                "Time entry in {} failed on attempt {}: {}",
+                target, attempt, error
+            ),
+        }
+        if attempt < MAXIMUM_ATTEMPTS {
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+    error!(
+        "Giving up on time entry in {} after {} attempts",
+        target, MAXIMUM_ATTEMPTS
+    );
+}
@@ -0,0 +1,36 @@
+use std::collections::HashMap;
+
+use time_tz::{timezones, Tz};
+
+use crate::{config::Config, users::UserIds};
+
+/// Time zones whose days, weeks and months are used for reports.
+#[derive(Debug)]
+pub struct Timezones {
+    default: &'static Tz,
+
+    /// Time zones of the users that have their own, by user ID.
+    users: HashMap<i32, &'static Tz>,
+}
+
+impl Timezones {
+    pub fn new(config: &Config, user_ids: &UserIds) -> Self {
+        let users = config
+            .users
+            .iter()
+            .filter_map(|user| Some((*user_ids.get(&user.name)?, user.timezone?)))
+            .collect();
+        Timezones {
+            default: config.timezone.unwrap_or(timezones::db::UTC),
+            users,
+        }
+    }
+
+    /// Time zone of the user, or that of the server for callers that are not
+    /// limited to a user.
+    pub fn get(&self, user_id: Option<i32>) -> &'static Tz {
+        user_id
+            .and_then(|user_id| self.users.get(&user_id).copied())
+            .unwrap_or(self.default)
+    }
+}
@@ -0,0 +1,109 @@
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+use rustls::{
+    crypto::ring,
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    RootCertStore, ServerConfig,
+};
+
+use crate::config::TlsConfig;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Client certificates are required but no CA to verify them was given.
+    MissingClientCa,
+
+    /// Key file does not contain a private key.
+    MissingPrivateKey,
+
+    /// IO error reading certificates or keys.
+    IOError(std::io::Error),
+
+    /// Certificates or keys were rejected.
+    TlsError(rustls::Error),
+
+    /// Client certificate verifier could not be created.
+    VerifierError(rustls::server::VerifierBuilderError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MissingClientCa => write!(
+                f,
+                "client certificates are required but no client CA is given"
+            ),
+            Error::MissingPrivateKey => write!(f, "the key file has no private key"),
+            Error::IOError(error) => write!(f, "could not read certificates or keys: {}", error),
+            Error::TlsError(error) => write!(f, "{}", error),
+            Error::VerifierError(error) => {
+                write!(f, "could not verify client certificates: {}", error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::MissingClientCa | Error::MissingPrivateKey => None,
+            Error::IOError(error) => Some(error),
+            Error::TlsError(error) => Some(error),
+            Error::VerifierError(error) => Some(error),
+        }
+    }
+}
+
+fn load_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, Error> {
+    let mut reader = BufReader::new(File::open(path).map_err(Error::IOError)?);
+    rustls_pemfile::certs(&mut reader)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Error::IOError)
+}
+
+fn load_private_key(path: &Path) -> Result<PrivateKeyDer<'static>, Error> {
+    let mut reader = BufReader::new(File::open(path).map_err(Error::IOError)?);
+    rustls_pemfile::private_key(&mut reader)
+        .map_err(Error::IOError)?
+        .ok_or(Error::MissingPrivateKey)
+}
+
+/// Build the rustls configuration, optionally verifying client certificates
+/// against the configured CA bundle.
+pub fn server_config(config: &TlsConfig) -> Result<ServerConfig, Error> {
+    let provider = Arc::new(ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(Error::TlsError)?;
+
+    let builder = match &config.client_ca {
+        Some(client_ca) => {
+            let mut roots = RootCertStore::empty();
+            for certificate in load_certificates(client_ca)? {
+                roots.add(certificate).map_err(Error::TlsError)?;
+            }
+            let mut verifier =
+                WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            if !config.require_client_certificate {
+                verifier = verifier.allow_unauthenticated();
+            }
+            builder.with_client_cert_verifier(verifier.build().map_err(Error::VerifierError)?)
+        }
+        None if config.require_client_certificate => return Err(Error::MissingClientCa),
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut server_config = builder
+        .with_single_cert(
+            load_certificates(&config.certificate)?,
+            load_private_key(&config.key)?,
+        )
+        .map_err(Error::TlsError)?;
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(server_config)
+}
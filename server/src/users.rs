@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Extension, Json,
+};
+use log::{error, info};
+
+use crate::{authorize_read, config, db::Database, AppState, Caller};
+
+/// User IDs by name.
+pub type UserIds = HashMap<String, i32>;
+
+/// Create or update the configured users and return their IDs.
+pub async fn sync(db: &Database, config: &config::Config) -> Result<UserIds, ()> {
+    for api_key in &config.api_keys {
+        if let Some(user) = &api_key.user {
+            if !config
+                .users
+                .iter()
+                .any(|config_user| &config_user.name == user)
+            {
+                error!("API key {} belongs to unknown user {}", api_key.name, user);
+                return Err(());
+            }
+        }
+    }
+    let configured = config
+        .users
+        .iter()
+        .map(|user| (user.name.clone(), user.leaderboard))
+        .collect::<Vec<(String, bool)>>();
+    match db.sync_users(configured).await {
+        Ok(ids) => {
+            info!("Loaded {} users", ids.len());
+            Ok(ids)
+        }
+        Err(error) => {
+            error!("Could not update users: {}", error);
+            Err(())
+        }
+    }
+}
+
+/// Total playtime per user for the users that opted in, most played first.
+#[utoipa::path(
+    get,
+    path = "/leaderboard",
+    params(shared::StatsQuery),
+    responses(
+        (status = 200, description = "Playtime per user", body = Vec<shared::LeaderboardEntry>),
+        (status = 401, description = "Not authenticated"),
+    ),
+)]
+pub async fn leaderboard(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    authorized: Option<Extension<Caller>>,
+    Query(query): Query<shared::StatsQuery>,
+) -> Result<Json<Vec<shared::LeaderboardEntry>>, StatusCode> {
+    if authorize_read(authorized, &headers, &state).await.is_none() {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    match state.db.leaderboard(query).await {
+        Ok(leaderboard) => Ok(Json(leaderboard)),
+        Err(error) => {
+            error!("Could not query leaderboard: {}", error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
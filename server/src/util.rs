@@ -1,14 +1,1608 @@
-pub fn clean_name(value: &String) -> &str {
-    return value.split('\0').next().unwrap_or(value);
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::sync::LazyLock;
+
+use diesel::pg::data_types::PgInterval;
+use regex::Regex;
+use ring::{digest, hmac};
+use shared::{pagination::Cursor, SessionDuration};
+use time::{Date, Month, OffsetDateTime, UtcOffset};
+use time_tz::{OffsetDateTimeExt, PrimitiveDateTimeExt, Tz};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::config::{IgnoreAction, IgnoreRule, NameNormalization, Network, SubmissionLimits};
+use crate::pagination::Sort;
+
+/// Characters that show nothing but change how the text around them is
+/// shown: zero-width spaces, direction marks and overrides, and byte order
+/// marks. Joiners are kept as emoji sequences need them.
+fn is_invisible(c: char) -> bool {
+    return matches!(
+        c,
+        '\u{200B}'
+            | '\u{200E}'
+            | '\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'
+            | '\u{2066}'..='\u{2069}'
+            | '\u{FEFF}'
+    );
+}
+
+/// Text reported by a client, cleaned up for storing. Windows APIs mean
+/// only the text up to the first NUL, and what is left is stripped of
+/// control and invisible characters, normalised to NFC, has its whitespace
+/// collapsed to single spaces and is cut to `maximum_length` characters.
+/// `None` if nothing is left of it.
+pub fn clean_name(value: &str, maximum_length: usize) -> Option<String> {
+    let value = value.split('\0').next().unwrap_or(value);
+    let cleaned = value
+        .chars()
+        .map(|c| if c.is_whitespace() { ' ' } else { c })
+        .filter(|c| !c.is_control() && !is_invisible(*c))
+        .nfc()
+        .collect::<String>();
+    let collapsed = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    let truncated = collapsed.chars().take(maximum_length).collect::<String>();
+    let truncated = truncated.trim_end();
+    if truncated.is_empty() {
+        return None;
+    }
+    return Some(truncated.to_string());
+}
+
+/// Name cleaned up with the rules, `None` if nothing is left of it.
+pub fn normalise_name(name: &str, rules: &NameNormalization) -> Option<String> {
+    let mut name = name.to_string();
+    if rules.strip_trademarks {
+        name.retain(|c| !matches!(c, '™' | '®' | '©'));
+    }
+    for replacement in &rules.replacements {
+        name = replacement
+            .pattern
+            .0
+            .replace_all(&name, replacement.replacement.as_str())
+            .into_owned();
+    }
+    if rules.trim {
+        name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    }
+    if name.is_empty() {
+        return None;
+    }
+    return Some(name);
+}
+
+/// Action of the first rule that ignores the executable, `None` if the
+/// sessions of the executable are recorded.
+pub fn ignored(executable: &str, rules: &[IgnoreRule]) -> Option<IgnoreAction> {
+    return rules
+        .iter()
+        .find(|rule| {
+            rule.executable
+                .as_ref()
+                .is_some_and(|ignored| ignored.eq_ignore_ascii_case(executable))
+                || rule
+                    .pattern
+                    .as_ref()
+                    .is_some_and(|pattern| pattern.0.is_match(executable))
+        })
+        .map(|rule| rule.action);
+}
+
+/// Network in CIDR notation such as `10.0.0.0/8`, or a single address.
+pub fn parse_network(text: &str) -> Option<Network> {
+    let (address, prefix) = match text.split_once('/') {
+        Some((address, prefix)) => (address, Some(prefix)),
+        None => (text, None),
+    };
+    let address = address.trim().parse::<IpAddr>().ok()?.to_canonical();
+    let bits = if address.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        Some(prefix) => prefix.trim().parse::<u8>().ok().filter(|prefix| *prefix <= bits)?,
+        None => bits,
+    };
+    return Some(Network { address, prefix });
+}
+
+/// Trigrams of the words of the text like those of pg_trgm: lowercased, with
+/// each word padded by two spaces before and one after.
+fn trigrams(text: &str) -> HashSet<[char; 3]> {
+    let mut trigrams = HashSet::new();
+    for word in text
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        let padded: Vec<char> = "  ".chars().chain(word.chars()).chain([' ']).collect();
+        for window in padded.windows(3) {
+            trigrams.insert([window[0], window[1], window[2]]);
+        }
+    }
+    return trigrams;
+}
+
+/// How well the query matches the text from 0 to 1, as the fraction of the
+/// trigrams of the query that are in the text, so that typos and version
+/// quirks such as `eldenring_dx12.exe` still match. Text that contains the
+/// query matches fully.
+pub fn fuzzy_match(query: &str, text: &str) -> f64 {
+    if text.to_lowercase().contains(&query.to_lowercase()) {
+        return 1.0;
+    }
+    let query = trigrams(query);
+    if query.is_empty() {
+        return 0.0;
+    }
+    let text = trigrams(text);
+    return query.intersection(&text).count() as f64 / query.len() as f64;
+}
+
+/// Version, build or bitness at the end of a name, such as " v1.2.3",
+/// " - 1.0", " Build 1234" or " (64-bit)".
+static NAME_SUFFIX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\s*(?:[-–:]\s*)?(?:[(\[]\s*)?(?:v(?:ersion)?\s*\d+(?:\.\d+)*[a-z]?|\d+(?:\.\d+)+[a-z]?|build\s*\d+|(?:64|32)[- ]?bit|x64|x86)(?:\s*[)\]])?$",
+    )
+    .unwrap()
+});
+
+/// Name of a game without case, trademark signs, punctuation and versions at
+/// the end, which differ between processes of the same game.
+pub fn duplicate_key(name: &str) -> String {
+    let mut name = name.trim().to_string();
+    loop {
+        let stripped = NAME_SUFFIX.replace(&name, "").into_owned();
+        if stripped == name || stripped.is_empty() {
+            break;
+        }
+        name = stripped;
+    }
+    return name
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+}
+
+/// Groups of the processes, given as executable and name, that are likely
+/// the same game: those with the same executable apart from case, and those
+/// whose names are the same but for versions and trademark signs. Processes
+/// are given by their index, and groups in the order of their first process.
+pub fn duplicate_groups(processes: &[(&str, Option<&str>)]) -> Vec<Vec<usize>> {
+    fn root(parents: &mut [usize], mut index: usize) -> usize {
+        while parents[index] != index {
+            parents[index] = parents[parents[index]];
+            index = parents[index];
+        }
+        return index;
+    }
+
+    let mut parents = (0..processes.len()).collect::<Vec<_>>();
+    let mut first: HashMap<(bool, String), usize> = HashMap::new();
+    for (index, (executable, name)) in processes.iter().enumerate() {
+        let mut keys = vec![(false, executable.to_lowercase())];
+        if let Some(name) = name.map(duplicate_key).filter(|key| !key.is_empty()) {
+            keys.push((true, name));
+        }
+        for key in keys {
+            let other = *first.entry(key).or_insert(index);
+            let (a, b) = (root(&mut parents, other), root(&mut parents, index));
+            parents[a.max(b)] = a.min(b);
+        }
+    }
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut positions: HashMap<usize, usize> = HashMap::new();
+    for index in 0..processes.len() {
+        let group = root(&mut parents, index);
+        match positions.get(&group) {
+            Some(position) => groups[*position].push(index),
+            None => {
+                positions.insert(group, groups.len());
+                groups.push(vec![index]);
+            }
+        }
+    }
+    groups.retain(|group| group.len() > 1);
+    return groups;
+}
+
+/// Parse a `major.minor.patch` version, ignoring any pre-release or build
+/// suffix. Missing components count as zero.
+pub fn parse_version(value: &str) -> Option<(u64, u64, u64)> {
+    let core = value.trim().split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    return Some((major, minor, patch));
+}
+
+/// Length of an interval, counting months as 30 days.
+pub fn interval_duration(interval: &PgInterval) -> SessionDuration {
+    let days = interval.days as i64 + interval.months as i64 * 30;
+    let microseconds = interval.microseconds + days * 86_400_000_000;
+    return SessionDuration::from_micros(microseconds);
+}
+
+/// Length of an interval in whole seconds, counting months as 30 days.
+pub fn interval_seconds(interval: &PgInterval) -> u64 {
+    return interval_duration(interval).as_secs();
+}
+
+/// Items sorted by the field in the query, or by the first sort if it has
+/// none. Returns None for an unknown sort.
+pub fn sort<T>(mut items: Vec<T>, query: &shared::ListQuery, sorts: &[Sort<T>]) -> Option<Vec<T>> {
+    let compare = match &query.sort {
+        Some(sort) => sorts.iter().find(|(name, _)| name == sort)?.1,
+        None => sorts.first()?.1,
+    };
+    match query.order {
+        shared::SortOrder::Asc => items.sort_by(compare),
+        shared::SortOrder::Desc => items.sort_by(|a, b| compare(b, a)),
+    }
+    return Some(items);
+}
+
+/// Items sorted like `sort`, from the offset given as the page. Returns None
+/// for an unknown sort or a page that is not an offset.
+pub fn paginate<T>(
+    items: Vec<T>,
+    query: &shared::ListQuery,
+    sorts: &[Sort<T>],
+    limit: usize,
+) -> Option<shared::Page<T>> {
+    let offset = match &query.page {
+        Some(page) => usize::decode(page)?,
+        None => 0,
+    };
+    let items = sort(items, query, sorts)?;
+    let next_page = (items.len() > offset + limit).then(|| (offset + limit).encode());
+    let items = items.into_iter().skip(offset).take(limit).collect();
+    return Some(shared::Page { items, next_page });
+}
+
+/// First moment of the date in the time zone. That is midnight, unless the
+/// clocks skip over midnight on the date.
+fn start_of_date(date: Date, timezone: &Tz) -> OffsetDateTime {
+    let midnight = date.midnight();
+    return midnight
+        .assume_timezone(timezone)
+        .take_first()
+        // Clocks skip an hour at most.
+        .or_else(|| {
+            (midnight + time::Duration::HOUR)
+                .assume_timezone(timezone)
+                .take_first()
+        })
+        .unwrap_or_else(|| midnight.assume_utc());
+}
+
+/// Start of the day the time is on in the time zone.
+pub fn start_of_day(time: OffsetDateTime, timezone: &Tz) -> OffsetDateTime {
+    return start_of_date(time.to_timezone(timezone).date(), timezone);
+}
+
+/// Start of the week, from Monday, the time is on in the time zone.
+pub fn start_of_week(time: OffsetDateTime, timezone: &Tz) -> OffsetDateTime {
+    let date = time.to_timezone(timezone).date();
+    let monday = date - time::Duration::days(date.weekday().number_days_from_monday().into());
+    return start_of_date(monday, timezone);
+}
+
+/// Start of the month the time is on in the time zone.
+pub fn start_of_month(time: OffsetDateTime, timezone: &Tz) -> OffsetDateTime {
+    let date = time.to_timezone(timezone).date();
+    // Every month has a first day.
+    return start_of_date(date.replace_day(1).unwrap_or(date), timezone);
+}
+
+/// Start of the month in UTC `months` months before the month of the time.
+pub fn start_of_months_ago(time: OffsetDateTime, months: u32) -> OffsetDateTime {
+    let time = time.to_offset(UtcOffset::UTC);
+    let index = time.year() * 12 + time.month() as i32 - 1 - months as i32;
+    let month = Month::try_from(index.rem_euclid(12) as u8 + 1).unwrap_or(Month::January);
+    return match Date::from_calendar_date(index.div_euclid(12), month, 1) {
+        Ok(date) => date.midnight().assume_utc(),
+        Err(_) => OffsetDateTime::UNIX_EPOCH,
+    };
+}
+
+/// Start of the period the time is in.
+pub fn start_of_period(
+    period: shared::SummaryPeriod,
+    time: OffsetDateTime,
+    timezone: &Tz,
+) -> OffsetDateTime {
+    return match period {
+        shared::SummaryPeriod::Day => start_of_day(time, timezone),
+        shared::SummaryPeriod::Week => start_of_week(time, timezone),
+        shared::SummaryPeriod::Month => start_of_month(time, timezone),
+    };
+}
+
+/// Start of the period after the one that starts at `start`. Days are 23 to
+/// 25 hours long and months 28 to 31 days, so stepping by more than the
+/// longest and less than twice the shortest always lands in the next one.
+fn start_of_next_period(
+    period: shared::SummaryPeriod,
+    start: OffsetDateTime,
+    timezone: &Tz,
+) -> OffsetDateTime {
+    let step = match period {
+        shared::SummaryPeriod::Day => time::Duration::hours(36),
+        shared::SummaryPeriod::Week => time::Duration::hours(7 * 24 + 12),
+        shared::SummaryPeriod::Month => time::Duration::days(32),
+    };
+    return start_of_period(period, start + step, timezone);
+}
+
+/// Playtime of the sessions, given as their end times and durations, per
+/// bucket in the time zone from the bucket of `since`, or of the first
+/// session, to the bucket of `until`. Buckets without sessions are included
+/// so that charts have no gaps.
+pub fn histogram(
+    sessions: &[(OffsetDateTime, u64)],
+    bucket: shared::SummaryPeriod,
+    since: Option<OffsetDateTime>,
+    until: OffsetDateTime,
+    timezone: &Tz,
+) -> Vec<shared::HistogramBucket> {
+    let Some(first) = since.or_else(|| sessions.iter().map(|(time, _)| *time).min()) else {
+        return Vec::new();
+    };
+    let mut buckets = Vec::new();
+    let mut start = start_of_period(bucket, first, timezone);
+    while start < until {
+        buckets.push(shared::HistogramBucket {
+            start,
+            duration: 0,
+            sessions: 0,
+        });
+        start = start_of_next_period(bucket, start, timezone);
+    }
+    for (time, duration) in sessions {
+        let start = start_of_period(bucket, *time, timezone);
+        if let Ok(index) = buckets.binary_search_by_key(&start, |bucket| bucket.start) {
+            buckets[index].duration += duration;
+            buckets[index].sessions += 1;
+        }
+    }
+    return buckets;
+}
+
+/// Start of the calendar year in the time zone and of the year after it.
+pub fn year_range(year: i32, timezone: &Tz) -> Option<(OffsetDateTime, OffsetDateTime)> {
+    let first = Date::from_calendar_date(year, Month::January, 1).ok()?;
+    let next = Date::from_calendar_date(year + 1, Month::January, 1).ok()?;
+    return Some((
+        start_of_date(first, timezone),
+        start_of_date(next, timezone),
+    ));
+}
+
+/// Seconds played on each day of the year in the time zone, given the end
+/// times and durations of the sessions. Sessions that ended in other years
+/// are ignored.
+pub fn heatmap(sessions: &[(OffsetDateTime, u64)], year: i32, timezone: &Tz) -> Vec<u64> {
+    let mut days = vec![0; time::util::days_in_year(year) as usize];
+    for (time, duration) in sessions {
+        let date = time.to_timezone(timezone).date();
+        if date.year() == year {
+            days[date.ordinal() as usize - 1] += duration;
+        }
+    }
+    return days;
+}
+
+/// Playtime per device from the per-device stats of each process, most
+/// played first and by name for the same playtime.
+pub fn device_totals(stats: &[shared::ProcessStats]) -> Vec<shared::DeviceStats> {
+    let mut totals: Vec<shared::DeviceStats> = Vec::new();
+    for process in stats {
+        match totals.iter_mut().find(|total| total.device == process.device) {
+            Some(total) => {
+                total.duration += process.duration;
+                total.sessions += process.sessions;
+                total.games += 1;
+            }
+            None => totals.push(shared::DeviceStats {
+                device: process.device.clone(),
+                duration: process.duration,
+                sessions: process.sessions,
+                games: 1,
+            }),
+        }
+    }
+    totals.sort_by(|a, b| {
+        b.duration
+            .cmp(&a.duration)
+            .then_with(|| a.device.cmp(&b.device))
+    });
+    return totals;
+}
+
+/// Playtime of the processes totalled by their executable or name, most
+/// played first, keeping devices apart. A total has the executable and name
+/// of its most played process.
+pub fn group_stats(
+    mut stats: Vec<shared::ProcessStats>,
+    grouping: shared::StatsGrouping,
+) -> Vec<shared::ProcessStats> {
+    let key = |process: &shared::ProcessStats| match grouping {
+        shared::StatsGrouping::Process => (
+            process.executable.clone(),
+            process.name.clone(),
+            process.device.clone(),
+        ),
+        shared::StatsGrouping::Executable => {
+            (process.executable.clone(), None, process.device.clone())
+        }
+        shared::StatsGrouping::Name => (
+            process
+                .name
+                .clone()
+                .unwrap_or_else(|| process.executable.clone()),
+            None,
+            process.device.clone(),
+        ),
+    };
+    stats.sort_by_key(|process| std::cmp::Reverse(process.duration));
+    let mut grouped: Vec<shared::ProcessStats> = Vec::new();
+    let mut keys = Vec::new();
+    for process in stats {
+        let process_key = key(&process);
+        match keys.iter().position(|other| *other == process_key) {
+            Some(index) => {
+                let total = &mut grouped[index];
+                total.duration += process.duration;
+                total.sessions += process.sessions;
+                total.first_played = total.first_played.into_iter().chain(process.first_played).min();
+                total.last_played = total.last_played.into_iter().chain(process.last_played).max();
+            }
+            None => {
+                keys.push(process_key);
+                grouped.push(process);
+            }
+        }
+    }
+    grouped.sort_by_key(|process| std::cmp::Reverse(process.duration));
+    return grouped;
+}
+
+/// Periods with the most playtime of the sessions, given their end times and
+/// durations, most played first and earlier first for the same playtime.
+pub fn top_periods(
+    sessions: &[(OffsetDateTime, u64)],
+    period: shared::SummaryPeriod,
+    timezone: &Tz,
+    count: usize,
+) -> Vec<shared::HistogramBucket> {
+    let mut totals: HashMap<OffsetDateTime, (u64, u64)> = HashMap::new();
+    for (time, duration) in sessions {
+        let total = totals
+            .entry(start_of_period(period, *time, timezone))
+            .or_default();
+        total.0 += duration;
+        total.1 += 1;
+    }
+    let mut periods: Vec<shared::HistogramBucket> = totals
+        .into_iter()
+        .map(|(start, (duration, sessions))| shared::HistogramBucket {
+            start,
+            duration,
+            sessions,
+        })
+        .collect();
+    periods.sort_by(|a, b| b.duration.cmp(&a.duration).then(a.start.cmp(&b.start)));
+    periods.truncate(count);
+    return periods;
+}
+
+/// Whole days in UTC from the first to before the end, with `None` for no
+/// limit.
+pub type DayRange = (Option<Date>, Option<Date>);
+
+/// Split the time range into the whole days in UTC, which can be summed from
+/// the daily totals, and the parts of days at its ends, which have to be
+/// summed from the events. There are no whole days when the range starts and
+/// ends within the same day or the next.
+pub fn split_days(
+    since: Option<OffsetDateTime>,
+    until: Option<OffsetDateTime>,
+) -> (Option<DayRange>, Vec<(OffsetDateTime, OffsetDateTime)>) {
+    let midnight = |date: Date| date.midnight().assume_utc();
+    let since = since.map(|time| time.to_offset(UtcOffset::UTC));
+    let until = until.map(|time| time.to_offset(UtcOffset::UTC));
+    let first = since.map(|time| match time == midnight(time.date()) {
+        true => time.date(),
+        false => time.date().next_day().unwrap_or(Date::MAX),
+    });
+    let end = until.map(|time| time.date());
+    if let (Some(since), Some(until), Some(first), Some(end)) = (since, until, first, end) {
+        if first >= end {
+            return match since < until {
+                true => (None, vec![(since, until)]),
+                false => (None, Vec::new()),
+            };
+        }
+    }
+    let mut edges = Vec::new();
+    if let (Some(since), Some(first)) = (since, first) {
+        if since < midnight(first) {
+            edges.push((since, midnight(first)));
+        }
+    }
+    if let (Some(until), Some(end)) = (until, end) {
+        if midnight(end) < until {
+            edges.push((midnight(end), until));
+        }
+    }
+    return (Some((first, end)), edges);
+}
+
+/// Add up the playtime and sessions of the same groups, such as of whole days
+/// and the parts of days around them, most played first.
+pub fn merge_totals<K: Clone + Eq + Hash>(totals: Vec<(K, u64, u64)>) -> Vec<(K, u64, u64)> {
+    let mut merged: Vec<(K, u64, u64)> = Vec::new();
+    let mut indices: HashMap<K, usize> = HashMap::new();
+    for (key, duration, sessions) in totals {
+        match indices.get(&key) {
+            Some(&index) => {
+                merged[index].1 += duration;
+                merged[index].2 += sessions;
+            }
+            None => {
+                indices.insert(key.clone(), merged.len());
+                merged.push((key, duration, sessions));
+            }
+        }
+    }
+    merged.sort_by_key(|(_, duration, _)| Reverse(*duration));
+    return merged;
+}
+
+/// Field of the backfilled session that is wrong and what is wrong with it.
+/// Backfilled sessions need an end time that has passed, as they would
+/// otherwise be recorded as ending when they were imported.
+pub fn backfill_problem(
+    submission: &shared::Submission,
+    limits: &SubmissionLimits,
+    now: OffsetDateTime,
+) -> Option<(&'static str, String)> {
+    let Some(ended_at) = submission.ended_at else {
+        return Some(("ended_at", "ended_at is missing".to_string()));
+    };
+    if ended_at > now {
+        return Some(("ended_at", "session ends in the future".to_string()));
+    }
+    return submission.problem(&limits.into());
+}
+
+/// Problem with the device of the submission, if it names a device other
+/// than the one it is authenticated as. Submissions that don't name a device
+/// are recorded for the authenticated device, if any.
+pub fn device_problem(
+    submission: &shared::Submission,
+    device: Option<i32>,
+) -> Option<(&'static str, String)> {
+    let device_id = submission.device_id?;
+    if device != Some(device_id) {
+        let message = format!("device {} is not the authenticated device", device_id);
+        return Some(("device_id", message));
+    }
+    return None;
+}
+
+/// Idempotency key of a backfilled session: its session ID, or the source,
+/// executable and end time, so that running the same import again doesn't
+/// save the sessions twice.
+pub fn backfill_key(source: &str, submission: &shared::Submission) -> String {
+    if let Some(session_id) = &submission.session_id {
+        return session_id.clone();
+    }
+    let ended_at = submission
+        .ended_at
+        .map_or(0, |ended_at| ended_at.unix_timestamp());
+    return format!(
+        "backfill:{}:{}:{}",
+        source, submission.executable, ended_at
+    );
+}
+
+/// The event with the note and rating replaced. A blank note clears it.
+pub fn annotate_event(
+    event: &shared::Event,
+    note: &shared::EventNote,
+    maximum_length: usize,
+) -> Result<shared::Event, String> {
+    let text = note
+        .note
+        .as_deref()
+        .map(str::trim)
+        .filter(|text| !text.is_empty());
+    if text.is_some_and(|text| text.chars().count() > maximum_length) {
+        return Err(format!("note is over {} characters", maximum_length));
+    }
+    if note.rating.is_some_and(|rating| !(1..=5).contains(&rating)) {
+        return Err("rating is not from 1 to 5".to_string());
+    }
+    let mut annotated = event.clone();
+    annotated.note = text.map(str::to_string);
+    annotated.rating = note.rating;
+    return Ok(annotated);
+}
+
+/// Categories without surrounding whitespace, empty names and duplicates,
+/// sorted by name.
+pub fn clean_categories(categories: Vec<String>) -> Vec<String> {
+    let mut categories = categories
+        .iter()
+        .map(|category| category.trim().to_string())
+        .filter(|category| !category.is_empty())
+        .collect::<Vec<String>>();
+    categories.sort();
+    categories.dedup();
+    return categories;
+}
+
+/// SHA-256 digest given as 64 hex digits.
+pub fn parse_sha256_hex(value: &str) -> Option<[u8; 32]> {
+    return parse_hex(value.trim());
+}
+
+/// Bytes given as exactly twice as many hex digits.
+pub fn parse_hex<const N: usize>(value: &str) -> Option<[u8; N]> {
+    if value.len() != N * 2 || !value.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut bytes = [0; N];
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&value[index * 2..index * 2 + 2], 16).ok()?;
+    }
+    return Some(bytes);
+}
+
+/// HMAC-SHA256 of the data as lowercase hex.
+pub fn hmac_sha256_hex(key: &str, data: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key.as_bytes());
+    let tag = hmac::sign(&key, data);
+    return hex(tag.as_ref());
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    return bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+}
+
+/// SHA-256 of the data as lowercase hex.
+pub fn sha256_hex(data: &[u8]) -> String {
+    return hex(digest::digest(&digest::SHA256, data).as_ref());
+}
+
+/// Duration in seconds as hours and minutes, e.g. `2h 14m`.
+pub fn format_duration(seconds: u64) -> String {
+    let minutes = seconds / 60;
+    if minutes < 60 {
+        return format!("{}m", minutes);
+    }
+    return format!("{}h {}m", minutes / 60, minutes % 60);
+}
+
+/// Duration in seconds as whole hours with thousands separators, e.g.
+/// `1,234 h`, or as minutes if it is under an hour.
+pub fn format_hours(seconds: u64) -> String {
+    if seconds < 3600 {
+        return format!("{} min", seconds / 60);
+    }
+    let hours = (seconds / 3600).to_string();
+    let mut grouped = String::new();
+    for (index, digit) in hours.chars().enumerate() {
+        if index > 0 && (hours.len() - index).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    return format!("{} h", grouped);
+}
+
+/// Template with every `{name}` placeholder replaced by its value. Unknown
+/// placeholders are left as they are.
+pub fn render_template(template: &str, values: &[(&str, &str)]) -> String {
+    let mut rendered = template.to_string();
+    for (name, value) in values {
+        rendered = rendered.replace(&format!("{{{}}}", name), value);
+    }
+    return rendered;
+}
+
+/// Template of a JSON body with its placeholders replaced by the values,
+/// escaped so that they can be put inside JSON strings.
+pub fn render_json_template(template: &str, values: &[(&str, &str)]) -> String {
+    let escaped = values
+        .iter()
+        .map(|(name, value)| {
+            let quoted = serde_json::Value::from(*value).to_string();
+            (*name, quoted[1..quoted.len() - 1].to_string())
+        })
+        .collect::<Vec<_>>();
+    let escaped = escaped
+        .iter()
+        .map(|(name, value)| (*name, value.as_str()))
+        .collect::<Vec<_>>();
+    return render_template(template, &escaped);
+}
+
+/// Change from the previous value in percent, rounded to one decimal. None if
+/// the previous value is zero.
+pub fn percent_change(current: u64, previous: u64) -> Option<f64> {
+    if previous == 0 {
+        return None;
+    }
+    let change = (current as f64 - previous as f64) / previous as f64 * 100.0;
+    return Some((change * 10.0).round() / 10.0);
+}
+
+/// Playtime of every game in either of the ranges, combined across devices,
+/// most played in the current range first and then in the previous range.
+pub fn compare_games(
+    current: Vec<shared::ProcessStats>,
+    previous: Vec<shared::ProcessStats>,
+) -> Vec<shared::GameComparison> {
+    let mut games: Vec<shared::GameComparison> = Vec::new();
+    for (process, is_current) in current
+        .into_iter()
+        .map(|process| (process, true))
+        .chain(previous.into_iter().map(|process| (process, false)))
+    {
+        let index = match games
+            .iter()
+            .position(|game| game.executable == process.executable && game.name == process.name)
+        {
+            Some(index) => index,
+            None => {
+                games.push(shared::GameComparison {
+                    executable: process.executable,
+                    name: process.name,
+                    duration: 0,
+                    sessions: 0,
+                    previous_duration: 0,
+                    previous_sessions: 0,
+                    difference: 0,
+                    change: None,
+                });
+                games.len() - 1
+            }
+        };
+        let game = &mut games[index];
+        if is_current {
+            game.duration += process.duration;
+            game.sessions += process.sessions;
+        } else {
+            game.previous_duration += process.duration;
+            game.previous_sessions += process.sessions;
+        }
+    }
+    for game in &mut games {
+        game.difference = game.duration as i64 - game.previous_duration as i64;
+        game.change = percent_change(game.duration, game.previous_duration);
+    }
+    games.sort_by(|a, b| {
+        (b.duration, b.previous_duration)
+            .cmp(&(a.duration, a.previous_duration))
+            .then_with(|| a.executable.cmp(&b.executable))
+    });
+    return games;
+}
+
+/// Comparison with the playtime of the period before, such as "up 25% from
+/// 1h 20m the week before".
+pub fn describe_change(change: Option<f64>, previous: u64, period: &str) -> String {
+    return match change {
+        None => format!("nothing was played the {} before", period),
+        Some(change) if change > 0.0 => format!(
+            "up {}% from {} the {} before",
+            change,
+            format_duration(previous),
+            period
+        ),
+        Some(change) if change < 0.0 => format!(
+            "down {}% from {} the {} before",
+            -change,
+            format_duration(previous),
+            period
+        ),
+        Some(_) => format!("the same as the {} before", period),
+    };
+}
+
+/// Pattern for LIKE, escaped with backslashes, that matches text containing
+/// the search, so that `%` and `_` in the search match only themselves.
+pub fn like_pattern(search: &str) -> String {
+    let escaped = search
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    return format!("%{}%", escaped);
+}
+
+/// Title to search for a process on game databases: its name, or the
+/// executable without the extension.
+pub fn search_title(executable: &str, name: Option<&str>) -> String {
+    return match name {
+        Some(name) => name.to_string(),
+        None => executable
+            .rsplit_once('.')
+            .map_or(executable, |(stem, _)| stem)
+            .to_string(),
+    };
+}
+
+/// Title with only its letters and digits in lowercase, so that `eldenring`
+/// matches `ELDEN RING`.
+pub fn normalise_title(title: &str) -> String {
+    return title
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+}
+
+/// Whether a `Content-Type` or `Accept` header lists the media type, ignoring
+/// parameters and case.
+pub fn has_media_type(header: &str, media_type: &str) -> bool {
+    return header.split(',').any(|value| {
+        let value = value.split(';').next().unwrap_or_default();
+        value.trim().eq_ignore_ascii_case(media_type)
+    });
+}
+
+/// Code of an error response that has no body of its own.
+pub fn error_code(status: u16) -> shared::ApiErrorCode {
+    return match status {
+        400 | 415 | 422 => shared::ApiErrorCode::Validation,
+        401 | 403 => shared::ApiErrorCode::Unauthenticated,
+        404 => shared::ApiErrorCode::NotFound,
+        409 => shared::ApiErrorCode::Duplicate,
+        413 => shared::ApiErrorCode::PayloadTooLarge,
+        426 => shared::ApiErrorCode::VersionMismatch,
+        429 => shared::ApiErrorCode::RateLimited,
+        500 | 503 => shared::ApiErrorCode::Storage,
+        _ => shared::ApiErrorCode::Unknown,
+    };
 }
 
 #[cfg(test)]
 mod tests {
+    use diesel::pg::data_types::PgInterval;
     use test_case::test_case;
+    use time::OffsetDateTime;
+    use unicode_normalization::is_nfc;
+
+    #[test_case("Grand Theft Auto IV", 255, Some("Grand Theft Auto IV"); "clean name")]
+    #[test_case("Rockstar Games Launcher Redirector\0\08\u{12}\u{1}ProductVersion\01.0.0.66\0\0D\0\0Va", 255, Some("Rockstar Games Launcher Redirector"); "corrupt name")]
+    #[test_case("Half\u{1}-Life\u{7f} 2", 255, Some("Half-Life 2"); "control characters")]
+    #[test_case("\u{FEFF}Portal\u{200B} 2\u{202E}", 255, Some("Portal 2"); "invisible characters")]
+    #[test_case("  Dark\tSouls\n\u{3000}III  ", 255, Some("Dark Souls III"); "whitespace")]
+    #[test_case("Poke\u{301}mon", 255, Some("Pok\u{e9}mon"); "decomposed")]
+    #[test_case("\u{1F468}\u{200D}\u{1F4BB}", 255, Some("\u{1F468}\u{200D}\u{1F4BB}"); "emoji sequence")]
+    #[test_case("abcdefghijkl", 10, Some("abcdefghij"); "too long")]
+    #[test_case("abcdefghi jkl", 10, Some("abcdefghi"); "too long at space")]
+    #[test_case(" \u{1}\u{200B}\t", 255, None; "nothing visible")]
+    #[test_case("\0Game", 255, None; "starts with NUL")]
+    #[test_case("", 255, None; "empty")]
+    fn clean_name(input: &str, maximum_length: usize, output: Option<&str>) {
+        assert_eq!(super::clean_name(input, maximum_length).as_deref(), output);
+    }
+
+    /// Text of characters that are hard to clean up, picked by a xorshift
+    /// generator so that every run checks the same texts.
+    fn dirty_text(state: &mut u64) -> String {
+        const CHARACTERS: &[char] = &[
+            'a', 'Z', '7', '-', ' ', ' ', '\t', '\n', '\r', '\0', '\u{1}', '\u{1b}', '\u{7f}',
+            '\u{85}', '\u{a0}', '\u{3000}', 'e', '\u{301}', '\u{308}', '\u{e9}', 'A',
+            '\u{30a}', '\u{1100}', '\u{1161}', '\u{200b}', '\u{200d}', '\u{202e}', '\u{2066}',
+            '\u{feff}', '\u{65e5}', '\u{1f3ae}', '\u{2122}',
+        ];
+        let mut next = || {
+            *state ^= *state << 13;
+            *state ^= *state >> 7;
+            *state ^= *state << 17;
+            *state
+        };
+        let length = next() % 40;
+        return (0..length)
+            .map(|_| CHARACTERS[(next() % CHARACTERS.len() as u64) as usize])
+            .collect();
+    }
+
+    #[test_case(1; "seed 1")]
+    #[test_case(0x5eed; "seed 2")]
+    #[test_case(0xdead_beef; "seed 3")]
+    #[test_case(u64::MAX; "seed 4")]
+    fn clean_name_properties(seed: u64) {
+        let mut state = seed;
+        for _ in 0..2000 {
+            let input = dirty_text(&mut state);
+            let maximum_length = 1 + (state % 30) as usize;
+            let Some(output) = super::clean_name(&input, maximum_length) else {
+                let visible = input
+                    .split('\0')
+                    .next()
+                    .unwrap()
+                    .chars()
+                    .any(|c| !c.is_whitespace() && !c.is_control() && !super::is_invisible(c));
+                assert!(!visible, "{:?} was cleaned to nothing", input);
+                continue;
+            };
+            assert!(!output.is_empty(), "{:?}", input);
+            assert!(output.chars().count() <= maximum_length, "{:?}", input);
+            assert!(
+                !output.chars().any(|c| c.is_control() || super::is_invisible(c)),
+                "{:?} was cleaned to {:?}",
+                input,
+                output
+            );
+            assert_eq!(output.trim(), output, "{:?}", input);
+            assert!(!output.contains("  "), "{:?}", input);
+            assert!(
+                !output.chars().any(|c| c.is_whitespace() && c != ' '),
+                "{:?}",
+                input
+            );
+            assert!(is_nfc(&output), "{:?} was cleaned to {:?}", input, output);
+            assert_eq!(
+                super::clean_name(&output, maximum_length).as_deref(),
+                Some(output.as_str()),
+                "cleaning {:?} again changes it",
+                input
+            );
+        }
+    }
+
+    #[test_case("0.1.0", Some((0, 1, 0)); "full version")]
+    #[test_case("1.2", Some((1, 2, 0)); "missing patch")]
+    #[test_case("2", Some((2, 0, 0)); "major only")]
+    #[test_case("0.2.0-beta.1", Some((0, 2, 0)); "pre-release")]
+    #[test_case("1.0.0+build", Some((1, 0, 0)); "build metadata")]
+    #[test_case("1.x.0", None; "not a number")]
+    #[test_case("1.2.3.4", None; "too many components")]
+    #[test_case("", None; "empty")]
+    fn parse_version(input: &str, output: Option<(u64, u64, u64)>) {
+        assert_eq!(super::parse_version(input), output);
+    }
+
+    #[test_case(PgInterval::from_microseconds(90_500_000), 90; "microseconds")]
+    #[test_case(PgInterval::new(1_000_000, 1, 0), 86_401; "days")]
+    #[test_case(PgInterval::new(0, 0, 1), 2_592_000; "months")]
+    #[test_case(PgInterval::from_microseconds(-5_000_000), 0; "negative")]
+    fn interval_seconds(input: PgInterval, output: u64) {
+        assert_eq!(super::interval_seconds(&input), output);
+    }
+
+    #[test_case(OffsetDateTime::from_unix_timestamp(1_709_294_400).unwrap(), "UTC", OffsetDateTime::from_unix_timestamp(1_709_251_200).unwrap(); "afternoon")]
+    #[test_case(OffsetDateTime::from_unix_timestamp(1_709_251_200).unwrap(), "UTC", OffsetDateTime::from_unix_timestamp(1_709_251_200).unwrap(); "midnight")]
+    #[test_case(OffsetDateTime::from_unix_timestamp(1_709_251_199).unwrap(), "UTC", OffsetDateTime::from_unix_timestamp(1_709_164_800).unwrap(); "before midnight")]
+    #[test_case(OffsetDateTime::from_unix_timestamp(1_709_251_200).unwrap().to_offset(time::UtcOffset::from_hms(9, 0, 0).unwrap()), "UTC", OffsetDateTime::from_unix_timestamp(1_709_251_200).unwrap(); "other offset")]
+    #[test_case(OffsetDateTime::from_unix_timestamp(1_709_294_400).unwrap(), "Europe/Helsinki", OffsetDateTime::from_unix_timestamp(1_709_244_000).unwrap(); "ahead of utc")]
+    #[test_case(OffsetDateTime::from_unix_timestamp(1_709_249_400).unwrap(), "Europe/Helsinki", OffsetDateTime::from_unix_timestamp(1_709_244_000).unwrap(); "next day in utc")]
+    #[test_case(OffsetDateTime::from_unix_timestamp(1_709_294_400).unwrap(), "America/New_York", OffsetDateTime::from_unix_timestamp(1_709_269_200).unwrap(); "behind utc")]
+    #[test_case(OffsetDateTime::from_unix_timestamp(1_711_886_400).unwrap(), "Europe/Helsinki", OffsetDateTime::from_unix_timestamp(1_711_836_000).unwrap(); "daylight saving")]
+    #[test_case(OffsetDateTime::from_unix_timestamp(1_725_796_800).unwrap(), "America/Santiago", OffsetDateTime::from_unix_timestamp(1_725_768_000).unwrap(); "midnight skipped")]
+    fn start_of_day(time: OffsetDateTime, timezone: &str, output: OffsetDateTime) {
+        let timezone = time_tz::timezones::get_by_name(timezone).unwrap();
+        assert_eq!(super::start_of_day(time, timezone), output);
+    }
+
+    #[test_case(OffsetDateTime::from_unix_timestamp(1_709_294_400).unwrap(), "UTC", OffsetDateTime::from_unix_timestamp(1_708_905_600).unwrap(); "friday")]
+    #[test_case(OffsetDateTime::from_unix_timestamp(1_708_905_600).unwrap(), "UTC", OffsetDateTime::from_unix_timestamp(1_708_905_600).unwrap(); "monday")]
+    #[test_case(OffsetDateTime::from_unix_timestamp(1_709_294_400).unwrap(), "Europe/Helsinki", OffsetDateTime::from_unix_timestamp(1_708_898_400).unwrap(); "ahead of utc")]
+    #[test_case(OffsetDateTime::from_unix_timestamp(1_708_905_600).unwrap(), "America/New_York", OffsetDateTime::from_unix_timestamp(1_708_318_800).unwrap(); "sunday locally")]
+    fn start_of_week(time: OffsetDateTime, timezone: &str, output: OffsetDateTime) {
+        let timezone = time_tz::timezones::get_by_name(timezone).unwrap();
+        assert_eq!(super::start_of_week(time, timezone), output);
+    }
+
+    #[test_case(OffsetDateTime::from_unix_timestamp(1_709_294_400).unwrap(), "UTC", OffsetDateTime::from_unix_timestamp(1_709_251_200).unwrap(); "first day")]
+    #[test_case(OffsetDateTime::from_unix_timestamp(1_709_249_400).unwrap(), "UTC", OffsetDateTime::from_unix_timestamp(1_706_745_600).unwrap(); "last day")]
+    #[test_case(OffsetDateTime::from_unix_timestamp(1_709_249_400).unwrap(), "Europe/Helsinki", OffsetDateTime::from_unix_timestamp(1_709_244_000).unwrap(); "next month locally")]
+    fn start_of_month(time: OffsetDateTime, timezone: &str, output: OffsetDateTime) {
+        let timezone = time_tz::timezones::get_by_name(timezone).unwrap();
+        assert_eq!(super::start_of_month(time, timezone), output);
+    }
+
+    #[test_case(1_709_294_400, 0, 1_709_251_200; "this month")]
+    #[test_case(1_709_294_400, 1, 1_706_745_600; "last month")]
+    #[test_case(1_709_294_400, 14, 1_672_531_200; "across years")]
+    fn start_of_months_ago(time: i64, months: u32, output: i64) {
+        let timestamp = |seconds| OffsetDateTime::from_unix_timestamp(seconds).unwrap();
+        assert_eq!(super::start_of_months_ago(timestamp(time), months), timestamp(output));
+    }
+
+    #[test_case(vec![(1_709_294_400, 3600), (1_709_298_000, 600), (1_709_460_000, 60)], shared::SummaryPeriod::Day, None, 1_709_510_400, "UTC", vec![(1_709_251_200, 4200, 2), (1_709_337_600, 0, 0), (1_709_424_000, 60, 1)]; "days")]
+    #[test_case(vec![], shared::SummaryPeriod::Day, None, 1_709_510_400, "UTC", vec![]; "nothing played")]
+    #[test_case(vec![], shared::SummaryPeriod::Day, Some(1_709_294_400), 1_709_380_800, "UTC", vec![(1_709_251_200, 0, 0), (1_709_337_600, 0, 0)]; "since without sessions")]
+    #[test_case(vec![(1_711_922_400, 60)], shared::SummaryPeriod::Day, Some(1_711_800_000), 1_711_972_800, "Europe/Helsinki", vec![(1_711_749_600, 0, 0), (1_711_836_000, 0, 0), (1_711_918_800, 60, 1)]; "daylight saving")]
+    #[test_case(vec![(1_709_294_400, 3600)], shared::SummaryPeriod::Week, None, 1_709_596_800, "UTC", vec![(1_708_905_600, 3600, 1), (1_709_510_400, 0, 0)]; "weeks")]
+    #[test_case(vec![(1_706_742_000, 100), (1_706_745_600, 200)], shared::SummaryPeriod::Month, None, 1_709_251_200, "UTC", vec![(1_704_067_200, 100, 1), (1_706_745_600, 200, 1)]; "months")]
+    fn histogram(
+        sessions: Vec<(i64, u64)>,
+        bucket: shared::SummaryPeriod,
+        since: Option<i64>,
+        until: i64,
+        timezone: &str,
+        output: Vec<(i64, u64, u64)>,
+    ) {
+        let timestamp = |seconds| OffsetDateTime::from_unix_timestamp(seconds).unwrap();
+        let sessions: Vec<(OffsetDateTime, u64)> = sessions
+            .into_iter()
+            .map(|(time, duration)| (timestamp(time), duration))
+            .collect();
+        let timezone = time_tz::timezones::get_by_name(timezone).unwrap();
+        let buckets = super::histogram(&sessions, bucket, since.map(timestamp), timestamp(until), timezone);
+        let buckets: Vec<(i64, u64, u64)> = buckets
+            .into_iter()
+            .map(|bucket| (bucket.start.unix_timestamp(), bucket.duration, bucket.sessions))
+            .collect();
+        assert_eq!(buckets, output);
+    }
+
+    #[test_case(vec![(1_704_110_400, 600), (1_704_114_000, 600), (1_709_294_400, 900)], shared::SummaryPeriod::Day, 5, vec![(1_704_067_200, 1200, 2), (1_709_251_200, 900, 1)]; "days")]
+    #[test_case(vec![(1_704_110_400, 600), (1_709_294_400, 600), (1_709_337_600, 60)], shared::SummaryPeriod::Day, 1, vec![(1_704_067_200, 600, 1)]; "earlier first")]
+    #[test_case(vec![(1_704_110_400, 600), (1_704_585_600, 60), (1_704_672_000, 60)], shared::SummaryPeriod::Week, 5, vec![(1_704_067_200, 660, 2), (1_704_672_000, 60, 1)]; "weeks")]
+    fn top_periods(
+        sessions: Vec<(i64, u64)>,
+        period: shared::SummaryPeriod,
+        count: usize,
+        output: Vec<(i64, u64, u64)>,
+    ) {
+        let sessions: Vec<(OffsetDateTime, u64)> = sessions
+            .into_iter()
+            .map(|(time, duration)| (OffsetDateTime::from_unix_timestamp(time).unwrap(), duration))
+            .collect();
+        let timezone = time_tz::timezones::get_by_name("UTC").unwrap();
+        let periods: Vec<(i64, u64, u64)> = super::top_periods(&sessions, period, timezone, count)
+            .iter()
+            .map(|period| (period.start.unix_timestamp(), period.duration, period.sessions))
+            .collect();
+        assert_eq!(periods, output);
+    }
+
+    #[test_case(vec![(1_704_110_400, 3600), (1_704_114_000, 600), (1_709_294_400, 60)], 2024, "UTC", vec![(0, 4200), (60, 60)]; "days")]
+    #[test_case(vec![(1_704_063_600, 60), (1_735_686_000, 120)], 2024, "UTC", vec![(365, 120)]; "other years")]
+    #[test_case(vec![(1_704_063_600, 60)], 2024, "Europe/Helsinki", vec![(0, 60)]; "new year locally")]
+    fn heatmap(sessions: Vec<(i64, u64)>, year: i32, timezone: &str, output: Vec<(usize, u64)>) {
+        let sessions: Vec<(OffsetDateTime, u64)> = sessions
+            .into_iter()
+            .map(|(time, duration)| (OffsetDateTime::from_unix_timestamp(time).unwrap(), duration))
+            .collect();
+        let timezone = time_tz::timezones::get_by_name(timezone).unwrap();
+        let days = super::heatmap(&sessions, year, timezone);
+        assert_eq!(days.len(), 366);
+        let played: Vec<(usize, u64)> = days
+            .into_iter()
+            .enumerate()
+            .filter(|(_, duration)| *duration > 0)
+            .collect();
+        assert_eq!(played, output);
+    }
+
+    #[test_case(None, None, Some((None, None)), vec![]; "everything")]
+    #[test_case(Some(1_709_251_200), Some(1_709_510_400), Some((Some(61), Some(64))), vec![]; "whole days")]
+    #[test_case(Some(1_709_294_400), Some(1_709_460_000), Some((Some(62), Some(63))), vec![(1_709_294_400, 1_709_337_600), (1_709_424_000, 1_709_460_000)]; "parts of days")]
+    #[test_case(Some(1_709_294_400), None, Some((Some(62), None)), vec![(1_709_294_400, 1_709_337_600)]; "since")]
+    #[test_case(None, Some(1_709_460_000), Some((None, Some(63))), vec![(1_709_424_000, 1_709_460_000)]; "until")]
+    #[test_case(Some(1_709_294_400), Some(1_709_298_000), None, vec![(1_709_294_400, 1_709_298_000)]; "within a day")]
+    #[test_case(Some(1_709_294_400), Some(1_709_380_800), None, vec![(1_709_294_400, 1_709_380_800)]; "across midnight")]
+    #[test_case(Some(1_709_298_000), Some(1_709_294_400), None, vec![]; "empty")]
+    fn split_days(
+        since: Option<i64>,
+        until: Option<i64>,
+        days: Option<(Option<u16>, Option<u16>)>,
+        edges: Vec<(i64, i64)>,
+    ) {
+        let timestamp = |seconds| OffsetDateTime::from_unix_timestamp(seconds).unwrap();
+        let date = |day| time::Date::from_ordinal_date(2024, day).unwrap();
+        let (output_days, output_edges) = super::split_days(since.map(timestamp), until.map(timestamp));
+        assert_eq!(output_days, days.map(|(first, end)| (first.map(date), end.map(date))));
+        let edges: Vec<(OffsetDateTime, OffsetDateTime)> = edges
+            .into_iter()
+            .map(|(since, until)| (timestamp(since), timestamp(until)))
+            .collect();
+        assert_eq!(output_edges, edges);
+    }
+
+    #[test_case(vec![], vec![]; "nothing")]
+    #[test_case(vec![("a", 60, 1), ("b", 120, 1), ("a", 120, 2)], vec![("a", 180, 3), ("b", 120, 1)]; "merged")]
+    #[test_case(vec![("a", 60, 1), ("b", 60, 2)], vec![("a", 60, 1), ("b", 60, 2)]; "ties keep their order")]
+    fn merge_totals(totals: Vec<(&str, u64, u64)>, output: Vec<(&str, u64, u64)>) {
+        assert_eq!(super::merge_totals(totals), output);
+    }
+
+    #[test_case("Game™", false, true, None, Some("Game"); "trademark")]
+    #[test_case("  Some   Game ®", true, true, None, Some("Some Game"); "trim")]
+    #[test_case("  Some Game ", false, false, None, Some("  Some Game "); "no rules")]
+    #[test_case("Game (64-bit)", true, false, Some((r"\(\d+-bit\)", "")), Some("Game"); "replacement")]
+    #[test_case("Game: Remastered", false, false, Some((r"(.+): (.+)", "$1 $2")), Some("Game Remastered"); "capture groups")]
+    #[test_case("™", true, true, None, None; "nothing left")]
+    fn normalise_name(name: &str, trim: bool, strip_trademarks: bool, replacement: Option<(&str, &str)>, output: Option<&str>) {
+        let rules = crate::config::NameNormalization {
+            trim,
+            strip_trademarks,
+            replacements: replacement
+                .into_iter()
+                .map(|(pattern, replacement)| crate::config::NameReplacement {
+                    pattern: crate::config::Pattern(regex::Regex::new(pattern).unwrap()),
+                    replacement: replacement.to_string(),
+                })
+                .collect(),
+            case_insensitive: false,
+        };
+        assert_eq!(super::normalise_name(name, &rules).as_deref(), output);
+    }
+
+    #[test_case("UnityCrashHandler64.exe", None; "not ignored")]
+    #[test_case("steamwebhelper.exe", Some(crate::config::IgnoreAction::Reject); "executable")]
+    #[test_case("SteamWebHelper.exe", Some(crate::config::IgnoreAction::Reject); "executable case")]
+    #[test_case("EpicWebHelper.exe", Some(crate::config::IgnoreAction::Drop); "pattern")]
+    #[test_case("CrashReporter.exe", None; "pattern case")]
+    fn ignored(executable: &str, action: Option<crate::config::IgnoreAction>) {
+        let rules = vec![
+            crate::config::IgnoreRule {
+                executable: Some("steamwebhelper.exe".to_string()),
+                pattern: None,
+                action: crate::config::IgnoreAction::Reject,
+            },
+            crate::config::IgnoreRule {
+                executable: None,
+                pattern: Some(crate::config::Pattern(regex::Regex::new(r"(Helper|crash)").unwrap())),
+                action: crate::config::IgnoreAction::Drop,
+            },
+        ];
+        assert_eq!(super::ignored(executable, &rules), action);
+    }
+
+    #[test_case(Some(1_709_294_400), 3600, None; "valid")]
+    #[test_case(None, 3600, Some(("ended_at", "ended_at is missing")); "no end")]
+    #[test_case(Some(1_709_384_400), 3600, Some(("ended_at", "session ends in the future")); "future")]
+    #[test_case(Some(1_709_294_400), 5, Some(("duration", "duration is under 10 seconds")); "too short")]
+    fn backfill_problem(ended_at: Option<i64>, duration: u64, output: Option<(&str, &str)>) {
+        let submission = shared::Submission {
+            duration: shared::SessionDuration::from_secs(duration),
+            executable: "game.exe".to_string(),
+            name: None,
+            product_version: None,
+            file_version: None,
+            started_at: None,
+            ended_at: ended_at.map(|time| OffsetDateTime::from_unix_timestamp(time).unwrap()),
+            focused_duration: None,
+            tags: Vec::new(),
+            window_title: None,
+            session_id: None,
+            device_id: None,
+        };
+        let limits = crate::config::SubmissionLimits {
+            minimum_duration: 10,
+            ..Default::default()
+        };
+        let now = OffsetDateTime::from_unix_timestamp(1_709_298_000).unwrap();
+        let problem = super::backfill_problem(&submission, &limits, now);
+        assert_eq!(
+            problem.as_ref().map(|(field, message)| (*field, message.as_str())),
+            output
+        );
+    }
+
+    #[test_case(None, None, None; "no device")]
+    #[test_case(None, Some(3), None; "device not named")]
+    #[test_case(Some(3), Some(3), None; "same device")]
+    #[test_case(Some(3), Some(4), Some(("device_id", "device 3 is not the authenticated device")); "other device")]
+    #[test_case(Some(3), None, Some(("device_id", "device 3 is not the authenticated device")); "not a device")]
+    fn device_problem(device_id: Option<i32>, device: Option<i32>, output: Option<(&str, &str)>) {
+        let submission = shared::Submission {
+            duration: shared::SessionDuration::from_secs(3600),
+            executable: "game.exe".to_string(),
+            name: None,
+            product_version: None,
+            file_version: None,
+            started_at: None,
+            ended_at: None,
+            focused_duration: None,
+            tags: Vec::new(),
+            window_title: None,
+            session_id: None,
+            device_id,
+        };
+        let problem = super::device_problem(&submission, device);
+        assert_eq!(
+            problem.as_ref().map(|(field, message)| (*field, message.as_str())),
+            output
+        );
+    }
+
+    #[test_case(None, "backfill:playnite:game.exe:1709294400"; "derived")]
+    #[test_case(Some("3f0c"), "3f0c"; "session ID")]
+    fn backfill_key(session_id: Option<&str>, output: &str) {
+        let submission = shared::Submission {
+            duration: shared::SessionDuration::from_secs(3600),
+            executable: "game.exe".to_string(),
+            name: None,
+            product_version: None,
+            file_version: None,
+            started_at: None,
+            ended_at: Some(OffsetDateTime::from_unix_timestamp(1_709_294_400).unwrap()),
+            focused_duration: None,
+            tags: Vec::new(),
+            window_title: None,
+            session_id: session_id.map(str::to_string),
+            device_id: None,
+        };
+        assert_eq!(super::backfill_key("playnite", &submission), output);
+    }
+
+    #[test_case(None, None, shared::SortOrder::Asc, Some((vec![1, 2], Some("2"))); "first page")]
+    #[test_case(Some("2"), None, shared::SortOrder::Asc, Some((vec![3, 4], Some("4"))); "next page")]
+    #[test_case(Some("4"), None, shared::SortOrder::Asc, Some((vec![5], None)); "last page")]
+    #[test_case(None, None, shared::SortOrder::Desc, Some((vec![5, 4], Some("2"))); "descending")]
+    #[test_case(None, Some("parity"), shared::SortOrder::Asc, Some((vec![4, 2], Some("2"))); "other sort keeps ties in order")]
+    #[test_case(None, Some("parity"), shared::SortOrder::Desc, Some((vec![3, 1], Some("2"))); "other sort descending")]
+    #[test_case(None, Some("size"), shared::SortOrder::Asc, None; "unknown sort")]
+    #[test_case(Some("next"), None, shared::SortOrder::Asc, None; "invalid page")]
+    fn paginate(
+        page: Option<&str>,
+        sort: Option<&str>,
+        order: shared::SortOrder,
+        output: Option<(Vec<u32>, Option<&str>)>,
+    ) {
+        let query = shared::ListQuery {
+            page: page.map(str::to_string),
+            limit: None,
+            sort: sort.map(str::to_string),
+            order,
+        };
+        let sorts: [crate::pagination::Sort<u32>; 2] = [
+            ("value", |a, b| a.cmp(b)),
+            ("parity", |a, b| (a % 2).cmp(&(b % 2))),
+        ];
+        let page = super::paginate(vec![3, 1, 4, 5, 2], &query, &sorts, 2);
+        let page = page.map(|page| (page.items, page.next_page));
+        let output = output.map(|(items, next)| (items, next.map(str::to_string)));
+        assert_eq!(page, output);
+    }
+
+    #[test_case(&["strategy", "4x"], &["4x", "strategy"]; "sorted")]
+    #[test_case(&[" shooter ", "shooter"], &["shooter"]; "duplicates")]
+    #[test_case(&["", "  ", "rpg"], &["rpg"]; "empty")]
+    fn clean_categories(input: &[&str], output: &[&str]) {
+        let input = input.iter().map(|category| category.to_string()).collect();
+        assert_eq!(super::clean_categories(input), output);
+    }
+
+    #[test_case("2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b", Some(0x2b), Some(0x5b); "lowercase")]
+    #[test_case("2BB80D537B1DA3E38BD30361AA855686BDE0EACD7162FEF6A25FE97BF527A25B", Some(0x2b), Some(0x5b); "uppercase")]
+    #[test_case("2bb80d537b1da3e38bd30361aa855686", None, None; "too short")]
+    #[test_case("zzb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b", None, None; "not hex")]
+    #[test_case("+bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b", None, None; "sign")]
+    fn parse_sha256_hex(value: &str, first: Option<u8>, last: Option<u8>) {
+        let digest = super::parse_sha256_hex(value);
+        assert_eq!(digest.map(|digest| digest[0]), first);
+        assert_eq!(digest.map(|digest| digest[31]), last);
+    }
+
+    #[test_case("key", "", "5d5d139563c95b5967b9bd9a8c9b233a9dedb45072794cd232dc1b74832607d0"; "empty")]
+    #[test_case("Jefe", "what do ya want for nothing?", "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"; "rfc 4231")]
+    fn hmac_sha256_hex(key: &str, data: &str, output: &str) {
+        assert_eq!(super::hmac_sha256_hex(key, data.as_bytes()), output);
+    }
+
+    #[test_case("", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"; "empty")]
+    #[test_case("abc", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"; "abc")]
+    fn sha256_hex(data: &str, output: &str) {
+        assert_eq!(super::sha256_hex(data.as_bytes()), output);
+    }
+
+    #[test_case(59, "0m"; "seconds")]
+    #[test_case(14 * 60, "14m"; "minutes")]
+    #[test_case(2 * 3600 + 14 * 60 + 30, "2h 14m"; "hours")]
+    fn format_duration(seconds: u64, output: &str) {
+        assert_eq!(super::format_duration(seconds), output);
+    }
+
+    #[test_case(59, "0 min"; "seconds")]
+    #[test_case(45 * 60, "45 min"; "minutes")]
+    #[test_case(3600 + 59 * 60, "1 h"; "hours")]
+    #[test_case(999 * 3600, "999 h"; "hundreds")]
+    #[test_case(1_234 * 3600, "1,234 h"; "thousands")]
+    #[test_case(1_234_567 * 3600, "1,234,567 h"; "millions")]
+    fn format_hours(seconds: u64, output: &str) {
+        assert_eq!(super::format_hours(seconds), output);
+    }
+
+    #[test_case("{user} played {game} for {duration}", "Hamuko played Factorio for 2h 14m"; "all")]
+    #[test_case("{game}: {game}", "Factorio: Factorio"; "repeated")]
+    #[test_case("{user} {unknown}", "Hamuko {unknown}"; "unknown")]
+    fn render_template(template: &str, output: &str) {
+        let values = [
+            ("user", "Hamuko"),
+            ("game", "Factorio"),
+            ("duration", "2h 14m"),
+        ];
+        assert_eq!(super::render_template(template, &values), output);
+    }
+
+    #[test_case(r#"{"text": "{game}"}"#, r#"{"text": "Factorio"}"#; "plain")]
+    #[test_case(r#"{"text": "{title}"}"#, r#"{"text": "\"Quoted\" \\ path\n"}"#; "escaped")]
+    #[test_case(r#"{"seconds": {duration}}"#, r#"{"seconds": 8040}"#; "number")]
+    fn render_json_template(template: &str, output: &str) {
+        let values = [
+            ("game", "Factorio"),
+            ("title", "\"Quoted\" \\ path\n"),
+            ("duration", "8040"),
+        ];
+        assert_eq!(super::render_json_template(template, &values), output);
+    }
+
+    #[test_case("elden", "ELDEN RING", 1.0; "contained")]
+    #[test_case("eldn ring", "ELDEN RING", 0.8; "typo")]
+    #[test_case("elden ring", "eldenring_dx12.exe", 8.0 / 11.0; "joined words")]
+    #[test_case("factorio", "ELDEN RING", 0.0; "different")]
+    #[test_case("--", "ELDEN RING", 0.0; "no words")]
+    fn fuzzy_match(query: &str, text: &str, output: f64) {
+        assert_eq!(super::fuzzy_match(query, text), output);
+    }
+
+    #[test_case(150, 100, Some(50.0); "increase")]
+    #[test_case(50, 100, Some(-50.0); "decrease")]
+    #[test_case(1, 3, Some(-66.7); "rounded")]
+    #[test_case(60, 0, None; "nothing before")]
+    fn percent_change(current: u64, previous: u64, output: Option<f64>) {
+        assert_eq!(super::percent_change(current, previous), output);
+    }
+
+    #[test_case(vec![("a", 600), ("b", 300)], vec![("a", 300)], vec![("a", 600, 300, 300, Some(100.0)), ("b", 300, 0, 300, None)]; "played more")]
+    #[test_case(vec![("a", 60)], vec![("b", 600), ("a", 120)], vec![("a", 60, 120, -60, Some(-50.0)), ("b", 0, 600, -600, Some(-100.0))]; "stopped playing")]
+    #[test_case(vec![("a", 60), ("a", 60)], vec![], vec![("a", 120, 0, 120, None)]; "devices combined")]
+    fn compare_games(
+        current: Vec<(&str, u64)>,
+        previous: Vec<(&str, u64)>,
+        output: Vec<(&str, u64, u64, i64, Option<f64>)>,
+    ) {
+        let stats = |processes: Vec<(&str, u64)>| {
+            processes
+                .into_iter()
+                .map(|(executable, duration)| shared::ProcessStats {
+                    executable: executable.to_string(),
+                    name: None,
+                    duration,
+                    sessions: 1,
+                    device: None,
+                    first_played: None,
+                    last_played: None,
+                    metadata: None,
+                })
+                .collect()
+        };
+        let games = super::compare_games(stats(current), stats(previous));
+        let games: Vec<(&str, u64, u64, i64, Option<f64>)> = games
+            .iter()
+            .map(|game| {
+                (
+                    game.executable.as_str(),
+                    game.duration,
+                    game.previous_duration,
+                    game.difference,
+                    game.change,
+                )
+            })
+            .collect();
+        assert_eq!(games, output);
+    }
+
+    #[test_case(Some("  finished the DLC "), Some(5), Ok((Some("finished the DLC"), Some(5))); "note and rating")]
+    #[test_case(Some("   "), None, Ok((None, None)); "blank note")]
+    #[test_case(Some("co-op with Alex and Sam"), None, Err("note is over 16 characters".to_string()); "long note")]
+    #[test_case(None, Some(0), Err("rating is not from 1 to 5".to_string()); "rating too low")]
+    #[test_case(None, Some(6), Err("rating is not from 1 to 5".to_string()); "rating too high")]
+    fn annotate_event(
+        note: Option<&str>,
+        rating: Option<u8>,
+        output: Result<(Option<&str>, Option<u8>), String>,
+    ) {
+        let start = OffsetDateTime::from_unix_timestamp(1_709_294_400).unwrap();
+        let event = shared::Event {
+            id: 1,
+            executable: "factorio.exe".to_string(),
+            name: None,
+            started_at: start,
+            ended_at: start + time::Duration::hours(1),
+            duration: 3600,
+            focused_duration: None,
+            product_version: None,
+            file_version: None,
+            tags: Vec::new(),
+            window_title: None,
+            api_key: None,
+            device: None,
+            imported: false,
+            manual: false,
+            note: Some("old note".to_string()),
+            rating: Some(3),
+        };
+        let note = shared::EventNote {
+            note: note.map(str::to_string),
+            rating,
+        };
+        let annotated = super::annotate_event(&event, &note, 16);
+        let annotated = annotated
+            .as_ref()
+            .map(|event| (event.note.as_deref(), event.rating))
+            .map_err(String::clone);
+        assert_eq!(annotated, output);
+    }
+
+    #[test_case(Some(25.0), 4800, "up 25% from 1h 20m the week before"; "increase")]
+    #[test_case(Some(-10.5), 600, "down 10.5% from 10m the week before"; "decrease")]
+    #[test_case(Some(0.0), 600, "the same as the week before"; "unchanged")]
+    #[test_case(None, 0, "nothing was played the week before"; "nothing before")]
+    fn describe_change(change: Option<f64>, previous: u64, output: &str) {
+        assert_eq!(super::describe_change(change, previous, "week"), output);
+    }
+
+    #[test_case("doom", "%doom%"; "plain")]
+    #[test_case("100%", "%100\\%%"; "percent")]
+    #[test_case("half_life", "%half\\_life%"; "underscore")]
+    #[test_case(r"C:\Games", r"%C:\\Games%"; "backslash")]
+    fn like_pattern(search: &str, output: &str) {
+        assert_eq!(super::like_pattern(search), output);
+    }
+
+    #[test_case("eldenring.exe", Some("ELDEN RING"), "ELDEN RING"; "name")]
+    #[test_case("eldenring.exe", None, "eldenring"; "executable")]
+    #[test_case("factorio", None, "factorio"; "no extension")]
+    fn search_title(executable: &str, name: Option<&str>, output: &str) {
+        assert_eq!(super::search_title(executable, name), output);
+    }
+
+    #[test_case("eldenring", "eldenring"; "executable")]
+    #[test_case("ELDEN RING", "eldenring"; "spaces and case")]
+    #[test_case("Baldur's Gate 3", "baldursgate3"; "punctuation")]
+    #[test_case("Ōkami HD", "ōkamihd"; "non-ascii")]
+    fn normalise_title(title: &str, output: &str) {
+        assert_eq!(super::normalise_title(title), output);
+    }
+
+    #[test_case("application/msgpack", true; "exact")]
+    #[test_case("Application/MsgPack; charset=binary", true; "parameters")]
+    #[test_case("application/json, application/msgpack;q=0.9", true; "list")]
+    #[test_case("application/json", false; "other")]
+    #[test_case("application/msgpack-extra", false; "prefix")]
+    fn has_media_type(header: &str, output: bool) {
+        assert_eq!(super::has_media_type(header, "application/msgpack"), output);
+    }
+
+    #[test_case("10.0.0.0/8", Some(("10.0.0.0", 8)); "ipv4 network")]
+    #[test_case("192.168.1.10", Some(("192.168.1.10", 32)); "ipv4 address")]
+    #[test_case("fd00::/8", Some(("fd00::", 8)); "ipv6 network")]
+    #[test_case("::ffff:10.1.2.3", Some(("10.1.2.3", 32)); "mapped ipv4")]
+    #[test_case("10.0.0.0/33", None; "too long prefix")]
+    #[test_case("proxy.internal", None; "hostname")]
+    fn parse_network(text: &str, output: Option<(&str, u8)>) {
+        let output = output.map(|(address, prefix)| (address.parse().unwrap(), prefix));
+        let network = super::parse_network(text).map(|network| (network.address, network.prefix));
+        assert_eq!(network, output);
+    }
+
+    #[test_case(vec![("a", Some("Deck"), 600, 2), ("b", Some("Deck"), 300, 1), ("a", Some("Desktop"), 1200, 3)], vec![(Some("Desktop"), 1200, 3, 1), (Some("Deck"), 900, 3, 2)]; "devices")]
+    #[test_case(vec![("a", None, 60, 1), ("a", Some("Laptop"), 60, 1)], vec![(None, 60, 1, 1), (Some("Laptop"), 60, 1, 1)]; "unregistered and tie")]
+    #[test_case(vec![], vec![]; "no sessions")]
+    fn device_totals(
+        stats: Vec<(&str, Option<&str>, u64, u64)>,
+        output: Vec<(Option<&str>, u64, u64, u64)>,
+    ) {
+        let stats = stats
+            .into_iter()
+            .map(|(executable, device, duration, sessions)| shared::ProcessStats {
+                executable: executable.to_string(),
+                name: None,
+                duration,
+                sessions,
+                device: device.map(str::to_string),
+                first_played: None,
+                last_played: None,
+                metadata: None,
+            })
+            .collect::<Vec<_>>();
+        let output = output
+            .into_iter()
+            .map(|(device, duration, sessions, games)| shared::DeviceStats {
+                device: device.map(str::to_string),
+                duration,
+                sessions,
+                games,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(super::device_totals(&stats), output);
+    }
+
+    #[test_case(shared::StatsGrouping::Process, vec![("game.exe", Some("Game"), 600, 100), ("game.exe", Some("Game™"), 300, 200), ("other.exe", Some("Game"), 60, 300)]; "pairs")]
+    #[test_case(shared::StatsGrouping::Executable, vec![("game.exe", Some("Game"), 900, 200), ("other.exe", Some("Game"), 60, 300)]; "executables")]
+    #[test_case(shared::StatsGrouping::Name, vec![("game.exe", Some("Game"), 660, 300), ("game.exe", Some("Game™"), 300, 200)]; "names")]
+    fn group_stats(
+        grouping: shared::StatsGrouping,
+        output: Vec<(&str, Option<&str>, u64, i64)>,
+    ) {
+        let process = |executable: &str, name: &str, duration, last_played| shared::ProcessStats {
+            executable: executable.to_string(),
+            name: Some(name.to_string()),
+            duration,
+            sessions: 1,
+            device: None,
+            first_played: Some(OffsetDateTime::from_unix_timestamp(last_played - 50).unwrap()),
+            last_played: Some(OffsetDateTime::from_unix_timestamp(last_played).unwrap()),
+            metadata: None,
+        };
+        let stats = vec![
+            process("game.exe", "Game™", 300, 200),
+            process("other.exe", "Game", 60, 300),
+            process("game.exe", "Game", 600, 100),
+        ];
+        let grouped = super::group_stats(stats, grouping)
+            .into_iter()
+            .map(|process| {
+                (
+                    process.executable,
+                    process.name,
+                    process.duration,
+                    process.last_played.unwrap().unix_timestamp(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let output = output
+            .into_iter()
+            .map(|(executable, name, duration, last_played)| {
+                (executable.to_string(), name.map(str::to_string), duration, last_played)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(grouped, output);
+    }
+
+    #[test_case("Elden Ring", "elden ring"; "plain")]
+    #[test_case("ELDEN RING™", "elden ring"; "trademark and case")]
+    #[test_case("Factorio v1.1.104", "factorio"; "version")]
+    #[test_case("Factorio - 1.1", "factorio"; "dashed version")]
+    #[test_case("Terraria (64-bit)", "terraria"; "bitness")]
+    #[test_case("Vintage Story Build 1234 [x64]", "vintage story"; "build and bitness")]
+    #[test_case("Hades II", "hades ii"; "sequel numeral")]
+    #[test_case("Left 4 Dead 2", "left 4 dead 2"; "sequel number")]
+    #[test_case("1.2.3", "1 2 3"; "only a version")]
+    fn duplicate_key(name: &str, output: &str) {
+        assert_eq!(super::duplicate_key(name), output);
+    }
+
+    #[test_case(vec![("game.exe", Some("Game")), ("Game.exe", Some("Game: Remastered"))], vec![vec![0, 1]]; "executable")]
+    #[test_case(vec![("game.exe", Some("Game v1.0")), ("game_dx12.exe", Some("Game™ v1.1"))], vec![vec![0, 1]]; "name")]
+    #[test_case(vec![("a.exe", Some("A")), ("b.exe", Some("B")), ("a.exe", Some("A 1.2")), ("c.exe", Some("B"))], vec![vec![0, 2], vec![1, 3]]; "separate groups")]
+    #[test_case(vec![("a.exe", Some("Game")), ("b.exe", Some("Other")), ("b.exe", Some("Game 2.0")), ("c.exe", Some("Other"))], vec![vec![0, 1, 2, 3]]; "chained")]
+    #[test_case(vec![("hades.exe", Some("Hades")), ("hades2.exe", Some("Hades II"))], vec![]; "sequel")]
+    #[test_case(vec![("a.exe", None), ("b.exe", None)], vec![]; "no names")]
+    fn duplicate_groups(processes: Vec<(&str, Option<&str>)>, output: Vec<Vec<usize>>) {
+        assert_eq!(super::duplicate_groups(&processes), output);
+    }
 
-    #[test_case("Grand Theft Auto IV", "Grand Theft Auto IV"; "clean name")]
-    #[test_case("Rockstar Games Launcher Redirector\0\08\u{12}\u{1}ProductVersion\01.0.0.66\0\0D\0\0Va", "Rockstar Games Launcher Redirector"; "corrupt name")]
-    fn clean_name(input: &str, output: &str) {
-        assert_eq!(super::clean_name(&String::from(input)), &String::from(output));
+    #[test_case(400, shared::ApiErrorCode::Validation; "bad request")]
+    #[test_case(422, shared::ApiErrorCode::Validation; "unprocessable")]
+    #[test_case(401, shared::ApiErrorCode::Unauthenticated; "unauthorized")]
+    #[test_case(409, shared::ApiErrorCode::Duplicate; "conflict")]
+    #[test_case(426, shared::ApiErrorCode::VersionMismatch; "upgrade required")]
+    #[test_case(429, shared::ApiErrorCode::RateLimited; "rate limited")]
+    #[test_case(503, shared::ApiErrorCode::Storage; "unavailable")]
+    #[test_case(418, shared::ApiErrorCode::Unknown; "other")]
+    fn error_code(status: u16, code: shared::ApiErrorCode) {
+        assert_eq!(super::error_code(status), code);
     }
 }
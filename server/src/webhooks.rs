@@ -0,0 +1,259 @@
+use std::sync::RwLock;
+use std::time::Duration;
+
+use log::{debug, error, warn};
+use serde::Serialize;
+use shared::SessionDuration;
+use time::OffsetDateTime;
+
+use crate::{
+    config::{NotifierConfig, NotifierService, WebhookConfig},
+    db::NewEvent,
+    users::UserIds,
+    util,
+};
+
+/// Header with the HMAC-SHA256 of the body, for webhooks with a secret.
+const SIGNATURE_HEADER: &str = "X-Beelzebub-Signature";
+
+/// Deliveries tried before the session is dropped.
+const MAXIMUM_ATTEMPTS: u32 = 5;
+
+/// Wait before the first retry, doubled for every retry after it.
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Recorded session as delivered to webhooks.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub executable: String,
+    pub name: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub ended_at: OffsetDateTime,
+
+    /// Duration in seconds.
+    pub duration: u64,
+    pub focused_duration: Option<u64>,
+    pub product_version: Option<String>,
+    pub file_version: Option<String>,
+    pub tags: Vec<String>,
+    pub window_title: Option<String>,
+    pub api_key: Option<String>,
+    pub user: Option<String>,
+    pub device: Option<i32>,
+}
+
+impl Session {
+    pub fn new(event: &NewEvent, users: &UserIds) -> Self {
+        let user = event.user_id.and_then(|user_id| {
+            users
+                .iter()
+                .find(|(_, id)| **id == user_id)
+                .map(|(name, _)| name.clone())
+        });
+        Session {
+            executable: event.executable.clone(),
+            name: event.name.clone(),
+            started_at: event.started_at,
+            ended_at: event.time,
+            duration: event.duration.as_secs(),
+            focused_duration: event.focused_duration.map(SessionDuration::as_secs),
+            product_version: event.product_version.clone(),
+            file_version: event.file_version.clone(),
+            tags: event.tags.clone(),
+            window_title: event.window_title.clone(),
+            api_key: event.api_key.clone(),
+            user,
+            device: event.device,
+        }
+    }
+}
+
+fn matches(webhook: &WebhookConfig, session: &Session) -> bool {
+    if session.duration < webhook.minimum_duration {
+        return false;
+    }
+    if !webhook.users.is_empty()
+        && !session
+            .user
+            .as_ref()
+            .is_some_and(|user| webhook.users.contains(user))
+    {
+        return false;
+    }
+    if !webhook.processes.is_empty() {
+        let executable = session.executable.to_lowercase();
+        let name = session.name.as_deref().unwrap_or("").to_lowercase();
+        return webhook.processes.iter().any(|process| {
+            let process = process.to_lowercase();
+            executable.contains(&process) || name.contains(&process)
+        });
+    }
+    true
+}
+
+/// Endpoints notified of recorded sessions.
+pub struct Webhooks {
+    client: reqwest::Client,
+
+    /// Replaced when the configuration is reloaded.
+    targets: RwLock<(Vec<WebhookConfig>, Vec<NotifierConfig>)>,
+}
+
+impl Webhooks {
+    pub fn new(
+        webhooks: Vec<WebhookConfig>,
+        notifiers: Vec<NotifierConfig>,
+    ) -> Result<Self, reqwest::Error> {
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("beelzebub-server/", env!("CARGO_PKG_VERSION")))
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+        Ok(Webhooks {
+            client,
+            targets: RwLock::new((webhooks, notifiers)),
+        })
+    }
+
+    /// Replace the webhooks and chat notifiers. Deliveries in progress go on
+    /// to the old ones.
+    pub fn configure(&self, webhooks: Vec<WebhookConfig>, notifiers: Vec<NotifierConfig>) {
+        match self.targets.write() {
+            Ok(mut targets) => *targets = (webhooks, notifiers),
+            Err(_) => error!("Could not update webhooks"),
+        }
+    }
+
+    /// Send the session to every matching webhook and chat notifier in the
+    /// background.
+    pub fn deliver(&self, session: &Session) {
+        let Ok(targets) = self.targets.read() else {
+            error!("Could not read webhooks");
+            return;
+        };
+        let (webhooks, notifiers) = &*targets;
+        let matching = webhooks
+            .iter()
+            .filter(|webhook| matches(webhook, session))
+            .collect::<Vec<_>>();
+        if !matching.is_empty() {
+            match serde_json::to_vec(session) {
+                Ok(body) => {
+                    for webhook in matching {
+                        let signature = webhook.secret.as_ref().map(|secret| {
+                            format!("sha256={}", util::hmac_sha256_hex(secret, &body))
+                        });
+                        let client = self.client.clone();
+                        tokio::spawn(send(client, webhook.url.clone(), signature, body.clone()));
+                    }
+                }
+                Err(error) => error!("Could not serialise session for webhooks: {}", error),
+            }
+        }
+        for notifier in notifiers {
+            if session.duration < notifier.minimum_duration {
+                continue;
+            }
+            let message = notification(&notifier.template, session);
+            let body = chat_body(notifier.service, &message);
+            let client = self.client.clone();
+            tokio::spawn(send(client, notifier.url.clone(), None, body));
+        }
+    }
+
+    /// Post the JSON body to the URL in the background, retrying like
+    /// session deliveries.
+    pub fn post(&self, url: &str, body: Vec<u8>) {
+        tokio::spawn(send(self.client.clone(), url.to_string(), None, body));
+    }
+}
+
+/// Body of an incoming webhook request posting the message to the channel.
+pub fn chat_body(service: NotifierService, message: &str) -> Vec<u8> {
+    let body = match service {
+        NotifierService::Discord => serde_json::json!({ "content": message }),
+        NotifierService::Slack => serde_json::json!({ "text": message }),
+    };
+    body.to_string().into_bytes()
+}
+
+/// Message for a chat notifier with the placeholders of the template filled
+/// in from the session.
+fn notification(template: &str, session: &Session) -> String {
+    let user = session
+        .user
+        .as_deref()
+        .or(session.api_key.as_deref())
+        .unwrap_or("Someone");
+    let game = session.name.as_deref().unwrap_or(&session.executable);
+    let duration = util::format_duration(session.duration);
+    util::render_template(
+        template,
+        &[
+            ("user", user),
+            ("game", game),
+            ("executable", &session.executable),
+            ("duration", &duration),
+        ],
+    )
+}
+
+/// Scheme and host of the URL for logging. Webhook URLs often contain a
+/// token in the path.
+fn origin(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(url) => url.origin().ascii_serialization(),
+        Err(_) => "with an invalid URL".to_string(),
+    }
+}
+
+async fn send(client: reqwest::Client, url: String, signature: Option<String>, body: Vec<u8>) {
+    let target = origin(&url);
+    let mut delay = RETRY_DELAY;
+    for attempt in 1..=MAXIMUM_ATTEMPTS {
+        let mut request = client
+            .post(&url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.clone());
+        if let Some(signature) = &signature {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+        match request.send().await {
+            Ok(response) if response.status().is_success() => {
+                debug!("Delivered session to webhook {}", target);
+                return;
+            }
+            // Other client errors will not go away by trying again.
+            Ok(response)
+                if response.status().is_client_error()
+                    && response.status() != reqwest::StatusCode::REQUEST_TIMEOUT
+                    && response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS =>
+            {
+                error!("Webhook {} rejected session: {}", target, response.status());
+                return;
+            }
+            Ok(response) => warn!(
+                "Webhook {} failed on attempt {}: {}",
+                target,
+                attempt,
+                response.status()
+            ),
+            Err(error) => warn!(
+                "Webhook {} failed on attempt {}: {}",
+                target, attempt, error
+            ),
+        }
+        if attempt < MAXIMUM_ATTEMPTS {
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+    error!(
+        "Giving up on webhook {} after {} attempts",
+        target, MAXIMUM_ATTEMPTS
+    );
+}
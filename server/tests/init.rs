@@ -0,0 +1,66 @@
+//! Runs `beelzebub-server init` with answers on standard input and starts the
+//! server with the configuration that it wrote.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use tempfile::TempDir;
+
+fn server() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_beelzebub-server"))
+}
+
+#[test]
+fn written_configuration_starts_the_server() {
+    let directory = TempDir::new().unwrap();
+    let config = directory.path().join("config").join("server.yaml");
+    let database = directory.path().join("data").join("beelzebub.db");
+    let answers = format!("sqlite://{}\nsecret\n\n", database.display());
+
+    let mut init = server()
+        .arg("--config")
+        .arg(&config)
+        .arg("init")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    init.stdin
+        .take()
+        .unwrap()
+        .write_all(answers.as_bytes())
+        .unwrap();
+    assert!(init.wait().unwrap().success());
+    let written = std::fs::read_to_string(&config).unwrap();
+    assert!(written.contains("secret: secret"), "{}", written);
+    assert!(written.contains("port: 8080"), "{}", written);
+
+    let migrate = server()
+        .arg("--config")
+        .arg(&config)
+        .arg("--migrate-only")
+        .status()
+        .unwrap();
+    assert!(migrate.success());
+    assert!(database.exists());
+}
+
+#[test]
+fn existing_configuration_is_kept() {
+    let directory = TempDir::new().unwrap();
+    let config = directory.path().join("server.yaml");
+    std::fs::write(&config, "dbUrl: sqlite://beelzebub.db\n").unwrap();
+
+    let init = server()
+        .arg("--config")
+        .arg(&config)
+        .arg("init")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(!init.status.success());
+    assert_eq!(
+        std::fs::read_to_string(&config).unwrap(),
+        "dbUrl: sqlite://beelzebub.db\n"
+    );
+}
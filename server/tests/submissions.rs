@@ -0,0 +1,441 @@
+//! Runs the server binary against an empty database and submits sessions to
+//! it over HTTP. The database is SQLite in a temporary directory, or a new
+//! database on the Postgres server of `BEELZEBUB_TEST_DATABASE_URL`, such as
+//! `postgres://postgres@localhost/postgres`, which is dropped afterwards.
+
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use diesel::{
+    pg::PgConnection, sql_query, sql_types::BigInt, sqlite::SqliteConnection, Connection,
+    QueryableByName, RunQueryDsl,
+};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+const SECRET: &str = "integration";
+
+/// Longest time to wait for the server to start and run its migrations.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Databases created on Postgres by this run, numbered so that tests running
+/// at the same time don't share one.
+static DATABASES: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(QueryableByName)]
+struct Count {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+enum TestDatabase {
+    Sqlite(PathBuf),
+    Postgres {
+        /// Database that the test database is created and dropped from.
+        admin_url: String,
+        name: String,
+        url: String,
+    },
+}
+
+impl TestDatabase {
+    fn new(directory: &TempDir) -> Self {
+        let Ok(admin_url) = std::env::var("BEELZEBUB_TEST_DATABASE_URL") else {
+            return TestDatabase::Sqlite(directory.path().join("beelzebub.db"));
+        };
+        let name = format!(
+            "beelzebub_test_{}_{}",
+            std::process::id(),
+            DATABASES.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut conn = PgConnection::establish(&admin_url).expect("connect to Postgres");
+        sql_query(format!("CREATE DATABASE {}", name))
+            .execute(&mut conn)
+            .expect("create test database");
+        let (base, _) = admin_url
+            .rsplit_once('/')
+            .expect("database URL has a database name");
+        let url = format!("{}/{}", base, name);
+        TestDatabase::Postgres {
+            admin_url,
+            name,
+            url,
+        }
+    }
+
+    fn url(&self) -> String {
+        match self {
+            TestDatabase::Sqlite(path) => format!("sqlite://{}", path.display()),
+            TestDatabase::Postgres { url, .. } => url.clone(),
+        }
+    }
+
+    /// Result of a `SELECT COUNT(*) AS count` query.
+    fn count(&self, query: &str) -> i64 {
+        let query = sql_query(query);
+        let row = match self {
+            TestDatabase::Sqlite(path) => {
+                let mut conn = SqliteConnection::establish(&path.display().to_string())
+                    .expect("open SQLite database");
+                query.get_result::<Count>(&mut conn)
+            }
+            TestDatabase::Postgres { url, .. } => {
+                let mut conn = PgConnection::establish(url).expect("connect to Postgres");
+                query.get_result::<Count>(&mut conn)
+            }
+        };
+        row.expect("count rows").count
+    }
+
+    /// Run statements that change the database, such as ones setting up
+    /// rows that the API can't make.
+    fn execute(&self, statement: &str) {
+        let statement = sql_query(statement);
+        let result = match self {
+            TestDatabase::Sqlite(path) => {
+                let mut conn = SqliteConnection::establish(&path.display().to_string())
+                    .expect("open SQLite database");
+                statement.execute(&mut conn)
+            }
+            TestDatabase::Postgres { url, .. } => {
+                let mut conn = PgConnection::establish(url).expect("connect to Postgres");
+                statement.execute(&mut conn)
+            }
+        };
+        result.expect("run statement");
+    }
+
+    fn drop_database(&self) {
+        if let TestDatabase::Postgres {
+            admin_url, name, ..
+        } = self
+        {
+            if let Ok(mut conn) = PgConnection::establish(admin_url) {
+                let _ = sql_query(format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", name))
+                    .execute(&mut conn);
+            }
+        }
+    }
+}
+
+struct TestServer {
+    process: Child,
+    url: String,
+    http: reqwest::Client,
+    database: TestDatabase,
+
+    /// Configuration and SQLite database, removed when the server is dropped.
+    _directory: TempDir,
+}
+
+impl TestServer {
+    async fn start() -> Self {
+        let directory = TempDir::new().expect("create temporary directory");
+        let database = TestDatabase::new(&directory);
+        // Free port for the server. Another process could take it before the
+        // server binds it, but that is unlikely enough for tests.
+        let port = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("find a free port")
+            .port();
+        let config = json!({
+            "dbUrl": database.url(),
+            "secret": SECRET,
+            "listen": {"address": "127.0.0.1", "port": port},
+        });
+        let config_path = directory.path().join("server.yaml");
+        std::fs::write(&config_path, config.to_string()).expect("write configuration");
+        let process = Command::new(env!("CARGO_BIN_EXE_beelzebub-server"))
+            .arg("--config")
+            .arg(&config_path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("start the server");
+        let mut server = TestServer {
+            process,
+            url: format!("http://127.0.0.1:{}", port),
+            http: reqwest::Client::new(),
+            database,
+            _directory: directory,
+        };
+        server.wait_until_ready().await;
+        server
+    }
+
+    async fn wait_until_ready(&mut self) {
+        let ready = format!("{}/ready", self.url);
+        let deadline = tokio::time::Instant::now() + STARTUP_TIMEOUT;
+        loop {
+            if let Ok(Some(status)) = self.process.try_wait() {
+                panic!("server exited while starting: {}", status);
+            }
+            let response = self.http.get(&ready).send().await;
+            if response.is_ok_and(|response| response.status().is_success()) {
+                return;
+            }
+            assert!(
+                tokio::time::Instant::now() < deadline,
+                "server did not start"
+            );
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    fn post(&self, path: &str, body: &Value) -> reqwest::RequestBuilder {
+        self.post_with_secret(path, body, Some(SECRET))
+    }
+
+    fn post_with_secret(
+        &self,
+        path: &str,
+        body: &Value,
+        secret: Option<&str>,
+    ) -> reqwest::RequestBuilder {
+        let request = self
+            .http
+            .post(format!("{}/api/v1{}", self.url, path))
+            .json(body);
+        match secret {
+            Some(secret) => request.header(shared::SECRET_KEY_HEADER, secret),
+            None => request,
+        }
+    }
+
+    fn get(&self, path: &str) -> reqwest::RequestBuilder {
+        self.http
+            .get(format!("{}/api/v1{}", self.url, path))
+            .header(shared::SECRET_KEY_HEADER, SECRET)
+    }
+
+    fn count(&self, query: &str) -> i64 {
+        self.database.count(query)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        self.database.drop_database();
+    }
+}
+
+fn session(executable: &str, ended_at: &str) -> Value {
+    json!({
+        "duration": 3600,
+        "executable": executable,
+        "name": null,
+        "ended_at": ended_at,
+    })
+}
+
+async fn json(response: reqwest::Response) -> Value {
+    response.json().await.expect("JSON response")
+}
+
+#[tokio::test]
+async fn submission_is_saved() {
+    let server = TestServer::start().await;
+    let response = server
+        .post("/submit", &session("eldenring.exe", "2024-03-01T12:00:00Z"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(json(response).await["status"], "Ok");
+    assert_eq!(server.count("SELECT COUNT(*) AS count FROM processes"), 1);
+    assert_eq!(
+        server.count(
+            "SELECT COUNT(*) AS count FROM events JOIN processes ON processes.id = events.process \
+             WHERE processes.executable = 'eldenring.exe'"
+        ),
+        1
+    );
+}
+
+#[tokio::test]
+async fn unauthenticated_submissions_are_refused() {
+    let server = TestServer::start().await;
+    let body = session("eldenring.exe", "2024-03-01T12:00:00Z");
+    let wrong_secret = server
+        .post_with_secret("/submit", &body, Some("wrong"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(wrong_secret.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(json(wrong_secret).await["code"], "unauthenticated");
+    let no_secret = server
+        .post_with_secret("/submit", &body, None)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(no_secret.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(server.count("SELECT COUNT(*) AS count FROM events"), 0);
+}
+
+#[tokio::test]
+async fn stats_are_answered_through_their_cache() {
+    let server = TestServer::start().await;
+    let response = server
+        .post("/submit", &session("eldenring.exe", "2024-03-01T12:00:00Z"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let response = server.get("/stats").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers()["etag"].clone();
+    assert_eq!(json(response).await[0]["executable"], "eldenring.exe");
+    let unchanged = server
+        .get("/stats")
+        .header("If-None-Match", etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unchanged.status(), StatusCode::NOT_MODIFIED);
+    let unauthenticated = server
+        .http
+        .get(format!("{}/api/v1/stats", server.url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unauthenticated.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn stats_of_part_of_a_day_include_archived_sessions() {
+    let server = TestServer::start().await;
+    for executable in ["eldenring.exe", "half_life.exe", "halfxlife.exe"] {
+        let response = server
+            .post("/submit", &session(executable, "2024-03-01T12:00:00Z"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+    server.database.execute(
+        "INSERT INTO archived_events (time, process, duration, started_at, archived_at) \
+         SELECT time, process, duration, started_at, time FROM events \
+         WHERE process = (SELECT id FROM processes WHERE executable = 'eldenring.exe')",
+    );
+    server.database.execute(
+        "DELETE FROM events \
+         WHERE process = (SELECT id FROM processes WHERE executable = 'eldenring.exe')",
+    );
+    let response = server
+        .get("/stats?since=2024-03-01T06:00:00Z&until=2024-03-01T18:00:00Z&process=eldenring")
+        .send()
+        .await
+        .unwrap();
+    let stats = json(response).await;
+    assert_eq!(stats[0]["executable"], "eldenring.exe");
+    assert_eq!(stats[0]["duration"], 3600);
+    // The underscore is not a wildcard.
+    let response = server
+        .get("/stats?since=2024-03-01T06:00:00Z&until=2024-03-01T18:00:00Z&process=f_l")
+        .send()
+        .await
+        .unwrap();
+    let stats = json(response).await;
+    assert_eq!(stats.as_array().map(Vec::len), Some(1));
+    assert_eq!(stats[0]["executable"], "half_life.exe");
+}
+
+#[tokio::test]
+async fn submission_for_another_device_is_rejected() {
+    let server = TestServer::start().await;
+    server.database.execute(
+        "INSERT INTO devices (id, name, nonce, token, approved, revoked, created_at) VALUES \
+         (1, 'desktop', 'a1', 'desktop-token', TRUE, FALSE, CURRENT_TIMESTAMP), \
+         (2, 'laptop', 'b2', 'laptop-token', TRUE, FALSE, CURRENT_TIMESTAMP)",
+    );
+    let submit = |device_id: i32| {
+        let mut body = session("eldenring.exe", "2024-03-01T12:00:00Z");
+        body["device_id"] = json!(device_id);
+        server
+            .http
+            .post(format!("{}/api/v1/submit", server.url))
+            .bearer_auth("desktop-token")
+            .json(&body)
+            .send()
+    };
+    let other_device = submit(2).await.unwrap();
+    assert_eq!(other_device.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(json(other_device).await["field"], "device_id");
+    let same_device = submit(1).await.unwrap();
+    assert_eq!(same_device.status(), StatusCode::CREATED);
+    assert_eq!(
+        server.count("SELECT COUNT(*) AS count FROM events WHERE device = 1"),
+        1
+    );
+    // Sessions sent with the secret aren't for any device.
+    let mut body = session("eldenring.exe", "2024-03-02T12:00:00Z");
+    body["device_id"] = json!(1);
+    let response = server.post("/submit", &body).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn retried_submission_is_saved_once() {
+    let server = TestServer::start().await;
+    let body = session("eldenring.exe", "2024-03-01T12:00:00Z");
+    let mut statuses = Vec::new();
+    for _ in 0..2 {
+        let response = server
+            .post("/submit", &body)
+            .header(shared::IDEMPOTENCY_KEY_HEADER, "8d6a3f1e")
+            .send()
+            .await
+            .unwrap();
+        statuses.push(json(response).await["status"].clone());
+    }
+    assert_eq!(statuses, [json!("Ok"), json!("Duplicate")]);
+    assert_eq!(server.count("SELECT COUNT(*) AS count FROM events"), 1);
+}
+
+/// Sessions of a process that the server hasn't seen yet race to create it,
+/// and the losers must use the process that the winner created.
+#[tokio::test]
+async fn concurrent_submissions_create_one_process() {
+    let server = TestServer::start().await;
+    let submissions = (0..24).map(|hour| {
+        let body = session("sekiro.exe", &format!("2024-03-01T{:02}:00:00Z", hour));
+        server.post("/submit", &body).send()
+    });
+    for response in futures_util::future::join_all(submissions).await {
+        let response = response.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(json(response).await["status"], "Ok");
+    }
+    assert_eq!(server.count("SELECT COUNT(*) AS count FROM processes"), 1);
+    assert_eq!(server.count("SELECT COUNT(*) AS count FROM events"), 24);
+}
+
+#[tokio::test]
+async fn backfill_skips_sessions_sent_before() {
+    let server = TestServer::start().await;
+    let backfill = json!({
+        "source": "playnite",
+        "sessions": [
+            session("eldenring.exe", "2024-03-01T12:00:00Z"),
+            session("eldenring.exe", "2024-03-02T12:00:00Z"),
+            session("", "2024-03-03T12:00:00Z"),
+        ],
+    });
+    let first = json(server.post("/backfill", &backfill).send().await.unwrap()).await;
+    assert_eq!(first["saved"], 2);
+    assert_eq!(first["duplicates"], 0);
+    assert_eq!(first["rejected"][0]["index"], 2);
+    let second = json(server.post("/backfill", &backfill).send().await.unwrap()).await;
+    assert_eq!(second["saved"], 0);
+    assert_eq!(second["duplicates"], 2);
+    assert_eq!(
+        server.count("SELECT COUNT(*) AS count FROM events WHERE imported"),
+        2
+    );
+}
@@ -0,0 +1,17 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    grpc();
+}
+
+/// Generate the messages and service of the gRPC transport. The proto file is
+/// parsed by protox, so building doesn't need `protoc`.
+#[cfg(feature = "grpc")]
+fn grpc() {
+    println!("cargo:rerun-if-changed=proto/beelzebub.proto");
+    let descriptors = protox::compile(["beelzebub.proto"], ["proto"])
+        .unwrap_or_else(|error| panic!("Could not parse beelzebub.proto: {}", error));
+    tonic_build::configure()
+        .build_transport(false)
+        .compile_fds(descriptors)
+        .unwrap_or_else(|error| panic!("Could not generate gRPC code: {}", error));
+}
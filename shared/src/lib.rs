@@ -1,31 +1,2177 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
 
 pub static CONFIG_QUALIFIER: &str = "moe";
 pub static CONFIG_ORGANIZATION: &str = "Hamuko";
 pub static CONFIG_APPLICATION: &str = "Beelzebub";
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Header with the shared secret, the admin secret or an API key.
+pub static SECRET_KEY_HEADER: &str = "X-Secret-Key";
+
+/// Scheme of the `Authorization` header with the token of a registered
+/// device.
+pub static BEARER_SCHEME: &str = "Bearer";
+
+/// Header with a key that identifies retries of the same submission.
+pub static IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Header in which the client sends its version.
+pub static CLIENT_VERSION_HEADER: &str = "X-Client-Version";
+
+/// Header with the ID the server logs a request under.
+pub static REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Header with `sha256=` and the signature of a request signed with the
+/// shared secret instead of sending it.
+pub static SIGNATURE_HEADER: &str = "X-Signature";
+
+/// Header with the Unix time in seconds at which the request was signed.
+pub static SIGNATURE_TIMESTAMP_HEADER: &str = "X-Signature-Timestamp";
+
+/// Content type of MessagePack bodies. Endpoints that take it answer with it
+/// as well when it is in `Accept`.
+pub static MSGPACK_CONTENT_TYPE: &str = "application/msgpack";
+
+/// Ways that a request is authenticated, shared so that the client and the
+/// server agree on the headers that carry them.
+pub mod auth {
+    use super::{BEARER_SCHEME, SECRET_KEY_HEADER};
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Credentials {
+        /// Shared secret, admin secret or API key, sent as it is.
+        SecretKey(String),
+
+        /// Token of a registered device.
+        DeviceToken(String),
+    }
+
+    impl Credentials {
+        /// Name and value of the header that carries the credentials.
+        pub fn header(&self) -> (&'static str, String) {
+            match self {
+                Credentials::SecretKey(key) => (SECRET_KEY_HEADER, key.clone()),
+                Credentials::DeviceToken(token) => {
+                    ("Authorization", format!("{} {}", BEARER_SCHEME, token))
+                }
+            }
+        }
+    }
+
+    /// Token of an `Authorization` header with the bearer scheme, whose name
+    /// is compared without case.
+    pub fn bearer_token(authorization: &str) -> Option<&str> {
+        let (scheme, token) = authorization.trim().split_once(' ')?;
+        scheme
+            .eq_ignore_ascii_case(BEARER_SCHEME)
+            .then_some(token.trim())
+            .filter(|token| !token.is_empty())
+    }
+}
+
+/// Signing of requests with the shared secret, so that the secret itself
+/// isn't sent and captured requests can't be sent again later.
+#[cfg(feature = "signing")]
+pub mod signing {
+    use hkdf::Hkdf;
+    use hmac::{Hmac, KeyInit, Mac};
+    use sha2::Sha256;
+
+    /// Salt of the key derivation, so that the key is nothing a server would
+    /// store in place of the secret, such as its plain SHA-256.
+    const KEY_SALT: &[u8] = b"beelzebub-request-signing";
+
+    /// Key that requests are signed with: HKDF-SHA256 of the secret with its
+    /// own salt. Servers that only store the hash of the secret can't derive
+    /// it, so reading their configuration isn't enough to sign requests.
+    pub fn key(secret: &str) -> [u8; 32] {
+        let mut key = [0; 32];
+        Hkdf::<Sha256>::new(Some(KEY_SALT), secret.as_bytes())
+            .expand(&[], &mut key)
+            .expect("HKDF-SHA256 expands to 32 bytes");
+        key
+    }
+
+    /// HMAC-SHA256 of the timestamp, method, path with the query and the
+    /// body of the request, each but the body followed by a newline.
+    fn mac(key: &[u8; 32], timestamp: i64, method: &str, path: &str, body: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+        mac.update(format!("{}\n{}\n{}\n", timestamp, method, path).as_bytes());
+        mac.update(body);
+        mac
+    }
+
+    /// Hex signature of the request.
+    pub fn sign(key: &[u8; 32], timestamp: i64, method: &str, path: &str, body: &[u8]) -> String {
+        let tag = mac(key, timestamp, method, path, body)
+            .finalize()
+            .into_bytes();
+        tag.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// The hex signature is that of the request, compared in constant time.
+    pub fn verify(
+        key: &[u8; 32],
+        timestamp: i64,
+        method: &str,
+        path: &str,
+        body: &[u8],
+        signature: &str,
+    ) -> bool {
+        let Some(tag) = parse_hex(signature) else {
+            return false;
+        };
+        mac(key, timestamp, method, path, body)
+            .verify_slice(&tag)
+            .is_ok()
+    }
+
+    fn parse_hex(text: &str) -> Option<Vec<u8>> {
+        if !text.len().is_multiple_of(2) {
+            return None;
+        }
+        text.as_bytes()
+            .chunks(2)
+            .map(|pair| {
+                // from_str_radix would take a sign as well.
+                if !pair.iter().all(u8::is_ascii_hexdigit) {
+                    return None;
+                }
+                u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()
+            })
+            .collect()
+    }
+}
+
+/// MessagePack encoding of payloads, which is smaller than JSON for batches
+/// such as backfills. Payloads go through the same data model as JSON, so
+/// fields are encoded the same way in both, such as times as RFC 3339 text.
+#[cfg(feature = "msgpack")]
+pub mod msgpack {
+    use std::io::Cursor;
+
+    use serde::{de::DeserializeOwned, Deserialize, Serialize};
+    use serde_json::Value;
+
+    /// Deepest nesting of arrays and maps that is decoded, like the limit of
+    /// serde_json.
+    const MAXIMUM_DEPTH: usize = 128;
+
+    #[derive(Debug)]
+    pub enum Error {
+        /// The payload doesn't match the type.
+        Data(serde_json::Error),
+
+        /// The value could not be written as MessagePack.
+        Encode(rmp_serde::encode::Error),
+
+        /// The body is not MessagePack that payloads have: truncated, nested
+        /// too deeply, or with binary, extension or other values that JSON
+        /// doesn't have.
+        Decode(rmp_serde::decode::Error),
+
+        /// The body has more after the value.
+        TrailingBytes,
+    }
+
+    impl std::fmt::Display for Error {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Error::Data(error) => write!(f, "{}", error),
+                Error::Encode(error) => write!(f, "{}", error),
+                Error::Decode(error) => write!(f, "invalid MessagePack: {}", error),
+                Error::TrailingBytes => write!(f, "trailing bytes after MessagePack value"),
+            }
+        }
+    }
+
+    impl std::error::Error for Error {}
+
+    pub fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+        let value = serde_json::to_value(value).map_err(Error::Data)?;
+        rmp_serde::to_vec_named(&value).map_err(Error::Encode)
+    }
+
+    pub fn decode<T: DeserializeOwned>(input: &[u8]) -> Result<T, Error> {
+        let mut deserializer = rmp_serde::Deserializer::new(Cursor::new(input));
+        deserializer.set_max_depth(MAXIMUM_DEPTH);
+        let value = Value::deserialize(&mut deserializer).map_err(Error::Decode)?;
+        if deserializer.position() != input.len() as u64 {
+            return Err(Error::TrailingBytes);
+        }
+        serde_json::from_value(value).map_err(Error::Data)
+    }
+}
+
+/// Messages and service of the gRPC transport, generated from
+/// `proto/beelzebub.proto`, and their conversions to the types of the REST
+/// API.
+#[cfg(feature = "grpc")]
+pub mod grpc {
+    use time::OffsetDateTime;
+
+    use crate::SessionDuration;
+
+    tonic::include_proto!("beelzebub.v1");
+
+    fn timestamp(time: OffsetDateTime) -> prost_types::Timestamp {
+        prost_types::Timestamp {
+            seconds: time.unix_timestamp(),
+            nanos: time.nanosecond() as i32,
+        }
+    }
+
+    /// Time of the timestamp in UTC, None if it is out of range.
+    fn time(timestamp: &prost_types::Timestamp) -> Option<OffsetDateTime> {
+        let nanoseconds =
+            i128::from(timestamp.seconds) * 1_000_000_000 + i128::from(timestamp.nanos);
+        OffsetDateTime::from_unix_timestamp_nanos(nanoseconds).ok()
+    }
+
+    impl From<&crate::Submission> for Submission {
+        fn from(submission: &crate::Submission) -> Self {
+            Submission {
+                duration: submission.duration.as_secs(),
+                executable: submission.executable.clone(),
+                name: submission.name.clone(),
+                product_version: submission.product_version.clone(),
+                file_version: submission.file_version.clone(),
+                started_at: submission.started_at.map(timestamp),
+                ended_at: submission.ended_at.map(timestamp),
+                focused_duration: submission.focused_duration.map(SessionDuration::as_secs),
+                tags: submission.tags.clone(),
+                window_title: submission.window_title.clone(),
+                session_id: submission.session_id.clone(),
+                device_id: submission.device_id,
+            }
+        }
+    }
+
+    impl TryFrom<Submission> for crate::Submission {
+        /// Field whose timestamp is out of range.
+        type Error = &'static str;
+
+        fn try_from(submission: Submission) -> Result<Self, Self::Error> {
+            let started_at = submission
+                .started_at
+                .as_ref()
+                .map(|started_at| time(started_at).ok_or("started_at"))
+                .transpose()?;
+            let ended_at = submission
+                .ended_at
+                .as_ref()
+                .map(|ended_at| time(ended_at).ok_or("ended_at"))
+                .transpose()?;
+            Ok(crate::Submission {
+                duration: SessionDuration::from_secs(submission.duration),
+                executable: submission.executable,
+                name: submission.name,
+                product_version: submission.product_version,
+                file_version: submission.file_version,
+                started_at,
+                ended_at,
+                focused_duration: submission.focused_duration.map(SessionDuration::from_secs),
+                tags: submission.tags,
+                window_title: submission.window_title,
+                session_id: submission.session_id,
+                device_id: submission.device_id,
+            })
+        }
+    }
+
+    impl From<crate::SubmissionResponseStatus> for SubmissionStatus {
+        fn from(status: crate::SubmissionResponseStatus) -> Self {
+            match status {
+                crate::SubmissionResponseStatus::DatabaseError => SubmissionStatus::DatabaseError,
+                crate::SubmissionResponseStatus::Duplicate => SubmissionStatus::Duplicate,
+                crate::SubmissionResponseStatus::Ok => SubmissionStatus::Ok,
+                crate::SubmissionResponseStatus::PayloadTooLarge => {
+                    SubmissionStatus::PayloadTooLarge
+                }
+                crate::SubmissionResponseStatus::Unauthenticated => {
+                    SubmissionStatus::Unauthenticated
+                }
+                crate::SubmissionResponseStatus::UpgradeRequired => {
+                    SubmissionStatus::UpgradeRequired
+                }
+                crate::SubmissionResponseStatus::ValidationError => {
+                    SubmissionStatus::ValidationError
+                }
+            }
+        }
+    }
+
+    impl TryFrom<SubmissionStatus> for crate::SubmissionResponseStatus {
+        /// The status is unspecified or of a newer server.
+        type Error = ();
+
+        fn try_from(status: SubmissionStatus) -> Result<Self, Self::Error> {
+            match status {
+                SubmissionStatus::Unspecified => Err(()),
+                SubmissionStatus::Ok => Ok(crate::SubmissionResponseStatus::Ok),
+                SubmissionStatus::Duplicate => Ok(crate::SubmissionResponseStatus::Duplicate),
+                SubmissionStatus::DatabaseError => {
+                    Ok(crate::SubmissionResponseStatus::DatabaseError)
+                }
+                SubmissionStatus::PayloadTooLarge => {
+                    Ok(crate::SubmissionResponseStatus::PayloadTooLarge)
+                }
+                SubmissionStatus::Unauthenticated => {
+                    Ok(crate::SubmissionResponseStatus::Unauthenticated)
+                }
+                SubmissionStatus::UpgradeRequired => {
+                    Ok(crate::SubmissionResponseStatus::UpgradeRequired)
+                }
+                SubmissionStatus::ValidationError => {
+                    Ok(crate::SubmissionResponseStatus::ValidationError)
+                }
+            }
+        }
+    }
+
+    impl From<&crate::SubmissionResponse> for SubmissionResponse {
+        fn from(response: &crate::SubmissionResponse) -> Self {
+            SubmissionResponse {
+                status: SubmissionStatus::from(response.status).into(),
+                message: response.message.clone(),
+                field: response.field.clone(),
+            }
+        }
+    }
+
+    impl TryFrom<SubmissionResponse> for crate::SubmissionResponse {
+        /// The status is unspecified or of a newer server.
+        type Error = ();
+
+        fn try_from(response: SubmissionResponse) -> Result<Self, Self::Error> {
+            let status = crate::SubmissionResponseStatus::try_from(response.status())?;
+            Ok(crate::SubmissionResponse {
+                status,
+                code: status.code(),
+                message: response.message,
+                field: response.field,
+            })
+        }
+    }
+
+    impl From<&crate::ClientSettings> for ClientSettings {
+        fn from(settings: &crate::ClientSettings) -> Self {
+            ClientSettings {
+                monitor: settings
+                    .monitor
+                    .iter()
+                    .map(|monitor| MonitorSetting {
+                        path: monitor.path.clone(),
+                        package: monitor.package.clone(),
+                        tags: monitor.tags.clone(),
+                    })
+                    .collect(),
+                name_overrides: settings.name_overrides.clone(),
+                minimum_duration: settings.minimum_duration,
+            }
+        }
+    }
+
+    impl From<ClientSettings> for crate::ClientSettings {
+        fn from(settings: ClientSettings) -> Self {
+            crate::ClientSettings {
+                monitor: settings
+                    .monitor
+                    .into_iter()
+                    .map(|monitor| crate::MonitorSetting {
+                        path: monitor.path,
+                        package: monitor.package,
+                        tags: monitor.tags,
+                    })
+                    .collect(),
+                name_overrides: settings.name_overrides,
+                minimum_duration: settings.minimum_duration,
+            }
+        }
+    }
+}
+
+/// Version of the API, raised when a change to the endpoints or their
+/// payloads would be misread by clients or servers that don't know it.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Path that the endpoints of `PROTOCOL_VERSION` are under.
+pub static API_PREFIX: &str = "/api/v1";
+
+/// Path of the `ApiVersion` of the server, which doesn't move between
+/// protocol versions.
+pub static API_VERSION_PATH: &str = "/api/version";
+
+/// Optional features that a server lists in `ApiVersion::capabilities`.
+pub mod capability {
+    /// `/backfill` accepts sessions recorded while the client was offline.
+    pub static BACKFILL: &str = "backfill";
+
+    /// `/heartbeat` accepts sessions in progress.
+    pub static HEARTBEAT: &str = "heartbeat";
+
+    /// `/register` hands out device tokens.
+    pub static DEVICE_REGISTRATION: &str = "deviceRegistration";
+
+    /// `/client-settings` has settings for clients that pull them.
+    pub static CLIENT_SETTINGS: &str = "clientSettings";
+
+    /// Request bodies can be gzipped.
+    pub static GZIP_REQUESTS: &str = "gzipRequests";
+
+    /// Retries of a submission with the same `Idempotency-Key` are only
+    /// saved once.
+    pub static IDEMPOTENCY_KEYS: &str = "idempotencyKeys";
+
+    /// `/backfill` and `/heartbeat` take MessagePack bodies.
+    pub static MSGPACK: &str = "msgpack";
+
+    /// Submissions, heartbeats and client settings are also served over
+    /// gRPC, with the service of `grpc`.
+    pub static GRPC: &str = "grpc";
+}
+
+/// Protocols and optional features of the server, which clients check
+/// before talking to it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ApiVersion {
+    /// Newest protocol the server speaks.
+    pub protocol: u32,
+
+    /// Oldest protocol the server still speaks.
+    pub minimum_protocol: u32,
+
+    /// Path of the endpoints, such as `/api/v1`.
+    pub prefix: String,
+
+    /// Version of the server itself.
+    pub server_version: String,
+
+    /// Names from `capability`. Clients ignore the ones they don't know.
+    pub capabilities: Vec<String>,
+}
+
+impl ApiVersion {
+    /// The server speaks the protocol.
+    pub fn speaks(&self, protocol: u32) -> bool {
+        (self.minimum_protocol..=self.protocol).contains(&protocol)
+    }
+
+    pub fn has(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|name| name == capability)
+    }
+}
+
+/// Length of a session in whole seconds, which is how submissions and the
+/// database count it. The conversions name their units, so that seconds
+/// aren't mixed up with the microseconds of Postgres intervals or the
+/// `Duration` of a timer.
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(transparent)]
+pub struct SessionDuration(u64);
+
+impl SessionDuration {
+    pub const fn from_secs(seconds: u64) -> Self {
+        SessionDuration(seconds)
+    }
+
+    pub const fn as_secs(self) -> u64 {
+        self.0
+    }
+
+    /// Whole seconds of a length in microseconds. Negative lengths are zero.
+    pub fn from_micros(microseconds: i64) -> Self {
+        SessionDuration((microseconds / 1_000_000).max(0) as u64)
+    }
+
+    pub fn as_micros(self) -> i64 {
+        i64::try_from(self.0)
+            .unwrap_or(i64::MAX)
+            .saturating_mul(1_000_000)
+    }
+
+    /// Seconds for the database, which stores them as a signed integer.
+    pub fn as_secs_i64(self) -> i64 {
+        i64::try_from(self.0).unwrap_or(i64::MAX)
+    }
+
+    /// Seconds read from the database. Negative lengths are zero.
+    pub fn from_secs_i64(seconds: i64) -> Self {
+        SessionDuration(seconds.max(0) as u64)
+    }
+}
+
+/// Whole seconds of the duration, leaving out the fraction.
+impl From<std::time::Duration> for SessionDuration {
+    fn from(duration: std::time::Duration) -> Self {
+        SessionDuration(duration.as_secs())
+    }
+}
+
+impl From<SessionDuration> for std::time::Duration {
+    fn from(duration: SessionDuration) -> Self {
+        std::time::Duration::from_secs(duration.0)
+    }
+}
+
+impl From<SessionDuration> for time::Duration {
+    fn from(duration: SessionDuration) -> Self {
+        time::Duration::seconds(duration.as_secs_i64())
+    }
+}
+
+/// Finished session sent by a client. Payloads of the first clients, with
+/// only the duration, executable and name, are still accepted since every
+/// later field has a default. The session is saved for the registered
+/// device whose token the submission is sent with, and a submission naming
+/// another device is rejected.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Submission {
-    pub duration: u64,
+    pub duration: SessionDuration,
     pub executable: String,
     pub name: Option<String>,
+
+    #[serde(default)]
+    pub product_version: Option<String>,
+    #[serde(default)]
+    pub file_version: Option<String>,
+
+    /// When the session started. Older clients only send the duration.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub started_at: Option<OffsetDateTime>,
+    /// When the session ended. Older clients only send the duration.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub ended_at: Option<OffsetDateTime>,
+
+    /// Time the process spent in the foreground.
+    #[serde(default)]
+    pub focused_duration: Option<SessionDuration>,
+
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Most common title of the main window during the session.
+    #[serde(default)]
+    pub window_title: Option<String>,
+
+    /// Random UUID generated when the session started, so that submissions
+    /// retried after an ambiguous failure are only saved once.
+    #[serde(default)]
+    pub session_id: Option<String>,
+
+    /// ID of the registered device the session was recorded on. Older
+    /// clients and clients without a registration don't send it.
+    #[serde(default)]
+    pub device_id: Option<i32>,
+}
+
+/// Bounds on submitted sessions, which the server rejects sessions outside
+/// of and the client checks before sending.
+#[derive(Clone, Copy, Debug)]
+pub struct SubmissionLimits {
+    /// Longest plausible session in seconds.
+    pub maximum_duration: u64,
+
+    /// Shortest session in seconds worth keeping.
+    pub minimum_duration: u64,
+
+    /// Most characters in the executable, name, versions, window title and
+    /// each tag.
+    pub maximum_length: usize,
+}
+
+impl Default for SubmissionLimits {
+    fn default() -> Self {
+        SubmissionLimits {
+            maximum_duration: 7 * 24 * 60 * 60,
+            minimum_duration: 0,
+            maximum_length: 255,
+        }
+    }
+}
+
+/// Text up to the first NUL, which is all that Windows APIs meant of it.
+fn until_nul(text: &str) -> &str {
+    text.split('\0').next().unwrap_or(text)
 }
 
 impl Submission {
     pub fn display(&self) -> String {
         let name = self.name.as_ref().unwrap_or(&self.executable);
-        format!("{} ({}s)", name, &self.duration)
+        format!("{} ({}s)", name, self.duration.as_secs())
+    }
+
+    /// Field of the submission that is wrong and what is wrong with it, if
+    /// the submission can't be saved.
+    pub fn problem(&self, limits: &SubmissionLimits) -> Option<(&'static str, String)> {
+        if self.executable.trim().is_empty() {
+            return Some(("executable", "executable is empty".to_string()));
+        }
+        if let (Some(started_at), Some(ended_at)) = (self.started_at, self.ended_at) {
+            if started_at > ended_at {
+                return Some(("ended_at", "session ends before it starts".to_string()));
+            }
+        }
+        if self.duration.as_secs() > limits.maximum_duration {
+            let message = format!("duration is over {} seconds", limits.maximum_duration);
+            return Some(("duration", message));
+        }
+        if self.duration.as_secs() < limits.minimum_duration {
+            let message = format!("duration is under {} seconds", limits.minimum_duration);
+            return Some(("duration", message));
+        }
+        let texts = [
+            ("executable", Some(&self.executable)),
+            ("name", self.name.as_ref()),
+            ("product_version", self.product_version.as_ref()),
+            ("file_version", self.file_version.as_ref()),
+            ("window_title", self.window_title.as_ref()),
+        ];
+        let tags = self.tags.iter().map(|tag| ("tags", Some(tag)));
+        for (field, text) in texts.into_iter().chain(tags) {
+            let Some(text) = text else {
+                continue;
+            };
+            if until_nul(text).chars().count() > limits.maximum_length {
+                let message = format!("{} is over {} characters", field, limits.maximum_length);
+                return Some((field, message));
+            }
+        }
+        None
     }
+
+    /// Shorten the name, versions, window title and tags to the longest that
+    /// is accepted, so that a long window title doesn't lose the session.
+    /// The executable identifies the process and is left as it is.
+    pub fn repair(&mut self, limits: &SubmissionLimits) {
+        let texts = [
+            self.name.as_mut(),
+            self.product_version.as_mut(),
+            self.file_version.as_mut(),
+            self.window_title.as_mut(),
+        ];
+        for text in texts.into_iter().flatten().chain(self.tags.iter_mut()) {
+            let cleaned = until_nul(text);
+            if cleaned.chars().count() > limits.maximum_length {
+                *text = cleaned.chars().take(limits.maximum_length).collect();
+            }
+        }
+    }
+}
+
+/// Kind of error, sent as the `code` of every error response so that clients
+/// can tell errors apart without reading messages or guessing from the
+/// status.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ApiErrorCode {
+    /// The request is malformed or has invalid values.
+    Validation,
+
+    /// The request conflicts with something that already exists.
+    Duplicate,
+
+    /// The credentials are missing, wrong or lack the scope for the request.
+    Unauthenticated,
+
+    /// Too many requests from the client. Retry after `Retry-After`.
+    RateLimited,
+
+    /// The database or the storage of the server failed. Retrying later may
+    /// work.
+    Storage,
+
+    /// The client is too old for the server or the other way around.
+    VersionMismatch,
+    NotFound,
+    PayloadTooLarge,
+
+    /// Code of a newer server.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Body of error responses that don't have a body of their own.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ApiError {
+    pub code: ApiErrorCode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum SubmissionResponseStatus {
+    /// The server could not store the session. Retrying later may work.
     DatabaseError,
+
+    /// The session was already saved by an earlier submission.
+    Duplicate,
     Ok,
+
+    /// The submission is too big to be accepted.
+    PayloadTooLarge,
     Unauthenticated,
+    UpgradeRequired,
+
+    /// The submission is malformed or has invalid values.
+    ValidationError,
+}
+
+impl SubmissionResponseStatus {
+    /// Whether the same submission would be rejected again no matter when or
+    /// by which client it is sent, so it should not be retried.
+    pub fn is_permanent(&self) -> bool {
+        matches!(
+            self,
+            SubmissionResponseStatus::PayloadTooLarge | SubmissionResponseStatus::ValidationError
+        )
+    }
+
+    /// Code of the error, `None` if the session was saved.
+    pub fn code(&self) -> Option<ApiErrorCode> {
+        match self {
+            SubmissionResponseStatus::DatabaseError => Some(ApiErrorCode::Storage),
+            SubmissionResponseStatus::Duplicate | SubmissionResponseStatus::Ok => None,
+            SubmissionResponseStatus::PayloadTooLarge => Some(ApiErrorCode::PayloadTooLarge),
+            SubmissionResponseStatus::Unauthenticated => Some(ApiErrorCode::Unauthenticated),
+            SubmissionResponseStatus::UpgradeRequired => Some(ApiErrorCode::VersionMismatch),
+            SubmissionResponseStatus::ValidationError => Some(ApiErrorCode::Validation),
+        }
+    }
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct SubmissionResponse {
     pub status: SubmissionResponseStatus,
+
+    /// Code of the error, missing if the session was saved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<ApiErrorCode>,
+
+    /// Details of what was wrong with the submission.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+
+    /// Field of the submission that failed validation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+}
+
+/// Sessions from before the server tracked them, such as from another
+/// tracker, imported with their recorded times.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Backfill {
+    /// Where the sessions come from, such as `playnite`. Saved as a tag of
+    /// every session.
+    pub source: String,
+
+    /// Sessions with their `ended_at`, which is required.
+    pub sessions: Vec<Submission>,
+}
+
+/// Session of a backfill that was not saved.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct BackfillRejection {
+    /// Position of the session in `sessions`.
+    pub index: usize,
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct BackfillResponse {
+    pub saved: usize,
+
+    /// Sessions that were saved by an earlier backfill or submission.
+    pub duplicates: usize,
+    pub rejected: Vec<BackfillRejection>,
+}
+
+/// Request to register a device, repeated until the device is approved.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct RegistrationRequest {
+    /// Name shown to the administrator approving the device.
+    pub name: String,
+
+    /// Random value generated by the client that identifies the request.
+    pub nonce: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum RegistrationStatus {
+    Pending,
+    Approved,
+    Revoked,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct RegistrationResponse {
+    pub status: RegistrationStatus,
+
+    /// Token for authenticating the device, once approved.
+    pub token: Option<String>,
+
+    /// ID of the device, once approved. Older servers don't send it.
+    #[serde(default)]
+    pub device_id: Option<i32>,
+}
+
+/// Client settings that are managed centrally on the server.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ClientSettings {
+    /// Replaces the monitor list of the client if not empty.
+    #[serde(default)]
+    pub monitor: Vec<MonitorSetting>,
+
+    /// Session names by executable, added to the names set on the client.
+    #[serde(default)]
+    pub name_overrides: HashMap<String, String>,
+
+    pub minimum_duration: Option<u32>,
+}
+
+/// Monitored directory or UWP package family. Exactly one of `path` and
+/// `package` should be set.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct MonitorSetting {
+    pub path: Option<String>,
+    pub package: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Filters for the stats endpoint.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+#[serde(rename_all = "camelCase")]
+pub struct StatsQuery {
+    /// Only count sessions that ended at or after this time.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub since: Option<OffsetDateTime>,
+
+    /// Only count sessions that ended before this time.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub until: Option<OffsetDateTime>,
+
+    /// Only count processes whose executable or name contains this text.
+    pub process: Option<String>,
+
+    /// Only count sessions from the registered device with this name.
+    pub device: Option<String>,
+
+    /// Give the playtime of each process separately for every device.
+    #[serde(default)]
+    pub by_device: bool,
+
+    /// Total the playtime of every executable, every name or every pair of
+    /// them. Defaults to the `statsGrouping` of the server.
+    pub group_by: Option<StatsGrouping>,
+}
+
+/// What the playtime in stats is totalled by.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum StatsGrouping {
+    /// Each executable and name pair, which is a separate process.
+    #[default]
+    Process,
+
+    /// Each executable, for games that change their name between patches.
+    Executable,
+
+    /// Each name, or executable for processes without one.
+    Name,
+}
+
+/// Details of a game from IGDB.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct GameMetadata {
+    /// Title of the game on IGDB.
+    pub title: String,
+    pub genres: Vec<String>,
+    pub cover_url: Option<String>,
+}
+
+/// Total playtime of a single process.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessStats {
+    pub executable: String,
+    pub name: Option<String>,
+
+    /// Total duration in seconds.
+    pub duration: u64,
+    pub sessions: u64,
+
+    /// Device the sessions were recorded on when grouping by device. Sessions
+    /// from unregistered clients have no device.
+    #[serde(default)]
+    pub device: Option<String>,
+
+    /// End of the first session ever, whatever the range of the stats, such
+    /// as for listing games that are new this month.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub first_played: Option<OffsetDateTime>,
+
+    /// End of the last session ever, whatever the range of the stats, such as
+    /// for listing games that haven't been played in a while.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub last_played: Option<OffsetDateTime>,
+
+    /// Details of the game if the server looks games up on IGDB and found it.
+    #[serde(default)]
+    pub metadata: Option<GameMetadata>,
+}
+
+/// Total playtime of a game next to how long it takes to beat on
+/// HowLongToBeat.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CompletionStats {
+    pub executable: String,
+    pub name: Option<String>,
+
+    /// Total duration in seconds.
+    pub duration: u64,
+
+    /// Title of the game on HowLongToBeat.
+    pub game: String,
+
+    /// Average seconds to beat the main story, the main story and extras,
+    /// and everything. Missing if HowLongToBeat doesn't know.
+    pub main_story: Option<u64>,
+    pub main_extra: Option<u64>,
+    pub completionist: Option<u64>,
+
+    /// Total duration as a fraction of the main story, e.g. 1.5 for half again
+    /// as long.
+    pub main_story_ratio: Option<f64>,
+}
+
+/// Total playtime of the processes in a category.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CategoryStats {
+    pub category: String,
+
+    /// Total duration in seconds.
+    pub duration: u64,
+    pub sessions: u64,
+}
+
+/// Total playtime of the sessions recorded on a device.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceStats {
+    /// Name of the registered device, `None` for sessions from unregistered
+    /// clients.
+    pub device: Option<String>,
+
+    /// Total duration in seconds.
+    pub duration: u64,
+    pub sessions: u64,
+
+    /// Number of distinct processes played on the device.
+    pub games: u64,
+}
+
+/// Total playtime of a single user on the leaderboard.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardEntry {
+    pub user: String,
+
+    /// Total duration in seconds.
+    pub duration: u64,
+    pub sessions: u64,
+}
+
+/// Filters and position for the events endpoint.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+#[serde(rename_all = "camelCase")]
+pub struct EventsQuery {
+    /// Only include sessions that ended at or after this time.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub from: Option<OffsetDateTime>,
+
+    /// Only include sessions that ended before this time.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub to: Option<OffsetDateTime>,
+
+    /// Only include processes whose executable or name contains this text.
+    pub process: Option<String>,
+
+    /// Only include sessions from the registered device with this name.
+    pub device: Option<String>,
+
+    /// `nextPage` of the previous response.
+    pub page: Option<String>,
+
+    /// Maximum number of events to return.
+    pub limit: Option<u32>,
+}
+
+/// Single recorded session.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Event {
+    pub id: i32,
+    pub executable: String,
+    pub name: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub ended_at: OffsetDateTime,
+
+    /// Duration in seconds.
+    pub duration: u64,
+    pub focused_duration: Option<u64>,
+    pub product_version: Option<String>,
+    pub file_version: Option<String>,
+    pub tags: Vec<String>,
+    pub window_title: Option<String>,
+
+    /// Name of the API key the session was submitted with.
+    pub api_key: Option<String>,
+
+    /// Name of the registered device the session was submitted from.
+    pub device: Option<String>,
+
+    /// Whether the session was backfilled by an importer instead of tracked.
+    pub imported: bool,
+
+    /// Whether the session was entered by hand instead of tracked, such as
+    /// one on a console.
+    #[serde(default)]
+    pub manual: bool,
+
+    /// Note about the session, entered with a manual session or added later.
+    #[serde(default)]
+    pub note: Option<String>,
+
+    /// Rating out of 5 given to the session along with its note.
+    #[serde(default)]
+    pub rating: Option<u8>,
+}
+
+/// Events from newest to oldest.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct EventsPage {
+    pub events: Vec<Event>,
+
+    /// Pass as `page` to get the following events. Missing on the last page.
+    pub next_page: Option<String>,
+}
+
+/// Session entered by hand, such as one played on a console or on the PC of a
+/// friend.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ManualSession {
+    /// Name of the game.
+    pub name: String,
+
+    /// Executable to record the session for, such as to count it for a game
+    /// that is also tracked on a PC. Defaults to the name.
+    #[serde(default)]
+    pub executable: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+
+    /// Duration in seconds.
+    pub duration: u64,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// Corrected times of a recorded session. Missing fields are left as they
+/// are.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct EventUpdate {
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub started_at: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub ended_at: Option<OffsetDateTime>,
+
+    /// Duration in seconds. Defaults to the previous duration, shortened to
+    /// fit between the start and end if needed.
+    pub duration: Option<u64>,
+}
+
+/// Note and rating of a recorded session, such as "finished the DLC". Missing
+/// fields are cleared.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct EventNote {
+    #[serde(default)]
+    pub note: Option<String>,
+
+    /// Rating from 1 to 5.
+    #[serde(default)]
+    pub rating: Option<u8>,
+}
+
+/// What was done to a recorded session.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum EventChangeAction {
+    Update,
+    Delete,
+}
+
+/// Edit or deletion of a recorded session in the audit trail.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct EventChange {
+    pub id: i32,
+    pub event: i32,
+    pub action: EventChangeAction,
+    #[serde(with = "time::serde::rfc3339")]
+    pub changed_at: OffsetDateTime,
+
+    /// Name of the API key that made the change, `device <ID>` for devices or
+    /// `secret`.
+    pub changed_by: String,
+    pub before: Event,
+
+    /// Missing for deletions.
+    pub after: Option<Event>,
+}
+
+/// Filters and position for the audit log endpoint.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditQuery {
+    /// Only include actions done at or after this time.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub since: Option<OffsetDateTime>,
+
+    /// Only include actions done before this time.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub until: Option<OffsetDateTime>,
+
+    /// Only include actions of this actor, such as the name of an API key.
+    pub actor: Option<String>,
+
+    /// Only include actions of this kind, such as `merge_process`.
+    pub action: Option<String>,
+
+    /// `nextPage` of the previous response.
+    pub page: Option<String>,
+
+    /// Maximum number of entries to return.
+    pub limit: Option<u32>,
+}
+
+/// Mutating action in the audit log.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub id: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub recorded_at: OffsetDateTime,
+
+    /// Name of the API key, `device <ID>` for devices, `secret` or `admin`
+    /// for the admin commands of the server.
+    pub actor: String,
+    pub action: String,
+
+    /// What the action was done to, such as `process 12`.
+    pub target: Option<String>,
+    pub details: Option<String>,
+
+    /// IP address of the client, `None` for actions not requested over HTTP.
+    pub address: Option<String>,
+}
+
+/// Audit log entries from newest to oldest.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditPage {
+    pub entries: Vec<AuditEntry>,
+
+    /// Pass as `page` to get the following entries. Missing on the last page.
+    pub next_page: Option<String>,
+}
+
+/// Direction of the sort of a list.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Position, size and sort of a page of a list endpoint. List endpoints take
+/// their filters as separate parameters.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+#[serde(rename_all = "camelCase")]
+pub struct ListQuery {
+    /// `nextPage` of the previous response.
+    pub page: Option<String>,
+
+    /// Maximum number of items to return.
+    pub limit: Option<u32>,
+
+    /// Field to sort by. Each endpoint has its own fields and default.
+    pub sort: Option<String>,
+
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+/// Items of a list endpoint, in the order asked for.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Page<T> {
+    pub items: Vec<T>,
+
+    /// Pass as `page` with the same sort to get the following items. Missing
+    /// on the last page.
+    pub next_page: Option<String>,
+}
+
+impl std::fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SortOrder::Asc => write!(f, "asc"),
+            SortOrder::Desc => write!(f, "desc"),
+        }
+    }
+}
+
+impl std::str::FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "asc" => Ok(SortOrder::Asc),
+            "desc" => Ok(SortOrder::Desc),
+            _ => Err(format!(
+                "unknown sort order {}, expected asc or desc",
+                value
+            )),
+        }
+    }
+}
+
+/// Size of pages and encoding of the `page` of list endpoints, shared so that
+/// the server and its clients agree on them.
+pub mod pagination {
+    use time::OffsetDateTime;
+
+    /// Items returned when the request doesn't give a limit.
+    pub const DEFAULT_LIMIT: u32 = 100;
+
+    /// Most items returned for a single request.
+    pub const MAXIMUM_LIMIT: u32 = 1000;
+
+    /// Number of items to return for the requested limit.
+    pub fn limit(limit: Option<u32>) -> u32 {
+        limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAXIMUM_LIMIT)
+    }
+
+    /// Position in a list that is passed as `page` and returned as
+    /// `nextPage`. Clients should pass it on as it is rather than make their
+    /// own.
+    pub trait Cursor: Sized {
+        fn encode(&self) -> String;
+
+        /// `None` if the value is not a cursor of this kind.
+        fn decode(value: &str) -> Option<Self>;
+    }
+
+    /// Offset of the first item, for lists that are sorted as a whole.
+    impl Cursor for usize {
+        fn encode(&self) -> String {
+            self.to_string()
+        }
+
+        fn decode(value: &str) -> Option<Self> {
+            value.parse().ok()
+        }
+    }
+
+    /// ID of the last item on the previous page, for lists from the newest
+    /// ID to the oldest, such as the audit log.
+    impl Cursor for i32 {
+        fn encode(&self) -> String {
+            self.to_string()
+        }
+
+        fn decode(value: &str) -> Option<Self> {
+            value.parse().ok()
+        }
+    }
+
+    /// End time and ID of the last event on the previous page, as the time in
+    /// microseconds and the ID separated by an underscore.
+    impl Cursor for (OffsetDateTime, i32) {
+        fn encode(&self) -> String {
+            format!("{}_{}", self.0.unix_timestamp_nanos() / 1000, self.1)
+        }
+
+        fn decode(value: &str) -> Option<Self> {
+            let (microseconds, id) = value.split_once('_')?;
+            let microseconds = microseconds.parse::<i128>().ok()?;
+            let time = OffsetDateTime::from_unix_timestamp_nanos(microseconds * 1000).ok()?;
+            Some((time, id.parse().ok()?))
+        }
+    }
+}
+
+/// Session in progress, as last reported in a heartbeat.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct NowPlaying {
+    pub executable: String,
+    pub name: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+
+    /// When the last heartbeat was received.
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+
+    /// Duration in seconds at the last heartbeat.
+    pub duration: u64,
+    pub user: Option<String>,
+
+    /// Name of the registered device the session is running on.
+    pub device: Option<String>,
+}
+
+/// Length of the period covered by a summary.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum SummaryPeriod {
+    /// The day so far.
+    Day,
+
+    /// The week so far, from Monday.
+    #[default]
+    Week,
+
+    /// The month so far.
+    Month,
+}
+
+/// Period and filters for the summary endpoint.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+#[serde(rename_all = "camelCase")]
+pub struct SummaryQuery {
+    #[serde(default)]
+    pub period: SummaryPeriod,
+
+    /// End of the summary, within the period it covers. Defaults to now. The
+    /// start of a period, such as midnight on Monday, summarises the whole
+    /// period before it.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub until: Option<OffsetDateTime>,
+
+    /// Only count sessions from the registered device with this name.
+    pub device: Option<String>,
+}
+
+/// Playtime over a period compared with the period before it. Periods follow
+/// the calendar of the reporting time zone.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Summary {
+    #[serde(with = "time::serde::rfc3339")]
+    pub since: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub until: OffsetDateTime,
+
+    /// IANA name of the time zone of the period, such as `Europe/Helsinki`.
+    pub timezone: String,
+
+    /// Total duration in seconds.
+    pub duration: u64,
+    pub sessions: u64,
+
+    /// Number of distinct games played.
+    pub games: u64,
+
+    /// Total duration in seconds of the same part of the period before, such
+    /// as Monday to Wednesday of last week.
+    pub previous_duration: u64,
+
+    /// Change of the total duration from the period before in percent.
+    /// Missing if nothing was played in the period before.
+    pub change: Option<f64>,
+
+    /// Most played games, most played first.
+    pub top_games: Vec<ProcessStats>,
+}
+
+/// Ranges and filters for the comparison endpoint.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+#[serde(rename_all = "camelCase")]
+pub struct ComparisonQuery {
+    /// Start of the range, such as the start of this month.
+    #[serde(with = "time::serde::rfc3339")]
+    pub since: OffsetDateTime,
+
+    /// End of the range.
+    #[serde(with = "time::serde::rfc3339")]
+    pub until: OffsetDateTime,
+
+    /// Start of the range to compare with, such as the start of last month.
+    #[serde(with = "time::serde::rfc3339")]
+    pub previous_since: OffsetDateTime,
+
+    /// End of the range to compare with.
+    #[serde(with = "time::serde::rfc3339")]
+    pub previous_until: OffsetDateTime,
+
+    /// Only count processes whose executable or name contains this text.
+    pub process: Option<String>,
+
+    /// Only count sessions from the registered device with this name.
+    pub device: Option<String>,
+}
+
+/// Playtime in a range compared with another range.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Comparison {
+    #[serde(with = "time::serde::rfc3339")]
+    pub since: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub until: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub previous_since: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub previous_until: OffsetDateTime,
+
+    /// Total duration in seconds.
+    pub duration: u64,
+    pub sessions: u64,
+    pub previous_duration: u64,
+    pub previous_sessions: u64,
+
+    /// Change of the total duration in percent. Missing if nothing was played
+    /// in the range compared with.
+    pub change: Option<f64>,
+
+    /// Games played in either range, most played in the range first.
+    pub games: Vec<GameComparison>,
+}
+
+/// Playtime of a game in the two ranges of a comparison.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct GameComparison {
+    pub executable: String,
+    pub name: Option<String>,
+
+    /// Total duration in seconds.
+    pub duration: u64,
+    pub sessions: u64,
+    pub previous_duration: u64,
+    pub previous_sessions: u64,
+
+    /// Difference of the durations in seconds, negative if the game was played
+    /// less.
+    pub difference: i64,
+
+    /// Change of the duration in percent. Missing if the game wasn't played in
+    /// the range compared with.
+    pub change: Option<f64>,
+}
+
+/// Whether a goal is a limit to stay under or a target to reach.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum GoalKind {
+    /// Crossed when the playtime or sessions go over the targets.
+    Maximum,
+
+    /// Crossed when the playtime and sessions reach the targets.
+    Minimum,
+}
+
+/// Progress towards a goal in its current period.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct GoalStatus {
+    pub name: String,
+
+    /// Processes whose executable or name contains this text, or every
+    /// process.
+    pub process: Option<String>,
+    pub user: Option<String>,
+    pub period: SummaryPeriod,
+    pub kind: GoalKind,
+
+    /// Target duration in seconds.
+    pub target_duration: Option<u64>,
+    pub target_sessions: Option<u64>,
+
+    /// Start of the current period.
+    #[serde(with = "time::serde::rfc3339")]
+    pub since: OffsetDateTime,
+
+    /// Total duration in seconds so far.
+    pub duration: u64,
+    pub sessions: u64,
+
+    /// Whether a maximum has been exceeded or a minimum reached.
+    pub crossed: bool,
+}
+
+/// Period and filters for the histogram endpoint.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+#[serde(rename_all = "camelCase")]
+pub struct HistogramQuery {
+    /// Length of each bucket.
+    #[serde(default)]
+    pub bucket: SummaryPeriod,
+
+    /// Only count sessions that ended at or after this time. Defaults to the
+    /// first session.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub since: Option<OffsetDateTime>,
+
+    /// Only count sessions that ended before this time. Defaults to now.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub until: Option<OffsetDateTime>,
+
+    /// Only count processes whose executable or name contains this text.
+    pub process: Option<String>,
+
+    /// Only count sessions from the registered device with this name.
+    pub device: Option<String>,
+}
+
+/// Filters for the heatmap endpoint.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+#[serde(rename_all = "camelCase")]
+pub struct HeatmapQuery {
+    /// Calendar year in the reporting time zone.
+    pub year: i32,
+
+    /// Only count processes whose executable or name contains this text.
+    pub process: Option<String>,
+
+    /// Only count sessions from the registered device with this name.
+    pub device: Option<String>,
+
+    /// Also give the playtime of each game per day.
+    #[serde(default)]
+    pub games: bool,
+}
+
+/// Playtime per day of a calendar year in the reporting time zone, by the day
+/// sessions ended on.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Heatmap {
+    pub year: i32,
+
+    /// IANA name of the time zone the days are in.
+    pub timezone: String,
+
+    /// Seconds played on each day of the year from the 1st of January.
+    pub days: Vec<u64>,
+
+    /// Seconds played on the most played day, for scaling colours.
+    pub maximum: u64,
+
+    /// Playtime of each game that was played, most played first. Only given
+    /// with `games`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub games: Vec<HeatmapGame>,
+}
+
+/// Playtime of a single process per day of the year.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct HeatmapGame {
+    pub executable: String,
+    pub name: Option<String>,
+
+    /// Total duration in seconds.
+    pub duration: u64,
+
+    /// Seconds played on each day of the year, as in `days` of the heatmap.
+    pub days: Vec<u64>,
+}
+
+/// Filters for the records endpoint.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+#[serde(rename_all = "camelCase")]
+pub struct RecordsQuery {
+    /// Only count processes whose executable or name contains this text.
+    pub process: Option<String>,
+
+    /// Only count sessions from the registered device with this name.
+    pub device: Option<String>,
+
+    /// Number of sessions, days and weeks to give overall. Defaults to 5.
+    pub limit: Option<u32>,
+}
+
+/// Longest sessions and most played days and weeks overall, and the best of
+/// each for every game, by the day sessions ended on in the reporting time
+/// zone.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Records {
+    /// IANA name of the time zone the days and weeks are in.
+    pub timezone: String,
+
+    /// Longest sessions, longest first.
+    pub longest_sessions: Vec<SessionRecord>,
+
+    /// Most played days, most played first.
+    pub days: Vec<HistogramBucket>,
+
+    /// Most played weeks from Monday, most played first.
+    pub weeks: Vec<HistogramBucket>,
+
+    /// Records of each game, most played game first.
+    pub games: Vec<GameRecords>,
+}
+
+/// Highlights of a calendar year of playtime in the reporting time zone.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct YearInReview {
+    pub year: i32,
+
+    /// IANA name of the time zone the year and its months are in.
+    pub timezone: String,
+
+    /// Total duration in seconds.
+    pub duration: u64,
+    pub sessions: u64,
+
+    /// Number of games played.
+    pub games: u64,
+
+    /// Most played games, most played first.
+    pub top_games: Vec<ProcessStats>,
+
+    /// Month with the most playtime, missing if nothing was played.
+    pub busiest_month: Option<HistogramBucket>,
+
+    /// Missing if nothing was played.
+    pub longest_session: Option<SessionRecord>,
+
+    /// Games played for the first time in the year, in the order they were
+    /// first played.
+    pub new_games: Vec<ProcessStats>,
+}
+
+/// Single session in the records.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SessionRecord {
+    pub executable: String,
+    pub name: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub ended_at: OffsetDateTime,
+
+    /// Duration in seconds.
+    pub duration: u64,
+}
+
+/// Longest session and most played day and week of a game.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct GameRecords {
+    pub executable: String,
+    pub name: Option<String>,
+
+    /// Total duration in seconds.
+    pub duration: u64,
+    pub longest_session: SessionRecord,
+    pub best_day: HistogramBucket,
+    pub best_week: HistogramBucket,
+}
+
+/// Filters for the streaks endpoint.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+#[serde(rename_all = "camelCase")]
+pub struct StreaksQuery {
+    /// Only count sessions from the registered device with this name.
+    pub device: Option<String>,
+}
+
+/// Runs of consecutive days with a session, by the day sessions ended on in
+/// the reporting time zone.
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Streak {
+    /// Days in a row up to today, or up to yesterday if nothing has been
+    /// played today yet.
+    pub current: u64,
+    pub longest: u64,
+
+    /// Days of the current month with a session.
+    pub days_this_month: u64,
+}
+
+/// Streaks of a single process.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct GameStreak {
+    pub executable: String,
+    pub name: Option<String>,
+    pub streak: Streak,
+}
+
+/// Streaks of any game and of each game, longest current streak first.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Streaks {
+    /// IANA name of the time zone the days are in.
+    pub timezone: String,
+    pub overall: Streak,
+    pub games: Vec<GameStreak>,
+}
+
+/// Playtime of the sessions that ended in a day, week or month.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct HistogramBucket {
+    #[serde(with = "time::serde::rfc3339")]
+    pub start: OffsetDateTime,
+
+    /// Total duration in seconds.
+    pub duration: u64,
+    pub sessions: u64,
+}
+
+/// Link to create for showing stats without authentication.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct NewShare {
+    /// Only share the games whose executable or name contains this, such as
+    /// `Elden Ring`. The totals of every game are shared if left out.
+    #[serde(default)]
+    pub process: Option<String>,
+}
+
+/// Link that shows stats at `/share/{token}` to anyone until it is revoked.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Share {
+    pub id: i32,
+    pub token: String,
+    pub process: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+/// Stats shown by a share link.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SharedStats {
+    /// User whose playtime is shared, missing if it is everyone's.
+    pub user: Option<String>,
+
+    /// Filter of the shared games, missing if every game is shared.
+    pub process: Option<String>,
+
+    /// Total duration in seconds.
+    pub duration: u64,
+    pub sessions: u64,
+
+    /// Playtime of each shared game, most played first.
+    pub games: Vec<ProcessStats>,
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+    use time::OffsetDateTime;
+
+    use super::pagination::Cursor;
+
+    #[test_case(90, 90_000_000; "seconds")]
+    #[test_case(u64::MAX, i64::MAX; "saturated")]
+    fn session_duration_micros(seconds: u64, microseconds: i64) {
+        let duration = super::SessionDuration::from_secs(seconds);
+        assert_eq!(duration.as_micros(), microseconds);
+    }
+
+    #[test_case(OffsetDateTime::from_unix_timestamp_nanos(1_709_294_400_500_000_000).unwrap(), 42, "1709294400500000_42"; "fractional seconds")]
+    #[test_case(OffsetDateTime::UNIX_EPOCH, 1, "0_1"; "epoch")]
+    fn format_cursor(time: OffsetDateTime, id: i32, output: &str) {
+        assert_eq!((time, id).encode(), output);
+    }
+
+    #[test_case("1709294400500000_42", Some((OffsetDateTime::from_unix_timestamp_nanos(1_709_294_400_500_000_000).unwrap(), 42)); "valid")]
+    #[test_case("1709294400500000", None; "missing id")]
+    #[test_case("soon_42", None; "not a time")]
+    #[test_case("1709294400500000_x", None; "not an id")]
+    #[test_case("", None; "empty")]
+    fn parse_cursor(input: &str, output: Option<(OffsetDateTime, i32)>) {
+        assert_eq!(<(OffsetDateTime, i32)>::decode(input), output);
+    }
+
+    #[test_case("game.exe", None, None, 3600, None; "valid")]
+    #[test_case("game.exe", Some(1_709_294_400), Some(1_709_298_000), 3600, None; "valid window")]
+    #[test_case(" ", None, None, 3600, Some(("executable", "executable is empty")); "empty executable")]
+    #[test_case("game.exe", Some(1_709_298_000), Some(1_709_294_400), 3600, Some(("ended_at", "session ends before it starts")); "reversed window")]
+    #[test_case("game.exe", None, None, 315_360_000, Some(("duration", "duration is over 604800 seconds")); "ten years")]
+    #[test_case("game.exe", None, None, 604_800, None; "longest")]
+    #[test_case("game.exe", None, None, 5, Some(("duration", "duration is under 10 seconds")); "too short")]
+    #[test_case(&"a".repeat(256), None, None, 3600, Some(("executable", "executable is over 255 characters")); "long executable")]
+    #[test_case(&"é".repeat(255), None, None, 3600, None; "longest executable")]
+    #[test_case(&format!("game.exe\0{}", "a".repeat(300)), None, None, 3600, None; "corrupt executable")]
+    fn submission_problem(
+        executable: &str,
+        started_at: Option<i64>,
+        ended_at: Option<i64>,
+        duration: u64,
+        output: Option<(&str, &str)>,
+    ) {
+        let submission = super::Submission {
+            duration: super::SessionDuration::from_secs(duration),
+            executable: executable.to_string(),
+            name: None,
+            product_version: None,
+            file_version: None,
+            started_at: started_at.map(|time| OffsetDateTime::from_unix_timestamp(time).unwrap()),
+            ended_at: ended_at.map(|time| OffsetDateTime::from_unix_timestamp(time).unwrap()),
+            focused_duration: None,
+            tags: Vec::new(),
+            window_title: None,
+            session_id: None,
+            device_id: None,
+        };
+        let limits = super::SubmissionLimits {
+            minimum_duration: 10,
+            ..Default::default()
+        };
+        let problem = submission.problem(&limits);
+        assert_eq!(
+            problem
+                .as_ref()
+                .map(|(field, message)| (*field, message.as_str())),
+            output
+        );
+    }
+
+    #[test_case(Some("x".repeat(256)), Vec::new(), Some(("name", "name is over 255 characters")); "long name")]
+    #[test_case(None, vec!["x".repeat(256)], Some(("tags", "tags is over 255 characters")); "long tag")]
+    #[test_case(Some("x".repeat(255)), vec!["x".repeat(255)], None; "longest name and tag")]
+    fn submission_problem_lengths(
+        name: Option<String>,
+        tags: Vec<String>,
+        output: Option<(&str, &str)>,
+    ) {
+        let submission = super::Submission {
+            duration: super::SessionDuration::from_secs(3600),
+            executable: "game.exe".to_string(),
+            name,
+            product_version: None,
+            file_version: None,
+            started_at: None,
+            ended_at: None,
+            focused_duration: None,
+            tags,
+            window_title: None,
+            session_id: None,
+            device_id: None,
+        };
+        let problem = submission.problem(&Default::default());
+        assert_eq!(
+            problem
+                .as_ref()
+                .map(|(field, message)| (*field, message.as_str())),
+            output
+        );
+    }
+
+    #[test_case(Some("x".repeat(300)), vec!["y".repeat(256)], Some("x".repeat(255)), vec!["y".repeat(255)]; "shortened")]
+    #[test_case(Some(format!("Game\0{}", "x".repeat(300))), vec!["rpg".to_string()], Some(format!("Game\0{}", "x".repeat(300))), vec!["rpg".to_string()]; "corrupt but short")]
+    #[test_case(None, Vec::new(), None, Vec::new(); "nothing to shorten")]
+    fn submission_repair(
+        window_title: Option<String>,
+        tags: Vec<String>,
+        repaired_title: Option<String>,
+        repaired_tags: Vec<String>,
+    ) {
+        let mut submission = super::Submission {
+            duration: super::SessionDuration::from_secs(3600),
+            executable: "x".repeat(300),
+            name: None,
+            product_version: None,
+            file_version: None,
+            started_at: None,
+            ended_at: None,
+            focused_duration: None,
+            tags,
+            window_title,
+            session_id: None,
+            device_id: None,
+        };
+        let limits = super::SubmissionLimits::default();
+        submission.repair(&limits);
+        assert_eq!(submission.window_title, repaired_title);
+        assert_eq!(submission.tags, repaired_tags);
+        assert_eq!(submission.executable, "x".repeat(300));
+    }
+
+    #[test_case(None, 100; "default")]
+    #[test_case(Some(20), 20; "given")]
+    #[test_case(Some(0), 1; "too small")]
+    #[test_case(Some(5000), 1000; "too large")]
+    fn pagination_limit(limit: Option<u32>, output: u32) {
+        assert_eq!(super::pagination::limit(limit), output);
+    }
+
+    #[test_case("Bearer token", Some("token"); "bearer")]
+    #[test_case("bearer  token ", Some("token"); "scheme without case")]
+    #[test_case("Basic dXNlcjpwYXNz", None; "other scheme")]
+    #[test_case("Bearer ", None; "no token")]
+    fn bearer_token(authorization: &str, output: Option<&str>) {
+        assert_eq!(super::auth::bearer_token(authorization), output);
+    }
+
+    #[cfg(feature = "signing")]
+    #[test_case("POST", "/api/v1/submit", "{}", "43bcd4503ae5c3c27b5f5bfb3519cad3758eaf09058ac21b0c43b5b3639699bf"; "body")]
+    #[test_case("GET", "/api/v1/stats?since=2026-01-01T00:00:00Z", "", "12a71f0fdd4628db73825786c172cf95f5877bc6e0dbe43eb2a3b9d32c6e330a"; "query")]
+    fn request_signature(method: &str, path: &str, body: &str, output: &str) {
+        let key = super::signing::key("secret");
+        assert_eq!(
+            super::signing::sign(&key, 1_700_000_000, method, path, body.as_bytes()),
+            output
+        );
+    }
+
+    #[cfg(feature = "signing")]
+    #[test]
+    fn signing_key() {
+        assert_eq!(
+            super::signing::key("secret"),
+            [
+                0x9f, 0x21, 0x99, 0x2c, 0x1d, 0x6d, 0x12, 0x14, 0x8f, 0x9f, 0x3a, 0xab, 0x86, 0x72,
+                0x76, 0x71, 0x85, 0xb8, 0xd1, 0xbc, 0xc4, 0x25, 0xff, 0x02, 0x6d, 0x03, 0xa7, 0xdb,
+                0xe3, 0x24, 0x43, 0xbe,
+            ]
+        );
+    }
+
+    #[cfg(feature = "signing")]
+    #[test_case("43bcd4503ae5c3c27b5f5bfb3519cad3758eaf09058ac21b0c43b5b3639699bf", true; "valid")]
+    #[test_case("43BCD4503AE5C3C27B5F5BFB3519CAD3758EAF09058AC21B0C43B5B3639699BF", true; "uppercase")]
+    #[test_case("43bcd4503ae5c3c27b5f5bfb3519cad3758eaf09058ac21b0c43b5b3639699be", false; "last byte")]
+    #[test_case("53bcd4503ae5c3c27b5f5bfb3519cad3758eaf09058ac21b0c43b5b3639699bf", false; "first byte")]
+    #[test_case("43bcd4503ae5c3c27b5f5bfb3519cad3", false; "prefix")]
+    #[test_case("43bcd4503ae5c3c27b5f5bfb3519cad3758eaf09058ac21b0c43b5b3639699bf00", false; "longer")]
+    #[test_case("+3bcd4503ae5c3c27b5f5bfb3519cad3758eaf09058ac21b0c43b5b3639699bf", false; "sign")]
+    #[test_case("", false; "empty")]
+    fn signing_verify(signature: &str, output: bool) {
+        let key = super::signing::key("secret");
+        let verified = super::signing::verify(
+            &key,
+            1_700_000_000,
+            "POST",
+            "/api/v1/submit",
+            b"{}",
+            signature,
+        );
+        assert_eq!(verified, output);
+    }
+
+    #[cfg(feature = "signing")]
+    #[test]
+    fn signing_verify_other_key() {
+        let key = super::signing::key("other secret");
+        let signature = "43bcd4503ae5c3c27b5f5bfb3519cad3758eaf09058ac21b0c43b5b3639699bf";
+        assert!(!super::signing::verify(
+            &key,
+            1_700_000_000,
+            "POST",
+            "/api/v1/submit",
+            b"{}",
+            signature
+        ));
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test_case(&serde_json::json!(null), &[0xc0]; "null")]
+    #[test_case(&serde_json::json!([true, 1, -1, 200, -200, 70000]), &[0x96, 0xc3, 0x01, 0xff, 0xcc, 0xc8, 0xd1, 0xff, 0x38, 0xce, 0x00, 0x01, 0x11, 0x70]; "numbers")]
+    #[test_case(&serde_json::json!(1.5), &[0xcb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0]; "float")]
+    #[test_case(&serde_json::json!({"name": "Doom"}), &[0x81, 0xa4, b'n', b'a', b'm', b'e', 0xa4, b'D', b'o', b'o', b'm']; "map")]
+    fn msgpack(value: &serde_json::Value, output: &[u8]) {
+        let encoded = super::msgpack::encode(value).unwrap();
+        assert_eq!(encoded, output);
+        let decoded: serde_json::Value = super::msgpack::decode(&encoded).unwrap();
+        assert_eq!(&decoded, value);
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test_case(&[0x92, 0x01]; "truncated")]
+    #[test_case(&[0x01, 0x02]; "trailing bytes")]
+    #[test_case(&[0xc4, 0x01, 0x00]; "binary")]
+    #[test_case(&[0x81, 0x01, 0x02]; "number key")]
+    #[test_case(&[0xa1, 0xff]; "invalid text")]
+    #[test_case(&[0x91; 200]; "too deep")]
+    fn msgpack_invalid(input: &[u8]) {
+        assert!(super::msgpack::decode::<serde_json::Value>(input).is_err());
+    }
+
+    #[cfg(feature = "grpc")]
+    #[test]
+    fn grpc_submission() {
+        let submission = super::grpc::Submission {
+            duration: 3600,
+            executable: "eldenring.exe".to_string(),
+            name: Some("ELDEN RING".to_string()),
+            product_version: Some("1.10".to_string()),
+            file_version: None,
+            started_at: Some(prost_types::Timestamp {
+                seconds: 1_709_290_800,
+                nanos: 500_000_000,
+            }),
+            ended_at: Some(prost_types::Timestamp {
+                seconds: 1_709_294_400,
+                nanos: 0,
+            }),
+            focused_duration: Some(1800),
+            tags: vec!["steam".to_string()],
+            window_title: None,
+            session_id: Some("8d6a3f1e".to_string()),
+            device_id: Some(3),
+        };
+        let converted = super::Submission::try_from(submission.clone()).unwrap();
+        assert_eq!(
+            converted.started_at,
+            Some(OffsetDateTime::from_unix_timestamp_nanos(1_709_290_800_500_000_000).unwrap())
+        );
+        assert_eq!(super::grpc::Submission::from(&converted), submission);
+    }
+
+    #[cfg(feature = "grpc")]
+    #[test_case(i64::MAX, "started_at"; "after the last time")]
+    #[test_case(i64::MIN, "started_at"; "before the first time")]
+    fn grpc_submission_out_of_range(seconds: i64, field: &str) {
+        let submission = super::grpc::Submission {
+            duration: 3600,
+            executable: "eldenring.exe".to_string(),
+            started_at: Some(prost_types::Timestamp { seconds, nanos: 0 }),
+            ..Default::default()
+        };
+        assert_eq!(super::Submission::try_from(submission).err(), Some(field));
+    }
+
+    #[cfg(feature = "grpc")]
+    #[test_case(crate::grpc::SubmissionStatus::Duplicate, Some(crate::SubmissionResponseStatus::Duplicate); "duplicate")]
+    #[test_case(crate::grpc::SubmissionStatus::ValidationError, Some(crate::SubmissionResponseStatus::ValidationError); "validation error")]
+    #[test_case(crate::grpc::SubmissionStatus::Unspecified, None; "unspecified")]
+    fn grpc_submission_status(
+        status: super::grpc::SubmissionStatus,
+        output: Option<super::SubmissionResponseStatus>,
+    ) {
+        let response = super::grpc::SubmissionResponse {
+            status: status.into(),
+            message: None,
+            field: None,
+        };
+        let response = super::SubmissionResponse::try_from(response).ok();
+        assert_eq!(response.map(|response| response.status), output);
+    }
 }